@@ -0,0 +1,398 @@
+use std::{fmt, future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use aws_sdk_athena::{
+    Client,
+    operation::start_query_execution::builders::StartQueryExecutionFluentBuilder,
+    types::{ColumnInfo, ResultConfiguration, Row},
+};
+use futures_util::TryStream;
+
+use crate::{
+    error::Error,
+    history::QueryHistoryRecord,
+    query::{get_query_execution, query_execution_context, rows_stream},
+    wait::{PollInterval, start_query_execution_id, wait_query_succeeded},
+};
+
+type CompletionHook =
+    Arc<dyn Fn(QueryHistoryRecord) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// Client with the work group, catalog, database and output location applied to every query, so
+/// QueryExecutionContext and ResultConfiguration are not built at each call site.
+#[derive(Clone)]
+pub struct AthenaContext {
+    pub client: Client,
+    pub workgroup: String,
+    /// Data catalog of unqualified table names, e.g. a federated catalog, `AwsDataCatalog`
+    /// if None.
+    pub catalog: Option<String>,
+    /// Database of unqualified table names, the `default` database if None.
+    pub database: Option<String>,
+    /// S3 location of the results, the one of the work group if None.
+    pub output_location: Option<String>,
+    on_complete: Option<CompletionHook>,
+}
+
+impl fmt::Debug for AthenaContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AthenaContext")
+            .field("client", &self.client)
+            .field("workgroup", &self.workgroup)
+            .field("catalog", &self.catalog)
+            .field("database", &self.database)
+            .field("output_location", &self.output_location)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AthenaContext {
+    pub fn new(client: Client, workgroup: impl Into<String>) -> Self {
+        Self {
+            client,
+            workgroup: workgroup.into(),
+            catalog: None,
+            database: None,
+            output_location: None,
+            on_complete: None,
+        }
+    }
+
+    pub fn catalog(mut self, catalog: impl Into<String>) -> Self {
+        self.catalog = Some(catalog.into());
+        self
+    }
+
+    pub fn database(mut self, database: impl Into<String>) -> Self {
+        self.database = Some(database.into());
+        self
+    }
+
+    pub fn output_location(mut self, output_location: impl Into<String>) -> Self {
+        self.output_location = Some(output_location.into());
+        self
+    }
+
+    /// Hook called when the wait of each query of `execute`, `execute_stream` and
+    /// `execute_prepared` ends, successful or not, e.g. to persist the query history for
+    /// auditing. It is not called when the query could not be started or its execution
+    /// could not be fetched.
+    pub fn on_complete<F, Fut>(mut self, on_complete: F) -> Self
+    where
+        F: Fn(QueryHistoryRecord) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_complete = Some(Arc::new(move |record| Box::pin(on_complete(record))));
+        self
+    }
+
+    /// StartQueryExecution with the context applied, for the parameters not covered by
+    /// the methods, e.g. ResultReuseConfiguration.
+    pub fn start_query_execution(
+        &self,
+        query_string: impl Into<String>,
+    ) -> StartQueryExecutionFluentBuilder {
+        let mut builder = self
+            .client
+            .start_query_execution()
+            .query_string(query_string)
+            .work_group(&self.workgroup)
+            .set_query_execution_context(query_execution_context(
+                self.database.as_ref(),
+                self.catalog.as_ref(),
+            ));
+        if let Some(output_location) = &self.output_location {
+            builder = builder.result_configuration(
+                ResultConfiguration::builder()
+                    .output_location(output_location)
+                    .build(),
+            );
+        }
+        builder
+    }
+
+    /// Runs the query and waits for it to succeed, returning the query execution ID.
+    pub async fn execute(
+        &self,
+        query_string: impl Into<String>,
+        timeout_duration: Duration,
+        check_interval: impl Into<PollInterval>,
+    ) -> Result<String, Error> {
+        let builder = self.start_query_execution(query_string);
+        self.run(builder, timeout_duration, check_interval.into())
+            .await
+    }
+
+    /// Runs the query like [`execute`](Self::execute), and returns its results with
+    /// [`rows_stream`].
+    pub async fn execute_stream(
+        &self,
+        query_string: impl Into<String>,
+        timeout_duration: Duration,
+        check_interval: impl Into<PollInterval>,
+    ) -> Result<
+        (
+            Vec<ColumnInfo>,
+            impl TryStream<Ok = Row, Error = Error> + Unpin,
+        ),
+        Error,
+    > {
+        let execution_id = self
+            .execute(query_string, timeout_duration, check_interval)
+            .await?;
+        rows_stream(&self.client, execution_id).await
+    }
+
+    /// Runs the prepared statement with `parameters` in the order of its `?` placeholders.
+    /// Parameters are SQL literals, so strings must be quoted, e.g. `'2024-01-01'`.
+    /// `statement_name` must be an identifier (`[A-Za-z_][A-Za-z0-9_]*`), as it is a part of
+    /// the query string.
+    pub async fn execute_prepared(
+        &self,
+        statement_name: &str,
+        parameters: Vec<String>,
+        timeout_duration: Duration,
+        check_interval: impl Into<PollInterval>,
+    ) -> Result<String, Error> {
+        validate_statement_name(statement_name)?;
+        let mut builder = self.start_query_execution(format!("EXECUTE {statement_name}"));
+        // パラメーターが空のリストだと API がエラーを返す
+        if !parameters.is_empty() {
+            builder = builder.set_execution_parameters(Some(parameters));
+        }
+        self.run(builder, timeout_duration, check_interval.into())
+            .await
+    }
+
+    async fn run(
+        &self,
+        builder: StartQueryExecutionFluentBuilder,
+        timeout_duration: Duration,
+        check_interval: PollInterval,
+    ) -> Result<String, Error> {
+        let execution_id = start_query_execution_id(builder).await?;
+        let result = wait_query_succeeded(
+            &self.client,
+            &execution_id,
+            timeout_duration,
+            check_interval,
+        )
+        .await;
+        if let Some(on_complete) = &self.on_complete {
+            // 履歴の取得に失敗してもクエリの結果は返す
+            if let Ok(output) = get_query_execution(&self.client, Some(&execution_id)).await
+                && let Some(execution) = output.query_execution()
+            {
+                on_complete(QueryHistoryRecord::from_execution(execution)).await;
+            }
+        }
+        result.map(|()| execution_id)
+    }
+}
+
+// クエリ文字列に埋め込むので識別子以外は拒否する
+fn validate_statement_name(statement_name: &str) -> Result<(), Error> {
+    let mut chars = statement_name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Invalid(format!(
+            "statement name must match [A-Za-z_][A-Za-z0-9_]*: {statement_name:?}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_athena::types::QueryExecutionState;
+    use mockito::{Matcher, Server};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_execute_prepared() {
+        let mut server = Server::new_async().await;
+        let start = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.StartQueryExecution")
+            .match_body(Matcher::PartialJsonString(
+                r#"{
+                    "QueryString": "EXECUTE daily_report",
+                    "WorkGroup": "analytics",
+                    "QueryExecutionContext": {"Database": "logs"},
+                    "ResultConfiguration": {"OutputLocation": "s3://results/"},
+                    "ExecutionParameters": ["'2024-01-01'"]
+                }"#
+                .to_string(),
+            ))
+            .with_status(200)
+            .with_body(r#"{"QueryExecutionId":"e1"}"#)
+            .create_async()
+            .await;
+        let get = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.GetQueryExecution")
+            .with_status(200)
+            .with_body(
+                r#"{"QueryExecution":{"QueryExecutionId":"e1","Status":{"State":"SUCCEEDED"}}}"#,
+            )
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        let context = AthenaContext::new(client, "analytics")
+            .database("logs")
+            .output_location("s3://results/");
+
+        let execution_id = context
+            .execute_prepared(
+                "daily_report",
+                vec!["'2024-01-01'".to_string()],
+                Duration::from_secs(1),
+                Duration::from_millis(10),
+            )
+            .await
+            .unwrap();
+        assert_eq!(execution_id, "e1");
+        start.assert_async().await;
+        get.assert_async().await;
+    }
+
+    #[test]
+    fn test_validate_statement_name() {
+        for name in ["daily_report", "_tmp", "Report2"] {
+            assert!(validate_statement_name(name).is_ok(), "{name}");
+        }
+        for name in [
+            "",
+            "2report",
+            "daily-report",
+            "report; DROP TABLE logs",
+            "レポート",
+        ] {
+            assert!(
+                matches!(validate_statement_name(name), Err(Error::Invalid(_))),
+                "{name}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_catalog() {
+        let mut server = Server::new_async().await;
+        let start = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.StartQueryExecution")
+            .match_body(Matcher::PartialJsonString(
+                r#"{
+                    "QueryString": "SELECT * FROM orders",
+                    "QueryExecutionContext": {"Catalog": "mysql_catalog", "Database": "shop"}
+                }"#
+                .to_string(),
+            ))
+            .with_status(200)
+            .with_body(r#"{"QueryExecutionId":"e3"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.GetQueryExecution")
+            .with_status(200)
+            .with_body(
+                r#"{"QueryExecution":{"QueryExecutionId":"e3","Status":{"State":"SUCCEEDED"}}}"#,
+            )
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        let context = AthenaContext::new(client, "analytics")
+            .catalog("mysql_catalog")
+            .database("shop");
+
+        context
+            .execute(
+                "SELECT * FROM orders",
+                Duration::from_secs(1),
+                Duration::from_millis(10),
+            )
+            .await
+            .unwrap();
+        start.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_on_complete() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.StartQueryExecution")
+            .with_status(200)
+            .with_body(r#"{"QueryExecutionId":"e2"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.GetQueryExecution")
+            .with_status(200)
+            .with_body(
+                r#"{"QueryExecution":{
+                    "QueryExecutionId":"e2",
+                    "Query":"SELECT * FROM access_logs",
+                    "WorkGroup":"analytics",
+                    "QueryExecutionContext":{"Database":"logs","Catalog":"AwsDataCatalog"},
+                    "Status":{
+                        "State":"FAILED",
+                        "StateChangeReason":"TABLE_NOT_FOUND",
+                        "SubmissionDateTime":1760572800.0,
+                        "CompletionDateTime":1760572801.5
+                    },
+                    "Statistics":{"DataScannedInBytes":0,"TotalExecutionTimeInMillis":1500}
+                }}"#,
+            )
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        let records = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let context = {
+            let records = records.clone();
+            AthenaContext::new(client, "analytics")
+                .database("logs")
+                .on_complete(move |record| {
+                    let records = records.clone();
+                    async move { records.lock().unwrap().push(record) }
+                })
+        };
+
+        let result = context
+            .execute(
+                "SELECT * FROM access_logs",
+                Duration::from_secs(1),
+                Duration::from_millis(10),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::QueryFailed(_))));
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.execution_id, "e2");
+        assert_eq!(record.query, "SELECT * FROM access_logs");
+        assert_eq!(record.workgroup.as_deref(), Some("analytics"));
+        assert_eq!(record.catalog.as_deref(), Some("AwsDataCatalog"));
+        assert_eq!(record.database.as_deref(), Some("logs"));
+        assert_eq!(record.state, Some(QueryExecutionState::Failed));
+        assert_eq!(
+            record.state_change_reason.as_deref(),
+            Some("TABLE_NOT_FOUND")
+        );
+        assert_eq!(
+            record.completed_at.unwrap().timestamp_millis(),
+            1_760_572_801_500
+        );
+        assert_eq!(record.stats.data_scanned_bytes, Some(0));
+        assert_eq!(
+            record.stats.total_execution_time,
+            Some(Duration::from_millis(1500))
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use aws_utils_s3::{aws_sdk_s3, transfer::TransferOptions};
+use futures_util::{StreamExt, TryStreamExt, stream};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::error::Error;
+
+type ProgressFn = Arc<dyn Fn(&DownloadProgress) + Send + Sync>;
+
+/// Files downloaded by [`download_output`] so far.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadProgress {
+    pub files: usize,
+    pub downloaded_files: usize,
+    pub total_bytes: u64,
+    pub downloaded_bytes: u64,
+}
+
+/// Options of [`download_output`] and [`download_merged`].
+#[derive(Clone)]
+pub struct DownloadOptions {
+    concurrency: usize,
+    on_progress: Option<ProgressFn>,
+}
+
+impl DownloadOptions {
+    /// 4 files at a time.
+    pub fn new() -> Self {
+        Self {
+            concurrency: 4,
+            on_progress: None,
+        }
+    }
+
+    /// Files downloaded concurrently. Raised to at least 1.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Called after each file is downloaded.
+    pub fn on_progress(
+        mut self,
+        on_progress: impl Fn(&DownloadProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for DownloadOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DownloadOptions")
+            .field("concurrency", &self.concurrency)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Local files of [`download_output`], in the order of their keys.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DownloadedOutput {
+    pub files: Vec<PathBuf>,
+    pub bytes: u64,
+}
+
+/// How [`merge_files`] joins the files into one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeFormat {
+    /// Files are concatenated as is. Valid for text files without a header row
+    /// (UNLOAD and CTAS write none) and for gzip files, whose members can be concatenated.
+    Concat,
+    /// Uncompressed CSV files each starting with a header row. Only the header of the first file is kept.
+    CsvWithHeader,
+    /// Parquet files with the same columns. The row groups are rewritten into one file.
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+/// Splits `s3://bucket/prefix` into the bucket name and the prefix.
+pub fn parse_s3_location(location: &str) -> Result<(String, String), Error> {
+    let (bucket_name, prefix) = location
+        .strip_prefix("s3://")
+        .map(|rest| rest.split_once('/').unwrap_or((rest, "")))
+        .filter(|(bucket_name, _)| !bucket_name.is_empty())
+        .ok_or_else(|| Error::Invalid(format!("invalid S3 location: {location}")))?;
+    Ok((bucket_name.to_string(), prefix.to_string()))
+}
+
+/// Data files written by UNLOAD or CTAS. Folder markers, empty objects and the manifest and
+/// metadata files of the query result location are skipped.
+fn is_data_object(key: &str, size: i64) -> bool {
+    size > 0
+        && !key.ends_with('/')
+        && !key.ends_with("_$folder$")
+        && !key.ends_with("-manifest.csv")
+        && !key.ends_with(".metadata")
+}
+
+/// Local path of `key` under `dir`. Partition directories (`dt=2024-01-01/`) are kept.
+fn local_path(dir: &Path, prefix: &str, key: &str) -> Result<PathBuf, Error> {
+    let relative = key
+        .strip_prefix(prefix)
+        .unwrap_or(key)
+        .trim_start_matches('/');
+    let mut path = dir.to_path_buf();
+    for part in relative.split('/').filter(|p| !p.is_empty()) {
+        if part == "." || part == ".." {
+            return Err(Error::Invalid(format!("invalid key: {key}")));
+        }
+        path.push(part);
+    }
+    Ok(path)
+}
+
+/// Downloads the files written by UNLOAD (the `TO` location) or CTAS (the `external_location`)
+/// under `location` to `dir`, `concurrency` files at a time.
+///
+/// Run it after the query has succeeded, e.g. after [`start_query_execution_wait`](crate::wait::start_query_execution_wait).
+pub async fn download_output(
+    s3_client: &aws_sdk_s3::Client,
+    location: &str,
+    dir: impl AsRef<Path>,
+    options: &DownloadOptions,
+) -> Result<DownloadedOutput, Error> {
+    let (bucket_name, prefix) = parse_s3_location(location)?;
+    let dir = dir.as_ref();
+    let mut objects = aws_utils_s3::object::list_all(s3_client, &bucket_name, Some(&prefix))
+        .await?
+        .into_iter()
+        .filter_map(|o| {
+            let size = o.size().unwrap_or_default();
+            o.key
+                .filter(|key| is_data_object(key, size))
+                .map(|key| (key, size as u64))
+        })
+        .collect::<Vec<_>>();
+    objects.sort();
+
+    let files = objects
+        .iter()
+        .map(|(key, _)| local_path(dir, &prefix, key))
+        .collect::<Result<Vec<_>, _>>()?;
+    let file_count = files.len();
+    let total_bytes = objects.iter().map(|(_, size)| size).sum::<u64>();
+    let downloaded_files = AtomicUsize::new(0);
+    let downloaded_bytes = AtomicU64::new(0);
+    let transfer_options = TransferOptions::default();
+
+    stream::iter(objects.iter().zip(&files))
+        .map(|((key, _), path)| {
+            let bucket_name = &bucket_name;
+            let transfer_options = &transfer_options;
+            let downloaded_files = &downloaded_files;
+            let downloaded_bytes = &downloaded_bytes;
+            async move {
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                let bytes = aws_utils_s3::object::get_object_to_path(
+                    s3_client,
+                    bucket_name,
+                    key,
+                    path,
+                    transfer_options,
+                )
+                .await?;
+                let progress = DownloadProgress {
+                    files: file_count,
+                    downloaded_files: downloaded_files.fetch_add(1, Ordering::Relaxed) + 1,
+                    total_bytes,
+                    downloaded_bytes: downloaded_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes,
+                };
+                if let Some(on_progress) = &options.on_progress {
+                    on_progress(&progress);
+                }
+                Ok::<_, Error>(())
+            }
+        })
+        .buffer_unordered(options.concurrency)
+        .try_collect::<()>()
+        .await?;
+
+    Ok(DownloadedOutput {
+        bytes: downloaded_bytes.into_inner(),
+        files,
+    })
+}
+
+/// Merges `files` into `dest` in order and returns the size of `dest`.
+pub async fn merge_files(
+    files: &[PathBuf],
+    dest: impl AsRef<Path>,
+    format: MergeFormat,
+) -> Result<u64, Error> {
+    let dest = dest.as_ref();
+    match format {
+        MergeFormat::Concat => merge_text(files, dest, false).await,
+        MergeFormat::CsvWithHeader => merge_text(files, dest, true).await,
+        #[cfg(feature = "parquet")]
+        MergeFormat::Parquet => {
+            let files = files.to_vec();
+            let dest = dest.to_path_buf();
+            tokio::task::spawn_blocking(move || merge_parquet(&files, &dest))
+                .await
+                .map_err(|e| Error::Invalid(format!("merge task failed: {e}")))?
+        }
+    }
+}
+
+/// Downloads the output under `location` and merges it into the single file `dest`.
+///
+/// The files are downloaded to a temporary directory next to `dest`, removed after the merge.
+pub async fn download_merged(
+    s3_client: &aws_sdk_s3::Client,
+    location: &str,
+    dest: impl AsRef<Path>,
+    format: MergeFormat,
+    options: &DownloadOptions,
+) -> Result<u64, Error> {
+    let dest = dest.as_ref();
+    let file_name = dest
+        .file_name()
+        .ok_or_else(|| Error::Invalid(format!("invalid file path: {}", dest.display())))?;
+    let mut dir_name = file_name.to_os_string();
+    dir_name.push(".parts");
+    let dir = dest.with_file_name(dir_name);
+
+    let result = async {
+        let output = download_output(s3_client, location, &dir, options).await?;
+        merge_files(&output.files, dest, format).await
+    }
+    .await;
+    // 一時ディレクトリは失敗しても消す
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    result
+}
+
+async fn merge_text(files: &[PathBuf], dest: &Path, skip_header: bool) -> Result<u64, Error> {
+    let mut out = tokio::fs::File::create(dest).await?;
+    let mut size = 0u64;
+    for (i, path) in files.iter().enumerate() {
+        let mut reader = BufReader::new(tokio::fs::File::open(path).await?);
+        if skip_header && i > 0 {
+            let mut header = vec![];
+            reader.read_until(b'\n', &mut header).await?;
+        }
+        size += tokio::io::copy_buf(&mut reader, &mut out).await?;
+    }
+    out.flush().await?;
+    Ok(size)
+}
+
+#[cfg(feature = "parquet")]
+fn merge_parquet(files: &[PathBuf], dest: &Path) -> Result<u64, Error> {
+    use parquet::{
+        arrow::{ArrowWriter, arrow_reader::ParquetRecordBatchReaderBuilder},
+        errors::ParquetError,
+        file::properties::WriterProperties,
+    };
+
+    let mut first_schema = None;
+    let mut writer = None;
+    for path in files {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(path)?)?;
+        let schema = builder.schema().clone();
+        let first_schema = first_schema.get_or_insert_with(|| schema.clone());
+        if schema.fields() != first_schema.fields() {
+            return Err(Error::Invalid(format!(
+                "columns of {} differ from the first file",
+                path.display()
+            )));
+        }
+        let writer = match &mut writer {
+            Some(writer) => writer,
+            None => {
+                // UNLOAD の圧縮形式 (既定は GZIP) を引き継ぐ
+                let properties = builder
+                    .metadata()
+                    .row_groups()
+                    .first()
+                    .and_then(|row_group| row_group.columns().first())
+                    .map(|column| {
+                        WriterProperties::builder()
+                            .set_compression(column.compression())
+                            .build()
+                    });
+                writer.insert(ArrowWriter::try_new(
+                    std::fs::File::create(dest)?,
+                    schema,
+                    properties,
+                )?)
+            }
+        };
+        for batch in builder.build()? {
+            writer.write(&batch.map_err(ParquetError::from)?)?;
+        }
+    }
+    let Some(writer) = writer else {
+        return Err(Error::Invalid("no Parquet files to merge".to_string()));
+    };
+    writer.close()?;
+    Ok(std::fs::metadata(dest)?.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_s3_location() {
+        assert_eq!(
+            parse_s3_location("s3://my-bucket/unload/users/").unwrap(),
+            ("my-bucket".to_string(), "unload/users/".to_string())
+        );
+        assert_eq!(
+            parse_s3_location("s3://my-bucket").unwrap(),
+            ("my-bucket".to_string(), String::new())
+        );
+        assert!(parse_s3_location("my-bucket/unload").is_err());
+        assert!(parse_s3_location("s3:///unload").is_err());
+    }
+
+    #[test]
+    fn test_is_data_object() {
+        assert!(is_data_object(
+            "unload/20240101_000000_00001_abcde_0a1b",
+            100
+        ));
+        assert!(!is_data_object(
+            "unload/20240101_000000_00001_abcde_0a1b",
+            0
+        ));
+        assert!(!is_data_object("unload/", 0));
+        assert!(!is_data_object("unload_$folder$", 1));
+        assert!(!is_data_object("results/abc-manifest.csv", 10));
+        assert!(!is_data_object("results/abc.metadata", 10));
+    }
+
+    #[test]
+    fn test_local_path() {
+        let dir = Path::new("/tmp/out");
+        assert_eq!(
+            local_path(dir, "unload/", "unload/dt=2024-01-01/part-0").unwrap(),
+            PathBuf::from("/tmp/out/dt=2024-01-01/part-0")
+        );
+        assert_eq!(
+            local_path(dir, "unload", "unload/part-0").unwrap(),
+            PathBuf::from("/tmp/out/part-0")
+        );
+        assert!(local_path(dir, "unload/", "unload/../etc/passwd").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_merge_files() {
+        let dir = std::env::temp_dir().join(format!("athena-merge-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let files = vec![dir.join("0.csv"), dir.join("1.csv")];
+        tokio::fs::write(&files[0], "id,name\r\n1,a\r\n")
+            .await
+            .unwrap();
+        tokio::fs::write(&files[1], "id,name\r\n2,b\r\n")
+            .await
+            .unwrap();
+
+        let dest = dir.join("merged.csv");
+        let size = merge_files(&files, &dest, MergeFormat::CsvWithHeader)
+            .await
+            .unwrap();
+        let merged = tokio::fs::read_to_string(&dest).await.unwrap();
+        assert_eq!(merged, "id,name\r\n1,a\r\n2,b\r\n");
+        assert_eq!(size, merged.len() as u64);
+
+        merge_files(&files, &dest, MergeFormat::Concat)
+            .await
+            .unwrap();
+        assert_eq!(
+            tokio::fs::read_to_string(&dest).await.unwrap(),
+            "id,name\r\n1,a\r\nid,name\r\n2,b\r\n"
+        );
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn test_merge_parquet() {
+        use std::sync::Arc;
+
+        use parquet::{
+            basic::Compression,
+            data_type::Int32Type,
+            file::{
+                properties::WriterProperties, reader::FileReader,
+                serialized_reader::SerializedFileReader, writer::SerializedFileWriter,
+            },
+            schema::parser::parse_message_type,
+        };
+
+        let dir = std::env::temp_dir().join(format!("athena-merge-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let schema = Arc::new(parse_message_type("message schema { REQUIRED INT32 id; }").unwrap());
+        let properties = Arc::new(
+            WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build(),
+        );
+        let files = vec![dir.join("0.parquet"), dir.join("1.parquet")];
+        for (path, ids) in files.iter().zip([[1, 2], [3, 4]]) {
+            let file = std::fs::File::create(path).unwrap();
+            let mut writer =
+                SerializedFileWriter::new(file, schema.clone(), properties.clone()).unwrap();
+            let mut row_group = writer.next_row_group().unwrap();
+            let mut column = row_group.next_column().unwrap().unwrap();
+            column
+                .typed::<Int32Type>()
+                .write_batch(&ids, None, None)
+                .unwrap();
+            column.close().unwrap();
+            row_group.close().unwrap();
+            writer.close().unwrap();
+        }
+
+        let dest = dir.join("merged.parquet");
+        merge_files(&files, &dest, MergeFormat::Parquet)
+            .await
+            .unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&dest).unwrap()).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 4);
+        assert_eq!(
+            metadata.row_group(0).column(0).compression(),
+            Compression::SNAPPY
+        );
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -1,4 +1,6 @@
-use aws_sdk_athena::types::QueryExecution;
+use aws_sdk_athena::{error::ProvideErrorMetadata, operation::RequestId, types::QueryExecution};
+pub use aws_utils_core::error::AwsUtilError;
+use aws_utils_core::error::{is_not_found_code, is_retryable_sdk_error};
 use thiserror::Error;
 use tokio::time::error::Elapsed;
 
@@ -19,10 +21,74 @@ pub enum Error {
     #[error("QueryFailed: {0:?}")]
     QueryFailed(Box<QueryExecution>),
 
+    #[error("NamedQueryNotFound: {0}")]
+    NamedQueryNotFound(String),
+
     #[error("Timeout {0}")]
     Timeout(#[from] Elapsed),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[cfg(feature = "s3")]
+    #[error(transparent)]
+    S3(#[from] aws_utils_s3::error::Error),
+
+    #[cfg(feature = "dynamodb")]
+    #[error(transparent)]
+    DynamoDb(#[from] aws_utils_dynamodb::error::Error),
+
+    #[cfg(feature = "parquet")]
+    #[error(transparent)]
+    Parquet(#[from] parquet::errors::ParquetError),
 }
 
 pub(crate) fn from_aws_sdk_error(e: impl Into<aws_sdk_athena::Error>) -> Error {
     Error::AwsSdk(Box::new(e.into()))
 }
+
+impl AwsUtilError for Error {
+    fn error_code(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.code(),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.error_code(),
+            #[cfg(feature = "dynamodb")]
+            Error::DynamoDb(e) => e.error_code(),
+            _ => None,
+        }
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.request_id(),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.aws_request_id(),
+            #[cfg(feature = "dynamodb")]
+            Error::DynamoDb(e) => e.aws_request_id(),
+            _ => None,
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::AwsSdk(e) => is_retryable_sdk_error(e.as_ref()),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.is_retryable(),
+            #[cfg(feature = "dynamodb")]
+            Error::DynamoDb(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+
+    fn is_not_found(&self) -> bool {
+        match self {
+            #[cfg(feature = "s3")]
+            Error::S3(e) => AwsUtilError::is_not_found(e),
+            #[cfg(feature = "dynamodb")]
+            Error::DynamoDb(e) => e.is_not_found(),
+            Error::NamedQueryNotFound(_) => true,
+            _ => self.error_code().is_some_and(is_not_found_code),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use aws_sdk_athena::types::{QueryExecution, QueryExecutionState};
+use chrono::{DateTime, Utc};
+
+use crate::stats::QueryStats;
+
+/// Query execution passed to the hook of
+/// [`AthenaContext::on_complete`](crate::context::AthenaContext::on_complete), to be
+/// persisted for auditing, e.g. to DynamoDB, S3 or a log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryHistoryRecord {
+    pub execution_id: String,
+    pub query: String,
+    pub workgroup: Option<String>,
+    pub catalog: Option<String>,
+    pub database: Option<String>,
+    /// Final state, or QUEUED / RUNNING when the wait timed out.
+    pub state: Option<QueryExecutionState>,
+    pub state_change_reason: Option<String>,
+    pub submitted_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Statistics of GetQueryExecution, without the runtime statistics.
+    pub stats: QueryStats,
+}
+
+impl QueryHistoryRecord {
+    pub fn from_execution(execution: &QueryExecution) -> Self {
+        let status = execution.status();
+        let to_chrono = |t: &aws_sdk_athena::primitives::DateTime| {
+            DateTime::from_timestamp(t.secs(), t.subsec_nanos())
+        };
+        Self {
+            execution_id: execution
+                .query_execution_id()
+                .unwrap_or_default()
+                .to_string(),
+            query: execution.query().unwrap_or_default().to_string(),
+            workgroup: execution.work_group().map(ToString::to_string),
+            catalog: execution
+                .query_execution_context()
+                .and_then(|c| c.catalog())
+                .map(ToString::to_string),
+            database: execution
+                .query_execution_context()
+                .and_then(|c| c.database())
+                .map(ToString::to_string),
+            state: status.and_then(|s| s.state()).cloned(),
+            state_change_reason: status
+                .and_then(|s| s.state_change_reason())
+                .map(ToString::to_string),
+            submitted_at: status
+                .and_then(|s| s.submission_date_time())
+                .and_then(to_chrono),
+            completed_at: status
+                .and_then(|s| s.completion_date_time())
+                .and_then(to_chrono),
+            stats: QueryStats::from_statistics(execution.statistics(), None),
+        }
+    }
+}
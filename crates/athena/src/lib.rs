@@ -1,5 +1,13 @@
+pub mod context;
+#[cfg(feature = "s3")]
+pub mod download;
 pub mod error;
+pub mod history;
+pub mod named_query;
+#[cfg(any(feature = "s3", feature = "dynamodb"))]
+pub mod pipeline;
 pub mod query;
+pub mod stats;
 pub mod wait;
 use std::time::Duration;
 
@@ -9,6 +17,8 @@ use aws_config::{
 };
 pub use aws_sdk_athena;
 use aws_sdk_athena::{Client, config::SharedInterceptor};
+#[cfg(feature = "metrics")]
+pub use aws_utils_core::metrics;
 
 pub async fn make_client_with_timeout_default(endpoint_url: Option<String>) -> Client {
     make_client_with_timeout(
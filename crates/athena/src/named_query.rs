@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use aws_sdk_athena::{
+    Client,
+    types::{NamedQuery, QueryExecutionContext, ResultConfiguration},
+};
+use aws_smithy_types_convert::stream::PaginationStreamExt;
+use futures_util::{TryStream, TryStreamExt};
+
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    wait::{PollInterval, start_query_execution_wait},
+};
+
+/// Max IDs of BatchGetNamedQuery.
+const BATCH_GET_SIZE: usize = 50;
+
+/// Creates a named query and returns its ID.
+pub async fn create_named_query(
+    client: &Client,
+    name: impl Into<String>,
+    database: impl Into<String>,
+    query_string: impl Into<String>,
+    description: Option<impl Into<String>>,
+    work_group: Option<impl Into<String>>,
+) -> Result<String, Error> {
+    let output = client
+        .create_named_query()
+        .name(name)
+        .database(database)
+        .query_string(query_string)
+        .set_description(description.map(Into::into))
+        .set_work_group(work_group.map(Into::into))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    output
+        .named_query_id
+        .ok_or_else(|| Error::Invalid("named query ID is missing".to_owned()))
+}
+
+pub async fn get_named_query(
+    client: &Client,
+    named_query_id: impl Into<String>,
+) -> Result<NamedQuery, Error> {
+    let output = client
+        .get_named_query()
+        .named_query_id(named_query_id)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    output
+        .named_query
+        .ok_or_else(|| Error::Invalid("named query is missing".to_owned()))
+}
+
+pub async fn delete_named_query(
+    client: &Client,
+    named_query_id: impl Into<String>,
+) -> Result<(), Error> {
+    client
+        .delete_named_query()
+        .named_query_id(named_query_id)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+/// Streams the IDs of the named queries in the work group (primary if None).
+pub fn list_named_query_ids_stream(
+    client: &Client,
+    work_group: Option<impl Into<String>>,
+) -> impl TryStream<Ok = String, Error = Error> + Unpin {
+    client
+        .list_named_queries()
+        .set_work_group(work_group.map(Into::into))
+        .into_paginator()
+        .send()
+        .into_stream_03x()
+        .map_err(from_aws_sdk_error)
+        .map_ok(|s| {
+            futures_util::stream::iter(s.named_query_ids.unwrap_or_default().into_iter().map(Ok))
+        })
+        .try_flatten()
+}
+
+/// Named queries in the work group (primary if None), fetched with BatchGetNamedQuery.
+pub async fn list_named_queries(
+    client: &Client,
+    work_group: Option<impl Into<String>>,
+) -> Result<Vec<NamedQuery>, Error> {
+    let ids: Vec<String> = list_named_query_ids_stream(client, work_group)
+        .try_collect()
+        .await?;
+    let mut named_queries = Vec::with_capacity(ids.len());
+    for chunk in ids.chunks(BATCH_GET_SIZE) {
+        let output = client
+            .batch_get_named_query()
+            .set_named_query_ids(Some(chunk.to_vec()))
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        named_queries.extend(output.named_queries.unwrap_or_default());
+    }
+    Ok(named_queries)
+}
+
+/// Named query with the name in the work group (primary if None).
+/// Names are not unique in Athena, so the first match is returned.
+pub async fn find_named_query(
+    client: &Client,
+    name: &str,
+    work_group: Option<impl Into<String>>,
+) -> Result<Option<NamedQuery>, Error> {
+    let named_queries = list_named_queries(client, work_group).await?;
+    Ok(named_queries.into_iter().find(|q| q.name() == name))
+}
+
+/// Looks up a named query by name and runs it on its database with
+/// [`start_query_execution_wait`], returning the query execution ID.
+pub async fn execute_named_query(
+    client: &Client,
+    name: &str,
+    work_group: Option<impl Into<String>>,
+    result_configuration: Option<ResultConfiguration>,
+    timeout_duration: Duration,
+    check_interval: impl Into<PollInterval>,
+) -> Result<String, Error> {
+    let work_group = work_group.map(Into::into);
+    let named_query = find_named_query(client, name, work_group.clone())
+        .await?
+        .ok_or_else(|| Error::NamedQueryNotFound(name.to_owned()))?;
+    let builder = client
+        .start_query_execution()
+        .query_string(named_query.query_string())
+        .query_execution_context(
+            QueryExecutionContext::builder()
+                .database(named_query.database())
+                .build(),
+        )
+        .set_result_configuration(result_configuration)
+        .set_work_group(work_group);
+    start_query_execution_wait(client, builder, timeout_duration, check_interval).await
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_utils_core::error::AwsUtilError;
+    use mockito::Server;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_find_named_query() {
+        let mut server = Server::new_async().await;
+        let list = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.ListNamedQueries")
+            .with_status(200)
+            .with_body(r#"{"NamedQueryIds":["q1","q2"]}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let batch_get = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.BatchGetNamedQuery")
+            .with_status(200)
+            .with_body(
+                r#"{"NamedQueries":[
+                    {"Name":"daily","Database":"logs","QueryString":"SELECT 1","NamedQueryId":"q1"},
+                    {"Name":"weekly","Database":"logs","QueryString":"SELECT 2","NamedQueryId":"q2"}
+                ]}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let named_query = find_named_query(&client, "weekly", None::<String>)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(named_query.named_query_id(), Some("q2"));
+        assert_eq!(named_query.query_string(), "SELECT 2");
+
+        let e = execute_named_query(
+            &client,
+            "monthly",
+            None::<String>,
+            None,
+            Duration::from_secs(1),
+            Duration::from_millis(10),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(e, Error::NamedQueryNotFound(_)));
+        assert!(e.is_not_found());
+        list.assert_async().await;
+        batch_get.assert_async().await;
+    }
+}
@@ -0,0 +1,436 @@
+#[cfg(feature = "dynamodb")]
+use std::collections::HashMap;
+use std::time::Duration;
+
+use aws_sdk_athena::{
+    Client,
+    operation::start_query_execution::builders::StartQueryExecutionFluentBuilder,
+    types::{ColumnInfo, ResultSet},
+};
+#[cfg(feature = "dynamodb")]
+use aws_utils_dynamodb::{
+    aws_sdk_dynamodb::{self, types::AttributeValue},
+    record::{MAX_BATCH_WRITE_ITEMS, batch_put_items},
+};
+#[cfg(feature = "s3")]
+use aws_utils_s3::aws_sdk_s3::{
+    self,
+    types::{CompletedMultipartUpload, CompletedPart},
+};
+use futures_util::{TryStream, TryStreamExt};
+
+use crate::{
+    error::Error,
+    query::{column_info, get_query_results_stream, is_header_row},
+    wait::{PollInterval, start_query_execution_wait},
+};
+
+/// Result pages of a query, with the header row of SELECT results skipped.
+struct ResultPages<S> {
+    stream: S,
+    columns: Option<Vec<ColumnInfo>>,
+}
+
+async fn run_query(
+    client: &Client,
+    builder: StartQueryExecutionFluentBuilder,
+    timeout_duration: Duration,
+    check_interval: impl Into<PollInterval>,
+) -> Result<ResultPages<impl TryStream<Ok = ResultSet, Error = Error> + Unpin>, Error> {
+    let query_execution_id =
+        start_query_execution_wait(client, builder, timeout_duration, check_interval).await?;
+    Ok(ResultPages {
+        stream: get_query_results_stream(client, Some(query_execution_id)),
+        columns: None,
+    })
+}
+
+impl<S> ResultPages<S>
+where
+    S: TryStream<Ok = ResultSet, Error = Error> + Unpin,
+{
+    /// Rows of the next page. Each page has at most 1000 rows.
+    async fn next(&mut self) -> Result<Option<Vec<Vec<Option<String>>>>, Error> {
+        let Some(result_set) = self.stream.try_next().await? else {
+            return Ok(None);
+        };
+        let is_first = self.columns.is_none();
+        let columns = self.columns.get_or_insert_with(|| column_info(&result_set));
+        Ok(Some(rows(&result_set, columns, is_first)))
+    }
+
+    fn columns(&self) -> &[ColumnInfo] {
+        self.columns.as_deref().unwrap_or_default()
+    }
+}
+
+fn rows(
+    result_set: &ResultSet,
+    columns: &[ColumnInfo],
+    is_first: bool,
+) -> Vec<Vec<Option<String>>> {
+    let mut rows = result_set
+        .rows()
+        .iter()
+        .map(|row| {
+            row.data()
+                .iter()
+                .map(|d| d.var_char_value().map(ToString::to_string))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    // SELECT の結果は最初のページの先頭行がカラム名になっている
+    if is_first
+        && result_set
+            .rows()
+            .first()
+            .is_some_and(|row| is_header_row(row, columns))
+    {
+        rows.remove(0);
+    }
+    rows
+}
+
+#[cfg(feature = "s3")]
+fn csv_field(value: Option<&str>) -> String {
+    match value {
+        Some(v) if v.contains([',', '"', '\n', '\r']) => format!("\"{}\"", v.replace('"', "\"\"")),
+        Some(v) => v.to_string(),
+        None => String::new(),
+    }
+}
+
+#[cfg(feature = "s3")]
+fn csv_line<'a>(buf: &mut Vec<u8>, values: impl Iterator<Item = Option<&'a str>>) {
+    let line = values.map(csv_field).collect::<Vec<_>>().join(",");
+    buf.extend_from_slice(line.as_bytes());
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// Runs the query and writes the results to S3 as CSV with a header row,
+/// returning the number of rows written. NULL is written as an empty field.
+///
+/// Results larger than the part size are uploaded with multipart upload,
+/// so the whole result is never held in memory. A failed multipart upload is aborted.
+#[cfg(feature = "s3")]
+#[allow(clippy::too_many_arguments)]
+pub async fn athena_to_s3_csv(
+    client: &Client,
+    builder: StartQueryExecutionFluentBuilder,
+    timeout_duration: Duration,
+    check_interval: impl Into<PollInterval>,
+    s3_client: &aws_sdk_s3::Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+) -> Result<u64, Error> {
+    let mut upload = CsvUpload {
+        client: s3_client,
+        bucket_name: bucket_name.into(),
+        key: key.into(),
+        upload_id: None,
+        parts: vec![],
+        buf: vec![],
+        header_written: false,
+        count: 0,
+    };
+    let result = async {
+        let mut pages = run_query(client, builder, timeout_duration, check_interval).await?;
+        while let Some(rows) = pages.next().await? {
+            upload.write(pages.columns(), rows).await?;
+        }
+        Ok(())
+    }
+    .await;
+    match result {
+        Ok(()) => upload.finish().await,
+        Err(e) => {
+            upload.abort().await;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(feature = "s3")]
+struct CsvUpload<'a> {
+    client: &'a aws_sdk_s3::Client,
+    bucket_name: String,
+    key: String,
+    upload_id: Option<String>,
+    parts: Vec<CompletedPart>,
+    buf: Vec<u8>,
+    header_written: bool,
+    count: u64,
+}
+
+#[cfg(feature = "s3")]
+impl CsvUpload<'_> {
+    async fn write(
+        &mut self,
+        columns: &[ColumnInfo],
+        rows: Vec<Vec<Option<String>>>,
+    ) -> Result<(), Error> {
+        if !self.header_written {
+            csv_line(&mut self.buf, columns.iter().map(|c| Some(c.name())));
+            self.header_written = true;
+        }
+        for row in &rows {
+            csv_line(&mut self.buf, row.iter().map(Option::as_deref));
+        }
+        self.count += rows.len() as u64;
+        if self.buf.len() >= aws_utils_s3::transfer::MIN_PART_SIZE {
+            self.upload_part().await?;
+        }
+        Ok(())
+    }
+
+    async fn upload_part(&mut self) -> Result<(), Error> {
+        let upload_id = match &self.upload_id {
+            Some(upload_id) => upload_id.clone(),
+            None => {
+                let output = self
+                    .client
+                    .create_multipart_upload()
+                    .bucket(&self.bucket_name)
+                    .key(&self.key)
+                    .content_type("text/csv")
+                    .send()
+                    .await
+                    .map_err(from_s3_sdk_error)?;
+                let upload_id = output
+                    .upload_id
+                    .ok_or_else(|| Error::Invalid("upload_id is missing".to_string()))?;
+                self.upload_id = Some(upload_id.clone());
+                upload_id
+            }
+        };
+        let part_number = self.parts.len() as i32 + 1;
+        let output = self
+            .client
+            .upload_part()
+            .bucket(&self.bucket_name)
+            .key(&self.key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(std::mem::take(&mut self.buf).into())
+            .send()
+            .await
+            .map_err(from_s3_sdk_error)?;
+        self.parts.push(
+            CompletedPart::builder()
+                .set_e_tag(output.e_tag)
+                .part_number(part_number)
+                .build(),
+        );
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<u64, Error> {
+        let Some(upload_id) = self.upload_id.clone() else {
+            aws_utils_s3::object::put_object(
+                self.client,
+                &self.bucket_name,
+                &self.key,
+                self.buf,
+                Some("text/csv"),
+                None::<String>,
+            )
+            .await?;
+            return Ok(self.count);
+        };
+        let result = async {
+            if !self.buf.is_empty() {
+                self.upload_part().await?;
+            }
+            self.client
+                .complete_multipart_upload()
+                .bucket(&self.bucket_name)
+                .key(&self.key)
+                .upload_id(&upload_id)
+                .multipart_upload(
+                    CompletedMultipartUpload::builder()
+                        .set_parts(Some(std::mem::take(&mut self.parts)))
+                        .build(),
+                )
+                .send()
+                .await
+                .map_err(from_s3_sdk_error)
+        }
+        .await;
+        match result {
+            Ok(_) => Ok(self.count),
+            Err(e) => {
+                self.abort().await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn abort(&self) {
+        if let Some(upload_id) = &self.upload_id {
+            // 途中のパートが残ると課金対象になるので中断する
+            let _ = self
+                .client
+                .abort_multipart_upload()
+                .bucket(&self.bucket_name)
+                .key(&self.key)
+                .upload_id(upload_id)
+                .send()
+                .await;
+        }
+    }
+}
+
+#[cfg(feature = "s3")]
+fn from_s3_sdk_error(e: impl Into<aws_utils_s3::error::Error>) -> Error {
+    Error::S3(e.into())
+}
+
+/// Runs the query and puts each row to the DynamoDB table as an item, with
+/// BatchWriteItem of 25 items. Returns the number of items written.
+///
+/// Columns are mapped to attributes by their Athena type: numeric types to `N`,
+/// `boolean` to `BOOL` and the others to `S`. NULL columns are omitted from the item,
+/// so the query must select the key attributes of the table with non NULL values.
+/// Unprocessed items are retried with [`batch_put_items`].
+#[cfg(feature = "dynamodb")]
+pub async fn athena_to_dynamodb(
+    client: &Client,
+    builder: StartQueryExecutionFluentBuilder,
+    timeout_duration: Duration,
+    check_interval: impl Into<PollInterval>,
+    dynamodb_client: &aws_sdk_dynamodb::Client,
+    table_name: impl Into<String>,
+) -> Result<u64, Error> {
+    let table_name = table_name.into();
+    let mut pages = run_query(client, builder, timeout_duration, check_interval).await?;
+    let mut count = 0;
+    while let Some(rows) = pages.next().await? {
+        count += rows.len() as u64;
+        let items = rows
+            .into_iter()
+            .map(|row| to_item(pages.columns(), row))
+            .collect::<Vec<_>>();
+        for chunk in items.chunks(MAX_BATCH_WRITE_ITEMS) {
+            batch_put_items(dynamodb_client, &table_name, chunk.to_vec()).await?;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(feature = "dynamodb")]
+fn to_item(columns: &[ColumnInfo], row: Vec<Option<String>>) -> HashMap<String, AttributeValue> {
+    columns
+        .iter()
+        .zip(row)
+        .filter_map(|(column, value)| {
+            let value = value?;
+            let value = match column.r#type() {
+                "tinyint" | "smallint" | "integer" | "int" | "bigint" | "float" | "real"
+                | "double" | "decimal" => AttributeValue::N(value),
+                "boolean" => AttributeValue::Bool(value == "true"),
+                _ => AttributeValue::S(value),
+            };
+            Some((column.name().to_string(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_athena::types::{Datum, ResultSetMetadata, Row};
+
+    use super::*;
+
+    fn result_set(columns: &[(&str, &str)], rows: &[&[Option<&str>]]) -> ResultSet {
+        ResultSet::builder()
+            .result_set_metadata(
+                ResultSetMetadata::builder()
+                    .set_column_info(Some(
+                        columns
+                            .iter()
+                            .map(|(name, r#type)| {
+                                ColumnInfo::builder()
+                                    .name(*name)
+                                    .r#type(*r#type)
+                                    .build()
+                                    .unwrap()
+                            })
+                            .collect(),
+                    ))
+                    .build(),
+            )
+            .set_rows(Some(
+                rows.iter()
+                    .map(|row| {
+                        Row::builder()
+                            .set_data(Some(
+                                row.iter()
+                                    .map(|v| {
+                                        Datum::builder()
+                                            .set_var_char_value(v.map(Into::into))
+                                            .build()
+                                    })
+                                    .collect(),
+                            ))
+                            .build()
+                    })
+                    .collect(),
+            ))
+            .build()
+    }
+
+    #[test]
+    fn test_rows() {
+        let result_set = result_set(
+            &[("id", "integer"), ("name", "varchar")],
+            &[
+                &[Some("id"), Some("name")],
+                &[Some("1"), Some("a")],
+                &[Some("2"), None],
+            ],
+        );
+        let columns = column_info(&result_set);
+
+        let first = rows(&result_set, &columns, true);
+        assert_eq!(
+            first,
+            vec![
+                vec![Some("1".to_string()), Some("a".to_string())],
+                vec![Some("2".to_string()), None],
+            ]
+        );
+        assert_eq!(rows(&result_set, &columns, false).len(), 3);
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_csv_line() {
+        let mut buf = vec![];
+        csv_line(
+            &mut buf,
+            [Some("a"), None, Some("b,c"), Some("say \"hi\"")].into_iter(),
+        );
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "a,,\"b,c\",\"say \"\"hi\"\"\"\r\n"
+        );
+    }
+
+    #[cfg(feature = "dynamodb")]
+    #[test]
+    fn test_to_item() {
+        use aws_sdk_dynamodb::types::AttributeValue;
+
+        let result_set = result_set(
+            &[("id", "bigint"), ("active", "boolean"), ("name", "varchar")],
+            &[],
+        );
+        let item = to_item(
+            &column_info(&result_set),
+            vec![Some("1".to_string()), Some("true".to_string()), None],
+        );
+        assert_eq!(item.len(), 2);
+        assert_eq!(item["id"], AttributeValue::N("1".to_string()));
+        assert_eq!(item["active"], AttributeValue::Bool(true));
+    }
+}
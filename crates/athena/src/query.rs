@@ -2,15 +2,36 @@ use aws_sdk_athena::{
     Client,
     operation::{
         get_query_execution::GetQueryExecutionOutput,
+        get_query_runtime_statistics::GetQueryRuntimeStatisticsOutput,
         start_query_execution::StartQueryExecutionOutput,
     },
-    types::{QueryExecutionContext, ResultConfiguration, ResultReuseConfiguration, ResultSet},
+    types::{
+        ColumnInfo, QueryExecutionContext, ResultConfiguration, ResultReuseConfiguration,
+        ResultSet, Row,
+    },
 };
 use aws_smithy_types_convert::stream::PaginationStreamExt;
-use futures_util::{TryStream, TryStreamExt};
+use futures_util::{StreamExt, TryStream, TryStreamExt, stream};
 
 use crate::error::{Error, from_aws_sdk_error};
 
+/// QueryExecutionContext of a database and a data catalog, e.g. a federated catalog of a
+/// Lambda connector. None if both are None (the `default` database of `AwsDataCatalog`).
+pub fn query_execution_context(
+    database: Option<impl Into<String>>,
+    catalog: Option<impl Into<String>>,
+) -> Option<QueryExecutionContext> {
+    if database.is_none() && catalog.is_none() {
+        return None;
+    }
+    Some(
+        QueryExecutionContext::builder()
+            .set_database(database.map(Into::into))
+            .set_catalog(catalog.map(Into::into))
+            .build(),
+    )
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn start_query_execution(
     client: &Client,
@@ -48,6 +69,18 @@ pub async fn get_query_execution(
         .map_err(from_aws_sdk_error)
 }
 
+pub async fn get_query_runtime_statistics(
+    client: &Client,
+    execution_id: Option<impl Into<String>>,
+) -> Result<GetQueryRuntimeStatisticsOutput, Error> {
+    client
+        .get_query_runtime_statistics()
+        .set_query_execution_id(execution_id.map(Into::into))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
 pub fn get_query_results_stream(
     client: &Client,
     execution_id: Option<impl Into<String>>,
@@ -66,3 +99,131 @@ pub fn get_query_results_stream(
             )
         )
 }
+
+/// Column metadata of the results and a stream of their rows, flattened from
+/// [`get_query_results_stream`] with the header row of SELECT results skipped.
+///
+/// The first page is fetched before returning, to read the column metadata.
+pub async fn rows_stream(
+    client: &Client,
+    execution_id: impl Into<String>,
+) -> Result<
+    (
+        Vec<ColumnInfo>,
+        impl TryStream<Ok = Row, Error = Error> + Unpin,
+    ),
+    Error,
+> {
+    let mut pages = get_query_results_stream(client, Some(execution_id))
+        .into_stream()
+        .fuse();
+    let (columns, first_rows) = match pages.try_next().await? {
+        Some(result_set) => {
+            let columns = column_info(&result_set);
+            let mut rows = result_set.rows.unwrap_or_default();
+            // ヘッダー行は最初のページの先頭にだけある
+            if rows.first().is_some_and(|row| is_header_row(row, &columns)) {
+                rows.remove(0);
+            }
+            (columns, rows)
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+    let rest = pages
+        .map_ok(|result_set| stream::iter(result_set.rows.unwrap_or_default().into_iter().map(Ok)))
+        .try_flatten();
+    Ok((
+        columns,
+        stream::iter(first_rows.into_iter().map(Ok)).chain(rest),
+    ))
+}
+
+pub(crate) fn column_info(result_set: &ResultSet) -> Vec<ColumnInfo> {
+    result_set
+        .result_set_metadata()
+        .map(|m| m.column_info().to_vec())
+        .unwrap_or_default()
+}
+
+/// Whether `row` is the header row that the first page of SELECT results starts with.
+pub(crate) fn is_header_row(row: &Row, columns: &[ColumnInfo]) -> bool {
+    row.data().len() == columns.len()
+        && row
+            .data()
+            .iter()
+            .zip(columns)
+            .all(|(d, c)| d.var_char_value() == Some(c.name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{Matcher, Server};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rows_stream() {
+        let mut server = Server::new_async().await;
+        let columns = r#""ResultSetMetadata":{"ColumnInfo":[
+            {"Name":"id","Type":"integer"},{"Name":"name","Type":"varchar"}
+        ]}"#;
+        let first = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.GetQueryResults")
+            .match_body(Matcher::Regex(
+                r#"^\{"QueryExecutionId":"q1"\}$"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(format!(
+                r#"{{"NextToken":"t1","ResultSet":{{{columns},"Rows":[
+                    {{"Data":[{{"VarCharValue":"id"}},{{"VarCharValue":"name"}}]}},
+                    {{"Data":[{{"VarCharValue":"1"}},{{"VarCharValue":"a"}}]}}
+                ]}}}}"#
+            ))
+            .create_async()
+            .await;
+        // 2 ページ目の先頭がカラム名と同じ値でもスキップしない
+        let second = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.GetQueryResults")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"NextToken":"t1"}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(format!(
+                r#"{{"ResultSet":{{{columns},"Rows":[
+                    {{"Data":[{{"VarCharValue":"id"}},{{"VarCharValue":"name"}}]}},
+                    {{"Data":[{{"VarCharValue":"2"}},{{}}]}}
+                ]}}}}"#
+            ))
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let (columns, rows) = rows_stream(&client, "q1").await.unwrap();
+        assert_eq!(
+            columns.iter().map(|c| c.name()).collect::<Vec<_>>(),
+            vec!["id", "name"]
+        );
+        let rows = rows
+            .map_ok(|row| {
+                row.data()
+                    .iter()
+                    .map(|d| d.var_char_value().map(ToString::to_string))
+                    .collect::<Vec<_>>()
+            })
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                vec![Some("1".to_string()), Some("a".to_string())],
+                vec![Some("id".to_string()), Some("name".to_string())],
+                vec![Some("2".to_string()), None],
+            ]
+        );
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+}
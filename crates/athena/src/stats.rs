@@ -0,0 +1,245 @@
+use std::time::Duration;
+
+use aws_sdk_athena::{
+    Client,
+    types::{QueryExecutionStatistics, QueryRuntimeStatistics, QueryStage, QueryStagePlanNode},
+};
+
+use crate::{
+    error::Error,
+    query::{get_query_execution, get_query_runtime_statistics},
+};
+
+/// Statistics of a query execution, combining QueryExecutionStatistics and
+/// GetQueryRuntimeStatistics.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryStats {
+    pub data_scanned_bytes: Option<i64>,
+    pub queue_time: Option<Duration>,
+    pub planning_time: Option<Duration>,
+    pub engine_execution_time: Option<Duration>,
+    pub service_processing_time: Option<Duration>,
+    pub total_execution_time: Option<Duration>,
+    pub input_rows: Option<i64>,
+    pub input_bytes: Option<i64>,
+    pub output_rows: Option<i64>,
+    pub output_bytes: Option<i64>,
+    /// The output stage and its sub stages. None if runtime statistics were not fetched.
+    pub output_stage: Option<StageStats>,
+}
+
+/// Rows, bytes and timing of a single stage of the query plan.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StageStats {
+    pub stage_id: Option<i64>,
+    pub state: Option<String>,
+    pub input_rows: Option<i64>,
+    pub input_bytes: Option<i64>,
+    pub output_rows: Option<i64>,
+    pub output_bytes: Option<i64>,
+    pub execution_time: Option<Duration>,
+    /// Operator names of the stage plan in depth-first order.
+    pub operators: Vec<String>,
+    pub sub_stages: Vec<StageStats>,
+}
+
+impl QueryStats {
+    pub fn from_statistics(
+        statistics: Option<&QueryExecutionStatistics>,
+        runtime_statistics: Option<&QueryRuntimeStatistics>,
+    ) -> Self {
+        let timeline = runtime_statistics.and_then(|r| r.timeline());
+        let rows = runtime_statistics.and_then(|r| r.rows());
+        // Runtime statistics are preferred, falling back to QueryExecutionStatistics.
+        let millis = |runtime: Option<i64>, execution: Option<i64>| {
+            runtime.or(execution).and_then(millis_to_duration)
+        };
+        Self {
+            data_scanned_bytes: statistics.and_then(|s| s.data_scanned_in_bytes()),
+            queue_time: millis(
+                timeline.and_then(|t| t.query_queue_time_in_millis()),
+                statistics.and_then(|s| s.query_queue_time_in_millis()),
+            ),
+            planning_time: millis(
+                timeline.and_then(|t| t.query_planning_time_in_millis()),
+                statistics.and_then(|s| s.query_planning_time_in_millis()),
+            ),
+            engine_execution_time: millis(
+                timeline.and_then(|t| t.engine_execution_time_in_millis()),
+                statistics.and_then(|s| s.engine_execution_time_in_millis()),
+            ),
+            service_processing_time: millis(
+                timeline.and_then(|t| t.service_processing_time_in_millis()),
+                statistics.and_then(|s| s.service_processing_time_in_millis()),
+            ),
+            total_execution_time: millis(
+                timeline.and_then(|t| t.total_execution_time_in_millis()),
+                statistics.and_then(|s| s.total_execution_time_in_millis()),
+            ),
+            input_rows: rows.and_then(|r| r.input_rows()),
+            input_bytes: rows.and_then(|r| r.input_bytes()),
+            output_rows: rows.and_then(|r| r.output_rows()),
+            output_bytes: rows.and_then(|r| r.output_bytes()),
+            output_stage: runtime_statistics
+                .and_then(|r| r.output_stage())
+                .map(StageStats::from),
+        }
+    }
+
+    /// All stages sorted by execution time, slowest first.
+    pub fn stages_by_execution_time(&self) -> Vec<&StageStats> {
+        let mut stages = vec![];
+        if let Some(stage) = &self.output_stage {
+            stage.collect(&mut stages);
+        }
+        stages.sort_by_key(|s| std::cmp::Reverse(s.execution_time));
+        stages
+    }
+}
+
+impl StageStats {
+    fn collect<'a>(&'a self, stages: &mut Vec<&'a StageStats>) {
+        stages.push(self);
+        for sub_stage in &self.sub_stages {
+            sub_stage.collect(stages);
+        }
+    }
+}
+
+impl From<&QueryStage> for StageStats {
+    fn from(stage: &QueryStage) -> Self {
+        let mut operators = vec![];
+        if let Some(plan) = stage.query_stage_plan() {
+            collect_operators(plan, &mut operators);
+        }
+        Self {
+            stage_id: stage.stage_id(),
+            state: stage.state().map(ToString::to_string),
+            input_rows: stage.input_rows(),
+            input_bytes: stage.input_bytes(),
+            output_rows: stage.output_rows(),
+            output_bytes: stage.output_bytes(),
+            execution_time: stage.execution_time().and_then(millis_to_duration),
+            operators,
+            sub_stages: stage.sub_stages().iter().map(StageStats::from).collect(),
+        }
+    }
+}
+
+fn collect_operators(node: &QueryStagePlanNode, operators: &mut Vec<String>) {
+    if let Some(name) = node.name() {
+        operators.push(name.to_string());
+    }
+    for child in node.children() {
+        collect_operators(child, operators);
+    }
+}
+
+fn millis_to_duration(millis: i64) -> Option<Duration> {
+    u64::try_from(millis).ok().map(Duration::from_millis)
+}
+
+/// Fetches GetQueryExecution and GetQueryRuntimeStatistics and combines them.
+pub async fn get_query_stats(
+    client: &Client,
+    execution_id: impl Into<String>,
+) -> Result<QueryStats, Error> {
+    let execution_id = execution_id.into();
+    let execution = get_query_execution(client, Some(&execution_id)).await?;
+    let runtime = get_query_runtime_statistics(client, Some(&execution_id)).await?;
+    Ok(QueryStats::from_statistics(
+        execution.query_execution().and_then(|q| q.statistics()),
+        runtime.query_runtime_statistics(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Server;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_query_stats() {
+        let mut server = Server::new_async().await;
+        let execution = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.GetQueryExecution")
+            .with_status(200)
+            .with_body(
+                r#"{"QueryExecution":{"QueryExecutionId":"q1","Statistics":{
+                    "DataScannedInBytes":4096,"QueryPlanningTimeInMillis":20,
+                    "TotalExecutionTimeInMillis":999
+                }}}"#,
+            )
+            .create_async()
+            .await;
+        // Timeline に無い QueryPlanningTimeInMillis は Statistics の値になる
+        let runtime = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonAthena.GetQueryRuntimeStatistics")
+            .with_status(200)
+            .with_body(
+                r#"{"QueryRuntimeStatistics":{
+                    "Timeline":{"QueryQueueTimeInMillis":10,"EngineExecutionTimeInMillis":300,
+                        "ServiceProcessingTimeInMillis":5,"TotalExecutionTimeInMillis":335},
+                    "Rows":{"InputRows":1000,"InputBytes":4096,"OutputRows":2,"OutputBytes":64},
+                    "OutputStage":{"StageId":0,"State":"FINISHED","InputRows":4,"OutputRows":2,
+                        "ExecutionTime":40,
+                        "QueryStagePlan":{"Name":"Output","Children":[{"Name":"Aggregate"}]},
+                        "SubStages":[{"StageId":1,"State":"FINISHED","InputRows":1000,
+                            "InputBytes":4096,"ExecutionTime":250,
+                            "QueryStagePlan":{"Name":"TableScan"}}]}
+                }}"#,
+            )
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let stats = get_query_stats(&client, "q1").await.unwrap();
+        let scan = StageStats {
+            stage_id: Some(1),
+            state: Some("FINISHED".to_string()),
+            input_rows: Some(1000),
+            input_bytes: Some(4096),
+            execution_time: Some(Duration::from_millis(250)),
+            operators: vec!["TableScan".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            stats,
+            QueryStats {
+                data_scanned_bytes: Some(4096),
+                queue_time: Some(Duration::from_millis(10)),
+                planning_time: Some(Duration::from_millis(20)),
+                engine_execution_time: Some(Duration::from_millis(300)),
+                service_processing_time: Some(Duration::from_millis(5)),
+                total_execution_time: Some(Duration::from_millis(335)),
+                input_rows: Some(1000),
+                input_bytes: Some(4096),
+                output_rows: Some(2),
+                output_bytes: Some(64),
+                output_stage: Some(StageStats {
+                    stage_id: Some(0),
+                    state: Some("FINISHED".to_string()),
+                    input_rows: Some(4),
+                    output_rows: Some(2),
+                    execution_time: Some(Duration::from_millis(40)),
+                    operators: vec!["Output".to_string(), "Aggregate".to_string()],
+                    sub_stages: vec![scan],
+                    ..Default::default()
+                }),
+            }
+        );
+        assert_eq!(
+            stats
+                .stages_by_execution_time()
+                .into_iter()
+                .map(|s| s.stage_id)
+                .collect::<Vec<_>>(),
+            vec![Some(1), Some(0)]
+        );
+        execution.assert_async().await;
+        runtime.assert_async().await;
+    }
+}
@@ -8,44 +8,138 @@ use aws_sdk_athena::{
     },
     types::QueryExecutionState,
 };
+use aws_utils_core::retry::equal_jitter;
 
 use crate::{
     error::{Error, from_aws_sdk_error},
     query::get_query_execution,
 };
 
+/// Interval between GetQueryExecution calls while waiting for a query.
+///
+/// A [`Duration`] converts to [`fixed`](Self::fixed). Use [`exponential`](Self::exponential)
+/// to check long queries less and less often.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PollInterval {
+    initial: Duration,
+    max: Duration,
+    multiplier: f64,
+    jitter: bool,
+}
+
+impl PollInterval {
+    /// The same interval for every check, without jitter.
+    pub fn fixed(interval: Duration) -> Self {
+        Self {
+            initial: interval,
+            max: interval,
+            multiplier: 1.0,
+            jitter: false,
+        }
+    }
+
+    /// Starts at `initial` and doubles after each check up to `max`, with jitter.
+    pub fn exponential(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max: max.max(initial),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+
+    /// Growth of the interval after each check. Lowered to at least 1.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Waits a random duration between half and all of the interval (see [`equal_jitter`]), so
+    /// that queries started together do not check at the same time.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Interval after the `attempt`th check (from 0), before jitter.
+    fn interval(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.powi(attempt.min(64) as i32);
+        let secs = self.initial.as_secs_f64() * factor;
+        if secs >= self.max.as_secs_f64() {
+            return self.max;
+        }
+        Duration::from_secs_f64(secs)
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let interval = self.interval(attempt);
+        if self.jitter {
+            equal_jitter(interval)
+        } else {
+            interval
+        }
+    }
+}
+
+impl From<Duration> for PollInterval {
+    fn from(interval: Duration) -> Self {
+        Self::fixed(interval)
+    }
+}
+
 pub async fn start_query_execution_wait(
     client: &Client,
     builder: StartQueryExecutionFluentBuilder,
     timeout_duration: Duration,
-    check_duration: Duration,
+    check_interval: impl Into<PollInterval>,
 ) -> Result<String, Error> {
-    let query_execution_id = builder.send().await.map_err(from_aws_sdk_error)?;
-    let query_execution_id = query_execution_id
+    let query_execution_id = start_query_execution_id(builder).await?;
+    wait_query_succeeded(
+        client,
+        &query_execution_id,
+        timeout_duration,
+        check_interval,
+    )
+    .await?;
+    Ok(query_execution_id)
+}
+
+pub(crate) async fn start_query_execution_id(
+    builder: StartQueryExecutionFluentBuilder,
+) -> Result<String, Error> {
+    let output = builder.send().await.map_err(from_aws_sdk_error)?;
+    Ok(output
         .query_execution_id()
         .ok_or_else(|| Error::Invalid("query execution ID is missing".to_owned()))?
-        .to_string();
+        .to_string())
+}
 
+pub(crate) async fn wait_query_succeeded(
+    client: &Client,
+    query_execution_id: &str,
+    timeout_duration: Duration,
+    check_interval: impl Into<PollInterval>,
+) -> Result<(), Error> {
     tokio::time::timeout(
         timeout_duration,
-        check_query_succeeded(client, &query_execution_id, check_duration),
+        check_query_succeeded(client, query_execution_id, check_interval.into()),
     )
-    .await??;
-
-    Ok(query_execution_id)
+    .await?
 }
 
 async fn check_query_succeeded(
     client: &Client,
     query_execution_id: &str,
-    duration: Duration,
+    check_interval: PollInterval,
 ) -> Result<(), Error> {
+    let mut attempt = 0;
     loop {
         let get_query_execution = get_query_execution(client, Some(query_execution_id)).await?;
         if inner_check_query_succeeded(&get_query_execution)? {
             return Ok(());
         };
-        tokio::time::sleep(duration).await;
+        tokio::time::sleep(check_interval.delay(attempt)).await;
+        attempt = attempt.saturating_add(1);
     }
 }
 
@@ -76,3 +170,34 @@ fn inner_check_query_succeeded(
         None => Err(Error::Invalid("query state is invalid".to_owned())),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poll_interval() {
+        let fixed = PollInterval::fixed(Duration::from_secs(2));
+        assert_eq!(fixed.delay(0), Duration::from_secs(2));
+        assert_eq!(fixed.delay(10), Duration::from_secs(2));
+
+        let exponential =
+            PollInterval::exponential(Duration::from_secs(1), Duration::from_secs(10));
+        assert_eq!(exponential.interval(0), Duration::from_secs(1));
+        assert_eq!(exponential.interval(3), Duration::from_secs(8));
+        assert_eq!(exponential.interval(4), Duration::from_secs(10));
+        assert_eq!(exponential.interval(u32::MAX), Duration::from_secs(10));
+        for attempt in 0..10 {
+            let delay = exponential.delay(attempt);
+            let interval = exponential.interval(attempt);
+            assert!(interval / 2 <= delay && delay <= interval);
+        }
+
+        let from_duration = PollInterval::from(Duration::from_secs(2));
+        assert_eq!(from_duration, fixed);
+
+        let multiplied =
+            PollInterval::exponential(Duration::from_millis(500), Duration::MAX).multiplier(1.5);
+        assert_eq!(multiplied.interval(2), Duration::from_millis(1125));
+    }
+}
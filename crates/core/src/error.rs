@@ -0,0 +1,240 @@
+use aws_smithy_runtime_api::client::{
+    orchestrator::HttpResponse,
+    result::{ConnectorError, SdkError},
+};
+use aws_smithy_types::error::metadata::ProvideErrorMetadata;
+
+// リトライ判定は aws-smithy-runtime の ErrorCode 分類に合わせる
+// https://github.com/smithy-lang/smithy-rs/blob/main/rust-runtime/aws-smithy-runtime/src/client/retries/classifiers.rs
+const THROTTLING_ERROR_CODES: &[&str] = &[
+    "Throttling",
+    "ThrottlingException",
+    "ThrottledException",
+    "RequestThrottledException",
+    "TooManyRequestsException",
+    "ProvisionedThroughputExceededException",
+    "TransactionInProgressException",
+    "RequestLimitExceeded",
+    "BandwidthLimitExceeded",
+    "LimitExceededException",
+    "RequestThrottled",
+    "SlowDown",
+    "PriorRequestNotComplete",
+    "EC2ThrottledException",
+    "KMS.ThrottlingException",
+];
+
+const TRANSIENT_ERROR_CODES: &[&str] = &[
+    "RequestTimeout",
+    "RequestTimeoutException",
+    "InternalError",
+    "InternalFailure",
+    "InternalServerError",
+    "InternalServerException",
+    "InternalServiceError",
+    "ServiceUnavailable",
+    "ServiceUnavailableException",
+    "ServiceException",
+];
+
+const NOT_FOUND_ERROR_CODES: &[&str] = &[
+    "NotFound",
+    "NoSuchKey",
+    "NoSuchBucket",
+    "NoSuchUpload",
+    "NoSuchVersion",
+    "NoSuchEntity",
+    "ReplicationConfigurationNotFoundError",
+    "ResourceNotFound",
+    "ResourceNotFoundException",
+    "ParameterNotFound",
+    "ParameterVersionNotFound",
+    "QueueDoesNotExist",
+    "AWS.SimpleQueueService.NonExistentQueue",
+    "TableNotFoundException",
+];
+
+/// Common classification of the Error types of the aws_utils crates,
+/// so retry and alerting code can handle errors of any service the same way.
+pub trait AwsUtilError: std::error::Error {
+    /// Error code returned by the service, e.g. `ThrottlingException`.
+    fn error_code(&self) -> Option<&str>;
+
+    /// Request ID of the failed request, for contacting AWS support.
+    fn aws_request_id(&self) -> Option<&str>;
+
+    /// Extended request ID (`x-amz-id-2`) of S3, which AWS support asks for with the request ID.
+    fn extended_request_id(&self) -> Option<&str> {
+        None
+    }
+
+    /// HTTP status of the response, None if no response was received.
+    fn http_status(&self) -> Option<u16> {
+        None
+    }
+
+    fn is_throttling(&self) -> bool {
+        self.error_code().is_some_and(is_throttling_code)
+    }
+
+    /// Throttling, server side errors and network failures.
+    fn is_retryable(&self) -> bool {
+        self.is_throttling() || self.error_code().is_some_and(is_transient_code)
+    }
+
+    fn is_not_found(&self) -> bool {
+        self.error_code().is_some_and(is_not_found_code)
+    }
+}
+
+/// Request IDs and HTTP status of the response to a failed request, read from the
+/// `SdkError` before it is converted into the service error, which does not keep them all.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponseMetadata {
+    pub status: Option<u16>,
+    pub request_id: Option<String>,
+    pub extended_request_id: Option<String>,
+}
+
+impl ResponseMetadata {
+    pub fn from_sdk_error<E>(error: &SdkError<E, HttpResponse>) -> Self {
+        error
+            .raw_response()
+            .map(Self::from_response)
+            .unwrap_or_default()
+    }
+
+    pub fn from_response(response: &HttpResponse) -> Self {
+        let header = |name: &str| response.headers().get(name).map(ToString::to_string);
+        Self {
+            status: Some(response.status().as_u16()),
+            request_id: header("x-amzn-requestid").or_else(|| header("x-amz-request-id")),
+            extended_request_id: header("x-amz-id-2"),
+        }
+    }
+}
+
+pub fn is_throttling_code(code: &str) -> bool {
+    THROTTLING_ERROR_CODES.contains(&code)
+}
+
+pub fn is_transient_code(code: &str) -> bool {
+    TRANSIENT_ERROR_CODES.contains(&code)
+}
+
+pub fn is_not_found_code(code: &str) -> bool {
+    NOT_FOUND_ERROR_CODES.contains(&code)
+}
+
+/// Whether an SDK error is worth retrying, by its error code or by an IO error
+/// or timeout of the connection in its sources.
+pub fn is_retryable_sdk_error<E>(error: &E) -> bool
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+{
+    error
+        .code()
+        .is_some_and(|code| is_throttling_code(code) || is_transient_code(code))
+        || has_transient_source(error)
+}
+
+fn has_transient_source(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = error.source();
+    while let Some(error) = source {
+        if let Some(e) = error.downcast_ref::<ConnectorError>()
+            && (e.is_io() || e.is_timeout())
+        {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_smithy_runtime_api::{client::result, http::StatusCode};
+    use aws_smithy_types::error::ErrorMetadata;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct SdkError(ErrorMetadata);
+
+    impl std::fmt::Display for SdkError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0.code())
+        }
+    }
+
+    impl std::error::Error for SdkError {}
+
+    impl ProvideErrorMetadata for SdkError {
+        fn meta(&self) -> &ErrorMetadata {
+            &self.0
+        }
+    }
+
+    impl AwsUtilError for SdkError {
+        fn error_code(&self) -> Option<&str> {
+            self.code()
+        }
+
+        fn aws_request_id(&self) -> Option<&str> {
+            None
+        }
+    }
+
+    fn error(code: &str) -> SdkError {
+        SdkError(ErrorMetadata::builder().code(code).build())
+    }
+
+    #[test]
+    fn test_classification() {
+        let throttling = error("ThrottlingException");
+        assert!(throttling.is_throttling());
+        assert!(throttling.is_retryable());
+        assert!(!throttling.is_not_found());
+
+        let unavailable = error("ServiceUnavailable");
+        assert!(!unavailable.is_throttling());
+        assert!(unavailable.is_retryable());
+
+        let not_found = error("NoSuchKey");
+        assert!(not_found.is_not_found());
+        assert!(!not_found.is_retryable());
+
+        assert!(is_retryable_sdk_error(&error("SlowDown")));
+        assert!(!is_retryable_sdk_error(&error("AccessDenied")));
+    }
+
+    #[test]
+    fn test_response_metadata() {
+        let mut response = HttpResponse::new(
+            StatusCode::try_from(403).unwrap(),
+            aws_smithy_types::body::SdkBody::empty(),
+        );
+        response
+            .headers_mut()
+            .insert("x-amz-request-id", "4442587FB7D0A2F9");
+        response
+            .headers_mut()
+            .insert("x-amz-id-2", "vlR7PnpV2Ce81puvXYuYd5ZDAbA=");
+        let error = result::SdkError::<(), _>::service_error((), response);
+
+        assert_eq!(
+            ResponseMetadata::from_sdk_error(&error),
+            ResponseMetadata {
+                status: Some(403),
+                request_id: Some("4442587FB7D0A2F9".to_string()),
+                extended_request_id: Some("vlR7PnpV2Ce81puvXYuYd5ZDAbA=".to_string()),
+            }
+        );
+        assert_eq!(
+            ResponseMetadata::from_sdk_error(&result::SdkError::<(), HttpResponse>::timeout_error(
+                "timeout"
+            )),
+            ResponseMetadata::default()
+        );
+    }
+}
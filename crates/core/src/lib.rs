@@ -0,0 +1,5 @@
+pub mod error;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod preload;
+pub mod retry;
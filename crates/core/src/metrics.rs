@@ -0,0 +1,288 @@
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use aws_smithy_runtime_api::{
+    box_error::BoxError,
+    client::{
+        interceptors::{
+            Intercept,
+            context::{BeforeSerializationInterceptorContextRef, FinalizerInterceptorContextRef},
+        },
+        orchestrator::Metadata,
+        runtime_components::RuntimeComponents,
+    },
+};
+use aws_smithy_types::config_bag::{ConfigBag, Storable, StoreReplace};
+
+/// Result of an AWS API call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    /// Error response of the service, with its HTTP status.
+    ServiceError(u16),
+    /// Timeout, connection failure or other error without a response.
+    Failure,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::ServiceError(_) => "service_error",
+            Outcome::Failure => "failure",
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Outcome::Success)
+    }
+}
+
+/// One AWS API call, including the retries of the SDK.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallMetrics<'a> {
+    /// e.g. `DynamoDB`
+    pub service: &'a str,
+    /// e.g. `GetItem`
+    pub operation: &'a str,
+    pub duration: Duration,
+    pub outcome: Outcome,
+}
+
+/// Metrics of a message consumer loop, e.g. `AdaptiveConsumer` of aws_utils_sqs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsumerMetrics<'a> {
+    /// One receive call, empty if `messages` is 0.
+    Receive {
+        /// e.g. the queue name
+        source: &'a str,
+        messages: usize,
+        duration: Duration,
+    },
+    /// The handler of one message.
+    Processed {
+        source: &'a str,
+        duration: Duration,
+        success: bool,
+    },
+}
+
+/// Receives the metrics of every call made with a client that has a [`MetricsInterceptor`].
+pub trait MetricsSink: Send + Sync + 'static {
+    fn record(&self, call: &CallMetrics<'_>);
+
+    /// Metrics of the consumer loops given this sink. Ignored by default.
+    fn record_consumer(&self, _metrics: &ConsumerMetrics<'_>) {}
+}
+
+/// Interceptor calling a [`MetricsSink`] after each operation. Pass it to `make_client` of
+/// any aws_utils crate with `SharedInterceptor::new(MetricsInterceptor::new(sink))`, so the
+/// calls of all wrapper functions are recorded.
+#[derive(Clone)]
+pub struct MetricsInterceptor {
+    sink: Arc<dyn MetricsSink>,
+}
+
+impl MetricsInterceptor {
+    pub fn new(sink: impl MetricsSink) -> Self {
+        Self {
+            sink: Arc::new(sink),
+        }
+    }
+}
+
+impl fmt::Debug for MetricsInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsInterceptor").finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct StartTime(Instant);
+
+impl Storable for StartTime {
+    type Storer = StoreReplace<Self>;
+}
+
+impl Intercept for MetricsInterceptor {
+    fn name(&self) -> &'static str {
+        "MetricsInterceptor"
+    }
+
+    fn read_before_execution(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        cfg.interceptor_state().store_put(StartTime(Instant::now()));
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let duration = cfg
+            .load::<StartTime>()
+            .map(|start| start.0.elapsed())
+            .unwrap_or_default();
+        let outcome = match (context.output_or_error(), context.response()) {
+            (Some(Ok(_)), _) => Outcome::Success,
+            (Some(Err(_)), Some(response)) => Outcome::ServiceError(response.status().as_u16()),
+            _ => Outcome::Failure,
+        };
+        let metadata = cfg.load::<Metadata>();
+        self.sink.record(&CallMetrics {
+            service: metadata.map(|m| m.service()).unwrap_or("unknown"),
+            operation: metadata.map(|m| m.name()).unwrap_or("unknown"),
+            duration,
+            outcome,
+        });
+        Ok(())
+    }
+}
+
+/// [`MetricsSink`] recording with the [metrics](https://crates.io/crates/metrics) crate:
+///
+/// - `aws_requests_total` counter with `service`, `operation` and `outcome` labels
+/// - `aws_request_errors_total` counter with `service`, `operation` and `status` labels
+/// - `aws_request_duration_seconds` histogram with `service` and `operation` labels
+///
+/// and of the consumer loops, with a `source` label:
+///
+/// - `consumer_receives_total` and `consumer_empty_receives_total` counters
+/// - `consumer_messages_per_receive` and `consumer_receive_duration_seconds` histograms
+/// - `consumer_processing_duration_seconds` histogram with an `outcome` label
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsCrateSink;
+
+impl MetricsSink for MetricsCrateSink {
+    fn record(&self, call: &CallMetrics<'_>) {
+        let service = call.service.to_string();
+        let operation = call.operation.to_string();
+        metrics::counter!(
+            "aws_requests_total",
+            "service" => service.clone(),
+            "operation" => operation.clone(),
+            "outcome" => call.outcome.as_str(),
+        )
+        .increment(1);
+        if call.outcome.is_error() {
+            let status = match call.outcome {
+                Outcome::ServiceError(status) => status.to_string(),
+                _ => "none".to_string(),
+            };
+            metrics::counter!(
+                "aws_request_errors_total",
+                "service" => service.clone(),
+                "operation" => operation.clone(),
+                "status" => status,
+            )
+            .increment(1);
+        }
+        metrics::histogram!(
+            "aws_request_duration_seconds",
+            "service" => service,
+            "operation" => operation,
+        )
+        .record(call.duration.as_secs_f64());
+    }
+
+    fn record_consumer(&self, metrics: &ConsumerMetrics<'_>) {
+        match metrics {
+            ConsumerMetrics::Receive {
+                source,
+                messages,
+                duration,
+            } => {
+                let source = source.to_string();
+                metrics::counter!("consumer_receives_total", "source" => source.clone())
+                    .increment(1);
+                if *messages == 0 {
+                    metrics::counter!("consumer_empty_receives_total", "source" => source.clone())
+                        .increment(1);
+                }
+                metrics::histogram!("consumer_messages_per_receive", "source" => source.clone())
+                    .record(*messages as f64);
+                metrics::histogram!("consumer_receive_duration_seconds", "source" => source)
+                    .record(duration.as_secs_f64());
+            }
+            ConsumerMetrics::Processed {
+                source,
+                duration,
+                success,
+            } => {
+                metrics::histogram!(
+                    "consumer_processing_duration_seconds",
+                    "source" => source.to_string(),
+                    "outcome" => if *success { "success" } else { "failure" },
+                )
+                .record(duration.as_secs_f64());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn test_sink() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl MetricsSink for Arc<Recorder> {
+            fn record(&self, call: &CallMetrics<'_>) {
+                self.0.lock().unwrap().push(format!(
+                    "{}.{} {}",
+                    call.service,
+                    call.operation,
+                    call.outcome.as_str()
+                ));
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let interceptor = MetricsInterceptor::new(recorder.clone());
+        interceptor.sink.record(&CallMetrics {
+            service: "SQS",
+            operation: "SendMessage",
+            duration: Duration::from_millis(5),
+            outcome: Outcome::ServiceError(400),
+        });
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec!["SQS.SendMessage service_error".to_string()]
+        );
+        assert!(Outcome::Failure.is_error());
+        assert!(!Outcome::Success.is_error());
+
+        // metrics のレコーダー未設定でもパニックしない
+        MetricsCrateSink.record(&CallMetrics {
+            service: "SQS",
+            operation: "SendMessage",
+            duration: Duration::from_millis(5),
+            outcome: Outcome::Success,
+        });
+        MetricsCrateSink.record_consumer(&ConsumerMetrics::Receive {
+            source: "jobs",
+            messages: 0,
+            duration: Duration::from_secs(20),
+        });
+        // 既定では消費側のメトリクスを無視する
+        recorder.record_consumer(&ConsumerMetrics::Processed {
+            source: "jobs",
+            duration: Duration::from_millis(5),
+            success: true,
+        });
+        assert_eq!(recorder.0.lock().unwrap().len(), 1);
+    }
+}
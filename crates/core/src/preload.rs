@@ -0,0 +1,277 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::error::is_not_found_code;
+
+const FORBIDDEN_ERROR_CODES: &[&str] = &[
+    "AccessDenied",
+    "AccessDeniedException",
+    "UnauthorizedOperation",
+    "UnrecognizedClientException",
+];
+
+/// Why a required key could not be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MissingReason {
+    NotFound,
+    /// The caller is not allowed to read the key, e.g. missing IAM or KMS permissions.
+    Forbidden,
+    /// Any other error, with its message.
+    Failed(String),
+}
+
+impl MissingReason {
+    /// Classifies the error code of a failed lookup.
+    pub fn from_error_code(code: Option<&str>, message: impl fmt::Display) -> Self {
+        match code {
+            Some(code) if is_not_found_code(code) => Self::NotFound,
+            Some(code) if FORBIDDEN_ERROR_CODES.contains(&code) => Self::Forbidden,
+            Some(code) => Self::Failed(format!("{code}: {message}")),
+            None => Self::Failed(message.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for MissingReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "not found"),
+            Self::Forbidden => write!(f, "forbidden"),
+            Self::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingKey {
+    pub key: String,
+    pub reason: MissingReason,
+}
+
+/// Every required key that could not be loaded, so they can all be fixed at once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MissingKeys {
+    pub keys: Vec<MissingKey>,
+}
+
+impl MissingKeys {
+    pub fn push(&mut self, key: impl Into<String>, reason: MissingReason) {
+        self.keys.push(MissingKey {
+            key: key.into(),
+            reason,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn not_found(&self) -> impl Iterator<Item = &str> {
+        self.keys_by(|r| *r == MissingReason::NotFound)
+    }
+
+    pub fn forbidden(&self) -> impl Iterator<Item = &str> {
+        self.keys_by(|r| *r == MissingReason::Forbidden)
+    }
+
+    fn keys_by(&self, f: impl Fn(&MissingReason) -> bool) -> impl Iterator<Item = &str> {
+        self.keys
+            .iter()
+            .filter(move |k| f(&k.reason))
+            .map(|k| k.key.as_str())
+    }
+}
+
+impl fmt::Display for MissingKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} required keys could not be loaded: ", self.keys.len())?;
+        for (i, k) in self.keys.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} ({})", k.key, k.reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingKeys {}
+
+/// Error of the typed getters of [`ConfigMap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The key was not preloaded.
+    NotLoaded(String),
+    /// The value could not be parsed as the requested type.
+    Invalid { key: String, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotLoaded(key) => write!(f, "{key} is not loaded"),
+            Self::Invalid { key, message } => write!(f, "invalid value of {key}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Values loaded at startup by the `preload` functions of the aws_utils crates, by key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigMap {
+    values: HashMap<String, String>,
+}
+
+impl ConfigMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(key.into(), value.into());
+    }
+
+    /// Adds the values of `other`, overwriting the same keys.
+    pub fn extend(&mut self, other: ConfigMap) {
+        self.values.extend(other.values);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<&str, ConfigError> {
+        self.get(key)
+            .ok_or_else(|| ConfigError::NotLoaded(key.to_string()))
+    }
+
+    /// The value parsed with [`FromStr`], e.g. `u16` for a port or `bool`.
+    pub fn get_parsed<T>(&self, key: &str) -> Result<T, ConfigError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.get_str(key)?
+            .trim()
+            .parse()
+            .map_err(|e: T::Err| ConfigError::Invalid {
+                key: key.to_string(),
+                message: e.to_string(),
+            })
+    }
+
+    /// Comma separated values, trimmed. Empty items are skipped.
+    pub fn get_list(&self, key: &str) -> Result<Vec<&str>, ConfigError> {
+        Ok(self
+            .get_str(key)?
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect())
+    }
+}
+
+/// Combines the results of several preloads, e.g. of SSM and Secrets Manager,
+/// reporting the missing keys of all of them.
+pub fn combine(
+    results: impl IntoIterator<Item = Result<ConfigMap, MissingKeys>>,
+) -> Result<ConfigMap, MissingKeys> {
+    let mut config = ConfigMap::new();
+    let mut missing = MissingKeys::default();
+    for result in results {
+        match result {
+            Ok(c) => config.extend(c),
+            Err(m) => missing.keys.extend(m.keys),
+        }
+    }
+    if missing.is_empty() {
+        Ok(config)
+    } else {
+        Err(missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_reason_from_error_code() {
+        assert_eq!(
+            MissingReason::from_error_code(Some("ParameterNotFound"), "x"),
+            MissingReason::NotFound
+        );
+        assert_eq!(
+            MissingReason::from_error_code(Some("AccessDeniedException"), "x"),
+            MissingReason::Forbidden
+        );
+        assert_eq!(
+            MissingReason::from_error_code(Some("DecryptionFailure"), "kms"),
+            MissingReason::Failed("DecryptionFailure: kms".to_string())
+        );
+        assert_eq!(
+            MissingReason::from_error_code(None, "timeout"),
+            MissingReason::Failed("timeout".to_string())
+        );
+    }
+
+    #[test]
+    fn test_config_map_getters() {
+        let mut config = ConfigMap::new();
+        config.insert("/app/port", " 8080 ");
+        config.insert("/app/debug", "true");
+        config.insert("/app/hosts", "a.example.com, b.example.com,");
+
+        assert_eq!(config.get_parsed::<u16>("/app/port"), Ok(8080));
+        assert_eq!(config.get_parsed::<bool>("/app/debug"), Ok(true));
+        assert_eq!(
+            config.get_list("/app/hosts"),
+            Ok(vec!["a.example.com", "b.example.com"])
+        );
+        assert!(matches!(
+            config.get_parsed::<u16>("/app/debug"),
+            Err(ConfigError::Invalid { .. })
+        ));
+        assert_eq!(
+            config.get_str("/app/unknown"),
+            Err(ConfigError::NotLoaded("/app/unknown".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_combine() {
+        let mut a = ConfigMap::new();
+        a.insert("a", "1");
+        let mut b = ConfigMap::new();
+        b.insert("b", "2");
+        let config = combine([Ok(a.clone()), Ok(b)]).unwrap();
+        assert_eq!(config.len(), 2);
+
+        let mut missing = MissingKeys::default();
+        missing.push("c", MissingReason::NotFound);
+        missing.push("d", MissingReason::Forbidden);
+        let err = combine([Ok(a), Err(missing)]).unwrap_err();
+        assert_eq!(err.not_found().collect::<Vec<_>>(), vec!["c"]);
+        assert_eq!(err.forbidden().collect::<Vec<_>>(), vec!["d"]);
+        assert_eq!(
+            err.to_string(),
+            "2 required keys could not be loaded: c (not found), d (forbidden)"
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+/// Upper bound of the delays of [`backoff_delay`], the same as the max backoff of the AWS SDKs.
+pub const MAX_BACKOFF_DELAY: Duration = Duration::from_secs(20);
+
+/// Delay before the `attempt`-th retry (1 for the first): exponential backoff from `base_delay`
+/// doubling each time, capped at [`MAX_BACKOFF_DELAY`], with full jitter so clients throttled at
+/// the same time do not retry together.
+pub fn backoff_delay(base_delay: Duration, attempt: u32) -> Duration {
+    backoff_ceiling(base_delay, attempt).mul_f64(fastrand::f64())
+}
+
+/// A random duration between half and all of `delay` (equal jitter), for polling loops that
+/// should keep most of their interval.
+pub fn equal_jitter(delay: Duration) -> Duration {
+    let half = delay / 2;
+    half + half.mul_f64(fastrand::f64())
+}
+
+// ジッター前の上限
+fn backoff_ceiling(base_delay: Duration, attempt: u32) -> Duration {
+    base_delay
+        .checked_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .map_or(MAX_BACKOFF_DELAY, |delay| delay.min(MAX_BACKOFF_DELAY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let base_delay = Duration::from_millis(100);
+        assert_eq!(backoff_ceiling(base_delay, 1), Duration::from_millis(100));
+        assert_eq!(backoff_ceiling(base_delay, 3), Duration::from_millis(400));
+        assert_eq!(backoff_ceiling(base_delay, 10), MAX_BACKOFF_DELAY);
+        assert_eq!(backoff_ceiling(base_delay, u32::MAX), MAX_BACKOFF_DELAY);
+        for attempt in 1..40 {
+            assert!(backoff_delay(base_delay, attempt) <= backoff_ceiling(base_delay, attempt));
+        }
+    }
+
+    #[test]
+    fn test_equal_jitter() {
+        let delay = Duration::from_secs(10);
+        for _ in 0..100 {
+            let jittered = equal_jitter(delay);
+            assert!(jittered >= delay / 2 && jittered <= delay);
+        }
+        assert_eq!(equal_jitter(Duration::ZERO), Duration::ZERO);
+    }
+}
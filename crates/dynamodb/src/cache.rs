@@ -1,4 +1,9 @@
-use std::{collections::HashMap, future::Future, hash::Hash, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    hash::Hash,
+    time::Duration,
+};
 
 use aws_sdk_dynamodb::Client;
 use chrono::prelude::*;
@@ -49,6 +54,49 @@ where
         );
         Ok(Some(value))
     }
+
+    /// Gets the values of `keys`, calling `f` once with the keys not cached or expired, e.g.
+    /// to load them with BatchGetItem. The loaded values expire after the expiration from
+    /// `now`. Keys without a value are not in the returned map.
+    pub async fn get_many<FutMany>(
+        &mut self,
+        keys: &[K],
+        f: impl FnOnce(Client, Vec<K>) -> FutMany,
+        now: Option<DateTime<Utc>>,
+    ) -> Result<HashMap<K, V>, Error>
+    where
+        FutMany: Future<Output = Result<HashMap<K, V>, Error>>,
+    {
+        let current = get_now(now);
+        let mut values = HashMap::new();
+        let mut missing = vec![];
+        let mut seen = HashSet::new();
+        for key in keys {
+            if !seen.insert(key) {
+                continue;
+            }
+            match self.map.get(key) {
+                Some((value, expire_at)) if current < *expire_at => {
+                    values.insert(key.clone(), value.clone());
+                }
+                _ => missing.push(key.clone()),
+            }
+        }
+        if missing.is_empty() {
+            return Ok(values);
+        }
+        let client = self.client.clone();
+        let loaded = f(client, missing.clone()).await?;
+        let expire_at = expire_at(now, self.expiration);
+        // 要求していないキーが返されてもキャッシュしない
+        for key in missing {
+            if let Some(value) = loaded.get(&key) {
+                self.map.insert(key.clone(), (value.clone(), expire_at));
+                values.insert(key, value.clone());
+            }
+        }
+        Ok(values)
+    }
 }
 
 fn get_now(now: Option<DateTime<Utc>>) -> DateTime<Utc> {
@@ -282,6 +330,87 @@ mod tests {
         assert!(cache.map.contains_key(&key2));
     }
 
+    #[tokio::test]
+    async fn test_cache_map_get_many() {
+        let client = create_test_client().await;
+        let expiration = Duration::from_secs(60);
+        let mut cache: CacheMap<String, TestValue> = CacheMap::new(client, expiration);
+        let now = Utc::now();
+
+        let cached = TestValue("cached".to_string());
+        let cached_clone = cached.clone();
+        cache
+            .get(
+                &"key1".to_string(),
+                |_client, _key| async move { Ok(Some(cached_clone)) },
+                Some(now),
+            )
+            .await
+            .unwrap();
+
+        let keys = ["key1", "key2", "key3", "key2"].map(String::from);
+        let requested = Arc::new(Mutex::new(vec![]));
+        let requested_clone = requested.clone();
+        let values = cache
+            .get_many(
+                &keys,
+                |_client, keys| async move {
+                    *requested_clone.lock().await = keys;
+                    Ok(HashMap::from([
+                        ("key2".to_string(), TestValue("loaded".to_string())),
+                        ("other".to_string(), TestValue("other".to_string())),
+                    ]))
+                },
+                Some(now),
+            )
+            .await
+            .unwrap();
+
+        // キャッシュ済みの key1 と重複した key2 は要求しない
+        assert_eq!(
+            *requested.lock().await,
+            vec!["key2".to_string(), "key3".to_string()]
+        );
+        assert_eq!(
+            values,
+            HashMap::from([
+                ("key1".to_string(), cached),
+                ("key2".to_string(), TestValue("loaded".to_string())),
+            ])
+        );
+        assert_eq!(cache.map.len(), 2);
+
+        // 全てキャッシュ済みならローダーを呼ばない
+        let values =
+            cache
+                .get_many(
+                    &keys[..2],
+                    |_client, _keys| async move {
+                        Err(Error::Invalid("should not be called".to_string()))
+                    },
+                    Some(now + chrono::Duration::seconds(30)),
+                )
+                .await
+                .unwrap();
+        assert_eq!(values.len(), 2);
+
+        // 期限切れのキーは再度読み込む
+        let values = cache
+            .get_many(
+                &keys[..1],
+                |_client, keys| async move {
+                    Ok(keys
+                        .into_iter()
+                        .map(|key| (key, TestValue("reloaded".to_string())))
+                        .collect())
+                },
+                Some(now + chrono::Duration::seconds(61)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(values["key1"], TestValue("reloaded".to_string()));
+    }
+
     #[test]
     fn test_get_now_with_none() {
         let now = get_now(None);
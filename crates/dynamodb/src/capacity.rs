@@ -0,0 +1,233 @@
+use std::{fmt, sync::Arc};
+
+use aws_sdk_dynamodb::{
+    config::{
+        ConfigBag, Intercept, RuntimeComponents,
+        interceptors::{
+            AfterDeserializationInterceptorContextRef, BeforeSerializationInterceptorContextMut,
+        },
+    },
+    error::BoxError,
+    operation::{
+        batch_get_item::{BatchGetItemInput, BatchGetItemOutput},
+        batch_write_item::{BatchWriteItemInput, BatchWriteItemOutput},
+        delete_item::{DeleteItemInput, DeleteItemOutput},
+        get_item::{GetItemInput, GetItemOutput},
+        put_item::{PutItemInput, PutItemOutput},
+        query::{QueryInput, QueryOutput},
+        scan::{ScanInput, ScanOutput},
+        update_item::{UpdateItemInput, UpdateItemOutput},
+    },
+    types::{ConsumedCapacity, ReturnConsumedCapacity},
+};
+
+/// Capacity consumed on a table by a call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapacityUsage<'a> {
+    /// e.g. `GetItem`
+    pub operation: &'a str,
+    pub table_name: &'a str,
+    pub read_capacity_units: f64,
+    pub write_capacity_units: f64,
+    /// Including the capacity of each index with [`ReturnConsumedCapacity::Indexes`].
+    pub consumed_capacity: &'a ConsumedCapacity,
+}
+
+/// Receives the capacity consumed by every call made with a client that has a
+/// [`ConsumedCapacityInterceptor`].
+pub trait CapacitySink: Send + Sync + 'static {
+    fn record(&self, usage: &CapacityUsage<'_>);
+}
+
+impl<F> CapacitySink for F
+where
+    F: Fn(&CapacityUsage<'_>) + Send + Sync + 'static,
+{
+    fn record(&self, usage: &CapacityUsage<'_>) {
+        self(usage)
+    }
+}
+
+/// Interceptor requesting ReturnConsumedCapacity on GetItem, PutItem, UpdateItem,
+/// DeleteItem, Query, Scan, BatchGetItem and BatchWriteItem, and passing the consumed
+/// capacity to a [`CapacitySink`]. Pass it to [`make_client`](crate::make_client) with
+/// `SharedInterceptor::new(ConsumedCapacityInterceptor::new(sink))`, so the calls of all
+/// functions of [`record`](crate::record) are recorded, including each page of a stream.
+///
+/// Use a client per feature to tell the capacity of features apart.
+#[derive(Clone)]
+pub struct ConsumedCapacityInterceptor {
+    mode: ReturnConsumedCapacity,
+    sink: Arc<dyn CapacitySink>,
+}
+
+impl ConsumedCapacityInterceptor {
+    pub fn new(sink: impl CapacitySink) -> Self {
+        Self {
+            mode: ReturnConsumedCapacity::Total,
+            sink: Arc::new(sink),
+        }
+    }
+
+    /// `ReturnConsumedCapacity::Indexes` also returns the capacity of each index.
+    /// Requests that already set ReturnConsumedCapacity are not changed.
+    pub fn mode(mut self, mode: ReturnConsumedCapacity) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+impl fmt::Debug for ConsumedCapacityInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConsumedCapacityInterceptor")
+            .field("mode", &self.mode)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Intercept for ConsumedCapacityInterceptor {
+    fn name(&self) -> &'static str {
+        "ConsumedCapacityInterceptor"
+    }
+
+    fn modify_before_serialization(
+        &self,
+        context: &mut BeforeSerializationInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let input = context.input_mut();
+        let return_consumed_capacity = if let Some(i) = input.downcast_mut::<GetItemInput>() {
+            &mut i.return_consumed_capacity
+        } else if let Some(i) = input.downcast_mut::<PutItemInput>() {
+            &mut i.return_consumed_capacity
+        } else if let Some(i) = input.downcast_mut::<UpdateItemInput>() {
+            &mut i.return_consumed_capacity
+        } else if let Some(i) = input.downcast_mut::<DeleteItemInput>() {
+            &mut i.return_consumed_capacity
+        } else if let Some(i) = input.downcast_mut::<QueryInput>() {
+            &mut i.return_consumed_capacity
+        } else if let Some(i) = input.downcast_mut::<ScanInput>() {
+            &mut i.return_consumed_capacity
+        } else if let Some(i) = input.downcast_mut::<BatchGetItemInput>() {
+            &mut i.return_consumed_capacity
+        } else if let Some(i) = input.downcast_mut::<BatchWriteItemInput>() {
+            &mut i.return_consumed_capacity
+        } else {
+            return Ok(());
+        };
+        if return_consumed_capacity.is_none() {
+            *return_consumed_capacity = Some(self.mode.clone());
+        }
+        Ok(())
+    }
+
+    fn read_after_deserialization(
+        &self,
+        context: &AfterDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Ok(output) = context.output_or_error() else {
+            return Ok(());
+        };
+        let (operation, capacities) = if let Some(o) = output.downcast_ref::<GetItemOutput>() {
+            ("GetItem", o.consumed_capacity.as_slice())
+        } else if let Some(o) = output.downcast_ref::<PutItemOutput>() {
+            ("PutItem", o.consumed_capacity.as_slice())
+        } else if let Some(o) = output.downcast_ref::<UpdateItemOutput>() {
+            ("UpdateItem", o.consumed_capacity.as_slice())
+        } else if let Some(o) = output.downcast_ref::<DeleteItemOutput>() {
+            ("DeleteItem", o.consumed_capacity.as_slice())
+        } else if let Some(o) = output.downcast_ref::<QueryOutput>() {
+            ("Query", o.consumed_capacity.as_slice())
+        } else if let Some(o) = output.downcast_ref::<ScanOutput>() {
+            ("Scan", o.consumed_capacity.as_slice())
+        } else if let Some(o) = output.downcast_ref::<BatchGetItemOutput>() {
+            ("BatchGetItem", o.consumed_capacity())
+        } else if let Some(o) = output.downcast_ref::<BatchWriteItemOutput>() {
+            ("BatchWriteItem", o.consumed_capacity())
+        } else {
+            return Ok(());
+        };
+        for capacity in capacities {
+            self.sink.record(&usage(operation, capacity));
+        }
+        Ok(())
+    }
+}
+
+fn usage<'a>(operation: &'a str, capacity: &'a ConsumedCapacity) -> CapacityUsage<'a> {
+    // 読み書きの内訳がない場合は操作の種類で CapacityUnits を振り分ける
+    let is_read = matches!(operation, "GetItem" | "Query" | "Scan" | "BatchGetItem");
+    let units = capacity.capacity_units.unwrap_or_default();
+    let (read_units, write_units) = if is_read { (units, 0.0) } else { (0.0, units) };
+    let read_capacity_units = capacity.read_capacity_units.unwrap_or(read_units);
+    let write_capacity_units = capacity.write_capacity_units.unwrap_or(write_units);
+    CapacityUsage {
+        operation,
+        table_name: capacity.table_name().unwrap_or_default(),
+        read_capacity_units,
+        write_capacity_units,
+        consumed_capacity: capacity,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use aws_sdk_dynamodb::{config::SharedInterceptor, types::AttributeValue};
+
+    use super::*;
+    use crate::record::get_item_raw;
+
+    #[tokio::test]
+    async fn test_get_item_capacity() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"ReturnConsumedCapacity":"TOTAL"}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"Item":{"id":{"S":"1"}},"ConsumedCapacity":{"TableName":"users","CapacityUnits":0.5}}"#,
+            )
+            .create_async()
+            .await;
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let recorded = recorded.clone();
+            move |usage: &CapacityUsage<'_>| {
+                recorded.lock().unwrap().push((
+                    usage.operation.to_string(),
+                    usage.table_name.to_string(),
+                    usage.read_capacity_units,
+                    usage.write_capacity_units,
+                ));
+            }
+        };
+        let interceptor = SharedInterceptor::new(ConsumedCapacityInterceptor::new(sink));
+        let client = crate::make_client(Some(server.url()), None, Some(interceptor)).await;
+
+        get_item_raw(
+            &client,
+            "users",
+            HashMap::from([("id".to_string(), AttributeValue::S("1".to_string()))]),
+            None,
+            None,
+            None::<String>,
+            None::<Vec<String>>,
+        )
+        .await
+        .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(
+            *recorded.lock().unwrap(),
+            vec![("GetItem".to_string(), "users".to_string(), 0.5, 0.0)]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use aws_sdk_dynamodb::{Client, types::AttributeValue};
+use aws_smithy_types_convert::stream::PaginationStreamExt;
+use futures_util::{TryStreamExt, future::try_join_all};
+
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    rate_limit::RateLimiter,
+    record::{MAX_BATCH_WRITE_ITEMS, batch_put_items},
+};
+
+type Item = HashMap<String, AttributeValue>;
+type TransformFn = Arc<dyn Fn(Item) -> Option<Item> + Send + Sync>;
+type ProgressFn = Arc<dyn Fn(&CopyProgress) + Send + Sync>;
+
+/// Items processed by [`copy_table`] so far, or in total when it returns.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CopyProgress {
+    pub scanned: usize,
+    pub written: usize,
+    /// Items the transform returned None for.
+    pub skipped: usize,
+}
+
+/// Options of [`copy_table`].
+#[derive(Clone)]
+pub struct CopyTableOptions {
+    total_segments: i32,
+    items_per_second: Option<u32>,
+    transform: Option<TransformFn>,
+    on_progress: Option<ProgressFn>,
+}
+
+impl CopyTableOptions {
+    /// 4 segments, no rate limit.
+    pub fn new() -> Self {
+        Self {
+            total_segments: 4,
+            items_per_second: None,
+            transform: None,
+            on_progress: None,
+        }
+    }
+
+    /// Segments of the parallel scan, scanned concurrently. Raised to at least 1.
+    pub fn total_segments(mut self, total_segments: i32) -> Self {
+        self.total_segments = total_segments.max(1);
+        self
+    }
+
+    /// Max items written per second by all segments, to keep the consumed capacity of the
+    /// destination under its provisioned throughput.
+    pub fn items_per_second(mut self, items_per_second: u32) -> Self {
+        self.items_per_second = Some(items_per_second.max(1));
+        self
+    }
+
+    /// Changes each item before it is written, e.g. to rename attributes for a new schema.
+    /// Items for which it returns None are not copied.
+    pub fn transform(
+        mut self,
+        transform: impl Fn(Item) -> Option<Item> + Send + Sync + 'static,
+    ) -> Self {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Called after each batch is written, from any segment.
+    pub fn on_progress(
+        mut self,
+        on_progress: impl Fn(&CopyProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Arc::new(on_progress));
+        self
+    }
+}
+
+impl Default for CopyTableOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CopyTableOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CopyTableOptions")
+            .field("total_segments", &self.total_segments)
+            .field("items_per_second", &self.items_per_second)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Counters and rate limit shared by the segments.
+struct CopyState {
+    scanned: AtomicUsize,
+    written: AtomicUsize,
+    skipped: AtomicUsize,
+    /// Write rate shared by the segments, None if unlimited.
+    limiter: Option<RateLimiter>,
+}
+
+impl CopyState {
+    fn progress(&self) -> CopyProgress {
+        CopyProgress {
+            scanned: self.scanned.load(Ordering::Relaxed),
+            written: self.written.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Copies all items of `source_table_name` to `destination_table_name` (which must exist
+/// with the same key schema) with a parallel scan and BatchWriteItem, e.g. for
+/// environment refreshes. Items with the same key in the destination are overwritten.
+pub async fn copy_table(
+    client: &Client,
+    source_table_name: &str,
+    destination_table_name: &str,
+    options: CopyTableOptions,
+) -> Result<CopyProgress, Error> {
+    let state = CopyState {
+        scanned: AtomicUsize::new(0),
+        written: AtomicUsize::new(0),
+        skipped: AtomicUsize::new(0),
+        limiter: options
+            .items_per_second
+            .map(|items_per_second| RateLimiter::new(items_per_second as f64)),
+    };
+    try_join_all((0..options.total_segments).map(|segment| {
+        copy_segment(
+            client,
+            source_table_name,
+            destination_table_name,
+            &options,
+            &state,
+            segment,
+        )
+    }))
+    .await?;
+    Ok(state.progress())
+}
+
+async fn copy_segment(
+    client: &Client,
+    source_table_name: &str,
+    destination_table_name: &str,
+    options: &CopyTableOptions,
+    state: &CopyState,
+    segment: i32,
+) -> Result<(), Error> {
+    let stream = client
+        .scan()
+        .table_name(source_table_name)
+        .segment(segment)
+        .total_segments(options.total_segments)
+        .into_paginator()
+        .items()
+        .send()
+        .into_stream_03x()
+        .map_err(from_aws_sdk_error);
+    futures_util::pin_mut!(stream);
+    let mut batch = Vec::with_capacity(MAX_BATCH_WRITE_ITEMS);
+    while let Some(item) = stream.try_next().await? {
+        state.scanned.fetch_add(1, Ordering::Relaxed);
+        let item = match &options.transform {
+            Some(transform) => transform(item),
+            None => Some(item),
+        };
+        let Some(item) = item else {
+            state.skipped.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+        batch.push(item);
+        if batch.len() == MAX_BATCH_WRITE_ITEMS {
+            write_batch(client, destination_table_name, options, state, &mut batch).await?;
+        }
+    }
+    if !batch.is_empty() {
+        write_batch(client, destination_table_name, options, state, &mut batch).await?;
+    }
+    Ok(())
+}
+
+async fn write_batch(
+    client: &Client,
+    table_name: &str,
+    options: &CopyTableOptions,
+    state: &CopyState,
+    batch: &mut Vec<Item>,
+) -> Result<(), Error> {
+    let count = batch.len();
+    if let Some(limiter) = &state.limiter {
+        // 全セグメントで共有する書き込み枠を予約してから待つ
+        limiter.acquire(count as f64).await;
+    }
+    batch_put_items(client, table_name, std::mem::take(batch)).await?;
+    state.written.fetch_add(count, Ordering::Relaxed);
+    if let Some(on_progress) = &options.on_progress {
+        on_progress(&state.progress());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use mockito::Matcher;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_copy_table() {
+        let mut server = mockito::Server::new_async().await;
+        let scan = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.Scan")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"TableName":"users","Segment":0,"TotalSegments":1}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"Items":[
+                    {"id":{"S":"1"},"name":{"S":"a"}},
+                    {"id":{"S":"2"},"name":{"S":"b"},"deleted":{"BOOL":true}},
+                    {"id":{"S":"3"},"name":{"S":"c"}}
+                ],"Count":3}"#,
+            )
+            .create_async()
+            .await;
+        let write = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.BatchWriteItem")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"RequestItems":{"users_copy":[
+                    {"PutRequest":{"Item":{"id":{"S":"1"},"display_name":{"S":"a"}}}},
+                    {"PutRequest":{"Item":{"id":{"S":"3"},"display_name":{"S":"c"}}}}
+                ]}}"#
+                    .to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(r#"{"UnprocessedItems":{}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        let progress = Arc::new(Mutex::new(Vec::new()));
+
+        let options = {
+            let progress = progress.clone();
+            CopyTableOptions::new()
+                .total_segments(1)
+                .items_per_second(1000)
+                .transform(|mut item| {
+                    if item.contains_key("deleted") {
+                        return None;
+                    }
+                    let name = item.remove("name")?;
+                    item.insert("display_name".to_string(), name);
+                    Some(item)
+                })
+                .on_progress(move |p| progress.lock().unwrap().push(p.clone()))
+        };
+        let summary = copy_table(&client, "users", "users_copy", options)
+            .await
+            .unwrap();
+
+        let expected = CopyProgress {
+            scanned: 3,
+            written: 2,
+            skipped: 1,
+        };
+        assert_eq!(summary, expected);
+        assert_eq!(*progress.lock().unwrap(), vec![expected]);
+        scan.assert_async().await;
+        write.assert_async().await;
+    }
+}
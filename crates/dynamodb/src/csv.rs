@@ -1,13 +1,17 @@
-use std::time::Duration;
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::Duration,
+};
 
 use aws_sdk_dynamodb::{
     Client,
     types::{
-        AttributeDefinition, BillingMode, CsvOptions, ImportStatus, InputFormat,
+        AttributeDefinition, AttributeValue, BillingMode, CsvOptions, ImportStatus, InputFormat,
         InputFormatOptions, KeySchemaElement, KeyType, ProvisionedThroughput, S3BucketSource,
         TableCreationParameters,
     },
 };
+use base64::{Engine, prelude::BASE64_STANDARD};
 use tokio::time::sleep;
 
 use crate::{
@@ -120,3 +124,440 @@ pub async fn import_table(
     }
     Ok(())
 }
+
+/// Header and delimiter of CSV files, inferred from the header row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvHeader {
+    pub delimiter: char,
+    pub header_list: Vec<String>,
+}
+
+/// Delimiter candidates. The one appearing most often in the header row is used.
+const DELIMITER_CANDIDATES: [char; 4] = [',', '\t', ';', '|'];
+
+/// Infers the delimiter (`,`, tab, `;` or `|`) and the column names from a header row.
+pub fn infer_csv_header(header_row: &str) -> CsvHeader {
+    let header_row = header_row
+        .trim_start_matches('\u{feff}')
+        .trim_end_matches(['\r', '\n']);
+    let delimiter = DELIMITER_CANDIDATES
+        .into_iter()
+        .max_by_key(|d| {
+            // 同数ならカンマを優先する
+            (header_row.matches(*d).count(), *d == ',')
+        })
+        .filter(|d| header_row.contains(*d))
+        .unwrap_or(',');
+    let header_list = header_row
+        .split(delimiter)
+        .map(|name| {
+            let name = name.trim();
+            name.strip_prefix('"')
+                .and_then(|n| n.strip_suffix('"'))
+                .unwrap_or(name)
+                .replace("\"\"", "\"")
+        })
+        .collect();
+    CsvHeader {
+        delimiter,
+        header_list,
+    }
+}
+
+/// Reads the header row of the first object under `key_prefix` and infers the header.
+#[cfg(feature = "s3")]
+pub async fn peek_csv_header(
+    s3_client: &aws_utils_s3::aws_sdk_s3::Client,
+    bucket_name: impl Into<String>,
+    key_prefix: impl Into<String>,
+) -> Result<CsvHeader, Error> {
+    use futures_util::TryStreamExt;
+
+    let bucket_name = bucket_name.into();
+    let key_prefix = key_prefix.into();
+    let stream = aws_utils_s3::object::list_stream(s3_client, &bucket_name, Some(&key_prefix))
+        .try_filter(|o| std::future::ready(o.size().unwrap_or_default() > 0));
+    futures_util::pin_mut!(stream);
+    let object = stream
+        .try_next()
+        .await?
+        .ok_or_else(|| Error::ValidationError(format!("no object under {key_prefix}")))?;
+    let key = object.key().unwrap_or_default();
+    let mut body = aws_utils_s3::object::get_object(s3_client, &bucket_name, key)
+        .await?
+        .body;
+    // ヘッダー行だけ読んで残りは読まない
+    let mut buf = Vec::new();
+    while let Some(chunk) = body
+        .try_next()
+        .await
+        .map_err(aws_utils_s3::error::Error::from)?
+    {
+        buf.extend_from_slice(&chunk);
+        if buf.contains(&b'\n') {
+            break;
+        }
+    }
+    let header_row = buf.split(|b| *b == b'\n').next().unwrap_or_default();
+    let header_row = std::str::from_utf8(header_row)
+        .map_err(|_| Error::ValidationError(format!("header row of {key} is not UTF-8")))?;
+    Ok(infer_csv_header(header_row))
+}
+
+/// [`import_table`] of CSV files with a header row, with the delimiter inferred from the
+/// first object under `key_prefix`. Fails with `ValidationError` before starting the import
+/// if the hash key or the sort key is not in the header. Returns the inferred header.
+#[cfg(feature = "s3")]
+#[allow(clippy::too_many_arguments)]
+pub async fn import_table_with_inferred_header(
+    client: &Client,
+    s3_client: &aws_utils_s3::aws_sdk_s3::Client,
+    bucket_name: impl Into<String>,
+    key_prefix: impl Into<String>,
+    table_name: impl Into<String>,
+    hash_key_name: impl Into<String>,
+    sort_key_name: Option<impl Into<String>>,
+    attribute_definitions: Vec<AttributeDefinition>,
+    table_type: TableType,
+) -> Result<CsvHeader, Error> {
+    let bucket_name = bucket_name.into();
+    let key_prefix = key_prefix.into();
+    let hash_key_name = hash_key_name.into();
+    let sort_key_name: Option<String> = sort_key_name.map(Into::into);
+
+    let header = peek_csv_header(s3_client, &bucket_name, &key_prefix).await?;
+    if let Some(missing) = std::iter::once(&hash_key_name)
+        .chain(&sort_key_name)
+        .find(|k| !header.header_list.contains(k))
+    {
+        return Err(Error::ValidationError(format!(
+            "key {missing} is not in the header {:?}",
+            header.header_list
+        )));
+    }
+
+    // ファイルにヘッダー行があるので header_list は渡さない
+    import_table(
+        client,
+        bucket_name,
+        key_prefix,
+        Some(header.delimiter.to_string()),
+        None,
+        table_name,
+        hash_key_name,
+        sort_key_name,
+        attribute_definitions,
+        table_type,
+    )
+    .await?;
+    Ok(header)
+}
+
+/// Result of [`export_table_to_s3_csv`], with the arguments of [`import_table`] to import it again.
+#[cfg(feature = "s3")]
+#[derive(Debug, Clone)]
+pub struct CsvExport {
+    pub hash_key_name: String,
+    pub sort_key_name: Option<String>,
+    pub attribute_definitions: Vec<AttributeDefinition>,
+    /// Header of every file.
+    pub header_list: Vec<String>,
+    pub object_keys: Vec<String>,
+    pub item_count: usize,
+}
+
+/// Scans the table and writes it to `{key_prefix}{n}.csv` objects of up to
+/// `max_rows_per_object` rows (100,000 by default).
+///
+/// Every file starts with the same header: the key attributes, then `attribute_names` in
+/// ascending order. Other attributes are not written. Without `attribute_names`, the table is
+/// scanned once more beforehand to collect all attribute names. The files can be imported again
+/// with [`import_table`], the same key prefix and `header_list = None`.
+#[cfg(feature = "s3")]
+pub async fn export_table_to_s3_csv(
+    client: &Client,
+    s3_client: &aws_utils_s3::aws_sdk_s3::Client,
+    table_name: impl Into<String>,
+    bucket_name: impl Into<String>,
+    key_prefix: impl Into<String>,
+    attribute_names: Option<Vec<String>>,
+    max_rows_per_object: Option<usize>,
+) -> Result<CsvExport, Error> {
+    use futures_util::TryStreamExt;
+
+    let table_name = table_name.into();
+    let bucket_name = bucket_name.into();
+    let key_prefix = key_prefix.into();
+    let max_rows_per_object = max_rows_per_object.unwrap_or(100_000).max(1);
+
+    let table = crate::table::describe_table(client, &table_name)
+        .await?
+        .table
+        .ok_or(Error::NotFound)?;
+    let mut hash_key_name = None;
+    let mut sort_key_name = None;
+    for key in table.key_schema() {
+        match key.key_type() {
+            KeyType::Hash => hash_key_name = Some(key.attribute_name().to_string()),
+            KeyType::Range => sort_key_name = Some(key.attribute_name().to_string()),
+            _ => {}
+        }
+    }
+    let hash_key_name =
+        hash_key_name.ok_or_else(|| Error::Invalid("hash key is missing".to_string()))?;
+    let key_names: Vec<String> = std::iter::once(hash_key_name.clone())
+        .chain(sort_key_name.clone())
+        .collect();
+    let attribute_definitions = table
+        .attribute_definitions()
+        .iter()
+        .filter(|d| key_names.iter().any(|k| k == d.attribute_name()))
+        .cloned()
+        .collect();
+
+    let scan = || {
+        crate::record::scan_stream(
+            client,
+            &table_name,
+            None::<String>,
+            None::<String>,
+            None,
+            None,
+            None,
+            None::<String>,
+            None::<Vec<String>>,
+        )
+    };
+    // ファイルごとにヘッダーが変わらないよう、列は書き出す前に決める
+    let attribute_names = match attribute_names {
+        Some(attribute_names) => attribute_names,
+        None => {
+            let mut attribute_names = BTreeSet::new();
+            let stream = scan();
+            futures_util::pin_mut!(stream);
+            while let Some(item) = stream.try_next().await? {
+                attribute_names.extend(item.into_keys());
+            }
+            attribute_names.into_iter().collect()
+        }
+    };
+    let header_list = csv_header(&key_names, attribute_names);
+
+    let stream = scan();
+    futures_util::pin_mut!(stream);
+
+    let mut object_keys = vec![];
+    let mut item_count = 0;
+    let mut items = Vec::with_capacity(max_rows_per_object);
+    loop {
+        let item = stream.try_next().await?;
+        let finished = item.is_none();
+        if let Some(item) = item {
+            items.push(item);
+            item_count += 1;
+        }
+        if items.len() >= max_rows_per_object || (finished && !items.is_empty()) {
+            let key = format!("{key_prefix}{:05}.csv", object_keys.len());
+            let body = items_to_csv_with_header(&header_list, &items);
+            aws_utils_s3::object::put_object(
+                s3_client,
+                &bucket_name,
+                &key,
+                body.into_bytes(),
+                Some("text/csv"),
+                None::<String>,
+            )
+            .await?;
+            object_keys.push(key);
+            items.clear();
+        }
+        if finished {
+            break;
+        }
+    }
+
+    Ok(CsvExport {
+        hash_key_name,
+        sort_key_name,
+        attribute_definitions,
+        header_list,
+        object_keys,
+        item_count,
+    })
+}
+
+/// CSV with a header row of `key_names` followed by the other attribute names in ascending order.
+pub fn items_to_csv(key_names: &[String], items: &[HashMap<String, AttributeValue>]) -> String {
+    let header_list = csv_header(
+        key_names,
+        items.iter().flat_map(|item| item.keys()).cloned(),
+    );
+    items_to_csv_with_header(&header_list, items)
+}
+
+/// CSV with a header row of `header_list`. Attributes not in `header_list` are not written.
+pub fn items_to_csv_with_header(
+    header_list: &[String],
+    items: &[HashMap<String, AttributeValue>],
+) -> String {
+    let mut csv = header_list
+        .iter()
+        .map(|name| escape_csv_field(name))
+        .collect::<Vec<_>>()
+        .join(",");
+    csv.push('\n');
+    for item in items {
+        let row = header_list
+            .iter()
+            .map(|name| {
+                item.get(name)
+                    .map(|v| escape_csv_field(&attribute_value_to_csv_field(v)))
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push_str(&row);
+        csv.push('\n');
+    }
+    csv
+}
+
+// key_names の後に残りの属性名を重複なく昇順で並べる
+fn csv_header(key_names: &[String], names: impl IntoIterator<Item = String>) -> Vec<String> {
+    let other_names = names
+        .into_iter()
+        .filter(|name| !key_names.contains(name))
+        .collect::<BTreeSet<_>>();
+    key_names.iter().cloned().chain(other_names).collect()
+}
+
+/// Scalars as they are, lists, maps and sets as JSON and binaries as base64.
+pub fn attribute_value_to_csv_field(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::S(s) | AttributeValue::N(s) => s.clone(),
+        AttributeValue::Bool(b) => b.to_string(),
+        AttributeValue::Null(_) => String::new(),
+        AttributeValue::B(b) => BASE64_STANDARD.encode(b.as_ref()),
+        _ => attribute_value_to_json(value).to_string(),
+    }
+}
+
+fn attribute_value_to_json(value: &AttributeValue) -> serde_json::Value {
+    match value {
+        AttributeValue::S(s) => serde_json::Value::String(s.clone()),
+        AttributeValue::N(n) => n
+            .parse::<serde_json::Number>()
+            .map(serde_json::Value::Number)
+            .unwrap_or_else(|_| serde_json::Value::String(n.clone())),
+        AttributeValue::Bool(b) => serde_json::Value::Bool(*b),
+        AttributeValue::B(b) => serde_json::Value::String(BASE64_STANDARD.encode(b.as_ref())),
+        AttributeValue::Ss(ss) => ss.iter().cloned().map(serde_json::Value::String).collect(),
+        AttributeValue::Ns(ns) => ns
+            .iter()
+            .map(|n| attribute_value_to_json(&AttributeValue::N(n.clone())))
+            .collect(),
+        AttributeValue::Bs(bs) => bs
+            .iter()
+            .map(|b| serde_json::Value::String(BASE64_STANDARD.encode(b.as_ref())))
+            .collect(),
+        AttributeValue::L(l) => l.iter().map(attribute_value_to_json).collect(),
+        AttributeValue::M(m) => serde_json::Value::Object(
+            m.iter()
+                .map(|(k, v)| (k.clone(), attribute_value_to_json(v)))
+                .collect(),
+        ),
+        _ => serde_json::Value::Null,
+    }
+}
+
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items_to_csv() {
+        let items = vec![
+            HashMap::from([
+                ("id".to_string(), AttributeValue::S("1".to_string())),
+                ("name".to_string(), AttributeValue::S("a,b".to_string())),
+                ("age".to_string(), AttributeValue::N("20".to_string())),
+            ]),
+            HashMap::from([
+                ("id".to_string(), AttributeValue::S("2".to_string())),
+                (
+                    "tags".to_string(),
+                    AttributeValue::Ss(vec!["x".to_string(), "y".to_string()]),
+                ),
+            ]),
+        ];
+
+        let csv = items_to_csv(&["id".to_string()], &items);
+
+        assert_eq!(
+            csv,
+            "id,age,name,tags\n1,20,\"a,b\",\n2,,,\"[\"\"x\"\",\"\"y\"\"]\"\n"
+        );
+    }
+
+    #[test]
+    fn test_items_to_csv_with_header() {
+        let header_list = csv_header(
+            &["id".to_string()],
+            ["name", "id", "age", "name"].map(ToString::to_string),
+        );
+        assert_eq!(header_list, vec!["id", "age", "name"]);
+
+        // 属性が欠けていても、ヘッダーに無い属性があっても列は変わらない
+        let items = vec![HashMap::from([
+            ("id".to_string(), AttributeValue::S("2".to_string())),
+            ("name".to_string(), AttributeValue::S("b".to_string())),
+            ("extra".to_string(), AttributeValue::Bool(true)),
+        ])];
+        assert_eq!(
+            items_to_csv_with_header(&header_list, &items),
+            "id,age,name\n2,,b\n"
+        );
+    }
+
+    #[test]
+    fn test_infer_csv_header() {
+        assert_eq!(
+            infer_csv_header("\u{feff}id,name,\"e\"\"mail\"\r\n"),
+            CsvHeader {
+                delimiter: ',',
+                header_list: vec!["id".to_string(), "name".to_string(), "e\"mail".to_string()],
+            }
+        );
+        assert_eq!(infer_csv_header("id\tname\tage").delimiter, '\t');
+        assert_eq!(infer_csv_header("id;name").delimiter, ';');
+        assert_eq!(infer_csv_header("a;b,c").delimiter, ',');
+        assert_eq!(infer_csv_header("id").header_list, vec!["id".to_string()]);
+    }
+
+    #[test]
+    fn test_attribute_value_to_csv_field() {
+        assert_eq!(
+            attribute_value_to_csv_field(&AttributeValue::Bool(true)),
+            "true"
+        );
+        assert_eq!(
+            attribute_value_to_csv_field(&AttributeValue::Null(true)),
+            ""
+        );
+        assert_eq!(
+            attribute_value_to_csv_field(&AttributeValue::M(HashMap::from([(
+                "n".to_string(),
+                AttributeValue::N("1.5".to_string())
+            )]))),
+            r#"{"n":1.5}"#
+        );
+    }
+}
@@ -1,3 +1,6 @@
+use aws_sdk_dynamodb::{error::ProvideErrorMetadata, operation::RequestId};
+pub use aws_utils_core::error::AwsUtilError;
+use aws_utils_core::error::{is_not_found_code, is_retryable_sdk_error};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,17 +14,42 @@ pub enum Error {
     #[error("ValidationError: {0}")]
     ValidationError(String),
 
+    #[cfg(feature = "s3")]
+    #[error(transparent)]
+    S3(#[from] aws_utils_s3::error::Error),
+
+    #[cfg(feature = "streams")]
+    #[error(transparent)]
+    Streams(#[from] Box<aws_sdk_dynamodbstreams::Error>),
+
+    #[error(transparent)]
+    SerdeDynamo(#[from] serde_dynamo::Error),
+
     #[error("NotFound")]
     NotFound,
 
     #[error("Invalid: {0}")]
     Invalid(String),
+
+    #[error("ReplicaFailed: {0}")]
+    ReplicaFailed(String),
+
+    #[error("MigrationLocked: {0} is being applied by another runner")]
+    MigrationLocked(String),
+
+    #[error("Timeout {0}")]
+    Timeout(#[from] tokio::time::error::Elapsed),
 }
 
 pub(crate) fn from_aws_sdk_error(e: impl Into<aws_sdk_dynamodb::Error>) -> Error {
     Error::AwsSdk(Box::new(e.into()))
 }
 
+#[cfg(feature = "streams")]
+pub(crate) fn from_streams_sdk_error(e: impl Into<aws_sdk_dynamodbstreams::Error>) -> Error {
+    Error::Streams(Box::new(e.into()))
+}
+
 impl Error {
     pub fn is_conditional_check_failed_exception(&self) -> bool {
         match self {
@@ -33,3 +61,42 @@ impl Error {
         }
     }
 }
+
+impl AwsUtilError for Error {
+    fn error_code(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.code(),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.error_code(),
+            #[cfg(feature = "streams")]
+            Error::Streams(e) => e.code(),
+            _ => None,
+        }
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.request_id(),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.aws_request_id(),
+            #[cfg(feature = "streams")]
+            Error::Streams(e) => e.request_id(),
+            _ => None,
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::AwsSdk(e) => is_retryable_sdk_error(e.as_ref()),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.is_retryable(),
+            #[cfg(feature = "streams")]
+            Error::Streams(e) => is_retryable_sdk_error(e.as_ref()),
+            _ => false,
+        }
+    }
+
+    fn is_not_found(&self) -> bool {
+        matches!(self, Error::NotFound) || self.error_code().is_some_and(is_not_found_code)
+    }
+}
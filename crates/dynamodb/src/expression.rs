@@ -0,0 +1,476 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde::{
+    Deserializer,
+    de::{DeserializeOwned, Error as _, Visitor, value::Error as ValueError},
+    forward_to_deserialize_any,
+};
+
+use crate::error::Error;
+
+// https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/ReservedWords.html
+const RESERVED_WORDS: &str = "
+ABORT ABSOLUTE ACTION ADD AFTER AGENT AGGREGATE ALL ALLOCATE ALTER ANALYZE AND ANY ARCHIVE
+ARE ARRAY AS ASC ASCII ASENSITIVE ASSERTION ASYMMETRIC AT ATOMIC ATTACH ATTRIBUTE AUTH
+AUTHORIZATION AUTHORIZE AUTO AVG BACK BACKUP BASE BATCH BEFORE BEGIN BETWEEN BIGINT BINARY
+BIT BLOB BLOCK BOOLEAN BOTH BREADTH BUCKET BULK BY BYTE CALL CALLED CALLING CAPACITY CASCADE
+CASCADED CASE CAST CATALOG CHAR CHARACTER CHECK CLASS CLOB CLOSE CLUSTER CLUSTERED
+CLUSTERING CLUSTERS COALESCE COLLATE COLLATION COLLECTION COLUMN COLUMNS COMBINE COMMENT
+COMMIT COMPACT COMPILE COMPRESS CONDITION CONFLICT CONNECT CONNECTION CONSISTENCY CONSISTENT
+CONSTRAINT CONSTRAINTS CONSTRUCTOR CONSUMED CONTINUE CONVERT COPY CORRESPONDING COUNT
+COUNTER CREATE CROSS CUBE CURRENT CURSOR CYCLE DATA DATABASE DATE DATETIME DAY DEALLOCATE
+DEC DECIMAL DECLARE DEFAULT DEFERRABLE DEFERRED DEFINE DEFINED DEFINITION DELETE DELIMITED
+DEPTH DEREF DESC DESCRIBE DESCRIPTOR DETACH DETERMINISTIC DIAGNOSTICS DIRECTORIES DISABLE
+DISCONNECT DISTINCT DISTRIBUTE DO DOMAIN DOUBLE DROP DUMP DURATION DYNAMIC EACH ELEMENT ELSE
+ELSEIF EMPTY ENABLE END EQUAL EQUALS ERROR ESCAPE ESCAPED EVAL EVALUATE EXCEEDED EXCEPT
+EXCEPTION EXCEPTIONS EXCLUSIVE EXEC EXECUTE EXISTS EXIT EXPLAIN EXPLODE EXPORT EXPRESSION
+EXTENDED EXTERNAL EXTRACT FAIL FALSE FAMILY FETCH FIELDS FILE FILTER FILTERING FINAL FINISH
+FIRST FIXED FLATTERN FLOAT FOR FORCE FOREIGN FORMAT FORWARD FOUND FREE FROM FULL FUNCTION
+FUNCTIONS GENERAL GENERATE GET GLOB GLOBAL GO GOTO GRANT GREATER GROUP GROUPING HANDLER HASH
+HAVE HAVING HEAP HIDDEN HOLD HOUR IDENTIFIED IDENTITY IF IGNORE IMMEDIATE IMPORT IN
+INCLUDING INCLUSIVE INCREMENT INCREMENTAL INDEX INDEXED INDEXES INDICATOR INFINITE INITIALLY
+INLINE INNER INNTER INOUT INPUT INSENSITIVE INSERT INSTEAD INT INTEGER INTERSECT INTERVAL
+INTO INVALIDATE IS ISOLATION ITEM ITEMS ITERATE JOIN KEY KEYS LAG LANGUAGE LARGE LAST
+LATERAL LEAD LEADING LEAVE LEFT LENGTH LESS LEVEL LIKE LIMIT LIMITED LINES LIST LOAD LOCAL
+LOCALTIME LOCALTIMESTAMP LOCATION LOCATOR LOCK LOCKS LOG LOGED LONG LOOP LOWER MAP MATCH
+MATERIALIZED MAX MAXLEN MEMBER MERGE METHOD METRICS MIN MINUS MINUTE MISSING MOD MODE
+MODIFIES MODIFY MODULE MONTH MULTI MULTISET NAME NAMES NATIONAL NATURAL NCHAR NCLOB NEW NEXT
+NO NONE NOT NULL NULLIF NUMBER NUMERIC OBJECT OF OFFLINE OFFSET OLD ON ONLINE ONLY OPAQUE
+OPEN OPERATOR OPTION OR ORDER ORDINALITY OTHER OTHERS OUT OUTER OUTPUT OVER OVERLAPS
+OVERRIDE OWNER PAD PARALLEL PARAMETER PARAMETERS PARTIAL PARTITION PARTITIONED PARTITIONS
+PATH PERCENT PERCENTILE PERMISSION PERMISSIONS PIPE PIPELINED PLAN POOL POSITION PRECISION
+PREPARE PRESERVE PRIMARY PRIOR PRIVATE PRIVILEGES PROCEDURE PROCESSED PROJECT PROJECTION
+PROPERTY PROVISIONING PUBLIC PUT QUERY QUIT QUORUM RAISE RANDOM RANGE RANK RAW READ READS
+REAL REBUILD RECORD RECURSIVE REDUCE REF REFERENCE REFERENCES REFERENCING REGEXP REGION
+REINDEX RELATIVE RELEASE REMAINDER RENAME REPEAT REPLACE REQUEST RESET RESIGNAL RESOURCE
+RESPONSE RESTORE RESTRICT RESULT RETURN RETURNING RETURNS REVERSE REVOKE RIGHT ROLE ROLES
+ROLLBACK ROLLUP ROUTINE ROW ROWS RULE RULES SAMPLE SATISFIES SAVE SAVEPOINT SCAN SCHEMA
+SCOPE SCROLL SEARCH SECOND SECTION SEGMENT SEGMENTS SELECT SELF SEMI SENSITIVE SEPARATE
+SEQUENCE SERIALIZABLE SESSION SET SETS SHARD SHARE SHARED SHORT SHOW SIGNAL SIMILAR SIZE
+SKEWED SMALLINT SNAPSHOT SOME SOURCE SPACE SPACES SPARSE SPECIFIC SPECIFICTYPE SPLIT SQL
+SQLCODE SQLERROR SQLEXCEPTION SQLSTATE SQLWARNING START STATE STATIC STATUS STORAGE STORE
+STORED STREAM STRING STRUCT STYLE SUB SUBMULTISET SUBPARTITION SUBSTRING SUBTYPE SUM SUPER
+SYMMETRIC SYNONYM SYSTEM TABLE TABLESAMPLE TEMP TEMPORARY TERMINATED TEXT THAN THEN
+THROUGHPUT TIME TIMESTAMP TIMEZONE TINYINT TO TOKEN TOTAL TOUCH TRAILING TRANSACTION
+TRANSFORM TRANSLATE TRANSLATION TREAT TRIGGER TRIM TRUE TRUNCATE TTL TUPLE TYPE UNDER UNDO
+UNION UNIQUE UNIT UNKNOWN UNLOGGED UNNEST UNPROCESSED UNSIGNED UNTIL UPDATE UPPER URL USAGE
+USE USER USERS USING UUID VACUUM VALUE VALUED VALUES VARCHAR VARIABLE VARIANCE VARINT
+VARYING VIEW VIEWS VIRTUAL VOID WAIT WHEN WHENEVER WHERE WHILE WINDOW WITH WITHIN WITHOUT
+WORK WRAPPED WRITE YEAR ZONE
+";
+
+/// An expression with its attribute name and value placeholders.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expression {
+    pub expression: String,
+    pub names: HashMap<String, String>,
+    pub values: HashMap<String, AttributeValue>,
+}
+
+/// Condition on the sort key of a key condition expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortKeyCondition {
+    Eq(AttributeValue),
+    Lt(AttributeValue),
+    Le(AttributeValue),
+    Gt(AttributeValue),
+    Ge(AttributeValue),
+    Between(AttributeValue, AttributeValue),
+    BeginsWith(AttributeValue),
+}
+
+/// Key condition expression for Query.
+///
+/// ```
+/// use aws_sdk_dynamodb::types::AttributeValue;
+/// use aws_utils_dynamodb::expression::{KeyCondition, SortKeyCondition};
+///
+/// let condition = KeyCondition::new("pk", AttributeValue::S("user#1".to_string())).sort_key(
+///     "sk",
+///     SortKeyCondition::BeginsWith(AttributeValue::S("order#".to_string())),
+/// );
+/// let expression = condition.to_expression();
+/// assert_eq!(expression.expression, "#k0 = :k0 AND begins_with(#k1, :k1)");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyCondition {
+    hash_key_name: String,
+    hash_key_value: AttributeValue,
+    sort_key: Option<(String, SortKeyCondition)>,
+}
+
+impl KeyCondition {
+    pub fn new(hash_key_name: impl Into<String>, hash_key_value: AttributeValue) -> Self {
+        Self {
+            hash_key_name: hash_key_name.into(),
+            hash_key_value,
+            sort_key: None,
+        }
+    }
+
+    pub fn sort_key(
+        mut self,
+        sort_key_name: impl Into<String>,
+        condition: SortKeyCondition,
+    ) -> Self {
+        self.sort_key = Some((sort_key_name.into(), condition));
+        self
+    }
+
+    pub(crate) fn hash_key_value(&self) -> &AttributeValue {
+        &self.hash_key_value
+    }
+
+    pub(crate) fn with_hash_key_value(&self, hash_key_value: AttributeValue) -> Self {
+        Self {
+            hash_key_value,
+            ..self.clone()
+        }
+    }
+
+    /// Builds the expression. Attribute names are always replaced by placeholders,
+    /// so reserved words can be used as key names.
+    pub fn to_expression(&self) -> Expression {
+        let mut names = HashMap::from([("#k0".to_string(), self.hash_key_name.clone())]);
+        let mut values = HashMap::from([(":k0".to_string(), self.hash_key_value.clone())]);
+        let mut expression = "#k0 = :k0".to_string();
+
+        if let Some((name, condition)) = &self.sort_key {
+            names.insert("#k1".to_string(), name.clone());
+            let sort_key_expression = match condition {
+                SortKeyCondition::Eq(v) => comparison("=", v, &mut values),
+                SortKeyCondition::Lt(v) => comparison("<", v, &mut values),
+                SortKeyCondition::Le(v) => comparison("<=", v, &mut values),
+                SortKeyCondition::Gt(v) => comparison(">", v, &mut values),
+                SortKeyCondition::Ge(v) => comparison(">=", v, &mut values),
+                SortKeyCondition::Between(low, high) => {
+                    values.insert(":k1".to_string(), low.clone());
+                    values.insert(":k2".to_string(), high.clone());
+                    "#k1 BETWEEN :k1 AND :k2".to_string()
+                }
+                SortKeyCondition::BeginsWith(v) => {
+                    values.insert(":k1".to_string(), v.clone());
+                    "begins_with(#k1, :k1)".to_string()
+                }
+            };
+            expression = format!("{expression} AND {sort_key_expression}");
+        }
+
+        Expression {
+            expression,
+            names,
+            values,
+        }
+    }
+}
+
+fn comparison(
+    operator: &str,
+    value: &AttributeValue,
+    values: &mut HashMap<String, AttributeValue>,
+) -> String {
+    values.insert(":k1".to_string(), value.clone());
+    format!("#k1 {operator} :k1")
+}
+
+/// Projection expression of `attributes` using `#p{n}` placeholders,
+/// merged into `names`.
+pub fn projection_expression(
+    attributes: &[impl AsRef<str>],
+    names: &mut HashMap<String, String>,
+) -> String {
+    attributes
+        .iter()
+        .enumerate()
+        .map(|(i, attribute)| {
+            let placeholder = format!("#p{i}");
+            names.insert(placeholder.clone(), attribute.as_ref().to_string());
+            placeholder
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Whether `word` is a DynamoDB reserved word (case insensitive), which cannot be used as
+/// an attribute name in an expression without a placeholder.
+pub fn is_reserved_word(word: &str) -> bool {
+    static WORDS: LazyLock<HashSet<&str>> =
+        LazyLock::new(|| RESERVED_WORDS.split_ascii_whitespace().collect());
+    WORDS.contains(word.to_ascii_uppercase().as_str())
+}
+
+/// Whether an attribute name needs a placeholder in an expression: it is a reserved word,
+/// or it does not start with a letter or contains characters other than letters, digits and
+/// `_` (e.g. `-`, `.`, `:`, spaces).
+pub fn needs_placeholder(name: &str) -> bool {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    !valid || is_reserved_word(name)
+}
+
+/// Expression attribute names for expressions written by hand. Attribute names which
+/// [need a placeholder](needs_placeholder) are replaced by `#n{i}` (the same placeholder for
+/// the same name) and the others are kept, so the expression stays readable.
+///
+/// ```
+/// use aws_utils_dynamodb::expression::AttributeNames;
+///
+/// let mut names = AttributeNames::new();
+/// let expression = format!(
+///     "{} = :status AND attribute_exists({})",
+///     names.path("order.status").unwrap(),
+///     names.path("order.shipping.zip_code").unwrap(),
+/// );
+/// assert_eq!(
+///     expression,
+///     "#n0.#n1 = :status AND attribute_exists(#n0.shipping.zip_code)"
+/// );
+/// assert_eq!(names.names()["#n1"], "status");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttributeNames {
+    names: HashMap<String, String>,
+}
+
+impl AttributeNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A top level attribute name, used as is even if it contains `.` or `[`.
+    pub fn name(&mut self, name: &str) -> String {
+        if !needs_placeholder(name) {
+            return name.to_string();
+        }
+        if let Some((placeholder, _)) = self.names.iter().find(|(_, n)| *n == name) {
+            return placeholder.clone();
+        }
+        let placeholder = format!("#n{}", self.names.len());
+        self.names.insert(placeholder.clone(), name.to_string());
+        placeholder
+    }
+
+    /// A document path, e.g. `order.items[0].status`: each element separated by `.` is
+    /// escaped like [`name`](Self::name) and list indexes are kept. Elements starting with `#`
+    /// are rejected, since a placeholder written by hand could be the same as a generated one;
+    /// use [`name`](Self::name) for an attribute whose name starts with `#`.
+    pub fn path(&mut self, path: &str) -> Result<String, Error> {
+        let invalid = || Error::ValidationError(format!("invalid attribute path: {path}"));
+        let mut escaped = Vec::new();
+        for element in path.split('.') {
+            let (name, indexes) = element.split_at(element.find('[').unwrap_or(element.len()));
+            if name.is_empty() || name.starts_with('#') || !is_list_indexes(indexes) {
+                return Err(invalid());
+            }
+            escaped.push(format!("{}{indexes}", self.name(name)));
+        }
+        Ok(escaped.join("."))
+    }
+
+    /// Placeholders and the attribute names they stand for.
+    pub fn names(&self) -> &HashMap<String, String> {
+        &self.names
+    }
+
+    /// For `set_expression_attribute_names`: None when no name needed a placeholder, since
+    /// DynamoDB rejects an empty ExpressionAttributeNames.
+    pub fn into_expression_attribute_names(self) -> Option<HashMap<String, String>> {
+        (!self.names.is_empty()).then_some(self.names)
+    }
+}
+
+/// `[0][1]` after the name of a path element.
+fn is_list_indexes(indexes: &str) -> bool {
+    let mut rest = indexes;
+    while !rest.is_empty() {
+        let Some((index, next)) = rest.strip_prefix('[').and_then(|rest| rest.split_once(']'))
+        else {
+            return false;
+        };
+        if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) {
+            return false;
+        }
+        rest = next;
+    }
+    true
+}
+
+/// Projection expression of document paths, with placeholders only for the names that need
+/// them (see [`AttributeNames`]).
+pub fn escaped_projection_expression(paths: &[impl AsRef<str>]) -> Result<Expression, Error> {
+    let mut names = AttributeNames::new();
+    let expression = paths
+        .iter()
+        .map(|path| names.path(path.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?
+        .join(", ");
+    Ok(Expression {
+        expression,
+        names: names.names,
+        values: HashMap::new(),
+    })
+}
+
+/// Attributes of the items deserialized into a type, used as the projection expression of
+/// [`query_typed_stream`](crate::record::query_typed_stream).
+///
+/// The default is the fields of the struct as deserialized by serde (with `rename` applied),
+/// so `impl Projection for Order {}` is enough for a plain struct.
+pub trait Projection: DeserializeOwned {
+    /// None fetches all attributes.
+    fn projected_attributes() -> Option<Vec<String>> {
+        struct_fields::<Self>().map(|fields| fields.iter().map(ToString::to_string).collect())
+    }
+}
+
+/// Field names the serde `Deserialize` implementation of a struct reads. None if `T` is not
+/// deserialized as a struct, e.g. a map or a struct with `#[serde(flatten)]`.
+pub fn struct_fields<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let mut fields = None;
+    // 構造体のフィールド名だけを受け取り、デシリアライズ自体は失敗させる
+    let _ = T::deserialize(FieldsDeserializer(&mut fields));
+    fields
+}
+
+struct FieldsDeserializer<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for FieldsDeserializer<'_> {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(ValueError::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = Some(fields);
+        Err(ValueError::custom("fields collected"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_struct_fields() {
+        #[derive(serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Order {
+            user_id: String,
+            #[serde(rename = "createdAt")]
+            created_at: i64,
+            #[serde(skip)]
+            cached: bool,
+        }
+        assert_eq!(
+            struct_fields::<Order>(),
+            Some(&["user_id", "createdAt"][..])
+        );
+        assert_eq!(struct_fields::<HashMap<String, String>>(), None);
+        assert_eq!(struct_fields::<String>(), None);
+    }
+
+    #[test]
+    fn test_key_condition() {
+        let expression =
+            KeyCondition::new("pk", AttributeValue::S("a".to_string())).to_expression();
+        assert_eq!(expression.expression, "#k0 = :k0");
+        assert_eq!(expression.names["#k0"], "pk");
+        assert_eq!(expression.values[":k0"], AttributeValue::S("a".to_string()));
+
+        let expression = KeyCondition::new("pk", AttributeValue::S("a".to_string()))
+            .sort_key(
+                "date",
+                SortKeyCondition::Between(
+                    AttributeValue::N("1".to_string()),
+                    AttributeValue::N("9".to_string()),
+                ),
+            )
+            .to_expression();
+        assert_eq!(
+            expression.expression,
+            "#k0 = :k0 AND #k1 BETWEEN :k1 AND :k2"
+        );
+        assert_eq!(expression.names["#k1"], "date");
+        assert_eq!(expression.values.len(), 3);
+    }
+
+    #[test]
+    fn test_needs_placeholder() {
+        assert!(is_reserved_word("status"));
+        assert!(is_reserved_word("Name"));
+        assert!(is_reserved_word("TTL"));
+        assert!(!is_reserved_word("user_id"));
+        assert!(!needs_placeholder("user_id"));
+        assert!(!needs_placeholder("createdAt"));
+        assert!(needs_placeholder("status"));
+        assert!(needs_placeholder("user-id"));
+        assert!(needs_placeholder("1st"));
+        assert!(needs_placeholder("_version"));
+        assert!(needs_placeholder("a b"));
+        assert!(needs_placeholder(""));
+    }
+
+    #[test]
+    fn test_attribute_names() {
+        let mut names = AttributeNames::new();
+        assert_eq!(names.name("user_id"), "user_id");
+        assert_eq!(names.name("a.b"), "#n0");
+        assert_eq!(
+            names.path("data.entries[0][1].status").unwrap(),
+            "#n1.entries[0][1].#n2"
+        );
+        assert_eq!(names.name("status"), "#n2");
+        assert_eq!(names.name("#n0"), "#n3");
+        assert_eq!(names.names().len(), 4);
+        assert_eq!(names.names()["#n0"], "a.b");
+        assert_eq!(names.names()["#n1"], "data");
+
+        for path in [
+            "", "a..b", "a[", "a[x]", "a[]", "[0]", "a[0]b", "data.#n0", "#n1",
+        ] {
+            assert!(names.path(path).is_err(), "{path}");
+        }
+        assert_eq!(
+            AttributeNames::new().into_expression_attribute_names(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_escaped_projection_expression() {
+        let expression = escaped_projection_expression(&["user_id", "name", "order-date"]).unwrap();
+        assert_eq!(expression.expression, "user_id, #n0, #n1");
+        assert_eq!(
+            expression.names,
+            HashMap::from([
+                ("#n0".to_string(), "name".to_string()),
+                ("#n1".to_string(), "order-date".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_projection_expression() {
+        let mut names = HashMap::new();
+        let expression = projection_expression(&["pk", "name"], &mut names);
+        assert_eq!(expression, "#p0, #p1");
+        assert_eq!(names["#p1"], "name");
+    }
+}
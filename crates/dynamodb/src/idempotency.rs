@@ -0,0 +1,352 @@
+use std::{collections::HashMap, time::Duration};
+
+use aws_sdk_dynamodb::{
+    Client,
+    types::{AttributeValue, ReturnValuesOnConditionCheckFailure},
+};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    record::{ConditionalOutcome, conditional_check_failed_item, get_item_raw},
+};
+
+pub const STATUS_ATTRIBUTE: &str = "status";
+pub const RESULT_ATTRIBUTE: &str = "result";
+/// Epoch seconds. Enable TTL of the table on this attribute to delete expired keys.
+pub const EXPIRES_AT_ATTRIBUTE: &str = "expires_at";
+/// Token of the request that acquired the key.
+pub const LEASE_TOKEN_ATTRIBUTE: &str = "lease_token";
+
+const IN_PROGRESS: &str = "IN_PROGRESS";
+const COMPLETED: &str = "COMPLETED";
+
+fn in_progress() -> AttributeValue {
+    AttributeValue::S(IN_PROGRESS.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyRecord<T> {
+    /// Acquired by a request that has not completed yet.
+    InProgress { expires_at: DateTime<Utc> },
+    Completed {
+        result: T,
+        expires_at: DateTime<Utc>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AcquireOutcome<T> {
+    /// The key is new or expired, and the request should be processed. Pass `token` to
+    /// `complete` or `release`, so they fail if the key expired and was acquired again.
+    Acquired { token: String },
+    /// Another request with the key is being processed.
+    InProgress { expires_at: DateTime<Utc> },
+    /// The key was processed, and its result should be returned again.
+    Completed(T),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReleaseOutcome {
+    Released,
+    /// The key is completed, and is kept so retries get its result.
+    Completed,
+    /// The key expired and was acquired by another request.
+    NotOwned,
+    /// The key does not exist, e.g. it expired and was deleted by TTL.
+    NotFound,
+}
+
+/// Idempotency keys of API requests in a table with a string hash key, so a retried
+/// request returns the result of the first one instead of being processed twice.
+///
+/// Call [`acquire`](Self::acquire) before processing, and then [`complete`](Self::complete)
+/// with the result, or [`release`](Self::release) on failure so the request can be retried,
+/// both with the token returned by `acquire`.
+/// Expired items are treated as absent, as TTL deletes them with a delay.
+#[derive(Debug, Clone)]
+pub struct IdempotencyStore {
+    client: Client,
+    table_name: String,
+    hash_key_name: String,
+}
+
+impl IdempotencyStore {
+    pub fn new(
+        client: Client,
+        table_name: impl Into<String>,
+        hash_key_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            client,
+            table_name: table_name.into(),
+            hash_key_name: hash_key_name.into(),
+        }
+    }
+
+    fn key(&self, key: &str) -> HashMap<String, AttributeValue> {
+        HashMap::from([(
+            self.hash_key_name.clone(),
+            AttributeValue::S(key.to_string()),
+        )])
+    }
+
+    /// Puts the key in progress for `ttl`, unless an unexpired item with the key exists.
+    /// `ttl` should be longer than the processing, and is also how long the result is kept.
+    pub async fn acquire<T>(&self, key: &str, ttl: Duration) -> Result<AcquireOutcome<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let now = Utc::now();
+        let ttl =
+            chrono::Duration::from_std(ttl).map_err(|e| Error::ValidationError(e.to_string()))?;
+        let expires_at = now + ttl;
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut item = self.key(key);
+        item.insert(STATUS_ATTRIBUTE.to_string(), in_progress());
+        item.insert(
+            LEASE_TOKEN_ATTRIBUTE.to_string(),
+            AttributeValue::S(token.clone()),
+        );
+        item.insert(
+            EXPIRES_AT_ATTRIBUTE.to_string(),
+            AttributeValue::N(expires_at.timestamp().to_string()),
+        );
+        let result = self
+            .client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item))
+            .condition_expression("attribute_not_exists(#c0) OR #c1 <= :c1")
+            .expression_attribute_names("#c0", &self.hash_key_name)
+            .expression_attribute_names("#c1", EXPIRES_AT_ATTRIBUTE)
+            .expression_attribute_values(":c1", AttributeValue::N(now.timestamp().to_string()))
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .send()
+            .await
+            .map_err(from_aws_sdk_error);
+        match result {
+            Ok(_) => Ok(AcquireOutcome::Acquired { token }),
+            Err(e) => match conditional_check_failed_item(&e) {
+                Some(Some(item)) => match parse_record(item)? {
+                    IdempotencyRecord::InProgress { expires_at } => {
+                        Ok(AcquireOutcome::InProgress { expires_at })
+                    }
+                    IdempotencyRecord::Completed { result, .. } => {
+                        Ok(AcquireOutcome::Completed(result))
+                    }
+                },
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Stores the result of the key acquired with `token`. Returns `NotFound` if the key does
+    /// not exist, e.g. it expired, and `Conflict` if it is already completed or was acquired
+    /// by another request after it expired.
+    pub async fn complete<T>(
+        &self,
+        key: &str,
+        token: &str,
+        result: &T,
+    ) -> Result<ConditionalOutcome<()>, Error>
+    where
+        T: Serialize,
+    {
+        let result = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .set_key(Some(self.key(key)))
+            .update_expression("SET #status = :status, #result = :result")
+            .condition_expression("#status = :in_progress AND #token = :token")
+            .expression_attribute_names("#status", STATUS_ATTRIBUTE)
+            .expression_attribute_names("#result", RESULT_ATTRIBUTE)
+            .expression_attribute_names("#token", LEASE_TOKEN_ATTRIBUTE)
+            .expression_attribute_values(":status", AttributeValue::S(COMPLETED.to_string()))
+            .expression_attribute_values(":result", serde_dynamo::to_attribute_value(result)?)
+            .expression_attribute_values(":in_progress", in_progress())
+            .expression_attribute_values(":token", AttributeValue::S(token.to_string()))
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .send()
+            .await
+            .map_err(from_aws_sdk_error);
+        match result {
+            Ok(_) => Ok(ConditionalOutcome::Written(())),
+            Err(e) => match conditional_check_failed_item(&e) {
+                Some(Some(_)) => Ok(ConditionalOutcome::Conflict),
+                Some(None) => Ok(ConditionalOutcome::NotFound),
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Deletes the key if it is in progress with `token`, so a failed request can be retried
+    /// before the key expires.
+    pub async fn release(&self, key: &str, token: &str) -> Result<ReleaseOutcome, Error> {
+        let result = self
+            .client
+            .delete_item()
+            .table_name(&self.table_name)
+            .set_key(Some(self.key(key)))
+            .condition_expression("#status = :in_progress AND #token = :token")
+            .expression_attribute_names("#status", STATUS_ATTRIBUTE)
+            .expression_attribute_names("#token", LEASE_TOKEN_ATTRIBUTE)
+            .expression_attribute_values(":in_progress", in_progress())
+            .expression_attribute_values(":token", AttributeValue::S(token.to_string()))
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .send()
+            .await
+            .map_err(from_aws_sdk_error);
+        match result {
+            Ok(_) => Ok(ReleaseOutcome::Released),
+            Err(e) => match conditional_check_failed_item(&e) {
+                Some(Some(item)) => {
+                    let status = item.get(STATUS_ATTRIBUTE).and_then(|v| v.as_s().ok());
+                    if status.is_some_and(|s| s == COMPLETED) {
+                        Ok(ReleaseOutcome::Completed)
+                    } else {
+                        Ok(ReleaseOutcome::NotOwned)
+                    }
+                }
+                Some(None) => Ok(ReleaseOutcome::NotFound),
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Record of the key, None if it does not exist or expired.
+    pub async fn get<T>(&self, key: &str) -> Result<Option<IdempotencyRecord<T>>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let output = get_item_raw(
+            &self.client,
+            &self.table_name,
+            self.key(key),
+            Some(true),
+            None,
+            None::<String>,
+            None::<Vec<String>>,
+        )
+        .await?;
+        let Some(item) = output.item else {
+            return Ok(None);
+        };
+        let record = parse_record(&item)?;
+        let expires_at = match &record {
+            IdempotencyRecord::InProgress { expires_at }
+            | IdempotencyRecord::Completed { expires_at, .. } => *expires_at,
+        };
+        Ok((expires_at > Utc::now()).then_some(record))
+    }
+}
+
+fn parse_record<T>(item: &HashMap<String, AttributeValue>) -> Result<IdempotencyRecord<T>, Error>
+where
+    T: DeserializeOwned,
+{
+    let expires_at = item
+        .get(EXPIRES_AT_ATTRIBUTE)
+        .and_then(|v| v.as_n().ok())
+        .and_then(|n| n.parse::<i64>().ok())
+        .and_then(|n| DateTime::from_timestamp(n, 0))
+        .ok_or_else(|| Error::Invalid(format!("{EXPIRES_AT_ATTRIBUTE} is not a timestamp")))?;
+    match item.get(STATUS_ATTRIBUTE).and_then(|v| v.as_s().ok()) {
+        Some(s) if s == IN_PROGRESS => Ok(IdempotencyRecord::InProgress { expires_at }),
+        Some(s) if s == COMPLETED => {
+            let result = item
+                .get(RESULT_ATTRIBUTE)
+                .cloned()
+                .unwrap_or(AttributeValue::Null(true));
+            Ok(IdempotencyRecord::Completed {
+                result: serde_dynamo::from_attribute_value(result)?,
+                expires_at,
+            })
+        }
+        status => Err(Error::Invalid(format!("unknown status: {status:?}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire_completed() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .with_status(400)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"__type":"com.amazonaws.dynamodb.v20120810#ConditionalCheckFailedException","message":"The conditional request failed","Item":{"id":{"S":"req-1"},"status":{"S":"COMPLETED"},"expires_at":{"N":"4102444800"},"result":{"M":{"order_id":{"S":"o-1"}}}}}"#,
+            )
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        let store = IdempotencyStore::new(client, "idempotency", "id");
+
+        let outcome = store
+            .acquire::<Value>("req-1", Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            AcquireOutcome::Completed(json!({"order_id": "o-1"}))
+        );
+        let outcome = store.complete("req-1", "token", &json!({})).await.unwrap();
+        assert_eq!(outcome, ConditionalOutcome::Conflict);
+        // 完了済みのキーは解放しない
+        let outcome = store.release("req-1", "token").await.unwrap();
+        assert_eq!(outcome, ReleaseOutcome::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_release_acquired_by_another_request() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.DeleteItem")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"ExpressionAttributeValues":{":token":{"S":"expired-token"}}}"#.to_string(),
+            ))
+            .with_status(400)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"__type":"com.amazonaws.dynamodb.v20120810#ConditionalCheckFailedException","message":"The conditional request failed","Item":{"id":{"S":"req-1"},"status":{"S":"IN_PROGRESS"},"lease_token":{"S":"new-token"},"expires_at":{"N":"4102444800"}}}"#,
+            )
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        let store = IdempotencyStore::new(client, "idempotency", "id");
+
+        let outcome = store.release("req-1", "expired-token").await.unwrap();
+        assert_eq!(outcome, ReleaseOutcome::NotOwned);
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_parse_record() {
+        let item = HashMap::from([
+            ("id".to_string(), AttributeValue::S("req-1".to_string())),
+            (
+                STATUS_ATTRIBUTE.to_string(),
+                AttributeValue::S(IN_PROGRESS.to_string()),
+            ),
+            (
+                EXPIRES_AT_ATTRIBUTE.to_string(),
+                AttributeValue::N("1700000000".to_string()),
+            ),
+        ]);
+        assert_eq!(
+            parse_record::<Value>(&item).unwrap(),
+            IdempotencyRecord::InProgress {
+                expires_at: DateTime::from_timestamp(1700000000, 0).unwrap()
+            }
+        );
+    }
+}
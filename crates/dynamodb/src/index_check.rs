@@ -0,0 +1,409 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+
+use aws_sdk_dynamodb::types::{AttributeValue, KeyType};
+
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    expression::{AttributeNames, Expression},
+    rate_limit::RateLimiter,
+    scanner::{RateLimitedScanner, ScanSummary},
+    table::describe_table,
+};
+
+type Item = HashMap<String, AttributeValue>;
+type ValidatorFn = Arc<dyn Fn(&Item) -> Vec<IndexViolation> + Send + Sync>;
+
+/// How to repair an attribute of an [`IndexViolation`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeFix {
+    /// Sets the attribute, e.g. to the value derived again from the source attributes.
+    Set(AttributeValue),
+    /// Removes the attribute, so the item leaves the sparse index.
+    Remove,
+}
+
+/// A derived attribute of an item that is missing or malformed for an index, reported by the
+/// validator of an [`IndexConsistencyCheck`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexViolation {
+    /// The attribute, e.g. `gsi1pk`.
+    pub attribute: String,
+    pub reason: String,
+    /// None if the violation can not be repaired automatically.
+    pub fix: Option<AttributeFix>,
+}
+
+impl IndexViolation {
+    pub fn new(attribute: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            attribute: attribute.into(),
+            reason: reason.into(),
+            fix: None,
+        }
+    }
+
+    pub fn fix(mut self, fix: AttributeFix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+/// Violations of an item found by [`IndexConsistencyCheck::run`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViolationReport {
+    /// Primary key of the item.
+    pub key: Item,
+    pub violations: Vec<IndexViolation>,
+    /// Whether the fixes of the violations were written. False without fixes, or if the item
+    /// was deleted or its fixed attributes were changed during the scan.
+    pub fixed: bool,
+}
+
+/// Scans a table and validates the derived (denormalized) attributes that its global
+/// secondary indexes are keyed on, e.g. `gsi1pk = "CUSTOMER#" + customer_id`, which are
+/// silently missing from the index when a writer forgot or malformed them.
+///
+/// The table is read with a [`RateLimitedScanner`], paced to its read capacity budget and
+/// resumable from its checkpoints. Violations are only reported by default. With
+/// [`fix`](Self::fix), the fixes returned by the validator are written with UpdateItem, rate
+/// limited, on the condition that the item still exists and that the fixed attributes still
+/// have the scanned values, so a concurrent write is not overwritten.
+#[derive(Clone)]
+pub struct IndexConsistencyCheck {
+    scanner: RateLimitedScanner,
+    validator: ValidatorFn,
+    updates_per_second: Option<u32>,
+}
+
+impl IndexConsistencyCheck {
+    /// Validates the items read by `scanner`. `validator` returns the violations of an item,
+    /// empty if it is consistent.
+    pub fn new(
+        scanner: RateLimitedScanner,
+        validator: impl Fn(&Item) -> Vec<IndexViolation> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            scanner,
+            validator: Arc::new(validator),
+            updates_per_second: None,
+        }
+    }
+
+    /// Writes the fixes with at most `updates_per_second` UpdateItem requests per second.
+    pub fn fix(mut self, updates_per_second: u32) -> Self {
+        self.updates_per_second = Some(updates_per_second.max(1));
+        self
+    }
+
+    /// Scans the table, passing the report of each item with violations to `on_report`.
+    /// Stops at the first error of the scan or of an update.
+    pub async fn run(&self, on_report: impl Fn(ViolationReport)) -> Result<ScanSummary, Error> {
+        let key_names = self.key_names().await?;
+        // 全セグメントで共有する更新枠
+        let limiter = self
+            .updates_per_second
+            .map(|updates_per_second| RateLimiter::with_burst(updates_per_second as f64, 1.0));
+        let (key_names, limiter, on_report) = (&key_names, limiter.as_ref(), &on_report);
+        self.scanner
+            .run(|items| async move {
+                for item in items {
+                    if let Some(report) = self.check_item(item, key_names, limiter).await? {
+                        on_report(report);
+                    }
+                }
+                Ok(())
+            })
+            .await
+    }
+
+    /// Names of the table keys, the hash key first.
+    async fn key_names(&self) -> Result<Vec<String>, Error> {
+        let table_name = self.scanner.table_name();
+        let output = describe_table(self.scanner.client(), table_name).await?;
+        let table = output.table().ok_or(Error::NotFound)?;
+        let mut key_schema = table.key_schema().to_vec();
+        if key_schema.is_empty() {
+            return Err(Error::Invalid(format!("{table_name} has no key schema")));
+        }
+        key_schema.sort_by_key(|k| *k.key_type() != KeyType::Hash);
+        Ok(key_schema.into_iter().map(|k| k.attribute_name).collect())
+    }
+
+    async fn check_item(
+        &self,
+        item: Item,
+        key_names: &[String],
+        limiter: Option<&RateLimiter>,
+    ) -> Result<Option<ViolationReport>, Error> {
+        let violations = (self.validator)(&item);
+        if violations.is_empty() {
+            return Ok(None);
+        }
+        let key: Item = key_names
+            .iter()
+            .filter_map(|name| item.get(name).map(|value| (name.clone(), value.clone())))
+            .collect();
+        let mut fixed = false;
+        if let Some(limiter) = limiter
+            && let Some((update, condition)) = fix_expression(&key_names[0], &item, &violations)
+        {
+            limiter.acquire(1.0).await;
+            let result = self
+                .scanner
+                .client()
+                .update_item()
+                .table_name(self.scanner.table_name())
+                .set_key(Some(key.clone()))
+                .update_expression(update.expression)
+                .condition_expression(condition)
+                .set_expression_attribute_names((!update.names.is_empty()).then_some(update.names))
+                .set_expression_attribute_values(
+                    (!update.values.is_empty()).then_some(update.values),
+                )
+                .send()
+                .await
+                .map_err(from_aws_sdk_error);
+            fixed = match result {
+                Ok(_) => true,
+                // スキャン後に削除・更新されたアイテムは直さない
+                Err(e) if e.is_conditional_check_failed_exception() => false,
+                Err(e) => return Err(e),
+            };
+        }
+        Ok(Some(ViolationReport {
+            key,
+            violations,
+            fixed,
+        }))
+    }
+}
+
+impl fmt::Debug for IndexConsistencyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IndexConsistencyCheck")
+            .field("scanner", &self.scanner)
+            .field("updates_per_second", &self.updates_per_second)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The update expression of the fixes and its condition: the item still exists, so an item
+/// deleted during the scan is not recreated, and each fixed attribute still has its value in
+/// `item`, or is still missing. None if no violation has a fix. Only the first fix of each
+/// attribute is used.
+fn fix_expression(
+    hash_key_name: &str,
+    item: &Item,
+    violations: &[IndexViolation],
+) -> Option<(Expression, String)> {
+    let mut names = AttributeNames::new();
+    let mut values = HashMap::new();
+    let mut set = Vec::new();
+    let mut remove = Vec::new();
+    let mut conditions = Vec::new();
+    let mut seen = HashSet::new();
+    for violation in violations {
+        let Some(fix) = &violation.fix else {
+            continue;
+        };
+        if !seen.insert(violation.attribute.as_str()) {
+            continue;
+        }
+        let name = names.name(&violation.attribute);
+        match fix {
+            AttributeFix::Set(value) => {
+                let placeholder = format!(":v{}", values.len());
+                set.push(format!("{name} = {placeholder}"));
+                values.insert(placeholder, value.clone());
+            }
+            AttributeFix::Remove => remove.push(name.clone()),
+        }
+        match item.get(&violation.attribute) {
+            Some(old) => {
+                let placeholder = format!(":v{}", values.len());
+                conditions.push(format!("{name} = {placeholder}"));
+                values.insert(placeholder, old.clone());
+            }
+            None => conditions.push(format!("attribute_not_exists({name})")),
+        }
+    }
+    if set.is_empty() && remove.is_empty() {
+        return None;
+    }
+    let mut clauses = Vec::new();
+    if !set.is_empty() {
+        clauses.push(format!("SET {}", set.join(", ")));
+    }
+    if !remove.is_empty() {
+        clauses.push(format!("REMOVE {}", remove.join(", ")));
+    }
+    conditions.insert(
+        0,
+        format!("attribute_exists({})", names.name(hash_key_name)),
+    );
+    Some((
+        Expression {
+            expression: clauses.join(" "),
+            names: names.names().clone(),
+            values,
+        },
+        conditions.join(" AND "),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use mockito::Matcher;
+
+    use super::*;
+
+    fn s(value: &str) -> AttributeValue {
+        AttributeValue::S(value.to_string())
+    }
+
+    #[test]
+    fn test_fix_expression() {
+        let item = HashMap::from([
+            ("pk".to_string(), s("o1")),
+            ("status".to_string(), s("open")),
+            ("gsi2pk".to_string(), s("STATUS#open")),
+        ]);
+        let violations = vec![
+            IndexViolation::new("gsi1pk", "missing").fix(AttributeFix::Set(s("CUSTOMER#1"))),
+            IndexViolation::new("status", "malformed").fix(AttributeFix::Set(s("OPEN"))),
+            IndexViolation::new("gsi1pk", "duplicate").fix(AttributeFix::Set(s("x"))),
+            IndexViolation::new("gsi2pk", "stale").fix(AttributeFix::Remove),
+            IndexViolation::new("gsi1sk", "unknown"),
+        ];
+        let (update, condition) = fix_expression("pk", &item, &violations).unwrap();
+        assert_eq!(
+            update.expression,
+            "SET gsi1pk = :v0, #n0 = :v1 REMOVE gsi2pk"
+        );
+        assert_eq!(update.names["#n0"], "status");
+        assert_eq!(update.values[":v0"], s("CUSTOMER#1"));
+        assert_eq!(update.values[":v1"], s("OPEN"));
+        assert_eq!(update.values[":v2"], s("open"));
+        assert_eq!(update.values[":v3"], s("STATUS#open"));
+        // スキャンした値のままの場合だけ直す
+        assert_eq!(
+            condition,
+            "attribute_exists(pk) AND attribute_not_exists(gsi1pk) AND #n0 = :v2 AND gsi2pk = :v3"
+        );
+
+        assert!(fix_expression("pk", &item, &[IndexViolation::new("gsi1sk", "unknown")]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_run_with_fix() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.DescribeTable")
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"Table":{"TableName":"orders","KeySchema":[
+                    {"AttributeName":"sk","KeyType":"RANGE"},
+                    {"AttributeName":"pk","KeyType":"HASH"}
+                ]}}"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.Scan")
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"Items":[
+                    {"pk":{"S":"o1"},"sk":{"S":"A"},"customer_id":{"S":"1"},"gsi1pk":{"S":"CUSTOMER#1"}},
+                    {"pk":{"S":"o2"},"sk":{"S":"A"},"customer_id":{"S":"2"}},
+                    {"pk":{"S":"o3"},"sk":{"S":"A"},"gsi1pk":{"S":"CUSTOMER#"}},
+                    {"pk":{"S":"o4"},"sk":{"S":"A"},"customer_id":{"S":"4"},"gsi1pk":{"S":"CUSTOMER#"}}
+                ],"Count":4}"#,
+            )
+            .create_async()
+            .await;
+        let update = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.UpdateItem")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"TableName":"orders","Key":{"pk":{"S":"o2"},"sk":{"S":"A"}},
+                    "UpdateExpression":"SET gsi1pk = :v0",
+                    "ConditionExpression":"attribute_exists(pk) AND attribute_not_exists(gsi1pk)",
+                    "ExpressionAttributeValues":{":v0":{"S":"CUSTOMER#2"}}}"#
+                    .to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        // o4 はスキャン後に更新された
+        let changed = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.UpdateItem")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"Key":{"pk":{"S":"o4"},"sk":{"S":"A"}},
+                    "ConditionExpression":"attribute_exists(pk) AND gsi1pk = :v1",
+                    "ExpressionAttributeValues":{":v0":{"S":"CUSTOMER#4"},":v1":{"S":"CUSTOMER#"}}}"#
+                    .to_string(),
+            ))
+            .with_status(400)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"__type":"com.amazonaws.dynamodb.v20120810#ConditionalCheckFailedException","message":"The conditional request failed"}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let scanner = RateLimitedScanner::new(client, "orders", 100.0);
+        let check = IndexConsistencyCheck::new(scanner, |item| {
+            let expected = match item.get("customer_id") {
+                Some(AttributeValue::S(id)) => format!("CUSTOMER#{id}"),
+                _ => {
+                    return vec![IndexViolation::new("customer_id", "missing")];
+                }
+            };
+            match item.get("gsi1pk") {
+                Some(AttributeValue::S(pk)) if *pk == expected => vec![],
+                _ => vec![
+                    IndexViolation::new("gsi1pk", "missing or stale")
+                        .fix(AttributeFix::Set(AttributeValue::S(expected))),
+                ],
+            }
+        })
+        .fix(100);
+        let reports = Mutex::new(Vec::new());
+        let summary = check
+            .run(|report| reports.lock().unwrap().push(report))
+            .await
+            .unwrap();
+        assert_eq!(summary.items, 4);
+
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0].key["pk"], s("o2"));
+        assert!(reports[0].fixed);
+        assert_eq!(
+            reports[1].key,
+            HashMap::from([("pk".to_string(), s("o3")), ("sk".to_string(), s("A")),])
+        );
+        assert_eq!(reports[1].violations[0].attribute, "customer_id");
+        assert!(!reports[1].fixed);
+        assert_eq!(reports[2].key["pk"], s("o4"));
+        assert!(!reports[2].fixed);
+        update.assert_async().await;
+        changed.assert_async().await;
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
+
+#[cfg(feature = "derive")]
+pub use aws_utils_dynamodb_derive::DynamoKey;
+
+use crate::{
+    error::Error,
+    expression::{KeyCondition, SortKeyCondition},
+};
+
+/// Value of a key attribute: a string (S), a number (N) or binary (B).
+pub trait KeyAttribute {
+    fn to_key_attribute(&self) -> AttributeValue;
+}
+
+impl KeyAttribute for str {
+    fn to_key_attribute(&self) -> AttributeValue {
+        AttributeValue::S(self.to_string())
+    }
+}
+
+impl KeyAttribute for String {
+    fn to_key_attribute(&self) -> AttributeValue {
+        AttributeValue::S(self.clone())
+    }
+}
+
+impl KeyAttribute for Blob {
+    fn to_key_attribute(&self) -> AttributeValue {
+        AttributeValue::B(self.clone())
+    }
+}
+
+impl<T: KeyAttribute + ?Sized> KeyAttribute for &T {
+    fn to_key_attribute(&self) -> AttributeValue {
+        (**self).to_key_attribute()
+    }
+}
+
+macro_rules! number_key_attribute {
+    ($($t:ty),*) => {
+        $(
+            impl KeyAttribute for $t {
+                fn to_key_attribute(&self) -> AttributeValue {
+                    AttributeValue::N(self.to_string())
+                }
+            }
+        )*
+    };
+}
+
+number_key_attribute!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize
+);
+
+/// Primary key of a table (or an index), usually implemented with `#[derive(DynamoKey)]`
+/// (feature `derive`):
+///
+/// ```ignore
+/// use aws_utils_dynamodb::key::DynamoKey;
+///
+/// #[derive(DynamoKey)]
+/// struct OrderKey {
+///     #[dynamo(hash_key)]
+///     user_id: String,
+///     #[dynamo(range_key, rename = "sk")]
+///     order_id: u64,
+/// }
+/// ```
+pub trait DynamoKey {
+    const HASH_KEY: &'static str;
+    const RANGE_KEY: Option<&'static str> = None;
+
+    fn hash_key_value(&self) -> AttributeValue;
+
+    fn range_key_value(&self) -> Option<AttributeValue> {
+        None
+    }
+
+    /// The key of GetItem, UpdateItem and DeleteItem.
+    fn key(&self) -> HashMap<String, AttributeValue> {
+        let mut key = HashMap::from([(Self::HASH_KEY.to_string(), self.hash_key_value())]);
+        if let (Some(name), Some(value)) = (Self::RANGE_KEY, self.range_key_value()) {
+            key.insert(name.to_string(), value);
+        }
+        key
+    }
+
+    /// Query of the items with the hash key of `self`.
+    fn hash_key_condition(&self) -> KeyCondition {
+        KeyCondition::new(Self::HASH_KEY, self.hash_key_value())
+    }
+
+    /// Query of the items with the hash key of `self` and the range key matching `condition`.
+    fn key_condition(&self, condition: SortKeyCondition) -> Result<KeyCondition, Error> {
+        let range_key = Self::RANGE_KEY.ok_or_else(|| {
+            Error::ValidationError(format!(
+                "{} has no range key",
+                std::any::type_name::<Self>()
+            ))
+        })?;
+        Ok(self.hash_key_condition().sort_key(range_key, condition))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct OrderKey {
+        user_id: String,
+        order_id: u64,
+    }
+
+    impl DynamoKey for OrderKey {
+        const HASH_KEY: &'static str = "user_id";
+        const RANGE_KEY: Option<&'static str> = Some("sk");
+
+        fn hash_key_value(&self) -> AttributeValue {
+            self.user_id.to_key_attribute()
+        }
+
+        fn range_key_value(&self) -> Option<AttributeValue> {
+            Some(self.order_id.to_key_attribute())
+        }
+    }
+
+    struct UserKey {
+        user_id: String,
+    }
+
+    impl DynamoKey for UserKey {
+        const HASH_KEY: &'static str = "user_id";
+
+        fn hash_key_value(&self) -> AttributeValue {
+            self.user_id.to_key_attribute()
+        }
+    }
+
+    #[test]
+    fn test_key() {
+        let key = OrderKey {
+            user_id: "u1".to_string(),
+            order_id: 42,
+        };
+        assert_eq!(
+            key.key(),
+            HashMap::from([
+                ("user_id".to_string(), AttributeValue::S("u1".to_string())),
+                ("sk".to_string(), AttributeValue::N("42".to_string())),
+            ])
+        );
+        let key = UserKey {
+            user_id: "u1".to_string(),
+        };
+        assert_eq!(
+            key.key(),
+            HashMap::from([("user_id".to_string(), AttributeValue::S("u1".to_string()))])
+        );
+    }
+
+    #[test]
+    fn test_key_condition() {
+        let key = OrderKey {
+            user_id: "u1".to_string(),
+            order_id: 42,
+        };
+        let expression = key
+            .key_condition(SortKeyCondition::Ge(42_u64.to_key_attribute()))
+            .unwrap()
+            .to_expression();
+        assert_eq!(expression.expression, "#k0 = :k0 AND #k1 >= :k1");
+        assert_eq!(expression.names["#k1"], "sk");
+        assert_eq!(
+            key.hash_key_condition().to_expression().expression,
+            "#k0 = :k0"
+        );
+
+        let key = UserKey {
+            user_id: "u1".to_string(),
+        };
+        assert!(matches!(
+            key.key_condition(SortKeyCondition::Eq(1_u64.to_key_attribute())),
+            Err(Error::ValidationError(_))
+        ));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive() {
+        #[derive(DynamoKey)]
+        struct DerivedKey {
+            #[dynamo(hash_key, rename = "PK")]
+            tenant_id: String,
+            #[dynamo(range_key)]
+            created_at: i64,
+            #[allow(dead_code)]
+            note: String,
+        }
+
+        let key = DerivedKey {
+            tenant_id: "t1".to_string(),
+            created_at: 1700000000,
+            note: String::new(),
+        };
+        assert_eq!(DerivedKey::HASH_KEY, "PK");
+        assert_eq!(DerivedKey::RANGE_KEY, Some("created_at"));
+        assert_eq!(
+            key.key(),
+            HashMap::from([
+                ("PK".to_string(), AttributeValue::S("t1".to_string())),
+                (
+                    "created_at".to_string(),
+                    AttributeValue::N("1700000000".to_string())
+                ),
+            ])
+        );
+    }
+}
@@ -7,12 +7,32 @@ use aws_config::{
 use aws_sdk_dynamodb::{Client, config::SharedInterceptor};
 
 pub mod cache;
+pub mod capacity;
+pub mod copy;
 pub mod csv;
 pub mod error;
+pub mod expression;
+pub mod idempotency;
+pub mod index_check;
+pub mod key;
+pub mod migrations;
+mod rate_limit;
 pub mod record;
+pub mod scanner;
+pub mod shard;
+#[cfg(feature = "streams")]
+pub mod streams;
 pub mod table;
 
 pub use aws_sdk_dynamodb;
+#[cfg(feature = "streams")]
+pub use aws_sdk_dynamodbstreams;
+#[cfg(feature = "metrics")]
+pub use aws_utils_core::metrics;
+
+// DynamoKey の derive が生成する ::aws_utils_dynamodb のパスをこのクレートのテストでも解決する
+#[cfg(test)]
+extern crate self as aws_utils_dynamodb;
 
 pub async fn make_client_with_timeout_default(endpoint_url: Option<String>) -> Client {
     make_client_with_timeout(
@@ -60,3 +80,25 @@ pub async fn make_client(
     }
     Client::from_conf(builder.build())
 }
+
+/// Client of DynamoDB Streams (`streams` feature), configured like [`make_client`].
+#[cfg(feature = "streams")]
+pub async fn make_streams_client(
+    endpoint_url: Option<String>,
+    timeout_config: Option<TimeoutConfig>,
+    interceptor: Option<SharedInterceptor>,
+) -> aws_sdk_dynamodbstreams::Client {
+    let mut config_loader = aws_config::defaults(BehaviorVersion::latest());
+    if let Some(timeout_config) = timeout_config {
+        config_loader = config_loader.timeout_config(timeout_config);
+    }
+    let config = config_loader.load().await;
+    let mut builder = aws_sdk_dynamodbstreams::config::Builder::from(&config);
+    if let Some(aws_endpoint_url) = endpoint_url {
+        builder = builder.endpoint_url(aws_endpoint_url)
+    }
+    if let Some(interceptor) = interceptor {
+        builder.push_interceptor(interceptor);
+    }
+    aws_sdk_dynamodbstreams::Client::from_conf(builder.build())
+}
@@ -0,0 +1,869 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    time::Duration,
+};
+
+use aws_sdk_dynamodb::{
+    Client,
+    types::{
+        AttributeDefinition, AttributeValue, CreateGlobalSecondaryIndexAction,
+        GlobalSecondaryIndex, GlobalSecondaryIndexUpdate, IndexStatus,
+        ReturnValuesOnConditionCheckFailure, ScalarAttributeType, TableDescription, TableStatus,
+    },
+};
+use chrono::Utc;
+use futures_util::TryStreamExt;
+
+use crate::{
+    error::{AwsUtilError, Error, from_aws_sdk_error},
+    rate_limit::RateLimiter,
+    record::{conditional_check_failed_item, scan_stream},
+    table::{TableType, create_table, describe_table},
+};
+
+/// Hash key of the metadata table, the ID of an applied migration.
+pub const ID_ATTRIBUTE: &str = "id";
+pub const DESCRIPTION_ATTRIBUTE: &str = "description";
+/// RFC 3339 time the migration was applied.
+pub const APPLIED_AT_ATTRIBUTE: &str = "applied_at";
+/// Token of the runner applying the migration, removed when it is applied.
+pub const LOCK_TOKEN_ATTRIBUTE: &str = "lock_token";
+/// Epoch seconds until which the lock of the runner is held.
+pub const LOCKED_UNTIL_ATTRIBUTE: &str = "locked_until";
+
+/// Update of an item by a [`MigrationStep::Backfill`].
+#[derive(Debug, Clone, Default)]
+pub struct BackfillUpdate {
+    pub key: HashMap<String, AttributeValue>,
+    pub update_expression: String,
+    pub condition_expression: Option<String>,
+    pub expression_attribute_names: Option<HashMap<String, String>>,
+    pub expression_attribute_values: Option<HashMap<String, AttributeValue>>,
+}
+
+type BackfillFn =
+    Box<dyn Fn(&HashMap<String, AttributeValue>) -> Option<BackfillUpdate> + Send + Sync>;
+
+pub enum MigrationStep {
+    /// Creates the table and waits until it is ACTIVE. Skipped if the table exists.
+    CreateTable {
+        table_name: String,
+        hash_key_name: String,
+        sort_key_name: Option<String>,
+        table_type: TableType,
+        attribute_definitions: Vec<AttributeDefinition>,
+        global_secondary_indexes: Option<Vec<GlobalSecondaryIndex>>,
+    },
+    /// Adds a global secondary index and waits until it is ACTIVE, including the
+    /// backfill of the index by DynamoDB. Skipped if the index exists.
+    AddGlobalSecondaryIndex {
+        table_name: String,
+        /// Definitions of the key attributes of the index.
+        attribute_definitions: Vec<AttributeDefinition>,
+        index: CreateGlobalSecondaryIndexAction,
+    },
+    /// Scans the table and updates the items for which `update` returns Some, at most
+    /// `items_per_second` updates per second. Make `update` return None for items already
+    /// updated, so an interrupted backfill can be run again.
+    Backfill {
+        table_name: String,
+        filter_expression: Option<String>,
+        expression_attribute_names: Option<HashMap<String, String>>,
+        expression_attribute_values: Option<HashMap<String, AttributeValue>>,
+        items_per_second: u32,
+        update: BackfillFn,
+    },
+}
+
+impl MigrationStep {
+    pub fn backfill(
+        table_name: impl Into<String>,
+        items_per_second: u32,
+        update: impl Fn(&HashMap<String, AttributeValue>) -> Option<BackfillUpdate>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        MigrationStep::Backfill {
+            table_name: table_name.into(),
+            filter_expression: None,
+            expression_attribute_names: None,
+            expression_attribute_values: None,
+            items_per_second,
+            update: Box::new(update),
+        }
+    }
+}
+
+impl fmt::Display for MigrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationStep::CreateTable { table_name, .. } => write!(f, "create table {table_name}"),
+            MigrationStep::AddGlobalSecondaryIndex {
+                table_name, index, ..
+            } => write!(f, "add index {} to {table_name}", index.index_name),
+            MigrationStep::Backfill { table_name, .. } => write!(f, "backfill {table_name}"),
+        }
+    }
+}
+
+impl fmt::Debug for MigrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MigrationStep({self})")
+    }
+}
+
+/// Migration applied once, in the order of the IDs, e.g. `0001_create_users`.
+#[derive(Debug)]
+pub struct Migration {
+    pub id: String,
+    pub description: String,
+    pub steps: Vec<MigrationStep>,
+}
+
+impl Migration {
+    pub fn new(id: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            description: description.into(),
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn step(mut self, step: MigrationStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+}
+
+/// Step run (or to be run in a dry run) by [`MigrationRunner::run`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepReport {
+    pub migration_id: String,
+    /// e.g. `backfill users`
+    pub description: String,
+    /// False if the table or the index already existed.
+    pub executed: bool,
+    /// Items updated by a backfill, or to be updated in a dry run.
+    pub updated_items: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub dry_run: bool,
+    /// Migrations applied by this run, or to be applied in a dry run.
+    pub applied: Vec<String>,
+    /// Migrations applied before.
+    pub skipped: Vec<String>,
+    pub steps: Vec<StepReport>,
+}
+
+/// Runs the migrations not yet recorded in a metadata table with the string hash key
+/// [`ID_ATTRIBUTE`], created on-demand by the first run.
+///
+/// Before the steps of a migration, the runner locks it with a conditional write of its item,
+/// so concurrent runners (e.g. of several deployments) do not run the steps twice: a runner
+/// finding the migration locked fails with `Error::MigrationLocked`, and one finding it applied
+/// skips it. A migration is recorded after all its steps succeeded, and unlocked if a step
+/// fails, so make the steps safe to run again after a failure.
+#[derive(Debug, Clone)]
+pub struct MigrationRunner {
+    client: Client,
+    metadata_table_name: String,
+    dry_run: bool,
+    timeout_duration: Duration,
+    check_duration: Duration,
+    lock_duration: Duration,
+}
+
+impl MigrationRunner {
+    pub fn new(client: Client, metadata_table_name: impl Into<String>) -> Self {
+        Self {
+            client,
+            metadata_table_name: metadata_table_name.into(),
+            dry_run: false,
+            timeout_duration: Duration::from_secs(3600),
+            check_duration: Duration::from_secs(5),
+            lock_duration: Duration::from_secs(24 * 3600),
+        }
+    }
+
+    /// Reports the steps without creating or updating anything. Backfills scan the
+    /// tables to count the items to update.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// How long to wait for a table or an index to be ACTIVE, 1 hour by default.
+    pub fn wait(mut self, timeout_duration: Duration, check_duration: Duration) -> Self {
+        self.timeout_duration = timeout_duration;
+        self.check_duration = check_duration;
+        self
+    }
+
+    /// How long the lock of a migration is held, 24 hours by default. A lock left by a runner
+    /// that crashed is taken over after it expires, so make it longer than the migrations.
+    pub fn lock_duration(mut self, lock_duration: Duration) -> Self {
+        self.lock_duration = lock_duration;
+        self
+    }
+
+    /// IDs of the applied migrations, empty if the metadata table does not exist.
+    pub async fn applied(&self) -> Result<Vec<String>, Error> {
+        let stream = scan_stream(
+            &self.client,
+            &self.metadata_table_name,
+            None::<String>,
+            None::<String>,
+            None,
+            None,
+            Some(true),
+            None::<String>,
+            None::<Vec<String>>,
+        );
+        let items: Vec<_> = match stream.try_collect().await {
+            Ok(items) => items,
+            Err(e) if e.is_not_found() => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        // ロック中のマイグレーションは適用済みに含めない
+        let mut ids: Vec<String> = items
+            .iter()
+            .filter(|item| item.contains_key(APPLIED_AT_ATTRIBUTE))
+            .filter_map(|item| item.get(ID_ATTRIBUTE)?.as_s().ok().cloned())
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Runs the migrations not applied yet, in order. The IDs must be in ascending order.
+    pub async fn run(&self, migrations: Vec<Migration>) -> Result<MigrationReport, Error> {
+        validate_order(&migrations)?;
+        let applied = self.applied().await?;
+        if !self.dry_run {
+            self.ensure_metadata_table().await?;
+        }
+        let mut report = MigrationReport {
+            dry_run: self.dry_run,
+            ..Default::default()
+        };
+        // ドライランで作成されるはずのテーブル
+        let mut planned_tables = HashSet::new();
+        for migration in migrations {
+            if applied.contains(&migration.id) {
+                report.skipped.push(migration.id);
+                continue;
+            }
+            let lock_token = if self.dry_run {
+                None
+            } else {
+                match self.lock(&migration.id).await? {
+                    Some(lock_token) => Some(lock_token),
+                    // 別のランナーが適用済み
+                    None => {
+                        report.skipped.push(migration.id);
+                        continue;
+                    }
+                }
+            };
+            for step in migration.steps {
+                let description = step.to_string();
+                let (executed, updated_items) = match self.run_step(step, &mut planned_tables).await
+                {
+                    Ok(result) => result,
+                    Err(e) => {
+                        if let Some(lock_token) = &lock_token {
+                            self.unlock(&migration.id, lock_token).await?;
+                        }
+                        return Err(e);
+                    }
+                };
+                report.steps.push(StepReport {
+                    migration_id: migration.id.clone(),
+                    description,
+                    executed,
+                    updated_items,
+                });
+            }
+            if let Some(lock_token) = &lock_token {
+                self.record(&migration.id, &migration.description, lock_token)
+                    .await?;
+            }
+            report.applied.push(migration.id);
+        }
+        Ok(report)
+    }
+
+    async fn ensure_metadata_table(&self) -> Result<(), Error> {
+        if self.find_table(&self.metadata_table_name).await?.is_some() {
+            return Ok(());
+        }
+        create_table(
+            &self.client,
+            &self.metadata_table_name,
+            ID_ATTRIBUTE,
+            None::<String>,
+            TableType::OnDemand,
+            vec![
+                AttributeDefinition::builder()
+                    .attribute_name(ID_ATTRIBUTE)
+                    .attribute_type(ScalarAttributeType::S)
+                    .build()?,
+            ],
+            None,
+        )
+        .await?;
+        self.wait_for_active(&self.metadata_table_name, None).await
+    }
+
+    fn metadata_key(&self, id: &str) -> HashMap<String, AttributeValue> {
+        HashMap::from([(ID_ATTRIBUTE.to_string(), AttributeValue::S(id.to_string()))])
+    }
+
+    /// Locks the migration, unless another runner holds an unexpired lock. Returns the lock
+    /// token, or None if the migration was applied by another runner.
+    async fn lock(&self, id: &str) -> Result<Option<String>, Error> {
+        let now = Utc::now();
+        let lock_duration = chrono::Duration::from_std(self.lock_duration)
+            .map_err(|e| Error::ValidationError(e.to_string()))?;
+        let lock_token = uuid::Uuid::new_v4().to_string();
+        let mut item = self.metadata_key(id);
+        item.insert(
+            LOCK_TOKEN_ATTRIBUTE.to_string(),
+            AttributeValue::S(lock_token.clone()),
+        );
+        item.insert(
+            LOCKED_UNTIL_ATTRIBUTE.to_string(),
+            AttributeValue::N((now + lock_duration).timestamp().to_string()),
+        );
+        let result = self
+            .client
+            .put_item()
+            .table_name(&self.metadata_table_name)
+            .set_item(Some(item))
+            .condition_expression(
+                "attribute_not_exists(#id) OR (attribute_not_exists(#applied_at) AND #locked_until < :now)",
+            )
+            .expression_attribute_names("#id", ID_ATTRIBUTE)
+            .expression_attribute_names("#applied_at", APPLIED_AT_ATTRIBUTE)
+            .expression_attribute_names("#locked_until", LOCKED_UNTIL_ATTRIBUTE)
+            .expression_attribute_values(":now", AttributeValue::N(now.timestamp().to_string()))
+            .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+            .send()
+            .await
+            .map_err(from_aws_sdk_error);
+        match result {
+            Ok(_) => Ok(Some(lock_token)),
+            Err(e) => match conditional_check_failed_item(&e) {
+                Some(Some(item)) if item.contains_key(APPLIED_AT_ATTRIBUTE) => Ok(None),
+                Some(_) => Err(Error::MigrationLocked(id.to_string())),
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Deletes the lock after a failed step, so the migration can be run again.
+    async fn unlock(&self, id: &str, lock_token: &str) -> Result<(), Error> {
+        let result = self
+            .client
+            .delete_item()
+            .table_name(&self.metadata_table_name)
+            .set_key(Some(self.metadata_key(id)))
+            .condition_expression("#lock_token = :lock_token")
+            .expression_attribute_names("#lock_token", LOCK_TOKEN_ATTRIBUTE)
+            .expression_attribute_values(":lock_token", AttributeValue::S(lock_token.to_string()))
+            .send()
+            .await
+            .map_err(from_aws_sdk_error);
+        match result {
+            Err(e) if !e.is_conditional_check_failed_exception() => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    async fn record(&self, id: &str, description: &str, lock_token: &str) -> Result<(), Error> {
+        // ロックが期限切れで別のランナーに取られていれば失敗させる
+        self.client
+            .update_item()
+            .table_name(&self.metadata_table_name)
+            .set_key(Some(self.metadata_key(id)))
+            .update_expression(
+                "SET #description = :description, #applied_at = :applied_at REMOVE #lock_token, #locked_until",
+            )
+            .condition_expression("#lock_token = :lock_token")
+            .expression_attribute_names("#description", DESCRIPTION_ATTRIBUTE)
+            .expression_attribute_names("#applied_at", APPLIED_AT_ATTRIBUTE)
+            .expression_attribute_names("#lock_token", LOCK_TOKEN_ATTRIBUTE)
+            .expression_attribute_names("#locked_until", LOCKED_UNTIL_ATTRIBUTE)
+            .expression_attribute_values(
+                ":description",
+                AttributeValue::S(description.to_string()),
+            )
+            .expression_attribute_values(
+                ":applied_at",
+                AttributeValue::S(Utc::now().to_rfc3339()),
+            )
+            .expression_attribute_values(":lock_token", AttributeValue::S(lock_token.to_string()))
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        Ok(())
+    }
+
+    /// Returns whether the step was executed and the items updated. In a dry run, the tables
+    /// to be created are added to `planned_tables`.
+    async fn run_step(
+        &self,
+        step: MigrationStep,
+        planned_tables: &mut HashSet<String>,
+    ) -> Result<(bool, usize), Error> {
+        match step {
+            MigrationStep::CreateTable {
+                table_name,
+                hash_key_name,
+                sort_key_name,
+                table_type,
+                attribute_definitions,
+                global_secondary_indexes,
+            } => {
+                if self.find_table(&table_name).await?.is_some() {
+                    return Ok((false, 0));
+                }
+                if !self.dry_run {
+                    create_table(
+                        &self.client,
+                        &table_name,
+                        hash_key_name,
+                        sort_key_name,
+                        table_type,
+                        attribute_definitions,
+                        global_secondary_indexes,
+                    )
+                    .await?;
+                    self.wait_for_active(&table_name, None).await?;
+                } else {
+                    planned_tables.insert(table_name);
+                }
+                Ok((true, 0))
+            }
+            MigrationStep::AddGlobalSecondaryIndex {
+                table_name,
+                attribute_definitions,
+                index,
+            } => {
+                // 前のステップで作成したテーブルはドライランでは存在しない
+                if let Some(table) = self.find_table(&table_name).await?
+                    && has_index(&table, &index.index_name)
+                {
+                    return Ok((false, 0));
+                }
+                if !self.dry_run {
+                    let index_name = index.index_name.clone();
+                    self.client
+                        .update_table()
+                        .table_name(&table_name)
+                        .set_attribute_definitions(Some(attribute_definitions))
+                        .global_secondary_index_updates(
+                            GlobalSecondaryIndexUpdate::builder().create(index).build(),
+                        )
+                        .send()
+                        .await
+                        .map_err(from_aws_sdk_error)?;
+                    self.wait_for_active(&table_name, Some(&index_name)).await?;
+                }
+                Ok((true, 0))
+            }
+            MigrationStep::Backfill {
+                table_name,
+                filter_expression,
+                expression_attribute_names,
+                expression_attribute_values,
+                items_per_second,
+                update,
+            } => {
+                // ドライランで作成されるはずのテーブルは空なので、スキャンしない
+                if planned_tables.contains(&table_name) {
+                    return Ok((true, 0));
+                }
+                let updated_items = self
+                    .backfill(
+                        &table_name,
+                        filter_expression,
+                        expression_attribute_names,
+                        expression_attribute_values,
+                        items_per_second,
+                        update,
+                    )
+                    .await?;
+                Ok((true, updated_items))
+            }
+        }
+    }
+
+    async fn backfill(
+        &self,
+        table_name: &str,
+        filter_expression: Option<String>,
+        expression_attribute_names: Option<HashMap<String, String>>,
+        expression_attribute_values: Option<HashMap<String, AttributeValue>>,
+        items_per_second: u32,
+        update: BackfillFn,
+    ) -> Result<usize, Error> {
+        let stream = scan_stream(
+            &self.client,
+            table_name,
+            None::<String>,
+            filter_expression,
+            expression_attribute_names,
+            expression_attribute_values,
+            None,
+            None::<String>,
+            None::<Vec<String>>,
+        );
+        futures_util::pin_mut!(stream);
+        // スキャンが遅れた後にまとめて更新しない
+        let limiter = RateLimiter::with_burst(items_per_second.max(1) as f64, 1.0);
+        let mut updated_items = 0;
+        while let Some(item) = stream.try_next().await? {
+            let Some(backfill) = update(&item) else {
+                continue;
+            };
+            updated_items += 1;
+            if self.dry_run {
+                continue;
+            }
+            limiter.acquire(1.0).await;
+            self.client
+                .update_item()
+                .table_name(table_name)
+                .set_key(Some(backfill.key))
+                .update_expression(backfill.update_expression)
+                .set_condition_expression(backfill.condition_expression)
+                .set_expression_attribute_names(backfill.expression_attribute_names)
+                .set_expression_attribute_values(backfill.expression_attribute_values)
+                .send()
+                .await
+                .map_err(from_aws_sdk_error)?;
+        }
+        Ok(updated_items)
+    }
+
+    async fn find_table(&self, table_name: &str) -> Result<Option<TableDescription>, Error> {
+        match describe_table(&self.client, table_name).await {
+            Ok(output) => Ok(output.table),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Waits until the table, and the index if any, are ACTIVE.
+    async fn wait_for_active(
+        &self,
+        table_name: &str,
+        index_name: Option<&str>,
+    ) -> Result<(), Error> {
+        tokio::time::timeout(self.timeout_duration, async {
+            loop {
+                if let Some(table) = self.find_table(table_name).await?
+                    && is_active(&table, index_name)
+                {
+                    return Ok(());
+                }
+                tokio::time::sleep(self.check_duration).await;
+            }
+        })
+        .await?
+    }
+}
+
+fn validate_order(migrations: &[Migration]) -> Result<(), Error> {
+    for pair in migrations.windows(2) {
+        if pair[0].id >= pair[1].id {
+            return Err(Error::ValidationError(format!(
+                "migration IDs must be in ascending order: {} >= {}",
+                pair[0].id, pair[1].id
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn has_index(table: &TableDescription, index_name: &str) -> bool {
+    table
+        .global_secondary_indexes()
+        .iter()
+        .any(|i| i.index_name() == Some(index_name))
+}
+
+fn is_active(table: &TableDescription, index_name: Option<&str>) -> bool {
+    if table.table_status() != Some(&TableStatus::Active) {
+        return false;
+    }
+    let Some(index_name) = index_name else {
+        return true;
+    };
+    table.global_secondary_indexes().iter().any(|i| {
+        i.index_name() == Some(index_name) && i.index_status() == Some(&IndexStatus::Active)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+
+    async fn mock_scan(
+        server: &mut mockito::Server,
+        table_name: &str,
+        body: &str,
+    ) -> mockito::Mock {
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.Scan")
+            .match_body(Matcher::PartialJsonString(format!(
+                r#"{{"TableName":"{table_name}"}}"#
+            )))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(body)
+            .create_async()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_run_backfill() {
+        let mut server = mockito::Server::new_async().await;
+        let metadata = mock_scan(
+            &mut server,
+            "migrations",
+            r#"{"Items":[
+                {"id":{"S":"0001_create_users"},"applied_at":{"S":"2026-01-01T00:00:00+00:00"}}
+            ],"Count":1}"#,
+        )
+        .await;
+        let users = mock_scan(
+            &mut server,
+            "users",
+            r#"{"Items":[
+                {"id":{"S":"1"},"email":{"S":"A@example.com"}},
+                {"id":{"S":"2"},"email":{"S":"b@example.com"},"email_lower":{"S":"b@example.com"}}
+            ],"Count":2}"#,
+        )
+        .await;
+        let describe = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.DescribeTable")
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(r#"{"Table":{"TableName":"migrations","TableStatus":"ACTIVE"}}"#)
+            .create_async()
+            .await;
+        let update = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.UpdateItem")
+            .match_body(Matcher::PartialJsonString(
+                r#"{
+                    "TableName":"users",
+                    "Key":{"id":{"S":"1"}},
+                    "ExpressionAttributeValues":{":v":{"S":"a@example.com"}}
+                }"#
+                .to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        let lock = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.PutItem")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"TableName":"migrations","Item":{"id":{"S":"0002_email_lower"}}}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        let record = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.UpdateItem")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"TableName":"migrations","Key":{"id":{"S":"0002_email_lower"}}}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let migrations =
+            vec![
+                Migration::new("0001_create_users", "create users"),
+                Migration::new("0002_email_lower", "add email_lower").step(
+                    MigrationStep::backfill("users", 100, |item| {
+                        if item.contains_key("email_lower") {
+                            return None;
+                        }
+                        let email = item.get("email")?.as_s().ok()?;
+                        Some(BackfillUpdate {
+                            key: HashMap::from([("id".to_string(), item.get("id")?.clone())]),
+                            update_expression: "SET email_lower = :v".to_string(),
+                            expression_attribute_values: Some(HashMap::from([(
+                                ":v".to_string(),
+                                AttributeValue::S(email.to_lowercase()),
+                            )])),
+                            ..Default::default()
+                        })
+                    }),
+                ),
+            ];
+        let report = MigrationRunner::new(client, "migrations")
+            .run(migrations)
+            .await
+            .unwrap();
+
+        assert_eq!(report.skipped, vec!["0001_create_users"]);
+        assert_eq!(report.applied, vec!["0002_email_lower"]);
+        assert_eq!(
+            report.steps,
+            vec![StepReport {
+                migration_id: "0002_email_lower".to_string(),
+                description: "backfill users".to_string(),
+                executed: true,
+                updated_items: 1,
+            }]
+        );
+        metadata.assert_async().await;
+        users.assert_async().await;
+        describe.assert_async().await;
+        update.assert_async().await;
+        lock.assert_async().await;
+        record.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_run_locked() {
+        let mut server = mockito::Server::new_async().await;
+        // ロック中のマイグレーションは適用済みではない
+        mock_scan(
+            &mut server,
+            "migrations",
+            r#"{"Items":[
+                {"id":{"S":"0001_email_lower"},"lock_token":{"S":"t"},"locked_until":{"N":"4102444800"}}
+            ],"Count":1}"#,
+        )
+        .await;
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.DescribeTable")
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(r#"{"Table":{"TableName":"migrations","TableStatus":"ACTIVE"}}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.PutItem")
+            .with_status(400)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"__type":"com.amazonaws.dynamodb.v20120810#ConditionalCheckFailedException","message":"The conditional request failed","Item":{"id":{"S":"0001_email_lower"},"lock_token":{"S":"t"},"locked_until":{"N":"4102444800"}}}"#,
+            )
+            .create_async()
+            .await;
+        let scan = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.Scan")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"TableName":"users"}"#.to_string(),
+            ))
+            .expect(0)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let migrations =
+            vec![
+                Migration::new("0001_email_lower", "add email_lower")
+                    .step(MigrationStep::backfill("users", 100, |_| None)),
+            ];
+        let result = MigrationRunner::new(client, "migrations")
+            .run(migrations)
+            .await;
+        assert!(
+            matches!(&result, Err(Error::MigrationLocked(id)) if id == "0001_email_lower"),
+            "{result:?}"
+        );
+        scan.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_backfill_of_new_table() {
+        let mut server = mockito::Server::new_async().await;
+        mock_scan(&mut server, "migrations", r#"{"Items":[],"Count":0}"#).await;
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.DescribeTable")
+            .with_status(400)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"__type":"com.amazonaws.dynamodb.v20120810#ResourceNotFoundException","message":"Requested resource not found"}"#,
+            )
+            .create_async()
+            .await;
+        let scan = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.Scan")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"TableName":"users"}"#.to_string(),
+            ))
+            .expect(0)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let migrations = vec![
+            Migration::new("0001_users", "create users")
+                .step(MigrationStep::CreateTable {
+                    table_name: "users".to_string(),
+                    hash_key_name: "id".to_string(),
+                    sort_key_name: None,
+                    table_type: TableType::OnDemand,
+                    attribute_definitions: vec![],
+                    global_secondary_indexes: None,
+                })
+                .step(MigrationStep::backfill("users", 100, |_| None)),
+        ];
+        let report = MigrationRunner::new(client, "migrations")
+            .dry_run(true)
+            .run(migrations)
+            .await
+            .unwrap();
+        assert_eq!(report.applied, vec!["0001_users"]);
+        assert_eq!(
+            report
+                .steps
+                .iter()
+                .map(|s| (s.description.as_str(), s.executed, s.updated_items))
+                .collect::<Vec<_>>(),
+            vec![("create table users", true, 0), ("backfill users", true, 0)]
+        );
+        scan.assert_async().await;
+    }
+
+    #[test]
+    fn test_validate_order() {
+        let migrations = vec![Migration::new("0002", ""), Migration::new("0001", "")];
+        assert!(validate_order(&migrations).is_err());
+        let migrations = vec![Migration::new("0001", ""), Migration::new("0002", "")];
+        assert!(validate_order(&migrations).is_ok());
+    }
+}
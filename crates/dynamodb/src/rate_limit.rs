@@ -0,0 +1,96 @@
+use std::{sync::Mutex, time::Duration};
+
+use tokio::time::Instant;
+
+/// Token bucket shared by the tasks of a scan, copy or backfill.
+///
+/// Units are taken up front, so the bucket may be overdrawn, e.g. by a scan page whose
+/// consumed capacity is only known after it is read; later callers then wait until it is
+/// paid back, in the order they asked.
+pub(crate) struct RateLimiter {
+    units_per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// At most one second of units is saved up.
+    pub(crate) fn new(units_per_second: f64) -> Self {
+        Self::with_burst(units_per_second, units_per_second)
+    }
+
+    /// At most `burst` units are saved up. Full, so the first callers start at once.
+    pub(crate) fn with_burst(units_per_second: f64, burst: f64) -> Self {
+        Self {
+            units_per_second,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes `units` and waits until the bucket is no longer overdrawn.
+    pub(crate) async fn acquire(&self, units: f64) {
+        let wait = self.reserve(units, Instant::now());
+        tokio::time::sleep(wait).await;
+    }
+
+    /// Takes `units` without waiting, e.g. the consumed capacity of a page already read.
+    pub(crate) fn take(&self, units: f64) {
+        self.reserve(units, Instant::now());
+    }
+
+    /// Takes `units` and returns how long until the bucket is paid back.
+    fn reserve(&self, units: f64, now: Instant) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.units_per_second).min(self.burst);
+        bucket.updated = bucket.updated.max(now);
+        bucket.tokens -= units;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.units_per_second)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve() {
+        let limiter = RateLimiter::new(10.0);
+        let start = limiter.bucket.lock().unwrap().updated;
+        assert_eq!(limiter.reserve(0.0, start), Duration::ZERO);
+
+        // 25 単位取ると 15 単位の借りを 1.5 秒で返す
+        assert_eq!(limiter.reserve(25.0, start), Duration::from_millis(1500));
+        assert_eq!(
+            limiter.reserve(0.0, start + Duration::from_millis(500)),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            limiter.reserve(0.0, start + Duration::from_secs(2)),
+            Duration::ZERO
+        );
+
+        // 貯められるのは1秒分まで
+        let later = start + Duration::from_secs(60);
+        assert_eq!(limiter.reserve(15.0, later), Duration::from_millis(500));
+
+        // 後から来た呼び出しは前の借りの分も待つ
+        let limiter = RateLimiter::with_burst(2.0, 1.0);
+        assert_eq!(limiter.reserve(1.0, start), Duration::ZERO);
+        assert_eq!(limiter.reserve(1.0, start), Duration::from_millis(500));
+        assert_eq!(limiter.reserve(1.0, start), Duration::from_secs(1));
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use aws_sdk_dynamodb::{
     Client,
@@ -6,12 +6,30 @@ use aws_sdk_dynamodb::{
         delete_item::DeleteItemOutput, get_item::GetItemOutput, put_item::PutItemOutput,
         update_item::UpdateItemOutput,
     },
-    types::{AttributeValue, ReturnValue},
+    types::{
+        AttributeValue, PutRequest, ReturnValue, ReturnValuesOnConditionCheckFailure, WriteRequest,
+    },
 };
 use aws_smithy_types_convert::stream::PaginationStreamExt;
+use aws_utils_core::retry::backoff_delay;
 use futures_util::{Stream, TryStreamExt};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    cache::CacheMap,
+    error::{Error, from_aws_sdk_error},
+    expression::{KeyCondition, Projection, projection_expression},
+    table::{IndexProjection, describe_index_projection},
+};
 
-use crate::error::{Error, from_aws_sdk_error};
+/// Max requests of BatchWriteItem.
+pub const MAX_BATCH_WRITE_ITEMS: usize = 25;
+/// Attempts of BatchWriteItem for the unprocessed items of a batch.
+const MAX_BATCH_WRITE_ATTEMPTS: u32 = 8;
+const BATCH_WRITE_BASE_DELAY: Duration = Duration::from_millis(50);
+
+/// Projections of secondary indexes by (table name, index name), for [`query_index`].
+pub type IndexProjectionCache = CacheMap<(String, String), IndexProjection>;
 
 pub async fn get_item_raw(
     client: &Client,
@@ -130,6 +148,186 @@ pub async fn delete_item(
         .map_err(from_aws_sdk_error)
 }
 
+/// Outcome of the conditional writes, with ConditionalCheckFailed mapped to the reason.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConditionalOutcome<T> {
+    Written(T),
+    /// An item with the key already exists.
+    AlreadyExists,
+    /// No item with the key exists.
+    NotFound,
+    /// The item exists but the condition (e.g. the version) did not match.
+    Conflict,
+}
+
+impl<T> ConditionalOutcome<T> {
+    pub fn is_written(&self) -> bool {
+        matches!(self, ConditionalOutcome::Written(_))
+    }
+
+    pub fn written(self) -> Option<T> {
+        match self {
+            ConditionalOutcome::Written(output) => Some(output),
+            _ => None,
+        }
+    }
+}
+
+/// The item in ConditionalCheckFailedException, when requested with
+/// `ReturnValuesOnConditionCheckFailure::AllOld`. None if it is another error.
+pub(crate) fn conditional_check_failed_item(
+    e: &Error,
+) -> Option<Option<&HashMap<String, AttributeValue>>> {
+    match e {
+        Error::AwsSdk(e) => match e.as_ref() {
+            aws_sdk_dynamodb::Error::ConditionalCheckFailedException(e) => Some(e.item()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// PutItem with `attribute_not_exists` on the hash key, so an existing item is never overwritten.
+pub async fn put_item_if_not_exists(
+    client: &Client,
+    table_name: impl Into<String>,
+    hash_key_name: impl Into<String>,
+    item: HashMap<String, AttributeValue>,
+) -> Result<ConditionalOutcome<PutItemOutput>, Error> {
+    let result = client
+        .put_item()
+        .table_name(table_name)
+        .set_item(Some(item))
+        .condition_expression("attribute_not_exists(#c0)")
+        .expression_attribute_names("#c0", hash_key_name)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error);
+    match result {
+        Ok(output) => Ok(ConditionalOutcome::Written(output)),
+        Err(e) if e.is_conditional_check_failed_exception() => {
+            Ok(ConditionalOutcome::AlreadyExists)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// UpdateItem only if the item exists, instead of creating a new item with the key
+/// as UpdateItem does by default. The placeholders `#c{n}` are reserved for the condition.
+#[allow(clippy::too_many_arguments)]
+pub async fn update_if_exists(
+    client: &Client,
+    table_name: impl Into<String>,
+    key: HashMap<String, AttributeValue>,
+    update_expression: impl Into<String>,
+    expression_attribute_names: Option<HashMap<String, String>>,
+    expression_attribute_values: Option<HashMap<String, AttributeValue>>,
+    return_values: Option<ReturnValue>,
+) -> Result<ConditionalOutcome<UpdateItemOutput>, Error> {
+    let mut names = expression_attribute_names.unwrap_or_default();
+    let mut key_names = key.keys().cloned().collect::<Vec<_>>();
+    key_names.sort();
+    let condition_expression = key_names
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            names.insert(format!("#c{i}"), name);
+            format!("attribute_exists(#c{i})")
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let result = update_item(
+        client,
+        table_name,
+        key,
+        update_expression,
+        Some(condition_expression),
+        Some(names),
+        expression_attribute_values,
+        return_values,
+    )
+    .await;
+    match result {
+        Ok(output) => Ok(ConditionalOutcome::Written(output)),
+        Err(e) if e.is_conditional_check_failed_exception() => Ok(ConditionalOutcome::NotFound),
+        Err(e) => Err(e),
+    }
+}
+
+/// DeleteItem only if the number attribute `version_attribute_name` equals `expected_version`,
+/// for optimistic locking. Returns `NotFound` if the item does not exist and `Conflict`
+/// if the version does not match.
+pub async fn delete_if_version(
+    client: &Client,
+    table_name: impl Into<String>,
+    key: HashMap<String, AttributeValue>,
+    version_attribute_name: impl Into<String>,
+    expected_version: i64,
+) -> Result<ConditionalOutcome<DeleteItemOutput>, Error> {
+    let result = client
+        .delete_item()
+        .table_name(table_name)
+        .set_key(Some(key))
+        .condition_expression("#c0 = :c0")
+        .expression_attribute_names("#c0", version_attribute_name)
+        .expression_attribute_values(":c0", AttributeValue::N(expected_version.to_string()))
+        // 失敗時に既存アイテムを返させて NotFound と Conflict を区別する
+        .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error);
+    match result {
+        Ok(output) => Ok(ConditionalOutcome::Written(output)),
+        Err(e) => match conditional_check_failed_item(&e) {
+            Some(Some(_)) => Ok(ConditionalOutcome::Conflict),
+            Some(None) => Ok(ConditionalOutcome::NotFound),
+            None => Err(e),
+        },
+    }
+}
+
+/// Puts up to [`MAX_BATCH_WRITE_ITEMS`] items with BatchWriteItem, retrying the unprocessed
+/// items with backoff (see [`backoff_delay`]). Returns `Invalid` if items are still unprocessed
+/// after 8 attempts.
+pub async fn batch_put_items(
+    client: &Client,
+    table_name: &str,
+    items: Vec<HashMap<String, AttributeValue>>,
+) -> Result<(), Error> {
+    let mut requests = items
+        .into_iter()
+        .map(|item| {
+            Ok(WriteRequest::builder()
+                .put_request(PutRequest::builder().set_item(Some(item)).build()?)
+                .build())
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let output = client
+            .batch_write_item()
+            .request_items(table_name, requests)
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        requests = output
+            .unprocessed_items
+            .and_then(|mut items| items.remove(table_name))
+            .unwrap_or_default();
+        if requests.is_empty() {
+            return Ok(());
+        }
+        if attempt >= MAX_BATCH_WRITE_ATTEMPTS {
+            return Err(Error::Invalid(format!(
+                "{} items of {table_name} are unprocessed after {attempt} attempts",
+                requests.len()
+            )));
+        }
+        tokio::time::sleep(backoff_delay(BATCH_WRITE_BASE_DELAY, attempt)).await;
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn scan_stream(
     client: &Client,
@@ -288,3 +486,197 @@ pub async fn query_all(
     }
     Ok(items)
 }
+
+/// Queries a secondary index and deserializes the items into `T`.
+///
+/// The projection expression is restricted to the attributes projected into the index,
+/// which are discovered with DescribeTable and kept in `cache`. This avoids the fetch
+/// from the base table that a projection of non-projected attributes would cause on a
+/// local secondary index. `T` should only have fields for the projected attributes.
+pub async fn query_index<T>(
+    client: &Client,
+    cache: &mut IndexProjectionCache,
+    table_name: impl Into<String>,
+    index_name: impl Into<String>,
+    key_condition: &KeyCondition,
+    consistent_read: Option<bool>,
+) -> Result<Vec<T>, Error>
+where
+    T: DeserializeOwned,
+{
+    let table_name = table_name.into();
+    let index_name = index_name.into();
+    let projection = cache
+        .get(
+            &(table_name.clone(), index_name.clone()),
+            |client, (table_name, index_name)| async move {
+                describe_index_projection(&client, table_name, &index_name).await
+            },
+            None,
+        )
+        .await?
+        .ok_or_else(|| Error::ValidationError(format!("index not found: {index_name}")))?;
+
+    let expression = key_condition.to_expression();
+    let mut names = expression.names;
+    let projection_expression = match &projection {
+        IndexProjection::All => None,
+        IndexProjection::Attributes(attributes) => {
+            Some(projection_expression(attributes, &mut names))
+        }
+    };
+    let items = query_all(
+        client,
+        table_name,
+        Some(index_name),
+        Some(expression.expression),
+        None::<String>,
+        Some(names),
+        Some(expression.values),
+        consistent_read,
+        projection_expression,
+        None::<Vec<String>>,
+    )
+    .await?;
+    items
+        .into_iter()
+        .map(|item| serde_dynamo::from_item(item).map_err(Error::from))
+        .collect()
+}
+
+/// Queries a table or an index and deserializes the items into `T`, fetching only the
+/// attributes of [`Projection::projected_attributes`].
+pub fn query_typed_stream<T>(
+    client: &Client,
+    table_name: impl Into<String>,
+    index_name: Option<impl Into<String>>,
+    key_condition: &KeyCondition,
+    consistent_read: Option<bool>,
+) -> impl Stream<Item = Result<T, Error>>
+where
+    T: Projection,
+{
+    let expression = key_condition.to_expression();
+    let mut names = expression.names;
+    let projection_expression = T::projected_attributes()
+        .filter(|attributes| !attributes.is_empty())
+        .map(|attributes| projection_expression(&attributes, &mut names));
+    query_stream(
+        client,
+        table_name,
+        index_name,
+        Some(expression.expression),
+        None::<String>,
+        Some(names),
+        Some(expression.values),
+        consistent_read,
+        projection_expression,
+        None::<Vec<String>>,
+    )
+    .and_then(|item| async move { serde_dynamo::from_item(item).map_err(Error::from) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn conditional_check_failed_client(body: &str) -> (mockito::ServerGuard, Client) {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .with_status(400)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(body)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        (server, client)
+    }
+
+    fn key() -> HashMap<String, AttributeValue> {
+        HashMap::from([("pk".to_string(), AttributeValue::S("a".to_string()))])
+    }
+
+    #[tokio::test]
+    async fn test_delete_if_version() {
+        let (_server, client) = conditional_check_failed_client(
+            r#"{"__type":"com.amazonaws.dynamodb.v20120810#ConditionalCheckFailedException","message":"The conditional request failed","Item":{"pk":{"S":"a"},"version":{"N":"2"}}}"#,
+        )
+        .await;
+        let outcome = delete_if_version(&client, "table", key(), "version", 1)
+            .await
+            .unwrap();
+        assert_eq!(outcome, ConditionalOutcome::Conflict);
+
+        let (_server, client) = conditional_check_failed_client(
+            r#"{"__type":"com.amazonaws.dynamodb.v20120810#ConditionalCheckFailedException","message":"The conditional request failed"}"#,
+        )
+        .await;
+        let outcome = delete_if_version(&client, "table", key(), "version", 1)
+            .await
+            .unwrap();
+        assert_eq!(outcome, ConditionalOutcome::NotFound);
+
+        let outcome = put_item_if_not_exists(&client, "table", "pk", key())
+            .await
+            .unwrap();
+        assert_eq!(outcome, ConditionalOutcome::AlreadyExists);
+    }
+
+    #[tokio::test]
+    async fn test_query_typed_stream() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Order {
+            order_id: String,
+            #[serde(rename = "totalAmount")]
+            total_amount: u64,
+        }
+        impl Projection for Order {}
+
+        let mut server = mockito::Server::new_async().await;
+        let query = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.Query")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r##"{
+                    "TableName": "orders",
+                    "KeyConditionExpression": "#k0 = :k0",
+                    "ProjectionExpression": "#p0, #p1",
+                    "ExpressionAttributeNames": {"#k0": "user_id", "#p0": "order_id", "#p1": "totalAmount"}
+                }"##
+                .to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"Items":[
+                    {"order_id":{"S":"o1"},"totalAmount":{"N":"300"}},
+                    {"order_id":{"S":"o2"},"totalAmount":{"N":"50"}}
+                ],"Count":2}"#,
+            )
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let condition = KeyCondition::new("user_id", AttributeValue::S("u1".to_string()));
+        let orders: Vec<Order> =
+            query_typed_stream(&client, "orders", None::<String>, &condition, None)
+                .try_collect()
+                .await
+                .unwrap();
+        assert_eq!(
+            orders,
+            vec![
+                Order {
+                    order_id: "o1".to_string(),
+                    total_amount: 300
+                },
+                Order {
+                    order_id: "o2".to_string(),
+                    total_amount: 50
+                },
+            ]
+        );
+        query.assert_async().await;
+    }
+}
@@ -0,0 +1,467 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use aws_sdk_dynamodb::{
+    Client,
+    primitives::Blob,
+    types::{AttributeValue, ReturnConsumedCapacity},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use futures_util::future::try_join_all;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    rate_limit::RateLimiter,
+};
+
+type Item = HashMap<String, AttributeValue>;
+type CheckpointFn = Arc<dyn Fn(&ScanCheckpoint) + Send + Sync>;
+
+/// Position of a segment in a [`ScanCheckpoint`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SegmentPosition {
+    NotStarted,
+    /// The pages up to this LastEvaluatedKey are handled.
+    After(Item),
+    Done,
+}
+
+/// Where each segment of a [`RateLimitedScanner`] is, to resume an interrupted scan.
+///
+/// Saved with [`to_json`](Self::to_json), e.g. to a file or an item, and restored with
+/// [`from_json`](Self::from_json).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanCheckpoint {
+    pub total_segments: i32,
+    pub segments: Vec<SegmentPosition>,
+}
+
+impl ScanCheckpoint {
+    pub fn new(total_segments: i32) -> Self {
+        Self {
+            total_segments,
+            segments: vec![SegmentPosition::NotStarted; total_segments.max(0) as usize],
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.segments.iter().all(|s| *s == SegmentPosition::Done)
+    }
+
+    /// `{"total_segments":2,"segments":[null,{"id":{"S":"42"}}]}`: null for not started,
+    /// "done", or the LastEvaluatedKey in DynamoDB JSON.
+    pub fn to_json(&self) -> String {
+        let segments = self
+            .segments
+            .iter()
+            .map(|s| match s {
+                SegmentPosition::NotStarted => Value::Null,
+                SegmentPosition::After(key) => key_to_json(key),
+                SegmentPosition::Done => json!("done"),
+            })
+            .collect::<Vec<_>>();
+        json!({"total_segments": self.total_segments, "segments": segments}).to_string()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let invalid = || Error::Invalid(format!("invalid scan checkpoint: {json}"));
+        let value: Value = serde_json::from_str(json).map_err(|_| invalid())?;
+        let total_segments = value["total_segments"]
+            .as_i64()
+            .and_then(|n| i32::try_from(n).ok())
+            .ok_or_else(invalid)?;
+        let segments = value["segments"]
+            .as_array()
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|s| match s {
+                Value::Null => Some(SegmentPosition::NotStarted),
+                Value::String(s) if s == "done" => Some(SegmentPosition::Done),
+                Value::Object(key) => key_from_json(key).map(SegmentPosition::After),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?;
+        if segments.len() != total_segments.max(0) as usize {
+            return Err(invalid());
+        }
+        Ok(Self {
+            total_segments,
+            segments,
+        })
+    }
+}
+
+/// Key attributes are S, N or B.
+fn key_to_json(key: &Item) -> Value {
+    key.iter()
+        .filter_map(|(name, value)| {
+            let value = match value {
+                AttributeValue::S(s) => json!({"S": s}),
+                AttributeValue::N(n) => json!({"N": n}),
+                AttributeValue::B(b) => json!({"B": BASE64_STANDARD.encode(b.as_ref())}),
+                _ => return None,
+            };
+            Some((name.clone(), value))
+        })
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+fn key_from_json(key: &Map<String, Value>) -> Option<Item> {
+    key.iter()
+        .map(|(name, value)| {
+            let (type_name, value) = value.as_object()?.iter().next()?;
+            let value = value.as_str()?;
+            let value = match type_name.as_str() {
+                "S" => AttributeValue::S(value.to_string()),
+                "N" => AttributeValue::N(value.to_string()),
+                "B" => AttributeValue::B(Blob::new(BASE64_STANDARD.decode(value).ok()?)),
+                _ => return None,
+            };
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+/// Pages and capacity read by [`RateLimitedScanner::run`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanSummary {
+    pub pages: usize,
+    pub items: usize,
+    /// Sum of the ConsumedCapacity of the pages, in read capacity units.
+    pub consumed_capacity: f64,
+}
+
+/// State shared by the segments of a run.
+struct ScanState {
+    /// Read capacity available for the next page.
+    limiter: RateLimiter,
+    checkpoint: Mutex<ScanCheckpoint>,
+    consumed_capacity: Mutex<f64>,
+    pages: AtomicUsize,
+    items: AtomicUsize,
+}
+
+/// Parallel scan paced to a read capacity budget, for backfills and exports that must not
+/// throttle production traffic on the same table.
+///
+/// Each page requests its ConsumedCapacity, and the next page of any segment waits until
+/// the budget is paid back. After each page is handled, the checkpoint moves past it, so an
+/// interrupted scan resumes with [`resume_from`](Self::resume_from) and handles each page
+/// at least once.
+#[derive(Clone)]
+pub struct RateLimitedScanner {
+    client: Client,
+    table_name: String,
+    read_capacity_per_second: f64,
+    total_segments: i32,
+    page_size: Option<i32>,
+    consistent_read: bool,
+    filter_expression: Option<String>,
+    expression_attribute_names: Option<HashMap<String, String>>,
+    expression_attribute_values: Option<HashMap<String, AttributeValue>>,
+    checkpoint: Option<ScanCheckpoint>,
+    on_checkpoint: Option<CheckpointFn>,
+}
+
+impl RateLimitedScanner {
+    /// 1 segment, eventually consistent reads. `read_capacity_per_second` is raised to at least 1.
+    pub fn new(
+        client: Client,
+        table_name: impl Into<String>,
+        read_capacity_per_second: f64,
+    ) -> Self {
+        Self {
+            client,
+            table_name: table_name.into(),
+            read_capacity_per_second: read_capacity_per_second.max(1.0),
+            total_segments: 1,
+            page_size: None,
+            consistent_read: false,
+            filter_expression: None,
+            expression_attribute_names: None,
+            expression_attribute_values: None,
+            checkpoint: None,
+            on_checkpoint: None,
+        }
+    }
+
+    /// Segments of the parallel scan, sharing the budget. Raised to at least 1.
+    pub fn total_segments(mut self, total_segments: i32) -> Self {
+        self.total_segments = total_segments.max(1);
+        self
+    }
+
+    /// Limit of each Scan request. Smaller pages keep the consumption of each request,
+    /// up to 1 MB (128 RCU eventually consistent), close to the budget.
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = Some(page_size.max(1));
+        self
+    }
+
+    /// Strongly consistent reads, consuming twice the capacity.
+    pub fn consistent_read(mut self, consistent_read: bool) -> Self {
+        self.consistent_read = consistent_read;
+        self
+    }
+
+    /// Filter of the items passed to the handler. Filtered items still consume capacity.
+    pub fn filter_expression(
+        mut self,
+        filter_expression: impl Into<String>,
+        expression_attribute_names: Option<HashMap<String, String>>,
+        expression_attribute_values: Option<HashMap<String, AttributeValue>>,
+    ) -> Self {
+        self.filter_expression = Some(filter_expression.into());
+        self.expression_attribute_names = expression_attribute_names;
+        self.expression_attribute_values = expression_attribute_values;
+        self
+    }
+
+    /// Continues a scan from a checkpoint with the same total segments.
+    pub fn resume_from(mut self, checkpoint: ScanCheckpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Called with the whole checkpoint after each page is handled, from any segment.
+    pub fn on_checkpoint(
+        mut self,
+        on_checkpoint: impl Fn(&ScanCheckpoint) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_checkpoint = Some(Arc::new(on_checkpoint));
+        self
+    }
+
+    pub(crate) fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub(crate) fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// Scans the table, passing the items of each page to `handler`. Stops at the first error
+    /// of a page or of the handler; the checkpoint does not move past that page.
+    pub async fn run<F, Fut>(&self, handler: F) -> Result<ScanSummary, Error>
+    where
+        F: Fn(Vec<Item>) -> Fut,
+        Fut: Future<Output = Result<(), Error>>,
+    {
+        let checkpoint = match &self.checkpoint {
+            Some(c) if c.total_segments != self.total_segments => {
+                return Err(Error::Invalid(format!(
+                    "checkpoint has {} segments, the scan {}",
+                    c.total_segments, self.total_segments
+                )));
+            }
+            Some(c) => c.clone(),
+            None => ScanCheckpoint::new(self.total_segments),
+        };
+        let state = ScanState {
+            limiter: RateLimiter::new(self.read_capacity_per_second),
+            checkpoint: Mutex::new(checkpoint),
+            consumed_capacity: Mutex::new(0.0),
+            pages: AtomicUsize::new(0),
+            items: AtomicUsize::new(0),
+        };
+        try_join_all(
+            (0..self.total_segments).map(|segment| self.scan_segment(&state, &handler, segment)),
+        )
+        .await?;
+        Ok(ScanSummary {
+            pages: state.pages.into_inner(),
+            items: state.items.into_inner(),
+            consumed_capacity: state
+                .consumed_capacity
+                .into_inner()
+                .unwrap_or_else(|e| e.into_inner()),
+        })
+    }
+
+    async fn scan_segment<F, Fut>(
+        &self,
+        state: &ScanState,
+        handler: &F,
+        segment: i32,
+    ) -> Result<(), Error>
+    where
+        F: Fn(Vec<Item>) -> Fut,
+        Fut: Future<Output = Result<(), Error>>,
+    {
+        let index = segment as usize;
+        let mut position = lock(&state.checkpoint).segments[index].clone();
+        loop {
+            let exclusive_start_key = match position {
+                SegmentPosition::Done => return Ok(()),
+                SegmentPosition::NotStarted => None,
+                SegmentPosition::After(key) => Some(key),
+            };
+            // 消費量はページを読むまで分からないので、前のページの借りを返すまで待つ
+            state.limiter.acquire(0.0).await;
+
+            let output = self
+                .client
+                .scan()
+                .table_name(&self.table_name)
+                .segment(segment)
+                .total_segments(self.total_segments)
+                .set_limit(self.page_size)
+                .consistent_read(self.consistent_read)
+                .set_filter_expression(self.filter_expression.clone())
+                .set_expression_attribute_names(self.expression_attribute_names.clone())
+                .set_expression_attribute_values(self.expression_attribute_values.clone())
+                .set_exclusive_start_key(exclusive_start_key)
+                .return_consumed_capacity(ReturnConsumedCapacity::Total)
+                .send()
+                .await
+                .map_err(from_aws_sdk_error)?;
+            let consumed = output
+                .consumed_capacity()
+                .and_then(|c| c.capacity_units())
+                .unwrap_or_default();
+            state.limiter.take(consumed);
+            *lock(&state.consumed_capacity) += consumed;
+
+            let items = output.items.unwrap_or_default();
+            let count = items.len();
+            handler(items).await?;
+            state.pages.fetch_add(1, Ordering::Relaxed);
+            state.items.fetch_add(count, Ordering::Relaxed);
+
+            position = match output.last_evaluated_key {
+                Some(key) => SegmentPosition::After(key),
+                None => SegmentPosition::Done,
+            };
+            let checkpoint = {
+                let mut checkpoint = lock(&state.checkpoint);
+                checkpoint.segments[index] = position.clone();
+                checkpoint.clone()
+            };
+            if let Some(on_checkpoint) = &self.on_checkpoint {
+                on_checkpoint(&checkpoint);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for RateLimitedScanner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimitedScanner")
+            .field("table_name", &self.table_name)
+            .field("read_capacity_per_second", &self.read_capacity_per_second)
+            .field("total_segments", &self.total_segments)
+            .field("page_size", &self.page_size)
+            .finish_non_exhaustive()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_json() {
+        let checkpoint = ScanCheckpoint {
+            total_segments: 3,
+            segments: vec![
+                SegmentPosition::NotStarted,
+                SegmentPosition::After(HashMap::from([
+                    ("pk".to_string(), AttributeValue::S("user#1".to_string())),
+                    ("sk".to_string(), AttributeValue::N("42".to_string())),
+                    (
+                        "bin".to_string(),
+                        AttributeValue::B(Blob::new(vec![0, 1, 2])),
+                    ),
+                ])),
+                SegmentPosition::Done,
+            ],
+        };
+        let json = checkpoint.to_json();
+        assert_eq!(ScanCheckpoint::from_json(&json).unwrap(), checkpoint);
+        assert!(!checkpoint.is_done());
+
+        assert!(ScanCheckpoint::from_json(r#"{"total_segments":2,"segments":[null]}"#).is_err());
+        assert!(
+            ScanCheckpoint::from_json(r#"{"total_segments":1,"segments":[{"pk":{"M":{}}}]}"#)
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resume_scan() {
+        let mut server = mockito::Server::new_async().await;
+        let scan = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.Scan")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"TableName":"users","Segment":0,"TotalSegments":1,"Limit":2,
+                    "ExclusiveStartKey":{"id":{"S":"2"}},"ReturnConsumedCapacity":"TOTAL"}"#
+                    .to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"Items":[{"id":{"S":"3"}},{"id":{"S":"4"}}],"Count":2,
+                    "ConsumedCapacity":{"TableName":"users","CapacityUnits":0.5}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        let checkpoints = Arc::new(Mutex::new(Vec::new()));
+        let handled = Mutex::new(Vec::new());
+
+        let scanner = {
+            let checkpoints = checkpoints.clone();
+            RateLimitedScanner::new(client, "users", 10.0)
+                .page_size(2)
+                .resume_from(ScanCheckpoint {
+                    total_segments: 1,
+                    segments: vec![SegmentPosition::After(HashMap::from([(
+                        "id".to_string(),
+                        AttributeValue::S("2".to_string()),
+                    )]))],
+                })
+                .on_checkpoint(move |c| checkpoints.lock().unwrap().push(c.clone()))
+        };
+        let summary = scanner
+            .run(|items| {
+                handled.lock().unwrap().extend(items);
+                async { Ok(()) }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            summary,
+            ScanSummary {
+                pages: 1,
+                items: 2,
+                consumed_capacity: 0.5,
+            }
+        );
+        assert_eq!(handled.lock().unwrap().len(), 2);
+        {
+            let checkpoints = checkpoints.lock().unwrap();
+            assert_eq!(checkpoints.len(), 1);
+            assert!(checkpoints[0].is_done());
+        }
+        scan.assert_async().await;
+    }
+}
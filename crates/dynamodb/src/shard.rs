@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::{Client, types::AttributeValue};
+use futures_util::future::try_join_all;
+
+use crate::{error::Error, expression::KeyCondition, record::query_all};
+
+pub const SHARD_SEPARATOR: char = '#';
+
+/// Shard in `0..shard_count` of `shard_source`, with a hash that is stable across builds.
+pub fn shard_of(shard_source: &str, shard_count: u32) -> u32 {
+    // FNV-1a (32 bit)
+    let hash = shard_source.bytes().fold(0x811c9dc5u32, |hash, b| {
+        (hash ^ b as u32).wrapping_mul(0x01000193)
+    });
+    hash % shard_count.max(1)
+}
+
+/// `key#shard`
+pub fn add_shard_suffix(key: &str, shard: u32) -> String {
+    format!("{key}{SHARD_SEPARATOR}{shard}")
+}
+
+/// Partition key with the shard calculated from `shard_source`, to spread the writes of a hot
+/// key over `shard_count` partitions. Calculating it from another attribute (e.g. the sort key)
+/// lets a single item be read without a fan-out.
+pub fn sharded_key(key: &str, shard_source: &str, shard_count: u32) -> String {
+    add_shard_suffix(key, shard_of(shard_source, shard_count))
+}
+
+/// Shard of a sharded key, None if it has no numeric suffix.
+pub fn shard_suffix(key: &str) -> Option<u32> {
+    let (_, shard) = key.rsplit_once(SHARD_SEPARATOR)?;
+    shard.parse().ok()
+}
+
+/// Key without the shard suffix, or the key as is if it has no numeric suffix.
+pub fn strip_shard_suffix(key: &str) -> &str {
+    match key.rsplit_once(SHARD_SEPARATOR) {
+        Some((base, shard)) if shard.parse::<u32>().is_ok() => base,
+        _ => key,
+    }
+}
+
+/// Runs [`query_all`] for each of the `shard_count` sharded keys of the hash key of
+/// `key_condition` concurrently, and returns the items in shard order.
+///
+/// The hash key value of `key_condition` is the key without suffix and must be a string.
+/// Items are not sorted across shards.
+pub async fn query_all_shards(
+    client: &Client,
+    table_name: impl Into<String>,
+    index_name: Option<impl Into<String>>,
+    key_condition: &KeyCondition,
+    shard_count: u32,
+    consistent_read: Option<bool>,
+) -> Result<Vec<HashMap<String, AttributeValue>>, Error> {
+    let table_name = table_name.into();
+    let index_name: Option<String> = index_name.map(Into::into);
+    let AttributeValue::S(key) = key_condition.hash_key_value() else {
+        return Err(Error::ValidationError(
+            "hash key of a sharded key must be a string".to_string(),
+        ));
+    };
+
+    let queries = (0..shard_count.max(1)).map(|shard| {
+        let expression = key_condition
+            .with_hash_key_value(AttributeValue::S(add_shard_suffix(key, shard)))
+            .to_expression();
+        query_all(
+            client,
+            &table_name,
+            index_name.as_ref(),
+            Some(expression.expression),
+            None::<String>,
+            Some(expression.names),
+            Some(expression.values),
+            consistent_read,
+            None::<String>,
+            None::<Vec<String>>,
+        )
+    });
+    let items = try_join_all(queries).await?;
+    Ok(items.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shard_suffix() {
+        assert_eq!(shard_of("a", u32::MAX), 3826002220);
+        assert_eq!(sharded_key("tenant-1", "order-1", 8), "tenant-1#5");
+        assert_eq!(sharded_key("tenant-1", "order-2", 8), "tenant-1#4");
+        assert_eq!(shard_of("order-1", 0), 0);
+
+        assert_eq!(shard_suffix("tenant-1#5"), Some(5));
+        assert_eq!(strip_shard_suffix("tenant-1#5"), "tenant-1");
+        assert_eq!(shard_suffix("user#abc"), None);
+        assert_eq!(strip_shard_suffix("user#abc"), "user#abc");
+        assert_eq!(strip_shard_suffix("user#abc#0"), "user#abc");
+    }
+}
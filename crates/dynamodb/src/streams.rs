@@ -0,0 +1,521 @@
+use std::{
+    collections::{HashMap, HashSet},
+    pin::Pin,
+    time::Duration,
+};
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodbstreams::{
+    Client,
+    types::{OperationType, Record, Shard, ShardIteratorType},
+};
+use futures_util::{
+    Stream, StreamExt, TryStreamExt, future,
+    stream::{self, SelectAll},
+};
+use serde::de::DeserializeOwned;
+
+use crate::error::{Error, from_streams_sdk_error};
+
+/// Shards of a stream, parents before their children.
+pub async fn list_shards(
+    client: &Client,
+    stream_arn: impl Into<String>,
+) -> Result<Vec<Shard>, Error> {
+    let stream_arn = stream_arn.into();
+    let mut shards = Vec::new();
+    let mut exclusive_start_shard_id = None;
+    loop {
+        let output = client
+            .describe_stream()
+            .stream_arn(&stream_arn)
+            .set_exclusive_start_shard_id(exclusive_start_shard_id)
+            .send()
+            .await
+            .map_err(from_streams_sdk_error)?;
+        let Some(description) = output.stream_description else {
+            return Err(Error::NotFound);
+        };
+        shards.extend(description.shards.unwrap_or_default());
+        exclusive_start_shard_id = description.last_evaluated_shard_id;
+        if exclusive_start_shard_id.is_none() {
+            return Ok(shards);
+        }
+    }
+}
+
+enum Position {
+    Start,
+    Iterator(String),
+    Closed,
+}
+
+struct ShardReader {
+    client: Client,
+    stream_arn: String,
+    shard_id: String,
+    iterator_type: ShardIteratorType,
+    poll_interval: Duration,
+    position: Position,
+    last_sequence_number: Option<String>,
+}
+
+impl ShardReader {
+    /// Next non-empty batch of records, None once the shard is closed and read.
+    async fn next_records(&mut self) -> Result<Option<Vec<Record>>, Error> {
+        loop {
+            let iterator = match std::mem::replace(&mut self.position, Position::Closed) {
+                Position::Start => self.shard_iterator().await?,
+                Position::Iterator(iterator) => iterator,
+                Position::Closed => return Ok(None),
+            };
+            let output = match self
+                .client
+                .get_records()
+                .shard_iterator(iterator)
+                .send()
+                .await
+            {
+                Ok(output) => output,
+                // イテレーターは 15 分で期限切れになるので、最後に読んだ位置から取り直す
+                Err(e)
+                    if e.as_service_error()
+                        .is_some_and(|e| e.is_expired_iterator_exception()) =>
+                {
+                    self.position = Position::Start;
+                    continue;
+                }
+                Err(e) => return Err(from_streams_sdk_error(e)),
+            };
+            self.position = match output.next_shard_iterator {
+                Some(iterator) => Position::Iterator(iterator),
+                None => Position::Closed,
+            };
+            let records = output.records.unwrap_or_default();
+            if let Some(sequence_number) = records
+                .last()
+                .and_then(|r| r.dynamodb())
+                .and_then(|r| r.sequence_number())
+            {
+                self.last_sequence_number = Some(sequence_number.to_string());
+            }
+            if !records.is_empty() {
+                return Ok(Some(records));
+            }
+            if matches!(self.position, Position::Iterator(_)) {
+                tokio::time::sleep(self.poll_interval).await;
+            }
+        }
+    }
+
+    async fn shard_iterator(&self) -> Result<String, Error> {
+        let (iterator_type, sequence_number) = match &self.last_sequence_number {
+            Some(sequence_number) => (
+                ShardIteratorType::AfterSequenceNumber,
+                Some(sequence_number.clone()),
+            ),
+            None => (self.iterator_type.clone(), None),
+        };
+        let output = self
+            .client
+            .get_shard_iterator()
+            .stream_arn(&self.stream_arn)
+            .shard_id(&self.shard_id)
+            .shard_iterator_type(iterator_type)
+            .set_sequence_number(sequence_number)
+            .send()
+            .await
+            .map_err(from_streams_sdk_error)?;
+        output
+            .shard_iterator
+            .ok_or_else(|| Error::Invalid("shard_iterator is None".to_string()))
+    }
+}
+
+/// Records of a shard from `iterator_type`, polling every `poll_interval` while the shard
+/// is open. Ends when the shard is closed and all of its records are read.
+pub fn shard_records_stream(
+    client: &Client,
+    stream_arn: impl Into<String>,
+    shard_id: impl Into<String>,
+    iterator_type: ShardIteratorType,
+    poll_interval: Duration,
+) -> impl Stream<Item = Result<Record, Error>> + Unpin + Send + 'static {
+    let reader = ShardReader {
+        client: client.clone(),
+        stream_arn: stream_arn.into(),
+        shard_id: shard_id.into(),
+        iterator_type,
+        poll_interval,
+        position: Position::Start,
+        last_sequence_number: None,
+    };
+    Box::pin(
+        stream::try_unfold(reader, |mut reader| async move {
+            let Some(records) = reader.next_records().await? else {
+                return Ok::<_, Error>(None);
+            };
+            Ok(Some((stream::iter(records.into_iter().map(Ok)), reader)))
+        })
+        .try_flatten(),
+    )
+}
+
+/// Records of a shard tagged with its id, followed by `(shard_id, None)` when the shard ends.
+type ShardEvents = Pin<Box<dyn Stream<Item = (String, Option<Result<Record, Error>>)> + Send>>;
+
+fn shard_events(
+    client: &Client,
+    stream_arn: &str,
+    shard_id: String,
+    iterator_type: ShardIteratorType,
+    poll_interval: Duration,
+) -> ShardEvents {
+    let records = shard_records_stream(
+        client,
+        stream_arn,
+        shard_id.clone(),
+        iterator_type,
+        poll_interval,
+    );
+    let end = stream::once(future::ready((shard_id.clone(), None)));
+    Box::pin(
+        records
+            .map(move |record| (shard_id.clone(), Some(record)))
+            .chain(end),
+    )
+}
+
+/// Shards to read first, and the children of each shard, read once the parent has ended.
+/// A shard whose parent is not listed (trimmed after 24 hours, or skipped) is read first.
+fn shard_lineage(shards: Vec<Shard>) -> (Vec<String>, HashMap<String, Vec<String>>) {
+    let shard_ids = shards
+        .iter()
+        .filter_map(|shard| shard.shard_id())
+        .map(ToString::to_string)
+        .collect::<HashSet<_>>();
+    let mut roots = vec![];
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    for shard in shards {
+        let Some(shard_id) = shard.shard_id else {
+            continue;
+        };
+        match shard.parent_shard_id.filter(|p| shard_ids.contains(p)) {
+            Some(parent_shard_id) => children.entry(parent_shard_id).or_default().push(shard_id),
+            None => roots.push(shard_id),
+        }
+    }
+    (roots, children)
+}
+
+struct LineageState {
+    client: Client,
+    stream_arn: String,
+    poll_interval: Duration,
+    children: HashMap<String, Vec<String>>,
+    /// Shards that ended with an error, whose children are not read.
+    failed: HashSet<String>,
+    active: SelectAll<ShardEvents>,
+}
+
+/// Records of all shards of the stream. A shard is read after its parent shard
+/// (`ParentShardId`) has been read to the end, and shards without each other as ancestors are
+/// read concurrently.
+///
+/// Records of an item are in order, as the shards of an item follow its lineage. Children are
+/// read from [`ShardIteratorType::TrimHorizon`]. Shards created after the call (e.g. when a
+/// shard is split) are not read, so a long running consumer should restart with
+/// [`ShardIteratorType::Latest`] when the stream ends. With `Latest`, closed shards are skipped.
+/// After an error in a shard its children are not read.
+pub async fn records_stream(
+    client: &Client,
+    stream_arn: impl Into<String>,
+    iterator_type: ShardIteratorType,
+    poll_interval: Duration,
+) -> Result<impl Stream<Item = Result<Record, Error>> + Unpin + Send + 'static, Error> {
+    let stream_arn = stream_arn.into();
+    let shards = list_shards(client, &stream_arn)
+        .await?
+        .into_iter()
+        .filter(|shard| {
+            iterator_type != ShardIteratorType::Latest
+                || shard
+                    .sequence_number_range()
+                    .is_none_or(|r| r.ending_sequence_number().is_none())
+        })
+        .collect();
+    let (roots, children) = shard_lineage(shards);
+    let active = roots
+        .into_iter()
+        .map(|shard_id| {
+            shard_events(
+                client,
+                &stream_arn,
+                shard_id,
+                iterator_type.clone(),
+                poll_interval,
+            )
+        })
+        .collect::<SelectAll<_>>();
+    let state = LineageState {
+        client: client.clone(),
+        stream_arn,
+        poll_interval,
+        children,
+        failed: HashSet::new(),
+        active,
+    };
+    Ok(Box::pin(stream::unfold(state, |mut state| async move {
+        loop {
+            let (shard_id, item) = state.active.next().await?;
+            match item {
+                Some(Ok(record)) => return Some((Ok(record), state)),
+                Some(Err(e)) => {
+                    state.failed.insert(shard_id);
+                    return Some((Err(e), state));
+                }
+                None if state.failed.remove(&shard_id) => {}
+                None => {
+                    // 親シャードを読み終えてから子シャードを読む
+                    for child in state.children.remove(&shard_id).unwrap_or_default() {
+                        let events = shard_events(
+                            &state.client,
+                            &state.stream_arn,
+                            child,
+                            ShardIteratorType::TrimHorizon,
+                            state.poll_interval,
+                        );
+                        state.active.push(events);
+                    }
+                }
+            }
+        }
+    })))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Insert,
+    Modify,
+    Remove,
+}
+
+/// Change of an item with its images deserialized with serde_dynamo.
+///
+/// `old` and `new` are None when the stream view type does not include them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<T> {
+    pub kind: ChangeKind,
+    pub keys: HashMap<String, AttributeValue>,
+    pub old: Option<T>,
+    pub new: Option<T>,
+    pub sequence_number: Option<String>,
+}
+
+impl<T> TryFrom<Record> for Change<T>
+where
+    T: DeserializeOwned,
+{
+    type Error = Error;
+
+    fn try_from(record: Record) -> Result<Self, Self::Error> {
+        let kind = match record.event_name {
+            Some(OperationType::Insert) => ChangeKind::Insert,
+            Some(OperationType::Modify) => ChangeKind::Modify,
+            Some(OperationType::Remove) => ChangeKind::Remove,
+            event_name => {
+                return Err(Error::Invalid(format!(
+                    "unknown event_name: {event_name:?}"
+                )));
+            }
+        };
+        let Some(stream_record) = record.dynamodb else {
+            return Err(Error::Invalid("dynamodb is None".to_string()));
+        };
+        let image = |image: Option<HashMap<_, _>>| {
+            image
+                .map(serde_dynamo::from_item::<_, T>)
+                .transpose()
+                .map_err(Error::from)
+        };
+        Ok(Change {
+            kind,
+            keys: serde_dynamo::Item::from(stream_record.keys.unwrap_or_default()).into(),
+            old: image(stream_record.old_image)?,
+            new: image(stream_record.new_image)?,
+            sequence_number: stream_record.sequence_number,
+        })
+    }
+}
+
+/// [`records_stream`] of [`Change`]s, so CDC consumers do not handle AttributeValues.
+pub async fn typed_changes<T>(
+    client: &Client,
+    stream_arn: impl Into<String>,
+    iterator_type: ShardIteratorType,
+    poll_interval: Duration,
+) -> Result<impl Stream<Item = Result<Change<T>, Error>> + Unpin + Send + 'static, Error>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let records = records_stream(client, stream_arn, iterator_type, poll_interval).await?;
+    Ok(records.map(|record| record.and_then(Change::try_from)))
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodbstreams::types::{AttributeValue as StreamAttributeValue, StreamRecord};
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn image(age: &str) -> HashMap<String, StreamAttributeValue> {
+        HashMap::from([
+            ("id".to_string(), StreamAttributeValue::S("u1".to_string())),
+            ("age".to_string(), StreamAttributeValue::N(age.to_string())),
+        ])
+    }
+
+    fn shard(shard_id: &str, parent_shard_id: Option<&str>) -> Shard {
+        Shard::builder()
+            .shard_id(shard_id)
+            .set_parent_shard_id(parent_shard_id.map(ToString::to_string))
+            .build()
+    }
+
+    #[test]
+    fn test_shard_lineage() {
+        let (roots, children) = shard_lineage(vec![
+            shard("c1", Some("b")),
+            shard("b", Some("a")),
+            shard("a", Some("trimmed")),
+            shard("c2", Some("b")),
+            shard("d", None),
+        ]);
+        assert_eq!(roots, vec!["a", "d"]);
+        assert_eq!(
+            children,
+            HashMap::from([
+                ("a".to_string(), vec!["b".to_string()]),
+                ("b".to_string(), vec!["c1".to_string(), "c2".to_string()]),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_records_stream_parent_first() {
+        let mut server = mockito::Server::new_async().await;
+        let target = |operation: &str| format!("DynamoDBStreams_20120810.{operation}");
+        // 子シャードが先に列挙されても親シャードから読む
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", target("DescribeStream").as_str())
+            .with_body(
+                r#"{"StreamDescription":{"Shards":[
+                    {"ShardId":"child","ParentShardId":"parent",
+                        "SequenceNumberRange":{"StartingSequenceNumber":"300"}},
+                    {"ShardId":"parent","SequenceNumberRange":
+                        {"StartingSequenceNumber":"100","EndingSequenceNumber":"200"}}
+                ]}}"#,
+            )
+            .create_async()
+            .await;
+        for shard_id in ["parent", "child"] {
+            server
+                .mock("POST", "/")
+                .match_header("x-amz-target", target("GetShardIterator").as_str())
+                .match_body(mockito::Matcher::PartialJsonString(format!(
+                    r#"{{"ShardId":"{shard_id}"}}"#
+                )))
+                .with_body(format!(r#"{{"ShardIterator":"it-{shard_id}"}}"#))
+                .create_async()
+                .await;
+        }
+        for (iterator, sequence_number) in [("it-parent", "100"), ("it-child", "300")] {
+            // NextShardIterator が無いのでシャードは閉じている
+            server
+                .mock("POST", "/")
+                .match_header("x-amz-target", target("GetRecords").as_str())
+                .match_body(mockito::Matcher::PartialJsonString(format!(
+                    r#"{{"ShardIterator":"{iterator}"}}"#
+                )))
+                .with_body(format!(
+                    r#"{{"Records":[{{"eventName":"INSERT","dynamodb":{{
+                        "Keys":{{"id":{{"S":"u1"}}}},"SequenceNumber":"{sequence_number}"
+                    }}}}]}}"#
+                ))
+                .create_async()
+                .await;
+        }
+        let client = crate::make_streams_client(Some(server.url()), None, None).await;
+
+        let records = records_stream(
+            &client,
+            "arn:aws:dynamodb:ap-northeast-1:123456789012:table/users/stream/2026",
+            ShardIteratorType::TrimHorizon,
+            Duration::from_millis(10),
+        )
+        .await
+        .unwrap()
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| r.dynamodb().and_then(|d| d.sequence_number()))
+                .collect::<Vec<_>>(),
+            vec![Some("100"), Some("300")]
+        );
+    }
+
+    #[test]
+    fn test_change_try_from() {
+        let record = Record::builder()
+            .event_name(OperationType::Modify)
+            .dynamodb(
+                StreamRecord::builder()
+                    .keys("id", StreamAttributeValue::S("u1".to_string()))
+                    .set_old_image(Some(image("20")))
+                    .set_new_image(Some(image("21")))
+                    .sequence_number("100")
+                    .build(),
+            )
+            .build();
+        let change = Change::<Value>::try_from(record).unwrap();
+        assert_eq!(change.kind, ChangeKind::Modify);
+        assert_eq!(change.keys["id"], AttributeValue::S("u1".to_string()));
+        assert_eq!(change.old.unwrap()["age"], 20);
+        assert_eq!(change.new, Some(json!({"id": "u1", "age": 21})));
+        assert_eq!(change.sequence_number.as_deref(), Some("100"));
+
+        // KEYS_ONLY のストリーム
+        let record = Record::builder()
+            .event_name(OperationType::Remove)
+            .dynamodb(
+                StreamRecord::builder()
+                    .keys("id", StreamAttributeValue::S("u1".to_string()))
+                    .build(),
+            )
+            .build();
+        let change = Change::<Value>::try_from(record).unwrap();
+        assert_eq!(change.kind, ChangeKind::Remove);
+        assert!(change.old.is_none() && change.new.is_none());
+
+        let record = Record::builder()
+            .event_name(OperationType::Insert)
+            .dynamodb(
+                StreamRecord::builder()
+                    .set_new_image(Some(HashMap::from([(
+                        "id".to_string(),
+                        StreamAttributeValue::S("u1".to_string()),
+                    )])))
+                    .build(),
+            )
+            .build();
+        assert!(matches!(
+            Change::<HashMap<String, u32>>::try_from(record),
+            Err(Error::SerdeDynamo(_))
+        ));
+    }
+}
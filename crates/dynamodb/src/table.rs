@@ -1,3 +1,5 @@
+use std::{collections::HashMap, time::Duration};
+
 use crate::error::{Error, from_aws_sdk_error};
 use aws_sdk_dynamodb::{
     Client,
@@ -5,7 +7,13 @@ use aws_sdk_dynamodb::{
         create_table::CreateTableOutput, delete_table::DeleteTableOutput,
         describe_table::DescribeTableOutput, update_table::UpdateTableOutput,
     },
-    types::{AttributeDefinition, BillingMode, KeySchemaElement, KeyType, ProvisionedThroughput},
+    types::{
+        AttributeDefinition, BillingMode, CreateReplicationGroupMemberAction,
+        DeleteReplicationGroupMemberAction, IndexStatus, KeySchemaElement, KeyType, Projection,
+        ProjectionType, ProvisionedThroughput, ReplicaDescription, ReplicaStatus,
+        ReplicationGroupUpdate, ScalarAttributeType, StreamViewType, TableDescription, TableStatus,
+        Tag,
+    },
 };
 use aws_smithy_types_convert::stream::PaginationStreamExt;
 use futures_util::{Stream, TryStreamExt};
@@ -66,10 +74,24 @@ pub async fn create_table(
     }
 }
 
+/// Deletes a table. With `force`, deletion protection of the table is disabled first,
+/// otherwise deleting a protected table fails.
 pub async fn delete_table(
     client: &Client,
     table_name: impl Into<String>,
+    force: bool,
 ) -> Result<DeleteTableOutput, Error> {
+    let table_name = table_name.into();
+    if force {
+        let res = describe_table(client, &table_name).await?;
+        if res
+            .table()
+            .and_then(|t| t.deletion_protection_enabled())
+            .unwrap_or_default()
+        {
+            set_deletion_protection(client, &table_name, false).await?;
+        }
+    }
     client
         .delete_table()
         .table_name(table_name)
@@ -92,7 +114,7 @@ pub async fn delete_tables(client: &Client) -> Result<(), Error> {
     let stream = list_tables_stream(client);
     futures_util::pin_mut!(stream);
     while let Some(table_name) = stream.try_next().await? {
-        delete_table(client, table_name).await?;
+        delete_table(client, table_name, false).await?;
     }
     Ok(())
 }
@@ -109,6 +131,196 @@ pub async fn describe_table(
         .map_err(from_aws_sdk_error)
 }
 
+/// Attributes projected into a secondary index.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexProjection {
+    All,
+    /// Table keys, index keys and the included non-key attributes.
+    Attributes(Vec<String>),
+}
+
+/// Finds the projection of a global or local secondary index.
+/// Returns None if the index does not exist.
+pub async fn describe_index_projection(
+    client: &Client,
+    table_name: impl Into<String>,
+    index_name: &str,
+) -> Result<Option<IndexProjection>, Error> {
+    let res = describe_table(client, table_name).await?;
+    let Some(table) = res.table() else {
+        return Err(Error::NotFound);
+    };
+    let index = table
+        .global_secondary_indexes()
+        .iter()
+        .find(|i| i.index_name() == Some(index_name))
+        .map(|i| (i.key_schema(), i.projection()))
+        .or_else(|| {
+            table
+                .local_secondary_indexes()
+                .iter()
+                .find(|i| i.index_name() == Some(index_name))
+                .map(|i| (i.key_schema(), i.projection()))
+        });
+    Ok(index.map(|(index_key_schema, projection)| {
+        index_projection(table.key_schema(), index_key_schema, projection)
+    }))
+}
+
+fn index_projection(
+    table_key_schema: &[KeySchemaElement],
+    index_key_schema: &[KeySchemaElement],
+    projection: Option<&Projection>,
+) -> IndexProjection {
+    let projection_type = projection.and_then(|p| p.projection_type());
+    if projection_type.is_none_or(|t| *t == ProjectionType::All) {
+        return IndexProjection::All;
+    }
+    let mut attributes: Vec<String> = vec![];
+    let keys = table_key_schema.iter().chain(index_key_schema);
+    let non_key_attributes = projection
+        .map(|p| p.non_key_attributes())
+        .unwrap_or_default();
+    for name in keys
+        .map(|k| k.attribute_name())
+        .chain(non_key_attributes.iter().map(String::as_str))
+    {
+        if !attributes.iter().any(|a| a == name) {
+            attributes.push(name.to_string());
+        }
+    }
+    IndexProjection::Attributes(attributes)
+}
+
+/// Key attribute names of a table or index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyInfo {
+    pub hash_key: String,
+    pub sort_key: Option<String>,
+}
+
+impl KeyInfo {
+    fn from_key_schema(key_schema: &[KeySchemaElement]) -> Result<Self, Error> {
+        let name = |key_type: KeyType| {
+            key_schema
+                .iter()
+                .find(|k| *k.key_type() == key_type)
+                .map(|k| k.attribute_name().to_string())
+        };
+        Ok(KeyInfo {
+            hash_key: name(KeyType::Hash)
+                .ok_or_else(|| Error::ValidationError("hash key is missing".to_string()))?,
+            sort_key: name(KeyType::Range),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexInfo {
+    pub name: String,
+    pub keys: KeyInfo,
+    pub projection: IndexProjection,
+    /// Only for global secondary indexes.
+    pub status: Option<IndexStatus>,
+}
+
+/// DescribeTable output in a flat form for tooling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    pub name: String,
+    pub arn: Option<String>,
+    pub status: Option<TableStatus>,
+    pub keys: KeyInfo,
+    /// Types of the attributes in the key schemas of the table and its indexes.
+    pub attribute_types: HashMap<String, ScalarAttributeType>,
+    pub billing_mode: BillingMode,
+    /// Updated by DynamoDB about every six hours.
+    pub item_count: Option<i64>,
+    pub table_size_bytes: Option<i64>,
+    pub global_secondary_indexes: Vec<IndexInfo>,
+    pub local_secondary_indexes: Vec<IndexInfo>,
+    /// None if the stream is disabled.
+    pub stream_arn: Option<String>,
+    pub stream_view_type: Option<StreamViewType>,
+}
+
+impl TryFrom<&TableDescription> for TableInfo {
+    type Error = Error;
+
+    fn try_from(table: &TableDescription) -> Result<Self, Self::Error> {
+        let table_key_schema = table.key_schema();
+        let global_secondary_indexes = table
+            .global_secondary_indexes()
+            .iter()
+            .map(|i| {
+                Ok(IndexInfo {
+                    name: i.index_name().unwrap_or_default().to_string(),
+                    keys: KeyInfo::from_key_schema(i.key_schema())?,
+                    projection: index_projection(table_key_schema, i.key_schema(), i.projection()),
+                    status: i.index_status().cloned(),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let local_secondary_indexes = table
+            .local_secondary_indexes()
+            .iter()
+            .map(|i| {
+                Ok(IndexInfo {
+                    name: i.index_name().unwrap_or_default().to_string(),
+                    keys: KeyInfo::from_key_schema(i.key_schema())?,
+                    projection: index_projection(table_key_schema, i.key_schema(), i.projection()),
+                    status: None,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let stream_enabled = table
+            .stream_specification()
+            .is_some_and(|s| s.stream_enabled());
+
+        Ok(TableInfo {
+            name: table.table_name().unwrap_or_default().to_string(),
+            arn: table.table_arn().map(ToString::to_string),
+            status: table.table_status().cloned(),
+            keys: KeyInfo::from_key_schema(table_key_schema)?,
+            attribute_types: table
+                .attribute_definitions()
+                .iter()
+                .map(|a| (a.attribute_name().to_string(), a.attribute_type().clone()))
+                .collect(),
+            // BillingModeSummary が無いのはプロビジョンドのテーブル
+            billing_mode: table
+                .billing_mode_summary()
+                .and_then(|b| b.billing_mode())
+                .cloned()
+                .unwrap_or(BillingMode::Provisioned),
+            item_count: table.item_count(),
+            table_size_bytes: table.table_size_bytes(),
+            global_secondary_indexes,
+            local_secondary_indexes,
+            stream_arn: table
+                .latest_stream_arn()
+                .filter(|_| stream_enabled)
+                .map(ToString::to_string),
+            stream_view_type: table
+                .stream_specification()
+                .filter(|_| stream_enabled)
+                .and_then(|s| s.stream_view_type())
+                .cloned(),
+        })
+    }
+}
+
+pub async fn get_table_info(
+    client: &Client,
+    table_name: impl Into<String>,
+) -> Result<TableInfo, Error> {
+    let res = describe_table(client, table_name).await?;
+    let Some(table) = res.table() else {
+        return Err(Error::NotFound);
+    };
+    TableInfo::try_from(table)
+}
+
 pub async fn get_capacity(
     client: &Client,
     table_name: impl Into<String>,
@@ -147,3 +359,368 @@ pub async fn set_capacity(
         .await
         .map_err(from_aws_sdk_error)
 }
+
+/// Enables or disables deletion protection of a table.
+pub async fn set_deletion_protection(
+    client: &Client,
+    table_name: impl Into<String>,
+    enabled: bool,
+) -> Result<UpdateTableOutput, Error> {
+    client
+        .update_table()
+        .table_name(table_name)
+        .deletion_protection_enabled(enabled)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Adds or overwrites tags of a table (`resource_arn` is the table ARN, see
+/// [`TableInfo::arn`]).
+pub async fn tag_resource(
+    client: &Client,
+    resource_arn: impl Into<String>,
+    tags: HashMap<String, String>,
+) -> Result<(), Error> {
+    let tags = tags
+        .into_iter()
+        .map(|(key, value)| Tag::builder().key(key).value(value).build())
+        .collect::<Result<Vec<_>, _>>()?;
+    client
+        .tag_resource()
+        .resource_arn(resource_arn)
+        .set_tags(Some(tags))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+pub async fn untag_resource(
+    client: &Client,
+    resource_arn: impl Into<String>,
+    tag_keys: Vec<String>,
+) -> Result<(), Error> {
+    client
+        .untag_resource()
+        .resource_arn(resource_arn)
+        .set_tag_keys(Some(tag_keys))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+pub async fn list_tags_of_resource(
+    client: &Client,
+    resource_arn: impl Into<String>,
+) -> Result<HashMap<String, String>, Error> {
+    let resource_arn = resource_arn.into();
+    let mut tags = HashMap::new();
+    let mut next_token = None;
+    loop {
+        let output = client
+            .list_tags_of_resource()
+            .resource_arn(&resource_arn)
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        tags.extend(
+            output
+                .tags
+                .unwrap_or_default()
+                .into_iter()
+                .map(|t| (t.key, t.value)),
+        );
+        next_token = output.next_token;
+        if next_token.is_none() {
+            return Ok(tags);
+        }
+    }
+}
+
+/// Adds a replica in `region_name` (global tables version 2019.11.21).
+/// The table must have streams with `NEW_AND_OLD_IMAGES` enabled.
+pub async fn add_replica(
+    client: &Client,
+    table_name: impl Into<String>,
+    region_name: impl Into<String>,
+) -> Result<UpdateTableOutput, Error> {
+    let action = CreateReplicationGroupMemberAction::builder()
+        .region_name(region_name)
+        .build()?;
+    client
+        .update_table()
+        .table_name(table_name)
+        .replica_updates(ReplicationGroupUpdate::builder().create(action).build())
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+pub async fn remove_replica(
+    client: &Client,
+    table_name: impl Into<String>,
+    region_name: impl Into<String>,
+) -> Result<UpdateTableOutput, Error> {
+    let action = DeleteReplicationGroupMemberAction::builder()
+        .region_name(region_name)
+        .build()?;
+    client
+        .update_table()
+        .table_name(table_name)
+        .replica_updates(ReplicationGroupUpdate::builder().delete(action).build())
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Replicas of a global table (version 2019.11.21), empty if the table is not replicated.
+pub async fn describe_global_table(
+    client: &Client,
+    table_name: impl Into<String>,
+) -> Result<Vec<ReplicaDescription>, Error> {
+    let res = describe_table(client, table_name).await?;
+    let Some(table) = res.table else {
+        return Err(Error::NotFound);
+    };
+    Ok(table.replicas.unwrap_or_default())
+}
+
+/// Waits until the replica in `region_name` is active, checking every `check_duration`.
+pub async fn wait_for_replica_active(
+    client: &Client,
+    table_name: impl Into<String>,
+    region_name: impl Into<String>,
+    timeout_duration: Duration,
+    check_duration: Duration,
+) -> Result<ReplicaDescription, Error> {
+    let table_name = table_name.into();
+    let region_name = region_name.into();
+    tokio::time::timeout(timeout_duration, async {
+        loop {
+            let replicas = describe_global_table(client, &table_name).await?;
+            if let Some(replica) = active_replica(replicas, &region_name)? {
+                return Ok(replica);
+            }
+            tokio::time::sleep(check_duration).await;
+        }
+    })
+    .await?
+}
+
+fn active_replica(
+    replicas: Vec<ReplicaDescription>,
+    region_name: &str,
+) -> Result<Option<ReplicaDescription>, Error> {
+    // add_replica 直後はまだ一覧に出てこないことがある
+    let Some(replica) = replicas
+        .into_iter()
+        .find(|r| r.region_name() == Some(region_name))
+    else {
+        return Ok(None);
+    };
+    match replica.replica_status() {
+        Some(ReplicaStatus::Active) => Ok(Some(replica)),
+        Some(
+            status @ (ReplicaStatus::CreationFailed
+            | ReplicaStatus::RegionDisabled
+            | ReplicaStatus::InaccessibleEncryptionCredentials
+            | ReplicaStatus::ReplicationNotAuthorized),
+        ) => Err(Error::ReplicaFailed(format!(
+            "{region_name} {status}: {}",
+            replica.replica_status_description().unwrap_or_default()
+        ))),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, key_type: KeyType) -> KeySchemaElement {
+        KeySchemaElement::builder()
+            .attribute_name(name)
+            .key_type(key_type)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_index_projection() {
+        let table_keys = [key("pk", KeyType::Hash), key("sk", KeyType::Range)];
+        let index_keys = [key("pk", KeyType::Hash), key("created_at", KeyType::Range)];
+
+        let keys_only = Projection::builder()
+            .projection_type(ProjectionType::KeysOnly)
+            .build();
+        assert_eq!(
+            index_projection(&table_keys, &index_keys, Some(&keys_only)),
+            IndexProjection::Attributes(vec![
+                "pk".to_string(),
+                "sk".to_string(),
+                "created_at".to_string()
+            ])
+        );
+
+        let include = Projection::builder()
+            .projection_type(ProjectionType::Include)
+            .non_key_attributes("status")
+            .build();
+        assert_eq!(
+            index_projection(&table_keys, &index_keys, Some(&include)),
+            IndexProjection::Attributes(vec![
+                "pk".to_string(),
+                "sk".to_string(),
+                "created_at".to_string(),
+                "status".to_string()
+            ])
+        );
+
+        let all = Projection::builder()
+            .projection_type(ProjectionType::All)
+            .build();
+        assert_eq!(
+            index_projection(&table_keys, &index_keys, Some(&all)),
+            IndexProjection::All
+        );
+    }
+
+    #[test]
+    fn test_table_info() {
+        use aws_sdk_dynamodb::types::{
+            AttributeDefinition, GlobalSecondaryIndexDescription, StreamSpecification,
+        };
+
+        let table = TableDescription::builder()
+            .table_name("users")
+            .table_status(TableStatus::Active)
+            .key_schema(key("pk", KeyType::Hash))
+            .key_schema(key("sk", KeyType::Range))
+            .attribute_definitions(
+                AttributeDefinition::builder()
+                    .attribute_name("pk")
+                    .attribute_type(ScalarAttributeType::S)
+                    .build()
+                    .unwrap(),
+            )
+            .global_secondary_indexes(
+                GlobalSecondaryIndexDescription::builder()
+                    .index_name("by_email")
+                    .key_schema(key("email", KeyType::Hash))
+                    .projection(
+                        Projection::builder()
+                            .projection_type(ProjectionType::All)
+                            .build(),
+                    )
+                    .index_status(IndexStatus::Active)
+                    .build(),
+            )
+            .stream_specification(
+                StreamSpecification::builder()
+                    .stream_enabled(true)
+                    .stream_view_type(StreamViewType::NewImage)
+                    .build()
+                    .unwrap(),
+            )
+            .latest_stream_arn("arn:aws:dynamodb:ap-northeast-1:123456789012:table/users/stream/x")
+            .item_count(42)
+            .build();
+
+        let info = TableInfo::try_from(&table).unwrap();
+        assert_eq!(info.name, "users");
+        assert_eq!(
+            info.keys,
+            KeyInfo {
+                hash_key: "pk".to_string(),
+                sort_key: Some("sk".to_string()),
+            }
+        );
+        assert_eq!(info.attribute_types["pk"], ScalarAttributeType::S);
+        assert_eq!(info.billing_mode, BillingMode::Provisioned);
+        assert_eq!(info.item_count, Some(42));
+        assert_eq!(info.global_secondary_indexes[0].name, "by_email");
+        assert_eq!(info.global_secondary_indexes[0].keys.sort_key, None);
+        assert_eq!(
+            info.global_secondary_indexes[0].projection,
+            IndexProjection::All
+        );
+        assert!(info.local_secondary_indexes.is_empty());
+        assert!(info.stream_arn.is_some());
+        assert_eq!(info.stream_view_type, Some(StreamViewType::NewImage));
+    }
+
+    #[test]
+    fn test_active_replica() {
+        let replica = |region: &str, status: ReplicaStatus| {
+            ReplicaDescription::builder()
+                .region_name(region)
+                .replica_status(status)
+                .build()
+        };
+
+        assert!(active_replica(vec![], "us-west-2").unwrap().is_none());
+        let creating = vec![
+            replica("ap-northeast-1", ReplicaStatus::Active),
+            replica("us-west-2", ReplicaStatus::Creating),
+        ];
+        assert!(active_replica(creating, "us-west-2").unwrap().is_none());
+
+        let active = vec![
+            replica("ap-northeast-1", ReplicaStatus::Active),
+            replica("us-west-2", ReplicaStatus::Active),
+        ];
+        let replica_description = active_replica(active, "us-west-2").unwrap().unwrap();
+        assert_eq!(replica_description.region_name(), Some("us-west-2"));
+
+        let failed = vec![replica("us-west-2", ReplicaStatus::CreationFailed)];
+        assert!(matches!(
+            active_replica(failed, "us-west-2"),
+            Err(Error::ReplicaFailed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_delete_table_force() {
+        use mockito::Matcher;
+
+        let mut server = mockito::Server::new_async().await;
+        let describe = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.DescribeTable")
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(
+                r#"{"Table":{"TableName":"users","TableStatus":"ACTIVE","DeletionProtectionEnabled":true}}"#,
+            )
+            .create_async()
+            .await;
+        let update = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.UpdateTable")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"TableName":"users","DeletionProtectionEnabled":false}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(r#"{"TableDescription":{"TableName":"users"}}"#)
+            .create_async()
+            .await;
+        let delete = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "DynamoDB_20120810.DeleteTable")
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.0")
+            .with_body(r#"{"TableDescription":{"TableName":"users","TableStatus":"DELETING"}}"#)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        delete_table(&client, "users", true).await.unwrap();
+        describe.assert_async().await;
+        update.assert_async().await;
+        delete.assert_async().await;
+    }
+}
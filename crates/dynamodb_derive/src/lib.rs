@@ -0,0 +1,246 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Data, DeriveInput, Expr, ExprLit, Field, Fields, Ident, Lit, LitStr, Meta, Token,
+    parse_macro_input, punctuated::Punctuated,
+};
+
+/// Implements `aws_utils_dynamodb::key::DynamoKey` for a struct with named fields.
+///
+/// One field is marked `#[dynamo(hash_key)]` and at most one `#[dynamo(range_key)]`. The
+/// attribute name is the field name, `#[serde(rename = "...")]` of the field or
+/// `#[dynamo(hash_key, rename = "...")]`. The field types implement
+/// `aws_utils_dynamodb::key::KeyAttribute`.
+#[proc_macro_derive(DynamoKey, attributes(dynamo))]
+pub fn derive_dynamo_key(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+enum KeyType {
+    Hash,
+    Range,
+}
+
+struct KeyField<'a> {
+    ident: &'a Ident,
+    attribute_name: String,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "DynamoKey can only be derived for structs with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "DynamoKey can only be derived for structs",
+            ));
+        }
+    };
+
+    let mut hash_key: Option<KeyField> = None;
+    let mut range_key: Option<KeyField> = None;
+    for field in fields {
+        let Some((key_type, rename)) = parse_key_attribute(field)? else {
+            continue;
+        };
+        let ident = field.ident.as_ref().expect("named field");
+        let attribute_name = match rename {
+            Some(rename) => rename,
+            None => serde_rename(field)?.unwrap_or_else(|| ident.to_string()),
+        };
+        let (slot, attribute) = match key_type {
+            KeyType::Hash => (&mut hash_key, "hash_key"),
+            KeyType::Range => (&mut range_key, "range_key"),
+        };
+        if slot.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                format!("only one field can have #[dynamo({attribute})]"),
+            ));
+        }
+        *slot = Some(KeyField {
+            ident,
+            attribute_name,
+        });
+    }
+    let Some(hash_key) = hash_key else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "DynamoKey requires a field with #[dynamo(hash_key)]",
+        ));
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let hash_key_name = &hash_key.attribute_name;
+    let hash_key_ident = hash_key.ident;
+    let range_key_items = match range_key {
+        Some(KeyField {
+            ident,
+            attribute_name,
+        }) => quote! {
+            const RANGE_KEY: ::core::option::Option<&'static str> =
+                ::core::option::Option::Some(#attribute_name);
+
+            fn range_key_value(
+                &self,
+            ) -> ::core::option::Option<::aws_utils_dynamodb::aws_sdk_dynamodb::types::AttributeValue>
+            {
+                ::core::option::Option::Some(
+                    ::aws_utils_dynamodb::key::KeyAttribute::to_key_attribute(&self.#ident),
+                )
+            }
+        },
+        None => quote! {},
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::aws_utils_dynamodb::key::DynamoKey for #name #ty_generics #where_clause {
+            const HASH_KEY: &'static str = #hash_key_name;
+
+            fn hash_key_value(&self) -> ::aws_utils_dynamodb::aws_sdk_dynamodb::types::AttributeValue {
+                ::aws_utils_dynamodb::key::KeyAttribute::to_key_attribute(&self.#hash_key_ident)
+            }
+
+            #range_key_items
+        }
+    })
+}
+
+/// The key type and the rename of `#[dynamo(...)]`, None without the attribute.
+fn parse_key_attribute(field: &Field) -> syn::Result<Option<(KeyType, Option<String>)>> {
+    let mut result = None;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("dynamo")) {
+        let mut key_type = None;
+        let mut rename = None;
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("hash_key") {
+                key_type = Some(KeyType::Hash);
+            } else if meta.path.is_ident("range_key") {
+                key_type = Some(KeyType::Range);
+            } else if meta.path.is_ident("rename") {
+                rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else {
+                return Err(meta.error("expected `hash_key`, `range_key` or `rename`"));
+            }
+            Ok(())
+        })?;
+        let Some(key_type) = key_type else {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "expected #[dynamo(hash_key)] or #[dynamo(range_key)]",
+            ));
+        };
+        if result.is_some() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "duplicate #[dynamo] attribute",
+            ));
+        }
+        result = Some((key_type, rename));
+    }
+    Ok(result)
+}
+
+/// `#[serde(rename = "...")]` of the field. Renames for serialize / deserialize only are ignored.
+fn serde_rename(field: &Field) -> syn::Result<Option<String>> {
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        let metas = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        for meta in metas {
+            if let Meta::NameValue(name_value) = meta
+                && name_value.path.is_ident("rename")
+                && let Expr::Lit(ExprLit {
+                    lit: Lit::Str(lit), ..
+                }) = name_value.value
+            {
+                return Ok(Some(lit.value()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let input: DeriveInput = parse_quote! {
+            struct OrderKey {
+                #[dynamo(hash_key, rename = "PK")]
+                user_id: String,
+                #[serde(rename = "SK")]
+                #[dynamo(range_key)]
+                order_id: u64,
+                note: String,
+            }
+        };
+        let expanded = expand(&input).unwrap().to_string();
+        assert!(expanded.contains(r#"const HASH_KEY : & 'static str = "PK""#));
+        assert!(expanded.contains(r#"Some ("SK")"#));
+        assert!(expanded.contains("self . order_id"));
+        assert!(!expanded.contains("self . note"));
+    }
+
+    #[test]
+    fn test_expand_without_range_key() {
+        let input: DeriveInput = parse_quote! {
+            struct UserKey {
+                #[dynamo(hash_key)]
+                user_id: String,
+            }
+        };
+        let expanded = expand(&input).unwrap().to_string();
+        assert!(expanded.contains(r#"const HASH_KEY : & 'static str = "user_id""#));
+        assert!(!expanded.contains("RANGE_KEY"));
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let missing_hash_key: DeriveInput = parse_quote! {
+            struct Key {
+                #[dynamo(range_key)]
+                sk: String,
+            }
+        };
+        assert!(expand(&missing_hash_key).is_err());
+
+        let duplicate_hash_key: DeriveInput = parse_quote! {
+            struct Key {
+                #[dynamo(hash_key)]
+                a: String,
+                #[dynamo(hash_key)]
+                b: String,
+            }
+        };
+        assert!(expand(&duplicate_hash_key).is_err());
+
+        let unknown_option: DeriveInput = parse_quote! {
+            struct Key {
+                #[dynamo(partition_key)]
+                pk: String,
+            }
+        };
+        assert!(expand(&unknown_option).is_err());
+
+        let tuple_struct: DeriveInput = parse_quote! {
+            struct Key(String);
+        };
+        assert!(expand(&tuple_struct).is_err());
+    }
+}
@@ -1,3 +1,6 @@
+use aws_sdk_firehose::{error::ProvideErrorMetadata, operation::RequestId};
+pub use aws_utils_core::error::AwsUtilError;
+use aws_utils_core::error::is_retryable_sdk_error;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,8 +13,102 @@ pub enum Error {
 
     #[error("Invalid: {0}")]
     Invalid(String),
+
+    #[cfg(feature = "s3")]
+    #[error(transparent)]
+    S3(#[from] aws_utils_s3::error::Error),
 }
 
 pub(crate) fn from_aws_sdk_error(e: impl Into<aws_sdk_firehose::Error>) -> Error {
     Error::AwsSdk(Box::new(e.into()))
 }
+
+impl Error {
+    pub fn is_service_unavailable(&self) -> bool {
+        if let Error::AwsSdk(e) = self {
+            matches!(**e, aws_sdk_firehose::Error::ServiceUnavailableException(_))
+        } else {
+            false
+        }
+    }
+
+    /// Firehose reports exceeded throughput of a delivery stream as ServiceUnavailableException
+    /// with "Slow down." and exceeded API limits as LimitExceededException.
+    pub fn is_throttled(&self) -> bool {
+        let Error::AwsSdk(e) = self else {
+            return false;
+        };
+        match &**e {
+            aws_sdk_firehose::Error::ServiceUnavailableException(e) => {
+                e.message().is_some_and(|m| m.contains("Slow down"))
+            }
+            aws_sdk_firehose::Error::LimitExceededException(_) => true,
+            _ => self.is_throttling(),
+        }
+    }
+}
+
+impl AwsUtilError for Error {
+    fn error_code(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.code(),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.error_code(),
+            _ => None,
+        }
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.request_id(),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.aws_request_id(),
+            _ => None,
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::AwsSdk(e) => is_retryable_sdk_error(e.as_ref()),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_firehose::types::error::{ResourceNotFoundException, ServiceUnavailableException};
+
+    use super::*;
+
+    #[test]
+    fn test_predicates() {
+        let slow_down = Error::AwsSdk(Box::new(
+            aws_sdk_firehose::Error::ServiceUnavailableException(
+                ServiceUnavailableException::builder()
+                    .message("Slow down.")
+                    .build(),
+            ),
+        ));
+        assert!(slow_down.is_service_unavailable());
+        assert!(slow_down.is_throttled());
+
+        let unavailable = Error::AwsSdk(Box::new(
+            aws_sdk_firehose::Error::ServiceUnavailableException(
+                ServiceUnavailableException::builder().build(),
+            ),
+        ));
+        assert!(unavailable.is_service_unavailable());
+        assert!(!unavailable.is_throttled());
+
+        let not_found = Error::AwsSdk(Box::new(
+            aws_sdk_firehose::Error::ResourceNotFoundException(
+                ResourceNotFoundException::builder().build(),
+            ),
+        ));
+        assert!(!not_found.is_service_unavailable());
+        assert!(!not_found.is_throttled());
+    }
+}
@@ -0,0 +1,215 @@
+use aws_sdk_firehose::Client;
+use aws_utils_s3::{aws_sdk_s3, object};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use futures_util::{Stream, TryStreamExt, stream};
+use serde::Deserialize;
+
+use crate::{error::Error, firehose::put_record_batch, producer::split_batches};
+
+/// Max size of an error output object, the max buffer size of a delivery stream.
+const MAX_OBJECT_SIZE: u64 = 128 * 1024 * 1024;
+
+/// A record Firehose failed to process or deliver, from an object under the error output
+/// prefix of the S3 bucket.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FailedRecord {
+    /// Key of the error output object.
+    pub key: String,
+    /// `processing-failed`, `format-conversion-failed`, `http-endpoint-failed` etc, from the
+    /// key. None when the error output prefix does not contain the error output type.
+    pub error_output_type: Option<String>,
+    pub error_code: Option<String>,
+    pub error_message: Option<String>,
+    pub attempts_made: Option<u32>,
+    /// Milliseconds since the epoch.
+    pub arrival_timestamp: Option<i64>,
+    pub attempt_ending_timestamp: Option<i64>,
+    /// Data transformation Lambda, for `processing-failed`.
+    pub lambda_arn: Option<String>,
+    /// Record data, decoded from base64.
+    pub data: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorOutputRecord {
+    error_code: Option<String>,
+    error_message: Option<String>,
+    attempts_made: Option<u32>,
+    arrival_timestamp: Option<i64>,
+    attempt_ending_timestamp: Option<i64>,
+    lambda_arn: Option<String>,
+    raw_data: String,
+}
+
+/// Result of [`redrive_failed_records`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RedriveSummary {
+    pub records: usize,
+    /// Records rejected by PutRecordBatch. They are not retried.
+    pub failed_records: usize,
+}
+
+/// Lists the objects under `prefix` (the error output prefix of the delivery stream, e.g.
+/// `errors/processing-failed/2026/10/`) and yields the failed records in them. Objects are
+/// read one at a time, so a prefix with many failed records is not held in memory.
+pub fn list_failed_records(
+    s3_client: &aws_sdk_s3::Client,
+    bucket_name: &str,
+    prefix: &str,
+) -> impl Stream<Item = Result<FailedRecord, Error>> {
+    object::list_stream(s3_client, bucket_name, Some(prefix))
+        .map_err(Error::from)
+        .and_then(move |s3_object| async move {
+            let key = s3_object.key().unwrap_or_default();
+            let body =
+                object::get_object_bytes(s3_client, bucket_name, key, MAX_OBJECT_SIZE).await?;
+            let records = parse_error_output(key, &body)?;
+            Ok(stream::iter(records.into_iter().map(Ok)))
+        })
+        .try_flatten()
+}
+
+/// Parses an error output object, JSON records each with the base64 `rawData` of a failed
+/// record, separated by newlines.
+pub fn parse_error_output(key: &str, body: &[u8]) -> Result<Vec<FailedRecord>, Error> {
+    let error_output_type = key
+        .split('/')
+        .find(|segment| segment.ends_with("-failed"))
+        .map(ToString::to_string);
+    serde_json::Deserializer::from_slice(body)
+        .into_iter::<ErrorOutputRecord>()
+        .map(|record| {
+            let record = record.map_err(|e| {
+                Error::Invalid(format!("invalid error output record in {key}: {e}"))
+            })?;
+            let data = STANDARD
+                .decode(&record.raw_data)
+                .map_err(|e| Error::Invalid(format!("invalid rawData in {key}: {e}")))?;
+            Ok(FailedRecord {
+                key: key.to_string(),
+                error_output_type: error_output_type.clone(),
+                error_code: record.error_code,
+                error_message: record.error_message,
+                attempts_made: record.attempts_made,
+                arrival_timestamp: record.arrival_timestamp,
+                attempt_ending_timestamp: record.attempt_ending_timestamp,
+                lambda_arn: record.lambda_arn,
+                data,
+            })
+        })
+        .collect()
+}
+
+/// Sends the data of `records` to the delivery stream again with PutRecordBatch, e.g. after
+/// fixing the transformation Lambda or the destination. The error output objects are not
+/// deleted.
+pub async fn redrive_failed_records(
+    client: &Client,
+    delivery_stream_name: &str,
+    records: &[FailedRecord],
+) -> Result<RedriveSummary, Error> {
+    let mut summary = RedriveSummary::default();
+    let data = records.iter().map(|record| record.data.clone()).collect();
+    for batch in split_batches(data) {
+        summary.records += batch.len();
+        let output = put_record_batch(client, delivery_stream_name, batch).await?;
+        summary.failed_records += output.failed_put_count as usize;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_output() {
+        let key = "errors/processing-failed/2026/10/16/03/stream-1-2026-10-16-03-00-00-abc";
+        let body = br#"{"attemptsMade":4,"arrivalTimestamp":1760583600000,"errorCode":"Lambda.FunctionError","errorMessage":"The Lambda function was successfully invoked but it returned an error result.","attemptEndingTimestamp":1760583660000,"rawData":"eyJpZCI6MX0=","lambdaArn":"arn:aws:lambda:ap-northeast-1:123456789012:function:transform:$LATEST"}
+{"attemptsMade":4,"arrivalTimestamp":1760583600001,"errorCode":"Lambda.FunctionError","errorMessage":"","attemptEndingTimestamp":1760583660000,"rawData":"eyJpZCI6Mn0="}
+"#;
+
+        let records = parse_error_output(key, body).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0],
+            FailedRecord {
+                key: key.to_string(),
+                error_output_type: Some("processing-failed".to_string()),
+                error_code: Some("Lambda.FunctionError".to_string()),
+                error_message: Some(
+                    "The Lambda function was successfully invoked but it returned an error result."
+                        .to_string()
+                ),
+                attempts_made: Some(4),
+                arrival_timestamp: Some(1760583600000),
+                attempt_ending_timestamp: Some(1760583660000),
+                lambda_arn: Some(
+                    "arn:aws:lambda:ap-northeast-1:123456789012:function:transform:$LATEST"
+                        .to_string()
+                ),
+                data: br#"{"id":1}"#.to_vec(),
+            }
+        );
+        assert_eq!(records[1].data, br#"{"id":2}"#.to_vec());
+        assert_eq!(records[1].lambda_arn, None);
+
+        assert!(parse_error_output("errors/x", br#"{"rawData":"!"}"#).is_err());
+        assert!(parse_error_output("errors/x", b"").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_failed_records() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/my-bucket/")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "prefix".to_string(),
+                "errors/".to_string(),
+            ))
+            .with_body(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult>
+  <Name>my-bucket</Name><Prefix>errors/</Prefix><KeyCount>2</KeyCount><IsTruncated>false</IsTruncated>
+  <Contents><Key>errors/processing-failed/a</Key><Size>10</Size></Contents>
+  <Contents><Key>errors/http-endpoint-failed/b</Key><Size>10</Size></Contents>
+</ListBucketResult>"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/my-bucket/errors/processing-failed/a")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{"errorCode":"Lambda.FunctionError","rawData":"eyJpZCI6MX0="}
+{"errorCode":"Lambda.FunctionError","rawData":"eyJpZCI6Mn0="}
+"#,
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/my-bucket/errors/http-endpoint-failed/b")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"errorCode":"HttpEndpoint.RequestTimeout","rawData":"eyJpZCI6M30="}"#)
+            .create_async()
+            .await;
+        let s3_client = aws_utils_s3::make_client(Some(server.url()), None, None).await;
+
+        let records: Vec<FailedRecord> = list_failed_records(&s3_client, "my-bucket", "errors/")
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| (r.error_output_type.as_deref(), r.data.as_slice()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some("processing-failed"), br#"{"id":1}"#.as_slice()),
+                (Some("processing-failed"), br#"{"id":2}"#.as_slice()),
+                (Some("http-endpoint-failed"), br#"{"id":3}"#.as_slice()),
+            ]
+        );
+    }
+}
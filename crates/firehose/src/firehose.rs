@@ -1,5 +1,12 @@
-use crate::error::{Error, from_aws_sdk_error};
-use aws_sdk_firehose::{Client, operation::put_record::PutRecordOutput, types::Record};
+use std::time::Duration;
+
+use crate::error::{AwsUtilError, Error, from_aws_sdk_error};
+use aws_sdk_firehose::{
+    Client,
+    operation::{put_record::PutRecordOutput, put_record_batch::PutRecordBatchOutput},
+    types::Record,
+};
+use aws_utils_core::retry::backoff_delay;
 
 pub async fn put_record(
     client: &Client,
@@ -16,3 +23,118 @@ pub async fn put_record(
         .await
         .map_err(from_aws_sdk_error)
 }
+
+/// Sends up to 500 records (4 MiB in total). Check `failed_put_count` of the output, as
+/// records can fail individually.
+pub async fn put_record_batch(
+    client: &Client,
+    delivery_stream_name: impl Into<String>,
+    records: impl IntoIterator<Item = impl Into<Vec<u8>>>,
+) -> Result<PutRecordBatchOutput, Error> {
+    let records = records
+        .into_iter()
+        .map(|data| Record::builder().data(data.into().into()).build())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    client
+        .put_record_batch()
+        .delivery_stream_name(delivery_stream_name.into())
+        .set_records(Some(records))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// put_record retrying ServiceUnavailableException and other retryable errors up to
+/// `max_attempts` times, waiting from `base_delay` doubling each time as Firehose
+/// recommends for throttling (capped at 20 seconds, with jitter).
+pub async fn put_record_with_retry(
+    client: &Client,
+    delivery_stream_name: impl Into<String>,
+    data: impl Into<Vec<u8>>,
+    max_attempts: u32,
+    base_delay: Duration,
+) -> Result<PutRecordOutput, Error> {
+    let delivery_stream_name = delivery_stream_name.into();
+    let data = data.into();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match put_record(client, &delivery_stream_name, data.clone()).await {
+            Ok(output) => return Ok(output),
+            Err(e)
+                if attempts < max_attempts && (e.is_service_unavailable() || e.is_retryable()) =>
+            {
+                tokio::time::sleep(backoff_delay(base_delay, attempts)).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_firehose::config::{Credentials, Region, retry::RetryConfig};
+    use mockito::Server;
+
+    use super::*;
+
+    fn client(endpoint_url: String) -> Client {
+        // SDK の再試行は無効にして put_record_with_retry の再試行だけを確認する
+        Client::from_conf(
+            aws_sdk_firehose::Config::builder()
+                .behavior_version_latest()
+                .region(Region::new("ap-northeast-1"))
+                .credentials_provider(Credentials::for_tests())
+                .retry_config(RetryConfig::disabled())
+                .endpoint_url(endpoint_url)
+                .build(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_put_record_with_retry() {
+        let mut server = Server::new_async().await;
+        let unavailable = server
+            .mock("POST", "/")
+            .with_status(503)
+            .with_body(r#"{"__type":"ServiceUnavailableException","message":"Slow down."}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let ok = server
+            .mock("POST", "/")
+            .with_status(200)
+            .with_body(r#"{"RecordId":"r1","Encrypted":false}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = client(server.url());
+
+        let output = put_record_with_retry(&client, "events", b"{}".to_vec(), 3, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(output.record_id(), "r1");
+        unavailable.assert_async().await;
+        ok.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_put_record_with_retry_gives_up() {
+        let mut server = Server::new_async().await;
+        let unavailable = server
+            .mock("POST", "/")
+            .with_status(503)
+            .with_body(r#"{"__type":"ServiceUnavailableException","message":"Slow down."}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let client = client(server.url());
+
+        let e = put_record_with_retry(&client, "events", b"{}".to_vec(), 2, Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert!(e.is_service_unavailable());
+        unavailable.assert_async().await;
+    }
+}
@@ -1,5 +1,10 @@
 pub mod error;
+#[cfg(feature = "s3")]
+pub mod error_output;
 pub mod firehose;
+#[cfg(feature = "partition")]
+pub mod partition;
+pub mod producer;
 use std::time::Duration;
 
 use aws_config::{
@@ -8,6 +13,8 @@ use aws_config::{
 };
 pub use aws_sdk_firehose;
 use aws_sdk_firehose::{Client, config::SharedInterceptor};
+#[cfg(feature = "metrics")]
+pub use aws_utils_core::metrics;
 
 pub async fn make_client_with_timeout_default(endpoint_url: Option<String>) -> Client {
     make_client_with_timeout(
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use aws_sdk_firehose::{
+    Client,
+    operation::{put_record::PutRecordOutput, put_record_batch::PutRecordBatchOutput},
+};
+use serde_json::Value;
+
+use crate::{
+    error::Error,
+    firehose::{put_record, put_record_batch},
+};
+
+/// Max partition keys of a delivery stream.
+pub const MAX_PARTITION_KEYS: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PartitionKey {
+    name: String,
+    path: Vec<String>,
+}
+
+/// Partition keys of a delivery stream with dynamic partitioning by inline JSON parsing, each
+/// read from a field of the record.
+///
+/// The same keys give the JQ query of the MetadataExtraction processor
+/// ([`jq_query`](Self::jq_query)), the S3 prefix ([`s3_prefix`](Self::s3_prefix)) and the
+/// validation of the records before sending ([`extract`](Self::extract)), so records without
+/// a key are rejected instead of being delivered to the error output prefix.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PartitionKeys {
+    keys: Vec<PartitionKey>,
+}
+
+impl PartitionKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the key `name` read from `field_path`, the field names of nested objects joined
+    /// with `.` (e.g. `customer.id`).
+    pub fn key(mut self, name: impl Into<String>, field_path: &str) -> Self {
+        self.keys.push(PartitionKey {
+            name: name.into(),
+            path: field_path.split('.').map(str::to_string).collect(),
+        });
+        self
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(|k| k.name.as_str())
+    }
+
+    /// MetadataExtractionQuery of the delivery stream, e.g. `{customer_id:.customer.id}`.
+    pub fn jq_query(&self) -> String {
+        let fields = self
+            .keys
+            .iter()
+            .map(|k| {
+                let path = k.path.iter().map(|f| jq_field(f)).collect::<String>();
+                format!("{}:{path}", jq_field(&k.name).trim_start_matches('.'))
+            })
+            .collect::<Vec<_>>();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// S3 prefix of the delivery stream with the keys as `name=value` directories after
+    /// `prefix`, e.g. `data/customer_id=!{partitionKeyFromQuery:customer_id}/`.
+    pub fn s3_prefix(&self, prefix: &str) -> String {
+        let mut s3_prefix = prefix.to_string();
+        for key in &self.keys {
+            s3_prefix.push_str(&format!("{0}=!{{partitionKeyFromQuery:{0}}}/", key.name));
+        }
+        s3_prefix
+    }
+
+    /// The partition values of `record`, which must be a JSON object with a string, number or
+    /// boolean at the path of each key.
+    pub fn extract(&self, record: &[u8]) -> Result<HashMap<String, String>, Error> {
+        if self.keys.is_empty() || self.keys.len() > MAX_PARTITION_KEYS {
+            return Err(Error::Invalid(format!(
+                "partition keys must be 1 to {MAX_PARTITION_KEYS}: {}",
+                self.keys.len()
+            )));
+        }
+        let value: Value = serde_json::from_slice(record)
+            .map_err(|e| Error::Invalid(format!("record is not JSON: {e}")))?;
+        if !value.is_object() {
+            return Err(Error::Invalid("record is not a JSON object".to_string()));
+        }
+        self.keys
+            .iter()
+            .map(|key| {
+                let field = key
+                    .path
+                    .iter()
+                    .try_fold(&value, |v, name| v.get(name))
+                    .ok_or_else(|| {
+                        Error::Invalid(format!(
+                            "partition key {} is missing: {}",
+                            key.name,
+                            key.path.join(".")
+                        ))
+                    })?;
+                let partition_value = match field {
+                    Value::String(s) if !s.is_empty() => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => {
+                        return Err(Error::Invalid(format!(
+                            "partition key {} must be a non-empty string, a number or a boolean: {field}",
+                            key.name
+                        )));
+                    }
+                };
+                Ok((key.name.clone(), partition_value))
+            })
+            .collect()
+    }
+}
+
+// 識別子以外のフィールド名は ."field-name" のようにクォートする
+fn jq_field(name: &str) -> String {
+    let is_identifier = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_identifier {
+        format!(".{name}")
+    } else {
+        format!(".{}", Value::String(name.to_string()))
+    }
+}
+
+/// [`put_record`] of a record validated by [`PartitionKeys::extract`].
+pub async fn put_partitioned_record(
+    client: &Client,
+    delivery_stream_name: impl Into<String>,
+    partition_keys: &PartitionKeys,
+    data: impl Into<Vec<u8>>,
+) -> Result<PutRecordOutput, Error> {
+    let data = data.into();
+    partition_keys.extract(&data)?;
+    put_record(client, delivery_stream_name, data).await
+}
+
+/// [`put_record_batch`] of records validated by [`PartitionKeys::extract`]. Nothing is sent if
+/// a record is invalid.
+pub async fn put_partitioned_record_batch(
+    client: &Client,
+    delivery_stream_name: impl Into<String>,
+    partition_keys: &PartitionKeys,
+    records: Vec<Vec<u8>>,
+) -> Result<PutRecordBatchOutput, Error> {
+    for (i, record) in records.iter().enumerate() {
+        partition_keys
+            .extract(record)
+            .map_err(|e| Error::Invalid(format!("record {i}: {e}")))?;
+    }
+    put_record_batch(client, delivery_stream_name, records).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partition_keys() -> PartitionKeys {
+        PartitionKeys::new()
+            .key("customer_id", "customer.id")
+            .key("event_type", "event-type")
+    }
+
+    #[test]
+    fn test_jq_query() {
+        assert_eq!(
+            partition_keys().jq_query(),
+            r#"{customer_id:.customer.id,event_type:."event-type"}"#
+        );
+    }
+
+    #[test]
+    fn test_s3_prefix() {
+        assert_eq!(
+            partition_keys().s3_prefix("data/"),
+            "data/customer_id=!{partitionKeyFromQuery:customer_id}/\
+             event_type=!{partitionKeyFromQuery:event_type}/"
+        );
+    }
+
+    #[test]
+    fn test_extract() {
+        let keys = partition_keys();
+        assert_eq!(
+            keys.extract(br#"{"customer":{"id":42},"event-type":"click","x":1}"#)
+                .unwrap(),
+            HashMap::from([
+                ("customer_id".to_string(), "42".to_string()),
+                ("event_type".to_string(), "click".to_string()),
+            ])
+        );
+
+        let invalid: [&[u8]; 6] = [
+            b"not json",
+            br#"["customer"]"#,
+            br#"{"event-type":"click"}"#,
+            br#"{"customer":{"id":null},"event-type":"click"}"#,
+            br#"{"customer":{"id":{"a":1}},"event-type":"click"}"#,
+            br#"{"customer":{"id":1},"event-type":""}"#,
+        ];
+        for record in invalid {
+            assert!(
+                matches!(keys.extract(record), Err(Error::Invalid(_))),
+                "{}",
+                String::from_utf8_lossy(record)
+            );
+        }
+        assert!(PartitionKeys::new().extract(b"{}").is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use aws_sdk_firehose::{Client, operation::put_record_batch::PutRecordBatchOutput};
+
+use crate::{error::Error, firehose::put_record_batch};
+
+/// Max records of PutRecordBatch.
+pub const MAX_BATCH_RECORDS: usize = 500;
+/// Max total size of PutRecordBatch.
+pub const MAX_BATCH_SIZE: usize = 4 * 1024 * 1024;
+
+/// Splits `records` into batches within the limits of PutRecordBatch, keeping the order.
+pub fn split_batches(records: Vec<Vec<u8>>) -> Vec<Vec<Vec<u8>>> {
+    let mut batches = Vec::new();
+    let mut batch: Vec<Vec<u8>> = Vec::new();
+    let mut batch_size = 0;
+    for record in records {
+        if !batch.is_empty()
+            && (batch.len() == MAX_BATCH_RECORDS || batch_size + record.len() > MAX_BATCH_SIZE)
+        {
+            batches.push(std::mem::take(&mut batch));
+            batch_size = 0;
+        }
+        batch_size += record.len();
+        batch.push(record);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+/// A batch within the limits and the lengths of its records.
+#[cfg(feature = "s3")]
+const MAX_LOG_SIZE: u64 = (MAX_BATCH_SIZE + 4 * MAX_BATCH_RECORDS) as u64;
+
+#[cfg(feature = "s3")]
+#[derive(Debug, Clone)]
+struct ReplayLog {
+    client: aws_utils_s3::aws_sdk_s3::Client,
+    bucket_name: String,
+    prefix: String,
+}
+
+/// Producer sending batches to a delivery stream with PutRecordBatch.
+///
+/// With [`replay_log`](Self::replay_log) (feature `s3`), each batch is also written to S3
+/// before it is sent, so the records can be sent again with
+/// [`replay_from`](Self::replay_from) after delivery failures of the stream.
+#[derive(Debug, Clone)]
+pub struct FirehoseProducer {
+    client: Client,
+    delivery_stream_name: String,
+    #[cfg(feature = "s3")]
+    replay_log: Option<ReplayLog>,
+}
+
+/// Result of [`FirehoseProducer::replay_from`].
+#[cfg(feature = "s3")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplaySummary {
+    pub objects: usize,
+    pub records: usize,
+    /// Records rejected by PutRecordBatch. They are not retried, and stay in the log.
+    pub failed_records: usize,
+}
+
+impl FirehoseProducer {
+    pub fn new(client: Client, delivery_stream_name: impl Into<String>) -> Self {
+        Self {
+            client,
+            delivery_stream_name: delivery_stream_name.into(),
+            #[cfg(feature = "s3")]
+            replay_log: None,
+        }
+    }
+
+    /// Writes each batch to an object under `prefix` in the bucket, named by the time it
+    /// was sent. Expire the objects with a lifecycle rule longer than the replay window.
+    #[cfg(feature = "s3")]
+    pub fn replay_log(
+        mut self,
+        s3_client: aws_utils_s3::aws_sdk_s3::Client,
+        bucket_name: impl Into<String>,
+        prefix: impl Into<String>,
+    ) -> Self {
+        self.replay_log = Some(ReplayLog {
+            client: s3_client,
+            bucket_name: bucket_name.into(),
+            prefix: prefix.into(),
+        });
+        self
+    }
+
+    pub fn delivery_stream_name(&self) -> &str {
+        &self.delivery_stream_name
+    }
+
+    /// Sends the records in batches within the limits of PutRecordBatch (see
+    /// [`split_batches`]), in order. Returns the output of each batch, whose failed records
+    /// are not retried.
+    pub async fn put_batch(
+        &self,
+        records: Vec<Vec<u8>>,
+    ) -> Result<Vec<PutRecordBatchOutput>, Error> {
+        let mut outputs = Vec::new();
+        for batch in split_batches(records) {
+            #[cfg(feature = "s3")]
+            if let Some(replay_log) = &self.replay_log {
+                // 送信に失敗したバッチも再送できるよう、先にログへ書き込む
+                replay_log.write(&batch).await?;
+            }
+            outputs.push(put_record_batch(&self.client, &self.delivery_stream_name, batch).await?);
+        }
+        Ok(outputs)
+    }
+
+    /// Sends the records logged under `s3_prefix` (a prefix in the bucket of the replay
+    /// log, e.g. the prefix and the first digits of the time of the failures) to the
+    /// delivery stream again, in the order they were logged. Objects are read one at a time.
+    ///
+    /// The log does not know which records were delivered, so all records under the prefix are
+    /// sent again, including the ones delivered before the failures. Consumers of the
+    /// destination should tolerate duplicates, e.g. by deduplicating on an ID in the records.
+    #[cfg(feature = "s3")]
+    pub async fn replay_from(&self, s3_prefix: &str) -> Result<ReplaySummary, Error> {
+        use futures_util::TryStreamExt;
+
+        let Some(replay_log) = &self.replay_log else {
+            return Err(Error::Invalid("replay_log is not set".to_string()));
+        };
+        let mut objects = std::pin::pin!(aws_utils_s3::object::list_stream(
+            &replay_log.client,
+            &replay_log.bucket_name,
+            Some(s3_prefix),
+        ));
+        let mut summary = ReplaySummary::default();
+        while let Some(object) = objects.try_next().await? {
+            let records = replay_log.read(object.key().unwrap_or_default()).await?;
+            summary.objects += 1;
+            summary.records += records.len();
+            for batch in split_batches(records) {
+                let output =
+                    put_record_batch(&self.client, &self.delivery_stream_name, batch).await?;
+                summary.failed_records += output.failed_put_count as usize;
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(feature = "s3")]
+impl ReplayLog {
+    async fn write(&self, records: &[Vec<u8>]) -> Result<(), Error> {
+        use std::{
+            sync::atomic::{AtomicU64, Ordering},
+            time::{SystemTime, UNIX_EPOCH},
+        };
+
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        // 辞書順が送信順になるようゼロ埋めする
+        let key = format!(
+            "{}{nanos:020}-{:06}",
+            self.prefix,
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        );
+        aws_utils_s3::object::put_object(
+            &self.client,
+            &self.bucket_name,
+            key,
+            encode_records(records),
+            Some("application/octet-stream"),
+            None::<String>,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn read(&self, key: &str) -> Result<Vec<Vec<u8>>, Error> {
+        let body = aws_utils_s3::object::get_object_bytes(
+            &self.client,
+            &self.bucket_name,
+            key,
+            MAX_LOG_SIZE,
+        )
+        .await?;
+        decode_records(&body)
+            .ok_or_else(|| Error::Invalid(format!("replay log is truncated: {key}")))
+    }
+}
+
+/// Records prefixed with their length as u32 big endian.
+#[cfg(feature = "s3")]
+fn encode_records(records: &[Vec<u8>]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(records.iter().map(|r| r.len() + 4).sum());
+    for record in records {
+        buf.extend_from_slice(&(record.len() as u32).to_be_bytes());
+        buf.extend_from_slice(record);
+    }
+    buf
+}
+
+#[cfg(feature = "s3")]
+fn decode_records(mut buf: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut records = Vec::new();
+    while !buf.is_empty() {
+        let (len, rest) = buf.split_first_chunk::<4>()?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if rest.len() < len {
+            return None;
+        }
+        let (record, rest) = rest.split_at(len);
+        records.push(record.to_vec());
+        buf = rest;
+    }
+    Some(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_batches() {
+        let batches = split_batches(vec![vec![0; 10]; 1001]);
+        assert_eq!(
+            batches.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![500, 500, 1]
+        );
+
+        let batches = split_batches(vec![vec![0; 1000 * 1024]; 5]);
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![4, 1]);
+        assert!(split_batches(Vec::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_put_batch_splits() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Firehose_20150804.PutRecordBatch")
+            .with_body(r#"{"FailedPutCount":0,"RequestResponses":[]}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        let producer = FirehoseProducer::new(client, "my-delivery-stream");
+
+        let outputs = producer.put_batch(vec![vec![0; 10]; 501]).await.unwrap();
+        assert_eq!(outputs.len(), 2);
+        mock.assert_async().await;
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn test_encode_records() {
+        let records = vec![b"a\nb".to_vec(), Vec::new(), b"{\"c\":1}".to_vec()];
+        let buf = encode_records(&records);
+        assert_eq!(decode_records(&buf), Some(records));
+        assert_eq!(decode_records(&buf[..buf.len() - 1]), None);
+    }
+}
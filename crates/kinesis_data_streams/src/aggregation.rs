@@ -0,0 +1,295 @@
+use aws_sdk_kinesis::types::Record;
+use md5::{Digest, Md5};
+
+use crate::error::Error;
+
+// KPL の集約フォーマット: マジックナンバー + AggregatedRecord (protobuf) + protobuf の MD5
+// https://github.com/awslabs/amazon-kinesis-producer/blob/master/aggregation-format.md
+
+pub const KPL_MAGIC: [u8; 4] = [0xF3, 0x89, 0x9A, 0xC2];
+const DIGEST_LEN: usize = 16;
+
+/// User record of a Kinesis record, after deaggregation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserRecord {
+    pub partition_key: String,
+    pub explicit_hash_key: Option<String>,
+    pub data: Vec<u8>,
+    /// Sequence number of the Kinesis record
+    pub sequence_number: String,
+    /// Index in the aggregated record, 0 if the record is not aggregated
+    pub sub_sequence_number: u64,
+    pub aggregated: bool,
+}
+
+/// Splits a record produced by the KPL into its user records.
+///
+/// A record without the magic number, or whose MD5 does not match, is returned as a single
+/// user record as is, like the KCL does.
+pub fn deaggregate(record: &Record) -> Result<Vec<UserRecord>, Error> {
+    let data = record.data().as_ref();
+    let Some(message) = aggregated_message(data) else {
+        return Ok(vec![UserRecord {
+            partition_key: record.partition_key().to_string(),
+            explicit_hash_key: None,
+            data: data.to_vec(),
+            sequence_number: record.sequence_number().to_string(),
+            sub_sequence_number: 0,
+            aggregated: false,
+        }]);
+    };
+
+    let aggregated = AggregatedRecord::decode(message)?;
+    aggregated
+        .records
+        .into_iter()
+        .enumerate()
+        .map(|(i, user_record)| {
+            let partition_key = aggregated
+                .partition_key_table
+                .get(user_record.partition_key_index as usize)
+                .ok_or_else(|| invalid("partition_key_index is out of the table"))?;
+            let explicit_hash_key = user_record
+                .explicit_hash_key_index
+                .map(|index| {
+                    aggregated
+                        .explicit_hash_key_table
+                        .get(index as usize)
+                        .ok_or_else(|| invalid("explicit_hash_key_index is out of the table"))
+                })
+                .transpose()?;
+            Ok(UserRecord {
+                partition_key: partition_key.clone(),
+                explicit_hash_key: explicit_hash_key.cloned(),
+                data: user_record.data,
+                sequence_number: record.sequence_number().to_string(),
+                sub_sequence_number: i as u64,
+                aggregated: true,
+            })
+        })
+        .collect()
+}
+
+/// [`deaggregate`] for all records of a GetRecords response, in order.
+pub fn deaggregate_records(records: &[Record]) -> Result<Vec<UserRecord>, Error> {
+    let mut user_records = Vec::with_capacity(records.len());
+    for record in records {
+        user_records.extend(deaggregate(record)?);
+    }
+    Ok(user_records)
+}
+
+/// Protobuf message of an aggregated record, None if `data` is not aggregated.
+fn aggregated_message(data: &[u8]) -> Option<&[u8]> {
+    let body = data.strip_prefix(&KPL_MAGIC)?;
+    if body.len() < DIGEST_LEN {
+        return None;
+    }
+    let (message, digest) = body.split_at(body.len() - DIGEST_LEN);
+    (Md5::digest(message).as_slice() == digest).then_some(message)
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidAggregatedRecord(message.to_string())
+}
+
+#[derive(Debug, Default)]
+struct AggregatedRecord {
+    partition_key_table: Vec<String>,
+    explicit_hash_key_table: Vec<String>,
+    records: Vec<AggregatedUserRecord>,
+}
+
+#[derive(Debug, Default)]
+struct AggregatedUserRecord {
+    partition_key_index: u64,
+    explicit_hash_key_index: Option<u64>,
+    data: Vec<u8>,
+}
+
+impl AggregatedRecord {
+    fn decode(message: &[u8]) -> Result<Self, Error> {
+        let mut aggregated = AggregatedRecord::default();
+        let mut reader = ProtobufReader(message);
+        while let Some((field, value)) = reader.next_field()? {
+            match (field, value) {
+                (1, Value::Bytes(b)) => aggregated.partition_key_table.push(utf8(b)?),
+                (2, Value::Bytes(b)) => aggregated.explicit_hash_key_table.push(utf8(b)?),
+                (3, Value::Bytes(b)) => aggregated.records.push(AggregatedUserRecord::decode(b)?),
+                _ => {}
+            }
+        }
+        Ok(aggregated)
+    }
+}
+
+impl AggregatedUserRecord {
+    fn decode(message: &[u8]) -> Result<Self, Error> {
+        let mut user_record = AggregatedUserRecord::default();
+        let mut reader = ProtobufReader(message);
+        // tags (4) は使わない
+        while let Some((field, value)) = reader.next_field()? {
+            match (field, value) {
+                (1, Value::Varint(v)) => user_record.partition_key_index = v,
+                (2, Value::Varint(v)) => user_record.explicit_hash_key_index = Some(v),
+                (3, Value::Bytes(b)) => user_record.data = b.to_vec(),
+                _ => {}
+            }
+        }
+        Ok(user_record)
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Result<String, Error> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| invalid("key is not UTF-8"))
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Reader of the protobuf wire format, enough for AggregatedRecord.
+struct ProtobufReader<'a>(&'a [u8]);
+
+impl<'a> ProtobufReader<'a> {
+    fn next_field(&mut self) -> Result<Option<(u64, Value<'a>)>, Error> {
+        if self.0.is_empty() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = self.varint()?;
+                Value::Bytes(self.take(len as usize)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed
+            }
+            wire_type => {
+                return Err(Error::InvalidAggregatedRecord(format!(
+                    "unsupported wire type {wire_type}"
+                )));
+            }
+        };
+        Ok(Some((key >> 3, value)))
+    }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self
+                .0
+                .split_first()
+                .ok_or_else(|| invalid("truncated varint"))?;
+            self.0 = rest;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("varint is too long"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(invalid("truncated field"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_kinesis::primitives::Blob;
+
+    use super::*;
+
+    fn field(buf: &mut Vec<u8>, field: u8, bytes: &[u8]) {
+        buf.push(field << 3 | 2);
+        buf.push(bytes.len() as u8);
+        buf.extend_from_slice(bytes);
+    }
+
+    fn record(data: Vec<u8>) -> Record {
+        Record::builder()
+            .sequence_number("49590338271490256608559692538361571095921575989136588898")
+            .partition_key("pk")
+            .data(Blob::new(data))
+            .build()
+            .unwrap()
+    }
+
+    fn aggregated_data() -> Vec<u8> {
+        let mut message = Vec::new();
+        field(&mut message, 1, b"user-1");
+        field(&mut message, 1, b"user-2");
+        field(&mut message, 2, b"12345");
+        // partition_key_index=0, data="a"
+        field(&mut message, 3, &[0x08, 0x00, 0x1A, 0x01, b'a']);
+        // partition_key_index=1, explicit_hash_key_index=0, data="bc", tag
+        field(
+            &mut message,
+            3,
+            &[
+                0x08, 0x01, 0x10, 0x00, 0x1A, 0x02, b'b', b'c', 0x22, 0x03, 0x0A, 0x01, b'k',
+            ],
+        );
+
+        let mut data = KPL_MAGIC.to_vec();
+        data.extend_from_slice(&message);
+        data.extend_from_slice(&Md5::digest(&message));
+        data
+    }
+
+    #[test]
+    fn test_deaggregate() {
+        let user_records = deaggregate(&record(aggregated_data())).unwrap();
+        assert_eq!(user_records.len(), 2);
+        assert_eq!(user_records[0].partition_key, "user-1");
+        assert_eq!(user_records[0].explicit_hash_key, None);
+        assert_eq!(user_records[0].data, b"a");
+        assert_eq!(user_records[1].partition_key, "user-2");
+        assert_eq!(user_records[1].explicit_hash_key.as_deref(), Some("12345"));
+        assert_eq!(user_records[1].data, b"bc");
+        assert_eq!(user_records[1].sub_sequence_number, 1);
+        assert!(user_records[1].aggregated);
+
+        // MD5 が一致しなければ集約されていないレコードとして扱う
+        let mut data = aggregated_data();
+        *data.last_mut().unwrap() ^= 0xFF;
+        let user_records = deaggregate(&record(data.clone())).unwrap();
+        assert_eq!(user_records.len(), 1);
+        assert_eq!(user_records[0].partition_key, "pk");
+        assert_eq!(user_records[0].data, data);
+        assert!(!user_records[0].aggregated);
+
+        let user_records =
+            deaggregate_records(&[record(b"plain".to_vec()), record(aggregated_data())]).unwrap();
+        assert_eq!(user_records.len(), 3);
+        assert_eq!(user_records[0].data, b"plain");
+    }
+
+    #[test]
+    fn test_deaggregate_invalid() {
+        let mut message = Vec::new();
+        // partition_key_index=1 だがテーブルは空
+        field(&mut message, 3, &[0x08, 0x01, 0x1A, 0x01, b'a']);
+        let mut data = KPL_MAGIC.to_vec();
+        data.extend_from_slice(&message);
+        data.extend_from_slice(&Md5::digest(&message));
+        assert!(matches!(
+            deaggregate(&record(data)),
+            Err(Error::InvalidAggregatedRecord(_))
+        ));
+    }
+}
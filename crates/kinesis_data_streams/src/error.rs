@@ -1,3 +1,6 @@
+use aws_sdk_kinesis::{error::ProvideErrorMetadata, operation::RequestId};
+pub use aws_utils_core::error::AwsUtilError;
+use aws_utils_core::error::is_retryable_sdk_error;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,10 +14,59 @@ pub enum Error {
     #[error("EntryOverItem {0}")]
     EntryOverItem(String),
 
+    #[error("ValidationError: {0}")]
+    ValidationError(String),
+
     #[error(transparent)]
     AwsSdk(#[from] Box<aws_sdk_kinesis::Error>),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[error("InvalidAggregatedRecord: {0}")]
+    InvalidAggregatedRecord(String),
+
+    /// Records of an automatic flush of `BatchProducer::push` still failing after the retries.
+    #[error("RecordsFailed: {} records were not written", .0.len())]
+    RecordsFailed(Vec<crate::producer::FailedRecord>),
+
+    #[error("Timeout {0}")]
+    Timeout(#[from] tokio::time::error::Elapsed),
+
+    #[cfg(feature = "firehose")]
+    #[error(transparent)]
+    Firehose(#[from] aws_utils_firehose::error::Error),
 }
 
 pub(crate) fn from_aws_sdk_error(e: impl Into<aws_sdk_kinesis::Error>) -> Error {
     Error::AwsSdk(Box::new(e.into()))
 }
+
+impl AwsUtilError for Error {
+    fn error_code(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.code(),
+            #[cfg(feature = "firehose")]
+            Error::Firehose(e) => e.error_code(),
+            _ => None,
+        }
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.request_id(),
+            #[cfg(feature = "firehose")]
+            Error::Firehose(e) => e.aws_request_id(),
+            _ => None,
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::AwsSdk(e) => is_retryable_sdk_error(e.as_ref()),
+            #[cfg(feature = "firehose")]
+            Error::Firehose(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+}
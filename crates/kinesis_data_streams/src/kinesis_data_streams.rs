@@ -1,10 +1,13 @@
 use aws_sdk_kinesis::{
     operation::{put_record::PutRecordOutput, put_records::PutRecordsOutput},
     primitives::Blob,
-    types::PutRecordsRequestEntry,
+    types::{PutRecordsRequestEntry, Shard},
 };
 
-use crate::error::{Error, from_aws_sdk_error};
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    shard::explicit_hash_key_for_shard,
+};
 
 pub async fn add_record(
     client: &aws_sdk_kinesis::Client,
@@ -23,6 +26,49 @@ pub async fn add_record(
         .map_err(from_aws_sdk_error)
 }
 
+/// Puts a record with SequenceNumberForOrdering, so its sequence number is greater than
+/// `sequence_number_for_ordering` (the sequence number of the previous record of the
+/// partition key).
+pub async fn add_record_ordered(
+    client: &aws_sdk_kinesis::Client,
+    stream_name: impl Into<String>,
+    partition_key: impl Into<String>,
+    data: impl Into<Vec<u8>>,
+    sequence_number_for_ordering: Option<impl Into<String>>,
+) -> Result<PutRecordOutput, Error> {
+    let blob = Blob::new(data);
+    client
+        .put_record()
+        .stream_name(stream_name)
+        .partition_key(partition_key)
+        .set_sequence_number_for_ordering(sequence_number_for_ordering.map(Into::into))
+        .data(blob)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Puts a record into `shard` regardless of `partition_key` by setting an explicit hash key.
+/// `shard` can be taken from [`crate::shard::list_open_shards`].
+pub async fn add_record_to_shard(
+    client: &aws_sdk_kinesis::Client,
+    stream_name: impl Into<String>,
+    shard: &Shard,
+    partition_key: impl Into<String>,
+    data: impl Into<Vec<u8>>,
+) -> Result<PutRecordOutput, Error> {
+    let blob = Blob::new(data);
+    client
+        .put_record()
+        .stream_name(stream_name)
+        .partition_key(partition_key)
+        .explicit_hash_key(explicit_hash_key_for_shard(shard)?)
+        .data(blob)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
 pub async fn add_records(
     client: &aws_sdk_kinesis::Client,
     stream_name: impl Into<String>,
@@ -108,6 +154,58 @@ mod tests {
         mock.assert_async().await;
     }
 
+    #[tokio::test]
+    async fn test_add_record_to_shard() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.PutRecord")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"ExplicitHashKey": "170141183460469231731687303715884105728"}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(
+                r#"{
+                "SequenceNumber": "12345",
+                "ShardId": "shardId-000000000001"
+            }"#,
+            )
+            .create_async()
+            .await;
+
+        let shard = Shard::builder()
+            .shard_id("shardId-000000000001")
+            .hash_key_range(
+                aws_sdk_kinesis::types::HashKeyRange::builder()
+                    .starting_hash_key("170141183460469231731687303715884105728")
+                    .ending_hash_key("340282366920938463463374607431768211455")
+                    .build()
+                    .unwrap(),
+            )
+            .sequence_number_range(
+                aws_sdk_kinesis::types::SequenceNumberRange::builder()
+                    .starting_sequence_number("0")
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        let client = make_client(Some(server.url()), None, None).await;
+        let output = add_record_to_shard(
+            &client,
+            "test-stream",
+            &shard,
+            "test-partition",
+            "test-data".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(output.shard_id(), "shardId-000000000001");
+        mock.assert_async().await;
+    }
+
     #[tokio::test]
     async fn test_add_records_success() {
         let mut server = Server::new_async().await;
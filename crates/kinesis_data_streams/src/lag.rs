@@ -0,0 +1,281 @@
+use std::{collections::HashMap, future::Future, time::Duration};
+
+use aws_sdk_kinesis::{Client, types::ShardIteratorType};
+
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    shard::list_open_shards,
+};
+
+/// Where a consumer keeps the sequence number of the last processed record of each shard.
+pub trait CheckpointStore: Send + Sync {
+    /// None if the consumer has not processed any record of the shard yet.
+    fn get_checkpoint(
+        &self,
+        shard_id: &str,
+    ) -> impl Future<Output = Result<Option<String>, Error>> + Send;
+}
+
+/// Checkpoints by shard ID.
+impl CheckpointStore for HashMap<String, String> {
+    fn get_checkpoint(
+        &self,
+        shard_id: &str,
+    ) -> impl Future<Output = Result<Option<String>, Error>> + Send {
+        let checkpoint = self.get(shard_id).cloned();
+        async move { Ok(checkpoint) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShardLag {
+    pub shard_id: String,
+    pub checkpoint: Option<String>,
+    /// How far the next record after the checkpoint is behind the tip of the shard.
+    /// 0 if the consumer has caught up.
+    pub millis_behind_latest: i64,
+}
+
+impl ShardLag {
+    pub fn lag(&self) -> Duration {
+        Duration::from_millis(self.millis_behind_latest.max(0) as u64)
+    }
+}
+
+/// Lag of the open shards of a stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LagSummary {
+    pub shards: Vec<ShardLag>,
+}
+
+impl LagSummary {
+    pub fn max_lag(&self) -> Duration {
+        self.shards
+            .iter()
+            .map(ShardLag::lag)
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn mean_lag(&self) -> Duration {
+        if self.shards.is_empty() {
+            return Duration::ZERO;
+        }
+        self.shards.iter().map(ShardLag::lag).sum::<Duration>() / self.shards.len() as u32
+    }
+
+    /// Shards lagging more than `threshold`, e.g. to scale out the consumer workers.
+    pub fn shards_behind(&self, threshold: Duration) -> impl Iterator<Item = &ShardLag> {
+        self.shards.iter().filter(move |s| s.lag() > threshold)
+    }
+}
+
+/// Lag of the consumer of each open shard, probed with GetRecords of one record after the
+/// checkpoint in `checkpoint_store` (from TRIM_HORIZON without a checkpoint).
+///
+/// The probe counts toward the 5 GetRecords calls per second of each shard shared with the
+/// consumers, so call it at intervals of seconds or more.
+pub async fn get_shard_lag(
+    client: &Client,
+    stream_name: impl Into<String>,
+    checkpoint_store: &impl CheckpointStore,
+) -> Result<LagSummary, Error> {
+    let stream_name = stream_name.into();
+    let mut summary = LagSummary::default();
+    for shard in list_open_shards(client, &stream_name).await? {
+        let checkpoint = checkpoint_store.get_checkpoint(shard.shard_id()).await?;
+        let millis_behind_latest =
+            probe_millis_behind_latest(client, &stream_name, shard.shard_id(), checkpoint.clone())
+                .await?;
+        summary.shards.push(ShardLag {
+            shard_id: shard.shard_id,
+            checkpoint,
+            millis_behind_latest,
+        });
+    }
+    Ok(summary)
+}
+
+async fn probe_millis_behind_latest(
+    client: &Client,
+    stream_name: &str,
+    shard_id: &str,
+    checkpoint: Option<String>,
+) -> Result<i64, Error> {
+    let shard_iterator_type = if checkpoint.is_some() {
+        ShardIteratorType::AfterSequenceNumber
+    } else {
+        ShardIteratorType::TrimHorizon
+    };
+    let output = client
+        .get_shard_iterator()
+        .stream_name(stream_name)
+        .shard_id(shard_id)
+        .shard_iterator_type(shard_iterator_type)
+        .set_starting_sequence_number(checkpoint)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    let shard_iterator = output
+        .shard_iterator
+        .ok_or_else(|| Error::ValidationError(format!("shard iterator is missing: {shard_id}")))?;
+    let output = client
+        .get_records()
+        .shard_iterator(shard_iterator)
+        .limit(1)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(output.millis_behind_latest.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+
+    fn shard_lag(shard_id: &str, millis_behind_latest: i64) -> ShardLag {
+        ShardLag {
+            shard_id: shard_id.to_string(),
+            checkpoint: None,
+            millis_behind_latest,
+        }
+    }
+
+    #[test]
+    fn test_lag_summary() {
+        let summary = LagSummary {
+            shards: vec![
+                shard_lag("shardId-000000000000", 0),
+                shard_lag("shardId-000000000001", 3000),
+                shard_lag("shardId-000000000002", 6000),
+            ],
+        };
+        assert_eq!(summary.max_lag(), Duration::from_secs(6));
+        assert_eq!(summary.mean_lag(), Duration::from_secs(3));
+        assert_eq!(
+            summary
+                .shards_behind(Duration::from_secs(3))
+                .map(|s| s.shard_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["shardId-000000000002"]
+        );
+        assert_eq!(LagSummary::default().max_lag(), Duration::ZERO);
+        assert_eq!(LagSummary::default().mean_lag(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_get_shard_lag() {
+        let mut server = mockito::Server::new_async().await;
+        let describe = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.DescribeStream")
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_body(
+                r#"{"StreamDescription":{
+                    "StreamName":"my-stream",
+                    "StreamARN":"arn:aws:kinesis:ap-northeast-1:123456789012:stream/my-stream",
+                    "StreamStatus":"ACTIVE",
+                    "Shards":[{
+                        "ShardId":"shardId-000000000000",
+                        "HashKeyRange":{"StartingHashKey":"0","EndingHashKey":"1"},
+                        "SequenceNumberRange":{"StartingSequenceNumber":"0"}
+                    },{
+                        "ShardId":"shardId-000000000001",
+                        "HashKeyRange":{"StartingHashKey":"2","EndingHashKey":"3"},
+                        "SequenceNumberRange":{"StartingSequenceNumber":"0"}
+                    }],
+                    "HasMoreShards":false,
+                    "RetentionPeriodHours":24,
+                    "StreamCreationTimestamp":1700000000,
+                    "EnhancedMonitoring":[]
+                }}"#,
+            )
+            .create_async()
+            .await;
+        let iterator_after_checkpoint = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.GetShardIterator")
+            .match_body(Matcher::PartialJsonString(
+                r#"{
+                    "ShardId":"shardId-000000000000",
+                    "ShardIteratorType":"AFTER_SEQUENCE_NUMBER",
+                    "StartingSequenceNumber":"100"
+                }"#
+                .to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_body(r#"{"ShardIterator":"iterator-0"}"#)
+            .create_async()
+            .await;
+        let iterator_trim_horizon = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.GetShardIterator")
+            .match_body(Matcher::PartialJsonString(
+                r#"{
+                    "ShardId":"shardId-000000000001",
+                    "ShardIteratorType":"TRIM_HORIZON"
+                }"#
+                .to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_body(r#"{"ShardIterator":"iterator-1"}"#)
+            .create_async()
+            .await;
+        let records_0 = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.GetRecords")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"ShardIterator":"iterator-0","Limit":1}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_body(r#"{"Records":[],"NextShardIterator":"next-0","MillisBehindLatest":0}"#)
+            .create_async()
+            .await;
+        let records_1 = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.GetRecords")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"ShardIterator":"iterator-1","Limit":1}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_body(
+                r#"{"Records":[{
+                    "SequenceNumber":"1",
+                    "Data":"ZGF0YQ==",
+                    "PartitionKey":"key"
+                }],"NextShardIterator":"next-1","MillisBehindLatest":45000}"#,
+            )
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        let checkpoints = HashMap::from([("shardId-000000000000".to_string(), "100".to_string())]);
+
+        let summary = get_shard_lag(&client, "my-stream", &checkpoints)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary.shards,
+            vec![
+                ShardLag {
+                    shard_id: "shardId-000000000000".to_string(),
+                    checkpoint: Some("100".to_string()),
+                    millis_behind_latest: 0,
+                },
+                shard_lag("shardId-000000000001", 45000),
+            ]
+        );
+        assert_eq!(summary.max_lag(), Duration::from_secs(45));
+        describe.assert_async().await;
+        iterator_after_checkpoint.assert_async().await;
+        iterator_trim_horizon.assert_async().await;
+        records_0.assert_async().await;
+        records_1.assert_async().await;
+    }
+}
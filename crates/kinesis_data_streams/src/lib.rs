@@ -6,11 +6,19 @@ use aws_config::{
 };
 use aws_sdk_kinesis::{Client, config::SharedInterceptor};
 
+pub mod aggregation;
 pub mod builder;
 pub mod error;
 pub mod kinesis_data_streams;
+pub mod lag;
+pub mod producer;
+pub mod reshard;
+pub mod shard;
+pub mod stream;
 
 pub use aws_sdk_kinesis;
+#[cfg(feature = "metrics")]
+pub use aws_utils_core::metrics;
 
 pub async fn make_client_with_timeout_default(endpoint_url: Option<String>) -> Client {
     make_client_with_timeout(
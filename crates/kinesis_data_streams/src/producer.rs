@@ -0,0 +1,808 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use aws_sdk_kinesis::{Client, primitives::Blob, types::PutRecordsRequestEntry};
+use aws_utils_core::retry::backoff_delay;
+
+use crate::{
+    error::{AwsUtilError, Error},
+    kinesis_data_streams::{add_record, add_record_ordered, add_records},
+};
+
+const SPOOL_EXTENSION: &str = "rec";
+
+/// Max records of PutRecords.
+const MAX_BATCH_RECORDS: usize = 500;
+/// Max total size of PutRecords, including the partition keys.
+const MAX_BATCH_SIZE: usize = 5 * 1024 * 1024;
+/// Max size of a record, including the partition key.
+const MAX_RECORD_SIZE: usize = 1024 * 1024;
+
+/// Where a record was delivered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delivery {
+    Kinesis {
+        shard_id: String,
+        sequence_number: String,
+    },
+    Firehose {
+        record_id: String,
+    },
+    /// File in the spool directory, to be sent later with [`drain_spool`].
+    Spool(PathBuf),
+}
+
+/// Events passed to the hook of [`DualProducer::on_event`], e.g. for metrics.
+#[derive(Debug)]
+pub enum ProducerEvent<'a> {
+    /// A retryable Kinesis error, before waiting for the next attempt.
+    KinesisRetry {
+        attempt: u32,
+        error: &'a Error,
+    },
+    /// Kinesis failed beyond the retry budget and the record goes to a fallback.
+    Fallback {
+        error: &'a Error,
+    },
+    /// The Firehose fallback failed and the record goes to the spool if configured.
+    FirehoseFailed {
+        error: &'a Error,
+    },
+    Delivered(&'a Delivery),
+}
+
+type EventHook = Arc<dyn Fn(ProducerEvent<'_>) + Send + Sync>;
+
+/// Producer writing to Kinesis Data Streams, falling back to a Firehose delivery stream
+/// (feature `firehose`) and then to a local spool directory when Kinesis is throttled or
+/// unavailable beyond the retry budget, so records are not lost during shard limit incidents.
+///
+/// Non retryable Kinesis errors (e.g. validation) are returned without fallback.
+#[derive(Clone)]
+pub struct DualProducer {
+    client: Client,
+    stream_name: String,
+    max_attempts: u32,
+    base_delay: Duration,
+    #[cfg(feature = "firehose")]
+    firehose: Option<(aws_utils_firehose::aws_sdk_firehose::Client, String)>,
+    spool_dir: Option<PathBuf>,
+    on_event: Option<EventHook>,
+}
+
+impl DualProducer {
+    /// Retries Kinesis 3 times from 100ms by default.
+    pub fn new(client: Client, stream_name: impl Into<String>) -> Self {
+        Self {
+            client,
+            stream_name: stream_name.into(),
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            #[cfg(feature = "firehose")]
+            firehose: None,
+            spool_dir: None,
+            on_event: None,
+        }
+    }
+
+    /// Attempts to Kinesis before falling back, waiting from `base_delay` doubling each time
+    /// with jitter (see [`backoff_delay`]).
+    pub fn retry(mut self, max_attempts: u32, base_delay: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.base_delay = base_delay;
+        self
+    }
+
+    #[cfg(feature = "firehose")]
+    pub fn firehose(
+        mut self,
+        client: aws_utils_firehose::aws_sdk_firehose::Client,
+        delivery_stream_name: impl Into<String>,
+    ) -> Self {
+        self.firehose = Some((client, delivery_stream_name.into()));
+        self
+    }
+
+    /// Directory to write records to when every other destination fails.
+    pub fn spool_dir(mut self, spool_dir: impl Into<PathBuf>) -> Self {
+        self.spool_dir = Some(spool_dir.into());
+        self
+    }
+
+    pub fn on_event(
+        mut self,
+        on_event: impl Fn(ProducerEvent<'_>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_event = Some(Arc::new(on_event));
+        self
+    }
+
+    fn emit(&self, event: ProducerEvent<'_>) {
+        if let Some(on_event) = &self.on_event {
+            on_event(event);
+        }
+    }
+
+    pub async fn put(
+        &self,
+        partition_key: impl Into<String>,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<Delivery, Error> {
+        let partition_key = partition_key.into();
+        let data = data.into();
+        let error = match self.put_kinesis(&partition_key, &data).await {
+            Ok(delivery) => {
+                self.emit(ProducerEvent::Delivered(&delivery));
+                return Ok(delivery);
+            }
+            Err(e) if !e.is_retryable() => return Err(e),
+            Err(e) => e,
+        };
+        self.emit(ProducerEvent::Fallback { error: &error });
+        tracing::warn!(stream_name = %self.stream_name, %error, "kinesis put failed, falling back");
+
+        let error = match self.put_firehose(&data).await {
+            Some(Ok(delivery)) => {
+                self.emit(ProducerEvent::Delivered(&delivery));
+                return Ok(delivery);
+            }
+            Some(Err(e)) => {
+                self.emit(ProducerEvent::FirehoseFailed { error: &e });
+                e
+            }
+            None => error,
+        };
+
+        let Some(spool_dir) = &self.spool_dir else {
+            return Err(error);
+        };
+        let delivery = Delivery::Spool(write_spool(spool_dir, &partition_key, &data).await?);
+        self.emit(ProducerEvent::Delivered(&delivery));
+        Ok(delivery)
+    }
+
+    async fn put_kinesis(&self, partition_key: &str, data: &[u8]) -> Result<Delivery, Error> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match add_record(&self.client, &self.stream_name, partition_key, data).await {
+                Ok(output) => {
+                    return Ok(Delivery::Kinesis {
+                        shard_id: output.shard_id,
+                        sequence_number: output.sequence_number,
+                    });
+                }
+                Err(e) if attempt < self.max_attempts && e.is_retryable() => {
+                    self.emit(ProducerEvent::KinesisRetry { attempt, error: &e });
+                    tokio::time::sleep(backoff_delay(self.base_delay, attempt)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    #[cfg(feature = "firehose")]
+    async fn put_firehose(&self, data: &[u8]) -> Option<Result<Delivery, Error>> {
+        let (client, delivery_stream_name) = self.firehose.as_ref()?;
+        let result = aws_utils_firehose::firehose::put_record(client, delivery_stream_name, data)
+            .await
+            .map(|output| Delivery::Firehose {
+                record_id: output.record_id,
+            })
+            .map_err(Error::from);
+        Some(result)
+    }
+
+    #[cfg(not(feature = "firehose"))]
+    async fn put_firehose(&self, _data: &[u8]) -> Option<Result<Delivery, Error>> {
+        None
+    }
+}
+
+type SequenceSlot = Arc<tokio::sync::Mutex<Option<String>>>;
+
+/// Producer guaranteeing the order of the records of each partition key: puts of a
+/// partition key are sent one at a time, each with the sequence number of the previous
+/// record as SequenceNumberForOrdering. Puts of different partition keys run concurrently.
+///
+/// Throughput per partition key is one PutRecord round trip at a time. Retryable errors are
+/// retried before the next record of the key is sent; a record failing beyond the retries
+/// is returned as an error and the next record is ordered after the last delivered one.
+#[derive(Clone)]
+pub struct OrderedProducer {
+    client: Client,
+    stream_name: String,
+    max_attempts: u32,
+    base_delay: Duration,
+    // パーティションキーごとの最後のシーケンス番号。ロックを保持している間は同じキーの put を待たせる
+    last_sequence_numbers: Arc<Mutex<HashMap<String, SequenceSlot>>>,
+}
+
+impl OrderedProducer {
+    /// Retries 3 times from 100ms by default.
+    pub fn new(client: Client, stream_name: impl Into<String>) -> Self {
+        Self {
+            client,
+            stream_name: stream_name.into(),
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            last_sequence_numbers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Attempts of PutRecord for a record, waiting from `base_delay` doubling each time
+    /// with jitter (see [`backoff_delay`]).
+    pub fn retry(mut self, max_attempts: u32, base_delay: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Waits for the previous put of the partition key, then puts the record after it.
+    pub async fn put(
+        &self,
+        partition_key: impl Into<String>,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<Delivery, Error> {
+        let partition_key = partition_key.into();
+        let data = data.into();
+        let slot = self
+            .last_sequence_numbers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(partition_key.clone())
+            .or_default()
+            .clone();
+        let mut last_sequence_number = slot.lock().await;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match add_record_ordered(
+                &self.client,
+                &self.stream_name,
+                &partition_key,
+                data.clone(),
+                last_sequence_number.clone(),
+            )
+            .await
+            {
+                Ok(output) => {
+                    *last_sequence_number = Some(output.sequence_number.clone());
+                    return Ok(Delivery::Kinesis {
+                        shard_id: output.shard_id,
+                        sequence_number: output.sequence_number,
+                    });
+                }
+                Err(e) if attempt < self.max_attempts && e.is_retryable() => {
+                    tokio::time::sleep(backoff_delay(self.base_delay, attempt)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Sequence number of the last record delivered for the partition key.
+    pub async fn last_sequence_number(&self, partition_key: &str) -> Option<String> {
+        let slot = self
+            .last_sequence_numbers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(partition_key)
+            .cloned()?;
+        slot.lock().await.clone()
+    }
+
+    /// Forgets the partition key, e.g. once its stream of records is complete, so the
+    /// producer does not keep every partition key it has seen. Must not be called while a
+    /// put of the partition key is in flight, or the next put is not ordered after it.
+    pub fn forget(&self, partition_key: &str) {
+        self.last_sequence_numbers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(partition_key);
+    }
+}
+
+/// Record rejected by every attempt of a flush of [`BatchProducer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedRecord {
+    /// ID returned by [`BatchProducer::push`].
+    pub id: u64,
+    pub partition_key: String,
+    pub data: Vec<u8>,
+    /// e.g. `ProvisionedThroughputExceededException`
+    pub error_code: Option<String>,
+}
+
+/// Metrics and outcome of a flush of [`BatchProducer`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlushReport {
+    pub records: usize,
+    /// Size of the data and the partition keys.
+    pub bytes: usize,
+    /// PutRecords calls retrying failed records.
+    pub retries: u32,
+    /// IDs of the records written to the stream, to acknowledge upstream.
+    pub delivered: Vec<u64>,
+    pub failed: Vec<FailedRecord>,
+    pub duration: Duration,
+}
+
+type FlushHook = Arc<dyn Fn(FlushReport) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+struct PendingRecord {
+    id: u64,
+    partition_key: String,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+struct Buffer {
+    records: Vec<PendingRecord>,
+    size: usize,
+    next_id: u64,
+}
+
+impl Buffer {
+    fn take(&mut self) -> Vec<PendingRecord> {
+        self.size = 0;
+        std::mem::take(&mut self.records)
+    }
+}
+
+/// Producer buffering records and writing them with PutRecords when a pushed record does not
+/// fit in the limits of a call (500 records or 5 MiB), or on [`flush`](Self::flush). Records
+/// failed by PutRecords are retried.
+///
+/// Each flush is reported to [`on_flush`](Self::on_flush) with its metrics and the IDs
+/// of the delivered records, e.g. to put CloudWatch metrics and acknowledge the records
+/// upstream only once they are written (at least once delivery).
+#[derive(Clone)]
+pub struct BatchProducer {
+    client: Client,
+    stream_name: String,
+    max_attempts: u32,
+    base_delay: Duration,
+    buffer: Arc<Mutex<Buffer>>,
+    on_flush: Option<FlushHook>,
+}
+
+impl BatchProducer {
+    /// Retries failed records 3 times from 100ms by default.
+    pub fn new(client: Client, stream_name: impl Into<String>) -> Self {
+        Self {
+            client,
+            stream_name: stream_name.into(),
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            buffer: Arc::new(Mutex::new(Buffer::default())),
+            on_flush: None,
+        }
+    }
+
+    /// Attempts of PutRecords for a record, waiting from `base_delay` doubling each time
+    /// with jitter (see [`backoff_delay`]).
+    pub fn retry(mut self, max_attempts: u32, base_delay: Duration) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Called after each flush, before [`push`](Self::push) or [`flush`](Self::flush)
+    /// returns.
+    pub fn on_flush<F, Fut>(mut self, on_flush: F) -> Self
+    where
+        F: Fn(FlushReport) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_flush = Some(Arc::new(move |report| Box::pin(on_flush(report))));
+        self
+    }
+
+    /// Buffers a record and returns its ID, flushing the buffer first if it is full.
+    ///
+    /// If records of that flush still fail after the retries, they are returned in
+    /// `Error::RecordsFailed` (after [`on_flush`](Self::on_flush) is called) and the pushed record
+    /// is not buffered, so it can be pushed again.
+    pub async fn push(
+        &self,
+        partition_key: impl Into<String>,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<u64, Error> {
+        let partition_key = partition_key.into();
+        let data = data.into();
+        let size = partition_key.len() + data.len();
+        if size > MAX_RECORD_SIZE {
+            return Err(Error::ValidationError(format!(
+                "record size {size} exceeds {MAX_RECORD_SIZE} bytes"
+            )));
+        }
+        loop {
+            let full = {
+                let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+                if buffer.size + size > MAX_BATCH_SIZE || buffer.records.len() >= MAX_BATCH_RECORDS
+                {
+                    buffer.take()
+                } else {
+                    let id = buffer.next_id;
+                    buffer.next_id += 1;
+                    buffer.size += size;
+                    buffer.records.push(PendingRecord {
+                        id,
+                        partition_key,
+                        data,
+                    });
+                    return Ok(id);
+                }
+            };
+            // 同時に push された場合は、書き込み後もまだ満杯のことがあるので確認し直す
+            let report = self.flush_records(full).await?;
+            if !report.failed.is_empty() {
+                return Err(Error::RecordsFailed(report.failed));
+            }
+        }
+    }
+
+    /// Writes the buffered records. Records still failing after the retries are in
+    /// `failed` of the report, and are not buffered again.
+    pub async fn flush(&self) -> Result<FlushReport, Error> {
+        let records = self.buffer.lock().unwrap_or_else(|e| e.into_inner()).take();
+        self.flush_records(records).await
+    }
+
+    async fn flush_records(&self, records: Vec<PendingRecord>) -> Result<FlushReport, Error> {
+        if records.is_empty() {
+            return Ok(FlushReport::default());
+        }
+        let started = Instant::now();
+        let mut report = FlushReport {
+            records: records.len(),
+            bytes: records
+                .iter()
+                .map(|r| r.partition_key.len() + r.data.len())
+                .sum(),
+            ..Default::default()
+        };
+        let mut pending = records;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let entries = pending
+                .iter()
+                .map(|r| {
+                    PutRecordsRequestEntry::builder()
+                        .partition_key(&r.partition_key)
+                        .data(Blob::new(r.data.clone()))
+                        .build()
+                        .map_err(Box::new)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let (failed, retryable): (Vec<_>, bool) =
+                match add_records(&self.client, &self.stream_name, entries).await {
+                    Ok(output) => {
+                        let mut failed = Vec::new();
+                        for (record, result) in pending.into_iter().zip(output.records) {
+                            match result.error_code {
+                                Some(error_code) => failed.push((record, Some(error_code))),
+                                None => report.delivered.push(record.id),
+                            }
+                        }
+                        (failed, true)
+                    }
+                    // 呼び出し全体の失敗はすべてのレコードの失敗として扱う
+                    Err(e) => {
+                        let error_code = e.error_code().map(ToString::to_string);
+                        let failed = pending
+                            .into_iter()
+                            .map(|r| (r, error_code.clone()))
+                            .collect();
+                        (failed, e.is_retryable())
+                    }
+                };
+            if failed.is_empty() {
+                break;
+            }
+            if !retryable || attempt >= self.max_attempts {
+                report.failed = failed
+                    .into_iter()
+                    .map(|(r, error_code)| FailedRecord {
+                        id: r.id,
+                        partition_key: r.partition_key,
+                        data: r.data,
+                        error_code,
+                    })
+                    .collect();
+                break;
+            }
+            report.retries += 1;
+            tokio::time::sleep(backoff_delay(self.base_delay, attempt)).await;
+            pending = failed.into_iter().map(|(r, _)| r).collect();
+        }
+        report.duration = started.elapsed();
+        if let Some(on_flush) = &self.on_flush {
+            on_flush(report.clone()).await;
+        }
+        Ok(report)
+    }
+}
+
+// ファイル形式: パーティションキー長 (u16 BE) + パーティションキー + データ
+async fn write_spool(spool_dir: &Path, partition_key: &str, data: &[u8]) -> Result<PathBuf, Error> {
+    tokio::fs::create_dir_all(spool_dir).await?;
+    let key = partition_key.as_bytes();
+    let key_len = u16::try_from(key.len())
+        .map_err(|_| Error::ValidationError("partition key is too long".to_string()))?;
+    let mut buf = Vec::with_capacity(2 + key.len() + data.len());
+    buf.extend_from_slice(&key_len.to_be_bytes());
+    buf.extend_from_slice(key);
+    buf.extend_from_slice(data);
+
+    // UUID v7 なのでファイル名順が書き込み順になる
+    let path = spool_dir.join(format!("{}.{SPOOL_EXTENSION}", uuid::Uuid::now_v7()));
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, buf).await?;
+    tokio::fs::rename(&tmp, &path).await?;
+    Ok(path)
+}
+
+async fn read_spool(path: &Path) -> Result<(String, Vec<u8>), Error> {
+    let buf = tokio::fs::read(path).await?;
+    let invalid = || Error::ValidationError(format!("invalid spool file: {}", path.display()));
+    let (key_len, rest) = buf.split_first_chunk::<2>().ok_or_else(invalid)?;
+    let key_len = u16::from_be_bytes(*key_len) as usize;
+    if rest.len() < key_len {
+        return Err(invalid());
+    }
+    let (key, data) = rest.split_at(key_len);
+    let key = String::from_utf8(key.to_vec()).map_err(|_| invalid())?;
+    Ok((key, data.to_vec()))
+}
+
+/// Sends the records in the spool directory to Kinesis in the order they were written,
+/// deleting each file after it is sent. Stops at the first error and returns the number sent.
+pub async fn drain_spool(
+    client: &Client,
+    stream_name: impl Into<String>,
+    spool_dir: impl AsRef<Path>,
+) -> Result<usize, Error> {
+    let stream_name = stream_name.into();
+    let mut paths = vec![];
+    let mut entries = tokio::fs::read_dir(spool_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_some_and(|e| e == SPOOL_EXTENSION) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    for (i, path) in paths.iter().enumerate() {
+        let (partition_key, data) = read_spool(path).await?;
+        if let Err(e) = add_record(client, &stream_name, partition_key, data).await {
+            tracing::warn!(sent = i, error = %e, "drain_spool stopped");
+            return Err(e);
+        }
+        tokio::fs::remove_file(path).await?;
+    }
+    Ok(paths.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use aws_sdk_kinesis::config::{BehaviorVersion, Credentials, Region, retry::RetryConfig};
+    use mockito::Server;
+
+    use super::*;
+
+    // SDK 側のリトライは無効にして DualProducer のリトライだけを確認する
+    fn make_client(endpoint_url: String) -> Client {
+        let config = aws_sdk_kinesis::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .endpoint_url(endpoint_url)
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .retry_config(RetryConfig::disabled())
+            .build();
+        Client::from_conf(config)
+    }
+
+    fn spool_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{name}-{}", uuid::Uuid::now_v7()))
+    }
+
+    #[tokio::test]
+    async fn test_batch_producer_retries_failed_records() {
+        let mut server = Server::new_async().await;
+        let first = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.PutRecords")
+            .match_body(mockito::Matcher::Regex(r#""key-2""#.to_string()))
+            .with_status(200)
+            .with_body(
+                r#"{"FailedRecordCount":1,"Records":[
+                    {"ErrorCode":"ProvisionedThroughputExceededException","ErrorMessage":"Rate exceeded"},
+                    {"SequenceNumber":"2","ShardId":"shardId-000000000000"}
+                ]}"#,
+            )
+            .create_async()
+            .await;
+        // 失敗した key-1 だけを再送する
+        let retry = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.PutRecords")
+            .match_body(mockito::Matcher::Regex(
+                r#""Records":\[\{[^{}]*"key-1"[^{}]*\}\]"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(
+                r#"{"FailedRecordCount":0,"Records":[
+                    {"SequenceNumber":"3","ShardId":"shardId-000000000000"}
+                ]}"#,
+            )
+            .create_async()
+            .await;
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let on_flush = {
+            let reports = reports.clone();
+            move |report: FlushReport| {
+                let reports = reports.clone();
+                async move { reports.lock().unwrap().push(report) }
+            }
+        };
+        let producer = BatchProducer::new(make_client(server.url()), "stream")
+            .retry(2, Duration::from_millis(1))
+            .on_flush(on_flush);
+
+        let id1 = producer.push("key-1", b"a".to_vec()).await.unwrap();
+        let id2 = producer.push("key-2", b"bc".to_vec()).await.unwrap();
+        let report = producer.flush().await.unwrap();
+
+        first.assert_async().await;
+        retry.assert_async().await;
+        assert_eq!(report.records, 2);
+        assert_eq!(report.bytes, 13);
+        assert_eq!(report.retries, 1);
+        assert_eq!(report.delivered, vec![id2, id1]);
+        assert!(report.failed.is_empty());
+        assert_eq!(*reports.lock().unwrap(), vec![report]);
+        assert_eq!(producer.flush().await.unwrap(), FlushReport::default());
+    }
+
+    #[tokio::test]
+    async fn test_batch_producer_push_returns_failed_records() {
+        let mut server = Server::new_async().await;
+        let put = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.PutRecords")
+            .with_status(400)
+            .with_header("x-amzn-ErrorType", "InvalidArgumentException")
+            .with_body(r#"{"__type":"InvalidArgumentException","message":"invalid"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let producer = BatchProducer::new(make_client(server.url()), "stream");
+
+        for i in 0..MAX_BATCH_RECORDS {
+            producer
+                .push(format!("key-{i}"), b"a".to_vec())
+                .await
+                .unwrap();
+        }
+        // 満杯のバッファの書き込みに失敗したら、失敗したレコードを返し新しいレコードは積まない
+        let Err(Error::RecordsFailed(failed)) = producer.push("key-new", b"b".to_vec()).await
+        else {
+            panic!("push did not fail");
+        };
+        assert_eq!(failed.len(), MAX_BATCH_RECORDS);
+        assert_eq!(
+            failed[0].error_code.as_deref(),
+            Some("InvalidArgumentException")
+        );
+        assert_eq!(producer.flush().await.unwrap(), FlushReport::default());
+        put.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_ordered_producer_chains_sequence_numbers() {
+        let mut server = Server::new_async().await;
+        let first = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.PutRecord")
+            .with_status(200)
+            .with_body(r#"{"SequenceNumber":"1","ShardId":"shardId-000000000000"}"#)
+            .create_async()
+            .await;
+        let producer = OrderedProducer::new(make_client(server.url()), "stream");
+
+        producer.put("account-1", b"a".to_vec()).await.unwrap();
+        first.assert_async().await;
+        first.remove_async().await;
+        assert_eq!(
+            producer.last_sequence_number("account-1").await.as_deref(),
+            Some("1")
+        );
+
+        // 同じパーティションキーの次のレコードは前のシーケンス番号の後に順序付ける
+        let second = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.PutRecord")
+            .match_body(mockito::Matcher::Regex(
+                r#""SequenceNumberForOrdering":"1""#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(r#"{"SequenceNumber":"2","ShardId":"shardId-000000000000"}"#)
+            .create_async()
+            .await;
+        let delivery = producer.put("account-1", b"b".to_vec()).await.unwrap();
+        second.assert_async().await;
+        assert_eq!(
+            delivery,
+            Delivery::Kinesis {
+                shard_id: "shardId-000000000000".to_string(),
+                sequence_number: "2".to_string(),
+            }
+        );
+        assert_eq!(producer.last_sequence_number("account-2").await, None);
+
+        producer.forget("account-1");
+        assert_eq!(producer.last_sequence_number("account-1").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_spool_and_drain() {
+        let mut server = Server::new_async().await;
+        let throttled = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.PutRecord")
+            .with_status(400)
+            .with_body(
+                r#"{"__type":"ProvisionedThroughputExceededException","message":"Rate exceeded"}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let client = make_client(server.url());
+        let dir = spool_dir("dual-producer");
+        let retries = Arc::new(AtomicUsize::new(0));
+        let counter = retries.clone();
+        let producer = DualProducer::new(client.clone(), "stream")
+            .retry(2, Duration::from_millis(1))
+            .spool_dir(&dir)
+            .on_event(move |event| {
+                if let ProducerEvent::KinesisRetry { .. } = event {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+            });
+
+        let Delivery::Spool(path) = producer.put("key-1", b"hello".to_vec()).await.unwrap() else {
+            panic!("not spooled");
+        };
+        throttled.assert_async().await;
+        assert_eq!(retries.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            read_spool(&path).await.unwrap(),
+            ("key-1".to_string(), b"hello".to_vec())
+        );
+
+        throttled.remove_async().await;
+        let ok = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.PutRecord")
+            .with_status(200)
+            .with_body(r#"{"SequenceNumber":"1","ShardId":"shardId-000000000000"}"#)
+            .create_async()
+            .await;
+        assert_eq!(drain_spool(&client, "stream", &dir).await.unwrap(), 1);
+        ok.assert_async().await;
+        assert!(!path.exists());
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
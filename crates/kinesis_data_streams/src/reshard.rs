@@ -0,0 +1,294 @@
+use std::time::Duration;
+
+use aws_sdk_kinesis::{Client, types::Shard};
+
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    shard::{hash_key_range, list_open_shards},
+    stream::wait_for_stream_active,
+};
+
+/// A SplitShard or MergeShards call of [`reshard_to_count`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReshardStep {
+    Split {
+        shard_id: String,
+        new_starting_hash_key: u128,
+    },
+    Merge {
+        shard_id: String,
+        adjacent_shard_id: String,
+    },
+}
+
+/// Splits an open shard into two shards with halves of its hash key range.
+/// The stream is UPDATING until the split completes, see
+/// [`wait_for_stream_active`](crate::stream::wait_for_stream_active).
+pub async fn split_shard_evenly(
+    client: &Client,
+    stream_name: impl Into<String>,
+    shard_id: &str,
+) -> Result<ReshardStep, Error> {
+    let stream_name = stream_name.into();
+    let shards = list_open_shards(client, &stream_name).await?;
+    let shard = shards
+        .iter()
+        .find(|shard| shard.shard_id() == shard_id)
+        .ok_or_else(|| Error::ValidationError(format!("open shard is not found: {shard_id}")))?;
+    let step = ReshardStep::Split {
+        shard_id: shard_id.to_string(),
+        new_starting_hash_key: midpoint(shard)?,
+    };
+    execute_step(client, &stream_name, &step).await?;
+    Ok(step)
+}
+
+/// Merges two open shards with adjacent hash key ranges into one shard.
+/// The stream is UPDATING until the merge completes.
+pub async fn merge_adjacent_shards(
+    client: &Client,
+    stream_name: impl Into<String>,
+    shard_id: &str,
+    adjacent_shard_id: &str,
+) -> Result<ReshardStep, Error> {
+    let step = ReshardStep::Merge {
+        shard_id: shard_id.to_string(),
+        adjacent_shard_id: adjacent_shard_id.to_string(),
+    };
+    execute_step(client, &stream_name.into(), &step).await?;
+    Ok(step)
+}
+
+/// Splits or merges shards one at a time, waiting until the stream is ACTIVE before
+/// each step, until the stream has `target_shard_count` open shards. Returns the executed
+/// steps.
+///
+/// The shard with the widest hash key range is split, and the adjacent pair with the
+/// narrowest combined range is merged, so the ranges stay as even as possible. Use
+/// UpdateShardCount instead to scale a uniformly split stream.
+pub async fn reshard_to_count(
+    client: &Client,
+    stream_name: impl Into<String>,
+    target_shard_count: usize,
+    timeout_duration: Duration,
+    check_duration: Duration,
+) -> Result<Vec<ReshardStep>, Error> {
+    let stream_name = stream_name.into();
+    tokio::time::timeout(timeout_duration, async {
+        let mut steps = vec![];
+        loop {
+            wait_for_stream_active(client, &stream_name, timeout_duration, check_duration).await?;
+            let shards = list_open_shards(client, &stream_name).await?;
+            let Some(step) = next_reshard_step(&shards, target_shard_count)? else {
+                return Ok(steps);
+            };
+            tracing::info!(?step, "reshard {stream_name}");
+            execute_step(client, &stream_name, &step).await?;
+            steps.push(step);
+        }
+    })
+    .await?
+}
+
+/// Next step from the open shards toward `target_shard_count`, or None when the stream
+/// already has that many shards.
+pub fn next_reshard_step(
+    open_shards: &[Shard],
+    target_shard_count: usize,
+) -> Result<Option<ReshardStep>, Error> {
+    if target_shard_count == 0 {
+        return Err(Error::ValidationError(
+            "target shard count must be at least 1".to_string(),
+        ));
+    }
+    let mut ranges = open_shards
+        .iter()
+        .map(|shard| Ok((shard, hash_key_range(shard)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    ranges.sort_by_key(|(_, (starting, _))| *starting);
+
+    if ranges.len() < target_shard_count {
+        let (shard, _) = ranges
+            .iter()
+            .max_by_key(|(_, (starting, ending))| ending - starting)
+            .ok_or_else(|| Error::ValidationError("no open shards".to_string()))?;
+        return Ok(Some(ReshardStep::Split {
+            shard_id: shard.shard_id().to_string(),
+            new_starting_hash_key: midpoint(shard)?,
+        }));
+    }
+    if ranges.len() > target_shard_count {
+        // 隣接するシャードのうち、結合後のハッシュキー範囲が最も狭い組をマージする
+        let (shard, adjacent) = ranges
+            .windows(2)
+            .filter(|pair| pair[0].1.1.checked_add(1) == Some(pair[1].1.0))
+            .min_by_key(|pair| pair[1].1.1 - pair[0].1.0)
+            .map(|pair| (pair[0].0, pair[1].0))
+            .ok_or_else(|| Error::ValidationError("no adjacent open shards".to_string()))?;
+        return Ok(Some(ReshardStep::Merge {
+            shard_id: shard.shard_id().to_string(),
+            adjacent_shard_id: adjacent.shard_id().to_string(),
+        }));
+    }
+    Ok(None)
+}
+
+/// Starting hash key of the upper half of the shard.
+fn midpoint(shard: &Shard) -> Result<u128, Error> {
+    let (starting, ending) = hash_key_range(shard)?;
+    if starting == ending {
+        return Err(Error::ValidationError(format!(
+            "hash key range of {} cannot be split",
+            shard.shard_id()
+        )));
+    }
+    Ok(starting + (ending - starting) / 2 + 1)
+}
+
+async fn execute_step(client: &Client, stream_name: &str, step: &ReshardStep) -> Result<(), Error> {
+    match step {
+        ReshardStep::Split {
+            shard_id,
+            new_starting_hash_key,
+        } => {
+            client
+                .split_shard()
+                .stream_name(stream_name)
+                .shard_to_split(shard_id)
+                .new_starting_hash_key(new_starting_hash_key.to_string())
+                .send()
+                .await
+                .map_err(from_aws_sdk_error)?;
+        }
+        ReshardStep::Merge {
+            shard_id,
+            adjacent_shard_id,
+        } => {
+            client
+                .merge_shards()
+                .stream_name(stream_name)
+                .shard_to_merge(shard_id)
+                .adjacent_shard_to_merge(adjacent_shard_id)
+                .send()
+                .await
+                .map_err(from_aws_sdk_error)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_kinesis::types::{HashKeyRange, SequenceNumberRange};
+    use mockito::Matcher;
+
+    use super::*;
+
+    const HALF: u128 = u128::MAX / 2;
+    const QUARTER: u128 = u128::MAX / 4;
+
+    fn shard(shard_id: &str, starting: u128, ending: u128) -> Shard {
+        Shard::builder()
+            .shard_id(shard_id)
+            .hash_key_range(
+                HashKeyRange::builder()
+                    .starting_hash_key(starting.to_string())
+                    .ending_hash_key(ending.to_string())
+                    .build()
+                    .unwrap(),
+            )
+            .sequence_number_range(
+                SequenceNumberRange::builder()
+                    .starting_sequence_number("0")
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_next_reshard_step() {
+        let shards = vec![
+            shard("shardId-000000000002", QUARTER + 1, HALF),
+            shard("shardId-000000000001", 0, QUARTER),
+            shard("shardId-000000000003", HALF + 1, u128::MAX),
+        ];
+
+        assert_eq!(next_reshard_step(&shards, 3).unwrap(), None);
+        assert_eq!(
+            next_reshard_step(&shards, 4).unwrap(),
+            Some(ReshardStep::Split {
+                shard_id: "shardId-000000000003".to_string(),
+                new_starting_hash_key: HALF + 1 + (u128::MAX - HALF - 1) / 2 + 1,
+            })
+        );
+        assert_eq!(
+            next_reshard_step(&shards, 2).unwrap(),
+            Some(ReshardStep::Merge {
+                shard_id: "shardId-000000000001".to_string(),
+                adjacent_shard_id: "shardId-000000000002".to_string(),
+            })
+        );
+        assert!(next_reshard_step(&shards, 0).is_err());
+
+        // 範囲が隣接していないシャードはマージできない
+        let shards = vec![
+            shard("shardId-000000000001", 0, QUARTER),
+            shard("shardId-000000000003", HALF + 1, u128::MAX),
+        ];
+        assert!(next_reshard_step(&shards, 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_split_shard_evenly() {
+        let mut server = mockito::Server::new_async().await;
+        let describe = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.DescribeStream")
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_body(format!(
+                r#"{{"StreamDescription":{{
+                    "StreamName":"my-stream",
+                    "StreamARN":"arn:aws:kinesis:ap-northeast-1:123456789012:stream/my-stream",
+                    "StreamStatus":"ACTIVE",
+                    "Shards":[{{
+                        "ShardId":"shardId-000000000000",
+                        "HashKeyRange":{{"StartingHashKey":"0","EndingHashKey":"{}"}},
+                        "SequenceNumberRange":{{"StartingSequenceNumber":"0"}}
+                    }}],
+                    "HasMoreShards":false,
+                    "RetentionPeriodHours":24,
+                    "StreamCreationTimestamp":1700000000,
+                    "EnhancedMonitoring":[]
+                }}}}"#,
+                u128::MAX
+            ))
+            .create_async()
+            .await;
+        let split = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.SplitShard")
+            .match_body(Matcher::PartialJsonString(format!(
+                r#"{{
+                    "StreamName":"my-stream",
+                    "ShardToSplit":"shardId-000000000000",
+                    "NewStartingHashKey":"{}"
+                }}"#,
+                1_u128 << 127
+            )))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_body("{}")
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        split_shard_evenly(&client, "my-stream", "shardId-000000000000")
+            .await
+            .unwrap();
+        describe.assert_async().await;
+        split.assert_async().await;
+    }
+}
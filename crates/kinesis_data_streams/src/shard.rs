@@ -0,0 +1,141 @@
+use aws_sdk_kinesis::{Client, types::Shard};
+use md5::{Digest, Md5};
+
+use crate::error::{Error, from_aws_sdk_error};
+
+// パーティションキーは MD5 で 128 bit の整数にマッピングされ、そのハッシュ値を含むシャードに書き込まれる
+// https://docs.aws.amazon.com/kinesis/latest/APIReference/API_PutRecord.html
+
+/// Hash key that Kinesis derives from `partition_key`.
+pub fn shard_hash_key_for(partition_key: &str) -> u128 {
+    let digest: [u8; 16] = Md5::digest(partition_key.as_bytes()).into();
+    u128::from_be_bytes(digest)
+}
+
+/// Explicit hash key that pins a record to `shard` (the starting hash key of the shard).
+pub fn explicit_hash_key_for_shard(shard: &Shard) -> Result<String, Error> {
+    let (starting, _) = hash_key_range(shard)?;
+    Ok(starting.to_string())
+}
+
+/// Returns the shard whose hash key range contains `hash_key`.
+pub fn shard_for_hash_key(shards: &[Shard], hash_key: u128) -> Option<&Shard> {
+    shards.iter().find(|shard| {
+        hash_key_range(shard)
+            .is_ok_and(|(starting, ending)| (starting..=ending).contains(&hash_key))
+    })
+}
+
+/// Returns the shard that a record with `partition_key` is written to.
+pub fn shard_for_partition_key<'a>(shards: &'a [Shard], partition_key: &str) -> Option<&'a Shard> {
+    shard_for_hash_key(shards, shard_hash_key_for(partition_key))
+}
+
+pub(crate) fn hash_key_range(shard: &Shard) -> Result<(u128, u128), Error> {
+    let range = shard.hash_key_range().ok_or_else(|| {
+        Error::ValidationError(format!("hash key range is missing: {}", shard.shard_id()))
+    })?;
+    let parse = |key: &str| {
+        key.parse::<u128>()
+            .map_err(|_| Error::ValidationError(format!("invalid hash key: {key}")))
+    };
+    Ok((
+        parse(range.starting_hash_key())?,
+        parse(range.ending_hash_key())?,
+    ))
+}
+
+/// Lists the open shards of the stream with DescribeStream.
+/// Closed shards (after resharding) have an ending sequence number and are excluded.
+pub async fn list_open_shards(
+    client: &Client,
+    stream_name: impl Into<String>,
+) -> Result<Vec<Shard>, Error> {
+    let stream_name = stream_name.into();
+    let mut shards = vec![];
+    let mut exclusive_start_shard_id: Option<String> = None;
+    loop {
+        let output = client
+            .describe_stream()
+            .stream_name(&stream_name)
+            .set_exclusive_start_shard_id(exclusive_start_shard_id.take())
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        let Some(description) = output.stream_description else {
+            break;
+        };
+        exclusive_start_shard_id = description.shards.last().map(|s| s.shard_id.clone());
+        shards.extend(description.shards.into_iter().filter(|s| {
+            s.sequence_number_range()
+                .is_some_and(|r| r.ending_sequence_number().is_none())
+        }));
+        if !description.has_more_shards || exclusive_start_shard_id.is_none() {
+            break;
+        }
+    }
+    Ok(shards)
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_kinesis::types::{HashKeyRange, SequenceNumberRange};
+
+    use super::*;
+
+    fn shard(shard_id: &str, starting: u128, ending: u128) -> Shard {
+        Shard::builder()
+            .shard_id(shard_id)
+            .hash_key_range(
+                HashKeyRange::builder()
+                    .starting_hash_key(starting.to_string())
+                    .ending_hash_key(ending.to_string())
+                    .build()
+                    .unwrap(),
+            )
+            .sequence_number_range(
+                SequenceNumberRange::builder()
+                    .starting_sequence_number("0")
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_shard_hash_key_for() {
+        // md5("") = d41d8cd98f00b204e9800998ecf8427e
+        assert_eq!(
+            shard_hash_key_for(""),
+            0xd41d8cd98f00b204e9800998ecf8427e_u128
+        );
+    }
+
+    #[test]
+    fn test_shard_for_hash_key() {
+        let half = u128::MAX / 2;
+        let shards = vec![
+            shard("shardId-000000000000", 0, half),
+            shard("shardId-000000000001", half + 1, u128::MAX),
+        ];
+
+        assert_eq!(
+            shard_for_hash_key(&shards, 0).unwrap().shard_id(),
+            "shardId-000000000000"
+        );
+        assert_eq!(
+            shard_for_hash_key(&shards, u128::MAX).unwrap().shard_id(),
+            "shardId-000000000001"
+        );
+
+        let explicit_hash_key = explicit_hash_key_for_shard(&shards[1]).unwrap();
+        assert_eq!(explicit_hash_key, (half + 1).to_string());
+        assert_eq!(
+            shard_for_hash_key(&shards, explicit_hash_key.parse().unwrap())
+                .unwrap()
+                .shard_id(),
+            "shardId-000000000001"
+        );
+    }
+}
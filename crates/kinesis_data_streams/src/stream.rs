@@ -0,0 +1,283 @@
+use std::{collections::HashMap, time::Duration};
+
+use aws_sdk_kinesis::{
+    Client,
+    types::{StreamDescriptionSummary, StreamMode, StreamModeDetails, StreamStatus},
+};
+
+use crate::error::{Error, from_aws_sdk_error};
+
+/// Min and max retention period of a stream in hours.
+pub const MIN_RETENTION_PERIOD_HOURS: i32 = 24;
+pub const MAX_RETENTION_PERIOD_HOURS: i32 = 8760;
+
+pub async fn describe_stream_summary(
+    client: &Client,
+    stream_name: impl Into<String>,
+) -> Result<StreamDescriptionSummary, Error> {
+    let output = client
+        .describe_stream_summary()
+        .stream_name(stream_name)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    output
+        .stream_description_summary
+        .ok_or_else(|| Error::ValidationError("stream_description_summary is None".to_string()))
+}
+
+/// Waits until the stream is ACTIVE after creation or an update, checking every
+/// `check_duration`.
+pub async fn wait_for_stream_active(
+    client: &Client,
+    stream_name: impl Into<String>,
+    timeout_duration: Duration,
+    check_duration: Duration,
+) -> Result<StreamDescriptionSummary, Error> {
+    let stream_name = stream_name.into();
+    tokio::time::timeout(timeout_duration, async {
+        loop {
+            let summary = describe_stream_summary(client, &stream_name).await?;
+            if summary.stream_status == StreamStatus::Active {
+                return Ok(summary);
+            }
+            tokio::time::sleep(check_duration).await;
+        }
+    })
+    .await?
+}
+
+fn validate_retention_period(hours: i32) -> Result<(), Error> {
+    if !(MIN_RETENTION_PERIOD_HOURS..=MAX_RETENTION_PERIOD_HOURS).contains(&hours) {
+        return Err(Error::ValidationError(format!(
+            "retention period must be between {MIN_RETENTION_PERIOD_HOURS} and \
+             {MAX_RETENTION_PERIOD_HOURS} hours: {hours}"
+        )));
+    }
+    Ok(())
+}
+
+/// The stream is UPDATING until the change completes, see [`wait_for_stream_active`].
+pub async fn increase_stream_retention_period(
+    client: &Client,
+    stream_name: impl Into<String>,
+    retention_period_hours: i32,
+) -> Result<(), Error> {
+    validate_retention_period(retention_period_hours)?;
+    client
+        .increase_stream_retention_period()
+        .stream_name(stream_name)
+        .retention_period_hours(retention_period_hours)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+/// Records older than the new retention period become inaccessible immediately.
+pub async fn decrease_stream_retention_period(
+    client: &Client,
+    stream_name: impl Into<String>,
+    retention_period_hours: i32,
+) -> Result<(), Error> {
+    validate_retention_period(retention_period_hours)?;
+    client
+        .decrease_stream_retention_period()
+        .stream_name(stream_name)
+        .retention_period_hours(retention_period_hours)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+/// Increases or decreases the retention period to `retention_period_hours` and waits until
+/// the stream is ACTIVE. Does nothing if the stream already has the period.
+pub async fn set_stream_retention_period(
+    client: &Client,
+    stream_name: impl Into<String>,
+    retention_period_hours: i32,
+    timeout_duration: Duration,
+    check_duration: Duration,
+) -> Result<StreamDescriptionSummary, Error> {
+    let stream_name = stream_name.into();
+    validate_retention_period(retention_period_hours)?;
+    let summary =
+        wait_for_stream_active(client, &stream_name, timeout_duration, check_duration).await?;
+    match retention_period_hours.cmp(&summary.retention_period_hours) {
+        std::cmp::Ordering::Equal => return Ok(summary),
+        std::cmp::Ordering::Greater => {
+            increase_stream_retention_period(client, &stream_name, retention_period_hours).await?
+        }
+        std::cmp::Ordering::Less => {
+            decrease_stream_retention_period(client, &stream_name, retention_period_hours).await?
+        }
+    }
+    wait_for_stream_active(client, stream_name, timeout_duration, check_duration).await
+}
+
+/// Adds or overwrites up to 50 tags of the stream.
+pub async fn add_tags_to_stream(
+    client: &Client,
+    stream_name: impl Into<String>,
+    tags: HashMap<String, String>,
+) -> Result<(), Error> {
+    client
+        .add_tags_to_stream()
+        .stream_name(stream_name)
+        .set_tags(Some(tags))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+pub async fn remove_tags_from_stream(
+    client: &Client,
+    stream_name: impl Into<String>,
+    tag_keys: Vec<String>,
+) -> Result<(), Error> {
+    client
+        .remove_tags_from_stream()
+        .stream_name(stream_name)
+        .set_tag_keys(Some(tag_keys))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+/// All tags of the stream.
+pub async fn list_tags_for_stream(
+    client: &Client,
+    stream_name: impl Into<String>,
+) -> Result<HashMap<String, String>, Error> {
+    let stream_name = stream_name.into();
+    let mut tags = HashMap::new();
+    let mut exclusive_start_tag_key: Option<String> = None;
+    loop {
+        let output = client
+            .list_tags_for_stream()
+            .stream_name(&stream_name)
+            .set_exclusive_start_tag_key(exclusive_start_tag_key.take())
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        exclusive_start_tag_key = output.tags.last().map(|t| t.key.clone());
+        tags.extend(
+            output
+                .tags
+                .into_iter()
+                .map(|t| (t.key, t.value.unwrap_or_default())),
+        );
+        if !output.has_more_tags || exclusive_start_tag_key.is_none() {
+            break;
+        }
+    }
+    Ok(tags)
+}
+
+/// Switches the capacity mode of the stream between on-demand and provisioned, and waits
+/// until the stream is ACTIVE. Does nothing if the stream is already in `stream_mode`.
+///
+/// The mode can be switched twice in 24 hours. A stream switched to provisioned keeps the
+/// shard count it had in on-demand mode.
+pub async fn switch_stream_mode(
+    client: &Client,
+    stream_name: impl Into<String>,
+    stream_mode: StreamMode,
+    timeout_duration: Duration,
+    check_duration: Duration,
+) -> Result<StreamDescriptionSummary, Error> {
+    let stream_name = stream_name.into();
+    let summary =
+        wait_for_stream_active(client, &stream_name, timeout_duration, check_duration).await?;
+    if summary
+        .stream_mode_details()
+        .is_some_and(|d| d.stream_mode == stream_mode)
+    {
+        return Ok(summary);
+    }
+    // UpdateStreamMode はストリーム名ではなく ARN で指定する
+    client
+        .update_stream_mode()
+        .stream_arn(summary.stream_arn)
+        .stream_mode_details(
+            StreamModeDetails::builder()
+                .stream_mode(stream_mode)
+                .build()
+                .map_err(Box::new)?,
+        )
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    wait_for_stream_active(client, stream_name, timeout_duration, check_duration).await
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_switch_stream_mode() {
+        let mut server = mockito::Server::new_async().await;
+        let describe = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.DescribeStreamSummary")
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_body(
+                r#"{"StreamDescriptionSummary":{
+                    "StreamName":"my-stream",
+                    "StreamARN":"arn:aws:kinesis:ap-northeast-1:123456789012:stream/my-stream",
+                    "StreamStatus":"ACTIVE",
+                    "StreamModeDetails":{"StreamMode":"ON_DEMAND"},
+                    "RetentionPeriodHours":24,
+                    "StreamCreationTimestamp":1700000000,
+                    "EnhancedMonitoring":[],
+                    "OpenShardCount":4
+                }}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let update = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "Kinesis_20131202.UpdateStreamMode")
+            .match_body(Matcher::PartialJsonString(
+                r#"{
+                    "StreamARN":"arn:aws:kinesis:ap-northeast-1:123456789012:stream/my-stream",
+                    "StreamModeDetails":{"StreamMode":"PROVISIONED"}
+                }"#
+                .to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/x-amz-json-1.1")
+            .with_body("{}")
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        switch_stream_mode(
+            &client,
+            "my-stream",
+            StreamMode::Provisioned,
+            Duration::from_secs(1),
+            Duration::from_millis(10),
+        )
+        .await
+        .unwrap();
+        describe.assert_async().await;
+        update.assert_async().await;
+    }
+
+    #[test]
+    fn test_validate_retention_period() {
+        assert!(validate_retention_period(24).is_ok());
+        assert!(validate_retention_period(8760).is_ok());
+        assert!(validate_retention_period(23).is_err());
+        assert!(validate_retention_period(8761).is_err());
+    }
+}
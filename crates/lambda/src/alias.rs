@@ -0,0 +1,233 @@
+use std::{collections::HashMap, time::Duration};
+
+use aws_sdk_lambda::{
+    Client, operation::update_alias::UpdateAliasOutput, types::AliasRoutingConfiguration,
+};
+
+use crate::error::{Error, from_aws_sdk_error};
+
+/// Routes `percentage` (0 to 100) of the invocations of the alias to `new_version`, and the
+/// rest to the version the alias points to. At 100, the alias is pointed to `new_version`.
+pub async fn shift_alias_traffic(
+    client: &Client,
+    function_name: impl Into<String>,
+    alias: impl Into<String>,
+    new_version: impl Into<String>,
+    percentage: f64,
+) -> Result<UpdateAliasOutput, Error> {
+    if !(0.0..=100.0).contains(&percentage) {
+        return Err(Error::ValidationError(format!(
+            "percentage must be between 0 and 100: {percentage}"
+        )));
+    }
+    let new_version = new_version.into();
+    let builder = client
+        .update_alias()
+        .function_name(function_name)
+        .name(alias);
+    let builder = if percentage == 100.0 {
+        builder
+            .routing_config(routing_config(&new_version, 0.0))
+            .function_version(new_version)
+    } else {
+        builder.routing_config(routing_config(&new_version, percentage))
+    };
+    builder.send().await.map_err(from_aws_sdk_error)
+}
+
+fn routing_config(new_version: &str, percentage: f64) -> AliasRoutingConfiguration {
+    // 重み 0 の追加バージョンは指定できないので、空にしてルーティングを解除する
+    if percentage == 0.0 {
+        return AliasRoutingConfiguration::builder()
+            .set_additional_version_weights(Some(HashMap::new()))
+            .build();
+    }
+    AliasRoutingConfiguration::builder()
+        .additional_version_weights(new_version, percentage / 100.0)
+        .build()
+}
+
+/// Shifts the traffic of the alias to `new_version` by `steps` (percentages in ascending
+/// order, each greater than 0 and less than 100), waiting `interval` after each step and then
+/// calling `health_check` with the percentage. The alias is pointed to `new_version` once all
+/// steps are healthy.
+///
+/// When a health check fails, the alias is pointed back to the version it pointed to before
+/// the first step, without routing, and `Error::CanaryRolledBack` is returned. When a request
+/// fails after the first step, the alias is restored as well and the error of the request is
+/// returned.
+pub async fn canary_deploy<F, Fut>(
+    client: &Client,
+    function_name: impl Into<String>,
+    alias: impl Into<String>,
+    new_version: impl Into<String>,
+    steps: &[f64],
+    interval: Duration,
+    mut health_check: F,
+) -> Result<UpdateAliasOutput, Error>
+where
+    F: FnMut(f64) -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let function_name = function_name.into();
+    let alias = alias.into();
+    let new_version = new_version.into();
+    if steps.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(Error::ValidationError(
+            "steps must be in ascending order".to_string(),
+        ));
+    }
+    if let Some(step) = steps.iter().find(|step| !(**step > 0.0 && **step < 100.0)) {
+        return Err(Error::ValidationError(format!(
+            "steps must be greater than 0 and less than 100: {step}"
+        )));
+    }
+    let current_version = client
+        .get_alias()
+        .function_name(&function_name)
+        .name(&alias)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?
+        .function_version
+        .ok_or_else(|| Error::ValidationError(format!("alias {alias} has no function version")))?;
+    let mut shifted = false;
+    let result = async {
+        for percentage in steps {
+            shift_alias_traffic(client, &function_name, &alias, &new_version, *percentage).await?;
+            shifted = true;
+            tokio::time::sleep(interval).await;
+            if !health_check(*percentage).await {
+                return Err(Error::CanaryRolledBack {
+                    version: new_version.clone(),
+                    percentage: *percentage,
+                });
+            }
+        }
+        shift_alias_traffic(client, &function_name, &alias, &new_version, 100.0).await
+    }
+    .await;
+    match result {
+        Err(e) if shifted => {
+            // 一部または全部のトラフィックが新バージョンに流れたままにしない
+            client
+                .update_alias()
+                .function_name(&function_name)
+                .name(&alias)
+                .function_version(current_version)
+                .routing_config(routing_config(&new_version, 0.0))
+                .send()
+                .await
+                .map_err(from_aws_sdk_error)?;
+            Err(e)
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routing_config() {
+        let config = routing_config("3", 10.0);
+        assert_eq!(
+            config.additional_version_weights().unwrap().get("3"),
+            Some(&0.1)
+        );
+        assert!(
+            routing_config("3", 0.0)
+                .additional_version_weights()
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_canary_deploy_rolls_back_on_error() {
+        let mut server = mockito::Server::new_async().await;
+        let path = "/2015-03-31/functions/my-function/aliases/live";
+        let alias_body = r#"{"Name":"live","FunctionVersion":"4"}"#;
+        let get = server
+            .mock("GET", path)
+            .with_body(alias_body)
+            .expect(1)
+            .create_async()
+            .await;
+        let first = server
+            .mock("PUT", path)
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"RoutingConfig":{"AdditionalVersionWeights":{"5":0.1}}}"#.to_string(),
+            ))
+            .with_body(alias_body)
+            .expect(1)
+            .create_async()
+            .await;
+        let second = server
+            .mock("PUT", path)
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"RoutingConfig":{"AdditionalVersionWeights":{"5":0.5}}}"#.to_string(),
+            ))
+            .with_status(400)
+            .with_header("x-amzn-ErrorType", "InvalidParameterValueException")
+            .with_body(r#"{"message":"invalid weight"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let rollback = server
+            .mock("PUT", path)
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"FunctionVersion":"4","RoutingConfig":{"AdditionalVersionWeights":{}}}"#
+                    .to_string(),
+            ))
+            .with_body(alias_body)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let result = canary_deploy(
+            &client,
+            "my-function",
+            "live",
+            "5",
+            &[10.0, 50.0],
+            Duration::ZERO,
+            |_| async { true },
+        )
+        .await;
+        let Err(Error::AwsSdk(e)) = result else {
+            panic!("unexpected result: {result:?}");
+        };
+        assert!(matches!(
+            *e,
+            aws_sdk_lambda::Error::InvalidParameterValueException(_)
+        ));
+        get.assert_async().await;
+        first.assert_async().await;
+        second.assert_async().await;
+        rollback.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_canary_deploy_rejects_steps() {
+        let client = crate::make_client(Some("http://localhost:1".to_string()), None, None).await;
+        for steps in [[50.0, 10.0], [10.0, 100.0], [0.0, 10.0]] {
+            let result = canary_deploy(
+                &client,
+                "my-function",
+                "live",
+                "5",
+                &steps,
+                Duration::ZERO,
+                |_| async { true },
+            )
+            .await;
+            assert!(
+                matches!(result, Err(Error::ValidationError(_))),
+                "{steps:?}: {result:?}"
+            );
+        }
+    }
+}
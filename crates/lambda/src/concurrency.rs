@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use aws_sdk_lambda::{
+    Client,
+    operation::{
+        get_provisioned_concurrency_config::GetProvisionedConcurrencyConfigOutput,
+        put_provisioned_concurrency_config::PutProvisionedConcurrencyConfigOutput,
+    },
+    types::ProvisionedConcurrencyStatusEnum,
+};
+
+use crate::error::{Error, from_aws_sdk_error};
+
+/// Reserves `reserved_concurrent_executions` of the concurrency of the account for the
+/// function, which is also its max concurrency. 0 stops all invocations.
+pub async fn put_function_concurrency(
+    client: &Client,
+    function_name: impl Into<String>,
+    reserved_concurrent_executions: i32,
+) -> Result<(), Error> {
+    client
+        .put_function_concurrency()
+        .function_name(function_name)
+        .reserved_concurrent_executions(reserved_concurrent_executions)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+/// Reserved concurrency of the function, None if it uses the unreserved concurrency.
+pub async fn get_function_concurrency(
+    client: &Client,
+    function_name: impl Into<String>,
+) -> Result<Option<i32>, Error> {
+    let output = client
+        .get_function_concurrency()
+        .function_name(function_name)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(output.reserved_concurrent_executions)
+}
+
+pub async fn delete_function_concurrency(
+    client: &Client,
+    function_name: impl Into<String>,
+) -> Result<(), Error> {
+    client
+        .delete_function_concurrency()
+        .function_name(function_name)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+/// Allocates provisioned concurrency to a version or an alias (`qualifier`). The
+/// environments are initialized in the background, see [`wait_for_provisioned_ready`].
+pub async fn put_provisioned_concurrency_config(
+    client: &Client,
+    function_name: impl Into<String>,
+    qualifier: impl Into<String>,
+    provisioned_concurrent_executions: i32,
+) -> Result<PutProvisionedConcurrencyConfigOutput, Error> {
+    client
+        .put_provisioned_concurrency_config()
+        .function_name(function_name)
+        .qualifier(qualifier)
+        .provisioned_concurrent_executions(provisioned_concurrent_executions)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+pub async fn get_provisioned_concurrency_config(
+    client: &Client,
+    function_name: impl Into<String>,
+    qualifier: impl Into<String>,
+) -> Result<GetProvisionedConcurrencyConfigOutput, Error> {
+    client
+        .get_provisioned_concurrency_config()
+        .function_name(function_name)
+        .qualifier(qualifier)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+pub async fn delete_provisioned_concurrency_config(
+    client: &Client,
+    function_name: impl Into<String>,
+    qualifier: impl Into<String>,
+) -> Result<(), Error> {
+    client
+        .delete_provisioned_concurrency_config()
+        .function_name(function_name)
+        .qualifier(qualifier)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+/// Waits until the provisioned concurrency of the version or alias is READY, checking
+/// every `check_duration`. Fails with `Error::ProvisionedConcurrencyFailed` if the
+/// allocation failed, e.g. the account has not enough unreserved concurrency.
+pub async fn wait_for_provisioned_ready(
+    client: &Client,
+    function_name: impl Into<String>,
+    qualifier: impl Into<String>,
+    timeout_duration: Duration,
+    check_duration: Duration,
+) -> Result<GetProvisionedConcurrencyConfigOutput, Error> {
+    let function_name = function_name.into();
+    let qualifier = qualifier.into();
+    tokio::time::timeout(timeout_duration, async {
+        loop {
+            let config =
+                get_provisioned_concurrency_config(client, &function_name, &qualifier).await?;
+            if is_provisioned_ready(&config)? {
+                return Ok(config);
+            }
+            tokio::time::sleep(check_duration).await;
+        }
+    })
+    .await?
+}
+
+fn is_provisioned_ready(config: &GetProvisionedConcurrencyConfigOutput) -> Result<bool, Error> {
+    match config.status() {
+        Some(ProvisionedConcurrencyStatusEnum::Ready) => Ok(true),
+        Some(ProvisionedConcurrencyStatusEnum::Failed) => Err(Error::ProvisionedConcurrencyFailed(
+            config.status_reason().unwrap_or_default().to_string(),
+        )),
+        _ => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_provisioned_ready() {
+        let config = GetProvisionedConcurrencyConfigOutput::builder()
+            .requested_provisioned_concurrent_executions(10)
+            .allocated_provisioned_concurrent_executions(4)
+            .status(ProvisionedConcurrencyStatusEnum::InProgress)
+            .build();
+        assert!(!is_provisioned_ready(&config).unwrap());
+
+        let config = GetProvisionedConcurrencyConfigOutput::builder()
+            .status(ProvisionedConcurrencyStatusEnum::Ready)
+            .build();
+        assert!(is_provisioned_ready(&config).unwrap());
+
+        let config = GetProvisionedConcurrencyConfigOutput::builder()
+            .status(ProvisionedConcurrencyStatusEnum::Failed)
+            .status_reason("not enough concurrency")
+            .build();
+        assert!(matches!(
+            is_provisioned_ready(&config),
+            Err(Error::ProvisionedConcurrencyFailed(reason)) if reason == "not enough concurrency"
+        ));
+    }
+}
@@ -1,3 +1,6 @@
+use aws_sdk_lambda::{error::ProvideErrorMetadata, operation::RequestId};
+pub use aws_utils_core::error::AwsUtilError;
+use aws_utils_core::error::is_retryable_sdk_error;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,8 +13,70 @@ pub enum Error {
 
     #[error("ValidationError: {0}")]
     ValidationError(String),
+
+    #[error("FunctionUpdateFailed: {0}")]
+    FunctionUpdateFailed(String),
+
+    #[error("CanaryRolledBack: version {version} failed the health check at {percentage}%")]
+    CanaryRolledBack { version: String, percentage: f64 },
+
+    #[error("ProvisionedConcurrencyFailed: {0}")]
+    ProvisionedConcurrencyFailed(String),
+
+    #[error("FunctionError: {function_error}: {payload}")]
+    FunctionError {
+        function_error: String,
+        payload: String,
+    },
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error("Timeout {0}")]
+    Timeout(#[from] tokio::time::error::Elapsed),
 }
 
 pub(crate) fn from_aws_sdk_error(e: impl Into<aws_sdk_lambda::Error>) -> Error {
     Error::AwsSdk(Box::new(e.into()))
 }
+
+impl Error {
+    pub fn is_too_many_requests(&self) -> bool {
+        if let Error::AwsSdk(e) = self {
+            matches!(**e, aws_sdk_lambda::Error::TooManyRequestsException(_))
+        } else {
+            false
+        }
+    }
+
+    pub fn is_service_exception(&self) -> bool {
+        if let Error::AwsSdk(e) = self {
+            matches!(**e, aws_sdk_lambda::Error::ServiceException(_))
+        } else {
+            false
+        }
+    }
+}
+
+impl AwsUtilError for Error {
+    fn error_code(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.code(),
+            _ => None,
+        }
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.request_id(),
+            _ => None,
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::AwsSdk(e) => is_retryable_sdk_error(e.as_ref()),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use std::{collections::HashMap, time::Duration};
+
+use aws_sdk_lambda::{
+    Client,
+    operation::{
+        get_function_configuration::GetFunctionConfigurationOutput,
+        update_function_code::UpdateFunctionCodeOutput,
+    },
+    types::{Environment, LastUpdateStatus, Runtime, State},
+};
+
+use crate::error::{Error, from_aws_sdk_error};
+
+/// Typed accessors for the function configuration.
+pub trait FunctionConfigurationExt {
+    fn memory_size_mb(&self) -> Option<u32>;
+
+    fn timeout_duration(&self) -> Option<Duration>;
+
+    /// Empty if the function has no environment variables.
+    fn environment_variables(&self) -> HashMap<String, String>;
+
+    fn environment_variable(&self, name: &str) -> Option<String> {
+        self.environment_variables().remove(name)
+    }
+}
+
+impl FunctionConfigurationExt for GetFunctionConfigurationOutput {
+    fn memory_size_mb(&self) -> Option<u32> {
+        self.memory_size().and_then(|m| u32::try_from(m).ok())
+    }
+
+    fn timeout_duration(&self) -> Option<Duration> {
+        self.timeout()
+            .and_then(|t| u64::try_from(t).ok())
+            .map(Duration::from_secs)
+    }
+
+    fn environment_variables(&self) -> HashMap<String, String> {
+        self.environment()
+            .and_then(|e| e.variables())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+pub async fn get_function_configuration(
+    client: &Client,
+    function_name: impl Into<String>,
+    qualifier: Option<impl Into<String>>,
+) -> Result<GetFunctionConfigurationOutput, Error> {
+    client
+        .get_function_configuration()
+        .function_name(function_name)
+        .set_qualifier(qualifier.map(Into::into))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Points the function to a container image in ECR.
+/// With `publish`, a version is published once the update completes.
+pub async fn update_function_code_from_image(
+    client: &Client,
+    function_name: impl Into<String>,
+    image_uri: impl Into<String>,
+    publish: bool,
+) -> Result<UpdateFunctionCodeOutput, Error> {
+    client
+        .update_function_code()
+        .function_name(function_name)
+        .image_uri(image_uri)
+        .publish(publish)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Desired configuration of [`ensure_function_config`]. Fields left None are not changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionConfigSpec {
+    pub memory_size_mb: Option<u32>,
+    /// Rounded down to seconds.
+    pub timeout: Option<Duration>,
+    /// Replaces all environment variables of the function.
+    pub environment_variables: Option<HashMap<String, String>>,
+    /// Layer version ARNs, in the order they are applied.
+    pub layers: Option<Vec<String>>,
+    pub runtime: Option<Runtime>,
+}
+
+impl FunctionConfigSpec {
+    /// Names of the fields that differ from `configuration`, e.g. `["memory_size", "layers"]`.
+    pub fn changed_fields(
+        &self,
+        configuration: &GetFunctionConfigurationOutput,
+    ) -> Vec<&'static str> {
+        let mut changed = vec![];
+        if self
+            .memory_size_mb
+            .is_some_and(|m| configuration.memory_size_mb() != Some(m))
+        {
+            changed.push("memory_size");
+        }
+        if self.timeout.is_some_and(|t| {
+            configuration.timeout_duration().map(|d| d.as_secs()) != Some(t.as_secs())
+        }) {
+            changed.push("timeout");
+        }
+        if self
+            .environment_variables
+            .as_ref()
+            .is_some_and(|v| configuration.environment_variables() != *v)
+        {
+            changed.push("environment");
+        }
+        if self.layers.as_ref().is_some_and(|layers| {
+            !configuration
+                .layers()
+                .iter()
+                .map(|l| l.arn().unwrap_or_default())
+                .eq(layers.iter().map(String::as_str))
+        }) {
+            changed.push("layers");
+        }
+        if self
+            .runtime
+            .as_ref()
+            .is_some_and(|r| configuration.runtime() != Some(r))
+        {
+            changed.push("runtime");
+        }
+        changed
+    }
+}
+
+/// Updates the configuration of the function to `desired` if it differs, and waits until the
+/// update completes. Only the changed fields are sent, so deploy tools can run it on every
+/// deploy. Returns the names of the changed fields, empty if nothing changed.
+pub async fn ensure_function_config(
+    client: &Client,
+    function_name: impl Into<String>,
+    desired: FunctionConfigSpec,
+    timeout_duration: Duration,
+    check_duration: Duration,
+) -> Result<Vec<&'static str>, Error> {
+    let function_name = function_name.into();
+    // 更新中の関数を更新すると ResourceConflictException になるので完了を待つ
+    let configuration = wait_for_function_updated(
+        client,
+        &function_name,
+        None::<String>,
+        timeout_duration,
+        check_duration,
+    )
+    .await?;
+    let changed = desired.changed_fields(&configuration);
+    if changed.is_empty() {
+        return Ok(changed);
+    }
+    let field = |name| changed.contains(&name);
+    let memory_size = desired
+        .memory_size_mb
+        .filter(|_| field("memory_size"))
+        .map(i32::try_from)
+        .transpose()
+        .map_err(|_| Error::ValidationError("memory_size_mb is too large".to_string()))?;
+    let timeout = desired
+        .timeout
+        .filter(|_| field("timeout"))
+        .map(|t| i32::try_from(t.as_secs()))
+        .transpose()
+        .map_err(|_| Error::ValidationError("timeout is too long".to_string()))?;
+    let environment = desired
+        .environment_variables
+        .filter(|_| field("environment"))
+        .map(|variables| {
+            Environment::builder()
+                .set_variables(Some(variables))
+                .build()
+        });
+    client
+        .update_function_configuration()
+        .function_name(&function_name)
+        .set_memory_size(memory_size)
+        .set_timeout(timeout)
+        .set_environment(environment)
+        .set_layers(desired.layers.filter(|_| field("layers")))
+        .set_runtime(desired.runtime.filter(|_| field("runtime")))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    wait_for_function_updated(
+        client,
+        &function_name,
+        None::<String>,
+        timeout_duration,
+        check_duration,
+    )
+    .await?;
+    Ok(changed)
+}
+
+/// Waits until the function is active and its last update succeeded, checking every
+/// `check_duration`. Returns the configuration after the update.
+pub async fn wait_for_function_updated(
+    client: &Client,
+    function_name: impl Into<String>,
+    qualifier: Option<impl Into<String>>,
+    timeout_duration: Duration,
+    check_duration: Duration,
+) -> Result<GetFunctionConfigurationOutput, Error> {
+    let function_name = function_name.into();
+    let qualifier = qualifier.map(Into::into);
+    tokio::time::timeout(timeout_duration, async {
+        loop {
+            let configuration =
+                get_function_configuration(client, &function_name, qualifier.clone()).await?;
+            if is_function_updated(&configuration)? {
+                return Ok(configuration);
+            }
+            tokio::time::sleep(check_duration).await;
+        }
+    })
+    .await?
+}
+
+fn is_function_updated(configuration: &GetFunctionConfigurationOutput) -> Result<bool, Error> {
+    if configuration.state() == Some(&State::Failed) {
+        return Err(Error::FunctionUpdateFailed(
+            configuration.state_reason().unwrap_or_default().to_string(),
+        ));
+    }
+    match configuration.last_update_status() {
+        Some(LastUpdateStatus::Failed) => Err(Error::FunctionUpdateFailed(
+            configuration
+                .last_update_status_reason()
+                .unwrap_or_default()
+                .to_string(),
+        )),
+        Some(LastUpdateStatus::InProgress) => Ok(false),
+        // 作成直後は Pending のまま LastUpdateStatus が Successful になることがある
+        _ => Ok(configuration.state() != Some(&State::Pending)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_lambda::types::{EnvironmentResponse, Layer};
+
+    use super::*;
+
+    #[test]
+    fn test_function_configuration() {
+        let configuration = GetFunctionConfigurationOutput::builder()
+            .memory_size(1024)
+            .timeout(30)
+            .environment(
+                EnvironmentResponse::builder()
+                    .variables("STAGE", "prod")
+                    .build(),
+            )
+            .state(State::Active)
+            .last_update_status(LastUpdateStatus::InProgress)
+            .build();
+
+        assert_eq!(configuration.memory_size_mb(), Some(1024));
+        assert_eq!(
+            configuration.timeout_duration(),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            configuration.environment_variable("STAGE"),
+            Some("prod".to_string())
+        );
+        assert!(!is_function_updated(&configuration).unwrap());
+
+        let updated = GetFunctionConfigurationOutput::builder()
+            .state(State::Active)
+            .last_update_status(LastUpdateStatus::Successful)
+            .build();
+        assert!(is_function_updated(&updated).unwrap());
+        assert!(updated.environment_variables().is_empty());
+
+        let failed = GetFunctionConfigurationOutput::builder()
+            .state(State::Active)
+            .last_update_status(LastUpdateStatus::Failed)
+            .last_update_status_reason("image not found")
+            .build();
+        assert!(matches!(
+            is_function_updated(&failed),
+            Err(Error::FunctionUpdateFailed(reason)) if reason == "image not found"
+        ));
+    }
+
+    #[test]
+    fn test_changed_fields() {
+        let configuration = GetFunctionConfigurationOutput::builder()
+            .memory_size(512)
+            .timeout(30)
+            .runtime(Runtime::Nodejs20x)
+            .environment(
+                EnvironmentResponse::builder()
+                    .variables("STAGE", "prod")
+                    .build(),
+            )
+            .layers(
+                Layer::builder()
+                    .arn("arn:aws:lambda:ap-northeast-1:123456789012:layer:common:3")
+                    .build(),
+            )
+            .build();
+
+        assert!(
+            FunctionConfigSpec::default()
+                .changed_fields(&configuration)
+                .is_empty()
+        );
+        let unchanged = FunctionConfigSpec {
+            memory_size_mb: Some(512),
+            timeout: Some(Duration::from_millis(30_500)),
+            environment_variables: Some(HashMap::from([("STAGE".to_string(), "prod".to_string())])),
+            layers: Some(vec![
+                "arn:aws:lambda:ap-northeast-1:123456789012:layer:common:3".to_string(),
+            ]),
+            runtime: Some(Runtime::Nodejs20x),
+        };
+        assert!(unchanged.changed_fields(&configuration).is_empty());
+
+        let changed = FunctionConfigSpec {
+            memory_size_mb: Some(1024),
+            environment_variables: Some(HashMap::new()),
+            layers: Some(vec![
+                "arn:aws:lambda:ap-northeast-1:123456789012:layer:common:4".to_string(),
+            ]),
+            runtime: Some(Runtime::Nodejs22x),
+            ..unchanged
+        };
+        assert_eq!(
+            changed.changed_fields(&configuration),
+            vec!["memory_size", "environment", "layers", "runtime"]
+        );
+    }
+}
@@ -1,11 +1,19 @@
+use std::time::{Duration, Instant};
+
 use aws_sdk_lambda::{
     Client,
     operation::invoke::InvokeOutput,
     primitives::Blob,
     types::{InvocationType, LogType},
 };
+use aws_utils_core::retry::backoff_delay;
+use futures_util::{StreamExt, stream};
+use serde::{Serialize, de::DeserializeOwned};
 
-use crate::error::{Error, from_aws_sdk_error};
+use crate::{
+    error::{AwsUtilError, Error, from_aws_sdk_error},
+    log_result::{InvokeOutputExt, InvokeReport},
+};
 
 pub async fn invoke(
     client: &Client,
@@ -28,3 +36,335 @@ pub async fn invoke(
         .await
         .map_err(from_aws_sdk_error)
 }
+
+/// Result of [`can_invoke`], e.g. for a health check at startup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvokePreflight {
+    /// The function exists and may be invoked.
+    Ok,
+    /// The function (or the version / alias) does not exist.
+    FunctionNotFound,
+    /// lambda:InvokeFunction is not allowed, with the error message.
+    AccessDenied(String),
+    /// Throttled, so it could not be checked.
+    Throttled,
+}
+
+impl InvokePreflight {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, InvokePreflight::Ok)
+    }
+}
+
+/// Checks that the function exists and may be invoked, with InvocationType::DryRun so that it
+/// does not run. A missing function, a denied permission and throttling are returned as
+/// [`InvokePreflight`], other errors as `Err`.
+pub async fn can_invoke(
+    client: &Client,
+    function_name: impl Into<String>,
+    qualifier: Option<impl Into<String>>,
+) -> Result<InvokePreflight, Error> {
+    let result = invoke(
+        client,
+        Some(function_name),
+        None::<String>,
+        Some(InvocationType::DryRun),
+        None,
+        None::<Blob>,
+        qualifier,
+    )
+    .await;
+    match result {
+        Ok(_) => Ok(InvokePreflight::Ok),
+        Err(e) => preflight_from_error(e),
+    }
+}
+
+/// Classifies an error of a DryRun call. Other errors are returned as they are.
+fn preflight_from_error(e: Error) -> Result<InvokePreflight, Error> {
+    match e.error_code() {
+        Some("ResourceNotFoundException") => Ok(InvokePreflight::FunctionNotFound),
+        Some("AccessDeniedException" | "AccessDenied") => {
+            Ok(InvokePreflight::AccessDenied(e.to_string()))
+        }
+        _ if e.is_throttling() => Ok(InvokePreflight::Throttled),
+        _ => Err(e),
+    }
+}
+
+pub struct InvokeWithRetryOutput {
+    pub output: InvokeOutput,
+    /// Calls made until one succeeded.
+    pub attempts: u32,
+    /// Duration of the successful call.
+    pub duration: Duration,
+    pub cold_start: bool,
+    pub init_duration: Option<Duration>,
+    /// REPORT line of the LogResult (billed duration, memory used, ...).
+    pub report: Option<InvokeReport>,
+}
+
+/// Invokes with RequestResponse, retrying TooManyRequestsException and ServiceException up to
+/// `max_attempts` calls in total, waiting from `base_delay` doubling each time (capped at 20
+/// seconds, with jitter). Requests LogType::Tail to tell cold starts.
+pub async fn invoke_with_retry(
+    client: &Client,
+    function_name: impl Into<String>,
+    payload: Option<impl Into<Blob>>,
+    qualifier: Option<impl Into<String>>,
+    max_attempts: u32,
+    base_delay: Duration,
+) -> Result<InvokeWithRetryOutput, Error> {
+    let function_name = function_name.into();
+    let payload = payload.map(Into::into);
+    let qualifier = qualifier.map(Into::into);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let start = Instant::now();
+        let result = invoke(
+            client,
+            Some(function_name.clone()),
+            None::<String>,
+            Some(InvocationType::RequestResponse),
+            Some(LogType::Tail),
+            payload.clone(),
+            qualifier.clone(),
+        )
+        .await;
+        let duration = start.elapsed();
+        match result {
+            Ok(output) => {
+                let report = output.report();
+                let init_duration = report.as_ref().and_then(|r| r.init_duration);
+                return Ok(InvokeWithRetryOutput {
+                    output,
+                    attempts,
+                    duration,
+                    cold_start: init_duration.is_some(),
+                    init_duration,
+                    report,
+                });
+            }
+            Err(e)
+                if attempts < max_attempts
+                    && (e.is_too_many_requests() || e.is_service_exception()) =>
+            {
+                tokio::time::sleep(backoff_delay(base_delay, attempts)).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvokeManyOutput<R> {
+    /// Index of the payload and the value returned by the function, by index.
+    pub succeeded: Vec<(usize, R)>,
+    /// Index of the payload and the error, by index.
+    pub failed: Vec<(usize, Error)>,
+}
+
+/// Invokes the function once per payload, serialized as JSON, with RequestResponse and up to
+/// `concurrency` calls at a time, and deserializes the returned values into `R`.
+///
+/// Each call is made with [`invoke_with_retry`]; `max_attempts` of 1 disables retries. Function
+/// errors (FunctionError) are in `failed` as `Error::FunctionError`.
+pub async fn invoke_many<T, R>(
+    client: &Client,
+    function_name: impl Into<String>,
+    payloads: Vec<T>,
+    concurrency: usize,
+    max_attempts: u32,
+    base_delay: Duration,
+) -> InvokeManyOutput<R>
+where
+    T: Serialize,
+    R: DeserializeOwned,
+{
+    let function_name = function_name.into();
+    let results = stream::iter(payloads.into_iter().enumerate())
+        .map(|(index, payload)| {
+            let function_name = &function_name;
+            async move {
+                let result = async {
+                    let payload = serde_json::to_vec(&payload)?;
+                    let output = invoke_with_retry(
+                        client,
+                        function_name,
+                        Some(payload),
+                        None::<String>,
+                        max_attempts,
+                        base_delay,
+                    )
+                    .await?;
+                    parse_invoke_output(output.output)
+                }
+                .await;
+                (index, result)
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut output = InvokeManyOutput {
+        succeeded: vec![],
+        failed: vec![],
+    };
+    for (index, result) in results {
+        match result {
+            Ok(value) => output.succeeded.push((index, value)),
+            Err(e) => output.failed.push((index, e)),
+        }
+    }
+    output.succeeded.sort_by_key(|(index, _)| *index);
+    output.failed.sort_by_key(|(index, _)| *index);
+    output
+}
+
+/// Deserializes the payload into `R`, or returns `Error::FunctionError` if the function failed.
+fn parse_invoke_output<R: DeserializeOwned>(output: InvokeOutput) -> Result<R, Error> {
+    let payload = output.payload.map(Blob::into_inner).unwrap_or_default();
+    if let Some(function_error) = output.function_error {
+        return Err(Error::FunctionError {
+            function_error,
+            payload: String::from_utf8_lossy(&payload).into_owned(),
+        });
+    }
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_lambda::{
+        error::ErrorMetadata,
+        types::error::{ResourceNotFoundException, ServiceException, TooManyRequestsException},
+    };
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    fn sdk_error(error: aws_sdk_lambda::Error) -> Error {
+        Error::AwsSdk(Box::new(error))
+    }
+
+    #[test]
+    fn test_preflight_from_error() {
+        let not_found = ResourceNotFoundException::builder()
+            .message("Function not found")
+            .meta(
+                ErrorMetadata::builder()
+                    .code("ResourceNotFoundException")
+                    .build(),
+            )
+            .build();
+        assert_eq!(
+            preflight_from_error(sdk_error(aws_sdk_lambda::Error::ResourceNotFoundException(
+                not_found
+            )))
+            .unwrap(),
+            InvokePreflight::FunctionNotFound
+        );
+
+        let throttled = TooManyRequestsException::builder()
+            .meta(
+                ErrorMetadata::builder()
+                    .code("TooManyRequestsException")
+                    .build(),
+            )
+            .build();
+        assert_eq!(
+            preflight_from_error(sdk_error(aws_sdk_lambda::Error::TooManyRequestsException(
+                throttled
+            )))
+            .unwrap(),
+            InvokePreflight::Throttled
+        );
+
+        let service = ServiceException::builder()
+            .meta(ErrorMetadata::builder().code("ServiceException").build())
+            .build();
+        assert!(
+            preflight_from_error(sdk_error(aws_sdk_lambda::Error::ServiceException(service)))
+                .is_err()
+        );
+        assert!(preflight_from_error(Error::ValidationError("invalid".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_parse_invoke_output() {
+        let output = InvokeOutput::builder()
+            .status_code(200)
+            .payload(Blob::new(r#"{"score":0.9}"#))
+            .build();
+        let value: Value = parse_invoke_output(output).unwrap();
+        assert_eq!(value, json!({"score": 0.9}));
+
+        let output = InvokeOutput::builder()
+            .status_code(200)
+            .function_error("Unhandled")
+            .payload(Blob::new(r#"{"errorMessage":"boom"}"#))
+            .build();
+        let error = parse_invoke_output::<Value>(output).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::FunctionError { function_error, payload }
+                if function_error == "Unhandled" && payload.contains("boom")
+        ));
+
+        let output = InvokeOutput::builder()
+            .status_code(200)
+            .payload(Blob::new("not json"))
+            .build();
+        assert!(matches!(
+            parse_invoke_output::<Value>(output),
+            Err(Error::Json(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_many() {
+        let mut server = mockito::Server::new_async().await;
+        let path = "/2015-03-31/functions/scorer/invocations";
+        for n in [1, 3] {
+            server
+                .mock("POST", path)
+                .match_body(mockito::Matcher::Json(json!({"n": n})))
+                .with_body(json!({"score": n * 10}).to_string())
+                .expect(1)
+                .create_async()
+                .await;
+        }
+        let failed = server
+            .mock("POST", path)
+            .match_body(mockito::Matcher::Json(json!({"n": 2})))
+            .with_header("X-Amz-Function-Error", "Unhandled")
+            .with_body(r#"{"errorMessage":"boom"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let output: InvokeManyOutput<Value> = invoke_many(
+            &client,
+            "scorer",
+            vec![json!({"n": 1}), json!({"n": 2}), json!({"n": 3})],
+            2,
+            1,
+            Duration::from_millis(10),
+        )
+        .await;
+        assert_eq!(
+            output.succeeded,
+            vec![(0, json!({"score": 10})), (2, json!({"score": 30}))]
+        );
+        assert_eq!(output.failed.len(), 1);
+        assert!(matches!(
+            &output.failed[0],
+            (1, Error::FunctionError { function_error, payload })
+                if function_error == "Unhandled" && payload.contains("boom")
+        ));
+        failed.assert_async().await;
+    }
+}
@@ -1,5 +1,9 @@
+pub mod alias;
+pub mod concurrency;
 pub mod error;
+pub mod function;
 pub mod lambda;
+pub mod log_result;
 
 use std::time::Duration;
 
@@ -9,6 +13,8 @@ use aws_config::{
 };
 pub use aws_sdk_lambda;
 use aws_sdk_lambda::{Client, config::SharedInterceptor};
+#[cfg(feature = "metrics")]
+pub use aws_utils_core::metrics;
 
 pub async fn make_client_with_timeout_default(endpoint_url: Option<String>) -> Client {
     make_client_with_timeout(
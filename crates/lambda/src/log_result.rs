@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use aws_sdk_lambda::operation::invoke::InvokeOutput;
+use base64::{Engine, prelude::BASE64_STANDARD};
+
+/// Values of the REPORT line written at the end of each invocation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvokeReport {
+    pub request_id: Option<String>,
+    pub duration: Duration,
+    /// Duration used for billing, rounded up to 1 ms.
+    pub billed_duration: Duration,
+    pub memory_size_mb: Option<u32>,
+    pub max_memory_used_mb: Option<u32>,
+    /// Only present on a cold start.
+    pub init_duration: Option<Duration>,
+}
+
+/// Helpers for the LogResult of an invocation with `LogType::Tail`.
+///
+/// LogResult holds only the last 4 KB of the log.
+pub trait InvokeOutputExt {
+    /// Decoded LogResult, None if LogType::Tail was not specified.
+    fn log_tail(&self) -> Option<String>;
+
+    fn log_lines(&self) -> Vec<String> {
+        self.log_tail()
+            .map(|log| log.lines().map(ToString::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    fn report(&self) -> Option<InvokeReport> {
+        self.log_lines()
+            .iter()
+            .find_map(|line| parse_report_line(line))
+    }
+}
+
+impl InvokeOutputExt for InvokeOutput {
+    fn log_tail(&self) -> Option<String> {
+        let log = BASE64_STANDARD.decode(self.log_result()?).ok()?;
+        Some(String::from_utf8_lossy(&log).into_owned())
+    }
+}
+
+/// Parses a line like
+/// `REPORT RequestId: xxx\tDuration: 1.23 ms\tBilled Duration: 2 ms\tMemory Size: 128 MB\tMax Memory Used: 64 MB`.
+/// Returns None if the line is not a REPORT line or Duration / Billed Duration is missing.
+pub fn parse_report_line(line: &str) -> Option<InvokeReport> {
+    let fields = line.strip_prefix("REPORT")?;
+    let field = |name: &str| {
+        fields
+            .split('\t')
+            .find_map(|f| f.trim().strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+    };
+    let millis = |name: &str| {
+        let value = field(name)?
+            .strip_suffix("ms")?
+            .trim()
+            .parse::<f64>()
+            .ok()?;
+        Some(Duration::from_secs_f64(value / 1000.0))
+    };
+    let megabytes = |name: &str| field(name)?.strip_suffix("MB")?.trim().parse().ok();
+
+    Some(InvokeReport {
+        request_id: field("RequestId").map(ToString::to_string),
+        duration: millis("Duration")?,
+        billed_duration: millis("Billed Duration")?,
+        memory_size_mb: megabytes("Memory Size"),
+        max_memory_used_mb: megabytes("Max Memory Used"),
+        init_duration: millis("Init Duration"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let log = "START RequestId: 8f5a Version: $LATEST\n\
+                   hello\n\
+                   END RequestId: 8f5a\n\
+                   REPORT RequestId: 8f5a\tDuration: 12.34 ms\tBilled Duration: 13 ms\tMemory Size: 128 MB\tMax Memory Used: 64 MB\tInit Duration: 150.50 ms\t\n";
+        let output = InvokeOutput::builder()
+            .log_result(BASE64_STANDARD.encode(log))
+            .build();
+
+        assert_eq!(output.log_lines().len(), 4);
+        assert_eq!(
+            output.report(),
+            Some(InvokeReport {
+                request_id: Some("8f5a".to_string()),
+                duration: Duration::from_secs_f64(0.01234),
+                billed_duration: Duration::from_millis(13),
+                memory_size_mb: Some(128),
+                max_memory_used_mb: Some(64),
+                init_duration: Some(Duration::from_secs_f64(0.1505)),
+            })
+        );
+
+        assert_eq!(InvokeOutput::builder().build().report(), None);
+        assert_eq!(parse_report_line("END RequestId: 8f5a"), None);
+    }
+}
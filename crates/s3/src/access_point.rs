@@ -0,0 +1,183 @@
+use std::{fmt, str::FromStr};
+
+use crate::error::Error;
+
+/// Kind of access point in an ARN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessPointKind {
+    /// `arn:aws:s3:{region}:{account_id}:accesspoint/{name}`
+    AccessPoint,
+    /// `arn:aws:s3-object-lambda:{region}:{account_id}:accesspoint/{name}`, for GetObject,
+    /// HeadObject and ListObjectsV2 transformed by a Lambda function.
+    ObjectLambda,
+}
+
+/// ARN of an S3 access point or Object Lambda access point. The helpers accept the ARN in
+/// place of the bucket name, with a client of [`make_access_point_client`](crate::make_access_point_client).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessPointArn {
+    pub partition: String,
+    pub kind: AccessPointKind,
+    pub region: String,
+    pub account_id: String,
+    pub name: String,
+}
+
+impl AccessPointArn {
+    pub fn parse(arn: &str) -> Result<Self, Error> {
+        let invalid = || Error::ValidationError(format!("invalid access point ARN: {arn}"));
+        let mut parts = arn.splitn(6, ':');
+        let (Some("arn"), Some(partition), Some(service), Some(region), Some(account_id)) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            return Err(invalid());
+        };
+        let kind = match service {
+            "s3" => AccessPointKind::AccessPoint,
+            "s3-object-lambda" => AccessPointKind::ObjectLambda,
+            _ => return Err(invalid()),
+        };
+        let name = parts
+            .next()
+            .and_then(|resource| resource.strip_prefix("accesspoint/"))
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+            .ok_or_else(invalid)?;
+        if partition.is_empty() || region.is_empty() || account_id.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            partition: partition.to_string(),
+            kind,
+            region: region.to_string(),
+            account_id: account_id.to_string(),
+            name: name.to_string(),
+        })
+    }
+}
+
+impl FromStr for AccessPointArn {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for AccessPointArn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let service = match self.kind {
+            AccessPointKind::AccessPoint => "s3",
+            AccessPointKind::ObjectLambda => "s3-object-lambda",
+        };
+        write!(
+            f,
+            "arn:{}:{service}:{}:{}:accesspoint/{}",
+            self.partition, self.region, self.account_id, self.name
+        )
+    }
+}
+
+/// Whether `bucket_name` is an access point ARN rather than a bucket name.
+pub fn is_access_point_arn(bucket_name: &str) -> bool {
+    bucket_name.starts_with("arn:")
+}
+
+/// CopySource of an object, `{bucket}/{key}` or `{access point ARN}/object/{key}`.
+pub(crate) fn copy_source(bucket_name: &str, key: &str) -> String {
+    if is_access_point_arn(bucket_name) {
+        format!("{bucket_name}/object/{}", urlencoding::Encoded(key))
+    } else {
+        format!(
+            "{}/{}",
+            urlencoding::Encoded(bucket_name),
+            urlencoding::Encoded(key)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_s3::{
+        Client,
+        config::{Credentials, Region},
+    };
+
+    use super::*;
+    use crate::presigned::{get_presigned, presigned_url};
+
+    #[test]
+    fn test_parse() {
+        let arn = "arn:aws:s3:us-west-2:123456789012:accesspoint/shared-data";
+        let access_point = AccessPointArn::parse(arn).unwrap();
+        assert_eq!(
+            access_point,
+            AccessPointArn {
+                partition: "aws".to_string(),
+                kind: AccessPointKind::AccessPoint,
+                region: "us-west-2".to_string(),
+                account_id: "123456789012".to_string(),
+                name: "shared-data".to_string(),
+            }
+        );
+        assert_eq!(access_point.to_string(), arn);
+
+        let object_lambda: AccessPointArn =
+            "arn:aws:s3-object-lambda:ap-northeast-1:123456789012:accesspoint/redacted"
+                .parse()
+                .unwrap();
+        assert_eq!(object_lambda.kind, AccessPointKind::ObjectLambda);
+        assert_eq!(object_lambda.name, "redacted");
+
+        for invalid in [
+            "my-bucket",
+            "arn:aws:s3:::my-bucket",
+            "arn:aws:s3:us-west-2:123456789012:accesspoint/",
+            "arn:aws:s3:us-west-2:123456789012:accesspoint/shared-data/object/key",
+            "arn:aws:sqs:us-west-2:123456789012:accesspoint/shared-data",
+        ] {
+            assert!(AccessPointArn::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_copy_source() {
+        assert_eq!(
+            copy_source("my-bucket", "reports/2026 10.csv"),
+            "my-bucket/reports%2F2026%2010.csv"
+        );
+        assert_eq!(
+            copy_source(
+                "arn:aws:s3:us-west-2:123456789012:accesspoint/shared-data",
+                "reports/a.csv"
+            ),
+            "arn:aws:s3:us-west-2:123456789012:accesspoint/shared-data/object/reports%2Fa.csv"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_access_point_endpoint() {
+        let client = Client::from_conf(
+            aws_sdk_s3::Config::builder()
+                .behavior_version_latest()
+                .region(Region::new("ap-northeast-1"))
+                .credentials_provider(Credentials::for_tests())
+                .use_arn_region(true)
+                .build(),
+        );
+        let presigned = get_presigned(
+            &client,
+            "arn:aws:s3:us-west-2:123456789012:accesspoint/shared-data",
+            "reports/a.csv",
+            std::time::Duration::from_secs(60),
+        )
+        .await
+        .unwrap();
+        assert!(presigned_url(&presigned).starts_with(
+            "https://shared-data-123456789012.s3-accesspoint.us-west-2.amazonaws.com/reports/a.csv?"
+        ));
+    }
+}
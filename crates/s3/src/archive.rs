@@ -0,0 +1,127 @@
+use async_zip::{Compression, ZipEntryBuilder, tokio::write::ZipFileWriter};
+use aws_sdk_s3::Client;
+use futures_util::{AsyncWriteExt as _, TryStreamExt};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio_tar::{Builder, EntryType, Header};
+
+use crate::{
+    error::Error,
+    object::{get_object, list_stream},
+};
+
+/// Result of [`archive_prefix_to_zip`] and [`archive_prefix_to_tar`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    pub objects: usize,
+    /// Total size of the objects, before compression.
+    pub bytes: u64,
+}
+
+/// Name of the entry of an object, the key without `prefix`. None for folder markers.
+fn entry_name<'a>(key: &'a str, prefix: &str) -> Option<&'a str> {
+    let name = key
+        .strip_prefix(prefix)
+        .unwrap_or(key)
+        .trim_start_matches('/');
+    if name.is_empty() || name.ends_with('/') {
+        return None;
+    }
+    Some(name)
+}
+
+/// Writes all objects under `prefix` to `writer` as a zip archive (deflate), named by
+/// their keys without `prefix`. Each object is streamed from S3 into its entry, so only
+/// a chunk of an object is held in memory.
+pub async fn archive_prefix_to_zip<W>(
+    client: &Client,
+    bucket_name: &str,
+    prefix: &str,
+    writer: W,
+) -> Result<ArchiveSummary, Error>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut zip = ZipFileWriter::with_tokio(writer);
+    let mut summary = ArchiveSummary::default();
+    let objects = list_stream(client, bucket_name, Some(prefix));
+    futures_util::pin_mut!(objects);
+    while let Some(object) = objects.try_next().await? {
+        let key = object.key().unwrap_or_default();
+        let Some(name) = entry_name(key, prefix) else {
+            continue;
+        };
+        let mut body = get_object(client, bucket_name, key).await?.body;
+        let entry = ZipEntryBuilder::new(name.into(), Compression::Deflate).unix_permissions(0o644);
+        let mut entry_writer = zip.write_entry_stream(entry).await?;
+        while let Some(chunk) = body.try_next().await? {
+            entry_writer.write_all(&chunk).await?;
+            summary.bytes += chunk.len() as u64;
+        }
+        entry_writer.close().await?;
+        summary.objects += 1;
+    }
+    let mut writer = zip.close().await?.into_inner();
+    writer.flush().await?;
+    Ok(summary)
+}
+
+/// Writes all objects under `prefix` to `writer` as a tar archive, like
+/// [`archive_prefix_to_zip`].
+pub async fn archive_prefix_to_tar<W>(
+    client: &Client,
+    bucket_name: &str,
+    prefix: &str,
+    writer: W,
+) -> Result<ArchiveSummary, Error>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    let mut tar = Builder::new_non_terminated(writer);
+    let mut summary = ArchiveSummary::default();
+    let objects = list_stream(client, bucket_name, Some(prefix));
+    futures_util::pin_mut!(objects);
+    while let Some(object) = objects.try_next().await? {
+        let key = object.key().unwrap_or_default();
+        let Some(name) = entry_name(key, prefix) else {
+            continue;
+        };
+        let output = get_object(client, bucket_name, key).await?;
+        // tar はヘッダーにサイズが必要なので、一覧ではなく取得時のサイズを使う
+        let size = output.content_length.unwrap_or_default().max(0) as u64;
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(
+            output
+                .last_modified
+                .map(|t| t.secs().max(0) as u64)
+                .unwrap_or_default(),
+        );
+        tar.append_data(&mut header, name, output.body.into_async_read())
+            .await?;
+        summary.objects += 1;
+        summary.bytes += size;
+    }
+    tar.finish().await?;
+    let mut writer = tar.into_inner().await?;
+    writer.flush().await?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_name() {
+        assert_eq!(entry_name("exports/1/a.pdf", "exports/1/"), Some("a.pdf"));
+        assert_eq!(
+            entry_name("exports/1/b/c.png", "exports/1"),
+            Some("b/c.png")
+        );
+        assert_eq!(entry_name("exports/1/b/", "exports/1/"), None);
+        assert_eq!(entry_name("exports/1/", "exports/1/"), None);
+        assert_eq!(entry_name("a.pdf", ""), Some("a.pdf"));
+    }
+}
@@ -1,13 +1,32 @@
+use std::collections::HashMap;
+
 use aws_sdk_s3::{
     Client,
-    operation::{create_bucket::CreateBucketOutput, delete_bucket::DeleteBucketOutput},
-    types::Bucket,
+    operation::{
+        create_bucket::CreateBucketOutput, delete_bucket::DeleteBucketOutput,
+        get_bucket_versioning::GetBucketVersioningOutput,
+        put_bucket_encryption::PutBucketEncryptionOutput,
+        put_bucket_logging::PutBucketLoggingOutput,
+        put_bucket_ownership_controls::PutBucketOwnershipControlsOutput,
+        put_bucket_replication::PutBucketReplicationOutput,
+        put_bucket_tagging::PutBucketTaggingOutput,
+        put_bucket_versioning::PutBucketVersioningOutput,
+    },
+    types::{
+        Bucket, BucketLoggingStatus, BucketVersioningStatus, DeleteMarkerReplication,
+        DeleteMarkerReplicationStatus, Destination, LoggingEnabled, Metrics, MetricsStatus,
+        MfaDelete, ObjectOwnership, OwnershipControls, OwnershipControlsRule,
+        ReplicationConfiguration, ReplicationRule, ReplicationRuleFilter, ReplicationRuleStatus,
+        ReplicationTime, ReplicationTimeStatus, ReplicationTimeValue, ServerSideEncryption,
+        ServerSideEncryptionByDefault, ServerSideEncryptionConfiguration, ServerSideEncryptionRule,
+        StorageClass, Tag, Tagging, VersioningConfiguration,
+    },
 };
 use aws_smithy_types_convert::stream::PaginationStreamExt;
 use futures_util::{TryStream, TryStreamExt};
 
 use crate::{
-    error::{Error, from_aws_sdk_error},
+    error::{AwsUtilError, Error, from_aws_sdk_error},
     object::delete_objects,
 };
 
@@ -65,3 +84,429 @@ pub async fn delete_buckets(client: &Client, prefix: impl Into<String>) -> Resul
     }
     Ok(())
 }
+
+/// Enables or suspends versioning.
+///
+/// `mfa_delete` can only be changed by the root account with its MFA device,
+/// passing `mfa` as `(device serial number, current code)`.
+pub async fn put_bucket_versioning(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    enabled: bool,
+    mfa_delete: Option<bool>,
+    mfa: Option<(&str, &str)>,
+) -> Result<PutBucketVersioningOutput, Error> {
+    let status = if enabled {
+        BucketVersioningStatus::Enabled
+    } else {
+        BucketVersioningStatus::Suspended
+    };
+    let mfa_delete = mfa_delete.map(|m| {
+        if m {
+            MfaDelete::Enabled
+        } else {
+            MfaDelete::Disabled
+        }
+    });
+    client
+        .put_bucket_versioning()
+        .bucket(bucket_name)
+        .versioning_configuration(
+            VersioningConfiguration::builder()
+                .status(status)
+                .set_mfa_delete(mfa_delete)
+                .build(),
+        )
+        .set_mfa(mfa.map(|(serial_number, code)| format!("{serial_number} {code}")))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+pub async fn get_bucket_versioning(
+    client: &Client,
+    bucket_name: impl Into<String>,
+) -> Result<GetBucketVersioningOutput, Error> {
+    client
+        .get_bucket_versioning()
+        .bucket(bucket_name)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Replication rule for objects under `prefix` (all objects if None) to the destination bucket.
+///
+/// With `replication_time_control`, S3 Replication Time Control (15 minutes) and
+/// replication metrics are enabled. Delete markers are not replicated.
+pub fn replication_rule(
+    id: impl Into<String>,
+    priority: i32,
+    prefix: Option<impl Into<String>>,
+    destination_bucket_arn: impl Into<String>,
+    storage_class: Option<StorageClass>,
+    replication_time_control: bool,
+) -> Result<ReplicationRule, Error> {
+    let mut destination = Destination::builder()
+        .bucket(destination_bucket_arn)
+        .set_storage_class(storage_class);
+    if replication_time_control {
+        // RTC はメトリクスの有効化も必須
+        destination = destination
+            .replication_time(
+                ReplicationTime::builder()
+                    .status(ReplicationTimeStatus::Enabled)
+                    .time(ReplicationTimeValue::builder().minutes(15).build())
+                    .build()?,
+            )
+            .metrics(
+                Metrics::builder()
+                    .status(MetricsStatus::Enabled)
+                    .event_threshold(ReplicationTimeValue::builder().minutes(15).build())
+                    .build()?,
+            );
+    }
+    let rule = ReplicationRule::builder()
+        .id(id)
+        .priority(priority)
+        .status(ReplicationRuleStatus::Enabled)
+        .filter(
+            ReplicationRuleFilter::builder()
+                .prefix(prefix.map(Into::into).unwrap_or_default())
+                .build(),
+        )
+        .delete_marker_replication(
+            DeleteMarkerReplication::builder()
+                .status(DeleteMarkerReplicationStatus::Disabled)
+                .build(),
+        )
+        .destination(destination.build()?)
+        .build()?;
+    Ok(rule)
+}
+
+/// Replaces the replication configuration. Versioning must be enabled on both buckets.
+pub async fn put_bucket_replication(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    role_arn: impl Into<String>,
+    rules: Vec<ReplicationRule>,
+) -> Result<PutBucketReplicationOutput, Error> {
+    client
+        .put_bucket_replication()
+        .bucket(bucket_name)
+        .replication_configuration(
+            ReplicationConfiguration::builder()
+                .role(role_arn)
+                .set_rules(Some(rules))
+                .build()?,
+        )
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Returns None if the bucket has no replication configuration.
+pub async fn get_bucket_replication(
+    client: &Client,
+    bucket_name: impl Into<String>,
+) -> Result<Option<ReplicationConfiguration>, Error> {
+    let result = client
+        .get_bucket_replication()
+        .bucket(bucket_name)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error);
+    match result {
+        Ok(output) => Ok(output.replication_configuration),
+        Err(e) if e.error_code() == Some("ReplicationConfigurationNotFoundError") => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Default encryption of a bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BucketEncryption {
+    /// SSE-S3 (AES256)
+    S3,
+    /// SSE-KMS with the key ID or ARN (the AWS managed key `aws/s3` if None).
+    /// With `bucket_key`, an S3 Bucket Key reduces the requests to KMS.
+    Kms {
+        kms_key_id: Option<String>,
+        bucket_key: bool,
+    },
+}
+
+impl BucketEncryption {
+    fn to_rule(&self) -> Result<ServerSideEncryptionRule, Error> {
+        let (algorithm, kms_key_id, bucket_key) = match self {
+            BucketEncryption::S3 => (ServerSideEncryption::Aes256, None, None),
+            BucketEncryption::Kms {
+                kms_key_id,
+                bucket_key,
+            } => (
+                ServerSideEncryption::AwsKms,
+                kms_key_id.clone(),
+                Some(*bucket_key),
+            ),
+        };
+        Ok(ServerSideEncryptionRule::builder()
+            .apply_server_side_encryption_by_default(
+                ServerSideEncryptionByDefault::builder()
+                    .sse_algorithm(algorithm)
+                    .set_kms_master_key_id(kms_key_id)
+                    .build()?,
+            )
+            .set_bucket_key_enabled(bucket_key)
+            .build())
+    }
+}
+
+impl TryFrom<&ServerSideEncryptionRule> for BucketEncryption {
+    type Error = Error;
+
+    fn try_from(rule: &ServerSideEncryptionRule) -> Result<Self, Self::Error> {
+        let Some(default) = rule.apply_server_side_encryption_by_default() else {
+            return Err(Error::ValidationError(
+                "encryption rule has no default encryption".to_string(),
+            ));
+        };
+        match default.sse_algorithm() {
+            ServerSideEncryption::Aes256 => Ok(BucketEncryption::S3),
+            ServerSideEncryption::AwsKms => Ok(BucketEncryption::Kms {
+                kms_key_id: default.kms_master_key_id().map(ToString::to_string),
+                bucket_key: rule.bucket_key_enabled().unwrap_or_default(),
+            }),
+            algorithm => Err(Error::ValidationError(format!(
+                "unsupported sse algorithm: {algorithm}"
+            ))),
+        }
+    }
+}
+
+pub async fn put_bucket_encryption(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    encryption: &BucketEncryption,
+) -> Result<PutBucketEncryptionOutput, Error> {
+    client
+        .put_bucket_encryption()
+        .bucket(bucket_name)
+        .server_side_encryption_configuration(
+            ServerSideEncryptionConfiguration::builder()
+                .rules(encryption.to_rule()?)
+                .build()?,
+        )
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Returns None if the bucket has no default encryption configuration.
+pub async fn get_bucket_encryption(
+    client: &Client,
+    bucket_name: impl Into<String>,
+) -> Result<Option<BucketEncryption>, Error> {
+    let result = client
+        .get_bucket_encryption()
+        .bucket(bucket_name)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error);
+    let configuration = match result {
+        Ok(output) => output.server_side_encryption_configuration,
+        Err(e) if e.error_code() == Some("ServerSideEncryptionConfigurationNotFoundError") => {
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+    configuration
+        .as_ref()
+        .and_then(|c| c.rules().first())
+        .map(BucketEncryption::try_from)
+        .transpose()
+}
+
+/// Enables server access logging to `target_bucket` under `target_prefix`.
+/// The logs can be read with [`server_access_log`](crate::server_access_log).
+pub async fn put_bucket_logging(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    target_bucket: impl Into<String>,
+    target_prefix: impl Into<String>,
+) -> Result<PutBucketLoggingOutput, Error> {
+    client
+        .put_bucket_logging()
+        .bucket(bucket_name)
+        .bucket_logging_status(
+            BucketLoggingStatus::builder()
+                .logging_enabled(
+                    LoggingEnabled::builder()
+                        .target_bucket(target_bucket)
+                        .target_prefix(target_prefix)
+                        .build()?,
+                )
+                .build(),
+        )
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Replaces all tags of the bucket.
+pub async fn put_bucket_tagging(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    tags: HashMap<String, String>,
+) -> Result<PutBucketTaggingOutput, Error> {
+    let tag_set = tags
+        .into_iter()
+        .map(|(key, value)| Tag::builder().key(key).value(value).build())
+        .collect::<Result<Vec<_>, _>>()?;
+    client
+        .put_bucket_tagging()
+        .bucket(bucket_name)
+        .tagging(Tagging::builder().set_tag_set(Some(tag_set)).build()?)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Returns an empty map if the bucket has no tags.
+pub async fn get_bucket_tagging(
+    client: &Client,
+    bucket_name: impl Into<String>,
+) -> Result<HashMap<String, String>, Error> {
+    let result = client
+        .get_bucket_tagging()
+        .bucket(bucket_name)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error);
+    match result {
+        Ok(output) => Ok(output
+            .tag_set
+            .into_iter()
+            .map(|tag| (tag.key, tag.value))
+            .collect()),
+        Err(e) if e.error_code() == Some("NoSuchTagSet") => Ok(HashMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Sets the object ownership of the bucket. `ObjectOwnership::BucketOwnerEnforced` disables
+/// ACLs, and the other settings are needed for [`put_object_acl`](crate::object::put_object_acl).
+pub async fn put_bucket_ownership_controls(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    object_ownership: ObjectOwnership,
+) -> Result<PutBucketOwnershipControlsOutput, Error> {
+    client
+        .put_bucket_ownership_controls()
+        .bucket(bucket_name)
+        .ownership_controls(
+            OwnershipControls::builder()
+                .rules(
+                    OwnershipControlsRule::builder()
+                        .object_ownership(object_ownership)
+                        .build()?,
+                )
+                .build()?,
+        )
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Returns None if the bucket has no ownership controls.
+pub async fn get_bucket_ownership_controls(
+    client: &Client,
+    bucket_name: impl Into<String>,
+) -> Result<Option<ObjectOwnership>, Error> {
+    let result = client
+        .get_bucket_ownership_controls()
+        .bucket(bucket_name)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error);
+    match result {
+        Ok(output) => Ok(output
+            .ownership_controls
+            .and_then(|c| c.rules.into_iter().next())
+            .map(|r| r.object_ownership)),
+        Err(e) if e.error_code() == Some("OwnershipControlsNotFoundError") => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replication_rule() {
+        let rule = replication_rule(
+            "dr",
+            1,
+            Some("logs/"),
+            "arn:aws:s3:::dr-bucket",
+            Some(StorageClass::StandardIa),
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(rule.filter().and_then(|f| f.prefix()), Some("logs/"));
+        let destination = rule.destination().unwrap();
+        assert_eq!(destination.bucket(), "arn:aws:s3:::dr-bucket");
+        assert_eq!(
+            destination.replication_time().map(|r| r.status()),
+            Some(&ReplicationTimeStatus::Enabled)
+        );
+        assert!(destination.metrics().is_some());
+
+        let rule = replication_rule(
+            "all",
+            2,
+            None::<String>,
+            "arn:aws:s3:::dr-bucket",
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(rule.filter().and_then(|f| f.prefix()), Some(""));
+        assert!(rule.destination().unwrap().replication_time().is_none());
+    }
+
+    #[test]
+    fn test_bucket_encryption() {
+        let kms = BucketEncryption::Kms {
+            kms_key_id: Some("alias/my-key".to_string()),
+            bucket_key: true,
+        };
+        let rule = kms.to_rule().unwrap();
+        assert_eq!(rule.bucket_key_enabled(), Some(true));
+        assert_eq!(
+            rule.apply_server_side_encryption_by_default()
+                .map(|d| d.sse_algorithm()),
+            Some(&ServerSideEncryption::AwsKms)
+        );
+        assert_eq!(BucketEncryption::try_from(&rule).unwrap(), kms);
+
+        let rule = BucketEncryption::S3.to_rule().unwrap();
+        assert_eq!(rule.bucket_key_enabled(), None);
+        assert_eq!(
+            BucketEncryption::try_from(&rule).unwrap(),
+            BucketEncryption::S3
+        );
+
+        let dsse = ServerSideEncryptionRule::builder()
+            .apply_server_side_encryption_by_default(
+                ServerSideEncryptionByDefault::builder()
+                    .sse_algorithm(ServerSideEncryption::AwsKmsDsse)
+                    .build()
+                    .unwrap(),
+            )
+            .build();
+        assert!(BucketEncryption::try_from(&dsse).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use std::time::{Duration, SystemTime};
+
+use aws_config::timeout::TimeoutConfig;
+use aws_credential_types::Credentials;
+use aws_sdk_s3::{Client, config::SharedInterceptor};
+
+use crate::{
+    error::{Error, from_aws_sdk_sts_error},
+    make_client_from_credentials,
+};
+
+/// Actions granted on objects under the prefix by [`bucket_prefix_policy`].
+const OBJECT_ACTIONS: [&str; 5] = [
+    "s3:GetObject",
+    "s3:PutObject",
+    "s3:DeleteObject",
+    "s3:AbortMultipartUpload",
+    "s3:ListMultipartUploadParts",
+];
+
+/// A client whose credentials are limited to a single bucket prefix.
+pub struct ScopedClient {
+    pub client: Client,
+    pub expiration: SystemTime,
+}
+
+/// Builds an inline session policy that only allows object access under
+/// `bucket/prefix` and listing of that prefix.
+pub fn bucket_prefix_policy(bucket_name: &str, prefix: &str) -> Result<String, Error> {
+    let policy = serde_json::json!({
+        "Version": "2012-10-17",
+        "Statement": [
+            {
+                "Effect": "Allow",
+                "Action": OBJECT_ACTIONS,
+                "Resource": format!("arn:aws:s3:::{bucket_name}/{prefix}*"),
+            },
+            {
+                "Effect": "Allow",
+                "Action": "s3:ListBucket",
+                "Resource": format!("arn:aws:s3:::{bucket_name}"),
+                "Condition": {
+                    "StringLike": {
+                        "s3:prefix": [format!("{prefix}*")],
+                    },
+                },
+            },
+        ],
+    });
+    serde_json::to_string(&policy).map_err(|e| Error::ValidationError(e.to_string()))
+}
+
+/// Assumes `role_arn` with a session policy scoped to `bucket/prefix` and
+/// returns an S3 client using the temporary credentials.
+///
+/// The effective permissions are the intersection of the role policy and the
+/// session policy, so the role itself must allow access to the bucket.
+/// The region is taken from the STS client configuration.
+#[allow(clippy::too_many_arguments)]
+pub async fn assume_role_for_prefix(
+    sts_client: &aws_sdk_sts::Client,
+    role_arn: impl Into<String>,
+    role_session_name: impl Into<String>,
+    bucket_name: &str,
+    prefix: &str,
+    duration: Duration,
+    endpoint_url: Option<String>,
+    timeout_config: Option<TimeoutConfig>,
+    interceptor: Option<SharedInterceptor>,
+) -> Result<ScopedClient, Error> {
+    let region = sts_client
+        .config()
+        .region()
+        .cloned()
+        .ok_or_else(|| Error::ValidationError("region is not configured".to_string()))?;
+    // AssumeRole の DurationSeconds は 900 秒以上
+    let duration_seconds = i32::try_from(duration.as_secs())
+        .ok()
+        .filter(|d| *d >= 900)
+        .ok_or_else(|| {
+            Error::ValidationError("duration must be at least 900 seconds".to_string())
+        })?;
+
+    let output = sts_client
+        .assume_role()
+        .role_arn(role_arn)
+        .role_session_name(role_session_name)
+        .policy(bucket_prefix_policy(bucket_name, prefix)?)
+        .duration_seconds(duration_seconds)
+        .send()
+        .await
+        .map_err(from_aws_sdk_sts_error)?;
+    let sts_credentials = output
+        .credentials()
+        .ok_or_else(|| Error::ValidationError("credentials are missing".to_string()))?;
+    let expiration = SystemTime::try_from(*sts_credentials.expiration())
+        .map_err(|e| Error::ValidationError(e.to_string()))?;
+
+    let credentials = Credentials::new(
+        sts_credentials.access_key_id(),
+        sts_credentials.secret_access_key(),
+        Some(sts_credentials.session_token().to_string()),
+        Some(expiration),
+        "assume_role_for_prefix",
+    );
+    let client = make_client_from_credentials(
+        credentials,
+        region,
+        endpoint_url,
+        timeout_config,
+        interceptor,
+    )
+    .await;
+
+    Ok(ScopedClient { client, expiration })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_prefix_policy() {
+        let policy = bucket_prefix_policy("my-bucket", "workers/1/").unwrap();
+        let value: serde_json::Value = serde_json::from_str(&policy).unwrap();
+
+        assert_eq!(
+            value["Statement"][0]["Resource"],
+            "arn:aws:s3:::my-bucket/workers/1/*"
+        );
+        assert_eq!(value["Statement"][1]["Resource"], "arn:aws:s3:::my-bucket");
+        assert_eq!(
+            value["Statement"][1]["Condition"]["StringLike"]["s3:prefix"][0],
+            "workers/1/*"
+        );
+    }
+}
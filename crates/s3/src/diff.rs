@@ -0,0 +1,264 @@
+use std::cmp::Ordering;
+
+use aws_sdk_s3::{Client, types::Object};
+use futures_util::{TryStream, TryStreamExt, future, stream};
+
+use crate::{error::Error, object::list_stream};
+
+/// Difference of an object between the source and the destination prefix, by the key relative
+/// to the prefix.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectDiff {
+    /// Only in the source.
+    Added { key: String, src: Box<Object> },
+    /// Only in the destination.
+    Removed { key: String, dst: Box<Object> },
+    /// In both, with a different size or ETag.
+    Changed {
+        key: String,
+        src: Box<Object>,
+        dst: Box<Object>,
+    },
+}
+
+impl ObjectDiff {
+    /// The key relative to the prefixes.
+    pub fn key(&self) -> &str {
+        match self {
+            Self::Added { key, .. } | Self::Removed { key, .. } | Self::Changed { key, .. } => key,
+        }
+    }
+}
+
+/// Relative keys of [`diff_prefixes_all`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PrefixDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl PrefixDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Differences of the objects under `src_prefix` of `src_bucket_name` and `dst_prefix` of
+/// `dst_bucket_name`, e.g. to validate replication.
+///
+/// Both listings are merged in key order as they are paged, so any number of objects can be
+/// compared without holding them in memory. Objects are compared by size and ETag; an object
+/// uploaded with different multipart part sizes has a different ETag even with the same content.
+/// Directory buckets, which do not list keys in order, are not supported.
+pub fn diff_prefixes(
+    client: &Client,
+    src_bucket_name: impl Into<String>,
+    src_prefix: impl Into<String>,
+    dst_bucket_name: impl Into<String>,
+    dst_prefix: impl Into<String>,
+) -> impl TryStream<Ok = ObjectDiff, Error = Error> {
+    let src = relative_keys(client, src_bucket_name.into(), src_prefix.into());
+    let dst = relative_keys(client, dst_bucket_name.into(), dst_prefix.into());
+    diff_sorted(src, dst)
+}
+
+/// [`diff_prefixes`] collected into the relative keys.
+pub async fn diff_prefixes_all(
+    client: &Client,
+    src_bucket_name: impl Into<String>,
+    src_prefix: impl Into<String>,
+    dst_bucket_name: impl Into<String>,
+    dst_prefix: impl Into<String>,
+) -> Result<PrefixDiff, Error> {
+    diff_prefixes(
+        client,
+        src_bucket_name,
+        src_prefix,
+        dst_bucket_name,
+        dst_prefix,
+    )
+    .try_fold(PrefixDiff::default(), |mut diff, object_diff| {
+        match object_diff {
+            ObjectDiff::Added { key, .. } => diff.added.push(key),
+            ObjectDiff::Removed { key, .. } => diff.removed.push(key),
+            ObjectDiff::Changed { key, .. } => diff.changed.push(key),
+        }
+        future::ready(Ok(diff))
+    })
+    .await
+}
+
+fn relative_keys(
+    client: &Client,
+    bucket_name: String,
+    prefix: String,
+) -> impl TryStream<Ok = (String, Object), Error = Error> {
+    list_stream(client, bucket_name, Some(prefix.clone())).try_filter_map(move |object| {
+        let key = object
+            .key()
+            .and_then(|key| key.strip_prefix(&prefix))
+            .map(str::to_string);
+        future::ready(Ok(key.map(|key| (key, object))))
+    })
+}
+
+fn is_same(src: &Object, dst: &Object) -> bool {
+    src.size() == dst.size() && src.e_tag() == dst.e_tag()
+}
+
+struct DiffState<S, D> {
+    src: S,
+    dst: D,
+    src_head: Option<(String, Object)>,
+    dst_head: Option<(String, Object)>,
+    src_done: bool,
+    dst_done: bool,
+}
+
+// 両方のリストはキーの UTF-8 バイト順に並んでいるので、マージしながら比較する
+fn diff_sorted<S, D>(src: S, dst: D) -> impl TryStream<Ok = ObjectDiff, Error = Error>
+where
+    S: TryStream<Ok = (String, Object), Error = Error>,
+    D: TryStream<Ok = (String, Object), Error = Error>,
+{
+    let state = DiffState {
+        src: Box::pin(src.into_stream()),
+        dst: Box::pin(dst.into_stream()),
+        src_head: None,
+        dst_head: None,
+        src_done: false,
+        dst_done: false,
+    };
+    stream::try_unfold(state, |mut state| async move {
+        loop {
+            if state.src_head.is_none() && !state.src_done {
+                state.src_head = state.src.try_next().await?;
+                state.src_done = state.src_head.is_none();
+            }
+            if state.dst_head.is_none() && !state.dst_done {
+                state.dst_head = state.dst.try_next().await?;
+                state.dst_done = state.dst_head.is_none();
+            }
+            let diff = match (state.src_head.take(), state.dst_head.take()) {
+                (None, None) => return Ok(None),
+                (Some((key, src)), None) => ObjectDiff::Added {
+                    key,
+                    src: Box::new(src),
+                },
+                (None, Some((key, dst))) => ObjectDiff::Removed {
+                    key,
+                    dst: Box::new(dst),
+                },
+                (Some((src_key, src)), Some((dst_key, dst))) => match src_key.cmp(&dst_key) {
+                    Ordering::Less => {
+                        state.dst_head = Some((dst_key, dst));
+                        ObjectDiff::Added {
+                            key: src_key,
+                            src: Box::new(src),
+                        }
+                    }
+                    Ordering::Greater => {
+                        state.src_head = Some((src_key, src));
+                        ObjectDiff::Removed {
+                            key: dst_key,
+                            dst: Box::new(dst),
+                        }
+                    }
+                    Ordering::Equal if is_same(&src, &dst) => continue,
+                    Ordering::Equal => ObjectDiff::Changed {
+                        key: src_key,
+                        src: Box::new(src),
+                        dst: Box::new(dst),
+                    },
+                },
+            };
+            return Ok(Some((diff, state)));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(key: &str, size: i64, e_tag: &str) -> (String, Object) {
+        (
+            key.to_string(),
+            Object::builder()
+                .key(format!("prefix/{key}"))
+                .size(size)
+                .e_tag(format!("\"{e_tag}\""))
+                .build(),
+        )
+    }
+
+    async fn diff(src: Vec<(String, Object)>, dst: Vec<(String, Object)>) -> Vec<(char, String)> {
+        diff_sorted(
+            stream::iter(src.into_iter().map(Ok)),
+            stream::iter(dst.into_iter().map(Ok)),
+        )
+        .map_ok(|d| {
+            let kind = match d {
+                ObjectDiff::Added { .. } => '+',
+                ObjectDiff::Removed { .. } => '-',
+                ObjectDiff::Changed { .. } => '~',
+            };
+            (kind, d.key().to_string())
+        })
+        .try_collect()
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_diff_sorted() {
+        let src = vec![
+            object("a.txt", 1, "a"),
+            object("b.txt", 2, "b"),
+            object("c/d.txt", 3, "d"),
+            object("e.txt", 5, "e"),
+        ];
+        let dst = vec![
+            object("a.txt", 1, "a"),
+            object("b.txt", 2, "b2"),
+            object("c/c.txt", 3, "c"),
+            object("e.txt", 6, "e"),
+            object("f.txt", 7, "f"),
+        ];
+        assert_eq!(
+            diff(src, dst).await,
+            vec![
+                ('~', "b.txt".to_string()),
+                ('-', "c/c.txt".to_string()),
+                ('+', "c/d.txt".to_string()),
+                ('~', "e.txt".to_string()),
+                ('-', "f.txt".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diff_sorted_empty() {
+        assert_eq!(diff(vec![], vec![]).await, vec![]);
+        assert_eq!(
+            diff(vec![object("a", 1, "a")], vec![]).await,
+            vec![('+', "a".to_string())]
+        );
+        assert_eq!(
+            diff(vec![], vec![object("a", 1, "a")]).await,
+            vec![('-', "a".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diff_sorted_error() {
+        let src = stream::iter(vec![
+            Ok(object("a", 1, "a")),
+            Err(Error::ValidationError("list failed".to_string())),
+        ]);
+        let dst = stream::iter(Vec::<Result<(String, Object), Error>>::new());
+        let result: Result<Vec<_>, _> = diff_sorted(src, dst).try_collect().await;
+        assert!(result.is_err());
+    }
+}
@@ -1,4 +1,12 @@
-use aws_sdk_s3::{presigning::PresigningConfigError, primitives::ByteStreamError};
+use aws_sdk_s3::{
+    config::http::HttpResponse,
+    error::{ProvideErrorMetadata, SdkError},
+    operation::RequestId,
+    presigning::PresigningConfigError,
+    primitives::ByteStreamError,
+};
+use aws_utils_core::error::is_retryable_sdk_error;
+pub use aws_utils_core::error::{AwsUtilError, ResponseMetadata};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,42 +20,161 @@ pub enum Error {
     #[error(transparent)]
     Presigned(#[from] PresigningConfigError),
 
+    /// Error of an S3 request, with the request IDs and HTTP status of its response.
+    #[error("{source}")]
+    AwsSdk {
+        source: Box<aws_sdk_s3::Error>,
+        response: ResponseMetadata,
+    },
+
     #[error(transparent)]
-    AwsSdk(#[from] Box<aws_sdk_s3::Error>),
+    Sts(#[from] Box<aws_sdk_sts::Error>),
 
     #[error(transparent)]
     IO(#[from] std::io::Error),
 
+    #[cfg(feature = "archive")]
+    #[error(transparent)]
+    Zip(#[from] async_zip::error::ZipError),
+
+    #[error("Timeout {0}")]
+    Timeout(#[from] tokio::time::error::Elapsed),
+
     #[error("ValidationError: {0}")]
     ValidationError(String),
+
+    /// A conditional write lost to a concurrent writer.
+    #[error("Conflict: {key}")]
+    Conflict {
+        key: String,
+        #[source]
+        source: Box<aws_sdk_s3::Error>,
+        response: ResponseMetadata,
+    },
+
+    /// The condition of a conditional write did not hold: the object already exists
+    /// (If-None-Match) or its ETag no longer matches (If-Match).
+    #[error("PreconditionFailed: {key}")]
+    PreconditionFailed {
+        key: String,
+        #[source]
+        source: Box<aws_sdk_s3::Error>,
+        response: ResponseMetadata,
+    },
+
+    /// The object is larger than the size limit of the download. `size` is the Content-Length,
+    /// or the bytes received when the object grew beyond it.
+    #[error("ObjectTooLarge: {key} ({size} > {max_size} bytes)")]
+    ObjectTooLarge {
+        key: String,
+        size: u64,
+        max_size: u64,
+    },
+}
+
+impl<E> From<SdkError<E, HttpResponse>> for Error
+where
+    aws_sdk_s3::Error: From<SdkError<E, HttpResponse>>,
+{
+    fn from(e: SdkError<E, HttpResponse>) -> Self {
+        let response = ResponseMetadata::from_sdk_error(&e);
+        Error::AwsSdk {
+            source: Box::new(e.into()),
+            response,
+        }
+    }
+}
+
+pub(crate) fn from_aws_sdk_error<E>(e: SdkError<E, HttpResponse>) -> Error
+where
+    aws_sdk_s3::Error: From<SdkError<E, HttpResponse>>,
+{
+    Error::from(e)
 }
 
-pub(crate) fn from_aws_sdk_error(e: impl Into<aws_sdk_s3::Error>) -> Error {
-    Error::AwsSdk(Box::new(e.into()))
+pub(crate) fn from_aws_sdk_sts_error(e: impl Into<aws_sdk_sts::Error>) -> Error {
+    Error::Sts(Box::new(e.into()))
 }
 
 impl Error {
     pub fn is_no_such_key(&self) -> bool {
-        if let Error::AwsSdk(e) = self {
-            matches!(**e, aws_sdk_s3::Error::NoSuchKey(_))
+        if let Error::AwsSdk { source, .. } = self {
+            matches!(**source, aws_sdk_s3::Error::NoSuchKey(_))
         } else {
             false
         }
     }
 
     pub fn is_no_such_bucket(&self) -> bool {
-        if let Error::AwsSdk(e) = self {
-            matches!(**e, aws_sdk_s3::Error::NoSuchBucket(_))
+        if let Error::AwsSdk { source, .. } = self {
+            matches!(**source, aws_sdk_s3::Error::NoSuchBucket(_))
         } else {
             false
         }
     }
 
+    pub fn is_precondition_failed(&self) -> bool {
+        matches!(self, Error::PreconditionFailed { .. })
+    }
+
+    /// Request IDs and HTTP status of the failed S3 request, if a response was received.
+    pub fn response(&self) -> Option<&ResponseMetadata> {
+        match self {
+            Error::AwsSdk { response, .. }
+            | Error::Conflict { response, .. }
+            | Error::PreconditionFailed { response, .. } => Some(response),
+            _ => None,
+        }
+    }
+
     pub fn is_not_found(&self) -> bool {
-        if let Error::AwsSdk(e) = self {
-            matches!(**e, aws_sdk_s3::Error::NotFound(_))
+        if let Error::AwsSdk { source, .. } = self {
+            matches!(**source, aws_sdk_s3::Error::NotFound(_))
         } else {
             false
         }
     }
 }
+
+impl AwsUtilError for Error {
+    fn error_code(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk { source: e, .. }
+            | Error::Conflict { source: e, .. }
+            | Error::PreconditionFailed { source: e, .. } => e.code(),
+            Error::Sts(e) => e.code(),
+            _ => None,
+        }
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk { source, response }
+            | Error::Conflict {
+                source, response, ..
+            }
+            | Error::PreconditionFailed {
+                source, response, ..
+            } => source.request_id().or(response.request_id.as_deref()),
+            Error::Sts(e) => e.request_id(),
+            _ => None,
+        }
+    }
+
+    fn extended_request_id(&self) -> Option<&str> {
+        self.response()
+            .and_then(|r| r.extended_request_id.as_deref())
+    }
+
+    fn http_status(&self) -> Option<u16> {
+        self.response().and_then(|r| r.status)
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::AwsSdk { source, .. } => is_retryable_sdk_error(source.as_ref()),
+            Error::Sts(e) => is_retryable_sdk_error(e.as_ref()),
+            _ => false,
+        }
+    }
+}
@@ -10,12 +10,23 @@ use aws_sdk_s3::{
     config::{Region, SharedInterceptor},
 };
 
+pub mod access_point;
+#[cfg(feature = "archive")]
+pub mod archive;
 pub mod bucket;
+pub mod credentials;
+pub mod diff;
 pub mod error;
 pub mod object;
 pub mod presigned;
+pub mod restore;
+pub mod server_access_log;
+pub mod static_assets;
+pub mod transfer;
 
 pub use aws_sdk_s3;
+#[cfg(feature = "metrics")]
+pub use aws_utils_core::metrics;
 
 pub async fn make_client_with_timeout_default(endpoint_url: Option<String>) -> Client {
     make_client_with_timeout(
@@ -66,6 +77,28 @@ pub async fn make_client(
     Client::from_conf(builder.build())
 }
 
+/// Client for access point and Object Lambda access point ARNs in place of the bucket name
+/// (see [`access_point::AccessPointArn`]). With `use_arn_region`, requests go to the region
+/// of the ARN, e.g. for access points of a data share in another region; otherwise an ARN
+/// of another region fails.
+pub async fn make_access_point_client(
+    use_arn_region: bool,
+    timeout_config: Option<TimeoutConfig>,
+    interceptor: Option<SharedInterceptor>,
+) -> Client {
+    let mut config_loader = aws_config::defaults(BehaviorVersion::latest());
+    if let Some(timeout_config) = timeout_config {
+        config_loader = config_loader.timeout_config(timeout_config);
+    }
+    let config = config_loader.load().await;
+    // パス形式とカスタムエンドポイントはアクセスポイントの ARN に使えない
+    let mut builder = aws_sdk_s3::config::Builder::from(&config).use_arn_region(use_arn_region);
+    if let Some(interceptor) = interceptor {
+        builder.push_interceptor(interceptor);
+    }
+    Client::from_conf(builder.build())
+}
+
 pub async fn make_client_with_credentials(
     access_key_id: String,
     secret_access_key: String,
@@ -75,10 +108,26 @@ pub async fn make_client_with_credentials(
     interceptor: Option<SharedInterceptor>,
 ) -> Client {
     let credentials = Credentials::new(access_key_id, secret_access_key, None, None, "manual");
+    make_client_from_credentials(
+        credentials,
+        Region::new(region),
+        endpoint_url,
+        timeout_config,
+        interceptor,
+    )
+    .await
+}
 
+pub(crate) async fn make_client_from_credentials(
+    credentials: Credentials,
+    region: Region,
+    endpoint_url: Option<String>,
+    timeout_config: Option<TimeoutConfig>,
+    interceptor: Option<SharedInterceptor>,
+) -> Client {
     let mut config_loader = aws_config::defaults(BehaviorVersion::latest())
         .credentials_provider(credentials)
-        .region(Region::new(region));
+        .region(region);
 
     if let Some(timeout_config) = timeout_config {
         config_loader = config_loader.timeout_config(timeout_config);
@@ -1,19 +1,33 @@
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 
 use aws_sdk_s3::{
     Client,
+    error::ProvideErrorMetadata,
     operation::{
         copy_object::CopyObjectOutput, delete_object::DeleteObjectOutput,
-        get_object::GetObjectOutput, put_object::PutObjectOutput,
+        get_object::GetObjectOutput, get_object_acl::GetObjectAclOutput,
+        put_object::PutObjectOutput, put_object_acl::PutObjectAclOutput,
     },
     primitives::ByteStream,
-    types::Object,
+    types::{CompletedMultipartUpload, CompletedPart, MetadataDirective, Object, ObjectCannedAcl},
 };
 use aws_smithy_types_convert::stream::PaginationStreamExt;
+use bytes::{Bytes, BytesMut};
 use futures_util::{TryStream, TryStreamExt};
 use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
 
-use crate::error::{Error, from_aws_sdk_error};
+use crate::{
+    access_point::copy_source,
+    error::{Error, from_aws_sdk_error},
+    transfer::TransferOptions,
+};
+
+/// Max object size copied with a single CopyObject.
+const MAX_COPY_OBJECT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+const COPY_PART_SIZE: u64 = 512 * 1024 * 1024;
+
+const MAX_PARTS: u64 = 10_000;
 
 pub fn list_stream(
     client: &Client,
@@ -78,6 +92,49 @@ pub async fn is_exists(
     }
 }
 
+/// Downloads an object into memory, failing with [`Error::ObjectTooLarge`] instead of reading
+/// more than `max_size` bytes. The Content-Length is checked before reading the body.
+pub async fn get_object_bytes(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+    max_size: u64,
+) -> Result<Bytes, Error> {
+    let key = key.into();
+    let object = get_object(client, bucket_name, &key).await?;
+    let content_length = object.content_length().and_then(|l| u64::try_from(l).ok());
+    read_body_capped(object.body, content_length, key, max_size).await
+}
+
+async fn read_body_capped(
+    mut body: ByteStream,
+    content_length: Option<u64>,
+    key: String,
+    max_size: u64,
+) -> Result<Bytes, Error> {
+    if let Some(size) = content_length.filter(|l| *l > max_size) {
+        return Err(Error::ObjectTooLarge {
+            key,
+            size,
+            max_size,
+        });
+    }
+    let mut buf = BytesMut::with_capacity(content_length.unwrap_or_default() as usize);
+    while let Some(chunk) = body.try_next().await? {
+        let size = (buf.len() + chunk.len()) as u64;
+        // Content-Length がない、または実際のボディが長い場合
+        if size > max_size {
+            return Err(Error::ObjectTooLarge {
+                key,
+                size,
+                max_size,
+            });
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf.freeze())
+}
+
 pub async fn get_object_string(object: GetObjectOutput) -> Result<(String, String), Error> {
     let content_type = object.content_type().unwrap_or_default().to_string();
     let mut reader = get_object_buf_reader(object);
@@ -90,6 +147,72 @@ pub fn get_object_buf_reader(object: GetObjectOutput) -> BufReader<impl AsyncRea
     BufReader::new(object.body.into_async_read())
 }
 
+/// Compression of an object, for [`get_object_decompressed_reader`].
+#[cfg(feature = "compression")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+#[cfg(feature = "compression")]
+impl Compression {
+    /// From the Content-Encoding, or else the suffix of the key (`.gz`, `.zst`).
+    /// None if the object is not compressed.
+    pub fn detect(content_encoding: Option<&str>, key: &str) -> Option<Self> {
+        match content_encoding
+            .map(|e| e.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("gzip" | "x-gzip") => Some(Compression::Gzip),
+            Some("zstd") => Some(Compression::Zstd),
+            _ if key.ends_with(".gz") || key.ends_with(".gzip") => Some(Compression::Gzip),
+            _ if key.ends_with(".zst") || key.ends_with(".zstd") => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Gets an object as an [`AsyncRead`] decompressing the body with gzip or zstd, detected
+/// with [`Compression::detect`]. An object that is not compressed is read as is.
+#[cfg(feature = "compression")]
+pub async fn get_object_decompressed_reader(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+) -> Result<Box<dyn AsyncRead + Send + Unpin>, Error> {
+    let key = key.into();
+    let object = get_object(client, bucket_name, &key).await?;
+    let compression = Compression::detect(object.content_encoding(), &key);
+    Ok(decompressed_reader(
+        get_object_buf_reader(object),
+        compression,
+    ))
+}
+
+#[cfg(feature = "compression")]
+fn decompressed_reader(
+    reader: impl tokio::io::AsyncBufRead + Send + Unpin + 'static,
+    compression: Option<Compression>,
+) -> Box<dyn AsyncRead + Send + Unpin> {
+    use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+
+    match compression {
+        Some(Compression::Gzip) => {
+            let mut decoder = GzipDecoder::new(reader);
+            // 連結された gzip も最後まで読む
+            decoder.multiple_members(true);
+            Box::new(decoder)
+        }
+        Some(Compression::Zstd) => {
+            let mut decoder = ZstdDecoder::new(reader);
+            decoder.multiple_members(true);
+            Box::new(decoder)
+        }
+        None => Box::new(reader),
+    }
+}
+
 pub async fn put_object(
     client: &Client,
     bucket_name: impl Into<String>,
@@ -132,6 +255,149 @@ pub async fn put_object_conditional(
         .map_err(from_aws_sdk_error)
 }
 
+/// Creates the object only if `key` does not exist (`If-None-Match: *`), e.g. to acquire a
+/// lock or lease object. Returns [`Error::PreconditionFailed`] when it exists, and
+/// [`Error::Conflict`] when a concurrent conditional write to the key is in progress.
+pub async fn put_object_if_absent(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+    body: impl Into<ByteStream>,
+    content_type: Option<impl Into<String>>,
+) -> Result<PutObjectOutput, Error> {
+    let key = key.into();
+    client
+        .put_object()
+        .set_bucket(Some(bucket_name.into()))
+        .key(&key)
+        .set_body(Some(body.into()))
+        .if_none_match("*")
+        .set_content_type(content_type.map(Into::into))
+        .send()
+        .await
+        .map_err(|e| precondition_or_sdk_error(e.into(), &key))
+}
+
+/// Replaces the object only if its ETag is still `expected_etag` (compare-and-swap), e.g. to
+/// renew or release a lease taken with [`put_object_if_absent`]. Returns
+/// [`Error::PreconditionFailed`] when the object was changed or deleted, and
+/// [`Error::Conflict`] when a concurrent conditional write to the key is in progress.
+pub async fn cas_object(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+    expected_etag: impl Into<String>,
+    body: impl Into<ByteStream>,
+) -> Result<PutObjectOutput, Error> {
+    let key = key.into();
+    let expected_etag = expected_etag.into();
+    if expected_etag.is_empty() {
+        return Err(Error::ValidationError(format!(
+            "expected ETag of {key} is empty"
+        )));
+    }
+    client
+        .put_object()
+        .set_bucket(Some(bucket_name.into()))
+        .key(&key)
+        .set_body(Some(body.into()))
+        .if_match(expected_etag)
+        .send()
+        .await
+        .map_err(|e| precondition_or_sdk_error(e.into(), &key))
+}
+
+fn precondition_or_sdk_error(e: Error, key: &str) -> Error {
+    match e {
+        // 削除済みのオブジェクトへの If-Match は 412 ではなく 404 NoSuchKey になる
+        Error::AwsSdk { source, response }
+            if matches!(source.code(), Some("PreconditionFailed" | "NoSuchKey")) =>
+        {
+            Error::PreconditionFailed {
+                key: key.to_string(),
+                source,
+                response,
+            }
+        }
+        e => conflict_or_sdk_error(e, key),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PutObjectAtomicOutput {
+    pub e_tag: Option<String>,
+    pub version_id: Option<String>,
+}
+
+/// Publishes an object by writing it to a temporary key and copying it to `key` with a
+/// condition, so readers never see a partial object and concurrent publishers do not
+/// overwrite each other. The temporary object is always deleted.
+///
+/// With `if_match`, `key` is replaced only if its ETag still matches; without it, `key` is
+/// only created if it does not exist. Otherwise [`Error::Conflict`] is returned.
+pub async fn put_object_atomic(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+    body: impl Into<ByteStream>,
+    content_type: Option<impl Into<String>>,
+    if_match: Option<impl Into<String>>,
+) -> Result<PutObjectAtomicOutput, Error> {
+    let bucket_name = bucket_name.into();
+    let key = key.into();
+    let nanos = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    let temp_key = format!("{key}.{nanos}-{}.tmp", std::process::id());
+    client
+        .put_object()
+        .bucket(&bucket_name)
+        .key(&temp_key)
+        .body(body.into())
+        .set_content_type(content_type.map(Into::into))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+
+    let source = copy_source(&bucket_name, &temp_key);
+    let mut copy = client
+        .copy_object()
+        .bucket(&bucket_name)
+        .key(&key)
+        .copy_source(source);
+    copy = match if_match {
+        Some(if_match) => copy.if_match(if_match),
+        None => copy.if_none_match("*"),
+    };
+    let result = copy.send().await;
+    let deleted = delete_object(client, &bucket_name, &temp_key).await;
+
+    let output = result.map_err(|e| conflict_or_sdk_error(e.into(), &key))?;
+    deleted?;
+    Ok(PutObjectAtomicOutput {
+        e_tag: output.copy_object_result.and_then(|r| r.e_tag),
+        version_id: output.version_id,
+    })
+}
+
+fn conflict_or_sdk_error(e: Error, key: &str) -> Error {
+    // 412: 条件不一致, 409: 同じキーへの条件付き書き込みの競合
+    match e {
+        Error::AwsSdk { source, response }
+            if matches!(
+                source.code(),
+                Some("PreconditionFailed" | "ConditionalRequestConflict")
+            ) =>
+        {
+            Error::Conflict {
+                key: key.to_string(),
+                source,
+                response,
+            }
+        }
+        e => e,
+    }
+}
+
+/// Uploads a file with the default [`TransferOptions`]. See [`put_object_from_path_with_options`].
 pub async fn put_object_from_path(
     client: &Client,
     bucket_name: impl Into<String>,
@@ -140,13 +406,58 @@ pub async fn put_object_from_path(
     content_type: Option<impl Into<String>>,
     content_disposition: Option<impl Into<String>>,
 ) -> Result<PutObjectOutput, Error> {
-    put_object(
+    put_object_from_path_with_options(
         client,
         bucket_name,
         key,
-        ByteStream::from_path(file_path).await?,
+        file_path,
         content_type,
         content_disposition,
+        &TransferOptions::default(),
+    )
+    .await
+}
+
+/// Uploads a file, with a multipart upload if it is larger than the part size of `options`.
+/// `options` also sets the progress callback and the bandwidth limit. A failed multipart upload
+/// is aborted.
+pub async fn put_object_from_path_with_options(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+    file_path: impl AsRef<Path>,
+    content_type: Option<impl Into<String>>,
+    content_disposition: Option<impl Into<String>>,
+    options: &TransferOptions,
+) -> Result<PutObjectOutput, Error> {
+    crate::transfer::upload_from_path(
+        client,
+        bucket_name.into(),
+        key.into(),
+        file_path.as_ref(),
+        content_type.map(Into::into),
+        content_disposition.map(Into::into),
+        options,
+    )
+    .await
+}
+
+/// Downloads an object to a file and returns the number of bytes written, with the progress
+/// callback and the bandwidth limit of `options`. The total passed to the progress callback is
+/// the content length (0 if unknown).
+pub async fn get_object_to_path(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+    file_path: impl AsRef<Path>,
+    options: &TransferOptions,
+) -> Result<u64, Error> {
+    crate::transfer::download_to_path(
+        client,
+        bucket_name.into(),
+        key.into(),
+        file_path.as_ref(),
+        options,
     )
     .await
 }
@@ -222,11 +533,7 @@ pub async fn copy_object(
     dst_bucket_name: impl Into<String>,
     dst_key: impl Into<String>,
 ) -> Result<CopyObjectOutput, Error> {
-    let source = format!(
-        "{}/{}",
-        urlencoding::Encoded(src_bucket_name.into()),
-        urlencoding::Encoded(src_key.into())
-    );
+    let source = copy_source(&src_bucket_name.into(), &src_key.into());
     client
         .copy_object()
         .bucket(dst_bucket_name.into())
@@ -262,3 +569,587 @@ pub async fn copy_objects_prefix(
     }
     Ok(())
 }
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpdateMetadataOutput {
+    pub e_tag: Option<String>,
+    pub version_id: Option<String>,
+}
+
+/// Replaces the user metadata (and the content type if given) of an object by copying it onto
+/// itself with `MetadataDirective::REPLACE`, with multipart copy for objects over 5 GB.
+///
+/// Cache-Control, Content-Disposition, Content-Encoding, Content-Language, the storage class
+/// and the server side encryption are kept. The copy fails if the object changed since it was read.
+/// Tags are not kept by the multipart copy.
+pub async fn update_object_metadata(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+    new_metadata: HashMap<String, String>,
+    new_content_type: Option<impl Into<String>>,
+) -> Result<UpdateMetadataOutput, Error> {
+    let bucket_name = bucket_name.into();
+    let key = key.into();
+    let head = client
+        .head_object()
+        .bucket(&bucket_name)
+        .key(&key)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    let e_tag = head
+        .e_tag()
+        .ok_or_else(|| Error::ValidationError("e_tag is missing".to_string()))?
+        .to_string();
+    let size = head.content_length().unwrap_or_default().max(0) as u64;
+    let content_type = new_content_type
+        .map(Into::into)
+        .or_else(|| head.content_type().map(ToString::to_string));
+    let source = copy_source(&bucket_name, &key);
+
+    if size <= MAX_COPY_OBJECT_SIZE {
+        let output = client
+            .copy_object()
+            .bucket(&bucket_name)
+            .key(&key)
+            .copy_source(source)
+            .copy_source_if_match(e_tag)
+            .metadata_directive(MetadataDirective::Replace)
+            .set_metadata(Some(new_metadata))
+            .set_content_type(content_type)
+            .set_cache_control(head.cache_control)
+            .set_content_disposition(head.content_disposition)
+            .set_content_encoding(head.content_encoding)
+            .set_content_language(head.content_language)
+            .set_storage_class(head.storage_class)
+            .set_server_side_encryption(head.server_side_encryption)
+            .set_ssekms_key_id(head.ssekms_key_id)
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        return Ok(UpdateMetadataOutput {
+            e_tag: output.copy_object_result.and_then(|r| r.e_tag),
+            version_id: output.version_id,
+        });
+    }
+
+    let upload = client
+        .create_multipart_upload()
+        .bucket(&bucket_name)
+        .key(&key)
+        .set_metadata(Some(new_metadata))
+        .set_content_type(content_type)
+        .set_cache_control(head.cache_control)
+        .set_content_disposition(head.content_disposition)
+        .set_content_encoding(head.content_encoding)
+        .set_content_language(head.content_language)
+        .set_storage_class(head.storage_class)
+        .set_server_side_encryption(head.server_side_encryption)
+        .set_ssekms_key_id(head.ssekms_key_id)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    let upload_id = upload
+        .upload_id()
+        .ok_or_else(|| Error::ValidationError("upload_id is missing".to_string()))?;
+
+    let result = copy_parts(client, &bucket_name, &key, upload_id, &source, &e_tag, size).await;
+    let parts = match result {
+        Ok(parts) => parts,
+        Err(e) => {
+            // 途中のパートが残ると課金対象になるので中断する
+            let _ = abort_multipart_upload(client, &bucket_name, &key, upload_id).await;
+            return Err(e);
+        }
+    };
+
+    let result = client
+        .complete_multipart_upload()
+        .bucket(&bucket_name)
+        .key(&key)
+        .upload_id(upload_id)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .set_parts(Some(parts))
+                .build(),
+        )
+        .send()
+        .await;
+    let output = match result {
+        Ok(output) => output,
+        Err(e) => {
+            // 完了に失敗した時もコピー済みのパートが残るので中断する
+            let _ = abort_multipart_upload(client, &bucket_name, &key, upload_id).await;
+            return Err(from_aws_sdk_error(e));
+        }
+    };
+    Ok(UpdateMetadataOutput {
+        e_tag: output.e_tag,
+        version_id: output.version_id,
+    })
+}
+
+async fn copy_parts(
+    client: &Client,
+    bucket_name: &str,
+    key: &str,
+    upload_id: &str,
+    source: &str,
+    e_tag: &str,
+    size: u64,
+) -> Result<Vec<CompletedPart>, Error> {
+    let mut parts = vec![];
+    for (i, (start, end)) in copy_part_ranges(size).into_iter().enumerate() {
+        let part_number = i as i32 + 1;
+        let output = client
+            .upload_part_copy()
+            .bucket(bucket_name)
+            .key(key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .copy_source(source)
+            .copy_source_if_match(e_tag)
+            .copy_source_range(format!("bytes={start}-{end}"))
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        parts.push(
+            CompletedPart::builder()
+                .set_e_tag(output.copy_part_result.and_then(|r| r.e_tag))
+                .part_number(part_number)
+                .build(),
+        );
+    }
+    Ok(parts)
+}
+
+/// Aborts a multipart upload so that its uploaded parts are no longer charged.
+pub async fn abort_multipart_upload(
+    client: &Client,
+    bucket_name: &str,
+    key: &str,
+    upload_id: &str,
+) -> Result<(), Error> {
+    client
+        .abort_multipart_upload()
+        .bucket(bucket_name)
+        .key(key)
+        .upload_id(upload_id)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+/// Inclusive byte ranges of the parts, within the limit of 10,000 parts.
+fn copy_part_ranges(size: u64) -> Vec<(u64, u64)> {
+    let part_size = COPY_PART_SIZE.max(size.div_ceil(MAX_PARTS));
+    (0..size)
+        .step_by(part_size as usize)
+        .map(|start| (start, (start + part_size).min(size) - 1))
+        .collect()
+}
+
+/// Sets a canned ACL on the object. Fails with AccessControlListNotSupported if the bucket
+/// enforces the bucket owner, see [`crate::bucket::put_bucket_ownership_controls`].
+pub async fn put_object_acl(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+    acl: ObjectCannedAcl,
+) -> Result<PutObjectAclOutput, Error> {
+    client
+        .put_object_acl()
+        .bucket(bucket_name)
+        .key(key)
+        .acl(acl)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Owner and grants of the object.
+pub async fn get_object_acl(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+) -> Result<GetObjectAclOutput, Error> {
+    client
+        .get_object_acl()
+        .bucket(bucket_name)
+        .key(key)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Makes the object readable by anyone with the `public-read` ACL. `allow_public_read` must
+/// be true to confirm that publishing the object is intended.
+///
+/// The Block Public Access settings of the bucket and the account must allow public ACLs.
+pub async fn make_object_public_read(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    key: impl Into<String>,
+    allow_public_read: bool,
+) -> Result<PutObjectAclOutput, Error> {
+    if !allow_public_read {
+        return Err(Error::ValidationError(
+            "allow_public_read must be true to make an object public".to_string(),
+        ));
+    }
+    put_object_acl(client, bucket_name, key, ObjectCannedAcl::PublicRead).await
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_s3::{
+        error::ErrorMetadata,
+        operation::{copy_object::CopyObjectError, put_object::PutObjectError},
+    };
+
+    use super::*;
+    use crate::error::{AwsUtilError, ResponseMetadata};
+
+    #[test]
+    fn test_conflict_or_sdk_error() {
+        let error = |code: &str| Error::AwsSdk {
+            source: Box::new(aws_sdk_s3::Error::from(CopyObjectError::generic(
+                ErrorMetadata::builder().code(code).build(),
+            ))),
+            response: ResponseMetadata {
+                status: Some(412),
+                request_id: Some("4442587FB7D0A2F9".to_string()),
+                extended_request_id: Some("vlR7PnpV2Ce81puvXYuYd5ZDAbA=".to_string()),
+            },
+        };
+        let e = conflict_or_sdk_error(error("PreconditionFailed"), "config.json");
+        assert!(matches!(&e, Error::Conflict { key, .. } if key == "config.json"));
+        assert_eq!(e.error_code(), Some("PreconditionFailed"));
+        // レスポンスのリクエスト ID は変換後も残る
+        assert_eq!(e.aws_request_id(), Some("4442587FB7D0A2F9"));
+        assert_eq!(
+            e.extended_request_id(),
+            Some("vlR7PnpV2Ce81puvXYuYd5ZDAbA=")
+        );
+        assert_eq!(e.http_status(), Some(412));
+        assert!(matches!(
+            conflict_or_sdk_error(error("AccessDenied"), "config.json"),
+            Error::AwsSdk { .. }
+        ));
+    }
+
+    #[test]
+    fn test_precondition_or_sdk_error() {
+        let error = |code: &str| Error::AwsSdk {
+            source: Box::new(aws_sdk_s3::Error::from(PutObjectError::generic(
+                ErrorMetadata::builder().code(code).build(),
+            ))),
+            response: ResponseMetadata::default(),
+        };
+        let e = precondition_or_sdk_error(error("PreconditionFailed"), "locks/job");
+        assert!(matches!(&e, Error::PreconditionFailed { key, .. } if key == "locks/job"));
+        assert!(e.is_precondition_failed());
+        assert_eq!(e.error_code(), Some("PreconditionFailed"));
+        assert!(
+            precondition_or_sdk_error(error("NoSuchKey"), "locks/job").is_precondition_failed()
+        );
+        assert!(matches!(
+            precondition_or_sdk_error(error("ConditionalRequestConflict"), "locks/job"),
+            Error::Conflict { .. }
+        ));
+        assert!(matches!(
+            precondition_or_sdk_error(error("AccessDenied"), "locks/job"),
+            Error::AwsSdk { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_put_object_if_absent() {
+        let mut server = mockito::Server::new_async().await;
+        let created = server
+            .mock("PUT", "/my-bucket/locks/job")
+            .match_query(mockito::Matcher::Any)
+            .match_header("If-None-Match", "*")
+            .match_body("owner-1")
+            .with_header("ETag", "\"etag-1\"")
+            .expect(1)
+            .create_async()
+            .await;
+        server
+            .mock("PUT", "/my-bucket/locks/taken")
+            .match_query(mockito::Matcher::Any)
+            .with_status(412)
+            .with_body("<Error><Code>PreconditionFailed</Code><Message>At least one of the pre-conditions you specified did not hold</Message></Error>")
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let output = put_object_if_absent(
+            &client,
+            "my-bucket",
+            "locks/job",
+            b"owner-1".to_vec(),
+            None::<String>,
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.e_tag(), Some("\"etag-1\""));
+        created.assert_async().await;
+
+        let e = put_object_if_absent(
+            &client,
+            "my-bucket",
+            "locks/taken",
+            b"owner-2".to_vec(),
+            None::<String>,
+        )
+        .await
+        .unwrap_err();
+        assert!(e.is_precondition_failed());
+    }
+
+    #[tokio::test]
+    async fn test_cas_object() {
+        let mut server = mockito::Server::new_async().await;
+        let replaced = server
+            .mock("PUT", "/my-bucket/locks/job")
+            .match_query(mockito::Matcher::Any)
+            .match_header("If-Match", "\"etag-1\"")
+            .match_body("owner-1 renewed")
+            .with_header("ETag", "\"etag-2\"")
+            .expect(1)
+            .create_async()
+            .await;
+        for (key, status, code) in [
+            ("locks/changed", 412, "PreconditionFailed"),
+            ("locks/deleted", 404, "NoSuchKey"),
+            ("locks/busy", 409, "ConditionalRequestConflict"),
+        ] {
+            server
+                .mock("PUT", format!("/my-bucket/{key}").as_str())
+                .match_query(mockito::Matcher::Any)
+                .with_status(status)
+                .with_body(format!(
+                    "<Error><Code>{code}</Code><Message>{code}</Message></Error>"
+                ))
+                .create_async()
+                .await;
+        }
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let output = cas_object(
+            &client,
+            "my-bucket",
+            "locks/job",
+            "\"etag-1\"",
+            b"owner-1 renewed".to_vec(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.e_tag(), Some("\"etag-2\""));
+        replaced.assert_async().await;
+
+        for key in ["locks/changed", "locks/deleted"] {
+            let e = cas_object(&client, "my-bucket", key, "\"etag-1\"", b"owner-1".to_vec())
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(&e, Error::PreconditionFailed { key: k, .. } if k == key),
+                "{e:?}"
+            );
+        }
+        let e = cas_object(
+            &client,
+            "my-bucket",
+            "locks/busy",
+            "\"etag-1\"",
+            b"owner-1".to_vec(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(e, Error::Conflict { .. }), "{e:?}");
+    }
+
+    #[tokio::test]
+    async fn test_cas_object_without_etag() {
+        let client = Client::from_conf(
+            aws_sdk_s3::Config::builder()
+                .behavior_version_latest()
+                .build(),
+        );
+        let result = cas_object(&client, "my-bucket", "locks/job", "", b"owner-1".to_vec()).await;
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_make_object_public_read_without_opt_in() {
+        let client = Client::from_conf(
+            aws_sdk_s3::Config::builder()
+                .behavior_version_latest()
+                .build(),
+        );
+        let result = make_object_public_read(&client, "my-bucket", "index.html", false).await;
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_decompressed_reader() {
+        use async_compression::tokio::bufread::{GzipEncoder, ZstdEncoder};
+
+        assert_eq!(
+            Compression::detect(Some("gzip"), "data.json"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::detect(None, "firehose/2026/10/16/part-0001.gz"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::detect(None, "athena/result.csv.zst"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::detect(None, "athena/result.csv"), None);
+
+        let data = b"id,name\n1,a\n".repeat(100);
+        let mut gzip = Vec::new();
+        GzipEncoder::new(&data[..])
+            .read_to_end(&mut gzip)
+            .await
+            .unwrap();
+        // 2 つのメンバーを連結した gzip
+        let gzip = [gzip.clone(), gzip].concat();
+        let mut zstd = Vec::new();
+        ZstdEncoder::new(&data[..])
+            .read_to_end(&mut zstd)
+            .await
+            .unwrap();
+
+        let mut out = Vec::new();
+        decompressed_reader(
+            BufReader::new(std::io::Cursor::new(gzip)),
+            Some(Compression::Gzip),
+        )
+        .read_to_end(&mut out)
+        .await
+        .unwrap();
+        assert_eq!(out, [data.clone(), data.clone()].concat());
+
+        let mut out = Vec::new();
+        decompressed_reader(
+            BufReader::new(std::io::Cursor::new(zstd)),
+            Some(Compression::Zstd),
+        )
+        .read_to_end(&mut out)
+        .await
+        .unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn test_copy_part_ranges() {
+        let gib = 1024 * 1024 * 1024;
+        let ranges = copy_part_ranges(6 * gib + 1);
+        assert_eq!(ranges.len(), 13);
+        assert_eq!(ranges[0], (0, COPY_PART_SIZE - 1));
+        assert_eq!(ranges[12], (6 * gib, 6 * gib));
+
+        // 5 TB でもパート数は上限以内
+        let ranges = copy_part_ranges(5 * 1024 * gib);
+        assert_eq!(ranges.len(), MAX_PARTS as usize);
+        assert_eq!(ranges.last().unwrap().1, 5 * 1024 * gib - 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_object_metadata_aborts_on_complete_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("HEAD", "/my-bucket/big.bin")
+            .with_header("ETag", "\"etag-1\"")
+            .with_header(
+                "Content-Length",
+                &(5 * 1024 * 1024 * 1024 + 1u64).to_string(),
+            )
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/my-bucket/big.bin")
+            .match_query(mockito::Matcher::UrlEncoded("uploads".into(), "".into()))
+            .with_body("<InitiateMultipartUploadResult><Bucket>my-bucket</Bucket><Key>big.bin</Key><UploadId>upload-1</UploadId></InitiateMultipartUploadResult>")
+            .create_async()
+            .await;
+        let copied = server
+            .mock("PUT", "/my-bucket/big.bin")
+            .match_query(mockito::Matcher::Any)
+            .with_body("<CopyPartResult><ETag>\"part\"</ETag></CopyPartResult>")
+            .expect(11)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/my-bucket/big.bin")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "uploadId".into(),
+                "upload-1".into(),
+            ))
+            .with_status(400)
+            .with_body("<Error><Code>InvalidPart</Code><Message>invalid part</Message></Error>")
+            .create_async()
+            .await;
+        let aborted = server
+            .mock("DELETE", "/my-bucket/big.bin")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "uploadId".into(),
+                "upload-1".into(),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let e = update_object_metadata(
+            &client,
+            "my-bucket",
+            "big.bin",
+            HashMap::from([("owner".to_string(), "team-a".to_string())]),
+            None::<String>,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(e.error_code(), Some("InvalidPart"));
+        copied.assert_async().await;
+        aborted.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_read_body_capped() {
+        let body = || ByteStream::from_static(b"0123456789");
+        let bytes = read_body_capped(body(), Some(10), "k".to_string(), 10)
+            .await
+            .unwrap();
+        assert_eq!(&bytes[..], b"0123456789");
+
+        let result = read_body_capped(body(), Some(10), "k".to_string(), 9).await;
+        assert!(matches!(
+            result,
+            Err(Error::ObjectTooLarge {
+                size: 10,
+                max_size: 9,
+                ..
+            })
+        ));
+
+        // Content-Length より実際のボディが長い
+        let result = read_body_capped(body(), Some(5), "k".to_string(), 8).await;
+        assert!(matches!(
+            result,
+            Err(Error::ObjectTooLarge {
+                size: 10,
+                max_size: 8,
+                ..
+            })
+        ));
+    }
+}
@@ -2,10 +2,15 @@ use std::time::Duration;
 
 use aws_sdk_s3::{
     Client,
+    operation::complete_multipart_upload::CompleteMultipartUploadOutput,
     presigning::{PresignedRequest, PresigningConfig},
+    types::{CompletedMultipartUpload, CompletedPart},
 };
 
-use crate::error::{Error, from_aws_sdk_error};
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    object::abort_multipart_upload,
+};
 
 pub async fn put_presigned(
     client: &Client,
@@ -40,3 +45,252 @@ pub async fn get_presigned(
 pub fn presigned_url(presigned_request: &PresignedRequest) -> String {
     presigned_request.uri().to_string()
 }
+
+/// Max parts of a multipart upload.
+const MAX_PARTS: i32 = 10_000;
+
+/// Multipart upload created by [`create_presigned_multipart`], to be passed to the client.
+#[derive(Debug, Clone)]
+pub struct PresignedMultipartUpload {
+    pub upload_id: String,
+    /// URLs to PUT each part to, in the order of the part numbers (from 1).
+    pub parts: Vec<PresignedPart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresignedPart {
+    pub part_number: i32,
+    pub url: String,
+}
+
+/// Part uploaded by the client, with the ETag header of the response to its PUT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadedPart {
+    pub part_number: i32,
+    pub e_tag: String,
+}
+
+/// Creates a multipart upload and presigns `part_count` UploadPart URLs, so that browsers and
+/// mobile clients upload large files directly to S3. Parts except the last must be at least
+/// 5 MiB. Finish it with [`complete_presigned_multipart`], or [`abort_presigned_multipart`].
+pub async fn create_presigned_multipart(
+    client: &Client,
+    bucket_name: &str,
+    key: &str,
+    content_type: Option<&str>,
+    part_count: i32,
+    duration: Duration,
+) -> Result<PresignedMultipartUpload, Error> {
+    if !(1..=MAX_PARTS).contains(&part_count) {
+        return Err(Error::ValidationError(format!(
+            "part_count must be between 1 and {MAX_PARTS}: {part_count}"
+        )));
+    }
+    let upload = client
+        .create_multipart_upload()
+        .bucket(bucket_name)
+        .key(key)
+        .set_content_type(content_type.map(Into::into))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    let upload_id = upload
+        .upload_id
+        .ok_or_else(|| Error::ValidationError("upload_id is missing".to_string()))?;
+    let mut parts = Vec::with_capacity(part_count as usize);
+    for part_number in 1..=part_count {
+        let presigned =
+            match presign_upload_part(client, bucket_name, key, &upload_id, part_number, duration)
+                .await
+            {
+                Ok(presigned) => presigned,
+                Err(e) => {
+                    // 呼び出し元は upload_id を受け取れないので、ここで中断する
+                    let _ = abort_multipart_upload(client, bucket_name, key, &upload_id).await;
+                    return Err(e);
+                }
+            };
+        parts.push(PresignedPart {
+            part_number,
+            url: presigned_url(&presigned),
+        });
+    }
+    Ok(PresignedMultipartUpload { upload_id, parts })
+}
+
+/// Presigns the UploadPart URL of a part, e.g. to reissue an expired one.
+pub async fn presign_upload_part(
+    client: &Client,
+    bucket_name: &str,
+    key: &str,
+    upload_id: &str,
+    part_number: i32,
+    duration: Duration,
+) -> Result<PresignedRequest, Error> {
+    client
+        .upload_part()
+        .bucket(bucket_name)
+        .key(key)
+        .upload_id(upload_id)
+        .part_number(part_number)
+        .presigned(PresigningConfig::expires_in(duration)?)
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Completes a multipart upload created by [`create_presigned_multipart`] with the parts the
+/// client reported. The parts may be in any order.
+pub async fn complete_presigned_multipart(
+    client: &Client,
+    bucket_name: &str,
+    key: &str,
+    upload_id: &str,
+    mut parts: Vec<UploadedPart>,
+) -> Result<CompleteMultipartUploadOutput, Error> {
+    if parts.is_empty() {
+        return Err(Error::ValidationError("parts are empty".to_string()));
+    }
+    // S3 はパート番号の昇順でないと InvalidPartOrder になる
+    parts.sort_by_key(|part| part.part_number);
+    client
+        .complete_multipart_upload()
+        .bucket(bucket_name)
+        .key(key)
+        .upload_id(upload_id)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .set_parts(Some(
+                    parts
+                        .into_iter()
+                        .map(|part| {
+                            CompletedPart::builder()
+                                .part_number(part.part_number)
+                                .e_tag(part.e_tag)
+                                .build()
+                        })
+                        .collect(),
+                ))
+                .build(),
+        )
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Aborts a multipart upload, e.g. when the client gave up, so that its parts are not charged.
+pub async fn abort_presigned_multipart(
+    client: &Client,
+    bucket_name: &str,
+    key: &str,
+    upload_id: &str,
+) -> Result<(), Error> {
+    abort_multipart_upload(client, bucket_name, key, upload_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_s3::config::{Credentials, Region};
+
+    use super::*;
+
+    fn client() -> Client {
+        Client::from_conf(
+            aws_sdk_s3::Config::builder()
+                .behavior_version_latest()
+                .region(Region::new("ap-northeast-1"))
+                .credentials_provider(Credentials::for_tests())
+                .build(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_presign_upload_part() {
+        let presigned = presign_upload_part(
+            &client(),
+            "my-bucket",
+            "uploads/video.mp4",
+            "upload-1",
+            3,
+            Duration::from_secs(900),
+        )
+        .await
+        .unwrap();
+        assert_eq!(presigned.method(), "PUT");
+        let url = presigned_url(&presigned);
+        assert!(
+            url.starts_with("https://my-bucket.s3.ap-northeast-1.amazonaws.com/uploads/video.mp4?")
+        );
+        assert!(url.contains("partNumber=3"));
+        assert!(url.contains("uploadId=upload-1"));
+        assert!(url.contains("X-Amz-Expires=900"));
+    }
+
+    #[tokio::test]
+    async fn test_create_presigned_multipart_aborts() {
+        let mut server = mockito::Server::new_async().await;
+        let create = server
+            .mock("POST", "/my-bucket/uploads/video.mp4")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "uploads".to_string(),
+                String::new(),
+            ))
+            .with_body(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<InitiateMultipartUploadResult><Bucket>my-bucket</Bucket><Key>uploads/video.mp4</Key><UploadId>upload-1</UploadId></InitiateMultipartUploadResult>"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let abort = server
+            .mock("DELETE", "/my-bucket/uploads/video.mp4")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "uploadId".to_string(),
+                "upload-1".to_string(),
+            ))
+            .with_status(204)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        // 署名付き URL の有効期限は 7 日まで
+        let result = create_presigned_multipart(
+            &client,
+            "my-bucket",
+            "uploads/video.mp4",
+            None,
+            2,
+            Duration::from_secs(8 * 24 * 3600),
+        )
+        .await;
+        assert!(result.is_err());
+        create.assert_async().await;
+        abort.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_presigned_multipart_validation() {
+        let client = client();
+        for part_count in [0, MAX_PARTS + 1] {
+            let result = create_presigned_multipart(
+                &client,
+                "my-bucket",
+                "uploads/video.mp4",
+                None,
+                part_count,
+                Duration::from_secs(900),
+            )
+            .await;
+            assert!(matches!(result, Err(Error::ValidationError(_))));
+        }
+        let result = complete_presigned_multipart(
+            &client,
+            "my-bucket",
+            "uploads/video.mp4",
+            "upload-1",
+            vec![],
+        )
+        .await;
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+    }
+}
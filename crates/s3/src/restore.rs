@@ -0,0 +1,312 @@
+use std::time::Duration;
+
+use aws_sdk_s3::{
+    Client,
+    error::ProvideErrorMetadata,
+    types::{GlacierJobParameters, ObjectStorageClass, RestoreRequest, Tier},
+};
+use futures_util::{StreamExt, TryStreamExt};
+
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    object::list_stream,
+};
+
+/// Concurrent RestoreObject / HeadObject calls.
+const CONCURRENCY: usize = 16;
+
+/// Result of [`restore_objects`], by key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RestoreSummary {
+    /// Keys a restore was requested for, to be passed to [`wait_for_restores`].
+    pub requested: Vec<String>,
+    /// Keys already being restored by an earlier request.
+    pub in_progress: Vec<String>,
+    /// Keys not in GLACIER or DEEP_ARCHIVE, readable without a restore.
+    pub skipped: Vec<String>,
+}
+
+impl RestoreSummary {
+    /// Requested and in progress keys.
+    pub fn pending(&self) -> Vec<String> {
+        self.requested
+            .iter()
+            .chain(&self.in_progress)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Restore status from the `x-amz-restore` header of HeadObject.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreStatus {
+    NotRequested,
+    InProgress,
+    /// Readable until `expiry_date` (e.g. `Fri, 21 Dec 2012 00:00:00 GMT`).
+    Restored {
+        expiry_date: Option<String>,
+    },
+}
+
+impl RestoreStatus {
+    /// Parses `ongoing-request="false", expiry-date="Fri, 21 Dec 2012 00:00:00 GMT"`.
+    pub fn parse(restore: Option<&str>) -> Self {
+        let Some(restore) = restore else {
+            return RestoreStatus::NotRequested;
+        };
+        if restore.contains(r#"ongoing-request="true""#) {
+            return RestoreStatus::InProgress;
+        }
+        let expiry_date = restore
+            .split_once(r#"expiry-date=""#)
+            .and_then(|(_, rest)| rest.split_once('"'))
+            .map(|(date, _)| date.to_string());
+        RestoreStatus::Restored { expiry_date }
+    }
+}
+
+enum Outcome {
+    Requested(String),
+    InProgress(String),
+    Skipped(String),
+}
+
+/// Requests a restore of every GLACIER / DEEP_ARCHIVE object under `prefix` for `days` days
+/// with the retrieval `tier`. Objects already being restored are reported as in progress.
+pub async fn restore_objects(
+    client: &Client,
+    bucket_name: &str,
+    prefix: &str,
+    days: i32,
+    tier: Tier,
+) -> Result<RestoreSummary, Error> {
+    let request = RestoreRequest::builder()
+        .days(days)
+        .glacier_job_parameters(GlacierJobParameters::builder().tier(tier).build()?)
+        .build();
+    let outcomes: Vec<Outcome> = list_stream(client, bucket_name, Some(prefix))
+        .map_ok(|object| {
+            let request = request.clone();
+            async move {
+                let key = object.key().unwrap_or_default().to_string();
+                if !matches!(
+                    object.storage_class(),
+                    Some(ObjectStorageClass::Glacier | ObjectStorageClass::DeepArchive)
+                ) {
+                    return Ok(Outcome::Skipped(key));
+                }
+                let result = client
+                    .restore_object()
+                    .bucket(bucket_name)
+                    .key(&key)
+                    .restore_request(request)
+                    .send()
+                    .await;
+                match result {
+                    Ok(_) => Ok(Outcome::Requested(key)),
+                    Err(e) if e.code() == Some("RestoreAlreadyInProgress") => {
+                        Ok(Outcome::InProgress(key))
+                    }
+                    Err(e) => Err(from_aws_sdk_error(e)),
+                }
+            }
+        })
+        .try_buffer_unordered(CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let mut summary = RestoreSummary::default();
+    for outcome in outcomes {
+        match outcome {
+            Outcome::Requested(key) => summary.requested.push(key),
+            Outcome::InProgress(key) => summary.in_progress.push(key),
+            Outcome::Skipped(key) => summary.skipped.push(key),
+        }
+    }
+    summary.requested.sort();
+    summary.in_progress.sort();
+    summary.skipped.sort();
+    Ok(summary)
+}
+
+/// Waits until all `keys` are restored, checking their HeadObject every `check_duration`.
+/// Restores take minutes (Expedited) to 48 hours (DEEP_ARCHIVE Bulk).
+pub async fn wait_for_restores(
+    client: &Client,
+    bucket_name: &str,
+    keys: Vec<String>,
+    timeout_duration: Duration,
+    check_duration: Duration,
+) -> Result<(), Error> {
+    tokio::time::timeout(timeout_duration, async {
+        let mut pending = keys;
+        loop {
+            let statuses: Vec<(String, RestoreStatus)> = futures_util::stream::iter(pending)
+                .map(|key| async move {
+                    let output = client
+                        .head_object()
+                        .bucket(bucket_name)
+                        .key(&key)
+                        .send()
+                        .await
+                        .map_err(from_aws_sdk_error)?;
+                    Ok::<_, Error>((key, RestoreStatus::parse(output.restore())))
+                })
+                .buffer_unordered(CONCURRENCY)
+                .try_collect()
+                .await?;
+            pending = vec![];
+            for (key, status) in statuses {
+                match status {
+                    RestoreStatus::Restored { .. } => {}
+                    RestoreStatus::InProgress => pending.push(key),
+                    RestoreStatus::NotRequested => {
+                        return Err(Error::ValidationError(format!(
+                            "restore is not requested: {key}"
+                        )));
+                    }
+                }
+            }
+            if pending.is_empty() {
+                return Ok(());
+            }
+            tokio::time::sleep(check_duration).await;
+        }
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{Matcher, Server};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_restore_objects() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/my-bucket/")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("list-type".to_string(), "2".to_string()),
+                Matcher::UrlEncoded("prefix".to_string(), "archive/".to_string()),
+            ]))
+            .with_body(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult>
+  <Name>my-bucket</Name><Prefix>archive/</Prefix><KeyCount>3</KeyCount><IsTruncated>false</IsTruncated>
+  <Contents><Key>archive/a.log</Key><Size>10</Size><StorageClass>GLACIER</StorageClass></Contents>
+  <Contents><Key>archive/b.log</Key><Size>10</Size><StorageClass>DEEP_ARCHIVE</StorageClass></Contents>
+  <Contents><Key>archive/c.log</Key><Size>10</Size><StorageClass>STANDARD</StorageClass></Contents>
+</ListBucketResult>"#,
+            )
+            .create_async()
+            .await;
+        let requested = server
+            .mock("POST", "/my-bucket/archive/a.log")
+            .match_query(Matcher::Regex("^restore".to_string()))
+            .match_body(Matcher::Regex(
+                "<Days>7</Days>.*<Tier>Bulk</Tier>".to_string(),
+            ))
+            .with_status(202)
+            .expect(1)
+            .create_async()
+            .await;
+        let in_progress = server
+            .mock("POST", "/my-bucket/archive/b.log")
+            .match_query(Matcher::Regex("^restore".to_string()))
+            .with_status(409)
+            .with_body(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error><Code>RestoreAlreadyInProgress</Code><Message>Object restore is already in progress</Message></Error>"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let summary = restore_objects(&client, "my-bucket", "archive/", 7, Tier::Bulk)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            RestoreSummary {
+                requested: vec!["archive/a.log".to_string()],
+                in_progress: vec!["archive/b.log".to_string()],
+                skipped: vec!["archive/c.log".to_string()],
+            }
+        );
+        assert_eq!(summary.pending(), vec!["archive/a.log", "archive/b.log"]);
+        requested.assert_async().await;
+        in_progress.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_restores() {
+        let mut server = Server::new_async().await;
+        // 1 回目は復元中、2 回目で復元済み
+        let ongoing = server
+            .mock("HEAD", "/my-bucket/archive/a.log")
+            .with_header("x-amz-restore", r#"ongoing-request="true""#)
+            .expect(1)
+            .create_async()
+            .await;
+        let restored = server
+            .mock("HEAD", "/my-bucket/archive/a.log")
+            .with_header(
+                "x-amz-restore",
+                r#"ongoing-request="false", expiry-date="Fri, 21 Dec 2012 00:00:00 GMT""#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        server
+            .mock("HEAD", "/my-bucket/archive/b.log")
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        wait_for_restores(
+            &client,
+            "my-bucket",
+            vec!["archive/a.log".to_string()],
+            Duration::from_secs(5),
+            Duration::from_millis(10),
+        )
+        .await
+        .unwrap();
+        ongoing.assert_async().await;
+        restored.assert_async().await;
+
+        // 復元を要求していないオブジェクトは待たない
+        let result = wait_for_restores(
+            &client,
+            "my-bucket",
+            vec!["archive/b.log".to_string()],
+            Duration::from_secs(5),
+            Duration::from_millis(10),
+        )
+        .await;
+        assert!(
+            matches!(result, Err(Error::ValidationError(_))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn test_restore_status() {
+        assert_eq!(RestoreStatus::parse(None), RestoreStatus::NotRequested);
+        assert_eq!(
+            RestoreStatus::parse(Some(r#"ongoing-request="true""#)),
+            RestoreStatus::InProgress
+        );
+        assert_eq!(
+            RestoreStatus::parse(Some(
+                r#"ongoing-request="false", expiry-date="Fri, 21 Dec 2012 00:00:00 GMT""#
+            )),
+            RestoreStatus::Restored {
+                expiry_date: Some("Fri, 21 Dec 2012 00:00:00 GMT".to_string())
+            }
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use aws_sdk_s3::Client;
+use chrono::{DateTime, FixedOffset};
+use futures_util::{TryStream, TryStreamExt};
+
+use crate::{
+    error::Error,
+    object::{get_object, get_object_string, list_stream},
+};
+
+/// One record of an S3 server access log.
+/// https://docs.aws.amazon.com/AmazonS3/latest/userguide/LogFormat.html
+///
+/// Fields logged as `-` are `None`. Fields added to the format later
+/// (host_header etc.) are `None` for older log lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessLogEntry {
+    pub bucket_owner: String,
+    pub bucket: String,
+    pub time: DateTime<FixedOffset>,
+    pub remote_ip: Option<String>,
+    pub requester: Option<String>,
+    pub request_id: String,
+    pub operation: String,
+    pub key: Option<String>,
+    pub request_uri: Option<String>,
+    pub http_status: Option<u16>,
+    pub error_code: Option<String>,
+    pub bytes_sent: Option<u64>,
+    pub object_size: Option<u64>,
+    pub total_time: Option<Duration>,
+    pub turnaround_time: Option<Duration>,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    pub version_id: Option<String>,
+    pub host_id: Option<String>,
+    pub signature_version: Option<String>,
+    pub cipher_suite: Option<String>,
+    pub authentication_type: Option<String>,
+    pub host_header: Option<String>,
+    pub tls_version: Option<String>,
+    pub access_point_arn: Option<String>,
+    pub acl_required: Option<String>,
+}
+
+/// Parses a single access log line.
+pub fn parse_line(line: &str) -> Result<AccessLogEntry, Error> {
+    let fields = split_fields(line)?;
+    if fields.len() < 18 {
+        return Err(Error::ValidationError(format!(
+            "access log line has {} fields: {line}",
+            fields.len()
+        )));
+    }
+    let field = |i: usize| -> Option<String> {
+        fields.get(i).filter(|f| **f != "-").map(|f| f.to_string())
+    };
+    let number = |i: usize| -> Result<Option<u64>, Error> {
+        field(i)
+            .map(|f| {
+                f.parse::<u64>()
+                    .map_err(|_| Error::ValidationError(format!("invalid number field {i}: {f}")))
+            })
+            .transpose()
+    };
+    let time = DateTime::parse_from_str(fields[2], "%d/%b/%Y:%H:%M:%S %z")
+        .map_err(|e| Error::ValidationError(format!("invalid time {}: {e}", fields[2])))?;
+
+    Ok(AccessLogEntry {
+        bucket_owner: fields[0].to_string(),
+        bucket: fields[1].to_string(),
+        time,
+        remote_ip: field(3),
+        requester: field(4),
+        request_id: fields[5].to_string(),
+        operation: fields[6].to_string(),
+        key: field(7),
+        request_uri: field(8),
+        http_status: number(9)?.and_then(|s| u16::try_from(s).ok()),
+        error_code: field(10),
+        bytes_sent: number(11)?,
+        object_size: number(12)?,
+        total_time: number(13)?.map(Duration::from_millis),
+        turnaround_time: number(14)?.map(Duration::from_millis),
+        referer: field(15),
+        user_agent: field(16),
+        version_id: field(17),
+        host_id: field(18),
+        signature_version: field(19),
+        cipher_suite: field(20),
+        authentication_type: field(21),
+        host_header: field(22),
+        tls_version: field(23),
+        access_point_arn: field(24),
+        acl_required: field(25),
+    })
+}
+
+/// Splits a line by spaces. `[...]` and `"..."` are treated as one field
+/// with the brackets or quotes removed.
+fn split_fields(line: &str) -> Result<Vec<&str>, Error> {
+    let mut fields = vec![];
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        let (field, remaining) = match rest.as_bytes()[0] {
+            b'[' => split_enclosed(rest, ']')?,
+            b'"' => split_enclosed(rest, '"')?,
+            _ => rest.split_once(' ').unwrap_or((rest, "")),
+        };
+        fields.push(field);
+        rest = remaining.trim_start();
+    }
+    Ok(fields)
+}
+
+fn split_enclosed(s: &str, close: char) -> Result<(&str, &str), Error> {
+    let end = s[1..]
+        .find(close)
+        .ok_or_else(|| Error::ValidationError(format!("unclosed field: {s}")))?;
+    Ok((&s[1..end + 1], &s[end + 2..]))
+}
+
+/// Streams entries of all log objects under `prefix` of the logging bucket.
+pub fn stream(
+    client: &Client,
+    bucket_name: impl Into<String>,
+    prefix: Option<impl Into<String>>,
+) -> impl TryStream<Ok = AccessLogEntry, Error = Error> {
+    let bucket_name = bucket_name.into();
+    list_stream(client, bucket_name.clone(), prefix)
+        .and_then(move |object| {
+            let client = client.clone();
+            let bucket_name = bucket_name.clone();
+            async move {
+                let output =
+                    get_object(&client, bucket_name, object.key().unwrap_or_default()).await?;
+                let (_, body) = get_object_string(output).await?;
+                let entries: Vec<Result<AccessLogEntry, Error>> = body
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .map(parse_line)
+                    .collect();
+                Ok(futures_util::stream::iter(entries))
+            }
+        })
+        .try_flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let line = r#"79a59df900b949e55d96a1e698fbacedfd6e09d98eacf8f8d5218e7cd47ef2be awsexamplebucket1 [06/Feb/2019:00:00:38 +0000] 192.0.2.3 79a59df900b949e55d96a1e698fbacedfd6e09d98eacf8f8d5218e7cd47ef2be 3E57427F3EXAMPLE REST.GET.VERSIONING - "GET /awsexamplebucket1?versioning HTTP/1.1" 200 - 113 - 7 - "-" "S3Console/0.4" - s9lzHYrFp76ZVxRcpX9+5cjAnEH2ROuNkd2BHfIa6UkFVdtjf5mKR3/eTPFvsiP/XV/VLi31234= SigV4 ECDHE-RSA-AES128-GCM-SHA256 AuthHeader awsexamplebucket1.s3.us-west-1.amazonaws.com TLSV1.2 arn:aws:s3:us-west-1:123456789012:accesspoint/example-AP Yes"#;
+
+        let entry = parse_line(line).unwrap();
+
+        assert_eq!(entry.bucket, "awsexamplebucket1");
+        assert_eq!(entry.time.to_rfc3339(), "2019-02-06T00:00:38+00:00");
+        assert_eq!(entry.remote_ip.as_deref(), Some("192.0.2.3"));
+        assert_eq!(entry.operation, "REST.GET.VERSIONING");
+        assert_eq!(entry.key, None);
+        assert_eq!(
+            entry.request_uri.as_deref(),
+            Some("GET /awsexamplebucket1?versioning HTTP/1.1")
+        );
+        assert_eq!(entry.http_status, Some(200));
+        assert_eq!(entry.bytes_sent, Some(113));
+        assert_eq!(entry.object_size, None);
+        assert_eq!(entry.total_time, Some(Duration::from_millis(7)));
+        assert_eq!(entry.turnaround_time, None);
+        assert_eq!(entry.referer, None);
+        assert_eq!(entry.user_agent.as_deref(), Some("S3Console/0.4"));
+        assert_eq!(entry.tls_version.as_deref(), Some("TLSV1.2"));
+        assert_eq!(entry.acl_required.as_deref(), Some("Yes"));
+    }
+
+    #[test]
+    fn test_parse_line_invalid() {
+        assert!(parse_line("owner bucket [06/Feb/2019:00:00:38 +0000").is_err());
+        assert!(parse_line("owner bucket").is_err());
+    }
+}
@@ -0,0 +1,502 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use aws_sdk_s3::{Client, primitives::ByteStream};
+use futures_util::{StreamExt, TryStreamExt, stream};
+use md5::{Digest, Md5};
+
+use crate::{
+    error::{Error, from_aws_sdk_error},
+    object::list_stream,
+};
+
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Encodings of the pre-compressed objects (feature `compression`).
+#[cfg(feature = "compression")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Gzip,
+    Brotli,
+}
+
+#[cfg(feature = "compression")]
+impl ContentEncoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Brotli => "br",
+        }
+    }
+}
+
+/// Rules of [`upload_static_assets`].
+///
+/// Cache-Control rules are matched in the order they are added against the path relative to
+/// the directory, with `/` separators. Patterns support `*` (any characters except `/`),
+/// `**` (any characters) and `?` (one character except `/`).
+#[derive(Debug, Clone)]
+pub struct AssetRules {
+    cache_control: Vec<(String, String)>,
+    default_cache_control: Option<String>,
+    #[cfg(feature = "compression")]
+    precompress: Option<ContentEncoding>,
+    force: bool,
+    concurrency: usize,
+}
+
+impl Default for AssetRules {
+    fn default() -> Self {
+        Self {
+            cache_control: vec![],
+            default_cache_control: None,
+            #[cfg(feature = "compression")]
+            precompress: None,
+            force: false,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+impl AssetRules {
+    /// Cache-Control of the files matching `pattern`, e.g.
+    /// `("assets/**", "public, max-age=31536000, immutable")`.
+    pub fn cache_control(
+        mut self,
+        pattern: impl Into<String>,
+        cache_control: impl Into<String>,
+    ) -> Self {
+        self.cache_control
+            .push((pattern.into(), cache_control.into()));
+        self
+    }
+
+    /// Cache-Control of the files matching no rule. None by default.
+    pub fn default_cache_control(mut self, cache_control: impl Into<String>) -> Self {
+        self.default_cache_control = Some(cache_control.into());
+        self
+    }
+
+    /// Uploads compressible files (text, JavaScript, JSON, SVG, wasm, ...) compressed with
+    /// `encoding` and the matching Content-Encoding, when it makes them smaller.
+    #[cfg(feature = "compression")]
+    pub fn precompress(mut self, encoding: ContentEncoding) -> Self {
+        self.precompress = Some(encoding);
+        self
+    }
+
+    /// Uploads every file even if the object has the same ETag, e.g. after changing the
+    /// Cache-Control rules of unchanged files.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Concurrent uploads, 8 by default.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    fn cache_control_for(&self, path: &str) -> Option<&str> {
+        self.cache_control
+            .iter()
+            .find(|(pattern, _)| glob_match(pattern, path))
+            .map(|(_, cache_control)| cache_control.as_str())
+            .or(self.default_cache_control.as_deref())
+    }
+}
+
+/// Result of [`upload_static_assets`], by key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StaticAssetsSummary {
+    pub uploaded: Vec<String>,
+    /// Keys whose object already had the ETag of the file.
+    pub unchanged: Vec<String>,
+}
+
+/// Uploads the files under `dir` to `bucket_name` with keys `{prefix}{relative path}`, with
+/// the Content-Type inferred from the extension ([`content_type_for`]) and the Cache-Control
+/// of `rules`.
+///
+/// Files whose object under the prefix has the MD5 of the uploaded body as ETag are skipped
+/// (the ETag of a PutObject is the MD5 of the body, except with SSE-KMS). Objects under the
+/// prefix without a file are kept.
+pub async fn upload_static_assets(
+    client: &Client,
+    dir: impl AsRef<Path>,
+    bucket_name: &str,
+    prefix: &str,
+    rules: &AssetRules,
+) -> Result<StaticAssetsSummary, Error> {
+    let dir = dir.as_ref();
+    let files = list_files(dir).await?;
+    let e_tags = if rules.force {
+        HashMap::new()
+    } else {
+        list_stream(client, bucket_name, Some(prefix))
+            .try_filter_map(|object| async move {
+                Ok(object
+                    .key
+                    .zip(object.e_tag)
+                    .map(|(key, e_tag)| (key, e_tag.trim_matches('"').to_string())))
+            })
+            .try_collect::<HashMap<_, _>>()
+            .await?
+    };
+
+    let results = stream::iter(files)
+        .map(|path| {
+            let e_tags = &e_tags;
+            async move {
+                let relative_path = relative_path(dir, &path)?;
+                let key = format!("{prefix}{relative_path}");
+                let asset = prepare_asset(&path, &relative_path, rules).await?;
+                if e_tags.get(&key) == Some(&asset.e_tag) {
+                    return Ok((key, false));
+                }
+                client
+                    .put_object()
+                    .bucket(bucket_name)
+                    .key(&key)
+                    .body(ByteStream::from(asset.body))
+                    .content_type(asset.content_type)
+                    .set_cache_control(asset.cache_control)
+                    .set_content_encoding(asset.content_encoding)
+                    .send()
+                    .await
+                    .map_err(from_aws_sdk_error)?;
+                Ok::<_, Error>((key, true))
+            }
+        })
+        .buffer_unordered(rules.concurrency)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let mut summary = StaticAssetsSummary::default();
+    for (key, uploaded) in results {
+        if uploaded {
+            summary.uploaded.push(key);
+        } else {
+            summary.unchanged.push(key);
+        }
+    }
+    summary.uploaded.sort();
+    summary.unchanged.sort();
+    Ok(summary)
+}
+
+struct Asset {
+    body: Vec<u8>,
+    e_tag: String,
+    content_type: &'static str,
+    cache_control: Option<String>,
+    content_encoding: Option<String>,
+}
+
+async fn prepare_asset(
+    path: &Path,
+    relative_path: &str,
+    rules: &AssetRules,
+) -> Result<Asset, Error> {
+    let body = tokio::fs::read(path).await?;
+    let content_type = content_type_for(relative_path);
+    #[cfg(feature = "compression")]
+    let (body, content_encoding) = match rules.precompress {
+        Some(encoding) if is_compressible(content_type) => {
+            let compressed = compress(&body, encoding).await?;
+            if compressed.len() < body.len() {
+                (compressed, Some(encoding.as_str().to_string()))
+            } else {
+                (body, None)
+            }
+        }
+        _ => (body, None),
+    };
+    #[cfg(not(feature = "compression"))]
+    let content_encoding = None;
+    Ok(Asset {
+        e_tag: md5_hex(&body),
+        body,
+        content_type,
+        cache_control: rules
+            .cache_control_for(relative_path)
+            .map(ToString::to_string),
+        content_encoding,
+    })
+}
+
+#[cfg(feature = "compression")]
+async fn compress(body: &[u8], encoding: ContentEncoding) -> Result<Vec<u8>, Error> {
+    use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder};
+    use tokio::io::AsyncReadExt;
+
+    // 同じ内容から同じバイト列になるので ETag で変更を判定できる
+    let mut compressed = Vec::new();
+    match encoding {
+        ContentEncoding::Gzip => GzipEncoder::new(body).read_to_end(&mut compressed).await?,
+        ContentEncoding::Brotli => {
+            BrotliEncoder::new(body)
+                .read_to_end(&mut compressed)
+                .await?
+        }
+    };
+    Ok(compressed)
+}
+
+#[cfg(feature = "compression")]
+fn is_compressible(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || [
+            "application/javascript",
+            "application/json",
+            "application/manifest+json",
+            "application/xml",
+            "application/wasm",
+            "image/svg+xml",
+            "font/ttf",
+            "font/otf",
+        ]
+        .iter()
+        .any(|t| content_type.starts_with(t))
+}
+
+fn md5_hex(body: &[u8]) -> String {
+    Md5::digest(body)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Content-Type from the extension of `path`, `application/octet-stream` if unknown.
+pub fn content_type_for(path: &str) -> &'static str {
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" | "cjs" => "application/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "webmanifest" => "application/manifest+json",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Matches `path` against a pattern with `*`, `**` and `?` (see [`AssetRules`]).
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let (pattern, path) = (pattern.as_bytes(), path.as_bytes());
+    let (mut p, mut s) = (0, 0);
+    // 一致しなかったときに伸ばすワイルドカード (種類, 直後のパターンの位置, パスの位置)
+    let mut backtrack: Vec<(Wildcard, usize, usize)> = vec![];
+    while p < pattern.len() || s < path.len() {
+        let (wildcard, len) = match &pattern[p..] {
+            [b'*', b'*', b'/', ..] => (Wildcard::Dirs, 3),
+            [b'*', b'*', ..] => (Wildcard::Globstar, 2),
+            [b'*', ..] => (Wildcard::Star, 1),
+            [b'?', ..] if path.get(s).is_some_and(|c| *c != b'/') => {
+                (p, s) = (p + 1, s + 1);
+                continue;
+            }
+            [c, ..] if *c != b'?' && path.get(s) == Some(c) => {
+                (p, s) = (p + 1, s + 1);
+                continue;
+            }
+            _ => match extend_wildcard(&mut backtrack, path) {
+                Some(next) => {
+                    (p, s) = next;
+                    continue;
+                }
+                None => return false,
+            },
+        };
+        p += len;
+        // 後の `**` は前のワイルドカードの分も伸ばせるので、前のものは伸ばさなくてよい。
+        // `*` が続く場合も後の `*` を伸ばせば足りる
+        match (wildcard, backtrack.last()) {
+            (Wildcard::Globstar, _) => backtrack.clear(),
+            (Wildcard::Star, Some((Wildcard::Star, ..))) => {
+                backtrack.pop();
+            }
+            _ => {}
+        }
+        backtrack.push((wildcard, p, s));
+    }
+    true
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Wildcard {
+    /// `*`, extended by a character other than `/`.
+    Star,
+    /// `**`, extended by any character.
+    Globstar,
+    /// `**/`, extended by a directory.
+    Dirs,
+}
+
+/// Extends the last wildcard that can match one more character (or directory) and drops the
+/// ones after it. Returns the positions in the pattern and the path to continue from.
+fn extend_wildcard(
+    backtrack: &mut Vec<(Wildcard, usize, usize)>,
+    path: &[u8],
+) -> Option<(usize, usize)> {
+    while let Some((wildcard, p, s)) = backtrack.pop() {
+        let next = match wildcard {
+            Wildcard::Star => path.get(s).filter(|c| **c != b'/').map(|_| s + 1),
+            Wildcard::Globstar => (s < path.len()).then_some(s + 1),
+            Wildcard::Dirs => path[s..].iter().position(|c| *c == b'/').map(|i| s + i + 1),
+        };
+        if let Some(next) = next {
+            backtrack.push((wildcard, p, next));
+            return Some((p, next));
+        }
+    }
+    None
+}
+
+fn relative_path(dir: &Path, path: &Path) -> Result<String, Error> {
+    let relative = path.strip_prefix(dir).map_err(|_| {
+        Error::ValidationError(format!("{} is not under {}", path.display(), dir.display()))
+    })?;
+    relative
+        .components()
+        .map(|c| {
+            c.as_os_str().to_str().ok_or_else(|| {
+                Error::ValidationError(format!("not a UTF-8 path: {}", path.display()))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|components| components.join("/"))
+}
+
+/// Files under `dir`, recursively, sorted.
+async fn list_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+            } else if file_type.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.html", "index.html"));
+        assert!(!glob_match("*.html", "docs/index.html"));
+        assert!(glob_match("**/*.html", "index.html"));
+        assert!(glob_match("**/*.html", "docs/guide/index.html"));
+        assert!(glob_match("assets/**", "assets/js/app.3f2a.js"));
+        assert!(!glob_match("assets/**", "public/assets/app.js"));
+        assert!(glob_match("assets/*.js", "assets/app.js"));
+        assert!(!glob_match("assets/*.js", "assets/js/app.js"));
+        assert!(glob_match("favicon.ic?", "favicon.ico"));
+        assert!(!glob_match("a?c", "a/c"));
+        assert!(glob_match(
+            "**/assets/**/*.js",
+            "public/assets/js/vendor/app.js"
+        ));
+        assert!(!glob_match(
+            "**/assets/**/*.js",
+            "public/assets/js/vendor/app.css"
+        ));
+        // バックトラックが多いパターンでも長いパスを照合できる
+        let path = format!("{}b", "a".repeat(10_000));
+        assert!(!glob_match("*a*a*a*a*a*a*a*a*c", &path));
+        assert!(glob_match("**a**a**a**b", &path));
+        assert!(!glob_match("**a**a**a**a**c", &path));
+    }
+
+    #[test]
+    fn test_cache_control_for() {
+        let rules = AssetRules::default()
+            .cache_control("**/*.html", "no-cache")
+            .cache_control("assets/**", "public, max-age=31536000, immutable")
+            .default_cache_control("public, max-age=300");
+        assert_eq!(rules.cache_control_for("index.html"), Some("no-cache"));
+        assert_eq!(
+            rules.cache_control_for("assets/app.js"),
+            Some("public, max-age=31536000, immutable")
+        );
+        assert_eq!(
+            rules.cache_control_for("robots.txt"),
+            Some("public, max-age=300")
+        );
+        assert_eq!(AssetRules::default().cache_control_for("index.html"), None);
+    }
+
+    #[test]
+    fn test_content_type_for() {
+        assert_eq!(content_type_for("index.HTML"), "text/html; charset=utf-8");
+        assert_eq!(
+            content_type_for("assets/app.mjs"),
+            "application/javascript; charset=utf-8"
+        );
+        assert_eq!(content_type_for("fonts/a.woff2"), "font/woff2");
+        assert_eq!(content_type_for("LICENSE"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_md5_hex() {
+        assert_eq!(md5_hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+    }
+
+    #[tokio::test]
+    async fn test_list_files() {
+        let dir = std::env::temp_dir().join(format!("static-assets-{}", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(dir.join("assets/js"))
+            .await
+            .unwrap();
+        tokio::fs::write(dir.join("index.html"), "<html></html>")
+            .await
+            .unwrap();
+        tokio::fs::write(dir.join("assets/js/app.js"), "console.log(1)")
+            .await
+            .unwrap();
+
+        let files = list_files(&dir).await.unwrap();
+        let relative = files
+            .iter()
+            .map(|path| relative_path(&dir, path).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(relative, vec!["assets/js/app.js", "index.html"]);
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,414 @@
+use std::{
+    convert::Infallible,
+    path::Path,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use aws_sdk_s3::{
+    Client,
+    operation::put_object::PutObjectOutput,
+    primitives::ByteStream,
+    types::{CompletedMultipartUpload, CompletedPart},
+};
+use aws_smithy_types::body::SdkBody;
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    time::{Instant, Sleep},
+};
+
+use crate::error::{Error, from_aws_sdk_error};
+
+/// Minimum part size of a multipart upload (except the last part).
+pub const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+const DEFAULT_PART_SIZE: usize = 8 * 1024 * 1024;
+
+const MAX_PARTS: u64 = 10_000;
+
+/// Size of the chunks of an upload body, each waiting for the bandwidth limit.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Options of [`crate::object::put_object_from_path_with_options`] and
+/// [`crate::object::get_object_to_path`].
+#[derive(Clone)]
+pub struct TransferOptions {
+    progress: Option<Arc<dyn Fn(u64, u64) + Send + Sync>>,
+    max_bytes_per_sec: Option<u64>,
+    part_size: usize,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            progress: None,
+            max_bytes_per_sec: None,
+            part_size: DEFAULT_PART_SIZE,
+        }
+    }
+}
+
+impl TransferOptions {
+    /// Called with (transferred bytes, total bytes) after each chunk or part.
+    pub fn progress(mut self, progress: impl Fn(u64, u64) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Limits the average transfer rate, checked every chunk of the body.
+    pub fn max_bytes_per_sec(mut self, max_bytes_per_sec: u64) -> Self {
+        self.max_bytes_per_sec = Some(max_bytes_per_sec).filter(|m| *m > 0);
+        self
+    }
+
+    /// Part size of multipart uploads. Files up to this size are uploaded with a single PutObject.
+    /// Values below [`MIN_PART_SIZE`] are raised to it, and the part size of a file is raised
+    /// further if needed to stay within 10,000 parts.
+    pub fn part_size(mut self, part_size: usize) -> Self {
+        self.part_size = part_size.max(MIN_PART_SIZE);
+        self
+    }
+
+    fn report(&self, transferred: u64, total: u64) {
+        if let Some(progress) = &self.progress {
+            progress(transferred, total);
+        }
+    }
+
+    /// Part size of a file of `size` bytes, within the limit of 10,000 parts.
+    fn part_size_for(&self, size: u64) -> u64 {
+        (self.part_size as u64).max(size.div_ceil(MAX_PARTS))
+    }
+}
+
+/// Bandwidth limit shared by the chunks of a transfer.
+struct Throttle {
+    max_bytes_per_sec: Option<u64>,
+    start: Instant,
+    transferred: AtomicU64,
+}
+
+impl Throttle {
+    fn new(max_bytes_per_sec: Option<u64>) -> Self {
+        Self {
+            max_bytes_per_sec,
+            start: Instant::now(),
+            transferred: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts `bytes` as transferred and returns how long to wait before sending them, so that
+    /// the bytes sent before them took the time allowed by the rate.
+    fn reserve(&self, bytes: u64) -> Option<Duration> {
+        self.max_bytes_per_sec?;
+        let sent = self.transferred.fetch_add(bytes, Ordering::Relaxed);
+        self.delay(sent, self.start.elapsed())
+    }
+
+    /// Waits after receiving `bytes`.
+    async fn wait(&self, bytes: u64) {
+        let transferred = self.transferred.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if let Some(delay) = self.delay(transferred, self.start.elapsed()) {
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// How long to wait so that `transferred` bytes took at least the time allowed by the rate.
+    fn delay(&self, transferred: u64, elapsed: Duration) -> Option<Duration> {
+        let max_bytes_per_sec = self.max_bytes_per_sec?;
+        let expected = Duration::from_secs_f64(transferred as f64 / max_bytes_per_sec as f64);
+        expected.checked_sub(elapsed).filter(|d| !d.is_zero())
+    }
+}
+
+/// Request body sent in chunks of [`CHUNK_SIZE`], each one after the delay of the throttle.
+struct ThrottledBody {
+    remaining: Bytes,
+    throttle: Arc<Throttle>,
+    pending: Option<(Bytes, Option<Pin<Box<Sleep>>>)>,
+}
+
+impl Body for ThrottledBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        let this = &mut *self;
+        if this.pending.is_none() {
+            if this.remaining.is_empty() {
+                return Poll::Ready(None);
+            }
+            let chunk = this
+                .remaining
+                .split_to(CHUNK_SIZE.min(this.remaining.len()));
+            let sleep = this
+                .throttle
+                .reserve(chunk.len() as u64)
+                .map(|delay| Box::pin(tokio::time::sleep(delay)));
+            this.pending = Some((chunk, sleep));
+        }
+        if let Some((_, Some(sleep))) = &mut this.pending {
+            ready!(sleep.as_mut().poll(cx));
+        }
+        let (chunk, _) = this.pending.take().expect("pending chunk is set above");
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.pending.is_none() && self.remaining.is_empty()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let pending = self.pending.as_ref().map_or(0, |(chunk, _)| chunk.len());
+        SizeHint::with_exact((pending + self.remaining.len()) as u64)
+    }
+}
+
+fn throttled_body(data: Bytes, throttle: &Arc<Throttle>) -> ByteStream {
+    if throttle.max_bytes_per_sec.is_none() {
+        return ByteStream::from(data);
+    }
+    let throttle = throttle.clone();
+    // 再試行時は同じデータから作り直す
+    ByteStream::new(SdkBody::retryable(move || {
+        SdkBody::from_body_1_x(ThrottledBody {
+            remaining: data.clone(),
+            throttle: throttle.clone(),
+            pending: None,
+        })
+    }))
+}
+
+pub(crate) async fn upload_from_path(
+    client: &Client,
+    bucket_name: String,
+    key: String,
+    file_path: &Path,
+    content_type: Option<String>,
+    content_disposition: Option<String>,
+    options: &TransferOptions,
+) -> Result<PutObjectOutput, Error> {
+    let mut file = tokio::fs::File::open(file_path).await?;
+    let size = file.metadata().await?.len();
+    let throttle = Arc::new(Throttle::new(options.max_bytes_per_sec));
+
+    if size <= options.part_size as u64 {
+        let mut body = Vec::with_capacity(size as usize);
+        file.read_to_end(&mut body).await?;
+        let output = client
+            .put_object()
+            .bucket(&bucket_name)
+            .key(&key)
+            .body(throttled_body(Bytes::from(body), &throttle))
+            .content_length(size as i64)
+            .set_content_type(content_type)
+            .set_content_disposition(content_disposition)
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        options.report(size, size);
+        return Ok(output);
+    }
+
+    let upload = client
+        .create_multipart_upload()
+        .bucket(&bucket_name)
+        .key(&key)
+        .set_content_type(content_type)
+        .set_content_disposition(content_disposition)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    let upload_id = upload
+        .upload_id()
+        .ok_or_else(|| Error::ValidationError("upload_id is missing".to_string()))?;
+
+    let result = upload_parts(
+        client,
+        &bucket_name,
+        &key,
+        upload_id,
+        &mut file,
+        size,
+        options,
+        &throttle,
+    )
+    .await;
+    let parts = match result {
+        Ok(parts) => parts,
+        Err(e) => {
+            // 途中のパートが残ると課金対象になるので中断する
+            let _ =
+                crate::object::abort_multipart_upload(client, &bucket_name, &key, upload_id).await;
+            return Err(e);
+        }
+    };
+
+    let output = client
+        .complete_multipart_upload()
+        .bucket(&bucket_name)
+        .key(&key)
+        .upload_id(upload_id)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .set_parts(Some(parts))
+                .build(),
+        )
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(PutObjectOutput::builder()
+        .set_e_tag(output.e_tag)
+        .set_version_id(output.version_id)
+        .set_expiration(output.expiration)
+        .set_server_side_encryption(output.server_side_encryption)
+        .set_ssekms_key_id(output.ssekms_key_id)
+        .set_bucket_key_enabled(output.bucket_key_enabled)
+        .size(size as i64)
+        .build())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn upload_parts(
+    client: &Client,
+    bucket_name: &str,
+    key: &str,
+    upload_id: &str,
+    file: &mut tokio::fs::File,
+    size: u64,
+    options: &TransferOptions,
+    throttle: &Arc<Throttle>,
+) -> Result<Vec<CompletedPart>, Error> {
+    let part_size = options.part_size_for(size);
+    let mut parts = vec![];
+    let mut transferred = 0u64;
+    let mut part_number = 1;
+    while transferred < size {
+        let mut buf = Vec::with_capacity(part_size as usize);
+        (&mut *file).take(part_size).read_to_end(&mut buf).await?;
+        if buf.is_empty() {
+            break;
+        }
+        let part_len = buf.len() as u64;
+        let output = client
+            .upload_part()
+            .bucket(bucket_name)
+            .key(key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(throttled_body(Bytes::from(buf), throttle))
+            .content_length(part_len as i64)
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        parts.push(
+            CompletedPart::builder()
+                .set_e_tag(output.e_tag)
+                .part_number(part_number)
+                .build(),
+        );
+        transferred += part_len;
+        part_number += 1;
+        options.report(transferred, size);
+    }
+    Ok(parts)
+}
+
+pub(crate) async fn download_to_path(
+    client: &Client,
+    bucket_name: String,
+    key: String,
+    file_path: &Path,
+    options: &TransferOptions,
+) -> Result<u64, Error> {
+    let output = client
+        .get_object()
+        .bucket(bucket_name)
+        .key(key)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    let total = output
+        .content_length()
+        .and_then(|l| u64::try_from(l).ok())
+        .unwrap_or_default();
+    let throttle = Throttle::new(options.max_bytes_per_sec);
+
+    let mut body = output.body;
+    let mut file = tokio::fs::File::create(file_path).await?;
+    let mut transferred = 0u64;
+    while let Some(chunk) = body.try_next().await? {
+        file.write_all(&chunk).await?;
+        transferred += chunk.len() as u64;
+        options.report(transferred, total);
+        throttle.wait(chunk.len() as u64).await;
+    }
+    file.flush().await?;
+    Ok(transferred)
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt;
+
+    use super::*;
+
+    #[test]
+    fn test_throttle_delay() {
+        let throttle = Throttle::new(Some(1000));
+
+        assert_eq!(
+            throttle.delay(500, Duration::from_millis(100)),
+            Some(Duration::from_millis(400))
+        );
+        assert_eq!(throttle.delay(500, Duration::from_secs(1)), None);
+        assert_eq!(Throttle::new(None).delay(500, Duration::ZERO), None);
+        assert_eq!(Throttle::new(None).reserve(500), None);
+    }
+
+    #[test]
+    fn test_transfer_options() {
+        let options = TransferOptions::default()
+            .part_size(1024)
+            .max_bytes_per_sec(0);
+
+        assert_eq!(options.part_size, MIN_PART_SIZE);
+        assert_eq!(options.max_bytes_per_sec, None);
+        assert_eq!(options.part_size_for(1024), MIN_PART_SIZE as u64);
+        // 10,000 パートに収まるようにパートサイズを大きくする
+        let size = 100 * 1024 * 1024 * 1024;
+        assert_eq!(options.part_size_for(size), size.div_ceil(MAX_PARTS));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttled_body() {
+        let data = Bytes::from(vec![0u8; CHUNK_SIZE * 2 + 10]);
+        let throttle = Arc::new(Throttle::new(Some(CHUNK_SIZE as u64)));
+        let mut body = ThrottledBody {
+            remaining: data,
+            throttle,
+            pending: None,
+        };
+        assert_eq!(body.size_hint().exact(), Some(CHUNK_SIZE as u64 * 2 + 10));
+
+        let start = tokio::time::Instant::now();
+        let mut chunks = vec![];
+        while let Some(frame) = body.frame().await {
+            let chunk = frame.unwrap().into_data().unwrap();
+            chunks.push((chunk.len(), start.elapsed().as_secs()));
+        }
+        // 1 秒あたり 1 チャンクずつ送る
+        assert_eq!(chunks, vec![(CHUNK_SIZE, 0), (CHUNK_SIZE, 1), (10, 2)]);
+        assert!(body.is_end_stream());
+    }
+}
@@ -1 +1,2 @@
 pub mod schedule_expression_builder;
+pub mod target_builder;
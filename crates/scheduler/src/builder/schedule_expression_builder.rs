@@ -1,4 +1,4 @@
-use crate::error::Error;
+use crate::error::{CronValidationError, Error};
 use chrono::{DateTime, Utc};
 
 /// Builder for one-time schedule expressions
@@ -33,7 +33,7 @@ impl Default for AtExpressionBuilder {
 }
 
 /// Time unit for rate-based schedules
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RateUnit {
     Minutes,
     Hours,
@@ -189,32 +189,6 @@ impl CronExpressionBuilder {
         self
     }
 
-    fn validate_field(field: &str, name: &str, min: i32, max: i32) -> Result<(), Error> {
-        // Skip validation for wildcards and special characters
-        if field == "*"
-            || field == "?"
-            || field.contains(',')
-            || field.contains('-')
-            || field.contains('/')
-            || field.contains('L')
-            || field.contains('W')
-            || field.contains('#')
-        {
-            return Ok(());
-        }
-
-        // Try to parse as number
-        if let Ok(value) = field.parse::<i32>()
-            && (value < min || value > max)
-        {
-            return Err(Error::ValidationError(format!(
-                "{name} must be between {min} and {max}"
-            )));
-        }
-
-        Ok(())
-    }
-
     pub fn build(&self) -> Result<String, Error> {
         let minutes = self.minutes.as_ref().ok_or_else(|| {
             Error::ValidationError("minutes is required for cron expression".to_string())
@@ -232,30 +206,13 @@ impl CronExpressionBuilder {
             Error::ValidationError("day_of_week is required for cron expression".to_string())
         })?;
 
-        // Validate fields
-        Self::validate_field(minutes, "minutes", 0, 59)?;
-        Self::validate_field(hours, "hours", 0, 23)?;
-        Self::validate_field(day_of_month, "day_of_month", 1, 31)?;
-
-        // Validate month (1-12 or JAN-DEC)
-        if !month
-            .chars()
-            .all(|c| c.is_alphabetic() || c == '-' || c == ',' || c == '*' || c == '?')
-        {
-            Self::validate_field(month, "month", 1, 12)?;
-        }
-
-        // Validate day_of_week (1-7 or SUN-SAT)
-        if !day_of_week
-            .chars()
-            .all(|c| c.is_alphabetic() || c == '-' || c == ',' || c == '*' || c == '?')
-        {
-            Self::validate_field(day_of_week, "day_of_week", 1, 7)?;
-        }
-
-        // Validate year if provided
+        validate_field(minutes, CronField::Minutes)?;
+        validate_field(hours, CronField::Hours)?;
+        validate_field(day_of_month, CronField::DayOfMonth)?;
+        validate_field(month, CronField::Month)?;
+        validate_field(day_of_week, CronField::DayOfWeek)?;
         if let Some(year) = &self.year {
-            Self::validate_field(year, "year", 1970, 2199)?;
+            validate_field(year, CronField::Year)?;
         }
 
         // Cannot specify both day_of_month and day_of_week
@@ -281,6 +238,165 @@ impl Default for CronExpressionBuilder {
     }
 }
 
+const MONTH_NAMES: &[&str] = &[
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+const DAY_OF_WEEK_NAMES: &[&str] = &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CronField {
+    Minutes,
+    Hours,
+    DayOfMonth,
+    Month,
+    DayOfWeek,
+    Year,
+}
+
+impl CronField {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            CronField::Minutes => "minutes",
+            CronField::Hours => "hours",
+            CronField::DayOfMonth => "day_of_month",
+            CronField::Month => "month",
+            CronField::DayOfWeek => "day_of_week",
+            CronField::Year => "year",
+        }
+    }
+
+    pub(crate) fn range(self) -> (u32, u32) {
+        match self {
+            CronField::Minutes => (0, 59),
+            CronField::Hours => (0, 23),
+            CronField::DayOfMonth => (1, 31),
+            CronField::Month => (1, 12),
+            CronField::DayOfWeek => (1, 7),
+            CronField::Year => (1970, 2199),
+        }
+    }
+
+    /// Number or name (`JAN`, `MON`, ...) of a value in the range of the field.
+    pub(crate) fn parse_value(self, token: &str) -> Result<u32, String> {
+        let (min, max) = self.range();
+        let names = match self {
+            CronField::Month => MONTH_NAMES,
+            CronField::DayOfWeek => DAY_OF_WEEK_NAMES,
+            _ => &[],
+        };
+        if let Some(index) = names.iter().position(|n| n.eq_ignore_ascii_case(token)) {
+            return Ok(index as u32 + 1);
+        }
+        let value = token
+            .parse::<u32>()
+            .map_err(|_| format!("`{token}` is not a valid value"))?;
+        if value < min || value > max {
+            return Err(format!("{value} is out of range {min}-{max}"));
+        }
+        Ok(value)
+    }
+}
+
+/// Validates a field of a cron expression with the syntax of EventBridge Scheduler:
+/// lists (`,`), ranges (`-`), steps (`/`), `*`, and `?`, `L`, `W`, `#` where allowed.
+pub(crate) fn validate_field(field: &str, kind: CronField) -> Result<(), CronValidationError> {
+    let error = |token: &str, reason: String| CronValidationError {
+        field: kind.name(),
+        token: token.to_string(),
+        reason,
+    };
+    let is_day = matches!(kind, CronField::DayOfMonth | CronField::DayOfWeek);
+    if field == "?" {
+        if is_day {
+            return Ok(());
+        }
+        return Err(error(
+            field,
+            "`?` is only allowed in day_of_month and day_of_week".to_string(),
+        ));
+    }
+    let items = field.split(',').collect::<Vec<_>>();
+    for item in &items {
+        match validate_special(item, kind) {
+            // L / W / # はリストに含められない
+            Some(Ok(())) if items.len() == 1 => {}
+            Some(Ok(())) => {
+                return Err(error(
+                    item,
+                    "cannot be combined with other values".to_string(),
+                ));
+            }
+            Some(Err(reason)) => return Err(error(item, reason)),
+            None => validate_item(item, kind).map_err(|reason| error(item, reason))?,
+        }
+    }
+    Ok(())
+}
+
+/// `L`, `W` and `#` items, None if `item` is not one of them.
+fn validate_special(item: &str, kind: CronField) -> Option<Result<(), String>> {
+    match kind {
+        CronField::DayOfMonth => {
+            if item == "L" || item == "LW" {
+                return Some(Ok(()));
+            }
+            let day = item.strip_suffix('W')?;
+            Some(kind.parse_value(day).map(|_| ()))
+        }
+        CronField::DayOfWeek => {
+            if item == "L" {
+                return Some(Ok(()));
+            }
+            if let Some((day, nth)) = item.split_once('#') {
+                return Some(
+                    kind.parse_value(day)
+                        .and_then(|_| match nth.parse::<u32>() {
+                            Ok(1..=5) => Ok(()),
+                            _ => Err(format!("`#{nth}` must be between 1 and 5")),
+                        }),
+                );
+            }
+            let day = item.strip_suffix('L')?;
+            Some(kind.parse_value(day).map(|_| ()))
+        }
+        _ => None,
+    }
+}
+
+/// `*`, `value`, `start-end`, optionally followed by `/step`.
+fn validate_item(item: &str, kind: CronField) -> Result<(), String> {
+    let (min, max) = kind.range();
+    let (base, step) = match item.split_once('/') {
+        Some((base, step)) => (base, Some(step)),
+        None => (item, None),
+    };
+    if let Some(step) = step {
+        match step.parse::<u32>() {
+            Ok(step) if (1..=max - min + 1).contains(&step) => {}
+            _ => {
+                return Err(format!(
+                    "step `{step}` must be between 1 and {}",
+                    max - min + 1
+                ));
+            }
+        }
+    }
+    if base == "*" {
+        return Ok(());
+    }
+    match base.split_once('-') {
+        Some((start, end)) => {
+            let start = kind.parse_value(start)?;
+            let end = kind.parse_value(end)?;
+            if start > end {
+                return Err(format!("range start {start} is after end {end}"));
+            }
+            Ok(())
+        }
+        None => kind.parse_value(base).map(|_| ()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -406,4 +522,72 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_cron_expression_builder_lists_ranges_steps() {
+        let expression = CronExpressionBuilder::new()
+            .minutes("0/15")
+            .hours("8-18/2")
+            .day_of_month("1,15,L")
+            .month("*")
+            .day_of_week("?")
+            .build();
+        assert!(matches!(
+            expression,
+            Err(Error::CronValidation(CronValidationError { field: "day_of_month", token, .. })) if token == "L"
+        ));
+
+        let expression = CronExpressionBuilder::new()
+            .minutes("0/15")
+            .hours("8-18/2")
+            .day_of_month("?")
+            .month("jan-MAR,OCT")
+            .day_of_week("MON#2")
+            .year("*/2")
+            .build()
+            .unwrap();
+        assert_eq!(expression, "cron(0/15 8-18/2 ? jan-MAR,OCT MON#2 */2)");
+
+        let expression = CronExpressionBuilder::new()
+            .minutes("0")
+            .hours("9")
+            .day_of_month("15W")
+            .month("*")
+            .day_of_week("?")
+            .build()
+            .unwrap();
+        assert_eq!(expression, "cron(0 9 15W * ?)");
+    }
+
+    #[test]
+    fn test_validate_field_invalid() {
+        let invalid = [
+            ("5-70", CronField::Minutes),
+            ("*/0", CronField::Minutes),
+            ("10,61", CronField::Minutes),
+            ("20-10", CronField::Hours),
+            ("?", CronField::Hours),
+            ("", CronField::Hours),
+            ("32W", CronField::DayOfMonth),
+            ("FOO", CronField::Month),
+            ("13#6", CronField::DayOfWeek),
+            ("2#6", CronField::DayOfWeek),
+            ("MON,6L", CronField::DayOfWeek),
+            ("1969", CronField::Year),
+        ];
+        for (field, kind) in invalid {
+            assert!(validate_field(field, kind).is_err(), "{field}");
+        }
+
+        let error = validate_field("0,5-70", CronField::Minutes).unwrap_err();
+        assert_eq!(error.field, "minutes");
+        assert_eq!(error.token, "5-70");
+        assert_eq!(error.reason, "70 is out of range 0-59");
+        assert_eq!(
+            validate_field("13#6", CronField::DayOfWeek)
+                .unwrap_err()
+                .reason,
+            "13 is out of range 1-7"
+        );
+    }
 }
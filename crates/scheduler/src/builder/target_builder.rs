@@ -0,0 +1,325 @@
+use aws_sdk_scheduler::types::{DeadLetterConfig, RetryPolicy, Target};
+
+use crate::error::Error;
+
+const MIN_EVENT_AGE_SECONDS: i32 = 60;
+const MAX_EVENT_AGE_SECONDS: i32 = 86_400;
+const MAX_RETRY_ATTEMPTS: i32 = 185;
+const MAX_INPUT_LENGTH: usize = 8_192;
+
+/// Builder for the retry policy of a target
+///
+/// Maximum event age: 60 to 86400 seconds, maximum retry attempts: 0 to 185.
+/// Unset values are left to the service defaults (86400 seconds, 185 attempts).
+pub struct RetryPolicyBuilder {
+    maximum_event_age_in_seconds: Option<i32>,
+    maximum_retry_attempts: Option<i32>,
+}
+
+impl RetryPolicyBuilder {
+    pub fn new() -> Self {
+        Self {
+            maximum_event_age_in_seconds: None,
+            maximum_retry_attempts: None,
+        }
+    }
+
+    pub fn maximum_event_age_in_seconds(mut self, seconds: i32) -> Self {
+        self.maximum_event_age_in_seconds = Some(seconds);
+        self
+    }
+
+    pub fn maximum_retry_attempts(mut self, attempts: i32) -> Self {
+        self.maximum_retry_attempts = Some(attempts);
+        self
+    }
+
+    /// No retries: the event goes to the dead-letter queue after the first failure.
+    pub fn no_retry(self) -> Self {
+        self.maximum_retry_attempts(0)
+    }
+
+    pub fn build(&self) -> Result<RetryPolicy, Error> {
+        if let Some(seconds) = self.maximum_event_age_in_seconds
+            && !(MIN_EVENT_AGE_SECONDS..=MAX_EVENT_AGE_SECONDS).contains(&seconds)
+        {
+            return Err(Error::ValidationError(format!(
+                "maximum_event_age_in_seconds must be between {MIN_EVENT_AGE_SECONDS} and {MAX_EVENT_AGE_SECONDS}: {seconds}"
+            )));
+        }
+        if let Some(attempts) = self.maximum_retry_attempts
+            && !(0..=MAX_RETRY_ATTEMPTS).contains(&attempts)
+        {
+            return Err(Error::ValidationError(format!(
+                "maximum_retry_attempts must be between 0 and {MAX_RETRY_ATTEMPTS}: {attempts}"
+            )));
+        }
+        Ok(RetryPolicy::builder()
+            .set_maximum_event_age_in_seconds(self.maximum_event_age_in_seconds)
+            .set_maximum_retry_attempts(self.maximum_retry_attempts)
+            .build())
+    }
+}
+
+impl Default for RetryPolicyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builder for the dead-letter queue of a target
+/// The queue must be an SQS queue ARN (arn:aws:sqs:region:account:name)
+pub struct DeadLetterConfigBuilder {
+    arn: Option<String>,
+}
+
+impl DeadLetterConfigBuilder {
+    pub fn new() -> Self {
+        Self { arn: None }
+    }
+
+    pub fn arn(mut self, arn: impl Into<String>) -> Self {
+        self.arn = Some(arn.into());
+        self
+    }
+
+    pub fn build(&self) -> Result<DeadLetterConfig, Error> {
+        let arn = self.arn.as_deref().ok_or_else(|| {
+            Error::ValidationError("arn is required for dead-letter config".to_string())
+        })?;
+        validate_arn(arn, "sqs", "dead-letter queue")?;
+        Ok(DeadLetterConfig::builder().arn(arn).build())
+    }
+}
+
+impl Default for DeadLetterConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builder for schedule targets, validating the ARNs, the input and the retry and
+/// dead-letter settings before the schedule is created
+pub struct TargetBuilder {
+    arn: Option<String>,
+    role_arn: Option<String>,
+    input: Option<String>,
+    retry_policy: Option<RetryPolicyBuilder>,
+    dead_letter_config: Option<DeadLetterConfigBuilder>,
+}
+
+impl TargetBuilder {
+    pub fn new() -> Self {
+        Self {
+            arn: None,
+            role_arn: None,
+            input: None,
+            retry_policy: None,
+            dead_letter_config: None,
+        }
+    }
+
+    /// ARN of the target, e.g. a Lambda function or an SQS queue
+    pub fn arn(mut self, arn: impl Into<String>) -> Self {
+        self.arn = Some(arn.into());
+        self
+    }
+
+    /// IAM role EventBridge Scheduler assumes to invoke the target
+    pub fn role_arn(mut self, role_arn: impl Into<String>) -> Self {
+        self.role_arn = Some(role_arn.into());
+        self
+    }
+
+    /// Input passed to the target, up to 8192 characters
+    pub fn input(mut self, input: impl Into<String>) -> Self {
+        self.input = Some(input.into());
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicyBuilder) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    pub fn dead_letter_config(mut self, dead_letter_config: DeadLetterConfigBuilder) -> Self {
+        self.dead_letter_config = Some(dead_letter_config);
+        self
+    }
+
+    /// Sends events to the SQS queue `arn` once the retries are exhausted
+    pub fn dead_letter_queue(self, arn: impl Into<String>) -> Self {
+        self.dead_letter_config(DeadLetterConfigBuilder::new().arn(arn))
+    }
+
+    pub fn build(&self) -> Result<Target, Error> {
+        let arn = self
+            .arn
+            .as_deref()
+            .ok_or_else(|| Error::ValidationError("arn is required for target".to_string()))?;
+        validate_arn(arn, "", "target")?;
+        let role_arn = self
+            .role_arn
+            .as_deref()
+            .ok_or_else(|| Error::ValidationError("role_arn is required for target".to_string()))?;
+        validate_arn(role_arn, "iam", "role")?;
+        if let Some(input) = &self.input
+            && input.chars().count() > MAX_INPUT_LENGTH
+        {
+            return Err(Error::ValidationError(format!(
+                "input must be at most {MAX_INPUT_LENGTH} characters"
+            )));
+        }
+        let retry_policy = self
+            .retry_policy
+            .as_ref()
+            .map(RetryPolicyBuilder::build)
+            .transpose()?;
+        let dead_letter_config = self
+            .dead_letter_config
+            .as_ref()
+            .map(DeadLetterConfigBuilder::build)
+            .transpose()?;
+        Ok(Target::builder()
+            .arn(arn)
+            .role_arn(role_arn)
+            .set_input(self.input.clone())
+            .set_retry_policy(retry_policy)
+            .set_dead_letter_config(dead_letter_config)
+            .build()?)
+    }
+}
+
+impl Default for TargetBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Checks `arn:{partition}:{service}:...` with 6 parts. Any service if `service` is empty.
+fn validate_arn(arn: &str, service: &str, what: &str) -> Result<(), Error> {
+    let parts = arn.splitn(6, ':').collect::<Vec<_>>();
+    let valid = parts.len() == 6
+        && parts[0] == "arn"
+        && parts[1].starts_with("aws")
+        && !parts[2].is_empty()
+        && (service.is_empty() || parts[2] == service)
+        && !parts[5].is_empty();
+    if !valid {
+        return Err(Error::ValidationError(format!("invalid {what} ARN: {arn}")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FUNCTION_ARN: &str = "arn:aws:lambda:us-east-1:123456789012:function:MyFunction";
+    const ROLE_ARN: &str = "arn:aws:iam::123456789012:role/MyRole";
+    const QUEUE_ARN: &str = "arn:aws:sqs:us-east-1:123456789012:my-dlq";
+
+    #[test]
+    fn test_retry_policy_builder() {
+        let policy = RetryPolicyBuilder::new()
+            .maximum_event_age_in_seconds(3600)
+            .maximum_retry_attempts(3)
+            .build()
+            .unwrap();
+        assert_eq!(policy.maximum_event_age_in_seconds(), Some(3600));
+        assert_eq!(policy.maximum_retry_attempts(), Some(3));
+
+        let policy = RetryPolicyBuilder::new().no_retry().build().unwrap();
+        assert_eq!(policy.maximum_retry_attempts(), Some(0));
+        assert_eq!(policy.maximum_event_age_in_seconds(), None);
+    }
+
+    #[test]
+    fn test_retry_policy_builder_out_of_range() {
+        for builder in [
+            RetryPolicyBuilder::new().maximum_event_age_in_seconds(59),
+            RetryPolicyBuilder::new().maximum_event_age_in_seconds(86_401),
+            RetryPolicyBuilder::new().maximum_retry_attempts(-1),
+            RetryPolicyBuilder::new().maximum_retry_attempts(186),
+        ] {
+            assert!(matches!(builder.build(), Err(Error::ValidationError(_))));
+        }
+    }
+
+    #[test]
+    fn test_dead_letter_config_builder() {
+        let config = DeadLetterConfigBuilder::new()
+            .arn(QUEUE_ARN)
+            .build()
+            .unwrap();
+        assert_eq!(config.arn(), Some(QUEUE_ARN));
+
+        assert!(DeadLetterConfigBuilder::new().build().is_err());
+        // SQS 以外は指定できない
+        assert!(
+            DeadLetterConfigBuilder::new()
+                .arn("arn:aws:sns:us-east-1:123456789012:topic")
+                .build()
+                .is_err()
+        );
+        assert!(
+            DeadLetterConfigBuilder::new()
+                .arn("my-dlq")
+                .build()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_target_builder() {
+        let target = TargetBuilder::new()
+            .arn(FUNCTION_ARN)
+            .role_arn(ROLE_ARN)
+            .input(r#"{"job":"cleanup"}"#)
+            .retry_policy(RetryPolicyBuilder::new().maximum_retry_attempts(2))
+            .dead_letter_queue(QUEUE_ARN)
+            .build()
+            .unwrap();
+        assert_eq!(target.arn(), FUNCTION_ARN);
+        assert_eq!(target.role_arn(), ROLE_ARN);
+        assert_eq!(
+            target
+                .retry_policy()
+                .and_then(|p| p.maximum_retry_attempts()),
+            Some(2)
+        );
+        assert_eq!(
+            target.dead_letter_config().and_then(|c| c.arn()),
+            Some(QUEUE_ARN)
+        );
+    }
+
+    #[test]
+    fn test_target_builder_validation() {
+        assert!(TargetBuilder::new().role_arn(ROLE_ARN).build().is_err());
+        assert!(TargetBuilder::new().arn(FUNCTION_ARN).build().is_err());
+        assert!(
+            TargetBuilder::new()
+                .arn(FUNCTION_ARN)
+                .role_arn("arn:aws:lambda:us-east-1:123456789012:function:NotARole")
+                .build()
+                .is_err()
+        );
+        assert!(
+            TargetBuilder::new()
+                .arn(FUNCTION_ARN)
+                .role_arn(ROLE_ARN)
+                .input("x".repeat(MAX_INPUT_LENGTH + 1))
+                .build()
+                .is_err()
+        );
+        assert!(
+            TargetBuilder::new()
+                .arn(FUNCTION_ARN)
+                .role_arn(ROLE_ARN)
+                .retry_policy(RetryPolicyBuilder::new().maximum_retry_attempts(200))
+                .build()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashSet;
+
+use aws_sdk_scheduler::{
+    Client,
+    operation::get_schedule::GetScheduleOutput,
+    primitives::DateTime as AwsDateTime,
+    types::{
+        ActionAfterCompletion, FlexibleTimeWindow, FlexibleTimeWindowMode, ScheduleState, Target,
+    },
+};
+use chrono::prelude::*;
+use futures_util::{StreamExt, stream};
+
+use crate::{
+    error::Error,
+    schedule::ScheduleExpression,
+    scheduler::{
+        create_schedule, delete_schedule, get_scheduler, list_schedules_all, update_schedule,
+    },
+};
+
+/// Desired state of a schedule for [`apply_schedules`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleSpec {
+    pub name: String,
+    pub schedule_expression: String,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+    /// UTC if None.
+    pub schedule_expression_timezone: Option<String>,
+    /// ENABLED if None.
+    pub state: Option<ScheduleState>,
+    pub kms_key_arn: Option<String>,
+    pub target: Target,
+    /// OFF if None.
+    pub flexible_time_window: Option<FlexibleTimeWindow>,
+    pub action_after_completion: Option<ActionAfterCompletion>,
+}
+
+impl ScheduleSpec {
+    pub fn new(
+        name: impl Into<String>,
+        schedule_expression: impl Into<String>,
+        target: Target,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            schedule_expression: schedule_expression.into(),
+            start_date: None,
+            end_date: None,
+            description: None,
+            schedule_expression_timezone: None,
+            state: None,
+            kms_key_arn: None,
+            target,
+            flexible_time_window: None,
+            action_after_completion: None,
+        }
+    }
+
+    /// Whether the existing schedule already matches this spec.
+    ///
+    /// Optional parts of the target that are not set in the spec (retry policy etc.)
+    /// are ignored, since the service fills in defaults for them.
+    pub fn matches(&self, existing: &GetScheduleOutput) -> bool {
+        let millis = |d: Option<&AwsDateTime>| d.and_then(|d| d.to_millis().ok());
+        let flexible_time_window = self.flexible_time_window.clone().unwrap_or_else(off_window);
+
+        existing
+            .schedule_expression()
+            .is_some_and(|e| expression_matches(&self.schedule_expression, e))
+            && millis(existing.start_date()) == self.start_date.map(|d| d.timestamp_millis())
+            && millis(existing.end_date()) == self.end_date.map(|d| d.timestamp_millis())
+            && non_empty(existing.description()) == non_empty(self.description.as_deref())
+            && existing.schedule_expression_timezone().unwrap_or("UTC")
+                == self
+                    .schedule_expression_timezone
+                    .as_deref()
+                    .unwrap_or("UTC")
+            && existing.state().unwrap_or(&ScheduleState::Enabled)
+                == self.state.as_ref().unwrap_or(&ScheduleState::Enabled)
+            && non_empty(existing.kms_key_arn()) == non_empty(self.kms_key_arn.as_deref())
+            && existing
+                .target()
+                .is_some_and(|t| target_matches(&self.target, t))
+            && existing.flexible_time_window() == Some(&flexible_time_window)
+            && existing
+                .action_after_completion()
+                .unwrap_or(&ActionAfterCompletion::None)
+                == self
+                    .action_after_completion
+                    .as_ref()
+                    .unwrap_or(&ActionAfterCompletion::None)
+    }
+}
+
+/// Equivalent expressions, e.g. `rate(60 minutes)` and `rate(1 hour)`, are not updated.
+fn expression_matches(desired: &str, existing: &str) -> bool {
+    if desired == existing {
+        return true;
+    }
+    match (
+        ScheduleExpression::parse(desired),
+        ScheduleExpression::parse(existing),
+    ) {
+        (Ok(desired), Ok(existing)) => desired.is_equivalent(&existing),
+        _ => false,
+    }
+}
+
+fn non_empty(s: Option<&str>) -> Option<&str> {
+    s.filter(|s| !s.is_empty())
+}
+
+fn off_window() -> FlexibleTimeWindow {
+    FlexibleTimeWindow::builder()
+        .mode(FlexibleTimeWindowMode::Off)
+        .build()
+        .expect("mode is set")
+}
+
+fn target_matches(spec: &Target, existing: &Target) -> bool {
+    fn optional<T: PartialEq>(spec: Option<&T>, existing: Option<&T>) -> bool {
+        spec.is_none() || spec == existing
+    }
+    spec.arn() == existing.arn()
+        && spec.role_arn() == existing.role_arn()
+        && spec.input() == existing.input()
+        && optional(spec.retry_policy(), existing.retry_policy())
+        && optional(spec.dead_letter_config(), existing.dead_letter_config())
+        && optional(spec.ecs_parameters(), existing.ecs_parameters())
+        && optional(
+            spec.event_bridge_parameters(),
+            existing.event_bridge_parameters(),
+        )
+        && optional(spec.kinesis_parameters(), existing.kinesis_parameters())
+        && optional(
+            spec.sage_maker_pipeline_parameters(),
+            existing.sage_maker_pipeline_parameters(),
+        )
+        && optional(spec.sqs_parameters(), existing.sqs_parameters())
+}
+
+/// Result of [`apply_schedules`] by schedule name.
+#[derive(Debug, Default)]
+pub struct ApplySchedulesReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub failed: Vec<(String, Error)>,
+}
+
+impl ApplySchedulesReport {
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+enum Change {
+    Created,
+    Updated,
+    Deleted,
+    Unchanged,
+}
+
+/// Converges the schedules in `group_name` to `specs`.
+///
+/// Schedules that are missing are created, ones that differ are updated and
+/// ones not in `specs` are deleted. Up to `concurrency` requests run at the same time.
+/// Failures of individual schedules are collected in the report instead of
+/// aborting the rest; only a failure to list the group is returned as an error.
+pub async fn apply_schedules(
+    client: &Client,
+    group_name: impl Into<String>,
+    specs: Vec<ScheduleSpec>,
+    concurrency: usize,
+) -> Result<ApplySchedulesReport, Error> {
+    let group_name = group_name.into();
+    let concurrency = concurrency.max(1);
+    let existing: HashSet<String> =
+        list_schedules_all(client, None::<String>, Some(&group_name), None)
+            .await?
+            .into_iter()
+            .filter_map(|s| s.name)
+            .collect();
+    let desired: HashSet<String> = specs.iter().map(|s| s.name.clone()).collect();
+    if desired.len() != specs.len() {
+        return Err(Error::ValidationError(
+            "schedule names must be unique".to_string(),
+        ));
+    }
+
+    let applied = stream::iter(specs)
+        .map(|spec| {
+            let exists = existing.contains(&spec.name);
+            let group_name = &group_name;
+            async move {
+                let result = apply_schedule(client, group_name, &spec, exists).await;
+                (spec.name, result)
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+    let deleted = stream::iter(existing.difference(&desired))
+        .map(|name| {
+            let group_name = &group_name;
+            async move {
+                let result = delete_schedule(client, name, Some(group_name), None::<String>)
+                    .await
+                    .map(|_| Change::Deleted);
+                (name.clone(), result)
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut report = ApplySchedulesReport::default();
+    for (name, result) in applied.into_iter().chain(deleted) {
+        match result {
+            Ok(Change::Created) => report.created.push(name),
+            Ok(Change::Updated) => report.updated.push(name),
+            Ok(Change::Deleted) => report.deleted.push(name),
+            Ok(Change::Unchanged) => report.unchanged.push(name),
+            Err(e) => report.failed.push((name, e)),
+        }
+    }
+    Ok(report)
+}
+
+async fn apply_schedule(
+    client: &Client,
+    group_name: &str,
+    spec: &ScheduleSpec,
+    exists: bool,
+) -> Result<Change, Error> {
+    let flexible_time_window = spec.flexible_time_window.clone().unwrap_or_else(off_window);
+    if !exists {
+        create_schedule(
+            client,
+            &spec.name,
+            Some(group_name),
+            &spec.schedule_expression,
+            spec.start_date,
+            spec.end_date,
+            spec.description.as_deref(),
+            spec.schedule_expression_timezone.as_deref(),
+            spec.state.clone(),
+            spec.kms_key_arn.as_deref(),
+            Some(spec.target.clone()),
+            Some(flexible_time_window),
+            None::<String>,
+            spec.action_after_completion.clone(),
+        )
+        .await?;
+        return Ok(Change::Created);
+    }
+
+    let current = get_scheduler(client, &spec.name, Some(group_name)).await?;
+    if spec.matches(&current) {
+        return Ok(Change::Unchanged);
+    }
+    update_schedule(
+        client,
+        &spec.name,
+        Some(group_name),
+        &spec.schedule_expression,
+        spec.start_date,
+        spec.end_date,
+        spec.description.as_deref(),
+        spec.schedule_expression_timezone.as_deref(),
+        spec.state.clone(),
+        spec.kms_key_arn.as_deref(),
+        Some(spec.target.clone()),
+        Some(flexible_time_window),
+        None::<String>,
+        spec.action_after_completion.clone(),
+    )
+    .await?;
+    Ok(Change::Updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_scheduler::types::RetryPolicy;
+
+    use super::*;
+
+    fn target(input: &str) -> Target {
+        Target::builder()
+            .arn("arn:aws:lambda:us-east-1:123456789012:function:MyFunction")
+            .role_arn("arn:aws:iam::123456789012:role/MyRole")
+            .input(input)
+            .build()
+            .unwrap()
+    }
+
+    fn existing(target: Target) -> GetScheduleOutput {
+        GetScheduleOutput::builder()
+            .schedule_expression("rate(5 minutes)")
+            .schedule_expression_timezone("UTC")
+            .state(ScheduleState::Enabled)
+            .target(target)
+            .flexible_time_window(off_window())
+            .build()
+    }
+
+    #[test]
+    fn test_matches_with_service_defaults() {
+        let spec = ScheduleSpec::new("tenant-1", "rate(5 minutes)", target("{}"));
+        let mut with_retry = target("{}");
+        with_retry.retry_policy = Some(
+            RetryPolicy::builder()
+                .maximum_retry_attempts(185)
+                .maximum_event_age_in_seconds(86400)
+                .build(),
+        );
+
+        assert!(spec.matches(&existing(with_retry)));
+
+        // 同じ間隔の別表記は更新しない
+        let spec = ScheduleSpec::new("tenant-1", "rate(300 minutes)", target("{}"));
+        let mut five_hours = existing(target("{}"));
+        five_hours.schedule_expression = Some("rate(5 hours)".to_string());
+        assert!(spec.matches(&five_hours));
+    }
+
+    #[test]
+    fn test_matches_detects_changes() {
+        let spec = ScheduleSpec::new("tenant-1", "rate(5 minutes)", target("{}"));
+
+        assert!(!spec.matches(&existing(target(r#"{"tenant":1}"#))));
+
+        let mut disabled = spec.clone();
+        disabled.state = Some(ScheduleState::Disabled);
+        assert!(!disabled.matches(&existing(target("{}"))));
+
+        let mut daily = spec.clone();
+        daily.schedule_expression = "rate(1 day)".to_string();
+        assert!(!daily.matches(&existing(target("{}"))));
+    }
+}
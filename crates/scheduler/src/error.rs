@@ -1,3 +1,6 @@
+use aws_sdk_scheduler::{error::ProvideErrorMetadata, operation::RequestId};
+pub use aws_utils_core::error::AwsUtilError;
+use aws_utils_core::error::is_retryable_sdk_error;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,8 +13,77 @@ pub enum Error {
 
     #[error("ValidationError: {0}")]
     ValidationError(String),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    CronValidation(#[from] CronValidationError),
+}
+
+/// Invalid field of a cron expression, with the list item that failed.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("CronValidationError: {field} `{token}`: {reason}")]
+pub struct CronValidationError {
+    /// e.g. `day_of_week`
+    pub field: &'static str,
+    pub token: String,
+    pub reason: String,
+}
+
+/// Error of an `_idempotent` operation of [`scheduler`](crate::scheduler), with the client
+/// token sent, to retry the operation with the same token.
+#[derive(Error, Debug)]
+#[error("{source} (client token {client_token})")]
+pub struct IdempotentError {
+    #[source]
+    pub source: Error,
+    pub client_token: String,
+}
+
+impl From<IdempotentError> for Error {
+    fn from(e: IdempotentError) -> Self {
+        e.source
+    }
 }
 
 pub(crate) fn from_aws_sdk_error(e: impl Into<aws_sdk_scheduler::Error>) -> Error {
     Error::AwsSdk(Box::new(e.into()))
 }
+
+impl AwsUtilError for Error {
+    fn error_code(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.code(),
+            _ => None,
+        }
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.request_id(),
+            _ => None,
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::AwsSdk(e) => is_retryable_sdk_error(e.as_ref()),
+            _ => false,
+        }
+    }
+}
+
+impl AwsUtilError for IdempotentError {
+    fn error_code(&self) -> Option<&str> {
+        self.source.error_code()
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        self.source.aws_request_id()
+    }
+
+    fn is_retryable(&self) -> bool {
+        self.source.is_retryable()
+    }
+}
@@ -1,5 +1,8 @@
 pub mod builder;
+pub mod bulk;
 pub mod error;
+pub mod occurrence;
+pub mod schedule;
 pub mod scheduler;
 use std::time::Duration;
 
@@ -9,6 +12,8 @@ use aws_config::{
 };
 pub use aws_sdk_scheduler;
 use aws_sdk_scheduler::config::SharedInterceptor;
+#[cfg(feature = "metrics")]
+pub use aws_utils_core::metrics;
 
 pub async fn make_client_with_timeout_default(
     endpoint_url: Option<String>,
@@ -0,0 +1,323 @@
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::{
+    builder::schedule_expression_builder::{CronExpressionBuilder, CronField, RateUnit},
+    error::Error,
+    schedule::ScheduleExpression,
+};
+
+/// Next `n` firings of an `at()`, `rate()` or `cron()` expression from now, evaluated locally.
+/// See [`next_occurrences_after`].
+pub fn next_occurrences(
+    expression: &str,
+    timezone: Option<&str>,
+    n: usize,
+) -> Result<Vec<DateTime<Utc>>, Error> {
+    next_occurrences_after(expression, timezone, Utc::now(), n)
+}
+
+/// Next `n` firings of an expression after `after`.
+///
+/// `timezone` is the IANA name of `ScheduleExpressionTimezone` (UTC if None), in which `at()`
+/// and `cron()` are evaluated. `rate()` is counted from `after`, as a rate schedule starts
+/// when it is created. Local times skipped by a DST change do not fire.
+pub fn next_occurrences_after(
+    expression: &str,
+    timezone: Option<&str>,
+    after: DateTime<Utc>,
+    n: usize,
+) -> Result<Vec<DateTime<Utc>>, Error> {
+    let tz = match timezone {
+        Some(timezone) => timezone
+            .parse::<Tz>()
+            .map_err(|_| Error::ValidationError(format!("invalid timezone: {timezone}")))?,
+        None => Tz::UTC,
+    };
+    match expression.parse::<ScheduleExpression>()? {
+        ScheduleExpression::At(datetime) => Ok(tz
+            .from_local_datetime(&datetime)
+            .earliest()
+            .map(|d| d.with_timezone(&Utc))
+            .filter(|d| *d > after && n > 0)
+            .into_iter()
+            .collect()),
+        ScheduleExpression::Rate { value, unit } => {
+            let interval = match unit {
+                RateUnit::Minutes => Duration::minutes(value.into()),
+                RateUnit::Hours => Duration::hours(value.into()),
+                RateUnit::Days => Duration::days(value.into()),
+            };
+            Ok((1..=n as i32).map(|i| after + interval * i).collect())
+        }
+        ScheduleExpression::Cron {
+            minutes,
+            hours,
+            day_of_month,
+            month,
+            day_of_week,
+            year,
+        } => {
+            let mut builder = CronExpressionBuilder::new()
+                .minutes(&minutes)
+                .hours(&hours)
+                .day_of_month(&day_of_month)
+                .month(&month)
+                .day_of_week(&day_of_week);
+            if let Some(year) = &year {
+                builder = builder.year(year);
+            }
+            builder.build()?;
+            let cron = Cron {
+                minutes: expand(&minutes, CronField::Minutes),
+                hours: expand(&hours, CronField::Hours),
+                day_of_month,
+                months: expand(&month, CronField::Month),
+                day_of_week,
+                years: expand(year.as_deref().unwrap_or("*"), CronField::Year),
+            };
+            Ok(cron.next_occurrences(&tz, after, n))
+        }
+    }
+}
+
+/// Validated cron expression, with the values of the fields without `L`, `W` and `#`.
+struct Cron {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    day_of_month: String,
+    months: Vec<u32>,
+    day_of_week: String,
+    years: Vec<u32>,
+}
+
+impl Cron {
+    fn next_occurrences(&self, tz: &Tz, after: DateTime<Utc>, n: usize) -> Vec<DateTime<Utc>> {
+        let mut occurrences = Vec::new();
+        let mut date = after.with_timezone(tz).date_naive();
+        while occurrences.len() < n {
+            if !self.years.contains(&(date.year() as u32)) {
+                // 年の指定は 2199 年まで
+                let Some(year) = self.years.iter().find(|y| **y > date.year() as u32) else {
+                    break;
+                };
+                date = NaiveDate::from_ymd_opt(*year as i32, 1, 1).unwrap();
+                continue;
+            }
+            if !self.months.contains(&date.month()) {
+                date = first_day_of_next_month(date);
+                continue;
+            }
+            if self.matches_day(date) {
+                for hour in &self.hours {
+                    for minute in &self.minutes {
+                        let local = date.and_hms_opt(*hour, *minute, 0).unwrap();
+                        let datetime = match tz.from_local_datetime(&local) {
+                            LocalResult::Single(datetime) => datetime,
+                            // 夏時間の終わりで重複する時刻は最初の方
+                            LocalResult::Ambiguous(datetime, _) => datetime,
+                            LocalResult::None => continue,
+                        };
+                        let datetime = datetime.with_timezone(&Utc);
+                        if datetime > after && occurrences.len() < n {
+                            occurrences.push(datetime);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt().unwrap();
+        }
+        occurrences
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        if self.day_of_month == "?" {
+            matches_day_of_week(&self.day_of_week, date)
+        } else {
+            matches_day_of_month(&self.day_of_month, date)
+        }
+    }
+}
+
+fn matches_day_of_month(field: &str, date: NaiveDate) -> bool {
+    let last_day = last_day_of_month(date);
+    match field {
+        "L" => date.day() == last_day,
+        "LW" => date.day() == nearest_weekday(date, last_day),
+        _ => match field.strip_suffix('W') {
+            Some(day) => {
+                let day = day.parse::<u32>().unwrap();
+                day <= last_day && date.day() == nearest_weekday(date, day)
+            }
+            None => expand(field, CronField::DayOfMonth).contains(&date.day()),
+        },
+    }
+}
+
+fn matches_day_of_week(field: &str, date: NaiveDate) -> bool {
+    let weekday = date.weekday().number_from_sunday();
+    let parse = |day: &str| CronField::DayOfWeek.parse_value(day).unwrap();
+    if field == "L" {
+        return weekday == 7;
+    }
+    if let Some((day, nth)) = field.split_once('#') {
+        return weekday == parse(day) && (date.day() - 1) / 7 + 1 == nth.parse::<u32>().unwrap();
+    }
+    match field.strip_suffix('L') {
+        Some(day) => weekday == parse(day) && date.day() + 7 > last_day_of_month(date),
+        None => expand(field, CronField::DayOfWeek).contains(&weekday),
+    }
+}
+
+/// Weekday nearest to `day` of the month of `date`, without crossing the month (`W`).
+fn nearest_weekday(date: NaiveDate, day: u32) -> u32 {
+    let target = date.with_day(day).unwrap();
+    match target.weekday().number_from_sunday() {
+        // 土曜日
+        7 if day == 1 => 3,
+        7 => day - 1,
+        // 日曜日
+        1 if day == last_day_of_month(date) => day - 2,
+        1 => day + 1,
+        _ => day,
+    }
+}
+
+fn first_day_of_next_month(date: NaiveDate) -> NaiveDate {
+    match date.month() {
+        12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+        month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1),
+    }
+    .unwrap()
+}
+
+fn last_day_of_month(date: NaiveDate) -> u32 {
+    first_day_of_next_month(date).pred_opt().unwrap().day()
+}
+
+/// Sorted values of a validated field without `?`, `L`, `W` and `#`.
+fn expand(field: &str, kind: CronField) -> Vec<u32> {
+    let (min, max) = kind.range();
+    let mut values = field
+        .split(',')
+        .flat_map(|item| {
+            let (base, step) = match item.split_once('/') {
+                Some((base, step)) => (base, Some(step.parse::<usize>().unwrap())),
+                None => (item, None),
+            };
+            let (start, end) = match (base.split_once('-'), step) {
+                _ if base == "*" => (min, max),
+                (Some((start, end)), _) => (
+                    kind.parse_value(start).unwrap(),
+                    kind.parse_value(end).unwrap(),
+                ),
+                // 5/15 は 5 から最大値まで
+                (None, Some(_)) => (kind.parse_value(base).unwrap(), max),
+                (None, None) => {
+                    let value = kind.parse_value(base).unwrap();
+                    (value, value)
+                }
+            };
+            (start..=end).step_by(step.unwrap_or(1))
+        })
+        .collect::<Vec<_>>();
+    values.sort_unstable();
+    values.dedup();
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_next_occurrences_cron() {
+        let after = utc("2026-10-16T10:30:00Z");
+        let occurrences =
+            next_occurrences_after("cron(0 9 ? * MON-FRI *)", Some("Asia/Tokyo"), after, 3)
+                .unwrap();
+        assert_eq!(
+            occurrences,
+            vec![
+                utc("2026-10-19T00:00:00Z"),
+                utc("2026-10-20T00:00:00Z"),
+                utc("2026-10-21T00:00:00Z"),
+            ]
+        );
+
+        let occurrences =
+            next_occurrences_after("cron(0/20 10-11 16 OCT ? 2026)", None, after, 10).unwrap();
+        assert_eq!(
+            occurrences,
+            vec![
+                utc("2026-10-16T10:40:00Z"),
+                utc("2026-10-16T11:00:00Z"),
+                utc("2026-10-16T11:20:00Z"),
+                utc("2026-10-16T11:40:00Z"),
+            ]
+        );
+
+        // 最終営業日 / 第 2 月曜日 / 最終金曜日
+        let occurrences = next_occurrences_after("cron(0 0 LW * ? *)", None, after, 2).unwrap();
+        assert_eq!(
+            occurrences,
+            vec![utc("2026-10-30T00:00:00Z"), utc("2026-11-30T00:00:00Z")]
+        );
+        let occurrences = next_occurrences_after("cron(0 0 ? * MON#2 *)", None, after, 1).unwrap();
+        assert_eq!(occurrences, vec![utc("2026-11-09T00:00:00Z")]);
+        let occurrences = next_occurrences_after("cron(0 0 ? * 6L *)", None, after, 1).unwrap();
+        assert_eq!(occurrences, vec![utc("2026-10-30T00:00:00Z")]);
+        // 2026/11/01 は日曜日
+        let occurrences = next_occurrences_after("cron(0 0 1W * ? *)", None, after, 1).unwrap();
+        assert_eq!(occurrences, vec![utc("2026-11-02T00:00:00Z")]);
+
+        // 夏時間で存在しない 2:30 は実行されない
+        let occurrences = next_occurrences_after(
+            "cron(30 2 * MAR ? 2027)",
+            Some("America/New_York"),
+            after,
+            14,
+        )
+        .unwrap();
+        assert_eq!(occurrences[0], utc("2027-03-01T07:30:00Z"));
+        assert_eq!(occurrences[12], utc("2027-03-13T07:30:00Z"));
+        assert_eq!(occurrences[13], utc("2027-03-15T06:30:00Z"));
+
+        assert!(
+            next_occurrences_after("cron(0 0 1 1 ? 2020)", None, after, 1)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(matches!(
+            next_occurrences_after("cron(0 25 * * ? *)", None, after, 1),
+            Err(Error::CronValidation(_))
+        ));
+        assert!(
+            next_occurrences_after("cron(0 0 * * ? *)", Some("Mars/Olympus"), after, 1).is_err()
+        );
+    }
+
+    #[test]
+    fn test_next_occurrences_at_and_rate() {
+        let after = utc("2026-10-16T10:30:00Z");
+        let occurrences =
+            next_occurrences_after("at(2026-10-17T09:00:00)", Some("Asia/Tokyo"), after, 3)
+                .unwrap();
+        assert_eq!(occurrences, vec![utc("2026-10-17T00:00:00Z")]);
+        assert!(
+            next_occurrences_after("at(2026-10-16T09:00:00)", None, after, 3)
+                .unwrap()
+                .is_empty()
+        );
+
+        let occurrences = next_occurrences_after("rate(2 hours)", None, after, 2).unwrap();
+        assert_eq!(
+            occurrences,
+            vec![utc("2026-10-16T12:30:00Z"), utc("2026-10-16T14:30:00Z")]
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use std::{fmt, str::FromStr};
+
+use aws_sdk_scheduler::{
+    Client,
+    operation::get_schedule::GetScheduleOutput,
+    primitives::DateTime as AwsDateTime,
+    types::{ActionAfterCompletion, FlexibleTimeWindowMode, ScheduleState},
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    builder::schedule_expression_builder::{CronField, RateUnit, validate_field},
+    error::Error,
+    scheduler::get_scheduler,
+};
+
+/// Parsed schedule expression, the inverse of the expression builders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleExpression {
+    /// One-time schedule, in the time zone of the schedule.
+    At(NaiveDateTime),
+    Rate {
+        value: u32,
+        unit: RateUnit,
+    },
+    Cron {
+        minutes: String,
+        hours: String,
+        day_of_month: String,
+        month: String,
+        day_of_week: String,
+        year: Option<String>,
+    },
+}
+
+/// Difference of a field between two expressions, from [`ScheduleExpression::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpressionDiff {
+    /// `kind` if the expressions are of different kinds, `at`, `rate`, or a cron field
+    /// such as `day_of_week`.
+    pub field: &'static str,
+    pub desired: String,
+    pub actual: String,
+}
+
+impl ScheduleExpression {
+    /// Parses an expression, validating the rate value and the cron fields like the
+    /// builders.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let expression = parse_expression(s)?;
+        expression.validate()?;
+        Ok(expression)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        match self {
+            ScheduleExpression::At(_) => Ok(()),
+            ScheduleExpression::Rate { value, .. } => {
+                if *value == 0 {
+                    return Err(Error::ValidationError(
+                        "value must be a positive number".to_string(),
+                    ));
+                }
+                Ok(())
+            }
+            ScheduleExpression::Cron {
+                minutes,
+                hours,
+                day_of_month,
+                month,
+                day_of_week,
+                year,
+            } => {
+                validate_field(minutes, CronField::Minutes)?;
+                validate_field(hours, CronField::Hours)?;
+                validate_field(day_of_month, CronField::DayOfMonth)?;
+                validate_field(month, CronField::Month)?;
+                validate_field(day_of_week, CronField::DayOfWeek)?;
+                if let Some(year) = year {
+                    validate_field(year, CronField::Year)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Equivalent expression in a canonical form: rates in the largest exact unit
+    /// (`rate(60 minutes)` is `rate(1 hour)`), cron names in upper case and a `*` year
+    /// omitted.
+    pub fn normalized(&self) -> Self {
+        match self {
+            ScheduleExpression::At(_) => self.clone(),
+            ScheduleExpression::Rate { value, unit } => {
+                let minutes = match unit {
+                    RateUnit::Minutes => *value as u64,
+                    RateUnit::Hours => *value as u64 * 60,
+                    RateUnit::Days => *value as u64 * 60 * 24,
+                };
+                let (value, unit) = if minutes % (60 * 24) == 0 {
+                    (minutes / (60 * 24), RateUnit::Days)
+                } else if minutes % 60 == 0 {
+                    (minutes / 60, RateUnit::Hours)
+                } else {
+                    (minutes, RateUnit::Minutes)
+                };
+                match u32::try_from(value) {
+                    Ok(value) => ScheduleExpression::Rate { value, unit },
+                    Err(_) => self.clone(),
+                }
+            }
+            ScheduleExpression::Cron {
+                minutes,
+                hours,
+                day_of_month,
+                month,
+                day_of_week,
+                year,
+            } => ScheduleExpression::Cron {
+                minutes: minutes.clone(),
+                hours: hours.clone(),
+                day_of_month: day_of_month.to_ascii_uppercase(),
+                month: month.to_ascii_uppercase(),
+                day_of_week: day_of_week.to_ascii_uppercase(),
+                year: year.clone().filter(|y| y != "*"),
+            },
+        }
+    }
+
+    /// Fields of `actual` that differ from this (desired) expression, compared in the
+    /// [`normalized`](Self::normalized) form. Empty if they are equivalent.
+    pub fn diff(&self, actual: &ScheduleExpression) -> Vec<ExpressionDiff> {
+        let diff = |field, desired: &str, actual: &str| ExpressionDiff {
+            field,
+            desired: desired.to_string(),
+            actual: actual.to_string(),
+        };
+        let desired = self.normalized();
+        let actual = actual.normalized();
+        match (&desired, &actual) {
+            (ScheduleExpression::At(_), ScheduleExpression::At(_))
+            | (ScheduleExpression::Rate { .. }, ScheduleExpression::Rate { .. })
+                if desired == actual =>
+            {
+                Vec::new()
+            }
+            (ScheduleExpression::At(_), ScheduleExpression::At(_)) => {
+                vec![diff("at", &desired.to_string(), &actual.to_string())]
+            }
+            (ScheduleExpression::Rate { .. }, ScheduleExpression::Rate { .. }) => {
+                vec![diff("rate", &desired.to_string(), &actual.to_string())]
+            }
+            (
+                ScheduleExpression::Cron {
+                    minutes,
+                    hours,
+                    day_of_month,
+                    month,
+                    day_of_week,
+                    year,
+                },
+                ScheduleExpression::Cron {
+                    minutes: actual_minutes,
+                    hours: actual_hours,
+                    day_of_month: actual_day_of_month,
+                    month: actual_month,
+                    day_of_week: actual_day_of_week,
+                    year: actual_year,
+                },
+            ) => [
+                (
+                    CronField::Minutes,
+                    minutes.as_str(),
+                    actual_minutes.as_str(),
+                ),
+                (CronField::Hours, hours, actual_hours),
+                (CronField::DayOfMonth, day_of_month, actual_day_of_month),
+                (CronField::Month, month, actual_month),
+                (CronField::DayOfWeek, day_of_week, actual_day_of_week),
+                (
+                    CronField::Year,
+                    year.as_deref().unwrap_or("*"),
+                    actual_year.as_deref().unwrap_or("*"),
+                ),
+            ]
+            .into_iter()
+            .filter(|(_, desired, actual)| desired != actual)
+            .map(|(field, desired, actual)| diff(field.name(), desired, actual))
+            .collect(),
+            _ => vec![diff("kind", &desired.to_string(), &actual.to_string())],
+        }
+    }
+
+    /// Whether the expressions fire at the same times, see [`diff`](Self::diff).
+    pub fn is_equivalent(&self, other: &ScheduleExpression) -> bool {
+        self.diff(other).is_empty()
+    }
+}
+
+impl FromStr for ScheduleExpression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ScheduleExpression::parse(s)
+    }
+}
+
+fn parse_expression(s: &str) -> Result<ScheduleExpression, Error> {
+    let invalid = || Error::ValidationError(format!("invalid schedule expression: {s}"));
+    let (kind, body) = s
+        .trim()
+        .strip_suffix(')')
+        .and_then(|s| s.split_once('('))
+        .ok_or_else(invalid)?;
+    match kind {
+        "at" => NaiveDateTime::parse_from_str(body, "%Y-%m-%dT%H:%M:%S")
+            .map(ScheduleExpression::At)
+            .map_err(|_| invalid()),
+        "rate" => {
+            let (value, unit) = body.trim().split_once(' ').ok_or_else(invalid)?;
+            let value = value.parse().map_err(|_| invalid())?;
+            let unit = match unit.trim() {
+                "minute" | "minutes" => RateUnit::Minutes,
+                "hour" | "hours" => RateUnit::Hours,
+                "day" | "days" => RateUnit::Days,
+                _ => return Err(invalid()),
+            };
+            Ok(ScheduleExpression::Rate { value, unit })
+        }
+        "cron" => {
+            let fields = body.split_whitespace().collect::<Vec<_>>();
+            let [minutes, hours, day_of_month, month, day_of_week, year @ ..] = fields.as_slice()
+            else {
+                return Err(invalid());
+            };
+            if year.len() > 1 {
+                return Err(invalid());
+            }
+            Ok(ScheduleExpression::Cron {
+                minutes: minutes.to_string(),
+                hours: hours.to_string(),
+                day_of_month: day_of_month.to_string(),
+                month: month.to_string(),
+                day_of_week: day_of_week.to_string(),
+                year: year.first().map(|y| y.to_string()),
+            })
+        }
+        _ => Err(invalid()),
+    }
+}
+
+impl fmt::Display for ScheduleExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleExpression::At(datetime) => {
+                write!(f, "at({})", datetime.format("%Y-%m-%dT%H:%M:%S"))
+            }
+            ScheduleExpression::Rate { value, unit } => {
+                let unit = match (unit, *value == 1) {
+                    (RateUnit::Minutes, true) => "minute",
+                    (RateUnit::Minutes, false) => "minutes",
+                    (RateUnit::Hours, true) => "hour",
+                    (RateUnit::Hours, false) => "hours",
+                    (RateUnit::Days, true) => "day",
+                    (RateUnit::Days, false) => "days",
+                };
+                write!(f, "rate({value} {unit})")
+            }
+            ScheduleExpression::Cron {
+                minutes,
+                hours,
+                day_of_month,
+                month,
+                day_of_week,
+                year,
+            } => {
+                write!(
+                    f,
+                    "cron({minutes} {hours} {day_of_month} {month} {day_of_week}"
+                )?;
+                if let Some(year) = year {
+                    write!(f, " {year}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// GetSchedule output with chrono dates, a parsed expression and the target input
+/// deserialized into `T`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule<T = serde_json::Value> {
+    pub name: String,
+    pub group_name: Option<String>,
+    pub arn: Option<String>,
+    pub description: Option<String>,
+    pub state: Option<ScheduleState>,
+    pub schedule_expression: ScheduleExpression,
+    pub schedule_expression_timezone: Option<String>,
+    pub start_date: Option<DateTime<Utc>>,
+    pub end_date: Option<DateTime<Utc>>,
+    pub creation_date: Option<DateTime<Utc>>,
+    pub last_modification_date: Option<DateTime<Utc>>,
+    /// None if the flexible time window is OFF.
+    pub maximum_window_in_minutes: Option<i32>,
+    pub action_after_completion: Option<ActionAfterCompletion>,
+    pub target_arn: Option<String>,
+    pub target_role_arn: Option<String>,
+    /// None if the target has no input.
+    pub target_input: Option<T>,
+}
+
+impl<T> TryFrom<GetScheduleOutput> for Schedule<T>
+where
+    T: DeserializeOwned,
+{
+    type Error = Error;
+
+    fn try_from(output: GetScheduleOutput) -> Result<Self, Self::Error> {
+        let schedule_expression = output
+            .schedule_expression()
+            .ok_or_else(|| Error::ValidationError("schedule_expression is missing".to_string()))?
+            .parse()?;
+        let target_input = output
+            .target()
+            .and_then(|t| t.input())
+            .map(serde_json::from_str)
+            .transpose()?;
+        let maximum_window_in_minutes = output
+            .flexible_time_window()
+            .filter(|w| w.mode() == &FlexibleTimeWindowMode::Flexible)
+            .and_then(|w| w.maximum_window_in_minutes());
+        Ok(Schedule {
+            name: output.name.clone().unwrap_or_default(),
+            group_name: output.group_name.clone(),
+            arn: output.arn.clone(),
+            description: output.description.clone(),
+            state: output.state.clone(),
+            schedule_expression,
+            schedule_expression_timezone: output.schedule_expression_timezone.clone(),
+            start_date: output.start_date().and_then(to_chrono),
+            end_date: output.end_date().and_then(to_chrono),
+            creation_date: output.creation_date().and_then(to_chrono),
+            last_modification_date: output.last_modification_date().and_then(to_chrono),
+            maximum_window_in_minutes,
+            action_after_completion: output.action_after_completion.clone(),
+            target_arn: output.target().map(|t| t.arn().to_string()),
+            target_role_arn: output.target().map(|t| t.role_arn().to_string()),
+            target_input,
+        })
+    }
+}
+
+fn to_chrono(datetime: &AwsDateTime) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(datetime.secs(), datetime.subsec_nanos())
+}
+
+/// GetSchedule mapped into [`Schedule`], e.g. for reconciliation tooling.
+/// Fails if the target input is not JSON deserializable into `T`.
+pub async fn get_schedule_typed<T>(
+    client: &Client,
+    name: impl Into<String>,
+    group_name: Option<impl Into<String>>,
+) -> Result<Schedule<T>, Error>
+where
+    T: DeserializeOwned,
+{
+    get_scheduler(client, name, group_name).await?.try_into()
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_scheduler::types::{FlexibleTimeWindow, Target};
+
+    use super::*;
+
+    #[test]
+    fn test_schedule_expression() {
+        for expression in [
+            "at(2022-11-20T13:00:00)",
+            "rate(1 hour)",
+            "rate(5 minutes)",
+            "cron(0 12 * * ? *)",
+            "cron(15 10 ? * MON-FRI)",
+        ] {
+            let parsed = expression.parse::<ScheduleExpression>().unwrap();
+            assert_eq!(parsed.to_string(), expression);
+        }
+        assert_eq!(
+            "rate(7 days)".parse::<ScheduleExpression>().unwrap(),
+            ScheduleExpression::Rate {
+                value: 7,
+                unit: RateUnit::Days
+            }
+        );
+        assert!("rate(7 weeks)".parse::<ScheduleExpression>().is_err());
+        assert!("rate(0 minutes)".parse::<ScheduleExpression>().is_err());
+        assert!(matches!(
+            ScheduleExpression::parse("cron(0 25 * * ? *)"),
+            Err(Error::CronValidation(e)) if e.field == "hours"
+        ));
+        assert!("cron(0 12 * *)".parse::<ScheduleExpression>().is_err());
+    }
+
+    #[test]
+    fn test_schedule_expression_diff() {
+        let parse = |s| ScheduleExpression::parse(s).unwrap();
+        assert!(parse("rate(60 minutes)").is_equivalent(&parse("rate(1 hour)")));
+        assert!(parse("rate(2 days)").is_equivalent(&parse("rate(48 hours)")));
+        assert!(
+            parse("cron(0 9 ? jan mon-fri *)").is_equivalent(&parse("cron(0 9 ? JAN MON-FRI)"))
+        );
+
+        assert_eq!(
+            parse("cron(0 9 ? * MON-FRI *)").diff(&parse("cron(30 9 ? * MON *)")),
+            vec![
+                ExpressionDiff {
+                    field: "minutes",
+                    desired: "0".to_string(),
+                    actual: "30".to_string(),
+                },
+                ExpressionDiff {
+                    field: "day_of_week",
+                    desired: "MON-FRI".to_string(),
+                    actual: "MON".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            parse("rate(90 minutes)").diff(&parse("rate(1 hour)")),
+            vec![ExpressionDiff {
+                field: "rate",
+                desired: "rate(90 minutes)".to_string(),
+                actual: "rate(1 hour)".to_string(),
+            }]
+        );
+        assert_eq!(
+            parse("rate(1 day)").diff(&parse("cron(0 0 * * ? *)"))[0].field,
+            "kind"
+        );
+    }
+
+    #[test]
+    fn test_schedule_try_from() {
+        let output = GetScheduleOutput::builder()
+            .name("daily")
+            .schedule_expression("cron(0 12 * * ? *)")
+            .start_date(AwsDateTime::from_secs(1_700_000_000))
+            .flexible_time_window(
+                FlexibleTimeWindow::builder()
+                    .mode(FlexibleTimeWindowMode::Off)
+                    .build()
+                    .unwrap(),
+            )
+            .target(
+                Target::builder()
+                    .arn("arn:aws:lambda:ap-northeast-1:123456789012:function:f")
+                    .role_arn("arn:aws:iam::123456789012:role/r")
+                    .input(r#"{"job":"report"}"#)
+                    .build()
+                    .unwrap(),
+            )
+            .build();
+
+        let schedule = Schedule::<serde_json::Value>::try_from(output).unwrap();
+        assert_eq!(schedule.name, "daily");
+        assert_eq!(
+            schedule.start_date.unwrap().to_rfc3339(),
+            "2023-11-14T22:13:20+00:00"
+        );
+        assert_eq!(schedule.maximum_window_in_minutes, None);
+        assert_eq!(
+            schedule.target_input,
+            Some(serde_json::json!({"job": "report"}))
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use crate::error::{Error, from_aws_sdk_error};
+use std::collections::HashMap;
+
+use crate::error::{Error, IdempotentError, from_aws_sdk_error};
 use aws_sdk_scheduler::{
     Client,
     operation::{
@@ -6,11 +8,30 @@ use aws_sdk_scheduler::{
         update_schedule::UpdateScheduleOutput,
     },
     primitives::DateTime as AwsDateTime,
-    types::{ActionAfterCompletion, FlexibleTimeWindow, ScheduleState, ScheduleSummary, Target},
+    types::{
+        ActionAfterCompletion, FlexibleTimeWindow, ScheduleGroupSummary, ScheduleState,
+        ScheduleSummary, Tag, Target,
+    },
 };
 use aws_smithy_types_convert::stream::PaginationStreamExt;
 use chrono::prelude::*;
-use futures_util::{Stream, TryStreamExt};
+use futures_util::{Stream, StreamExt, TryStreamExt, stream};
+use uuid::Uuid;
+
+/// Output of an `_idempotent` operation with the client token sent, to retry the operation
+/// with the same token (e.g. after a timeout) without applying it twice.
+#[derive(Debug, Clone)]
+pub struct IdempotentOutput<T> {
+    pub output: T,
+    pub client_token: String,
+}
+
+/// `client_token`, or a new UUID if None.
+pub fn client_token(client_token: Option<impl Into<String>>) -> String {
+    client_token
+        .map(Into::into)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
 
 #[allow(clippy::too_many_arguments)]
 pub async fn create_schedule(
@@ -102,6 +123,116 @@ pub async fn delete_schedule(
         .map_err(from_aws_sdk_error)
 }
 
+/// [`create_schedule`] with a UUID client token generated if `client_token` is None.
+/// The token is returned with the output and with the error, and retrying with it (e.g. after
+/// a timeout) does not create a duplicate schedule.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_schedule_idempotent(
+    client: &Client,
+    name: impl Into<String>,
+    group_name: Option<impl Into<String>>,
+    schedule_expression: impl Into<String>,
+    start_date: Option<DateTime<Utc>>,
+    end_date: Option<DateTime<Utc>>,
+    description: Option<impl Into<String>>,
+    schedule_expression_timezone: Option<impl Into<String>>,
+    state: Option<ScheduleState>,
+    kms_key_arn: Option<impl Into<String>>,
+    target: Option<Target>,
+    flexible_time_window: Option<FlexibleTimeWindow>,
+    client_token: Option<impl Into<String>>,
+    action_after_completion: Option<ActionAfterCompletion>,
+) -> Result<IdempotentOutput<CreateScheduleOutput>, IdempotentError> {
+    let client_token = self::client_token(client_token);
+    let result = create_schedule(
+        client,
+        name,
+        group_name,
+        schedule_expression,
+        start_date,
+        end_date,
+        description,
+        schedule_expression_timezone,
+        state,
+        kms_key_arn,
+        target,
+        flexible_time_window,
+        Some(&client_token),
+        action_after_completion,
+    )
+    .await;
+    idempotent_output(result, client_token)
+}
+
+/// [`update_schedule`] with a UUID client token generated if `client_token` is None, returned
+/// with the output and with the error.
+#[allow(clippy::too_many_arguments)]
+pub async fn update_schedule_idempotent(
+    client: &Client,
+    name: impl Into<String>,
+    group_name: Option<impl Into<String>>,
+    schedule_expression: impl Into<String>,
+    start_date: Option<DateTime<Utc>>,
+    end_date: Option<DateTime<Utc>>,
+    description: Option<impl Into<String>>,
+    schedule_expression_timezone: Option<impl Into<String>>,
+    state: Option<ScheduleState>,
+    kms_key_arn: Option<impl Into<String>>,
+    target: Option<Target>,
+    flexible_time_window: Option<FlexibleTimeWindow>,
+    client_token: Option<impl Into<String>>,
+    action_after_completion: Option<ActionAfterCompletion>,
+) -> Result<IdempotentOutput<UpdateScheduleOutput>, IdempotentError> {
+    let client_token = self::client_token(client_token);
+    let result = update_schedule(
+        client,
+        name,
+        group_name,
+        schedule_expression,
+        start_date,
+        end_date,
+        description,
+        schedule_expression_timezone,
+        state,
+        kms_key_arn,
+        target,
+        flexible_time_window,
+        Some(&client_token),
+        action_after_completion,
+    )
+    .await;
+    idempotent_output(result, client_token)
+}
+
+/// [`delete_schedule`] with a UUID client token generated if `client_token` is None, returned
+/// with the output and with the error.
+pub async fn delete_schedule_idempotent(
+    client: &Client,
+    name: impl Into<String>,
+    group_name: Option<impl Into<String>>,
+    client_token: Option<impl Into<String>>,
+) -> Result<IdempotentOutput<DeleteScheduleOutput>, IdempotentError> {
+    let client_token = self::client_token(client_token);
+    let result = delete_schedule(client, name, group_name, Some(&client_token)).await;
+    idempotent_output(result, client_token)
+}
+
+fn idempotent_output<T>(
+    result: Result<T, Error>,
+    client_token: String,
+) -> Result<IdempotentOutput<T>, IdempotentError> {
+    match result {
+        Ok(output) => Ok(IdempotentOutput {
+            output,
+            client_token,
+        }),
+        Err(source) => Err(IdempotentError {
+            source,
+            client_token,
+        }),
+    }
+}
+
 pub async fn get_scheduler(
     client: &Client,
     name: impl Into<String>,
@@ -148,3 +279,295 @@ pub async fn list_schedules_all(
     }
     Ok(result)
 }
+
+/// Adds or overwrites tags of a resource. EventBridge Scheduler only supports tags on
+/// schedule groups, so `resource_arn` is the ARN of a schedule group.
+pub async fn tag_resource(
+    client: &Client,
+    resource_arn: impl Into<String>,
+    tags: HashMap<String, String>,
+) -> Result<(), Error> {
+    let tags = tags
+        .into_iter()
+        .map(|(key, value)| Tag::builder().key(key).value(value).build())
+        .collect::<Result<Vec<_>, _>>()?;
+    client
+        .tag_resource()
+        .resource_arn(resource_arn.into())
+        .set_tags(Some(tags))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+pub async fn untag_resource(
+    client: &Client,
+    resource_arn: impl Into<String>,
+    tag_keys: Vec<String>,
+) -> Result<(), Error> {
+    client
+        .untag_resource()
+        .resource_arn(resource_arn.into())
+        .set_tag_keys(Some(tag_keys))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(())
+}
+
+pub async fn list_tags_for_resource(
+    client: &Client,
+    resource_arn: impl Into<String>,
+) -> Result<HashMap<String, String>, Error> {
+    let output = client
+        .list_tags_for_resource()
+        .resource_arn(resource_arn.into())
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(output
+        .tags
+        .unwrap_or_default()
+        .into_iter()
+        .map(|tag| (tag.key, tag.value))
+        .collect())
+}
+
+pub fn list_schedule_groups_stream(
+    client: &Client,
+    name_prefix: Option<impl Into<String>>,
+) -> impl Stream<Item = Result<ScheduleGroupSummary, Error>> {
+    client
+        .list_schedule_groups()
+        .set_name_prefix(name_prefix.map(|n| n.into()))
+        .into_paginator()
+        .items()
+        .send()
+        .into_stream_03x()
+        .map_err(from_aws_sdk_error)
+}
+
+/// Lists the schedules of every schedule group tagged with `tag_key` = `tag_value`
+/// (e.g. `tenant` = `acme`). The tags of the groups and the schedules of the matching
+/// groups are fetched with up to `concurrency` requests at the same time.
+pub async fn list_schedules_by_tag(
+    client: &Client,
+    tag_key: &str,
+    tag_value: &str,
+    concurrency: usize,
+) -> Result<Vec<ScheduleSummary>, Error> {
+    let concurrency = concurrency.max(1);
+    let group_names: Vec<String> = list_schedule_groups_stream(client, None::<String>)
+        .map_ok(|group| async move {
+            let Some(arn) = group.arn() else {
+                return Ok(None);
+            };
+            let tags = list_tags_for_resource(client, arn).await?;
+            let matched = tags.get(tag_key).is_some_and(|v| v == tag_value);
+            Ok(group.name.filter(|_| matched))
+        })
+        .try_buffer_unordered(concurrency)
+        .try_filter_map(|name| async move { Ok(name) })
+        .try_collect()
+        .await?;
+
+    let schedules: Vec<Vec<ScheduleSummary>> = stream::iter(group_names)
+        .map(|group_name| async move {
+            list_schedules_all(client, None::<String>, Some(group_name), None).await
+        })
+        .buffer_unordered(concurrency)
+        .try_collect()
+        .await?;
+    Ok(schedules.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AwsUtilError;
+
+    #[test]
+    fn test_client_token() {
+        assert_eq!(client_token(Some("retry-1")), "retry-1");
+        let generated = client_token(None::<String>);
+        assert!(Uuid::parse_str(&generated).is_ok());
+        assert_ne!(generated, client_token(None::<String>));
+    }
+
+    #[tokio::test]
+    async fn test_create_schedule_idempotent() {
+        let mut server = mockito::Server::new_async().await;
+        let failed = server
+            .mock("POST", "/schedules/my-schedule")
+            .with_status(400)
+            .with_header("x-amzn-ErrorType", "ValidationException")
+            .with_body(serde_json::json!({"Message": "invalid target"}).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        let create = |client_token: Option<String>| {
+            create_schedule_idempotent(
+                &client,
+                "my-schedule",
+                None::<String>,
+                "rate(5 minutes)",
+                None,
+                None,
+                None::<String>,
+                None::<String>,
+                None,
+                None::<String>,
+                None,
+                Some(
+                    FlexibleTimeWindow::builder()
+                        .mode(aws_sdk_scheduler::types::FlexibleTimeWindowMode::Off)
+                        .build()
+                        .unwrap(),
+                ),
+                client_token,
+                None,
+            )
+        };
+
+        // 失敗しても送ったトークンを返す
+        let error = create(None).await.unwrap_err();
+        assert!(Uuid::parse_str(&error.client_token).is_ok());
+        assert_eq!(error.error_code(), Some("ValidationException"));
+        failed.assert_async().await;
+        failed.remove_async().await;
+
+        let created = server
+            .mock("POST", "/schedules/my-schedule")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"ClientToken": error.client_token}),
+            ))
+            .with_body(
+                serde_json::json!({
+                    "ScheduleArn": "arn:aws:scheduler:ap-northeast-1:123456789012:schedule/default/my-schedule",
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let output = create(Some(error.client_token.clone())).await.unwrap();
+        assert_eq!(output.client_token, error.client_token);
+        assert!(output.output.schedule_arn().ends_with("/my-schedule"));
+        created.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_delete_schedule_idempotent() {
+        let mut server = mockito::Server::new_async().await;
+        let deleted = server
+            .mock("DELETE", "/schedules/my-schedule")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "clientToken".to_string(),
+                "retry-1".to_string(),
+            ))
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let output =
+            delete_schedule_idempotent(&client, "my-schedule", None::<String>, Some("retry-1"))
+                .await
+                .unwrap();
+        assert_eq!(output.client_token, "retry-1");
+        deleted.assert_async().await;
+    }
+
+    fn group_arn(name: &str) -> String {
+        format!("arn:aws:scheduler:ap-northeast-1:123456789012:schedule-group/{name}")
+    }
+
+    #[tokio::test]
+    async fn test_list_schedules_by_tag() {
+        let mut server = mockito::Server::new_async().await;
+        // 2 ページ目の一覧
+        server
+            .mock("GET", "/schedule-groups")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "NextToken".to_string(),
+                "page-2".to_string(),
+            ))
+            .with_body(
+                serde_json::json!({
+                    "ScheduleGroups": [{"Arn": group_arn("acme-b"), "Name": "acme-b"}],
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/schedule-groups")
+            .with_body(
+                serde_json::json!({
+                    "ScheduleGroups": [
+                        {"Arn": group_arn("acme-a"), "Name": "acme-a"},
+                        {"Arn": group_arn("globex"), "Name": "globex"},
+                        {"Arn": group_arn("untagged"), "Name": "untagged"},
+                    ],
+                    "NextToken": "page-2",
+                })
+                .to_string(),
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        for (group, tags) in [
+            (
+                "acme-a",
+                serde_json::json!([{"Key": "tenant", "Value": "acme"}]),
+            ),
+            (
+                "acme-b",
+                serde_json::json!([{"Key": "tenant", "Value": "acme"}]),
+            ),
+            (
+                "globex",
+                serde_json::json!([{"Key": "tenant", "Value": "globex"}]),
+            ),
+            ("untagged", serde_json::json!([])),
+        ] {
+            server
+                .mock("GET", mockito::Matcher::Regex(format!("^/tags/.*{group}$")))
+                .with_body(serde_json::json!({"Tags": tags}).to_string())
+                .expect(1)
+                .create_async()
+                .await;
+        }
+        for group in ["acme-a", "acme-b"] {
+            server
+                .mock("GET", "/schedules")
+                .match_query(mockito::Matcher::UrlEncoded(
+                    "ScheduleGroup".to_string(),
+                    group.to_string(),
+                ))
+                .with_body(
+                    serde_json::json!({
+                        "Schedules": [{"Name": format!("{group}-daily"), "GroupName": group}],
+                    })
+                    .to_string(),
+                )
+                .expect(1)
+                .create_async()
+                .await;
+        }
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let mut names = list_schedules_by_tag(&client, "tenant", "acme", 2)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|schedule| schedule.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["acme-a-daily", "acme-b-daily"]);
+    }
+}
@@ -1,3 +1,6 @@
+use aws_sdk_secretsmanager::{error::ProvideErrorMetadata, operation::RequestId};
+pub use aws_utils_core::error::AwsUtilError;
+use aws_utils_core::error::{is_not_found_code, is_retryable_sdk_error};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -18,3 +21,30 @@ pub enum Error {
 pub(crate) fn from_aws_sdk_error(e: impl Into<aws_sdk_secretsmanager::Error>) -> Error {
     Error::AwsSdk(Box::new(e.into()))
 }
+
+impl AwsUtilError for Error {
+    fn error_code(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.code(),
+            _ => None,
+        }
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.request_id(),
+            _ => None,
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::AwsSdk(e) => is_retryable_sdk_error(e.as_ref()),
+            _ => false,
+        }
+    }
+
+    fn is_not_found(&self) -> bool {
+        matches!(self, Error::NotFound) || self.error_code().is_some_and(is_not_found_code)
+    }
+}
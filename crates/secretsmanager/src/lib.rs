@@ -7,9 +7,13 @@ use aws_config::{
 use aws_sdk_secretsmanager::{Client, config::SharedInterceptor};
 
 pub mod error;
+pub mod replication;
 pub mod secretsmanager;
 
 pub use aws_sdk_secretsmanager;
+#[cfg(feature = "metrics")]
+pub use aws_utils_core::metrics;
+pub use aws_utils_core::preload;
 
 pub async fn make_client_with_timeout_default(endpoint_url: Option<String>) -> Client {
     make_client_with_timeout(
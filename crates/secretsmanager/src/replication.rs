@@ -0,0 +1,223 @@
+use aws_sdk_secretsmanager::{
+    Client,
+    config::Region,
+    operation::get_secret_value::GetSecretValueOutput,
+    types::{ReplicaRegionType, ReplicationStatusType},
+};
+
+use crate::{
+    error::{AwsUtilError, Error, from_aws_sdk_error},
+    secretsmanager::get_secret_value_raw,
+};
+
+/// Replicates the secret to `regions`, encrypted with `kms_key_id` in each of them
+/// (the AWS managed key if None). Returns the replication status of all replicas.
+pub async fn replicate_secret_to_regions(
+    client: &Client,
+    secret_id: impl Into<String>,
+    regions: impl IntoIterator<Item = impl Into<String>>,
+    kms_key_id: Option<impl Into<String>>,
+    force_overwrite_replica_secret: bool,
+) -> Result<Vec<ReplicationStatusType>, Error> {
+    let kms_key_id: Option<String> = kms_key_id.map(Into::into);
+    let replica_regions = regions
+        .into_iter()
+        .map(|region| {
+            ReplicaRegionType::builder()
+                .region(region)
+                .set_kms_key_id(kms_key_id.clone())
+                .build()
+        })
+        .collect();
+    let output = client
+        .replicate_secret_to_regions()
+        .secret_id(secret_id)
+        .set_add_replica_regions(Some(replica_regions))
+        .force_overwrite_replica_secret(force_overwrite_replica_secret)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(output.replication_status.unwrap_or_default())
+}
+
+/// Reads secrets from the primary region, and from the replica regions in order when the
+/// request fails with a retryable error (throttling, server side errors or network failures,
+/// e.g. during a regional outage).
+///
+/// Other errors such as not found or access denied are returned without trying the replicas,
+/// as they have the same secrets and policies. Use the secret name as `secret_id`, since an
+/// ARN contains the region.
+#[derive(Debug, Clone)]
+pub struct MultiRegionSecretReader {
+    clients: Vec<Client>,
+}
+
+impl MultiRegionSecretReader {
+    pub fn new(primary: Client, replicas: Vec<Client>) -> Self {
+        let mut clients = vec![primary];
+        clients.extend(replicas);
+        Self { clients }
+    }
+
+    /// Reader with clients for `replica_regions` made from the configuration of `primary`,
+    /// so the timeouts and interceptors are shared.
+    pub fn with_replica_regions(
+        primary: Client,
+        replica_regions: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let replicas = replica_regions
+            .into_iter()
+            .map(|region| {
+                let config = primary
+                    .config()
+                    .to_builder()
+                    .region(Region::new(region.into()))
+                    .build();
+                Client::from_conf(config)
+            })
+            .collect();
+        Self::new(primary, replicas)
+    }
+
+    pub async fn get_secret_value_raw(
+        &self,
+        secret_id: &str,
+        version_id: Option<&str>,
+        version_stage: Option<&str>,
+    ) -> Result<GetSecretValueOutput, Error> {
+        let mut last_error = None;
+        for client in &self.clients {
+            match get_secret_value_raw(client, Some(secret_id), version_id, version_stage).await {
+                Err(e) if e.is_retryable() => last_error = Some(e),
+                result => return result,
+            }
+        }
+        Err(last_error.unwrap_or(Error::NotFound))
+    }
+
+    pub async fn get_secret_value(&self, secret_id: &str) -> Result<String, Error> {
+        let res = self.get_secret_value_raw(secret_id, None, None).await?;
+        res.secret_string()
+            .ok_or_else(|| Error::NotFound)
+            .map(|s| s.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_secretsmanager::config::retry::RetryConfig;
+    use mockito::{Mock, Server, ServerGuard};
+
+    use super::*;
+
+    async fn client(endpoint_url: String) -> Client {
+        let client = crate::make_client(Some(endpoint_url), None, None).await;
+        let config = client
+            .config()
+            .to_builder()
+            .retry_config(RetryConfig::disabled())
+            .build();
+        Client::from_conf(config)
+    }
+
+    async fn mock(server: &mut ServerGuard, status: usize, body: &str, hits: usize) -> Mock {
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "secretsmanager.GetSecretValue")
+            .with_status(status)
+            .with_body(body)
+            .expect(hits)
+            .create_async()
+            .await
+    }
+
+    const SECRET: &str = r#"{"Name":"my-secret","SecretString":"replica"}"#;
+
+    #[tokio::test]
+    async fn test_falls_back_on_retryable_error() {
+        let mut primary = Server::new_async().await;
+        let mut replica = Server::new_async().await;
+        let primary_mock = mock(
+            &mut primary,
+            500,
+            r#"{"__type":"InternalServiceError","message":"unavailable"}"#,
+            1,
+        )
+        .await;
+        let replica_mock = mock(&mut replica, 200, SECRET, 1).await;
+        let reader = MultiRegionSecretReader::new(
+            client(primary.url()).await,
+            vec![client(replica.url()).await],
+        );
+
+        assert_eq!(
+            reader.get_secret_value("my-secret").await.unwrap(),
+            "replica"
+        );
+        primary_mock.assert_async().await;
+        replica_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_on_transport_error() {
+        let mut replica = Server::new_async().await;
+        let replica_mock = mock(&mut replica, 200, SECRET, 1).await;
+        // 接続できないエンドポイント
+        let reader = MultiRegionSecretReader::new(
+            client("http://127.0.0.1:1".to_string()).await,
+            vec![client(replica.url()).await],
+        );
+
+        assert_eq!(
+            reader.get_secret_value("my-secret").await.unwrap(),
+            "replica"
+        );
+        replica_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_does_not_fall_back_on_other_errors() {
+        for (status, body) in [
+            (
+                400,
+                r#"{"__type":"ResourceNotFoundException","message":"not found"}"#,
+            ),
+            (
+                400,
+                r#"{"__type":"AccessDeniedException","message":"denied"}"#,
+            ),
+        ] {
+            let mut primary = Server::new_async().await;
+            let mut replica = Server::new_async().await;
+            let primary_mock = mock(&mut primary, status, body, 1).await;
+            let replica_mock = mock(&mut replica, 200, SECRET, 0).await;
+            let reader = MultiRegionSecretReader::new(
+                client(primary.url()).await,
+                vec![client(replica.url()).await],
+            );
+
+            let e = reader.get_secret_value("my-secret").await.unwrap_err();
+            assert!(!e.is_retryable(), "{e:?}");
+            primary_mock.assert_async().await;
+            replica_mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_returns_last_error_when_all_fail() {
+        let mut primary = Server::new_async().await;
+        let mut replica = Server::new_async().await;
+        let body = r#"{"__type":"InternalServiceError","message":"unavailable"}"#;
+        let primary_mock = mock(&mut primary, 500, body, 1).await;
+        let replica_mock = mock(&mut replica, 500, body, 1).await;
+        let reader = MultiRegionSecretReader::new(
+            client(primary.url()).await,
+            vec![client(replica.url()).await],
+        );
+
+        let e = reader.get_secret_value("my-secret").await.unwrap_err();
+        assert_eq!(e.error_code(), Some("InternalServiceError"));
+        primary_mock.assert_async().await;
+        replica_mock.assert_async().await;
+    }
+}
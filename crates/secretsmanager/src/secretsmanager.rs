@@ -1,6 +1,23 @@
-use aws_sdk_secretsmanager::{Client, operation::get_secret_value::GetSecretValueOutput};
+use std::collections::HashMap;
 
-use crate::error::{Error, from_aws_sdk_error};
+use aws_sdk_secretsmanager::{
+    Client,
+    operation::get_secret_value::GetSecretValueOutput,
+    types::{Filter, FilterNameStringType},
+};
+use aws_utils_core::preload::{ConfigMap, MissingKeys, MissingReason};
+use futures_util::{StreamExt, TryStreamExt, stream};
+
+use crate::error::{AwsUtilError, Error, from_aws_sdk_error};
+
+pub const AWS_CURRENT: &str = "AWSCURRENT";
+pub const AWS_PENDING: &str = "AWSPENDING";
+
+/// Max concurrent GetSecretValue calls of [`get_secrets_by_tag`].
+const MAX_CONCURRENT_FETCHES: usize = 10;
+
+/// Max secret IDs of a BatchGetSecretValue call.
+const MAX_BATCH_GET_SECRETS: usize = 20;
 
 pub async fn get_secret_value_raw(
     client: &Client,
@@ -24,3 +41,403 @@ pub async fn get_secret_value(client: &Client, secret_id: &str) -> Result<String
         .ok_or_else(|| Error::NotFound)
         .map(|s| s.to_string())
 }
+
+/// SecretBinary of the current version, e.g. a certificate stored as binary.
+pub async fn get_secret_binary(client: &Client, secret_id: &str) -> Result<Vec<u8>, Error> {
+    let res = get_secret_value_raw(client, Some(secret_id), None::<String>, None::<String>).await?;
+    res.secret_binary
+        .ok_or_else(|| Error::NotFound)
+        .map(|b| b.into_inner())
+}
+
+/// The AWSCURRENT version, or the AWSPENDING version if there is no AWSCURRENT one during a
+/// rotation.
+pub async fn get_secret_current_or_pending(
+    client: &Client,
+    secret_id: &str,
+) -> Result<GetSecretValueOutput, Error> {
+    match get_secret_value_raw(client, Some(secret_id), None::<String>, Some(AWS_CURRENT)).await {
+        Err(e) if e.is_not_found() => {
+            get_secret_value_raw(client, Some(secret_id), None::<String>, Some(AWS_PENDING)).await
+        }
+        result => result,
+    }
+}
+
+/// Names of the secrets with the tag `key` = `value`.
+pub async fn list_secret_names_by_tag(
+    client: &Client,
+    key: &str,
+    value: &str,
+) -> Result<Vec<String>, Error> {
+    let mut names = vec![];
+    let mut next_token = None;
+    loop {
+        let output = client
+            .list_secrets()
+            .filters(
+                Filter::builder()
+                    .key(FilterNameStringType::TagKey)
+                    .values(key)
+                    .build(),
+            )
+            .filters(
+                Filter::builder()
+                    .key(FilterNameStringType::TagValue)
+                    .values(value)
+                    .build(),
+            )
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(from_aws_sdk_error)?;
+        // tag-key と tag-value のフィルターは別々のタグにも一致するので、組で確認する
+        names.extend(
+            output
+                .secret_list()
+                .iter()
+                .filter(|secret| {
+                    secret
+                        .tags()
+                        .iter()
+                        .any(|tag| tag.key() == Some(key) && tag.value() == Some(value))
+                })
+                .filter_map(|secret| secret.name().map(ToString::to_string)),
+        );
+        next_token = output.next_token;
+        if next_token.is_none() {
+            return Ok(names);
+        }
+    }
+}
+
+/// SecretString of all secrets with the tag `key` = `value` by name, e.g. the credentials of
+/// a tenant. Values are fetched concurrently. Secrets without a SecretString are not included.
+pub async fn get_secrets_by_tag(
+    client: &Client,
+    key: &str,
+    value: &str,
+) -> Result<HashMap<String, String>, Error> {
+    let names = list_secret_names_by_tag(client, key, value).await?;
+    stream::iter(names)
+        .map(|name| async move {
+            let output =
+                get_secret_value_raw(client, Some(&name), None::<String>, None::<String>).await?;
+            Ok::<_, Error>(output.secret_string.map(|value| (name, value)))
+        })
+        .buffer_unordered(MAX_CONCURRENT_FETCHES)
+        .try_filter_map(|entry| async move { Ok(entry) })
+        .try_collect()
+        .await
+}
+
+/// Loads the SecretString of the required secrets at startup with BatchGetSecretValue
+/// (20 secrets per call). A secret can be given by name, ARN or partial ARN (without the
+/// random suffix), and keeps that key in the map.
+///
+/// Fails with every secret that is missing or cannot be read, instead of failing one by one
+/// at runtime. Secrets with only a SecretBinary are reported as failed.
+pub async fn preload(
+    client: &Client,
+    required_keys: impl IntoIterator<Item = impl Into<String>>,
+) -> Result<ConfigMap, MissingKeys> {
+    let mut keys = required_keys
+        .into_iter()
+        .map(Into::into)
+        .collect::<Vec<String>>();
+    keys.sort();
+    keys.dedup();
+
+    let mut config = ConfigMap::new();
+    let mut missing = MissingKeys::default();
+    for chunk in keys.chunks(MAX_BATCH_GET_SECRETS) {
+        let result = client
+            .batch_get_secret_value()
+            .set_secret_id_list(Some(chunk.to_vec()))
+            .send()
+            .await;
+        match result {
+            Ok(output) => {
+                for key in chunk {
+                    let entry = output
+                        .secret_values()
+                        .iter()
+                        .find(|e| is_secret_id_of(key, e.name(), e.arn()));
+                    let error = output.errors().iter().find(|e| e.secret_id() == Some(key));
+                    match (entry, error) {
+                        (Some(entry), _) => match entry.secret_string() {
+                            Some(value) => config.insert(key, value),
+                            None => missing.push(key, secret_binary_only()),
+                        },
+                        (None, Some(error)) => missing.push(
+                            key,
+                            MissingReason::from_error_code(
+                                error.error_code(),
+                                error.message().unwrap_or_default(),
+                            ),
+                        ),
+                        (None, None) => missing.push(key, MissingReason::NotFound),
+                    }
+                }
+            }
+            // BatchGetSecretValue の権限がない場合などは1つずつ取得して原因を確認する
+            Err(_) => {
+                for key in chunk {
+                    match get_secret_value(client, key).await {
+                        Ok(value) => config.insert(key, value),
+                        Err(Error::NotFound) => missing.push(key, secret_binary_only()),
+                        Err(e) => {
+                            missing.push(key, MissingReason::from_error_code(e.error_code(), &e))
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if missing.is_empty() {
+        Ok(config)
+    } else {
+        Err(missing)
+    }
+}
+
+/// Whether `secret_id` is the name, the ARN or the partial ARN (the ARN without `-` and the
+/// 6 random characters Secrets Manager appends) of a secret.
+fn is_secret_id_of(secret_id: &str, name: Option<&str>, arn: Option<&str>) -> bool {
+    name == Some(secret_id)
+        || arn.is_some_and(|arn| {
+            arn == secret_id
+                || arn
+                    .rsplit_once('-')
+                    .is_some_and(|(partial, suffix)| suffix.len() == 6 && partial == secret_id)
+        })
+}
+
+fn secret_binary_only() -> MissingReason {
+    MissingReason::Failed("SecretString is not set".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{Matcher, Server};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_secrets_by_tag() {
+        let mut server = Server::new_async().await;
+        let second_page = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "secretsmanager.ListSecrets")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"NextToken":"page-2"}"#.to_string(),
+            ))
+            .with_body(
+                r#"{"SecretList":[
+                    {"Name":"prod/api-key","Tags":[{"Key":"env","Value":"prod"}]},
+                    {"Name":"prod/cert","Tags":[{"Key":"env","Value":"prod"}]}
+                ]}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        // タグのキーと値が別々のタグで一致するシークレットは含めない
+        let first_page = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "secretsmanager.ListSecrets")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"Filters":[
+                    {"Key":"tag-key","Values":["env"]},
+                    {"Key":"tag-value","Values":["prod"]}
+                ]}"#
+                .to_string(),
+            ))
+            .with_body(
+                r#"{"SecretList":[
+                    {"Name":"prod/db","Tags":[{"Key":"env","Value":"prod"}]},
+                    {"Name":"dev/db","Tags":[
+                        {"Key":"env","Value":"dev"},{"Key":"team","Value":"prod"}
+                    ]}
+                ],"NextToken":"page-2"}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        for (name, body) in [
+            (
+                "prod/db",
+                r#"{"Name":"prod/db","SecretString":"db-password"}"#,
+            ),
+            (
+                "prod/api-key",
+                r#"{"Name":"prod/api-key","SecretString":"key"}"#,
+            ),
+            // SecretBinary だけのシークレットは含めない
+            ("prod/cert", r#"{"Name":"prod/cert","SecretBinary":"AAEC"}"#),
+        ] {
+            server
+                .mock("POST", "/")
+                .match_header("x-amz-target", "secretsmanager.GetSecretValue")
+                .match_body(Matcher::PartialJsonString(format!(
+                    r#"{{"SecretId":"{name}"}}"#
+                )))
+                .with_body(body)
+                .expect(1)
+                .create_async()
+                .await;
+        }
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let names = list_secret_names_by_tag(&client, "env", "prod")
+            .await
+            .unwrap();
+        assert_eq!(names, vec!["prod/db", "prod/api-key", "prod/cert"]);
+        let secrets = get_secrets_by_tag(&client, "env", "prod").await.unwrap();
+        assert_eq!(
+            secrets,
+            HashMap::from([
+                ("prod/db".to_string(), "db-password".to_string()),
+                ("prod/api-key".to_string(), "key".to_string()),
+            ])
+        );
+        first_page.assert_async().await;
+        second_page.assert_async().await;
+    }
+
+    #[test]
+    fn test_is_secret_id_of() {
+        let arn = "arn:aws:secretsmanager:ap-northeast-1:123456789012:secret:prod/db-AbCdEf";
+        for secret_id in [
+            "prod/db",
+            arn,
+            "arn:aws:secretsmanager:ap-northeast-1:123456789012:secret:prod/db",
+        ] {
+            assert!(
+                is_secret_id_of(secret_id, Some("prod/db"), Some(arn)),
+                "{secret_id}"
+            );
+        }
+        assert!(!is_secret_id_of("prod", Some("prod/db"), Some(arn)));
+        assert!(!is_secret_id_of(
+            "arn:aws:secretsmanager:ap-northeast-1:123456789012:secret:prod",
+            Some("prod/db"),
+            Some(arn)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_preload() {
+        let mut server = Server::new_async().await;
+        let batch = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "secretsmanager.BatchGetSecretValue")
+            .with_body(
+                r#"{"SecretValues":[
+                    {"Name":"prod/db","ARN":"arn:aws:secretsmanager:ap-northeast-1:123456789012:secret:prod/db-AbCdEf","SecretString":"db-password"},
+                    {"Name":"prod/api-key","ARN":"arn:aws:secretsmanager:ap-northeast-1:123456789012:secret:prod/api-key-GhIjKl","SecretString":"key"},
+                    {"Name":"prod/cert","ARN":"arn:aws:secretsmanager:ap-northeast-1:123456789012:secret:prod/cert-MnOpQr","SecretBinary":"AAEC"}
+                ],"Errors":[
+                    {"SecretId":"prod/kms","ErrorCode":"DecryptionFailure","Message":"KMS key is disabled"}
+                ]}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let missing = preload(
+            &client,
+            [
+                "prod/db",
+                "arn:aws:secretsmanager:ap-northeast-1:123456789012:secret:prod/api-key",
+                "prod/cert",
+                "prod/kms",
+                "prod/unknown",
+            ],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            missing.not_found().collect::<Vec<_>>(),
+            vec!["prod/unknown"]
+        );
+        assert_eq!(
+            missing
+                .keys
+                .iter()
+                .map(|k| k.key.as_str())
+                .collect::<Vec<_>>(),
+            vec!["prod/cert", "prod/kms", "prod/unknown"]
+        );
+        batch.assert_async().await;
+
+        server.reset();
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "secretsmanager.BatchGetSecretValue")
+            .with_body(r#"{"SecretValues":[{"Name":"prod/db","SecretString":"db-password"}]}"#)
+            .create_async()
+            .await;
+        let config = preload(&client, ["prod/db", "prod/db"]).await.unwrap();
+        assert_eq!(config.get("prod/db"), Some("db-password"));
+        assert_eq!(config.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_preload_without_batch_permission() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "secretsmanager.BatchGetSecretValue")
+            .with_status(400)
+            .with_body(
+                r#"{"__type":"AccessDeniedException","message":"not authorized to perform secretsmanager:BatchGetSecretValue"}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        for (secret_id, status, body) in [
+            (
+                "prod/db",
+                200,
+                r#"{"Name":"prod/db","SecretString":"db-password"}"#,
+            ),
+            (
+                "prod/private",
+                400,
+                r#"{"__type":"AccessDeniedException","message":"not authorized"}"#,
+            ),
+            (
+                "prod/unknown",
+                400,
+                r#"{"__type":"ResourceNotFoundException","message":"not found"}"#,
+            ),
+        ] {
+            server
+                .mock("POST", "/")
+                .match_header("x-amz-target", "secretsmanager.GetSecretValue")
+                .match_body(Matcher::PartialJsonString(format!(
+                    r#"{{"SecretId":"{secret_id}"}}"#
+                )))
+                .with_status(status)
+                .with_body(body)
+                .expect(1)
+                .create_async()
+                .await;
+        }
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let missing = preload(&client, ["prod/db", "prod/private", "prod/unknown"])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            missing.forbidden().collect::<Vec<_>>(),
+            vec!["prod/private"]
+        );
+        assert_eq!(
+            missing.not_found().collect::<Vec<_>>(),
+            vec!["prod/unknown"]
+        );
+        assert_eq!(missing.keys.len(), 2);
+    }
+}
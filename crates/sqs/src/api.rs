@@ -0,0 +1,164 @@
+use std::{collections::HashMap, future::Future};
+
+use aws_sdk_sqs::{
+    Client,
+    operation::{
+        delete_message::DeleteMessageOutput, delete_message_batch::DeleteMessageBatchOutput,
+        receive_message::ReceiveMessageOutput, send_message::SendMessageOutput,
+        send_message_batch::SendMessageBatchOutput,
+    },
+    types::{
+        DeleteMessageBatchRequestEntry, MessageAttributeValue, MessageSystemAttributeName,
+        MessageSystemAttributeNameForSends, MessageSystemAttributeValue,
+        SendMessageBatchRequestEntry,
+    },
+};
+
+use crate::{
+    error::Error,
+    sqs::{self, ReceiveOptions},
+};
+
+/// Message operations of [`sqs`], implemented by [`Client`] and by
+/// [`InMemorySqs`](crate::in_memory::InMemorySqs) for unit tests of consumers.
+pub trait SqsApi: Send + Sync {
+    #[allow(clippy::too_many_arguments)]
+    fn receive_message(
+        &self,
+        queue_url: &str,
+        max_number_of_messages: Option<i32>,
+        message_attribute_names: Option<Vec<String>>,
+        message_system_attribute_names: Option<Vec<MessageSystemAttributeName>>,
+        receive_request_attempt_id: Option<String>,
+        visibility_timeout: Option<i32>,
+        wait_time_seconds: Option<i32>,
+    ) -> impl Future<Output = Result<ReceiveMessageOutput, Error>> + Send;
+
+    /// [`receive_message`](Self::receive_message) with [`ReceiveOptions`].
+    fn receive_with(
+        &self,
+        queue_url: &str,
+        options: &ReceiveOptions,
+    ) -> impl Future<Output = Result<ReceiveMessageOutput, Error>> + Send {
+        async move {
+            options.validate()?;
+            let options = options.clone();
+            self.receive_message(
+                queue_url,
+                Some(options.max_number_of_messages),
+                options.message_attribute_names,
+                options.message_system_attribute_names,
+                options.receive_request_attempt_id,
+                options.visibility_timeout,
+                Some(options.wait_time_seconds),
+            )
+            .await
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn send_message(
+        &self,
+        queue_url: &str,
+        message: Option<String>,
+        message_group_id: Option<String>,
+        message_deduplication_id: Option<String>,
+        delay_seconds: Option<i32>,
+        message_attributes: Option<HashMap<String, MessageAttributeValue>>,
+        message_system_attributes: Option<
+            HashMap<MessageSystemAttributeNameForSends, MessageSystemAttributeValue>,
+        >,
+    ) -> impl Future<Output = Result<SendMessageOutput, Error>> + Send;
+
+    fn send_message_batch(
+        &self,
+        queue_url: &str,
+        entries: Vec<SendMessageBatchRequestEntry>,
+    ) -> impl Future<Output = Result<SendMessageBatchOutput, Error>> + Send;
+
+    fn delete_message(
+        &self,
+        queue_url: &str,
+        receipt_handle: &str,
+    ) -> impl Future<Output = Result<DeleteMessageOutput, Error>> + Send;
+
+    fn delete_message_batch(
+        &self,
+        queue_url: &str,
+        entries: Vec<DeleteMessageBatchRequestEntry>,
+    ) -> impl Future<Output = Result<DeleteMessageBatchOutput, Error>> + Send;
+}
+
+impl SqsApi for Client {
+    async fn receive_message(
+        &self,
+        queue_url: &str,
+        max_number_of_messages: Option<i32>,
+        message_attribute_names: Option<Vec<String>>,
+        message_system_attribute_names: Option<Vec<MessageSystemAttributeName>>,
+        receive_request_attempt_id: Option<String>,
+        visibility_timeout: Option<i32>,
+        wait_time_seconds: Option<i32>,
+    ) -> Result<ReceiveMessageOutput, Error> {
+        sqs::receive_message(
+            self,
+            queue_url,
+            max_number_of_messages,
+            message_attribute_names,
+            message_system_attribute_names,
+            receive_request_attempt_id,
+            visibility_timeout,
+            wait_time_seconds,
+        )
+        .await
+    }
+
+    async fn send_message(
+        &self,
+        queue_url: &str,
+        message: Option<String>,
+        message_group_id: Option<String>,
+        message_deduplication_id: Option<String>,
+        delay_seconds: Option<i32>,
+        message_attributes: Option<HashMap<String, MessageAttributeValue>>,
+        message_system_attributes: Option<
+            HashMap<MessageSystemAttributeNameForSends, MessageSystemAttributeValue>,
+        >,
+    ) -> Result<SendMessageOutput, Error> {
+        sqs::send_message(
+            self,
+            queue_url,
+            message,
+            message_group_id,
+            message_deduplication_id,
+            delay_seconds,
+            message_attributes,
+            message_system_attributes,
+        )
+        .await
+    }
+
+    async fn send_message_batch(
+        &self,
+        queue_url: &str,
+        entries: Vec<SendMessageBatchRequestEntry>,
+    ) -> Result<SendMessageBatchOutput, Error> {
+        sqs::send_message_batch(self, queue_url, entries).await
+    }
+
+    async fn delete_message(
+        &self,
+        queue_url: &str,
+        receipt_handle: &str,
+    ) -> Result<DeleteMessageOutput, Error> {
+        sqs::delete_message(self, queue_url, receipt_handle).await
+    }
+
+    async fn delete_message_batch(
+        &self,
+        queue_url: &str,
+        entries: Vec<DeleteMessageBatchRequestEntry>,
+    ) -> Result<DeleteMessageBatchOutput, Error> {
+        sqs::delete_message_batch(self, queue_url, entries).await
+    }
+}
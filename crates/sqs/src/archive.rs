@@ -0,0 +1,385 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use aws_sdk_sqs::types::{DeleteMessageBatchRequestEntry, Message, MessageSystemAttributeName};
+use aws_utils_s3::{aws_sdk_s3, object::put_object};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde_json::{Value, json};
+
+use crate::{api::SqsApi, error::Error};
+
+/// Max size of an archived object.
+const MAX_OBJECT_BYTES: usize = 8 * 1024 * 1024;
+/// Messages stay invisible while their chunk is being filled and uploaded.
+const VISIBILITY_TIMEOUT: i32 = 900;
+/// A chunk is written once its first message was received this long ago, well before the
+/// visibility timeout of its messages expires.
+const MAX_CHUNK_AGE: Duration = Duration::from_secs(300);
+const WAIT_TIME_SECONDS: i32 = 2;
+/// Max entries of DeleteMessageBatch.
+const MAX_BATCH_ENTRIES: usize = 10;
+
+/// Result of [`archive_queue`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    pub messages: usize,
+    pub bytes: u64,
+    /// Keys of the written objects.
+    pub keys: Vec<String>,
+}
+
+/// Receives all messages of the queue and writes them to S3 as NDJSON objects of up to 8 MiB
+/// (`{prefix}{timestamp}-{n}.ndjson`), e.g. before deleting a queue or for incident forensics.
+/// Each line has the message ID, body, system attributes and message attributes (binary values
+/// in base64). Messages are deleted only after the object containing them is written.
+/// A chunk is also written after 5 minutes, so its messages are deleted before their 15 minute
+/// visibility timeout expires on slow queues.
+///
+/// Returns when a receive returns no messages, so messages delayed or in flight for another
+/// consumer are not archived.
+pub async fn archive_queue(
+    client: &impl SqsApi,
+    queue_url: &str,
+    s3_client: &aws_sdk_s3::Client,
+    bucket_name: &str,
+    prefix: &str,
+) -> Result<ArchiveSummary, Error> {
+    let key_prefix = format!("{prefix}{}", chrono::Utc::now().format("%Y%m%dT%H%M%SZ"));
+    let mut summary = ArchiveSummary::default();
+    let mut chunk = String::new();
+    let mut receipt_handles = vec![];
+    let mut chunk_started_at = None;
+    loop {
+        let output = client
+            .receive_message(
+                queue_url,
+                Some(10),
+                Some(vec!["All".to_string()]),
+                Some(vec![MessageSystemAttributeName::All]),
+                None,
+                Some(VISIBILITY_TIMEOUT),
+                Some(WAIT_TIME_SECONDS),
+            )
+            .await?;
+        let messages = output.messages.unwrap_or_default();
+        if messages.is_empty() {
+            break;
+        }
+        let received_at = Instant::now();
+        for message in messages {
+            let mut line = message_to_json(&message).to_string();
+            line.push('\n');
+            // 8 MiB を超える前にそれまでのメッセージを書き出す
+            if !chunk.is_empty() && chunk.len() + line.len() > MAX_OBJECT_BYTES {
+                flush_chunk(
+                    client,
+                    queue_url,
+                    s3_client,
+                    bucket_name,
+                    &key_prefix,
+                    &mut chunk,
+                    &mut receipt_handles,
+                    &mut summary,
+                )
+                .await?;
+                chunk_started_at = None;
+            }
+            chunk_started_at.get_or_insert(received_at);
+            chunk.push_str(&line);
+            receipt_handles.extend(message.receipt_handle);
+            summary.messages += 1;
+        }
+        if chunk.len() >= MAX_OBJECT_BYTES
+            || chunk_started_at.is_some_and(|t| t.elapsed() >= MAX_CHUNK_AGE)
+        {
+            flush_chunk(
+                client,
+                queue_url,
+                s3_client,
+                bucket_name,
+                &key_prefix,
+                &mut chunk,
+                &mut receipt_handles,
+                &mut summary,
+            )
+            .await?;
+            chunk_started_at = None;
+        }
+    }
+    if !chunk.is_empty() {
+        flush_chunk(
+            client,
+            queue_url,
+            s3_client,
+            bucket_name,
+            &key_prefix,
+            &mut chunk,
+            &mut receipt_handles,
+            &mut summary,
+        )
+        .await?;
+    }
+    Ok(summary)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn flush_chunk(
+    client: &impl SqsApi,
+    queue_url: &str,
+    s3_client: &aws_sdk_s3::Client,
+    bucket_name: &str,
+    key_prefix: &str,
+    chunk: &mut String,
+    receipt_handles: &mut Vec<String>,
+    summary: &mut ArchiveSummary,
+) -> Result<(), Error> {
+    let key = format!("{key_prefix}-{:06}.ndjson", summary.keys.len());
+    let body = std::mem::take(chunk);
+    summary.bytes += body.len() as u64;
+    put_object(
+        s3_client,
+        bucket_name,
+        &key,
+        body.into_bytes(),
+        Some("application/x-ndjson"),
+        None::<String>,
+    )
+    .await?;
+    summary.keys.push(key);
+    // S3 に書き込んだメッセージだけを削除する
+    for handles in receipt_handles.chunks(MAX_BATCH_ENTRIES) {
+        let entries = handles
+            .iter()
+            .enumerate()
+            .map(|(i, handle)| {
+                DeleteMessageBatchRequestEntry::builder()
+                    .id(i.to_string())
+                    .receipt_handle(handle)
+                    .build()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let output = client.delete_message_batch(queue_url, entries).await?;
+        if let Some(failed) = output.failed.first() {
+            return Err(Error::ValidationError(format!(
+                "failed to delete an archived message: {} {:?}",
+                failed.code, failed.message
+            )));
+        }
+    }
+    receipt_handles.clear();
+    Ok(())
+}
+
+/// NDJSON line of a message.
+fn message_to_json(message: &Message) -> Value {
+    let attributes = message
+        .attributes()
+        .map(|attributes| {
+            attributes
+                .iter()
+                .map(|(name, value)| (name.as_str().to_string(), Value::from(value.as_str())))
+                .collect::<serde_json::Map<_, _>>()
+        })
+        .unwrap_or_default();
+    let message_attributes = message
+        .message_attributes()
+        .map(|attributes| {
+            attributes
+                .iter()
+                .map(|(name, value)| {
+                    let mut attribute = json!({"data_type": value.data_type()});
+                    if let Some(string_value) = value.string_value() {
+                        attribute["string_value"] = string_value.into();
+                    }
+                    if let Some(binary_value) = value.binary_value() {
+                        attribute["binary_value"] = STANDARD.encode(binary_value.as_ref()).into();
+                    }
+                    (name.clone(), attribute)
+                })
+                .collect::<HashMap<_, _>>()
+        })
+        .unwrap_or_default();
+    json!({
+        "message_id": message.message_id(),
+        "body": message.body(),
+        "attributes": attributes,
+        "message_attributes": message_attributes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_sqs::{
+        primitives::Blob,
+        types::{MessageAttributeValue, MessageSystemAttributeName},
+    };
+
+    use super::*;
+    use crate::in_memory::InMemorySqs;
+
+    const QUEUE_URL: &str = "https://sqs.ap-northeast-1.amazonaws.com/123456789012/orders";
+
+    #[tokio::test]
+    async fn test_archive_queue() {
+        let sqs = InMemorySqs::new();
+        // 受信 2 回分のメッセージ
+        for i in 0..12 {
+            sqs.send_message(
+                QUEUE_URL,
+                Some(format!(r#"{{"order_id":"{i}"}}"#)),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        let mut server = mockito::Server::new_async().await;
+        let put = server
+            .mock(
+                "PUT",
+                mockito::Matcher::Regex(
+                    r"^/my-archive/sqs/orders/\d{8}T\d{6}Z-000000\.ndjson\?".to_string(),
+                ),
+            )
+            .match_body(mockito::Matcher::Regex(
+                r#"order_id\\":\\"0\\"(.|\n)*order_id\\":\\"11\\""#.to_string(),
+            ))
+            .with_status(200)
+            .expect(1)
+            .create_async()
+            .await;
+        let s3_client = aws_utils_s3::make_client(Some(server.url()), None, None).await;
+
+        let summary = archive_queue(&sqs, QUEUE_URL, &s3_client, "my-archive", "sqs/orders/")
+            .await
+            .unwrap();
+        put.assert_async().await;
+        assert_eq!(summary.messages, 12);
+        assert_eq!(summary.keys.len(), 1);
+        assert!(summary.keys[0].starts_with("sqs/orders/"));
+        assert!(summary.bytes > 0);
+        assert_eq!(sqs.message_count(QUEUE_URL), 0);
+    }
+
+    #[tokio::test]
+    async fn test_archive_queue_splits_objects_before_max_size() {
+        let sqs = InMemorySqs::new();
+        // 250 KiB x 40 = 約 9.8 MiB
+        for _ in 0..40 {
+            sqs.send_message(
+                QUEUE_URL,
+                Some("x".repeat(250 * 1024)),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        let mut server = mockito::Server::new_async().await;
+        let mut puts = vec![];
+        for n in 0..2 {
+            puts.push(
+                server
+                    .mock(
+                        "PUT",
+                        mockito::Matcher::Regex(format!(
+                            r"^/my-archive/sqs/orders/\d{{8}}T\d{{6}}Z-{n:06}\.ndjson\?"
+                        )),
+                    )
+                    .with_status(200)
+                    .expect(1)
+                    .create_async()
+                    .await,
+            );
+        }
+        let s3_client = aws_utils_s3::make_client(Some(server.url()), None, None).await;
+
+        let summary = archive_queue(&sqs, QUEUE_URL, &s3_client, "my-archive", "sqs/orders/")
+            .await
+            .unwrap();
+        for put in puts {
+            put.assert_async().await;
+        }
+        assert_eq!(summary.messages, 40);
+        assert_eq!(summary.keys.len(), 2);
+        assert!(summary.bytes > MAX_OBJECT_BYTES as u64);
+        assert_eq!(sqs.message_count(QUEUE_URL), 0);
+    }
+
+    #[tokio::test]
+    async fn test_archive_queue_keeps_messages_on_put_error() {
+        let sqs = InMemorySqs::new();
+        sqs.send_message(
+            QUEUE_URL,
+            Some("order".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("PUT", mockito::Matcher::Any)
+            .with_status(403)
+            .with_body("<Error><Code>AccessDenied</Code><Message>denied</Message></Error>")
+            .create_async()
+            .await;
+        let s3_client = aws_utils_s3::make_client(Some(server.url()), None, None).await;
+
+        assert!(
+            archive_queue(&sqs, QUEUE_URL, &s3_client, "my-archive", "sqs/orders/")
+                .await
+                .is_err()
+        );
+        // S3 に書き込めなかったメッセージは削除しない
+        assert_eq!(sqs.message_count(QUEUE_URL), 1);
+    }
+
+    #[test]
+    fn test_message_to_json() {
+        let message = Message::builder()
+            .message_id("m1")
+            .body(r#"{"order_id":"1"}"#)
+            .attributes(MessageSystemAttributeName::ApproximateReceiveCount, "2")
+            .message_attributes(
+                "EventType",
+                MessageAttributeValue::builder()
+                    .data_type("String")
+                    .string_value("order.created")
+                    .build()
+                    .unwrap(),
+            )
+            .message_attributes(
+                "Signature",
+                MessageAttributeValue::builder()
+                    .data_type("Binary")
+                    .binary_value(Blob::new(b"sig".to_vec()))
+                    .build()
+                    .unwrap(),
+            )
+            .build();
+        assert_eq!(
+            message_to_json(&message),
+            json!({
+                "message_id": "m1",
+                "body": r#"{"order_id":"1"}"#,
+                "attributes": {"ApproximateReceiveCount": "2"},
+                "message_attributes": {
+                    "EventType": {"data_type": "String", "string_value": "order.created"},
+                    "Signature": {"data_type": "Binary", "binary_value": "c2ln"},
+                },
+            })
+        );
+    }
+}
@@ -1,3 +1,5 @@
 pub mod create_queue_attribute_builder;
 pub mod delete_message_batch_entries_builder;
 pub mod send_message_batch_entries_builder;
+
+pub mod queue_policy_builder;
@@ -109,6 +109,7 @@ pub struct CreateQueueAttributeBuilder {
     sqs_managed_sse_enabled: Option<bool>,
     fifo_throughput_limit: Option<FifoThroughputLimit>,
     deduplication_scope: Option<DeduplicationScope>,
+    fifo_queue: Option<bool>,
 }
 
 impl Default for CreateQueueAttributeBuilder {
@@ -134,6 +135,7 @@ impl CreateQueueAttributeBuilder {
             sqs_managed_sse_enabled: None,
             fifo_throughput_limit: None,
             deduplication_scope: None,
+            fifo_queue: None,
         }
     }
 
@@ -207,6 +209,48 @@ impl CreateQueueAttributeBuilder {
         self
     }
 
+    /// Sets the FifoQueue attribute. FIFO-only attributes (ContentBasedDeduplication,
+    /// FifoThroughputLimit, DeduplicationScope) are rejected with `false`.
+    pub fn fifo_queue(mut self, value: bool) -> Self {
+        self.fifo_queue = Some(value);
+        self
+    }
+
+    /// Builds the attributes of the queue named `queue_name`. A name with the `.fifo` suffix
+    /// is a FIFO queue, and FifoQueue is set unless `fifo_queue(false)` contradicts it. Other
+    /// names are standard queues, rejecting `fifo_queue(true)` and FIFO-only attributes.
+    pub fn build_for_queue_name(
+        mut self,
+        queue_name: &str,
+    ) -> Result<std::collections::HashMap<QueueAttributeName, String>, Error> {
+        let base_name = queue_name.strip_suffix(".fifo").unwrap_or(queue_name);
+        if base_name.is_empty()
+            || queue_name.len() > 80
+            || !base_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(Error::ValidationError(format!(
+                "QueueName must be 1 to 80 alphanumeric characters, hyphens or underscores: {queue_name}"
+            )));
+        }
+        let is_fifo_name = queue_name.ends_with(".fifo");
+        match (is_fifo_name, self.fifo_queue) {
+            (true, Some(false)) => {
+                return Err(Error::ValidationError(format!(
+                    "FifoQueue must be true for a queue name with the .fifo suffix: {queue_name}"
+                )));
+            }
+            (false, Some(true)) => {
+                return Err(Error::ValidationError(format!(
+                    "The name of a FIFO queue must end with the .fifo suffix: {queue_name}"
+                )));
+            }
+            _ => self.fifo_queue = Some(is_fifo_name),
+        }
+        self.build()
+    }
+
     pub fn build(self) -> Result<std::collections::HashMap<QueueAttributeName, String>, Error> {
         // Validate all attributes
         if let Some(value) = self.delay_seconds
@@ -292,6 +336,23 @@ impl CreateQueueAttributeBuilder {
             }
         }
 
+        // Validate FIFO-only attributes of a standard queue
+        if self.fifo_queue == Some(false) {
+            let fifo_only = [
+                (
+                    "ContentBasedDeduplication",
+                    self.content_based_deduplication.is_some(),
+                ),
+                ("FifoThroughputLimit", self.fifo_throughput_limit.is_some()),
+                ("DeduplicationScope", self.deduplication_scope.is_some()),
+            ];
+            if let Some((name, _)) = fifo_only.iter().find(|(_, is_set)| *is_set) {
+                return Err(Error::ValidationError(format!(
+                    "{name} is only valid for FIFO queues."
+                )));
+            }
+        }
+
         // Validate FifoThroughputLimit and DeduplicationScope combination
         if let Some(ref fifo_limit) = self.fifo_throughput_limit
             && matches!(fifo_limit, FifoThroughputLimit::PerMessageGroupId)
@@ -310,6 +371,11 @@ impl CreateQueueAttributeBuilder {
 
         let mut attributes = std::collections::HashMap::new();
 
+        // 標準キューは FifoQueue を指定しない (false を指定すると CreateQueue が失敗する)
+        if self.fifo_queue == Some(true) {
+            attributes.insert(QueueAttributeName::FifoQueue, "true".to_string());
+        }
+
         if let Some(value) = self.delay_seconds {
             attributes.insert(QueueAttributeName::DelaySeconds, value.to_string());
         }
@@ -386,3 +452,65 @@ impl CreateQueueAttributeBuilder {
         Ok(attributes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fifo_queue() {
+        let attributes = CreateQueueAttributeBuilder::new()
+            .content_based_deduplication(true)
+            .fifo_throughput_limit(FifoThroughputLimit::PerMessageGroupId)
+            .deduplication_scope(DeduplicationScope::MessageGroup)
+            .build_for_queue_name("orders.fifo")
+            .unwrap();
+        assert_eq!(attributes[&QueueAttributeName::FifoQueue], "true");
+        assert_eq!(
+            attributes[&QueueAttributeName::FifoThroughputLimit],
+            "perMessageGroupId"
+        );
+
+        let attributes = CreateQueueAttributeBuilder::new()
+            .visibility_timeout(60)
+            .build_for_queue_name("orders")
+            .unwrap();
+        assert!(!attributes.contains_key(&QueueAttributeName::FifoQueue));
+
+        let result = CreateQueueAttributeBuilder::new()
+            .fifo_queue(true)
+            .build_for_queue_name("orders");
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+        let result = CreateQueueAttributeBuilder::new()
+            .fifo_queue(false)
+            .build_for_queue_name("orders.fifo");
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+        let result = CreateQueueAttributeBuilder::new().build_for_queue_name("orders/v2");
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_fifo_only_attributes() {
+        let result = CreateQueueAttributeBuilder::new()
+            .content_based_deduplication(true)
+            .build_for_queue_name("orders");
+        assert!(
+            matches!(result, Err(Error::ValidationError(m)) if m.contains("ContentBasedDeduplication"))
+        );
+        let result = CreateQueueAttributeBuilder::new()
+            .fifo_queue(false)
+            .deduplication_scope(DeduplicationScope::Queue)
+            .build();
+        assert!(
+            matches!(result, Err(Error::ValidationError(m)) if m.contains("DeduplicationScope"))
+        );
+
+        // fifo_queue を指定しない場合は従来通り検証しない
+        assert!(
+            CreateQueueAttributeBuilder::new()
+                .content_based_deduplication(true)
+                .build()
+                .is_ok()
+        );
+    }
+}
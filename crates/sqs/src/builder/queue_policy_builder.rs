@@ -0,0 +1,271 @@
+use serde_json::{Value, json};
+
+use crate::error::Error;
+
+enum Grant {
+    SnsTopic(String),
+    S3Bucket {
+        bucket_arn: String,
+        source_account: Option<String>,
+    },
+    Accounts(Vec<String>),
+}
+
+/// Builder of the access policy of a queue, set with
+/// [`CreateQueueAttributeBuilder::policy`](super::create_queue_attribute_builder::CreateQueueAttributeBuilder::policy).
+///
+/// Each grant is a statement allowing `sqs:SendMessage` on the queue.
+pub struct QueuePolicyBuilder {
+    queue_arn: String,
+    grants: Vec<Grant>,
+}
+
+impl QueuePolicyBuilder {
+    pub fn new(queue_arn: impl Into<String>) -> Self {
+        Self {
+            queue_arn: queue_arn.into(),
+            grants: Vec::new(),
+        }
+    }
+
+    /// Allows the SNS topic to deliver messages to the queue.
+    pub fn allow_sns_topic(mut self, topic_arn: impl Into<String>) -> Self {
+        self.grants.push(Grant::SnsTopic(topic_arn.into()));
+        self
+    }
+
+    /// Allows event notifications of the S3 bucket. `source_account` is the account owning
+    /// the bucket, which prevents a bucket re-created by another account from sending.
+    pub fn allow_s3_bucket(
+        mut self,
+        bucket_arn: impl Into<String>,
+        source_account: Option<impl Into<String>>,
+    ) -> Self {
+        self.grants.push(Grant::S3Bucket {
+            bucket_arn: bucket_arn.into(),
+            source_account: source_account.map(Into::into),
+        });
+        self
+    }
+
+    /// Allows the principals of the accounts (12 digit IDs) to send messages.
+    pub fn allow_accounts(
+        mut self,
+        account_ids: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.grants.push(Grant::Accounts(
+            account_ids.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    pub fn build(self) -> Result<String, Error> {
+        let queue = parse_arn(&self.queue_arn, "sqs")?;
+        if !is_account_id(queue.account) || queue.region.is_empty() {
+            return Err(invalid_arn("sqs", &self.queue_arn));
+        }
+        if self.grants.is_empty() {
+            return Err(Error::ValidationError(
+                "QueuePolicyBuilder requires at least one statement.".to_string(),
+            ));
+        }
+
+        let mut statements = Vec::with_capacity(self.grants.len());
+        for (i, grant) in self.grants.into_iter().enumerate() {
+            let statement = match grant {
+                Grant::SnsTopic(topic_arn) => {
+                    let topic = parse_arn(&topic_arn, "sns")?;
+                    if !is_account_id(topic.account) || topic.region.is_empty() {
+                        return Err(invalid_arn("sns", &topic_arn));
+                    }
+                    json!({
+                        "Sid": format!("AllowSnsTopic{i}"),
+                        "Effect": "Allow",
+                        "Principal": { "Service": "sns.amazonaws.com" },
+                        "Action": "sqs:SendMessage",
+                        "Resource": self.queue_arn,
+                        "Condition": { "ArnEquals": { "aws:SourceArn": topic_arn } }
+                    })
+                }
+                Grant::S3Bucket {
+                    bucket_arn,
+                    source_account,
+                } => {
+                    // バケットの ARN にはリージョンとアカウントが含まれない
+                    let bucket = parse_arn(&bucket_arn, "s3")?;
+                    if !bucket.region.is_empty()
+                        || !bucket.account.is_empty()
+                        || bucket.resource.contains('/')
+                    {
+                        return Err(invalid_arn("s3", &bucket_arn));
+                    }
+                    let mut condition = json!({ "ArnLike": { "aws:SourceArn": bucket_arn } });
+                    if let Some(account) = source_account {
+                        if !is_account_id(&account) {
+                            return Err(invalid_account_id(&account));
+                        }
+                        condition["StringEquals"] = json!({ "aws:SourceAccount": account });
+                    }
+                    json!({
+                        "Sid": format!("AllowS3Bucket{i}"),
+                        "Effect": "Allow",
+                        "Principal": { "Service": "s3.amazonaws.com" },
+                        "Action": "sqs:SendMessage",
+                        "Resource": self.queue_arn,
+                        "Condition": condition
+                    })
+                }
+                Grant::Accounts(account_ids) => {
+                    if account_ids.is_empty() {
+                        return Err(Error::ValidationError(
+                            "account_ids cannot be empty.".to_string(),
+                        ));
+                    }
+                    let principals = account_ids
+                        .iter()
+                        .map(|account| {
+                            if is_account_id(account) {
+                                Ok(Value::String(format!(
+                                    "arn:{}:iam::{account}:root",
+                                    queue.partition
+                                )))
+                            } else {
+                                Err(invalid_account_id(account))
+                            }
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    json!({
+                        "Sid": format!("AllowAccounts{i}"),
+                        "Effect": "Allow",
+                        "Principal": { "AWS": principals },
+                        "Action": "sqs:SendMessage",
+                        "Resource": self.queue_arn
+                    })
+                }
+            };
+            statements.push(statement);
+        }
+
+        let policy = json!({
+            "Version": "2012-10-17",
+            "Statement": statements
+        });
+        serde_json::to_string(&policy).map_err(|e| Error::ValidationError(e.to_string()))
+    }
+}
+
+struct Arn<'a> {
+    partition: &'a str,
+    region: &'a str,
+    account: &'a str,
+    resource: &'a str,
+}
+
+fn parse_arn<'a>(arn: &'a str, service: &str) -> Result<Arn<'a>, Error> {
+    let parts: Vec<&str> = arn.splitn(6, ':').collect();
+    match parts.as_slice() {
+        ["arn", partition, s, region, account, resource]
+            if !partition.is_empty() && *s == service && !resource.is_empty() =>
+        {
+            Ok(Arn {
+                partition,
+                region,
+                account,
+                resource,
+            })
+        }
+        _ => Err(invalid_arn(service, arn)),
+    }
+}
+
+fn is_account_id(account: &str) -> bool {
+    account.len() == 12 && account.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn invalid_arn(service: &str, arn: &str) -> Error {
+    Error::ValidationError(format!("Invalid {service} ARN: {arn}"))
+}
+
+fn invalid_account_id(account: &str) -> Error {
+    Error::ValidationError(format!("Invalid account ID: {account}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUEUE_ARN: &str = "arn:aws:sqs:ap-northeast-1:123456789012:my-queue";
+
+    #[test]
+    fn test_sns_and_s3() {
+        let policy = QueuePolicyBuilder::new(QUEUE_ARN)
+            .allow_sns_topic("arn:aws:sns:ap-northeast-1:123456789012:my-topic")
+            .allow_s3_bucket("arn:aws:s3:::my-bucket", Some("123456789012"))
+            .build()
+            .unwrap();
+        let policy: Value = serde_json::from_str(&policy).unwrap();
+
+        assert_eq!(policy["Version"], "2012-10-17");
+        let statements = policy["Statement"].as_array().unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0]["Principal"]["Service"], "sns.amazonaws.com");
+        assert_eq!(statements[0]["Resource"], QUEUE_ARN);
+        assert_eq!(
+            statements[0]["Condition"]["ArnEquals"]["aws:SourceArn"],
+            "arn:aws:sns:ap-northeast-1:123456789012:my-topic"
+        );
+        assert_eq!(
+            statements[1]["Condition"]["StringEquals"]["aws:SourceAccount"],
+            "123456789012"
+        );
+    }
+
+    #[test]
+    fn test_accounts() {
+        let policy = QueuePolicyBuilder::new(QUEUE_ARN)
+            .allow_accounts(["111111111111", "222222222222"])
+            .build()
+            .unwrap();
+        let policy: Value = serde_json::from_str(&policy).unwrap();
+
+        assert_eq!(
+            policy["Statement"][0]["Principal"]["AWS"],
+            json!([
+                "arn:aws:iam::111111111111:root",
+                "arn:aws:iam::222222222222:root"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_invalid_arns() {
+        let result = QueuePolicyBuilder::new("my-queue")
+            .allow_accounts(["111111111111"])
+            .build();
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+
+        let result = QueuePolicyBuilder::new(QUEUE_ARN)
+            .allow_sns_topic("arn:aws:sqs:ap-northeast-1:123456789012:my-topic")
+            .build();
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+
+        let result = QueuePolicyBuilder::new(QUEUE_ARN)
+            .allow_s3_bucket(
+                "arn:aws:s3:ap-northeast-1:123456789012:my-bucket",
+                None::<String>,
+            )
+            .build();
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+
+        let result = QueuePolicyBuilder::new(QUEUE_ARN)
+            .allow_accounts(["12345"])
+            .build();
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_empty() {
+        let result = QueuePolicyBuilder::new(QUEUE_ARN).build();
+        assert!(matches!(result, Err(Error::ValidationError(_))));
+    }
+}
@@ -0,0 +1,288 @@
+use std::{collections::HashMap, fmt::Display};
+
+use aws_sdk_sqs::types::{Message, MessageAttributeValue, MessageSystemAttributeName};
+use serde::de::DeserializeOwned;
+
+use crate::{api::SqsApi, message::MessageExt, sqs::Error};
+
+/// Message attribute added to messages forwarded to a dead-letter queue.
+pub const POISON_MESSAGE_ERROR_ATTRIBUTE: &str = "PoisonMessageError";
+
+/// Maximum number of message attributes allowed on a single message.
+const MAX_MESSAGE_ATTRIBUTES: usize = 10;
+
+/// What to do with a message whose body repeatedly fails to parse.
+#[derive(Debug, Clone)]
+pub enum PoisonMessagePolicy {
+    /// Leave the message in the queue; it is retried until the queue's redrive policy applies.
+    Retry,
+    /// Once the message was received `max_receive_count` times, forward it to
+    /// `queue_url` and delete it from the source queue.
+    DeadLetter {
+        queue_url: String,
+        max_receive_count: u32,
+    },
+    /// Once the message was received `max_receive_count` times, delete it.
+    Delete { max_receive_count: u32 },
+}
+
+pub struct TypedMessage<T> {
+    pub body: T,
+    pub message: Message,
+}
+
+impl<T> TypedMessage<T> {
+    pub fn receipt_handle(&self) -> Option<&str> {
+        self.message.receipt_handle()
+    }
+
+    pub fn message_id(&self) -> Option<&str> {
+        self.message.message_id()
+    }
+
+    pub fn message_attributes(&self) -> Option<&HashMap<String, MessageAttributeValue>> {
+        self.message.message_attributes()
+    }
+}
+
+/// Receives messages and parses each body with `parse`.
+///
+/// All message attributes and system attributes are requested, so they can be read
+/// from `message` with [`MessageExt`].
+///
+/// Messages that fail to parse are not returned. They are handled according to
+/// `policy`, so a poison message does not block a FIFO message group forever.
+/// `parse` can be `|s| serde_json::from_str(s)` or `|s| T::try_from(s.to_string())`.
+#[allow(clippy::too_many_arguments)]
+pub async fn receive_typed<T, E>(
+    client: &impl SqsApi,
+    queue_url: impl Into<String>,
+    max_number_of_messages: Option<i32>,
+    visibility_timeout: Option<i32>,
+    wait_time_seconds: Option<i32>,
+    policy: &PoisonMessagePolicy,
+    parse: impl Fn(&str) -> Result<T, E>,
+) -> Result<Vec<TypedMessage<T>>, Error>
+where
+    E: Display,
+{
+    let queue_url = queue_url.into();
+    let output = client
+        .receive_message(
+            &queue_url,
+            max_number_of_messages,
+            Some(vec!["All".to_string()]),
+            Some(vec![MessageSystemAttributeName::All]),
+            None,
+            visibility_timeout,
+            wait_time_seconds,
+        )
+        .await?;
+
+    let mut messages = vec![];
+    for message in output.messages.unwrap_or_default() {
+        match parse(message.body().unwrap_or_default()) {
+            Ok(body) => messages.push(TypedMessage { body, message }),
+            Err(e) => handle_poison_message(client, &queue_url, message, policy, e).await?,
+        }
+    }
+    Ok(messages)
+}
+
+/// [`receive_typed`] with the body deserialized from JSON.
+pub async fn receive_json<T>(
+    client: &impl SqsApi,
+    queue_url: impl Into<String>,
+    max_number_of_messages: Option<i32>,
+    visibility_timeout: Option<i32>,
+    wait_time_seconds: Option<i32>,
+    policy: &PoisonMessagePolicy,
+) -> Result<Vec<TypedMessage<T>>, Error>
+where
+    T: DeserializeOwned,
+{
+    receive_typed(
+        client,
+        queue_url,
+        max_number_of_messages,
+        visibility_timeout,
+        wait_time_seconds,
+        policy,
+        |body| serde_json::from_str(body),
+    )
+    .await
+}
+
+async fn handle_poison_message(
+    client: &impl SqsApi,
+    queue_url: &str,
+    message: Message,
+    policy: &PoisonMessagePolicy,
+    error: impl Display,
+) -> Result<(), Error> {
+    let max_receive_count = match policy {
+        PoisonMessagePolicy::Retry => return Ok(()),
+        PoisonMessagePolicy::DeadLetter {
+            max_receive_count, ..
+        }
+        | PoisonMessagePolicy::Delete { max_receive_count } => *max_receive_count,
+    };
+    if message.approximate_receive_count().unwrap_or(1) < max_receive_count {
+        return Ok(());
+    }
+    let Some(receipt_handle) = message.receipt_handle() else {
+        return Ok(());
+    };
+
+    if let PoisonMessagePolicy::DeadLetter {
+        queue_url: dead_letter_queue_url,
+        ..
+    } = policy
+    {
+        forward_to_dead_letter(client, dead_letter_queue_url, &message, error).await?;
+    }
+    client.delete_message(queue_url, receipt_handle).await?;
+    Ok(())
+}
+
+/// Sends a copy of `message` to `dead_letter_queue_url` with `error` in the
+/// [`POISON_MESSAGE_ERROR_ATTRIBUTE`] attribute. The message is not deleted.
+pub(crate) async fn forward_to_dead_letter(
+    client: &impl SqsApi,
+    dead_letter_queue_url: &str,
+    message: &Message,
+    error: impl Display,
+) -> Result<(), Error> {
+    let mut attributes: HashMap<String, MessageAttributeValue> =
+        message.message_attributes().cloned().unwrap_or_default();
+    if attributes.len() < MAX_MESSAGE_ATTRIBUTES {
+        attributes.insert(
+            POISON_MESSAGE_ERROR_ATTRIBUTE.to_string(),
+            MessageAttributeValue::builder()
+                .data_type("String")
+                .string_value(error.to_string())
+                .build()?,
+        );
+    }
+    // FIFO キューへ転送する場合は MessageGroupId と重複排除 ID が必要
+    let (message_group_id, message_deduplication_id) = if dead_letter_queue_url.ends_with(".fifo") {
+        (
+            Some(message.message_group_id().unwrap_or("poison").to_string()),
+            message.message_id().map(ToString::to_string),
+        )
+    } else {
+        (None, None)
+    };
+    client
+        .send_message(
+            dead_letter_queue_url,
+            message.body().map(ToString::to_string),
+            message_group_id,
+            message_deduplication_id,
+            None,
+            Some(attributes),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::in_memory::InMemorySqs;
+
+    const QUEUE_URL: &str = "https://sqs.ap-northeast-1.amazonaws.com/123456789012/orders";
+    const DEAD_LETTER_QUEUE_URL: &str =
+        "https://sqs.ap-northeast-1.amazonaws.com/123456789012/orders-dlq";
+
+    #[tokio::test]
+    async fn test_poison_message_dead_letter() {
+        let sqs = InMemorySqs::new();
+        for body in ["not json", r#"{"id":1}"#] {
+            sqs.send_message(
+                QUEUE_URL,
+                Some(body.to_string()),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        let policy = PoisonMessagePolicy::DeadLetter {
+            queue_url: DEAD_LETTER_QUEUE_URL.to_string(),
+            max_receive_count: 2,
+        };
+
+        // 1 回目の受信では受信回数が閾値未満なのでキューに残す
+        let messages = receive_json::<Value>(&sqs, QUEUE_URL, Some(10), Some(0), None, &policy)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].body["id"], 1);
+        assert_eq!(sqs.message_count(DEAD_LETTER_QUEUE_URL), 0);
+        sqs.delete_message(QUEUE_URL, messages[0].receipt_handle().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(sqs.bodies(QUEUE_URL), vec!["not json".to_string()]);
+
+        let messages = receive_json::<Value>(&sqs, QUEUE_URL, Some(10), Some(0), None, &policy)
+            .await
+            .unwrap();
+        assert!(messages.is_empty());
+        assert_eq!(sqs.message_count(QUEUE_URL), 0);
+        let forwarded = sqs
+            .receive_message(
+                DEAD_LETTER_QUEUE_URL,
+                None,
+                Some(vec!["All".to_string()]),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap()
+            .messages
+            .unwrap_or_default();
+        assert_eq!(forwarded.len(), 1);
+        assert_eq!(forwarded[0].body(), Some("not json"));
+        assert!(
+            forwarded[0]
+                .message_attributes()
+                .and_then(|a| a.get(POISON_MESSAGE_ERROR_ATTRIBUTE))
+                .and_then(|a| a.string_value())
+                .is_some_and(|error| error.contains("expected ident"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_poison_message_delete() {
+        let sqs = InMemorySqs::new();
+        sqs.send_message(
+            QUEUE_URL,
+            Some("not json".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let policy = PoisonMessagePolicy::Delete {
+            max_receive_count: 1,
+        };
+
+        let messages = receive_json::<Value>(&sqs, QUEUE_URL, Some(10), Some(0), None, &policy)
+            .await
+            .unwrap();
+        assert!(messages.is_empty());
+        assert_eq!(sqs.message_count(QUEUE_URL), 0);
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use aws_sdk_sqs::Client;
+#[cfg(feature = "scheduler")]
+use aws_utils_scheduler::{
+    aws_sdk_scheduler::{
+        self,
+        types::{ActionAfterCompletion, FlexibleTimeWindow, FlexibleTimeWindowMode, Target},
+    },
+    builder::schedule_expression_builder::AtExpressionBuilder,
+};
+use chrono::{DateTime, Utc};
+
+use crate::{error::Error, sqs};
+
+/// Max DelaySeconds of SQS.
+pub const MAX_DELAY: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DelayedMessage {
+    /// Sent to the queue with DelaySeconds.
+    Sent { message_id: Option<String> },
+    /// One-shot EventBridge schedule sending the message, deleted after it runs.
+    Scheduled {
+        schedule_name: String,
+        send_at: DateTime<Utc>,
+    },
+}
+
+#[cfg(feature = "scheduler")]
+#[derive(Clone)]
+struct ScheduleFallback {
+    client: aws_sdk_scheduler::Client,
+    queue_arn: String,
+    role_arn: String,
+    group_name: Option<String>,
+}
+
+/// Sender of deferred messages to a queue. Delays up to [`MAX_DELAY`] use DelaySeconds,
+/// and longer delays a one-shot EventBridge schedule if
+/// [`schedule_fallback`](Self::schedule_fallback) is set (feature `scheduler`).
+#[derive(Clone)]
+pub struct DelayedSender {
+    client: Client,
+    queue_url: String,
+    #[cfg(feature = "scheduler")]
+    fallback: Option<ScheduleFallback>,
+}
+
+impl DelayedSender {
+    pub fn new(client: Client, queue_url: impl Into<String>) -> Self {
+        Self {
+            client,
+            queue_url: queue_url.into(),
+            #[cfg(feature = "scheduler")]
+            fallback: None,
+        }
+    }
+
+    /// Sends messages delayed longer than [`MAX_DELAY`] with schedules in `group_name`
+    /// (the default group if None). `role_arn` must allow the scheduler to call
+    /// `sqs:SendMessage` on the queue of `queue_arn`.
+    ///
+    /// A schedule fires within a minute of its time, and only standard queues are supported.
+    #[cfg(feature = "scheduler")]
+    pub fn schedule_fallback(
+        mut self,
+        scheduler_client: aws_sdk_scheduler::Client,
+        queue_arn: impl Into<String>,
+        role_arn: impl Into<String>,
+        group_name: Option<impl Into<String>>,
+    ) -> Self {
+        self.fallback = Some(ScheduleFallback {
+            client: scheduler_client,
+            queue_arn: queue_arn.into(),
+            role_arn: role_arn.into(),
+            group_name: group_name.map(Into::into),
+        });
+        self
+    }
+
+    pub fn queue_url(&self) -> &str {
+        &self.queue_url
+    }
+
+    pub async fn send_delayed(
+        &self,
+        message: impl Into<String>,
+        delay: Duration,
+    ) -> Result<DelayedMessage, Error> {
+        if delay <= MAX_DELAY {
+            let output = sqs::send_message(
+                &self.client,
+                &self.queue_url,
+                Some(message.into()),
+                None,
+                None,
+                Some(delay.as_secs() as i32),
+                None,
+                None,
+            )
+            .await?;
+            return Ok(DelayedMessage::Sent {
+                message_id: output.message_id,
+            });
+        }
+        #[cfg(feature = "scheduler")]
+        if let Some(fallback) = &self.fallback {
+            return fallback.schedule(message.into(), delay).await;
+        }
+        Err(Error::ValidationError(format!(
+            "delay {delay:?} is over 15 minutes without schedule_fallback"
+        )))
+    }
+}
+
+#[cfg(feature = "scheduler")]
+impl ScheduleFallback {
+    async fn schedule(&self, message: String, delay: Duration) -> Result<DelayedMessage, Error> {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+        let now = Utc::now();
+        let send_at = now
+            + chrono::Duration::from_std(delay)
+                .map_err(|e| Error::ValidationError(e.to_string()))?;
+        // スケジュール名は 64 文字以内
+        let schedule_name = format!(
+            "sqs-delayed-{}-{}",
+            now.timestamp_nanos_opt().unwrap_or_default(),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        );
+        let target = Target::builder()
+            .arn(&self.queue_arn)
+            .role_arn(&self.role_arn)
+            .input(message)
+            .build()
+            .map_err(aws_utils_scheduler::error::Error::from)?;
+        let flexible_time_window = FlexibleTimeWindow::builder()
+            .mode(FlexibleTimeWindowMode::Off)
+            .build()
+            .map_err(aws_utils_scheduler::error::Error::from)?;
+        aws_utils_scheduler::scheduler::create_schedule(
+            &self.client,
+            &schedule_name,
+            self.group_name.as_ref(),
+            AtExpressionBuilder::new().datetime(send_at).build()?,
+            None,
+            None,
+            Some(format!("Delayed message to {}", self.queue_arn)),
+            None::<String>,
+            None,
+            None::<String>,
+            Some(target),
+            Some(flexible_time_window),
+            None::<String>,
+            Some(ActionAfterCompletion::Delete),
+        )
+        .await?;
+        Ok(DelayedMessage::Scheduled {
+            schedule_name,
+            send_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_send_delayed_without_fallback() {
+        let client = Client::from_conf(
+            aws_sdk_sqs::Config::builder()
+                .behavior_version_latest()
+                .build(),
+        );
+        let sender = DelayedSender::new(client, "https://sqs.example.com/123456789012/queue");
+        assert!(matches!(
+            sender
+                .send_delayed("hello", Duration::from_secs(16 * 60))
+                .await,
+            Err(Error::ValidationError(_))
+        ));
+    }
+}
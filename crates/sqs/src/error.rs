@@ -1,3 +1,6 @@
+use aws_sdk_sqs::{error::ProvideErrorMetadata, operation::RequestId};
+pub use aws_utils_core::error::AwsUtilError;
+use aws_utils_core::error::is_retryable_sdk_error;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,8 +13,72 @@ pub enum Error {
 
     #[error("ValidationError: {0}")]
     ValidationError(String),
+
+    /// A request of `RateLimitedSender::send_message_batch` failed after the earlier requests
+    /// of the batch were sent. `sent` holds their results.
+    #[error("PartialBatch: {} entries were sent before {source}", sent.successful.len() + sent.failed.len())]
+    PartialBatch {
+        sent: Box<aws_sdk_sqs::operation::send_message_batch::SendMessageBatchOutput>,
+        source: Box<Error>,
+    },
+
+    #[cfg(feature = "scheduler")]
+    #[error(transparent)]
+    Scheduler(#[from] aws_utils_scheduler::error::Error),
+
+    #[cfg(feature = "s3")]
+    #[error(transparent)]
+    S3(#[from] aws_utils_s3::error::Error),
+
+    /// The signature of an SNS message does not match its content or certificate.
+    #[cfg(feature = "sns")]
+    #[error("InvalidSignature: {0}")]
+    InvalidSignature(String),
+
+    /// Fetching the signing certificate or the SubscribeURL of an SNS message failed.
+    #[cfg(feature = "sns")]
+    #[error("Http: {0}")]
+    Http(String),
 }
 
 pub(crate) fn from_aws_sdk_error(e: impl Into<aws_sdk_sqs::Error>) -> Error {
     Error::AwsSdk(Box::new(e.into()))
 }
+
+impl AwsUtilError for Error {
+    fn error_code(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.code(),
+            Error::PartialBatch { source, .. } => source.error_code(),
+            #[cfg(feature = "scheduler")]
+            Error::Scheduler(e) => e.error_code(),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.error_code(),
+            _ => None,
+        }
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.request_id(),
+            Error::PartialBatch { source, .. } => source.aws_request_id(),
+            #[cfg(feature = "scheduler")]
+            Error::Scheduler(e) => e.aws_request_id(),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.aws_request_id(),
+            _ => None,
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::AwsSdk(e) => is_retryable_sdk_error(e.as_ref()),
+            Error::PartialBatch { source, .. } => source.is_retryable(),
+            #[cfg(feature = "scheduler")]
+            Error::Scheduler(e) => e.is_retryable(),
+            #[cfg(feature = "s3")]
+            Error::S3(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,689 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use aws_sdk_sqs::{
+    error::ErrorMetadata,
+    operation::{
+        delete_message::DeleteMessageOutput, delete_message_batch::DeleteMessageBatchOutput,
+        receive_message::ReceiveMessageOutput, send_message::SendMessageOutput,
+        send_message_batch::SendMessageBatchOutput,
+    },
+    types::{
+        BatchResultErrorEntry, DeleteMessageBatchRequestEntry, DeleteMessageBatchResultEntry,
+        Message, MessageAttributeValue, MessageSystemAttributeName,
+        MessageSystemAttributeNameForSends, MessageSystemAttributeValue,
+        SendMessageBatchRequestEntry, SendMessageBatchResultEntry, error::ReceiptHandleIsInvalid,
+    },
+};
+use tokio::time::Instant;
+
+use crate::{
+    api::SqsApi,
+    error::{Error, from_aws_sdk_error},
+};
+
+/// Max entries of a batch request.
+const MAX_BATCH_SIZE: usize = 10;
+
+/// Default visibility timeout of a queue.
+const DEFAULT_VISIBILITY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Max delay of a message.
+const MAX_DELAY_SECONDS: i32 = 900;
+
+/// Interval to check for messages while long polling.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A message that SQS would reject, with the error code of its failed batch entry.
+struct Rejected {
+    code: &'static str,
+    message: String,
+}
+
+impl From<Rejected> for Error {
+    fn from(rejected: Rejected) -> Self {
+        Error::ValidationError(rejected.message)
+    }
+}
+
+struct StoredMessage {
+    message_id: String,
+    body: String,
+    message_attributes: HashMap<String, MessageAttributeValue>,
+    message_group_id: Option<String>,
+    message_deduplication_id: Option<String>,
+    sequence_number: u64,
+    sent_timestamp: i64,
+    first_receive_timestamp: Option<i64>,
+    receive_count: u32,
+    visible_at: Instant,
+    /// The latest receipt handle, the only one that deletes the message.
+    receipt_handle: Option<String>,
+    stale_receipt_handles: HashSet<String>,
+}
+
+#[derive(Default)]
+struct Queue {
+    messages: Vec<StoredMessage>,
+    deduplication_ids: HashMap<String, (String, Instant)>,
+}
+
+#[derive(Default)]
+struct State {
+    queues: HashMap<String, Queue>,
+    sequence: u64,
+}
+
+impl State {
+    fn next(&mut self) -> u64 {
+        self.sequence += 1;
+        self.sequence
+    }
+}
+
+/// In-memory [`SqsApi`] for unit tests of consumers, without ElasticMQ or LocalStack.
+///
+/// Queues are created on first use, keyed by the queue URL, and are FIFO if the URL ends with
+/// `.fifo`. Received messages are invisible for the visibility timeout and can be received
+/// again after it expires, with a new receipt handle. Clones share the queues.
+///
+/// Time is measured with [`tokio::time::Instant`], so `tokio::time::pause` and `advance`
+/// can be used to expire visibility timeouts. MD5 digests are not computed.
+#[derive(Clone)]
+pub struct InMemorySqs {
+    state: Arc<Mutex<State>>,
+    visibility_timeout: Duration,
+}
+
+impl Default for InMemorySqs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InMemorySqs {
+    /// Visibility timeout is 30 seconds by default.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State::default())),
+            visibility_timeout: DEFAULT_VISIBILITY_TIMEOUT,
+        }
+    }
+
+    /// Visibility timeout used when a receive request does not specify it.
+    pub fn visibility_timeout(mut self, visibility_timeout: Duration) -> Self {
+        self.visibility_timeout = visibility_timeout;
+        self
+    }
+
+    /// Number of messages in the queue, including in-flight ones.
+    pub fn message_count(&self, queue_url: &str) -> usize {
+        let state = self.state.lock().expect("lock is never poisoned");
+        state.queues.get(queue_url).map_or(0, |q| q.messages.len())
+    }
+
+    /// Number of received messages within their visibility timeout.
+    pub fn in_flight_count(&self, queue_url: &str) -> usize {
+        let now = Instant::now();
+        let state = self.state.lock().expect("lock is never poisoned");
+        state.queues.get(queue_url).map_or(0, |q| {
+            q.messages
+                .iter()
+                .filter(|m| m.receipt_handle.is_some() && m.visible_at > now)
+                .count()
+        })
+    }
+
+    /// Bodies of the messages in the queue in order, including in-flight ones.
+    pub fn bodies(&self, queue_url: &str) -> Vec<String> {
+        let state = self.state.lock().expect("lock is never poisoned");
+        state.queues.get(queue_url).map_or(vec![], |q| {
+            q.messages.iter().map(|m| m.body.clone()).collect()
+        })
+    }
+
+    /// Returns the message ID.
+    fn enqueue(
+        &self,
+        queue_url: &str,
+        body: String,
+        message_group_id: Option<String>,
+        message_deduplication_id: Option<String>,
+        delay_seconds: Option<i32>,
+        message_attributes: HashMap<String, MessageAttributeValue>,
+    ) -> Result<String, Rejected> {
+        let fifo = queue_url.ends_with(".fifo");
+        if fifo && message_group_id.is_none() {
+            return Err(Rejected {
+                code: "MissingParameter",
+                message: "message_group_id is required for a FIFO queue".to_string(),
+            });
+        }
+        if !(0..=MAX_DELAY_SECONDS).contains(&delay_seconds.unwrap_or_default()) {
+            return Err(Rejected {
+                code: "InvalidParameterValue",
+                message: format!(
+                    "delay_seconds must be between 0 and {MAX_DELAY_SECONDS}: {delay_seconds:?}"
+                ),
+            });
+        }
+        let now = Instant::now();
+        let mut state = self.state.lock().expect("lock is never poisoned");
+        let sequence_number = state.next();
+        let queue = state.queues.entry(queue_url.to_string()).or_default();
+
+        // FIFO キューの重複排除期間は 5 分
+        if let Some(deduplication_id) = message_deduplication_id.as_ref().filter(|_| fifo) {
+            queue
+                .deduplication_ids
+                .retain(|_, (_, sent)| now.duration_since(*sent) < Duration::from_secs(300));
+            if let Some((message_id, _)) = queue.deduplication_ids.get(deduplication_id) {
+                return Ok(message_id.clone());
+            }
+        }
+
+        let message_id = format!("00000000-0000-0000-0000-{sequence_number:012}");
+        if let Some(deduplication_id) = message_deduplication_id.as_ref().filter(|_| fifo) {
+            queue
+                .deduplication_ids
+                .insert(deduplication_id.clone(), (message_id.clone(), now));
+        }
+        let delay = Duration::from_secs(delay_seconds.unwrap_or_default() as u64);
+        queue.messages.push(StoredMessage {
+            message_id: message_id.clone(),
+            body,
+            message_attributes,
+            message_group_id,
+            message_deduplication_id,
+            sequence_number,
+            sent_timestamp: chrono::Utc::now().timestamp_millis(),
+            first_receive_timestamp: None,
+            receive_count: 0,
+            visible_at: now + delay,
+            receipt_handle: None,
+            stale_receipt_handles: HashSet::new(),
+        });
+        Ok(message_id)
+    }
+
+    fn try_receive(
+        &self,
+        queue_url: &str,
+        max_number_of_messages: usize,
+        message_attribute_names: &[String],
+        message_system_attribute_names: &[MessageSystemAttributeName],
+        visibility_timeout: Duration,
+    ) -> Vec<Message> {
+        let fifo = queue_url.ends_with(".fifo");
+        let now = Instant::now();
+        let mut state = self.state.lock().expect("lock is never poisoned");
+        let mut receipt_sequences = (0..max_number_of_messages)
+            .map(|_| state.next())
+            .collect::<Vec<_>>()
+            .into_iter();
+        let Some(queue) = state.queues.get_mut(queue_url) else {
+            return vec![];
+        };
+
+        let mut messages = vec![];
+        // FIFO キューでは処理中のメッセージがあるグループは後続を返さない
+        let mut blocked_groups = HashSet::new();
+        for stored in queue.messages.iter_mut() {
+            if messages.len() >= max_number_of_messages {
+                break;
+            }
+            if stored.visible_at > now {
+                if fifo && stored.receipt_handle.is_some() {
+                    blocked_groups.insert(stored.message_group_id.clone());
+                }
+                continue;
+            }
+            if fifo && blocked_groups.contains(&stored.message_group_id) {
+                continue;
+            }
+            let Some(receipt_sequence) = receipt_sequences.next() else {
+                break;
+            };
+            let receipt_handle = format!("{}#{receipt_sequence}", stored.message_id);
+            if let Some(old) = stored.receipt_handle.replace(receipt_handle.clone()) {
+                stored.stale_receipt_handles.insert(old);
+            }
+            stored.receive_count += 1;
+            stored
+                .first_receive_timestamp
+                .get_or_insert_with(|| chrono::Utc::now().timestamp_millis());
+            stored.visible_at = now + visibility_timeout;
+            messages.push(to_message(
+                stored,
+                receipt_handle,
+                message_attribute_names,
+                message_system_attribute_names,
+            ));
+        }
+        messages
+    }
+
+    fn remove(&self, queue_url: &str, receipt_handle: &str) -> Result<(), Error> {
+        let mut state = self.state.lock().expect("lock is never poisoned");
+        let queue = state.queues.get_mut(queue_url);
+        let Some(queue) = queue else {
+            return Err(receipt_handle_is_invalid(receipt_handle));
+        };
+        if let Some(i) = queue
+            .messages
+            .iter()
+            .position(|m| m.receipt_handle.as_deref() == Some(receipt_handle))
+        {
+            queue.messages.remove(i);
+            return Ok(());
+        }
+        // SQS と同様に古い受信ハンドルでの削除は成功するがメッセージは残る
+        if queue
+            .messages
+            .iter()
+            .any(|m| m.stale_receipt_handles.contains(receipt_handle))
+        {
+            return Ok(());
+        }
+        Err(receipt_handle_is_invalid(receipt_handle))
+    }
+}
+
+fn receipt_handle_is_invalid(receipt_handle: &str) -> Error {
+    let message =
+        format!("The input receipt handle \"{receipt_handle}\" is not a valid receipt handle.");
+    from_aws_sdk_error(aws_sdk_sqs::Error::ReceiptHandleIsInvalid(
+        ReceiptHandleIsInvalid::builder()
+            .message(&message)
+            .meta(
+                ErrorMetadata::builder()
+                    .code("ReceiptHandleIsInvalid")
+                    .message(message)
+                    .build(),
+            )
+            .build(),
+    ))
+}
+
+fn is_requested(names: &[String], name: &str) -> bool {
+    names.iter().any(|n| {
+        n == "All"
+            || n == ".*"
+            || n == name
+            || n.strip_suffix(".*")
+                .is_some_and(|prefix| name.starts_with(&format!("{prefix}.")))
+    })
+}
+
+fn to_message(
+    stored: &StoredMessage,
+    receipt_handle: String,
+    message_attribute_names: &[String],
+    message_system_attribute_names: &[MessageSystemAttributeName],
+) -> Message {
+    let message_attributes = stored
+        .message_attributes
+        .iter()
+        .filter(|(name, _)| is_requested(message_attribute_names, name))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let mut attributes = HashMap::from([
+        (
+            MessageSystemAttributeName::SentTimestamp,
+            stored.sent_timestamp.to_string(),
+        ),
+        (
+            MessageSystemAttributeName::ApproximateReceiveCount,
+            stored.receive_count.to_string(),
+        ),
+    ]);
+    if let Some(first_receive_timestamp) = stored.first_receive_timestamp {
+        attributes.insert(
+            MessageSystemAttributeName::ApproximateFirstReceiveTimestamp,
+            first_receive_timestamp.to_string(),
+        );
+    }
+    if let Some(message_group_id) = &stored.message_group_id {
+        attributes.insert(
+            MessageSystemAttributeName::MessageGroupId,
+            message_group_id.clone(),
+        );
+        attributes.insert(
+            MessageSystemAttributeName::SequenceNumber,
+            stored.sequence_number.to_string(),
+        );
+    }
+    if let Some(message_deduplication_id) = &stored.message_deduplication_id {
+        attributes.insert(
+            MessageSystemAttributeName::MessageDeduplicationId,
+            message_deduplication_id.clone(),
+        );
+    }
+    attributes.retain(|name, _| {
+        message_system_attribute_names
+            .iter()
+            .any(|n| n == name || n == &MessageSystemAttributeName::All)
+    });
+
+    Message::builder()
+        .message_id(&stored.message_id)
+        .receipt_handle(receipt_handle)
+        .body(&stored.body)
+        .set_attributes(Some(attributes).filter(|a| !a.is_empty()))
+        .set_message_attributes(Some(message_attributes).filter(|a| !a.is_empty()))
+        .build()
+}
+
+impl SqsApi for InMemorySqs {
+    async fn receive_message(
+        &self,
+        queue_url: &str,
+        max_number_of_messages: Option<i32>,
+        message_attribute_names: Option<Vec<String>>,
+        message_system_attribute_names: Option<Vec<MessageSystemAttributeName>>,
+        _receive_request_attempt_id: Option<String>,
+        visibility_timeout: Option<i32>,
+        wait_time_seconds: Option<i32>,
+    ) -> Result<ReceiveMessageOutput, Error> {
+        let max_number_of_messages = max_number_of_messages.unwrap_or(1).clamp(1, 10) as usize;
+        let message_attribute_names = message_attribute_names.unwrap_or_default();
+        let message_system_attribute_names = message_system_attribute_names.unwrap_or_default();
+        let visibility_timeout = visibility_timeout
+            .map(|v| Duration::from_secs(v.max(0) as u64))
+            .unwrap_or(self.visibility_timeout);
+        let deadline = Instant::now()
+            + Duration::from_secs(wait_time_seconds.unwrap_or_default().max(0) as u64);
+        loop {
+            let messages = self.try_receive(
+                queue_url,
+                max_number_of_messages,
+                &message_attribute_names,
+                &message_system_attribute_names,
+                visibility_timeout,
+            );
+            if !messages.is_empty() || Instant::now() >= deadline {
+                return Ok(ReceiveMessageOutput::builder()
+                    .set_messages(Some(messages).filter(|m| !m.is_empty()))
+                    .build());
+            }
+            tokio::time::sleep(POLL_INTERVAL.min(deadline - Instant::now())).await;
+        }
+    }
+
+    async fn send_message(
+        &self,
+        queue_url: &str,
+        message: Option<String>,
+        message_group_id: Option<String>,
+        message_deduplication_id: Option<String>,
+        delay_seconds: Option<i32>,
+        message_attributes: Option<HashMap<String, MessageAttributeValue>>,
+        _message_system_attributes: Option<
+            HashMap<MessageSystemAttributeNameForSends, MessageSystemAttributeValue>,
+        >,
+    ) -> Result<SendMessageOutput, Error> {
+        let message_id = self.enqueue(
+            queue_url,
+            message.unwrap_or_default(),
+            message_group_id,
+            message_deduplication_id,
+            delay_seconds,
+            message_attributes.unwrap_or_default(),
+        )?;
+        Ok(SendMessageOutput::builder().message_id(message_id).build())
+    }
+
+    async fn send_message_batch(
+        &self,
+        queue_url: &str,
+        entries: Vec<SendMessageBatchRequestEntry>,
+    ) -> Result<SendMessageBatchOutput, Error> {
+        if entries.len() > MAX_BATCH_SIZE {
+            return Err(Error::ValidationError(format!(
+                "too many entries in a batch: {}",
+                entries.len()
+            )));
+        }
+        let mut successful = vec![];
+        let mut failed = vec![];
+        for entry in entries {
+            let result = self.enqueue(
+                queue_url,
+                entry.message_body,
+                entry.message_group_id,
+                entry.message_deduplication_id,
+                entry.delay_seconds,
+                entry.message_attributes.unwrap_or_default(),
+            );
+            match result {
+                Ok(message_id) => successful.push(
+                    SendMessageBatchResultEntry::builder()
+                        .id(entry.id)
+                        .message_id(message_id)
+                        .md5_of_message_body("")
+                        .build()?,
+                ),
+                Err(rejected) => failed.push(
+                    BatchResultErrorEntry::builder()
+                        .id(entry.id)
+                        .sender_fault(true)
+                        .code(rejected.code)
+                        .message(rejected.message)
+                        .build()?,
+                ),
+            }
+        }
+        Ok(SendMessageBatchOutput::builder()
+            .set_successful(Some(successful))
+            .set_failed(Some(failed))
+            .build()?)
+    }
+
+    async fn delete_message(
+        &self,
+        queue_url: &str,
+        receipt_handle: &str,
+    ) -> Result<DeleteMessageOutput, Error> {
+        self.remove(queue_url, receipt_handle)?;
+        Ok(DeleteMessageOutput::builder().build())
+    }
+
+    async fn delete_message_batch(
+        &self,
+        queue_url: &str,
+        entries: Vec<DeleteMessageBatchRequestEntry>,
+    ) -> Result<DeleteMessageBatchOutput, Error> {
+        if entries.len() > MAX_BATCH_SIZE {
+            return Err(Error::ValidationError(format!(
+                "too many entries in a batch: {}",
+                entries.len()
+            )));
+        }
+        let mut successful = vec![];
+        let mut failed = vec![];
+        for entry in entries {
+            match self.remove(queue_url, &entry.receipt_handle) {
+                Ok(()) => successful.push(
+                    DeleteMessageBatchResultEntry::builder()
+                        .id(entry.id)
+                        .build()?,
+                ),
+                Err(e) => failed.push(
+                    BatchResultErrorEntry::builder()
+                        .id(entry.id)
+                        .sender_fault(true)
+                        .code("ReceiptHandleIsInvalid")
+                        .message(e.to_string())
+                        .build()?,
+                ),
+            }
+        }
+        Ok(DeleteMessageBatchOutput::builder()
+            .set_successful(Some(successful))
+            .set_failed(Some(failed))
+            .build()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::AwsUtilError;
+
+    use super::*;
+
+    const QUEUE_URL: &str = "https://sqs.ap-northeast-1.amazonaws.com/123456789012/jobs";
+
+    async fn receive(sqs: &InMemorySqs, queue_url: &str) -> Vec<Message> {
+        sqs.receive_message(
+            queue_url,
+            Some(10),
+            None,
+            Some(vec![MessageSystemAttributeName::All]),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+        .messages
+        .unwrap_or_default()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_visibility_timeout() {
+        let sqs = InMemorySqs::new().visibility_timeout(Duration::from_secs(10));
+        sqs.send_message(
+            QUEUE_URL,
+            Some("a".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let first = receive(&sqs, QUEUE_URL).await;
+        assert_eq!(first.len(), 1);
+        assert_eq!(sqs.in_flight_count(QUEUE_URL), 1);
+        assert!(receive(&sqs, QUEUE_URL).await.is_empty());
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let second = receive(&sqs, QUEUE_URL).await;
+        assert_eq!(second.len(), 1);
+        assert_eq!(
+            second[0]
+                .attributes()
+                .and_then(|a| a.get(&MessageSystemAttributeName::ApproximateReceiveCount)),
+            Some(&"2".to_string())
+        );
+
+        // 古い受信ハンドルでは削除されない
+        sqs.delete_message(QUEUE_URL, first[0].receipt_handle().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(sqs.message_count(QUEUE_URL), 1);
+        sqs.delete_message(QUEUE_URL, second[0].receipt_handle().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(sqs.message_count(QUEUE_URL), 0);
+
+        let e = sqs.delete_message(QUEUE_URL, "unknown").await.unwrap_err();
+        assert_eq!(e.error_code(), Some("ReceiptHandleIsInvalid"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fifo_group_ordering() {
+        let queue_url = format!("{QUEUE_URL}.fifo");
+        let sqs = InMemorySqs::new();
+        for (body, group) in [("a1", "a"), ("a2", "a"), ("b1", "b")] {
+            sqs.send_message(
+                &queue_url,
+                Some(body.to_string()),
+                Some(group.to_string()),
+                Some(body.to_string()),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        // 重複排除 ID が同じメッセージは追加されない
+        sqs.send_message(
+            &queue_url,
+            Some("a1".to_string()),
+            Some("a".to_string()),
+            Some("a1".to_string()),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(sqs.bodies(&queue_url), vec!["a1", "a2", "b1"]);
+
+        let output = sqs
+            .receive_message(&queue_url, Some(1), None, None, None, None, None)
+            .await
+            .unwrap();
+        let a1 = &output.messages()[0];
+        assert_eq!(a1.body(), Some("a1"));
+
+        // グループ a は処理中なのでグループ b だけ返る
+        let messages = receive(&sqs, &queue_url).await;
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].body(), Some("b1"));
+
+        sqs.delete_message(&queue_url, a1.receipt_handle().unwrap())
+            .await
+            .unwrap();
+        let messages = receive(&sqs, &queue_url).await;
+        assert_eq!(messages[0].body(), Some("a2"));
+    }
+
+    #[tokio::test]
+    async fn test_send_message_batch_failed() {
+        let queue_url = format!("{QUEUE_URL}.fifo");
+        let sqs = InMemorySqs::new();
+        let entry = |id: &str, group: Option<&str>, delay_seconds: Option<i32>| {
+            SendMessageBatchRequestEntry::builder()
+                .id(id)
+                .message_body(id)
+                .set_message_group_id(group.map(ToString::to_string))
+                .set_delay_seconds(delay_seconds)
+                .build()
+                .unwrap()
+        };
+
+        let output = sqs
+            .send_message_batch(
+                &queue_url,
+                vec![
+                    entry("ok", Some("a"), None),
+                    entry("no-group", None, None),
+                    entry("too-late", Some("a"), Some(901)),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.successful().len(), 1);
+        assert_eq!(
+            output
+                .failed()
+                .iter()
+                .map(|f| (f.id(), f.code()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("no-group", "MissingParameter"),
+                ("too-late", "InvalidParameterValue")
+            ]
+        );
+        assert_eq!(sqs.bodies(&queue_url), vec!["ok"]);
+    }
+}
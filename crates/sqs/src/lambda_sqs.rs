@@ -0,0 +1,259 @@
+use std::future::Future;
+
+use aws_lambda_events::event::sqs::{
+    BatchItemFailure, SqsBatchResponse, SqsEvent, SqsMessage, SqsMessageAttribute,
+};
+use aws_sdk_sqs::{
+    primitives::Blob,
+    types::{Message, MessageAttributeValue, MessageSystemAttributeName},
+};
+use futures_util::{StreamExt, stream};
+
+/// Message of a Lambda SQS event as a received [`Message`], so the handlers of
+/// [`AdaptiveConsumer`](crate::poller::AdaptiveConsumer) and
+/// [`MessageRouter`](crate::router::MessageRouter) can run in Lambda too.
+pub fn to_message(message: SqsMessage) -> Message {
+    let attributes = message
+        .attributes
+        .into_iter()
+        .map(|(name, value)| (MessageSystemAttributeName::from(name.as_str()), value))
+        .collect::<std::collections::HashMap<_, _>>();
+    let message_attributes = message
+        .message_attributes
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, to_message_attribute_value(value)?)))
+        .collect::<std::collections::HashMap<_, _>>();
+    Message::builder()
+        .set_message_id(message.message_id)
+        .set_receipt_handle(message.receipt_handle)
+        .set_md5_of_body(message.md5_of_body)
+        .set_body(message.body)
+        .set_md5_of_message_attributes(message.md5_of_message_attributes)
+        .set_attributes(Some(attributes).filter(|a| !a.is_empty()))
+        .set_message_attributes(Some(message_attributes).filter(|a| !a.is_empty()))
+        .build()
+}
+
+/// None without a data type.
+fn to_message_attribute_value(attribute: SqsMessageAttribute) -> Option<MessageAttributeValue> {
+    MessageAttributeValue::builder()
+        .set_data_type(attribute.data_type)
+        .set_string_value(attribute.string_value)
+        .set_binary_value(attribute.binary_value.map(|b| Blob::new(b.0)))
+        .set_string_list_values(Some(attribute.string_list_values).filter(|v| !v.is_empty()))
+        .set_binary_list_values(
+            Some(
+                attribute
+                    .binary_list_values
+                    .into_iter()
+                    .map(|b| Blob::new(b.0))
+                    .collect::<Vec<_>>(),
+            )
+            .filter(|v| !v.is_empty()),
+        )
+        .build()
+        .ok()
+}
+
+/// `batchItemFailures` response of the results by message ID. Only the failed messages
+/// are received again; Lambda deletes the others.
+///
+/// The function must have `ReportBatchItemFailures` enabled on its event source mapping.
+/// A failure with an empty message ID makes Lambda retry the whole batch.
+pub fn batch_response<E>(
+    results: impl IntoIterator<Item = (String, Result<(), E>)>,
+) -> SqsBatchResponse {
+    let mut response = SqsBatchResponse::default();
+    for (message_id, result) in results {
+        if result.is_err() {
+            response.batch_item_failures.push(BatchItemFailure {
+                item_identifier: message_id,
+            });
+        }
+    }
+    response
+}
+
+/// Runs `handler` on the messages of `event`, up to `concurrency` at a time, and returns the
+/// failed messages as `batchItemFailures`.
+///
+/// Messages of a FIFO queue are handled one by one in order. After the first failure the
+/// remaining messages are not handled and are reported as failed too, so the order within
+/// a message group is kept.
+pub async fn handle_batch<F, Fut, E>(
+    event: SqsEvent,
+    concurrency: usize,
+    handler: F,
+) -> SqsBatchResponse
+where
+    F: Fn(Message) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+{
+    let fifo = event.records.iter().any(|m| {
+        m.event_source_arn
+            .as_deref()
+            .is_some_and(|arn| arn.ends_with(".fifo"))
+    });
+    if fifo {
+        let mut results = Vec::with_capacity(event.records.len());
+        let mut failed = false;
+        for message in event.records {
+            let message_id = message.message_id.clone().unwrap_or_default();
+            if !failed {
+                failed = handler(to_message(message)).await.is_err();
+            }
+            results.push((message_id, if failed { Err(()) } else { Ok(()) }));
+        }
+        batch_response(results)
+    } else {
+        let results = stream::iter(event.records)
+            .map(|message| {
+                let message_id = message.message_id.clone().unwrap_or_default();
+                let handled = handler(to_message(message));
+                async move { (message_id, handled.await) }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+        batch_response(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use aws_lambda_events::encodings::Base64Data;
+
+    use super::*;
+
+    fn sqs_message(message_id: &str, body: &str, event_source_arn: &str) -> SqsMessage {
+        SqsMessage {
+            message_id: Some(message_id.to_string()),
+            receipt_handle: Some(format!("handle-{message_id}")),
+            body: Some(body.to_string()),
+            event_source_arn: Some(event_source_arn.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn sqs_event(records: Vec<SqsMessage>) -> SqsEvent {
+        SqsEvent { records }
+    }
+
+    fn failed_ids(response: &SqsBatchResponse) -> Vec<&str> {
+        let mut ids = response
+            .batch_item_failures
+            .iter()
+            .map(|f| f.item_identifier.as_str())
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    const QUEUE_ARN: &str = "arn:aws:sqs:us-east-1:123456789012:orders";
+    const FIFO_QUEUE_ARN: &str = "arn:aws:sqs:us-east-1:123456789012:orders.fifo";
+
+    #[test]
+    fn test_to_message() {
+        let mut message = sqs_message("1", "hello", QUEUE_ARN);
+        message.attributes = HashMap::from([
+            ("ApproximateReceiveCount".to_string(), "2".to_string()),
+            ("MessageGroupId".to_string(), "g1".to_string()),
+        ]);
+        let attribute = SqsMessageAttribute {
+            data_type: Some("String".to_string()),
+            string_value: Some("order.created".to_string()),
+            ..Default::default()
+        };
+        let binary = SqsMessageAttribute {
+            data_type: Some("Binary".to_string()),
+            binary_value: Some(Base64Data(vec![1, 2, 3])),
+            ..Default::default()
+        };
+        message.message_attributes = HashMap::from([
+            ("type".to_string(), attribute),
+            ("raw".to_string(), binary),
+            ("invalid".to_string(), SqsMessageAttribute::default()),
+        ]);
+
+        let message = to_message(message);
+        assert_eq!(message.message_id(), Some("1"));
+        assert_eq!(message.receipt_handle(), Some("handle-1"));
+        assert_eq!(message.body(), Some("hello"));
+        let attributes = message.attributes().unwrap();
+        assert_eq!(
+            attributes.get(&MessageSystemAttributeName::ApproximateReceiveCount),
+            Some(&"2".to_string())
+        );
+        assert_eq!(
+            attributes.get(&MessageSystemAttributeName::MessageGroupId),
+            Some(&"g1".to_string())
+        );
+        let message_attributes = message.message_attributes().unwrap();
+        assert_eq!(message_attributes.len(), 2);
+        assert_eq!(
+            message_attributes["type"].string_value(),
+            Some("order.created")
+        );
+        assert_eq!(
+            message_attributes["raw"].binary_value(),
+            Some(&Blob::new(vec![1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn test_batch_response() {
+        let response = batch_response([
+            ("1".to_string(), Ok(())),
+            ("2".to_string(), Err("failed")),
+            ("3".to_string(), Ok(())),
+        ]);
+        assert_eq!(failed_ids(&response), vec!["2"]);
+    }
+
+    #[tokio::test]
+    async fn test_handle_batch() {
+        let event = sqs_event(vec![
+            sqs_message("1", "ok", QUEUE_ARN),
+            sqs_message("2", "ng", QUEUE_ARN),
+            sqs_message("3", "ok", QUEUE_ARN),
+            sqs_message("4", "ng", QUEUE_ARN),
+        ]);
+        let response = handle_batch(event, 2, |message| async move {
+            match message.body() {
+                Some("ok") => Ok(()),
+                _ => Err("failed"),
+            }
+        })
+        .await;
+        assert_eq!(failed_ids(&response), vec!["2", "4"]);
+    }
+
+    #[tokio::test]
+    async fn test_handle_batch_fifo() {
+        let event = sqs_event(vec![
+            sqs_message("1", "ok", FIFO_QUEUE_ARN),
+            sqs_message("2", "ng", FIFO_QUEUE_ARN),
+            sqs_message("3", "ok", FIFO_QUEUE_ARN),
+        ]);
+        let handled = std::sync::Mutex::new(Vec::new());
+        let response = handle_batch(event, 10, |message| {
+            handled
+                .lock()
+                .unwrap()
+                .push(message.message_id().unwrap_or_default().to_string());
+            async move {
+                match message.body() {
+                    Some("ok") => Ok(()),
+                    _ => Err("failed"),
+                }
+            }
+        })
+        .await;
+
+        // 失敗以降のメッセージは処理せずに失敗として返す
+        assert_eq!(failed_ids(&response), vec!["2", "3"]);
+        assert_eq!(*handled.lock().unwrap(), vec!["1", "2"]);
+    }
+}
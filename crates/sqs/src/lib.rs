@@ -6,11 +6,29 @@ use aws_config::{
 };
 use aws_sdk_sqs::{Client, config::SharedInterceptor};
 
+pub mod api;
+#[cfg(feature = "s3")]
+pub mod archive;
 pub mod builder;
+pub mod consumer;
+pub mod delay;
 pub mod error;
+pub mod in_memory;
+#[cfg(feature = "lambda")]
+pub mod lambda_sqs;
+pub mod message;
+pub mod poller;
+pub mod router;
+#[cfg(feature = "s3_events")]
+pub mod s3_events;
+pub mod sender;
+#[cfg(feature = "sns")]
+pub mod sns;
 pub mod sqs;
 
 pub use aws_sdk_sqs;
+#[cfg(feature = "metrics")]
+pub use aws_utils_core::metrics;
 
 pub async fn make_client_with_timeout_default(endpoint_url: Option<String>) -> Client {
     make_client_with_timeout(
@@ -0,0 +1,94 @@
+use aws_sdk_sqs::types::{Message, MessageSystemAttributeName};
+use chrono::{DateTime, Utc};
+
+/// Typed accessors for the system attributes of a received message.
+///
+/// The attributes are only present when requested in ReceiveMessage, e.g. with
+/// `Some(vec![MessageSystemAttributeName::All])` as `message_system_attribute_names`.
+pub trait MessageExt {
+    fn system_attribute(&self, name: MessageSystemAttributeName) -> Option<&str>;
+
+    /// FIFO queues only.
+    fn message_group_id(&self) -> Option<&str> {
+        self.system_attribute(MessageSystemAttributeName::MessageGroupId)
+    }
+
+    /// FIFO queues only.
+    fn message_deduplication_id(&self) -> Option<&str> {
+        self.system_attribute(MessageSystemAttributeName::MessageDeduplicationId)
+    }
+
+    /// FIFO queues only.
+    fn sequence_number(&self) -> Option<&str> {
+        self.system_attribute(MessageSystemAttributeName::SequenceNumber)
+    }
+
+    fn sender_id(&self) -> Option<&str> {
+        self.system_attribute(MessageSystemAttributeName::SenderId)
+    }
+
+    fn approximate_receive_count(&self) -> Option<u32> {
+        self.system_attribute(MessageSystemAttributeName::ApproximateReceiveCount)
+            .and_then(|c| c.parse().ok())
+    }
+
+    fn sent_timestamp(&self) -> Option<DateTime<Utc>> {
+        self.system_attribute(MessageSystemAttributeName::SentTimestamp)
+            .and_then(parse_epoch_millis)
+    }
+
+    fn approximate_first_receive_timestamp(&self) -> Option<DateTime<Utc>> {
+        self.system_attribute(MessageSystemAttributeName::ApproximateFirstReceiveTimestamp)
+            .and_then(parse_epoch_millis)
+    }
+
+    /// Set when the message was moved from a dead-letter queue source.
+    fn dead_letter_queue_source_arn(&self) -> Option<&str> {
+        self.system_attribute(MessageSystemAttributeName::DeadLetterQueueSourceArn)
+    }
+}
+
+impl MessageExt for Message {
+    fn system_attribute(&self, name: MessageSystemAttributeName) -> Option<&str> {
+        self.attributes()
+            .and_then(|a| a.get(&name))
+            .map(String::as_str)
+    }
+}
+
+// SentTimestamp 等はエポックミリ秒の文字列
+fn parse_epoch_millis(value: &str) -> Option<DateTime<Utc>> {
+    value
+        .parse::<i64>()
+        .ok()
+        .and_then(DateTime::from_timestamp_millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_attributes() {
+        let message = Message::builder()
+            .message_id("id-1")
+            .attributes(MessageSystemAttributeName::MessageGroupId, "group-1")
+            .attributes(
+                MessageSystemAttributeName::MessageDeduplicationId,
+                "dedup-1",
+            )
+            .attributes(MessageSystemAttributeName::ApproximateReceiveCount, "3")
+            .attributes(MessageSystemAttributeName::SentTimestamp, "1700000000123")
+            .build();
+
+        assert_eq!(message.message_group_id(), Some("group-1"));
+        assert_eq!(message.message_deduplication_id(), Some("dedup-1"));
+        assert_eq!(message.approximate_receive_count(), Some(3));
+        assert_eq!(
+            message.sent_timestamp().unwrap().to_rfc3339(),
+            "2023-11-14T22:13:20.123+00:00"
+        );
+        assert_eq!(message.sequence_number(), None);
+        assert_eq!(message.approximate_first_receive_timestamp(), None);
+    }
+}
@@ -0,0 +1,622 @@
+use std::{
+    fmt::Display,
+    future::{Future, poll_fn},
+    pin::pin,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+use aws_sdk_sqs::types::{Message, MessageSystemAttributeName};
+use futures_util::{StreamExt, stream::FuturesUnordered};
+use tokio::sync::mpsc;
+
+#[cfg(feature = "metrics")]
+use crate::metrics::{ConsumerMetrics, MetricsSink};
+use crate::{
+    api::SqsApi,
+    error::{AwsUtilError, Error},
+};
+
+/// Wait before a poller retries a retryable error of ReceiveMessage.
+const ERROR_BACKOFF: Duration = Duration::from_secs(1);
+/// Pollers are removed when more than this ratio of receives are empty.
+const SCALE_DOWN_EMPTY_RATIO: f64 = 0.5;
+/// Pollers are added when at most this ratio of receives are empty.
+const SCALE_UP_EMPTY_RATIO: f64 = 0.1;
+
+/// What woke up the loop of [`AdaptiveConsumer::run`].
+enum LoopEvent {
+    Shutdown,
+    Handled(Result<(), Error>),
+    Received(Option<Result<Message, Error>>),
+    Adjust,
+}
+
+/// Receives and empty receives of the pollers since the last adjustment.
+#[derive(Debug, Default)]
+struct PollStats {
+    receives: AtomicUsize,
+    empty_receives: AtomicUsize,
+}
+
+impl PollStats {
+    fn record(&self, empty: bool) {
+        self.receives.fetch_add(1, Ordering::Relaxed);
+        if empty {
+            self.empty_receives.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn take(&self) -> (usize, usize) {
+        (
+            self.receives.swap(0, Ordering::Relaxed),
+            self.empty_receives.swap(0, Ordering::Relaxed),
+        )
+    }
+}
+
+/// Records the receives and the processing of the messages to the sink of
+/// [`AdaptiveConsumer::metrics_sink`], labeled with the queue name.
+#[derive(Clone, Default)]
+struct LoopMetrics {
+    #[cfg(feature = "metrics")]
+    sink: Option<(Arc<dyn MetricsSink>, Arc<str>)>,
+}
+
+impl LoopMetrics {
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    fn receive(&self, messages: usize, duration: Duration) {
+        #[cfg(feature = "metrics")]
+        if let Some((sink, source)) = &self.sink {
+            sink.record_consumer(&ConsumerMetrics::Receive {
+                source,
+                messages,
+                duration,
+            });
+        }
+    }
+
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    fn processed(&self, duration: Duration, success: bool) {
+        #[cfg(feature = "metrics")]
+        if let Some((sink, source)) = &self.sink {
+            sink.record_consumer(&ConsumerMetrics::Processed {
+                source,
+                duration,
+                success,
+            });
+        }
+    }
+}
+
+impl std::fmt::Debug for LoopMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoopMetrics").finish_non_exhaustive()
+    }
+}
+
+type HandlerErrorFn = dyn Fn(&str, &dyn Display) + Send + Sync;
+
+/// Hook of [`AdaptiveConsumer::on_handler_error`].
+#[derive(Clone, Default)]
+struct HandlerErrorHook(Option<Arc<HandlerErrorFn>>);
+
+impl std::fmt::Debug for HandlerErrorHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("HandlerErrorHook")
+            .field(&self.0.is_some())
+            .finish()
+    }
+}
+
+/// Consumer loop running between `min_pollers` and `max_pollers` concurrent
+/// ReceiveMessage long polls, adjusted every `adjust_interval`:
+///
+/// - a poller is removed when the handler queue is at least half full (the handlers are
+///   the bottleneck) or more than half of the receives were empty (the queue is idle),
+/// - a poller is added when almost all receives returned messages and the handler queue
+///   has room.
+///
+/// Pollers wait while the handler queue is full, so no more messages are received than
+/// `max_in_flight` plus the handlers running.
+#[derive(Debug, Clone)]
+pub struct AdaptiveConsumer<C> {
+    client: C,
+    queue_url: String,
+    min_pollers: usize,
+    max_pollers: usize,
+    concurrency: usize,
+    max_in_flight: usize,
+    max_number_of_messages: i32,
+    visibility_timeout: Option<i32>,
+    wait_time_seconds: i32,
+    adjust_interval: Duration,
+    metrics: LoopMetrics,
+    on_handler_error: HandlerErrorHook,
+}
+
+impl<C> AdaptiveConsumer<C>
+where
+    C: SqsApi + Clone + 'static,
+{
+    /// 1 to 4 pollers receiving 10 messages with 20 seconds long polls, 10 concurrent
+    /// handlers and 100 messages in the handler queue.
+    pub fn new(client: C, queue_url: impl Into<String>) -> Self {
+        Self {
+            client,
+            queue_url: queue_url.into(),
+            min_pollers: 1,
+            max_pollers: 4,
+            concurrency: 10,
+            max_in_flight: 100,
+            max_number_of_messages: 10,
+            visibility_timeout: None,
+            wait_time_seconds: 20,
+            adjust_interval: Duration::from_secs(10),
+            metrics: LoopMetrics::default(),
+            on_handler_error: HandlerErrorHook::default(),
+        }
+    }
+
+    /// `min_pollers` is raised to at least 1, and `max_pollers` to at least `min_pollers`.
+    pub fn pollers(mut self, min_pollers: usize, max_pollers: usize) -> Self {
+        self.min_pollers = min_pollers.max(1);
+        self.max_pollers = max_pollers.max(self.min_pollers);
+        self
+    }
+
+    /// Max handlers running at the same time, raised to at least 1.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Capacity of the queue between the pollers and the handlers, raised to at least 1.
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    pub fn max_number_of_messages(mut self, max_number_of_messages: i32) -> Self {
+        self.max_number_of_messages = max_number_of_messages;
+        self
+    }
+
+    /// Must cover the time a message waits in the handler queue and its handler.
+    pub fn visibility_timeout(mut self, visibility_timeout: i32) -> Self {
+        self.visibility_timeout = Some(visibility_timeout);
+        self
+    }
+
+    pub fn wait_time_seconds(mut self, wait_time_seconds: i32) -> Self {
+        self.wait_time_seconds = wait_time_seconds;
+        self
+    }
+
+    pub fn adjust_interval(mut self, adjust_interval: Duration) -> Self {
+        self.adjust_interval = adjust_interval;
+        self
+    }
+
+    /// Records every receive (messages per receive, empty receives) and the processing
+    /// duration of every message with [`MetricsSink::record_consumer`], with the queue name
+    /// as the source, to tune `wait_time_seconds` and the pollers.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_sink(mut self, sink: impl MetricsSink) -> Self {
+        let queue_name = self.queue_url.rsplit('/').next().unwrap_or_default();
+        self.metrics.sink = Some((Arc::new(sink), Arc::from(queue_name)));
+        self
+    }
+
+    /// Called with the message ID and the error when a handler returns Err, e.g. to log it.
+    /// The message is received again after the visibility timeout.
+    pub fn on_handler_error(
+        mut self,
+        on_handler_error: impl Fn(&str, &dyn Display) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_handler_error = HandlerErrorHook(Some(Arc::new(on_handler_error)));
+        self
+    }
+
+    /// Runs `handler` on the received messages until `shutdown` completes. A message is
+    /// deleted when its handler returns Ok, and received again after the visibility
+    /// timeout otherwise, after the error is passed to
+    /// [`on_handler_error`](Self::on_handler_error).
+    ///
+    /// On shutdown the pollers stop and the running handlers are awaited. Messages still
+    /// in the handler queue are received again after the visibility timeout.
+    /// Fails on the first error of ReceiveMessage that is not retryable, or of
+    /// DeleteMessage.
+    pub async fn run<F, Fut, E>(
+        &self,
+        handler: F,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), Error>
+    where
+        F: Fn(Message) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: Display,
+    {
+        let (tx, mut rx) = mpsc::channel(self.max_in_flight);
+        let stats = Arc::new(PollStats::default());
+        // ポーラーはタスクを生成せず、このループの中でポーリングする
+        let mut pollers = FuturesUnordered::new();
+        let mut stops = Vec::new();
+        for _ in 0..self.min_pollers {
+            let (poller, stop) = self.poller();
+            pollers.push(poller.run(tx.clone(), stats.clone()));
+            stops.push(stop);
+        }
+        let mut handlers = FuturesUnordered::new();
+        let mut interval = tokio::time::interval(self.adjust_interval);
+        interval.tick().await;
+        let mut shutdown = pin!(shutdown);
+
+        let result = loop {
+            let event = poll_fn(|cx| {
+                if shutdown.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(LoopEvent::Shutdown);
+                }
+                while let Poll::Ready(Some(())) = pollers.poll_next_unpin(cx) {}
+                if let Poll::Ready(Some(result)) = handlers.poll_next_unpin(cx) {
+                    return Poll::Ready(LoopEvent::Handled(result));
+                }
+                if handlers.len() < self.concurrency
+                    && let Poll::Ready(item) = rx.poll_recv(cx)
+                {
+                    return Poll::Ready(LoopEvent::Received(item));
+                }
+                if interval.poll_tick(cx).is_ready() {
+                    return Poll::Ready(LoopEvent::Adjust);
+                }
+                Poll::Pending
+            })
+            .await;
+            match event {
+                LoopEvent::Shutdown => break Ok(()),
+                LoopEvent::Handled(Err(e)) => break Err(e),
+                LoopEvent::Handled(Ok(())) => {}
+                LoopEvent::Received(Some(Ok(message))) => {
+                    handlers.push(self.handle(&handler, message))
+                }
+                LoopEvent::Received(Some(Err(e))) => break Err(e),
+                LoopEvent::Received(None) => break Ok(()),
+                LoopEvent::Adjust => {
+                    let (receives, empty_receives) = stats.take();
+                    let target = next_poller_count(
+                        stops.len(),
+                        self.min_pollers,
+                        self.max_pollers,
+                        rx.len(),
+                        self.max_in_flight,
+                        receives,
+                        empty_receives,
+                    );
+                    while stops.len() < target {
+                        let (poller, stop) = self.poller();
+                        pollers.push(poller.run(tx.clone(), stats.clone()));
+                        stops.push(stop);
+                    }
+                    while stops.len() > target {
+                        // 止めたポーラーは実行中の受信を終えてから抜ける
+                        if let Some(stop) = stops.pop() {
+                            stop.store(true, Ordering::Relaxed);
+                        }
+                    }
+                }
+            }
+        };
+
+        // 受信中のポーラーは破棄し、受信済みのメッセージは可視性タイムアウト後に再受信される
+        drop(pollers);
+        rx.close();
+        while let Some(handled) = handlers.next().await {
+            handled?;
+        }
+        result
+    }
+
+    fn poller(&self) -> (Poller<C>, Arc<AtomicBool>) {
+        let stop = Arc::new(AtomicBool::new(false));
+        let poller = Poller {
+            client: self.client.clone(),
+            queue_url: self.queue_url.clone(),
+            max_number_of_messages: self.max_number_of_messages,
+            visibility_timeout: self.visibility_timeout,
+            wait_time_seconds: self.wait_time_seconds,
+            stop: stop.clone(),
+            metrics: self.metrics.clone(),
+        };
+        (poller, stop)
+    }
+
+    async fn handle<F, Fut, E>(&self, handler: &F, message: Message) -> Result<(), Error>
+    where
+        F: Fn(Message) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: Display,
+    {
+        let receipt_handle = message.receipt_handle().map(ToString::to_string);
+        let message_id = message.message_id().unwrap_or_default().to_string();
+        let started = Instant::now();
+        let handled = handler(message).await;
+        self.metrics.processed(started.elapsed(), handled.is_ok());
+        if let Err(e) = handled {
+            if let Some(on_handler_error) = &self.on_handler_error.0 {
+                on_handler_error(&message_id, &e);
+            }
+            return Ok(());
+        }
+        if let Some(receipt_handle) = receipt_handle {
+            self.client
+                .delete_message(&self.queue_url, &receipt_handle)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+struct Poller<C> {
+    client: C,
+    queue_url: String,
+    max_number_of_messages: i32,
+    visibility_timeout: Option<i32>,
+    wait_time_seconds: i32,
+    stop: Arc<AtomicBool>,
+    metrics: LoopMetrics,
+}
+
+impl<C: SqsApi> Poller<C> {
+    async fn run(self, tx: mpsc::Sender<Result<Message, Error>>, stats: Arc<PollStats>) {
+        while !self.stop.load(Ordering::Relaxed) {
+            let started = Instant::now();
+            let result = self
+                .client
+                .receive_message(
+                    &self.queue_url,
+                    Some(self.max_number_of_messages),
+                    Some(vec!["All".to_string()]),
+                    Some(vec![MessageSystemAttributeName::All]),
+                    None,
+                    self.visibility_timeout,
+                    Some(self.wait_time_seconds),
+                )
+                .await;
+            match result {
+                Ok(output) => {
+                    let messages = output.messages.unwrap_or_default();
+                    stats.record(messages.is_empty());
+                    self.metrics.receive(messages.len(), started.elapsed());
+                    for message in messages {
+                        if tx.send(Ok(message)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) if e.is_retryable() => tokio::time::sleep(ERROR_BACKOFF).await,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Number of pollers for the next interval.
+fn next_poller_count(
+    current: usize,
+    min_pollers: usize,
+    max_pollers: usize,
+    queue_depth: usize,
+    max_in_flight: usize,
+    receives: usize,
+    empty_receives: usize,
+) -> usize {
+    // 長いポーリングが調整間隔より長い場合は、受信がないまま判断しない
+    let target = if queue_depth * 2 >= max_in_flight {
+        current.saturating_sub(1)
+    } else if receives == 0 {
+        current
+    } else {
+        let empty_ratio = empty_receives as f64 / receives as f64;
+        if empty_ratio > SCALE_DOWN_EMPTY_RATIO {
+            current.saturating_sub(1)
+        } else if empty_ratio <= SCALE_UP_EMPTY_RATIO {
+            current + 1
+        } else {
+            current
+        }
+    };
+    target.clamp(min_pollers, max_pollers)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::in_memory::InMemorySqs;
+
+    const QUEUE_URL: &str = "https://sqs.ap-northeast-1.amazonaws.com/123456789012/jobs";
+
+    #[test]
+    fn test_next_poller_count() {
+        // 受信がすべて空なら減らす
+        assert_eq!(next_poller_count(3, 1, 4, 0, 100, 10, 10), 2);
+        assert_eq!(next_poller_count(1, 1, 4, 0, 100, 10, 10), 1);
+        // すべて受信できていれば増やす
+        assert_eq!(next_poller_count(2, 1, 4, 0, 100, 10, 0), 3);
+        assert_eq!(next_poller_count(4, 1, 4, 0, 100, 10, 0), 4);
+        // ハンドラーが詰まっていれば受信できていても減らす
+        assert_eq!(next_poller_count(3, 1, 4, 50, 100, 10, 0), 2);
+        assert_eq!(next_poller_count(2, 1, 4, 0, 100, 10, 3), 2);
+        assert_eq!(next_poller_count(2, 1, 4, 0, 100, 0, 0), 2);
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let sqs = InMemorySqs::new();
+        for i in 0..25 {
+            sqs.send_message(QUEUE_URL, Some(i.to_string()), None, None, None, None, None)
+                .await
+                .unwrap();
+        }
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let consumer = AdaptiveConsumer::new(sqs.clone(), QUEUE_URL)
+            .pollers(1, 3)
+            .wait_time_seconds(1)
+            .adjust_interval(Duration::from_millis(10));
+
+        let shutdown = {
+            let sqs = sqs.clone();
+            async move {
+                while sqs.message_count(QUEUE_URL) > 0 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            }
+        };
+        consumer
+            .run(
+                |message| {
+                    let handled = handled.clone();
+                    async move {
+                        let body = message.body().unwrap_or_default().to_string();
+                        handled.lock().unwrap().push(body);
+                        Ok::<_, String>(())
+                    }
+                },
+                shutdown,
+            )
+            .await
+            .unwrap();
+
+        let mut handled = handled.lock().unwrap().clone();
+        handled.sort_by_key(|b| b.parse::<u32>().unwrap());
+        assert_eq!(handled, (0..25).map(|i| i.to_string()).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_on_handler_error() {
+        let sqs = InMemorySqs::new();
+        let message_id = sqs
+            .send_message(
+                QUEUE_URL,
+                Some("1".to_string()),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap()
+            .message_id
+            .unwrap();
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let consumer = {
+            let errors = errors.clone();
+            AdaptiveConsumer::new(sqs.clone(), QUEUE_URL)
+                .wait_time_seconds(1)
+                .on_handler_error(move |message_id, e| {
+                    errors
+                        .lock()
+                        .unwrap()
+                        .push((message_id.to_string(), e.to_string()))
+                })
+        };
+        let shutdown = {
+            let errors = errors.clone();
+            async move {
+                while errors.lock().unwrap().is_empty() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            }
+        };
+        consumer
+            .run(|_| async { Err::<(), _>("boom") }, shutdown)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *errors.lock().unwrap(),
+            vec![(message_id, "boom".to_string())]
+        );
+        // 失敗したメッセージは削除されない
+        assert_eq!(sqs.message_count(QUEUE_URL), 1);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn test_run_metrics() {
+        use crate::metrics::CallMetrics;
+
+        #[derive(Default)]
+        struct Recorder {
+            receives: Mutex<Vec<usize>>,
+            processed: Mutex<Vec<bool>>,
+        }
+
+        struct Sink(Arc<Recorder>);
+
+        impl MetricsSink for Sink {
+            fn record(&self, _call: &CallMetrics<'_>) {}
+
+            fn record_consumer(&self, metrics: &ConsumerMetrics<'_>) {
+                match metrics {
+                    ConsumerMetrics::Receive {
+                        source, messages, ..
+                    } => {
+                        assert_eq!(*source, "jobs");
+                        self.0.receives.lock().unwrap().push(*messages);
+                    }
+                    ConsumerMetrics::Processed { success, .. } => {
+                        self.0.processed.lock().unwrap().push(*success);
+                    }
+                }
+            }
+        }
+
+        let sqs = InMemorySqs::new();
+        for i in 0..5 {
+            sqs.send_message(QUEUE_URL, Some(i.to_string()), None, None, None, None, None)
+                .await
+                .unwrap();
+        }
+        let recorder = Arc::new(Recorder::default());
+        let consumer = AdaptiveConsumer::new(sqs.clone(), QUEUE_URL)
+            .wait_time_seconds(1)
+            .metrics_sink(Sink(recorder.clone()));
+        let shutdown = {
+            let recorder = recorder.clone();
+            async move {
+                while recorder.processed.lock().unwrap().len() < 5 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            }
+        };
+        consumer
+            .run(
+                |message| async move {
+                    // 奇数のメッセージは失敗させる
+                    match message.body().unwrap_or_default().parse::<u32>() {
+                        Ok(i) if i % 2 == 0 => Ok(()),
+                        _ => Err("odd"),
+                    }
+                },
+                shutdown,
+            )
+            .await
+            .unwrap();
+
+        let receives = recorder.receives.lock().unwrap().clone();
+        assert!(receives.iter().sum::<usize>() >= 5);
+        let processed = recorder.processed.lock().unwrap().clone();
+        assert_eq!(processed.iter().filter(|s| **s).count(), 3);
+        assert_eq!(processed.iter().filter(|s| !**s).count(), 2);
+    }
+}
@@ -0,0 +1,389 @@
+use std::{collections::HashMap, fmt::Display, future::Future};
+
+use aws_sdk_sqs::types::Message;
+use futures_util::future::BoxFuture;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{
+    api::SqsApi,
+    consumer::{TypedMessage, forward_to_dead_letter},
+    sqs::Error,
+};
+
+/// Where [`MessageRouter`] reads the type of a message from.
+#[derive(Debug, Clone)]
+pub enum TypeSource {
+    /// String message attribute. The whole body is the payload.
+    Attribute(String),
+    /// Field of a JSON envelope body, e.g. `{"type": "order.created.v2", "data": {...}}`.
+    /// The payload is `payload_field` of the envelope, or the whole envelope when None.
+    Envelope {
+        type_field: String,
+        payload_field: Option<String>,
+    },
+}
+
+/// What [`MessageRouter::dispatch`] does with a message of a type without a handler,
+/// or without a type.
+#[derive(Debug, Clone, Default)]
+pub enum UnknownTypePolicy {
+    /// Forward it to `queue_url` (with a `PoisonMessageError` attribute) and return `Ok`.
+    DeadLetter { queue_url: String },
+    /// Return `Ok` without handling it.
+    Ignore,
+    /// Return [`RouteError::UnknownType`], so it is left in the queue.
+    #[default]
+    Error,
+}
+
+#[derive(Error, Debug)]
+pub enum RouteError<E> {
+    #[error("unknown message type: {0:?}")]
+    UnknownType(Option<String>),
+
+    #[error("invalid payload of {message_type}: {source}")]
+    Payload {
+        message_type: String,
+        source: serde_json::Error,
+    },
+
+    #[error(transparent)]
+    Sqs(#[from] Error),
+
+    #[error("{0}")]
+    Handler(E),
+}
+
+type Handler<E> = Box<
+    dyn Fn(Message, Value) -> Result<BoxFuture<'static, Result<(), E>>, serde_json::Error>
+        + Send
+        + Sync,
+>;
+
+/// Dispatches the messages of a queue carrying several event types to the handler
+/// registered for their type, with the payload deserialized from JSON.
+///
+/// [`dispatch`](Self::dispatch) returns `Ok` when the message can be deleted, so it can be
+/// the handler of [`AdaptiveConsumer::run`](crate::poller::AdaptiveConsumer::run).
+pub struct MessageRouter<C, E> {
+    client: C,
+    type_source: TypeSource,
+    unknown_type_policy: UnknownTypePolicy,
+    handlers: HashMap<String, Handler<E>>,
+}
+
+impl<C, E> MessageRouter<C, E>
+where
+    C: SqsApi,
+    E: Display,
+{
+    pub fn new(client: C, type_source: TypeSource) -> Self {
+        Self {
+            client,
+            type_source,
+            unknown_type_policy: UnknownTypePolicy::default(),
+            handlers: HashMap::new(),
+        }
+    }
+
+    pub fn unknown_type_policy(mut self, unknown_type_policy: UnknownTypePolicy) -> Self {
+        self.unknown_type_policy = unknown_type_policy;
+        self
+    }
+
+    /// Registers the handler of `message_type`, replacing the previous one. Register each
+    /// schema version as its own type (e.g. `order.created.v1` and `order.created.v2`) to
+    /// deserialize them into different structs.
+    pub fn on<T, F, Fut>(mut self, message_type: impl Into<String>, handler: F) -> Self
+    where
+        T: DeserializeOwned,
+        F: Fn(TypedMessage<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+    {
+        self.handlers.insert(
+            message_type.into(),
+            Box::new(move |message, payload| {
+                let body = serde_json::from_value(payload)?;
+                Ok(Box::pin(handler(TypedMessage { body, message })))
+            }),
+        );
+        self
+    }
+
+    /// Handles `message` with the handler of its type, or by the unknown type policy.
+    /// The message is not deleted.
+    pub async fn dispatch(&self, message: Message) -> Result<(), RouteError<E>> {
+        let (message_type, payload) = self.parse(&message);
+        let Some(handler) = message_type
+            .as_deref()
+            .and_then(|message_type| self.handlers.get(message_type))
+        else {
+            return self.handle_unknown(&message, message_type).await;
+        };
+        let message_type = message_type.unwrap_or_default();
+        let payload = payload.map_err(|source| RouteError::Payload {
+            message_type: message_type.clone(),
+            source,
+        })?;
+        let future = handler(message, payload).map_err(|source| RouteError::Payload {
+            message_type,
+            source,
+        })?;
+        future.await.map_err(RouteError::Handler)
+    }
+
+    /// Type and payload of a message.
+    fn parse(&self, message: &Message) -> (Option<String>, Result<Value, serde_json::Error>) {
+        let body = serde_json::from_str::<Value>(message.body().unwrap_or_default());
+        match &self.type_source {
+            TypeSource::Attribute(name) => {
+                let message_type = message
+                    .message_attributes()
+                    .and_then(|attributes| attributes.get(name))
+                    .and_then(|value| value.string_value())
+                    .map(ToString::to_string);
+                (message_type, body)
+            }
+            TypeSource::Envelope {
+                type_field,
+                payload_field,
+            } => {
+                let Ok(mut envelope) = body else {
+                    return (None, body);
+                };
+                let message_type = envelope
+                    .get(type_field)
+                    .and_then(Value::as_str)
+                    .map(ToString::to_string);
+                let payload = match payload_field {
+                    Some(field) => envelope
+                        .get_mut(field.as_str())
+                        .map(Value::take)
+                        .unwrap_or_default(),
+                    None => envelope,
+                };
+                (message_type, Ok(payload))
+            }
+        }
+    }
+
+    async fn handle_unknown(
+        &self,
+        message: &Message,
+        message_type: Option<String>,
+    ) -> Result<(), RouteError<E>> {
+        match &self.unknown_type_policy {
+            UnknownTypePolicy::DeadLetter { queue_url } => {
+                let error = RouteError::<E>::UnknownType(message_type);
+                forward_to_dead_letter(&self.client, queue_url, message, error).await?;
+                Ok(())
+            }
+            UnknownTypePolicy::Ignore => Ok(()),
+            UnknownTypePolicy::Error => Err(RouteError::UnknownType(message_type)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use aws_sdk_sqs::types::MessageAttributeValue;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::in_memory::InMemorySqs;
+
+    const QUEUE_URL: &str = "https://sqs.ap-northeast-1.amazonaws.com/123456789012/events";
+    const DEAD_LETTER_QUEUE_URL: &str =
+        "https://sqs.ap-northeast-1.amazonaws.com/123456789012/events-dlq";
+
+    #[derive(Deserialize)]
+    struct OrderCreatedV1 {
+        id: String,
+    }
+
+    #[derive(Deserialize)]
+    struct OrderCreatedV2 {
+        order_id: String,
+        amount: u64,
+    }
+
+    async fn receive(sqs: &InMemorySqs) -> Vec<Message> {
+        sqs.receive_message(
+            QUEUE_URL,
+            Some(10),
+            Some(vec!["All".to_string()]),
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+        .messages
+        .unwrap_or_default()
+    }
+
+    fn router(
+        sqs: &InMemorySqs,
+        handled: &Arc<Mutex<Vec<String>>>,
+    ) -> MessageRouter<InMemorySqs, String> {
+        let v1 = handled.clone();
+        let v2 = handled.clone();
+        MessageRouter::new(
+            sqs.clone(),
+            TypeSource::Envelope {
+                type_field: "type".to_string(),
+                payload_field: Some("data".to_string()),
+            },
+        )
+        .on(
+            "order.created.v1",
+            move |message: TypedMessage<OrderCreatedV1>| {
+                let handled = v1.clone();
+                async move {
+                    handled
+                        .lock()
+                        .unwrap()
+                        .push(format!("v1 {}", message.body.id));
+                    Ok(())
+                }
+            },
+        )
+        .on(
+            "order.created.v2",
+            move |message: TypedMessage<OrderCreatedV2>| {
+                let handled = v2.clone();
+                async move {
+                    if message.body.amount == 0 {
+                        return Err("amount is zero".to_string());
+                    }
+                    handled.lock().unwrap().push(format!(
+                        "v2 {} {}",
+                        message.body.order_id, message.body.amount
+                    ));
+                    Ok(())
+                }
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_envelope() {
+        let sqs = InMemorySqs::new();
+        for body in [
+            r#"{"type":"order.created.v1","data":{"id":"1"}}"#,
+            r#"{"type":"order.created.v2","data":{"order_id":"2","amount":300}}"#,
+            r#"{"type":"order.created.v2","data":{"order_id":"3","amount":0}}"#,
+            r#"{"type":"order.created.v2","data":{"id":"4"}}"#,
+            r#"{"type":"order.cancelled.v1","data":{"id":"5"}}"#,
+            r#"not json"#,
+        ] {
+            sqs.send_message(
+                QUEUE_URL,
+                Some(body.to_string()),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let router = router(&sqs, &handled).unknown_type_policy(UnknownTypePolicy::DeadLetter {
+            queue_url: DEAD_LETTER_QUEUE_URL.to_string(),
+        });
+
+        let mut results = Vec::new();
+        for message in receive(&sqs).await {
+            results.push(router.dispatch(message).await);
+        }
+
+        assert!(results[0].is_ok());
+        assert!(results[1].is_ok());
+        assert!(matches!(&results[2], Err(RouteError::Handler(e)) if e == "amount is zero"));
+        assert!(
+            matches!(&results[3], Err(RouteError::Payload { message_type, .. }) if message_type == "order.created.v2")
+        );
+        assert!(results[4].is_ok());
+        assert!(results[5].is_ok());
+        assert_eq!(*handled.lock().unwrap(), vec!["v1 1", "v2 2 300"]);
+        assert_eq!(
+            sqs.bodies(DEAD_LETTER_QUEUE_URL),
+            vec![
+                r#"{"type":"order.cancelled.v1","data":{"id":"5"}}"#,
+                "not json"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_attribute() {
+        let sqs = InMemorySqs::new();
+        for (message_type, body) in [
+            (Some("order.created.v1"), r#"{"id":"1"}"#),
+            (Some("order.cancelled.v1"), r#"{"id":"2"}"#),
+            (None, r#"{"id":"3"}"#),
+        ] {
+            let attributes = message_type.map(|message_type| {
+                HashMap::from([(
+                    "EventType".to_string(),
+                    MessageAttributeValue::builder()
+                        .data_type("String")
+                        .string_value(message_type)
+                        .build()
+                        .unwrap(),
+                )])
+            });
+            sqs.send_message(
+                QUEUE_URL,
+                Some(body.to_string()),
+                None,
+                None,
+                None,
+                attributes,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let router = {
+            let handled = handled.clone();
+            MessageRouter::new(sqs.clone(), TypeSource::Attribute("EventType".to_string())).on(
+                "order.created.v1",
+                move |message: TypedMessage<OrderCreatedV1>| {
+                    let handled = handled.clone();
+                    async move {
+                        handled.lock().unwrap().push(message.body.id);
+                        Ok::<_, String>(())
+                    }
+                },
+            )
+        };
+
+        let mut results = Vec::new();
+        for message in receive(&sqs).await {
+            results.push(router.dispatch(message).await);
+        }
+
+        assert!(results[0].is_ok());
+        assert!(
+            matches!(&results[1], Err(RouteError::UnknownType(Some(t))) if t == "order.cancelled.v1")
+        );
+        assert!(matches!(&results[2], Err(RouteError::UnknownType(None))));
+        assert_eq!(*handled.lock().unwrap(), vec!["1"]);
+
+        let router = router.unknown_type_policy(UnknownTypePolicy::Ignore);
+        let message = Message::builder().body(r#"{"id":"4"}"#).build();
+        assert!(router.dispatch(message).await.is_ok());
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, stream};
+use serde_json::Value;
+
+use crate::{api::SqsApi, error::Error};
+
+const WAIT_TIME_SECONDS: i32 = 20;
+
+/// A record of an S3 event notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3ObjectEvent {
+    /// e.g. `ObjectCreated:Put` or `ObjectCreated:CompleteMultipartUpload`.
+    pub event_name: String,
+    pub event_time: Option<DateTime<Utc>>,
+    pub bucket: String,
+    /// The object key, URL-decoded.
+    pub key: String,
+    /// None for delete events.
+    pub size: Option<u64>,
+    pub e_tag: Option<String>,
+    pub version_id: Option<String>,
+    /// Orders the events of the same key.
+    pub sequencer: Option<String>,
+}
+
+impl S3ObjectEvent {
+    pub fn is_object_created(&self) -> bool {
+        self.event_name.starts_with("ObjectCreated:")
+    }
+}
+
+/// The records of an S3 event notification sent directly to SQS (or through SNS with raw
+/// message delivery). Empty for the `s3:TestEvent` sent when the notification is configured.
+pub fn parse_s3_event(body: &str) -> Result<Vec<S3ObjectEvent>, Error> {
+    let invalid =
+        |reason: &str| Error::ValidationError(format!("invalid S3 event notification: {reason}"));
+    let value: Value = serde_json::from_str(body).map_err(|e| invalid(&e.to_string()))?;
+    if value["Event"] == "s3:TestEvent" {
+        return Ok(vec![]);
+    }
+    let records = value["Records"]
+        .as_array()
+        .ok_or_else(|| invalid("no Records"))?;
+    records
+        .iter()
+        .map(|record| {
+            let object = &record["s3"]["object"];
+            let optional = |value: &Value| value.as_str().map(str::to_string);
+            Ok(S3ObjectEvent {
+                event_name: optional(&record["eventName"])
+                    .ok_or_else(|| invalid("no eventName"))?,
+                event_time: record["eventTime"]
+                    .as_str()
+                    .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                    .map(|t| t.with_timezone(&Utc)),
+                bucket: optional(&record["s3"]["bucket"]["name"])
+                    .ok_or_else(|| invalid("no bucket name"))?,
+                key: decode_key(
+                    object["key"]
+                        .as_str()
+                        .ok_or_else(|| invalid("no object key"))?,
+                )
+                .ok_or_else(|| invalid("object key is not URL-encoded UTF-8"))?,
+                size: object["size"].as_u64(),
+                e_tag: optional(&object["eTag"]),
+                version_id: optional(&object["versionId"]),
+                sequencer: optional(&object["sequencer"]),
+            })
+        })
+        .collect()
+}
+
+// 通知のキーはフォームエンコードされている (スペースは +)
+fn decode_key(key: &str) -> Option<String> {
+    let bytes = key.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3)?;
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                decoded.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+                i += 2;
+            }
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+struct StreamState<'a, C> {
+    client: &'a C,
+    queue_url: String,
+    max_number_of_messages: i32,
+    /// Records not yet returned, with the receipt handle of the message after its last record.
+    pending: VecDeque<(Result<S3ObjectEvent, Error>, Option<String>)>,
+    /// Message of the record returned last, deleted when the next item is requested.
+    delete_after: Option<String>,
+}
+
+/// Object created events of the S3 event notifications sent to `queue_url`, received with long
+/// polling until the stream is dropped.
+///
+/// `max_number_of_messages` (1 if None) messages are received at a time, and their records are
+/// buffered until they are consumed. Raise it only if all the records of a receive are consumed
+/// within the visibility timeout of the queue; otherwise buffered messages become visible again
+/// and their records are returned twice.
+///
+/// A message is deleted when the item after its last record is requested, so the records of a
+/// message that was not fully consumed are received again after the visibility timeout.
+/// Messages without object created records (e.g. `s3:TestEvent` or `ObjectRemoved:*`) are
+/// deleted without being returned. A message that is not an S3 event notification yields an
+/// error and is left in the queue for its redrive policy; the stream continues after errors.
+pub fn object_created_stream<C: SqsApi>(
+    client: &C,
+    queue_url: impl Into<String>,
+    max_number_of_messages: Option<i32>,
+) -> impl Stream<Item = Result<S3ObjectEvent, Error>> {
+    let state = StreamState {
+        client,
+        queue_url: queue_url.into(),
+        max_number_of_messages: max_number_of_messages.unwrap_or(1),
+        pending: VecDeque::new(),
+        delete_after: None,
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(receipt_handle) = state.delete_after.take()
+                && let Err(e) = state
+                    .client
+                    .delete_message(&state.queue_url, &receipt_handle)
+                    .await
+            {
+                return Some((Err(e), state));
+            }
+            if let Some((item, delete_after)) = state.pending.pop_front() {
+                state.delete_after = delete_after;
+                return Some((item, state));
+            }
+            let output = match state
+                .client
+                .receive_message(
+                    &state.queue_url,
+                    Some(state.max_number_of_messages),
+                    None,
+                    None,
+                    None,
+                    None,
+                    Some(WAIT_TIME_SECONDS),
+                )
+                .await
+            {
+                Ok(output) => output,
+                Err(e) => return Some((Err(e), state)),
+            };
+            for message in output.messages.unwrap_or_default() {
+                let Some(receipt_handle) = message.receipt_handle else {
+                    continue;
+                };
+                let records = match parse_s3_event(message.body.as_deref().unwrap_or_default()) {
+                    Ok(records) => records,
+                    Err(e) => {
+                        state.pending.push_back((Err(e), None));
+                        continue;
+                    }
+                };
+                let created = records
+                    .into_iter()
+                    .filter(S3ObjectEvent::is_object_created)
+                    .collect::<Vec<_>>();
+                if created.is_empty() {
+                    if let Err(e) = state
+                        .client
+                        .delete_message(&state.queue_url, &receipt_handle)
+                        .await
+                    {
+                        state.pending.push_back((Err(e), None));
+                    }
+                    continue;
+                }
+                let last = created.len() - 1;
+                for (i, record) in created.into_iter().enumerate() {
+                    let delete_after = (i == last).then(|| receipt_handle.clone());
+                    state.pending.push_back((Ok(record), delete_after));
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+    use crate::in_memory::InMemorySqs;
+
+    const QUEUE_URL: &str = "https://sqs.ap-northeast-1.amazonaws.com/123456789012/uploads";
+
+    fn notification(records: &[(&str, &str)]) -> String {
+        let records = records
+            .iter()
+            .map(|(event_name, key)| {
+                serde_json::json!({
+                    "eventVersion": "2.1",
+                    "eventSource": "aws:s3",
+                    "eventTime": "2026-10-01T12:34:56.789Z",
+                    "eventName": event_name,
+                    "s3": {
+                        "bucket": {"name": "uploads", "arn": "arn:aws:s3:::uploads"},
+                        "object": {
+                            "key": key,
+                            "size": 1024,
+                            "eTag": "d41d8cd98f00b204e9800998ecf8427e",
+                            "sequencer": "0055AED6DCD90281E5"
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({ "Records": records }).to_string()
+    }
+
+    #[test]
+    fn test_parse_s3_event() {
+        let records = parse_s3_event(&notification(&[(
+            "ObjectCreated:Put",
+            "photos/my+cat%2B1.jpg",
+        )]))
+        .unwrap();
+        assert_eq!(
+            records,
+            vec![S3ObjectEvent {
+                event_name: "ObjectCreated:Put".to_string(),
+                event_time: Some(
+                    DateTime::parse_from_rfc3339("2026-10-01T12:34:56.789Z")
+                        .unwrap()
+                        .with_timezone(&Utc)
+                ),
+                bucket: "uploads".to_string(),
+                key: "photos/my cat+1.jpg".to_string(),
+                size: Some(1024),
+                e_tag: Some("d41d8cd98f00b204e9800998ecf8427e".to_string()),
+                version_id: None,
+                sequencer: Some("0055AED6DCD90281E5".to_string()),
+            }]
+        );
+        assert!(
+            parse_s3_event(r#"{"Service":"Amazon S3","Event":"s3:TestEvent"}"#)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(parse_s3_event("not json").is_err());
+        assert!(parse_s3_event(&notification(&[("ObjectCreated:Put", "a%2")])).is_err());
+        assert!(parse_s3_event(&notification(&[("ObjectCreated:Put", "a%ff")])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_object_created_stream() {
+        let sqs = InMemorySqs::new();
+        let bodies = [
+            notification(&[
+                ("ObjectCreated:Put", "a.txt"),
+                ("ObjectCreated:Copy", "b.txt"),
+            ]),
+            r#"{"Service":"Amazon S3","Event":"s3:TestEvent"}"#.to_string(),
+            "not an event".to_string(),
+            notification(&[("ObjectRemoved:Delete", "c.txt")]),
+        ];
+        for body in bodies {
+            sqs.send_message(QUEUE_URL, Some(body), None, None, None, None, None)
+                .await
+                .unwrap();
+        }
+
+        let stream = object_created_stream(&sqs, QUEUE_URL, Some(10));
+        futures_util::pin_mut!(stream);
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.key, "a.txt");
+        let second = stream.next().await.unwrap().unwrap();
+        assert_eq!(second.key, "b.txt");
+        assert_eq!(second.event_name, "ObjectCreated:Copy");
+        // 2件目を返した時点ではまだ削除されない
+        assert_eq!(sqs.message_count(QUEUE_URL), 2);
+
+        let third = stream.next().await.unwrap();
+        assert!(matches!(third, Err(Error::ValidationError(_))));
+        assert_eq!(sqs.bodies(QUEUE_URL), vec!["not an event".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_object_created_stream_receives_one_message() {
+        let sqs = InMemorySqs::new();
+        for key in ["a.txt", "b.txt"] {
+            sqs.send_message(
+                QUEUE_URL,
+                Some(notification(&[("ObjectCreated:Put", key)])),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+
+        let stream = object_created_stream(&sqs, QUEUE_URL, None);
+        futures_util::pin_mut!(stream);
+        assert_eq!(stream.next().await.unwrap().unwrap().key, "a.txt");
+        // 処理中のメッセージ以外は受信しない
+        assert_eq!(sqs.in_flight_count(QUEUE_URL), 1);
+        assert_eq!(stream.next().await.unwrap().unwrap().key, "b.txt");
+        assert_eq!(sqs.message_count(QUEUE_URL), 1);
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use aws_sdk_sqs::{
+    Client,
+    operation::{send_message::SendMessageOutput, send_message_batch::SendMessageBatchOutput},
+    types::{
+        MessageAttributeValue, MessageSystemAttributeNameForSends, MessageSystemAttributeValue,
+        SendMessageBatchRequestEntry,
+    },
+};
+use tokio::sync::{Mutex, Semaphore};
+
+use crate::{error::Error, sqs};
+
+/// Max entries of SendMessageBatch.
+const MAX_BATCH_SIZE: usize = 10;
+
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        Self {
+            rate,
+            capacity: rate,
+            tokens: rate,
+            last: now,
+        }
+    }
+
+    /// Takes up to `n` whole tokens and returns how many were taken, or how long to wait until
+    /// one is available.
+    fn try_take_up_to(&mut self, n: usize, now: Instant) -> Result<usize, Duration> {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last = now;
+        if self.tokens >= 1.0 {
+            let taken = n.min(self.tokens as usize);
+            self.tokens -= taken as f64;
+            Ok(taken)
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
+
+/// Sender for a queue limiting messages per second (token bucket) and concurrent requests,
+/// shared by clones, so batch jobs do not flood consumers or hit SQS throttling.
+///
+/// A batch takes as many tokens as its entries. When fewer tokens are available, the batch is
+/// sent in smaller requests as tokens become available, so the rate holds even below 10
+/// messages per second.
+#[derive(Clone)]
+pub struct RateLimitedSender {
+    client: Client,
+    queue_url: String,
+    bucket: Arc<Mutex<TokenBucket>>,
+    semaphore: Arc<Semaphore>,
+}
+
+impl RateLimitedSender {
+    /// `max_messages_per_sec` and `max_concurrency` are raised to at least 1.
+    pub fn new(
+        client: Client,
+        queue_url: impl Into<String>,
+        max_messages_per_sec: u32,
+        max_concurrency: usize,
+    ) -> Self {
+        Self {
+            client,
+            queue_url: queue_url.into(),
+            bucket: Arc::new(Mutex::new(TokenBucket::new(
+                max_messages_per_sec.max(1) as f64,
+                Instant::now(),
+            ))),
+            semaphore: Arc::new(Semaphore::new(max_concurrency.max(1))),
+        }
+    }
+
+    pub fn queue_url(&self) -> &str {
+        &self.queue_url
+    }
+
+    /// Waits for at least one token and takes up to `n`.
+    async fn acquire_up_to(&self, n: usize) -> usize {
+        loop {
+            let result = self.bucket.lock().await.try_take_up_to(n, Instant::now());
+            match result {
+                Ok(taken) => return taken,
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn send_message(
+        &self,
+        message: Option<String>,
+        message_group_id: Option<String>,
+        message_deduplication_id: Option<String>,
+        delay_seconds: Option<i32>,
+        message_attributes: Option<HashMap<String, MessageAttributeValue>>,
+        message_system_attributes: Option<
+            HashMap<MessageSystemAttributeNameForSends, MessageSystemAttributeValue>,
+        >,
+    ) -> Result<SendMessageOutput, Error> {
+        let _permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("semaphore is never closed");
+        self.acquire_up_to(1).await;
+        sqs::send_message(
+            &self.client,
+            &self.queue_url,
+            message,
+            message_group_id,
+            message_deduplication_id,
+            delay_seconds,
+            message_attributes,
+            message_system_attributes,
+        )
+        .await
+    }
+
+    /// Sends the entries (at most 10), split into several requests when fewer tokens are
+    /// available. If a request fails after earlier ones were sent, returns
+    /// [`Error::PartialBatch`] with their results.
+    pub async fn send_message_batch(
+        &self,
+        entries: Vec<SendMessageBatchRequestEntry>,
+    ) -> Result<SendMessageBatchOutput, Error> {
+        if entries.len() > MAX_BATCH_SIZE {
+            return Err(Error::ValidationError(format!(
+                "too many entries in a batch: {}",
+                entries.len()
+            )));
+        }
+        let mut entries = entries;
+        let mut output: Option<SendMessageBatchOutput> = None;
+        loop {
+            let _permit = self
+                .semaphore
+                .acquire()
+                .await
+                .expect("semaphore is never closed");
+            let taken = self.acquire_up_to(entries.len().max(1)).await;
+            let rest = entries.split_off(taken.min(entries.len()));
+            let sent = match sqs::send_message_batch(&self.client, &self.queue_url, entries).await {
+                Ok(sent) => sent,
+                // 先に送った分の結果はエラーと一緒に返す
+                Err(e) => {
+                    return Err(match output {
+                        Some(sent) => Error::PartialBatch {
+                            sent: Box::new(sent),
+                            source: Box::new(e),
+                        },
+                        None => e,
+                    });
+                }
+            };
+            entries = rest;
+            // 分割して送った結果をまとめる
+            match &mut output {
+                Some(output) => {
+                    output.successful.extend(sent.successful);
+                    output.failed.extend(sent.failed);
+                }
+                None => output = Some(sent),
+            }
+            if entries.is_empty() {
+                return Ok(output.expect("at least one batch is sent"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(5.0, start);
+
+        // 容量はレートと同じ 5 なので 10 件のバッチは 5 件だけ取れる
+        assert_eq!(bucket.try_take_up_to(10, start), Ok(5));
+        assert_eq!(
+            bucket.try_take_up_to(1, start),
+            Err(Duration::from_millis(200))
+        );
+        assert_eq!(
+            bucket.try_take_up_to(10, start + Duration::from_millis(200)),
+            Ok(1)
+        );
+        assert_eq!(
+            bucket.try_take_up_to(3, start + Duration::from_secs(100)),
+            Ok(3)
+        );
+        assert_eq!(
+            bucket.try_take_up_to(10, start + Duration::from_secs(100)),
+            Ok(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_message_batch_returns_sent_results_with_error() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonSQS.SendMessageBatch")
+            .match_body(mockito::Matcher::Regex(r#""Id":"1""#.to_string()))
+            .with_status(200)
+            .with_body(r#"{"Successful":[{"Id":"1","MessageId":"m1","MD5OfMessageBody":"x"}],"Failed":[]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let second = server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonSQS.SendMessageBatch")
+            .match_body(mockito::Matcher::Regex(r#""Id":"2""#.to_string()))
+            .with_status(400)
+            .with_header(
+                "x-amzn-query-error",
+                "AWS.SimpleQueueService.NonExistentQueue;Sender",
+            )
+            .with_body(r#"{"__type":"com.amazonaws.sqs#QueueDoesNotExist","message":"gone"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+        // 1 件/秒なので 2 件のバッチは 2 回に分けて送られる
+        let sender = RateLimitedSender::new(client, format!("{}/queue", server.url()), 1, 1);
+        let entries = ["1", "2"]
+            .into_iter()
+            .map(|id| {
+                SendMessageBatchRequestEntry::builder()
+                    .id(id)
+                    .message_body("body")
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+
+        let err = sender.send_message_batch(entries).await.unwrap_err();
+        first.assert_async().await;
+        second.assert_async().await;
+        let Error::PartialBatch { sent, source } = err else {
+            panic!("unexpected error: {err:?}");
+        };
+        assert_eq!(sent.successful.len(), 1);
+        assert_eq!(sent.successful[0].id, "1");
+        assert!(matches!(*source, Error::AwsSdk(_)));
+    }
+}
@@ -0,0 +1,468 @@
+use std::{collections::HashMap, sync::Arc};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::TokioExecutor,
+};
+use rustls_pki_types::{
+    AlgorithmIdentifier, CertificateDer, InvalidSignature, SignatureVerificationAlgorithm, alg_id,
+    pem::PemObject,
+};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use webpki::EndEntityCert;
+
+use crate::error::Error;
+
+/// An SNS message delivered to an HTTP(S) endpoint, or to an SQS queue without raw message
+/// delivery, after its signature is verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnsMessage {
+    Notification(Notification),
+    SubscriptionConfirmation(SubscriptionConfirmation),
+    UnsubscribeConfirmation(SubscriptionConfirmation),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub message_id: String,
+    pub topic_arn: String,
+    pub subject: Option<String>,
+    pub message: String,
+    /// ISO 8601, e.g. `2026-10-16T03:00:00.000Z`.
+    pub timestamp: String,
+    pub unsubscribe_url: Option<String>,
+    pub message_attributes: HashMap<String, MessageAttribute>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MessageAttribute {
+    /// `String`, `Number`, `Binary` or `String.Array`.
+    #[serde(rename = "Type")]
+    pub data_type: String,
+    #[serde(rename = "Value")]
+    pub value: String,
+}
+
+/// A SubscriptionConfirmation or UnsubscribeConfirmation. Visiting `subscribe_url` confirms
+/// the (re)subscription, see [`SnsVerifier::confirm_subscription`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionConfirmation {
+    pub message_id: String,
+    pub topic_arn: String,
+    pub message: String,
+    pub timestamp: String,
+    pub token: String,
+    pub subscribe_url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawMessage {
+    #[serde(rename = "Type")]
+    message_type: String,
+    message_id: String,
+    topic_arn: String,
+    subject: Option<String>,
+    message: String,
+    timestamp: String,
+    token: Option<String>,
+    signature_version: String,
+    signature: String,
+    #[serde(rename = "SigningCertURL")]
+    signing_cert_url: String,
+    #[serde(rename = "SubscribeURL")]
+    subscribe_url: Option<String>,
+    #[serde(rename = "UnsubscribeURL")]
+    unsubscribe_url: Option<String>,
+    #[serde(default)]
+    message_attributes: HashMap<String, MessageAttribute>,
+}
+
+impl RawMessage {
+    fn parse(body: &str) -> Result<Self, Error> {
+        serde_json::from_str(body)
+            .map_err(|e| Error::ValidationError(format!("invalid SNS message: {e}")))
+    }
+
+    /// The `{name}\n{value}\n` pairs the signature is computed over, in the order of the
+    /// message type.
+    fn string_to_sign(&self) -> Result<String, Error> {
+        let fields = match self.message_type.as_str() {
+            "Notification" => vec![
+                ("Message", Some(&self.message)),
+                ("MessageId", Some(&self.message_id)),
+                ("Subject", self.subject.as_ref()),
+                ("Timestamp", Some(&self.timestamp)),
+                ("TopicArn", Some(&self.topic_arn)),
+                ("Type", Some(&self.message_type)),
+            ],
+            "SubscriptionConfirmation" | "UnsubscribeConfirmation" => vec![
+                ("Message", Some(&self.message)),
+                ("MessageId", Some(&self.message_id)),
+                ("SubscribeURL", self.subscribe_url.as_ref()),
+                ("Timestamp", Some(&self.timestamp)),
+                ("Token", self.token.as_ref()),
+                ("TopicArn", Some(&self.topic_arn)),
+                ("Type", Some(&self.message_type)),
+            ],
+            other => {
+                return Err(Error::ValidationError(format!(
+                    "unknown SNS message type: {other}"
+                )));
+            }
+        };
+        Ok(fields
+            .into_iter()
+            .filter_map(|(name, value)| value.map(|value| format!("{name}\n{value}\n")))
+            .collect())
+    }
+
+    fn verify(&self, certificate_pem: &[u8]) -> Result<(), Error> {
+        let algorithm: &dyn SignatureVerificationAlgorithm = match self.signature_version.as_str() {
+            "1" => &RsaPkcs1Sha1,
+            "2" => webpki::ring::RSA_PKCS1_2048_8192_SHA256,
+            other => {
+                return Err(Error::InvalidSignature(format!(
+                    "unsupported SignatureVersion: {other}"
+                )));
+            }
+        };
+        let der = CertificateDer::from_pem_slice(certificate_pem)
+            .map_err(|e| Error::InvalidSignature(format!("invalid signing certificate: {e}")))?;
+        let certificate = EndEntityCert::try_from(&der)
+            .map_err(|e| Error::InvalidSignature(format!("invalid signing certificate: {e}")))?;
+        let signature = STANDARD
+            .decode(&self.signature)
+            .map_err(|e| Error::InvalidSignature(format!("invalid Signature: {e}")))?;
+        certificate
+            .verify_signature(algorithm, self.string_to_sign()?.as_bytes(), &signature)
+            .map_err(|e| {
+                Error::InvalidSignature(format!("signature of {} is invalid: {e}", self.message_id))
+            })
+    }
+
+    fn into_message(self) -> Result<SnsMessage, Error> {
+        if self.message_type == "Notification" {
+            return Ok(SnsMessage::Notification(Notification {
+                message_id: self.message_id,
+                topic_arn: self.topic_arn,
+                subject: self.subject,
+                message: self.message,
+                timestamp: self.timestamp,
+                unsubscribe_url: self.unsubscribe_url,
+                message_attributes: self.message_attributes,
+            }));
+        }
+        let (Some(token), Some(subscribe_url)) = (self.token, self.subscribe_url) else {
+            return Err(Error::ValidationError(format!(
+                "{} without Token or SubscribeURL",
+                self.message_type
+            )));
+        };
+        let confirmation = SubscriptionConfirmation {
+            message_id: self.message_id,
+            topic_arn: self.topic_arn,
+            message: self.message,
+            timestamp: self.timestamp,
+            token,
+            subscribe_url,
+        };
+        if self.message_type == "SubscriptionConfirmation" {
+            Ok(SnsMessage::SubscriptionConfirmation(confirmation))
+        } else {
+            Ok(SnsMessage::UnsubscribeConfirmation(confirmation))
+        }
+    }
+}
+
+/// SignatureVersion 1 (SHA1withRSA), which webpki does not provide.
+#[derive(Debug)]
+struct RsaPkcs1Sha1;
+
+impl SignatureVerificationAlgorithm for RsaPkcs1Sha1 {
+    fn verify_signature(
+        &self,
+        public_key: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<(), InvalidSignature> {
+        ring::signature::UnparsedPublicKey::new(
+            &ring::signature::RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY,
+            public_key,
+        )
+        .verify(message, signature)
+        .map_err(|_| InvalidSignature)
+    }
+
+    fn public_key_alg_id(&self) -> AlgorithmIdentifier {
+        alg_id::RSA_ENCRYPTION
+    }
+
+    fn signature_alg_id(&self) -> AlgorithmIdentifier {
+        // sha1WithRSAEncryption (1.2.840.113549.1.1.5), NULL
+        AlgorithmIdentifier::from_slice(&[
+            0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x05, 0x05, 0x00,
+        ])
+    }
+}
+
+/// Verifies the signature of an SNS message with the certificate in PEM, e.g. one pinned in
+/// the application, without fetching its SigningCertURL.
+pub fn verify_message_with_certificate(
+    body: &str,
+    certificate_pem: &[u8],
+) -> Result<SnsMessage, Error> {
+    let raw = RawMessage::parse(body)?;
+    raw.verify(certificate_pem)?;
+    raw.into_message()
+}
+
+/// Checks that `url` is `https://sns.{region}.amazonaws.com[.cn]/...`, so a forged message
+/// cannot make the verifier fetch a certificate or confirm a subscription on another host.
+fn validate_sns_url(url: &str) -> Result<(), Error> {
+    let region = url
+        .strip_prefix("https://")
+        .and_then(|rest| rest.split(['/', '?']).next())
+        .and_then(|host| host.strip_prefix("sns."))
+        .and_then(|host| {
+            host.strip_suffix(".amazonaws.com")
+                .or_else(|| host.strip_suffix(".amazonaws.com.cn"))
+        });
+    match region {
+        Some(region)
+            if !region.is_empty()
+                && region
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') =>
+        {
+            Ok(())
+        }
+        _ => Err(Error::ValidationError(format!("not an SNS URL: {url}"))),
+    }
+}
+
+/// Verifies SNS messages with the certificate of their SigningCertURL, fetched over HTTPS and
+/// cached by URL.
+pub struct SnsVerifier {
+    http_client: Client<HttpsConnector<HttpConnector>, Empty<Bytes>>,
+    certificates: Mutex<HashMap<String, Arc<Bytes>>>,
+}
+
+impl SnsVerifier {
+    pub fn new() -> Result<Self, Error> {
+        let connector = HttpsConnectorBuilder::new()
+            .with_provider_and_native_roots(rustls::crypto::ring::default_provider())
+            .map_err(|e| Error::Http(format!("failed to load root certificates: {e}")))?
+            .https_only()
+            .enable_http1()
+            .build();
+        Ok(Self {
+            http_client: Client::builder(TokioExecutor::new()).build(connector),
+            certificates: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Parses `body` (the HTTP request body, or the SQS message body) and verifies its
+    /// signature.
+    pub async fn verify(&self, body: &str) -> Result<SnsMessage, Error> {
+        let raw = RawMessage::parse(body)?;
+        validate_sns_url(&raw.signing_cert_url)?;
+        if !raw.signing_cert_url.ends_with(".pem") {
+            return Err(Error::ValidationError(format!(
+                "SigningCertURL is not a certificate: {}",
+                raw.signing_cert_url
+            )));
+        }
+        let certificate = self.certificate(&raw.signing_cert_url).await?;
+        raw.verify(&certificate)?;
+        raw.into_message()
+    }
+
+    /// Verifies `body` like [`verify`](Self::verify), and confirms it when it is a
+    /// SubscriptionConfirmation.
+    pub async fn verify_and_confirm(&self, body: &str) -> Result<SnsMessage, Error> {
+        let message = self.verify(body).await?;
+        if let SnsMessage::SubscriptionConfirmation(confirmation) = &message {
+            self.confirm_subscription(confirmation).await?;
+        }
+        Ok(message)
+    }
+
+    /// Confirms the subscription by visiting its SubscribeURL.
+    pub async fn confirm_subscription(
+        &self,
+        confirmation: &SubscriptionConfirmation,
+    ) -> Result<(), Error> {
+        validate_sns_url(&confirmation.subscribe_url)?;
+        self.get(&confirmation.subscribe_url).await?;
+        Ok(())
+    }
+
+    async fn certificate(&self, url: &str) -> Result<Arc<Bytes>, Error> {
+        if let Some(certificate) = self.certificates.lock().await.get(url) {
+            return Ok(certificate.clone());
+        }
+        // 取得中はロックを保持しない (同時に取得しても同じ証明書になる)
+        let certificate = Arc::new(self.get(url).await?);
+        self.certificates
+            .lock()
+            .await
+            .insert(url.to_string(), certificate.clone());
+        Ok(certificate)
+    }
+
+    async fn get(&self, url: &str) -> Result<Bytes, Error> {
+        let uri = url
+            .parse()
+            .map_err(|e| Error::ValidationError(format!("invalid URL {url}: {e}")))?;
+        let response = self
+            .http_client
+            .get(uri)
+            .await
+            .map_err(|e| Error::Http(format!("GET {url}: {e}")))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Http(format!("GET {url}: {status}")));
+        }
+        Ok(response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| Error::Http(format!("GET {url}: {e}")))?
+            .to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Self-signed, for tests only.
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIDGzCCAgOgAwIBAgIULOEGwem+uk17+eevx/Vq+qOiJoQwDQYJKoZIhvcNAQEL
+BQAwHDEaMBgGA1UEAwwRc25zLmFtYXpvbmF3cy5jb20wIBcNMjYxMDE2MjExODAw
+WhgPMjEyNjA5MjIyMTE4MDBaMBwxGjAYBgNVBAMMEXNucy5hbWF6b25hd3MuY29t
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAofdptW0K0tKmcVXUbmNE
+AiC8URIfpPJqvpqsej4yMSI4W6pZFk0QpengSV3YxW4un0Iq4Eb4As5TIk+6DwwA
+3UDpJ4VmLKWsKMIJDW/cuUfQ+MY7iBoyEyTmIs8sr+knqIvmnsE4zX+mAd8NQvHT
+eEjxwGB3YLlILDZSs2JI/hC4iA3kGr529DJvyY5BvJTxTyA+nbsuXsRLgdygleKL
+gZ5VrLwK/N6nA85JzhlZA1uhOD1cb6nP0qYsEr1Rdhc+Xw8gwnzG50FpSRrQ4o8l
+gslusTrO6MQGJGuaET6FsVQoGO9uTtaWuah90pd8pmsT1mB9z8Dq+VMgWDUxE9cN
+7QIDAQABo1MwUTAdBgNVHQ4EFgQU9uIuWuea++yqZZS57UF+qFqvLHgwHwYDVR0j
+BBgwFoAU9uIuWuea++yqZZS57UF+qFqvLHgwDwYDVR0TAQH/BAUwAwEB/zANBgkq
+hkiG9w0BAQsFAAOCAQEAKnXoT+G6yTHXx+OkZtMHTJC9uR+tXEYfYjHeQgfqTFeC
+9fHDhej8P3uL3hqj40+bAi/YvIxPXF+NUKUlzxMDLDe++nm/lRRqkM9EGnqMqoUH
+p8n+4MQXQpWC8AnPWYFvochVZTZbnf0PsU37JG7gil41r+ZBzRZvoQHCMKjCb4ua
+oyIYKiUqjNgQINVnUlsXSiMYjlptYbKixs5/ohEdyPmjeXWJKBXC3FConS2qVtHe
+uxA3DYUMeYzYU5+tmVd8nIqjkZ7vOWjeqCwBNDdwzAXwj9DqjG3QG3pnDZf5sVN0
+8kSxOySvhMHUF9DkT8ITIx2ka1k1RD4MA2CXhd1ptQ==
+-----END CERTIFICATE-----
+";
+
+    fn notification() -> serde_json::Value {
+        json!({
+            "Type": "Notification",
+            "MessageId": "22b80b92-fdea-4c2c-8f9d-bdfb0c7bf324",
+            "TopicArn": "arn:aws:sns:ap-northeast-1:123456789012:orders",
+            "Subject": "Order",
+            "Message": r#"{"order_id":"1"}"#,
+            "Timestamp": "2026-10-16T03:00:00.000Z",
+            "SignatureVersion": "2",
+            "Signature": "LGb2T9zUTve3n2aUtenfDG8RQuTNpm1+GSJEr2LD3aOpCaEGzwc3CPsybeN/mKk/TqUvGhWESebbQrcY9DBxmo3PTLduzVlmaS1pE5y/G+IG/YRSLns6B3xl8+x7YnEiTt5QiBH/2iVq+/YNZxC0keB45/ZjW8CtRw/QD3hJMzn+0x1qtWWezPWSws3F53c9P0u144M+Qq6X0fLrW9tMj3CYQa/PHEO+9V5Ca6D6Eit4iqiWpkCdlcC0OihmG0ZfXDzD4kHbtzlhbqGIB0SFh2vuKjbOV/bCiWuSCEXXcO2lQ6P6OBLJzQnvvWfJbhg9p7dZt3gI1ew9bcrizW4A0A==",
+            "SigningCertURL": "https://sns.ap-northeast-1.amazonaws.com/SimpleNotificationService-0000000000000000000000.pem",
+            "UnsubscribeURL": "https://sns.ap-northeast-1.amazonaws.com/?Action=Unsubscribe&SubscriptionArn=arn:aws:sns:ap-northeast-1:123456789012:orders:0",
+            "MessageAttributes": {"EventType": {"Type": "String", "Value": "order.created"}},
+        })
+    }
+
+    #[test]
+    fn test_verify_notification() {
+        let message =
+            verify_message_with_certificate(&notification().to_string(), CERTIFICATE.as_bytes())
+                .unwrap();
+        let SnsMessage::Notification(notif) = message else {
+            panic!("unexpected message: {message:?}");
+        };
+        assert_eq!(notif.message, r#"{"order_id":"1"}"#);
+        assert_eq!(notif.subject.as_deref(), Some("Order"));
+        assert_eq!(
+            notif.message_attributes["EventType"],
+            MessageAttribute {
+                data_type: "String".to_string(),
+                value: "order.created".to_string(),
+            }
+        );
+
+        // 署名対象のフィールドが改ざんされている
+        let mut tampered = notification();
+        tampered["Message"] = r#"{"order_id":"2"}"#.into();
+        assert!(matches!(
+            verify_message_with_certificate(&tampered.to_string(), CERTIFICATE.as_bytes()),
+            Err(Error::InvalidSignature(_))
+        ));
+        let mut unsupported = notification();
+        unsupported["SignatureVersion"] = "3".into();
+        assert!(matches!(
+            verify_message_with_certificate(&unsupported.to_string(), CERTIFICATE.as_bytes()),
+            Err(Error::InvalidSignature(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_subscription_confirmation() {
+        let subscribe_url = "https://sns.ap-northeast-1.amazonaws.com/?Action=ConfirmSubscription&TopicArn=arn:aws:sns:ap-northeast-1:123456789012:orders&Token=2336412f37";
+        let body = json!({
+            "Type": "SubscriptionConfirmation",
+            "MessageId": "165545c9-2a5c-472c-8df2-7ff2be2b3b1b",
+            "Token": "2336412f37",
+            "TopicArn": "arn:aws:sns:ap-northeast-1:123456789012:orders",
+            "Message": "You have chosen to subscribe to the topic arn:aws:sns:ap-northeast-1:123456789012:orders.",
+            "SubscribeURL": subscribe_url,
+            "Timestamp": "2026-10-16T02:00:00.000Z",
+            "SignatureVersion": "1",
+            "Signature": "A31J9lPdf7Dw/9wAJHMtIquXvXgdL0rC5f8wMyeRpp1E3k5eUK9fs9acAew0NWgkxUQNbNJ542NotpHfu0+PJT2OQCQIFriesnbydTWekmTCJREZ+dnbU9LSj2vCxje+0LArYaTjlQDeBrVMDa0xnrKzaFC2mGl+i5tegiB/BcqeGsQ21VRIahWfS1JTxG+r0TaJLvgCe0H+1tWMUR+dBqhNfcErX/P5C5TK3WTG+1qkKR9JYr4XXbNbXDIFEGK3jO2WXFG6MyxZD63FGc6KS6kpLLJ7YwCCsJ5VukagKQj+nEhLbAW0lSVTfHYU2nupDqjF8dHNW28oY4uM17mrGA==",
+            "SigningCertURL": "https://sns.ap-northeast-1.amazonaws.com/SimpleNotificationService-0000000000000000000000.pem",
+        });
+        let message =
+            verify_message_with_certificate(&body.to_string(), CERTIFICATE.as_bytes()).unwrap();
+        assert_eq!(
+            message,
+            SnsMessage::SubscriptionConfirmation(SubscriptionConfirmation {
+                message_id: "165545c9-2a5c-472c-8df2-7ff2be2b3b1b".to_string(),
+                topic_arn: "arn:aws:sns:ap-northeast-1:123456789012:orders".to_string(),
+                message: "You have chosen to subscribe to the topic arn:aws:sns:ap-northeast-1:123456789012:orders.".to_string(),
+                timestamp: "2026-10-16T02:00:00.000Z".to_string(),
+                token: "2336412f37".to_string(),
+                subscribe_url: subscribe_url.to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_sns_url() {
+        for url in [
+            "https://sns.us-east-1.amazonaws.com/SimpleNotificationService-abc.pem",
+            "https://sns.cn-north-1.amazonaws.com.cn/SimpleNotificationService-abc.pem",
+            "https://sns.ap-northeast-1.amazonaws.com/?Action=ConfirmSubscription",
+        ] {
+            assert!(validate_sns_url(url).is_ok(), "{url}");
+        }
+        for url in [
+            "http://sns.us-east-1.amazonaws.com/cert.pem",
+            "https://sns.us-east-1.amazonaws.com.example.com/cert.pem",
+            "https://sns.us-east-1.amazonaws.com@example.com/cert.pem",
+            "https://sns.us-east-1.amazonaws.com:8443/cert.pem",
+            "https://example.com/sns.us-east-1.amazonaws.com/cert.pem",
+            "https://sns..amazonaws.com/cert.pem",
+        ] {
+            assert!(validate_sns_url(url).is_err(), "{url}");
+        }
+    }
+}
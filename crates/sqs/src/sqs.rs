@@ -7,7 +7,8 @@ use aws_sdk_sqs::{
         create_queue::CreateQueueOutput, delete_message::DeleteMessageOutput,
         delete_message_batch::DeleteMessageBatchOutput, delete_queue::DeleteQueueOutput,
         receive_message::ReceiveMessageOutput, send_message::SendMessageOutput,
-        send_message_batch::SendMessageBatchOutput,
+        send_message_batch::SendMessageBatchOutput, tag_queue::TagQueueOutput,
+        untag_queue::UntagQueueOutput,
     },
     types::{
         DeleteMessageBatchRequestEntry, MessageAttributeValue, MessageSystemAttributeName,
@@ -16,7 +17,13 @@ use aws_sdk_sqs::{
     },
 };
 
-use crate::error::from_aws_sdk_error;
+use aws_smithy_types_convert::stream::PaginationStreamExt;
+use futures_util::{TryStream, TryStreamExt};
+
+use crate::{
+    api::SqsApi,
+    error::{AwsUtilError, from_aws_sdk_error},
+};
 
 pub async fn create_queue(
     client: &Client,
@@ -46,6 +53,141 @@ pub async fn delete_queue(
         .map_err(from_aws_sdk_error)
 }
 
+/// Streams the URLs of the queues whose name starts with `prefix` (all queues if None).
+pub fn list_queues_stream(
+    client: &Client,
+    prefix: Option<impl Into<String>>,
+) -> impl TryStream<Ok = String, Error = Error> + Unpin {
+    client
+        .list_queues()
+        .set_queue_name_prefix(prefix.map(Into::into))
+        .into_paginator()
+        .send()
+        .into_stream_03x()
+        .map_err(from_aws_sdk_error)
+        .map_ok(|s| {
+            futures_util::stream::iter(s.queue_urls.unwrap_or_default().into_iter().map(Ok))
+        })
+        .try_flatten()
+}
+
+/// URL of the queue named `queue_name`, None if it does not exist.
+pub async fn find_queue_url_by_name(
+    client: &Client,
+    queue_name: impl Into<String>,
+) -> Result<Option<String>, Error> {
+    let result = client
+        .get_queue_url()
+        .queue_name(queue_name)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error);
+    match result {
+        Ok(output) => Ok(output.queue_url),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Adds or overwrites tags of the queue.
+pub async fn tag_queue(
+    client: &Client,
+    queue_url: impl Into<String>,
+    tags: HashMap<String, String>,
+) -> Result<TagQueueOutput, Error> {
+    client
+        .tag_queue()
+        .queue_url(queue_url)
+        .set_tags(Some(tags))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+pub async fn untag_queue(
+    client: &Client,
+    queue_url: impl Into<String>,
+    tag_keys: Vec<String>,
+) -> Result<UntagQueueOutput, Error> {
+    client
+        .untag_queue()
+        .queue_url(queue_url)
+        .set_tag_keys(Some(tag_keys))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)
+}
+
+/// Tags of the queue, empty if it has none.
+pub async fn list_queue_tags(
+    client: &Client,
+    queue_url: impl Into<String>,
+) -> Result<HashMap<String, String>, Error> {
+    let output = client
+        .list_queue_tags()
+        .queue_url(queue_url)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(output.tags.unwrap_or_default())
+}
+
+/// Changes made by [`ensure_tags`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagChanges {
+    /// Tags added or whose value was changed
+    pub set: HashMap<String, String>,
+    pub removed: Vec<String>,
+}
+
+impl TagChanges {
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Reconciles the tags of the queue with `tags`, calling TagQueue / UntagQueue only for the
+/// differences. With `remove_others`, tags not in `tags` are removed.
+pub async fn ensure_tags(
+    client: &Client,
+    queue_url: impl Into<String>,
+    tags: HashMap<String, String>,
+    remove_others: bool,
+) -> Result<TagChanges, Error> {
+    let queue_url = queue_url.into();
+    let current = list_queue_tags(client, &queue_url).await?;
+    let changes = tag_changes(&current, tags, remove_others);
+    if !changes.set.is_empty() {
+        tag_queue(client, &queue_url, changes.set.clone()).await?;
+    }
+    if !changes.removed.is_empty() {
+        untag_queue(client, &queue_url, changes.removed.clone()).await?;
+    }
+    Ok(changes)
+}
+
+fn tag_changes(
+    current: &HashMap<String, String>,
+    tags: HashMap<String, String>,
+    remove_others: bool,
+) -> TagChanges {
+    let mut removed = if remove_others {
+        current
+            .keys()
+            .filter(|key| !tags.contains_key(*key))
+            .cloned()
+            .collect()
+    } else {
+        Vec::new()
+    };
+    removed.sort();
+    let set = tags
+        .into_iter()
+        .filter(|(key, value)| current.get(key) != Some(value))
+        .collect();
+    TagChanges { set, removed }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn receive_message(
     client: &Client,
@@ -71,6 +213,129 @@ pub async fn receive_message(
         .map_err(from_aws_sdk_error)
 }
 
+/// Options of [`receive_with`]. By default receives up to 10 messages with 20 seconds of
+/// long polling, the visibility timeout of the queue and no attributes.
+///
+/// ```
+/// use aws_utils_sqs::sqs::ReceiveOptions;
+///
+/// let options = ReceiveOptions::default()
+///     .max_number_of_messages(5)
+///     .visibility_timeout(60)
+///     .all_message_attributes()
+///     .all_system_attributes();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiveOptions {
+    pub(crate) max_number_of_messages: i32,
+    pub(crate) wait_time_seconds: i32,
+    pub(crate) visibility_timeout: Option<i32>,
+    pub(crate) message_attribute_names: Option<Vec<String>>,
+    pub(crate) message_system_attribute_names: Option<Vec<MessageSystemAttributeName>>,
+    pub(crate) receive_request_attempt_id: Option<String>,
+}
+
+impl Default for ReceiveOptions {
+    fn default() -> Self {
+        Self {
+            max_number_of_messages: 10,
+            wait_time_seconds: 20,
+            visibility_timeout: None,
+            message_attribute_names: None,
+            message_system_attribute_names: None,
+            receive_request_attempt_id: None,
+        }
+    }
+}
+
+impl ReceiveOptions {
+    /// 1 to 10.
+    pub fn max_number_of_messages(mut self, max_number_of_messages: i32) -> Self {
+        self.max_number_of_messages = max_number_of_messages;
+        self
+    }
+
+    /// 0 (short polling) to 20.
+    pub fn wait_time_seconds(mut self, wait_time_seconds: i32) -> Self {
+        self.wait_time_seconds = wait_time_seconds;
+        self
+    }
+
+    /// 0 to 43200 (12 hours). The visibility timeout of the queue if not set.
+    pub fn visibility_timeout(mut self, visibility_timeout: i32) -> Self {
+        self.visibility_timeout = Some(visibility_timeout);
+        self
+    }
+
+    /// Message attributes to receive, e.g. `["EventType", "Trace.*"]`.
+    pub fn message_attribute_names(
+        mut self,
+        names: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.message_attribute_names = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn all_message_attributes(self) -> Self {
+        self.message_attribute_names(["All"])
+    }
+
+    pub fn message_system_attribute_names(
+        mut self,
+        names: impl IntoIterator<Item = MessageSystemAttributeName>,
+    ) -> Self {
+        self.message_system_attribute_names = Some(names.into_iter().collect());
+        self
+    }
+
+    /// All system attributes, for [`MessageExt`](crate::message::MessageExt).
+    pub fn all_system_attributes(self) -> Self {
+        self.message_system_attribute_names([MessageSystemAttributeName::All])
+    }
+
+    /// Deduplication of retried receives of FIFO queues.
+    pub fn receive_request_attempt_id(
+        mut self,
+        receive_request_attempt_id: impl Into<String>,
+    ) -> Self {
+        self.receive_request_attempt_id = Some(receive_request_attempt_id.into());
+        self
+    }
+
+    /// Checks the ranges SQS accepts, before sending the request.
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(1..=10).contains(&self.max_number_of_messages) {
+            return Err(Error::ValidationError(format!(
+                "max_number_of_messages must be 1 to 10: {}",
+                self.max_number_of_messages
+            )));
+        }
+        if !(0..=20).contains(&self.wait_time_seconds) {
+            return Err(Error::ValidationError(format!(
+                "wait_time_seconds must be 0 to 20: {}",
+                self.wait_time_seconds
+            )));
+        }
+        if let Some(visibility_timeout) = self.visibility_timeout
+            && !(0..=43_200).contains(&visibility_timeout)
+        {
+            return Err(Error::ValidationError(format!(
+                "visibility_timeout must be 0 to 43200: {visibility_timeout}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// [`receive_message`] with [`ReceiveOptions`].
+pub async fn receive_with(
+    client: &Client,
+    queue_url: impl Into<String>,
+    options: &ReceiveOptions,
+) -> Result<ReceiveMessageOutput, Error> {
+    SqsApi::receive_with(client, &queue_url.into(), options).await
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn send_message(
     client: &Client,
@@ -139,3 +404,109 @@ pub async fn delete_message_batch(
         .await
         .map_err(from_aws_sdk_error)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receive_options_validate() {
+        assert!(ReceiveOptions::default().validate().is_ok());
+        assert!(
+            ReceiveOptions::default()
+                .max_number_of_messages(0)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            ReceiveOptions::default()
+                .max_number_of_messages(11)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            ReceiveOptions::default()
+                .wait_time_seconds(21)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            ReceiveOptions::default()
+                .visibility_timeout(43_201)
+                .validate()
+                .is_err()
+        );
+        assert_eq!(
+            ReceiveOptions::default()
+                .all_message_attributes()
+                .message_attribute_names,
+            Some(vec!["All".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_receive_with() {
+        use crate::{api::SqsApi, in_memory::InMemorySqs};
+
+        const QUEUE_URL: &str = "https://sqs.ap-northeast-1.amazonaws.com/123456789012/jobs";
+        let sqs = InMemorySqs::new();
+        for body in ["a", "b", "c"] {
+            sqs.send_message(
+                QUEUE_URL,
+                Some(body.to_string()),
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        }
+        let options = ReceiveOptions::default()
+            .max_number_of_messages(2)
+            .wait_time_seconds(0)
+            .all_system_attributes();
+        let messages = sqs
+            .receive_with(QUEUE_URL, &options)
+            .await
+            .unwrap()
+            .messages
+            .unwrap_or_default();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].attributes().is_some());
+
+        let invalid = ReceiveOptions::default().max_number_of_messages(11);
+        assert!(sqs.receive_with(QUEUE_URL, &invalid).await.is_err());
+    }
+
+    #[test]
+    fn test_tag_changes() {
+        let current = HashMap::from([
+            ("team".to_string(), "a".to_string()),
+            ("env".to_string(), "dev".to_string()),
+            ("owner".to_string(), "x".to_string()),
+        ]);
+        let tags = HashMap::from([
+            ("team".to_string(), "a".to_string()),
+            ("env".to_string(), "prod".to_string()),
+            ("cost-center".to_string(), "123".to_string()),
+        ]);
+
+        let changes = tag_changes(&current, tags.clone(), false);
+        assert_eq!(
+            changes.set,
+            HashMap::from([
+                ("env".to_string(), "prod".to_string()),
+                ("cost-center".to_string(), "123".to_string()),
+            ])
+        );
+        assert!(changes.removed.is_empty());
+
+        let changes = tag_changes(&current, tags.clone(), true);
+        assert_eq!(changes.removed, vec!["owner".to_string()]);
+
+        let changes = tag_changes(&tags, tags.clone(), true);
+        assert!(changes.is_empty());
+    }
+}
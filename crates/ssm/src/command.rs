@@ -0,0 +1,173 @@
+use std::{collections::HashMap, time::Duration};
+
+use aws_sdk_ssm::{
+    Client,
+    types::{CommandInvocation, CommandInvocationStatus, CommandStatus, Target},
+};
+use aws_smithy_types_convert::stream::PaginationStreamExt;
+use futures_util::{TryStreamExt, stream};
+
+use crate::error::{Error, from_aws_sdk_error};
+
+pub const AWS_RUN_SHELL_SCRIPT: &str = "AWS-RunShellScript";
+
+/// Runs a document (e.g. [`AWS_RUN_SHELL_SCRIPT`] with the `commands` parameter) on the
+/// instances of `targets`, and returns the command ID.
+pub async fn send_command(
+    client: &Client,
+    document_name: impl Into<String>,
+    targets: Vec<Target>,
+    parameters: HashMap<String, Vec<String>>,
+    comment: Option<impl Into<String>>,
+) -> Result<String, Error> {
+    let output = client
+        .send_command()
+        .document_name(document_name)
+        .set_targets(Some(targets))
+        .set_parameters(Some(parameters))
+        .set_comment(comment.map(Into::into))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    output
+        .command
+        .and_then(|c| c.command_id)
+        .ok_or_else(|| Error::ValidationError("command_id is None".to_string()))
+}
+
+/// Invocations of the command, one per instance.
+pub async fn list_command_invocations(
+    client: &Client,
+    command_id: impl Into<String>,
+) -> Result<Vec<CommandInvocation>, Error> {
+    client
+        .list_command_invocations()
+        .command_id(command_id)
+        .into_paginator()
+        .send()
+        .into_stream_03x()
+        .map_err(from_aws_sdk_error)
+        .map_ok(|o| {
+            stream::iter(
+                o.command_invocations
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Ok),
+            )
+        })
+        .try_flatten()
+        .try_collect()
+        .await
+}
+
+/// Waits until the invocations on all instances finish, checking every `check_duration`,
+/// and returns them. Check the status of each invocation, since some instances may fail.
+pub async fn wait_for_command(
+    client: &Client,
+    command_id: impl Into<String>,
+    timeout_duration: Duration,
+    check_duration: Duration,
+) -> Result<Vec<CommandInvocation>, Error> {
+    let command_id = command_id.into();
+    tokio::time::timeout(timeout_duration, async {
+        loop {
+            let invocations = list_command_invocations(client, &command_id).await?;
+            // 送信直後はインボケーションがまだ作られていないことがある
+            if !invocations.is_empty()
+                && invocations
+                    .iter()
+                    .all(|i| i.status().is_some_and(is_finished))
+                && is_command_finished(client, &command_id).await?
+            {
+                return Ok(invocations);
+            }
+            tokio::time::sleep(check_duration).await;
+        }
+    })
+    .await?
+}
+
+async fn is_command_finished(client: &Client, command_id: &str) -> Result<bool, Error> {
+    let output = client
+        .list_commands()
+        .command_id(command_id)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(output.commands().first().is_some_and(|c| {
+        matches!(
+            c.status(),
+            Some(
+                CommandStatus::Success
+                    | CommandStatus::Failed
+                    | CommandStatus::Cancelled
+                    | CommandStatus::TimedOut
+            )
+        )
+    }))
+}
+
+fn is_finished(status: &CommandInvocationStatus) -> bool {
+    !matches!(
+        status,
+        CommandInvocationStatus::Pending
+            | CommandInvocationStatus::InProgress
+            | CommandInvocationStatus::Delayed
+            | CommandInvocationStatus::Cancelling
+    )
+}
+
+/// Result of a command on an instance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandOutput {
+    pub status: Option<CommandInvocationStatus>,
+    /// -1 while the command is running
+    pub response_code: i32,
+    /// Up to 24,000 characters. The full output is only in S3 or CloudWatch Logs if configured.
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    pub fn is_success(&self) -> bool {
+        self.status == Some(CommandInvocationStatus::Success)
+    }
+}
+
+/// Output of the command on an instance. `plugin_name` selects a step of a document with
+/// multiple steps.
+pub async fn get_command_output(
+    client: &Client,
+    command_id: impl Into<String>,
+    instance_id: impl Into<String>,
+    plugin_name: Option<impl Into<String>>,
+) -> Result<CommandOutput, Error> {
+    let output = client
+        .get_command_invocation()
+        .command_id(command_id)
+        .instance_id(instance_id)
+        .set_plugin_name(plugin_name.map(Into::into))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(CommandOutput {
+        status: output.status,
+        response_code: output.response_code,
+        stdout: output.standard_output_content.unwrap_or_default(),
+        stderr: output.standard_error_content.unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_finished() {
+        assert!(!is_finished(&CommandInvocationStatus::InProgress));
+        assert!(!is_finished(&CommandInvocationStatus::Delayed));
+        assert!(is_finished(&CommandInvocationStatus::Success));
+        assert!(is_finished(&CommandInvocationStatus::Failed));
+        assert!(is_finished(&CommandInvocationStatus::TimedOut));
+    }
+}
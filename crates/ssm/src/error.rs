@@ -1,3 +1,6 @@
+use aws_sdk_ssm::{error::ProvideErrorMetadata, operation::RequestId};
+pub use aws_utils_core::error::AwsUtilError;
+use aws_utils_core::error::{is_not_found_code, is_retryable_sdk_error};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,8 +16,38 @@ pub enum Error {
 
     #[error("Parameter not found")]
     NotFound,
+
+    #[error("Timeout {0}")]
+    Timeout(#[from] tokio::time::error::Elapsed),
 }
 
 pub(crate) fn from_aws_sdk_error(e: impl Into<aws_sdk_ssm::Error>) -> Error {
     Error::AwsSdk(Box::new(e.into()))
 }
+
+impl AwsUtilError for Error {
+    fn error_code(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.code(),
+            _ => None,
+        }
+    }
+
+    fn aws_request_id(&self) -> Option<&str> {
+        match self {
+            Error::AwsSdk(e) => e.request_id(),
+            _ => None,
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            Error::AwsSdk(e) => is_retryable_sdk_error(e.as_ref()),
+            _ => false,
+        }
+    }
+
+    fn is_not_found(&self) -> bool {
+        matches!(self, Error::NotFound) || self.error_code().is_some_and(is_not_found_code)
+    }
+}
@@ -6,10 +6,14 @@ use aws_config::{
 };
 use aws_sdk_ssm::{Client, config::SharedInterceptor};
 
+pub mod command;
 pub mod error;
 pub mod ssm;
 
 pub use aws_sdk_ssm;
+#[cfg(feature = "metrics")]
+pub use aws_utils_core::metrics;
+pub use aws_utils_core::preload;
 
 pub async fn make_client_with_timeout_default(endpoint_url: Option<String>) -> Client {
     make_client_with_timeout(
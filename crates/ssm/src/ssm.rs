@@ -1,6 +1,20 @@
-use aws_sdk_ssm::{Client, operation::get_parameter::GetParameterOutput};
+use std::collections::HashMap;
 
-use crate::error::{Error, from_aws_sdk_error};
+use aws_sdk_ssm::{
+    Client,
+    operation::get_parameter::GetParameterOutput,
+    types::{Parameter, ParameterHistory, ParameterTier, ParameterType},
+};
+use aws_smithy_types_convert::stream::PaginationStreamExt;
+use aws_utils_core::preload::{ConfigMap, MissingKeys, MissingReason};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::{TryStream, TryStreamExt, stream};
+use serde_json::json;
+
+use crate::error::{AwsUtilError, Error, from_aws_sdk_error};
+
+/// Max names of a GetParameters call.
+const MAX_GET_PARAMETERS: usize = 10;
 
 pub async fn get_parameter_raw(
     client: &Client,
@@ -24,10 +38,340 @@ pub async fn get_parameter(client: &Client, name: &str) -> Result<String, Error>
         .map(|s| s.to_string())
 }
 
+/// Value of a version of the parameter, e.g. to pin a validated version.
+pub async fn get_parameter_version(
+    client: &Client,
+    name: &str,
+    version: i64,
+) -> Result<String, Error> {
+    get_parameter_selector(client, name, &version.to_string()).await
+}
+
+/// Value of the version of the parameter with `label` (see [`label_parameter_version`]).
+pub async fn get_parameter_label(
+    client: &Client,
+    name: &str,
+    label: &str,
+) -> Result<String, Error> {
+    get_parameter_selector(client, name, label).await
+}
+
+async fn get_parameter_selector(
+    client: &Client,
+    name: &str,
+    selector: &str,
+) -> Result<String, Error> {
+    let res = get_parameter_raw(client, Some(format!("{name}:{selector}")), Some(true)).await?;
+    res.parameter()
+        .and_then(|p| p.value())
+        .ok_or_else(|| Error::NotFound)
+        .map(|s| s.to_string())
+}
+
+/// Versions of the parameter, oldest first.
+pub fn get_parameter_history_stream(
+    client: &Client,
+    name: impl Into<String>,
+    with_decryption: Option<bool>,
+) -> impl TryStream<Ok = ParameterHistory, Error = Error> {
+    client
+        .get_parameter_history()
+        .name(name)
+        .set_with_decryption(with_decryption)
+        .into_paginator()
+        .send()
+        .into_stream_03x()
+        .map_err(from_aws_sdk_error)
+        .map_ok(|o| stream::iter(o.parameters.unwrap_or_default().into_iter().map(Ok)))
+        .try_flatten()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyTimeUnit {
+    Days,
+    Hours,
+}
+
+impl PolicyTimeUnit {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PolicyTimeUnit::Days => "Days",
+            PolicyTimeUnit::Hours => "Hours",
+        }
+    }
+}
+
+/// Parameter policy, only for the Advanced tier.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterPolicy {
+    /// The parameter is deleted at the timestamp.
+    Expiration(DateTime<Utc>),
+    /// EventBridge notification `before` the expiration.
+    ExpirationNotification { before: u32, unit: PolicyTimeUnit },
+    /// EventBridge notification when the parameter was not changed for `after`.
+    NoChangeNotification { after: u32, unit: PolicyTimeUnit },
+}
+
+/// JSON of the Policies parameter of PutParameter.
+pub fn parameter_policies_json(policies: &[ParameterPolicy]) -> String {
+    let policies = policies
+        .iter()
+        .map(|policy| match policy {
+            ParameterPolicy::Expiration(timestamp) => json!({
+                "Type": "Expiration",
+                "Version": "1.0",
+                "Attributes": {
+                    "Timestamp": timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+                },
+            }),
+            ParameterPolicy::ExpirationNotification { before, unit } => json!({
+                "Type": "ExpirationNotification",
+                "Version": "1.0",
+                "Attributes": {"Before": before.to_string(), "Unit": unit.as_str()},
+            }),
+            ParameterPolicy::NoChangeNotification { after, unit } => json!({
+                "Type": "NoChangeNotification",
+                "Version": "1.0",
+                "Attributes": {"After": after.to_string(), "Unit": unit.as_str()},
+            }),
+        })
+        .collect::<Vec<_>>();
+    serde_json::Value::Array(policies).to_string()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PutParameterOptions {
+    /// String if None.
+    pub parameter_type: Option<ParameterType>,
+    pub description: Option<String>,
+    /// KMS key of a SecureString, the AWS managed key if None.
+    pub key_id: Option<String>,
+    pub overwrite: bool,
+    /// Standard if None, or Advanced if `policies` are set.
+    pub tier: Option<ParameterTier>,
+    pub policies: Vec<ParameterPolicy>,
+}
+
+/// Creates or updates (with `overwrite`) a parameter, returning the new version.
+pub async fn put_parameter(
+    client: &Client,
+    name: &str,
+    value: &str,
+    options: PutParameterOptions,
+) -> Result<i64, Error> {
+    let tier = match options.tier {
+        None if !options.policies.is_empty() => Some(ParameterTier::Advanced),
+        Some(ParameterTier::Standard) if !options.policies.is_empty() => {
+            return Err(Error::ValidationError(
+                "parameter policies need the Advanced tier".to_string(),
+            ));
+        }
+        tier => tier,
+    };
+    let policies =
+        (!options.policies.is_empty()).then(|| parameter_policies_json(&options.policies));
+    let output = client
+        .put_parameter()
+        .name(name)
+        .value(value)
+        .r#type(options.parameter_type.unwrap_or(ParameterType::String))
+        .set_description(options.description)
+        .set_key_id(options.key_id)
+        .overwrite(options.overwrite)
+        .set_tier(tier)
+        .set_policies(policies)
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(output.version)
+}
+
+/// Attaches `labels` to a version of the parameter (the latest if None), moving them from
+/// other versions. Returns the labels that were invalid and not attached.
+pub async fn label_parameter_version(
+    client: &Client,
+    name: &str,
+    version: Option<i64>,
+    labels: Vec<String>,
+) -> Result<Vec<String>, Error> {
+    let output = client
+        .label_parameter_version()
+        .name(name)
+        .set_parameter_version(version)
+        .set_labels(Some(labels))
+        .send()
+        .await
+        .map_err(from_aws_sdk_error)?;
+    Ok(output.invalid_labels.unwrap_or_default())
+}
+
+/// Loads the required parameters at startup, decrypted, with GetParameters (10 names per call).
+///
+/// Fails with every parameter that is missing or cannot be read, instead of failing one by one
+/// at runtime. A parameter can be given by name or ARN, and `name:label` and `name:version` are
+/// looked up as is; each keeps the requested key in the map.
+pub async fn preload(
+    client: &Client,
+    required_keys: impl IntoIterator<Item = impl Into<String>>,
+) -> Result<ConfigMap, MissingKeys> {
+    let mut keys = required_keys
+        .into_iter()
+        .map(Into::into)
+        .collect::<Vec<String>>();
+    keys.sort();
+    keys.dedup();
+
+    let mut config = ConfigMap::new();
+    let mut missing = MissingKeys::default();
+    for chunk in keys.chunks(MAX_GET_PARAMETERS) {
+        let result = client
+            .get_parameters()
+            .set_names(Some(chunk.to_vec()))
+            .with_decryption(true)
+            .send()
+            .await;
+        match result {
+            Ok(output) => {
+                let values = output
+                    .parameters()
+                    .iter()
+                    .filter_map(|p| Some((parameter_keys(p), p.value()?)))
+                    .flat_map(|(keys, value)| keys.into_iter().map(move |key| (key, value)))
+                    .collect::<HashMap<_, _>>();
+                for key in chunk {
+                    match values.get(key) {
+                        Some(value) => config.insert(key, *value),
+                        None => missing.push(key, MissingReason::NotFound),
+                    }
+                }
+            }
+            // 1つでも読めないパラメータがあると全体が失敗するので、1つずつ取得して原因を確認する
+            Err(_) => {
+                for key in chunk {
+                    match get_parameter(client, key).await {
+                        Ok(value) => config.insert(key, value),
+                        Err(e) => missing.push(key, missing_reason(&e)),
+                    }
+                }
+            }
+        }
+    }
+    if missing.is_empty() {
+        Ok(config)
+    } else {
+        Err(missing)
+    }
+}
+
+/// Keys a GetParameters result can be requested by: the name and the ARN, with the `:label`
+/// or `:version` selector requested.
+fn parameter_keys(parameter: &Parameter) -> Vec<String> {
+    let selector = parameter.selector().unwrap_or_default();
+    [parameter.name(), parameter.arn()]
+        .into_iter()
+        .flatten()
+        .map(|id| format!("{id}{selector}"))
+        .collect()
+}
+
+fn missing_reason(e: &Error) -> MissingReason {
+    match e {
+        Error::NotFound => MissingReason::NotFound,
+        e => MissingReason::from_error_code(e.error_code(), e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_get_parameter_version_and_label() {
+        let mut server = mockito::Server::new_async().await;
+        for (selector, value) in [("3", "v3"), ("current", "v4")] {
+            server
+                .mock("POST", "/")
+                .match_header("x-amz-target", "AmazonSSM.GetParameter")
+                .match_body(mockito::Matcher::PartialJsonString(format!(
+                    r#"{{"Name":"/app/key:{selector}","WithDecryption":true}}"#
+                )))
+                .with_body(format!(
+                    r#"{{"Parameter":{{"Name":"/app/key","Value":"{value}","Type":"SecureString"}}}}"#
+                ))
+                .expect(1)
+                .create_async()
+                .await;
+        }
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        assert_eq!(
+            get_parameter_version(&client, "/app/key", 3).await.unwrap(),
+            "v3"
+        );
+        assert_eq!(
+            get_parameter_label(&client, "/app/key", "current")
+                .await
+                .unwrap(),
+            "v4"
+        );
+    }
+
+    #[test]
+    fn test_parameter_policies_json() {
+        let json = parameter_policies_json(&[
+            ParameterPolicy::Expiration(
+                DateTime::parse_from_rfc3339("2026-12-02T21:34:33Z")
+                    .unwrap()
+                    .to_utc(),
+            ),
+            ParameterPolicy::ExpirationNotification {
+                before: 15,
+                unit: PolicyTimeUnit::Days,
+            },
+            ParameterPolicy::NoChangeNotification {
+                after: 20,
+                unit: PolicyTimeUnit::Hours,
+            },
+        ]);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            json!([
+                {"Type": "Expiration", "Version": "1.0", "Attributes": {"Timestamp": "2026-12-02T21:34:33.000Z"}},
+                {"Type": "ExpirationNotification", "Version": "1.0", "Attributes": {"Before": "15", "Unit": "Days"}},
+                {"Type": "NoChangeNotification", "Version": "1.0", "Attributes": {"After": "20", "Unit": "Hours"}},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parameter_keys() {
+        let parameter = Parameter::builder().name("/app/db/password").build();
+        assert_eq!(parameter_keys(&parameter), vec!["/app/db/password"]);
+
+        let parameter = Parameter::builder()
+            .name("/app/db/password")
+            .arn("arn:aws:ssm:ap-northeast-1:123456789012:parameter/app/db/password")
+            .selector(":prod")
+            .build();
+        assert_eq!(
+            parameter_keys(&parameter),
+            vec![
+                "/app/db/password:prod",
+                "arn:aws:ssm:ap-northeast-1:123456789012:parameter/app/db/password:prod",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_reason() {
+        assert_eq!(missing_reason(&Error::NotFound), MissingReason::NotFound);
+        assert!(matches!(
+            missing_reason(&Error::ValidationError("x".to_string())),
+            MissingReason::Failed(_)
+        ));
+    }
+
     #[tokio::test]
     async fn test_get_parameter() {
         if std::env::var("REALM_CODE").is_err() {
@@ -84,4 +428,146 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_get_parameter_history_stream() {
+        if std::env::var("REALM_CODE").is_err() {
+            eprintln!("REALM_CODE is not set. Skipping test.");
+            return;
+        }
+
+        let endpoint_url = std::env::var("SSM_ENDPOINT_URL").ok();
+        let client = crate::make_client(endpoint_url, None, None).await;
+
+        let parameter_name = std::env::var("TEST_SSM_PARAMETER_NAME")
+            .unwrap_or_else(|_| "/test/parameter".to_string());
+
+        // 履歴の各バージョンが取得できることを確認
+        match get_parameter_history_stream(&client, &parameter_name, Some(true))
+            .try_collect::<Vec<_>>()
+            .await
+        {
+            Ok(history) => {
+                for h in &history {
+                    let value = get_parameter_version(&client, &parameter_name, h.version)
+                        .await
+                        .unwrap();
+                    assert_eq!(Some(value.as_str()), h.value());
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to get parameter history: {:?}", e);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preload() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonSSM.GetParameters")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"WithDecryption":true}"#.to_string(),
+            ))
+            .with_body(
+                r#"{"Parameters":[
+                    {"Name":"/app/db/password","Value":"secret","Type":"SecureString"},
+                    {"Name":"/app/db/host","ARN":"arn:aws:ssm:ap-northeast-1:123456789012:parameter/app/db/host","Value":"db.local","Type":"String"},
+                    {"Name":"/app/feature","Selector":":prod","Value":"on","Type":"String"}
+                ],"InvalidParameters":["/app/unknown"]}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let missing = preload(
+            &client,
+            [
+                "/app/db/password",
+                "arn:aws:ssm:ap-northeast-1:123456789012:parameter/app/db/host",
+                "/app/feature:prod",
+                "/app/unknown",
+            ],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            missing.not_found().collect::<Vec<_>>(),
+            vec!["/app/unknown"]
+        );
+        assert_eq!(missing.keys.len(), 1);
+
+        let config = preload(
+            &client,
+            [
+                "/app/db/password",
+                "arn:aws:ssm:ap-northeast-1:123456789012:parameter/app/db/host",
+                "/app/feature:prod",
+            ],
+        )
+        .await;
+        assert!(config.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_preload_falls_back_to_get_parameter() {
+        let mut server = mockito::Server::new_async().await;
+        // 読めないパラメータが 1 つでもあると GetParameters 全体が失敗する
+        server
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AmazonSSM.GetParameters")
+            .with_status(400)
+            .with_body(r#"{"__type":"AccessDeniedException","message":"not authorized"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        for (name, status, body) in [
+            (
+                "/app/db/password",
+                200,
+                r#"{"Parameter":{"Name":"/app/db/password","Value":"secret","Type":"SecureString"}}"#,
+            ),
+            (
+                "/app/private",
+                400,
+                r#"{"__type":"AccessDeniedException","message":"not authorized"}"#,
+            ),
+            (
+                "/app/unknown",
+                400,
+                r#"{"__type":"ParameterNotFound","message":"not found"}"#,
+            ),
+        ] {
+            server
+                .mock("POST", "/")
+                .match_header("x-amz-target", "AmazonSSM.GetParameter")
+                .match_body(mockito::Matcher::PartialJsonString(format!(
+                    r#"{{"Name":"{name}"}}"#
+                )))
+                .with_status(status)
+                .with_body(body)
+                .expect(1)
+                .create_async()
+                .await;
+        }
+        let client = crate::make_client(Some(server.url()), None, None).await;
+
+        let missing = preload(
+            &client,
+            ["/app/db/password", "/app/private", "/app/unknown"],
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            missing.forbidden().collect::<Vec<_>>(),
+            vec!["/app/private"]
+        );
+        assert_eq!(
+            missing.not_found().collect::<Vec<_>>(),
+            vec!["/app/unknown"]
+        );
+        assert_eq!(missing.keys.len(), 2);
+    }
 }